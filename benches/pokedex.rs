@@ -0,0 +1,139 @@
+//! Benchmarks of the description cleanup and of the handlers, run
+//! end to end against a mock PokeAPI with `cargo bench`.
+
+use criterion::{
+    BenchmarkId, Criterion, black_box, criterion_group,
+    criterion_main,
+};
+use serde_json::json;
+use std::{
+    net::TcpListener,
+    process::{Child, Command, Stdio},
+    time::Duration,
+};
+use tokio::runtime::Runtime;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+// Its unit tests are not run from here.
+#[allow(dead_code, unused_imports)]
+#[path = "../src/text.rs"]
+mod text;
+
+/// Pikachu's FireRed flavor text, with the line breaks of the game.
+const FLAVOR_TEXT: &str = "When several of\nthese POKéMON\ngather, their\nelectricity could\u{000C}build and cause\nlightning storms.";
+
+fn clean_description(c: &mut Criterion) {
+    c.bench_function("clean_description", |b| {
+        b.iter(|| text::clean_description(black_box(FLAVOR_TEXT)))
+    });
+}
+
+/// The `pokedex` binary, killed when dropped.
+struct Server {
+    child: Child,
+    base_url: String,
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+async fn mock_pokeapi() -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/pokemon-species/pikachu"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(
+            json!({
+                "name": "pikachu",
+                "habitat": {"name": "forest", "url": ""},
+                "flavor_text_entries": [{
+                    "flavor_text": FLAVOR_TEXT,
+                    "language": {"name": "en", "url": ""},
+                }],
+                "is_legendary": false,
+                "is_mythical": false,
+                "is_baby": false,
+            }),
+        ))
+        .mount(&server)
+        .await;
+    server
+}
+
+/// Starts the server against `pokeapi`, with upstream overrides on
+/// so that requests can bypass the caches.
+async fn start_server(pokeapi: &str) -> Server {
+    let port = TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .expect("no free port")
+        .port();
+    let child = Command::new(env!("CARGO_BIN_EXE_pokedex"))
+        .env("HOST", "127.0.0.1")
+        .env("PORT", port.to_string())
+        .env("POKEAPI_BASE_URL", pokeapi)
+        .env("TRANSLATION_API_BASE_URL", pokeapi)
+        .env("DEBUG_UPSTREAM_OVERRIDES", "true")
+        .env("NAME_GUARD_REFRESH_SECS", "0")
+        .env("RUST_LOG", "error")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("failed to start the server");
+    let server = Server {
+        child,
+        base_url: format!("http://127.0.0.1:{}", port),
+    };
+
+    let health = format!("{}/health", server.base_url);
+    for _ in 0..100 {
+        if reqwest::get(&health).await.is_ok() {
+            return server;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("the server did not start");
+}
+
+/// `GET /pokemon/pikachu` served from the cache and, with the
+/// upstream override header, fetched from the mock every time.
+fn get_pokemon(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let (pokeapi, server) = runtime.block_on(async {
+        let pokeapi = mock_pokeapi().await;
+        let server = start_server(&pokeapi.uri()).await;
+        (pokeapi, server)
+    });
+    let client = reqwest::Client::new();
+    let url = format!("{}/pokemon/pikachu", server.base_url);
+
+    let mut group = c.benchmark_group("get_pokemon");
+    for cached in [true, false] {
+        let id = BenchmarkId::from_parameter(if cached {
+            "cached"
+        } else {
+            "uncached"
+        });
+        group.bench_function(id, |b| {
+            b.to_async(&runtime).iter(|| async {
+                let mut request = client.get(&url);
+                if !cached {
+                    request = request.header(
+                        "x-pokedex-upstream-pokeapi",
+                        pokeapi.uri(),
+                    );
+                }
+                let response = request.send().await.unwrap();
+                assert!(response.status().is_success());
+                response.bytes().await.unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, clean_description, get_pokemon);
+criterion_main!(benches);
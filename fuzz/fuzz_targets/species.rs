@@ -0,0 +1,43 @@
+//! Feeds arbitrary bytes to the PokeAPI species parsing, then runs
+//! the description cleanup on whatever flavor texts come out.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[allow(dead_code, unused_imports)]
+#[path = "../../src/text.rs"]
+mod text;
+#[allow(dead_code)]
+#[path = "../../src/tolerant.rs"]
+mod tolerant;
+#[allow(dead_code)]
+#[path = "../../src/wire.rs"]
+mod wire;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(species) =
+        serde_json::from_slice::<wire::PokeApiSpecies>(data)
+    else {
+        return;
+    };
+    let normalization =
+        text::Normalization::parse("nfc,quotes,case=title").unwrap();
+    let _ =
+        species.flavor_text_entries.warnings("flavor_text_entries");
+    if let Some(entry) = text::in_language(
+        &species.flavor_text_entries.items,
+        "en",
+        |entry| &entry.language.name,
+    ) {
+        let cleaned = text::clean_description(&entry.flavor_text);
+        let normalized = normalization.apply(&cleaned);
+        let _ = text::first_sentences(&normalized, 2);
+        let _ = text::truncate(&normalized, 80);
+        let _ = text::replace_ignore_case(
+            &normalized,
+            &species.name.replace('-', " "),
+            "???",
+        );
+    }
+});
@@ -0,0 +1,31 @@
+//! Runs the text helpers on arbitrary strings, checking the
+//! invariants the handlers rely on.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[allow(dead_code, unused_imports)]
+#[path = "../../src/text.rs"]
+mod text;
+
+fuzz_target!(|input: (&str, &str, u8)| {
+    let (text, needle, count) = input;
+
+    let cleaned = text::clean_description(text);
+    assert_eq!(text::clean_description(&cleaned), cleaned);
+
+    for passes in ["nfc", "quotes", "case=upper", "case=title"] {
+        let normalization =
+            text::Normalization::parse(passes).unwrap();
+        let _ = normalization.apply(text);
+    }
+
+    let sentences = text::first_sentences(text, count as usize);
+    assert!(text.starts_with(sentences));
+
+    let truncated = text::truncate(text, count as usize);
+    assert!(truncated.chars().count() <= count as usize);
+
+    let _ = text::replace_ignore_case(text, needle, "???");
+});
@@ -0,0 +1,24 @@
+//! Feeds arbitrary bytes to the funtranslations response parsing.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[allow(dead_code, unused_imports)]
+#[path = "../../src/text.rs"]
+mod text;
+#[allow(dead_code)]
+#[path = "../../src/tolerant.rs"]
+mod tolerant;
+#[allow(dead_code)]
+#[path = "../../src/wire.rs"]
+mod wire;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(translation) =
+        serde_json::from_slice::<wire::TranslationResponse>(data)
+    {
+        let _ =
+            text::clean_description(&translation.contents.translated);
+    }
+});
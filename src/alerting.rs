@@ -0,0 +1,431 @@
+use crate::config::Secret;
+use crate::error::{AppError, Result};
+use crate::http::Upstream;
+use crate::mailer::{MailConfig, Mailer};
+use crate::upstreams::{UpstreamRegistry, UpstreamSnapshot};
+use axum::async_trait;
+use reqwest::Client;
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tracing::{info, warn};
+
+/// When upstream failures alert, from `ALERT_*`.
+#[derive(Debug, Clone)]
+pub struct AlertSettings {
+    pub sinks: Vec<AlertSinkConfig>,
+    /// How long a circuit may stay open; zero disables the alert.
+    pub circuit_open: Duration,
+    /// Share of failed calls over `error_window`, between 0 and 1,
+    /// past which an upstream alerts; zero disables the alert.
+    pub error_rate: f64,
+    pub error_window: Duration,
+    /// Calls over the window below which the error rate is ignored.
+    pub min_calls: u64,
+    pub check: Duration,
+}
+
+/// An alert sink selected with `ALERT_SINKS`. The webhook URLs
+/// carry their token, so they are redacted when logged.
+#[derive(Debug, Clone)]
+pub enum AlertSinkConfig {
+    /// Slack incoming webhook.
+    Slack {
+        url: Secret,
+    },
+    /// Discord channel webhook.
+    Discord {
+        url: Secret,
+    },
+    Email(MailConfig),
+}
+
+impl AlertSinkConfig {
+    pub fn open(&self, client: Client) -> Result<Arc<dyn AlertSink>> {
+        Ok(match self {
+            AlertSinkConfig::Slack { url } => Arc::new(ChatSink {
+                name: "slack",
+                upstream: Upstream::new("Slack", client),
+                url: url.0.clone(),
+                field: "text",
+            }),
+            AlertSinkConfig::Discord { url } => Arc::new(ChatSink {
+                name: "discord",
+                upstream: Upstream::new("Discord", client),
+                url: url.0.clone(),
+                field: "content",
+            }),
+            AlertSinkConfig::Email(config) => {
+                Arc::new(EmailSink(Mailer::new(config)?))
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AlertKind {
+    CircuitOpen,
+    ErrorRate,
+}
+
+/// An alert starting or, when `resolved`, ending.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alert {
+    pub upstream: &'static str,
+    pub kind: AlertKind,
+    pub resolved: bool,
+    pub message: String,
+}
+
+impl Alert {
+    /// e.g. `[FIRING] PokeAPI circuit has been open for 320s`.
+    pub fn summary(&self) -> String {
+        let state = if self.resolved { "RESOLVED" } else { "FIRING" };
+        format!("[{}] {}", state, self.message)
+    }
+}
+
+/// Where alerts are sent.
+#[async_trait]
+pub trait AlertSink: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn send(&self, alert: &Alert) -> Result<()>;
+}
+
+/// Posts the summary as a chat message, in `field` of a JSON body.
+struct ChatSink {
+    name: &'static str,
+    upstream: Upstream,
+    url: String,
+    field: &'static str,
+}
+
+#[async_trait]
+impl AlertSink for ChatSink {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    async fn send(&self, alert: &Alert) -> Result<()> {
+        let body = serde_json::json!({ self.field: alert.summary() });
+        let response = self
+            .upstream
+            .send(self.upstream.post(&self.url).json(&body))
+            .await?;
+        if !response.status().is_success() {
+            return Err(AppError::ExternalApi(format!(
+                "{} alert sink error: status {}",
+                self.name,
+                response.status()
+            )));
+        }
+        Ok(())
+    }
+}
+
+struct EmailSink(Mailer);
+
+#[async_trait]
+impl AlertSink for EmailSink {
+    fn name(&self) -> &'static str {
+        "email"
+    }
+
+    async fn send(&self, alert: &Alert) -> Result<()> {
+        self.0.send(&alert.summary(), alert.message.clone()).await
+    }
+}
+
+/// Watches the upstreams for circuits that stay open and error rates
+/// over the limit. Each alert is sent when it starts and when it
+/// resolves.
+pub struct AlertMonitor {
+    settings: AlertSettings,
+    /// (taken, calls, failures) of each upstream over the error
+    /// window, oldest first.
+    samples: BTreeMap<&'static str, VecDeque<(Instant, u64, u64)>>,
+    firing: BTreeSet<(&'static str, AlertKind)>,
+}
+
+impl AlertMonitor {
+    pub fn new(settings: AlertSettings) -> Self {
+        Self {
+            settings,
+            samples: BTreeMap::new(),
+            firing: BTreeSet::new(),
+        }
+    }
+
+    /// The alerts that started or resolved since the last check.
+    pub fn evaluate(
+        &mut self,
+        upstreams: &[UpstreamSnapshot],
+        now: Instant,
+    ) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        for upstream in upstreams {
+            let name = upstream.name;
+            if !self.settings.circuit_open.is_zero() {
+                let open_secs =
+                    upstream.circuit_open_secs.filter(|secs| {
+                        *secs >= self.settings.circuit_open.as_secs()
+                    });
+                self.transition(
+                    &mut alerts,
+                    name,
+                    AlertKind::CircuitOpen,
+                    open_secs.map(|secs| {
+                        format!(
+                            "{} circuit has been open for {}s",
+                            name, secs
+                        )
+                    }),
+                    || format!("{} circuit closed", name),
+                );
+            }
+
+            if self.settings.error_rate > 0.0 {
+                let samples = self.samples.entry(name).or_default();
+                samples.push_back((
+                    now,
+                    upstream.total_calls,
+                    upstream.total_failures,
+                ));
+                while samples.front().is_some_and(|(taken, ..)| {
+                    now.duration_since(*taken)
+                        > self.settings.error_window
+                }) {
+                    samples.pop_front();
+                }
+                let (since, calls, failures) = samples[0];
+                let calls = upstream.total_calls - calls;
+                let failures = upstream.total_failures - failures;
+                let rate = failures as f64 / calls.max(1) as f64;
+                let limit = self.settings.error_rate;
+                let over = (calls >= self.settings.min_calls
+                    && rate >= limit)
+                    .then(|| {
+                        format!(
+                            "{} failed {:.0}% of {} calls over the last {}s",
+                            name,
+                            rate * 100.0,
+                            calls,
+                            now.duration_since(since).as_secs()
+                        )
+                    });
+                self.transition(
+                    &mut alerts,
+                    name,
+                    AlertKind::ErrorRate,
+                    over,
+                    || {
+                        format!(
+                            "{} error rate is back under {:.0}%",
+                            name,
+                            limit * 100.0
+                        )
+                    },
+                );
+            }
+        }
+        alerts
+    }
+
+    /// Records an alert starting when `firing` describes it and it
+    /// was not already firing, or resolving otherwise.
+    fn transition(
+        &mut self,
+        alerts: &mut Vec<Alert>,
+        upstream: &'static str,
+        kind: AlertKind,
+        firing: Option<String>,
+        resolved: impl FnOnce() -> String,
+    ) {
+        match firing {
+            Some(message) if self.firing.insert((upstream, kind)) => {
+                alerts.push(Alert {
+                    upstream,
+                    kind,
+                    resolved: false,
+                    message,
+                })
+            }
+            None if self.firing.remove(&(upstream, kind)) => alerts
+                .push(Alert {
+                    upstream,
+                    kind,
+                    resolved: true,
+                    message: resolved(),
+                }),
+            _ => {}
+        }
+    }
+
+    /// Checks the upstreams every `ALERT_CHECK_SECS`, sending the
+    /// alerts to every sink.
+    pub fn watch(
+        mut self,
+        registry: Arc<UpstreamRegistry>,
+        sinks: Vec<Arc<dyn AlertSink>>,
+    ) {
+        let names: Vec<_> =
+            sinks.iter().map(|sink| sink.name()).collect();
+        info!(sinks = ?names, "Alerting on upstream failures");
+        tokio::spawn(async move {
+            let mut ticker =
+                tokio::time::interval(self.settings.check);
+            loop {
+                ticker.tick().await;
+                let alerts = self
+                    .evaluate(&registry.snapshot(), Instant::now());
+                for alert in alerts {
+                    warn!(
+                        upstream = alert.upstream,
+                        resolved = alert.resolved,
+                        "{}",
+                        alert.message
+                    );
+                    for sink in &sinks {
+                        if let Err(e) = sink.send(&alert).await {
+                            warn!(sink = sink.name(), error = %e, "Failed to send alert");
+                        }
+                    }
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::upstreams::CircuitState;
+
+    #[test]
+    fn test_webhook_urls_are_redacted() {
+        let settings = AlertSettings {
+            sinks: vec![
+                AlertSinkConfig::Slack {
+                    url: Secret(
+                        "https://hooks.slack.com/services/T0/B0/s3cret"
+                            .to_string(),
+                    ),
+                },
+                AlertSinkConfig::Discord {
+                    url: Secret(
+                        "https://discord.com/api/webhooks/1/hunter2"
+                            .to_string(),
+                    ),
+                },
+            ],
+            ..settings()
+        };
+        let debug = format!("{:?}", settings);
+        assert!(!debug.contains("s3cret"));
+        assert!(!debug.contains("hunter2"));
+        assert!(debug.contains("Slack"));
+    }
+
+    fn settings() -> AlertSettings {
+        AlertSettings {
+            sinks: Vec::new(),
+            circuit_open: Duration::from_secs(60),
+            error_rate: 0.5,
+            error_window: Duration::from_secs(300),
+            min_calls: 10,
+            check: Duration::from_secs(30),
+        }
+    }
+
+    fn upstream(
+        circuit_open_secs: Option<u64>,
+        total_calls: u64,
+        total_failures: u64,
+    ) -> UpstreamSnapshot {
+        UpstreamSnapshot {
+            name: "PokeAPI",
+            in_flight: 0,
+            requests: 0,
+            success_rate: None,
+            last_error: None,
+            circuit: if circuit_open_secs.is_some() {
+                CircuitState::Open
+            } else {
+                CircuitState::Closed
+            },
+            circuit_open_secs,
+            total_calls,
+            total_failures,
+        }
+    }
+
+    fn summaries(alerts: Vec<Alert>) -> Vec<String> {
+        alerts.iter().map(Alert::summary).collect()
+    }
+
+    #[test]
+    fn test_circuit_alert_fires_once_and_resolves() {
+        let mut monitor = AlertMonitor::new(settings());
+        let now = Instant::now();
+        assert!(
+            monitor
+                .evaluate(&[upstream(Some(30), 0, 0)], now)
+                .is_empty()
+        );
+        assert_eq!(
+            summaries(
+                monitor.evaluate(&[upstream(Some(90), 0, 0)], now)
+            ),
+            ["[FIRING] PokeAPI circuit has been open for 90s"]
+        );
+        assert!(
+            monitor
+                .evaluate(&[upstream(Some(120), 0, 0)], now)
+                .is_empty()
+        );
+        assert_eq!(
+            summaries(monitor.evaluate(&[upstream(None, 0, 0)], now)),
+            ["[RESOLVED] PokeAPI circuit closed"]
+        );
+    }
+
+    #[test]
+    fn test_error_rate_over_the_window() {
+        let mut monitor = AlertMonitor::new(settings());
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        assert!(
+            monitor
+                .evaluate(&[upstream(None, 100, 0)], at(0))
+                .is_empty()
+        );
+        // Too few calls to judge.
+        assert!(
+            monitor
+                .evaluate(&[upstream(None, 105, 5)], at(30))
+                .is_empty()
+        );
+        assert_eq!(
+            summaries(
+                monitor.evaluate(&[upstream(None, 120, 12)], at(60))
+            ),
+            [
+                "[FIRING] PokeAPI failed 60% of 20 calls over the last 60s"
+            ]
+        );
+        assert!(
+            monitor
+                .evaluate(&[upstream(None, 140, 24)], at(90))
+                .is_empty()
+        );
+        assert_eq!(
+            summaries(
+                monitor.evaluate(&[upstream(None, 300, 24)], at(400))
+            ),
+            ["[RESOLVED] PokeAPI error rate is back under 50%"]
+        );
+    }
+}
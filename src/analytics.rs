@@ -0,0 +1,251 @@
+use crate::error::{AppError, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tracing::{info, warn};
+
+/// Most names `/admin/stats/top` returns.
+pub const MAX_TOP: usize = 100;
+
+/// What was served for one Pokemon.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+struct Counters {
+    requests: u64,
+    /// Requests answered from the caches alone.
+    cache_hits: u64,
+    translations: u64,
+    /// Translations that failed and kept the original description.
+    translation_fallbacks: u64,
+}
+
+/// A Pokemon in the `/admin/stats/top` ranking.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct PokemonUsage {
+    pub name: String,
+    pub requests: u64,
+    /// Share of the requests answered from the caches alone.
+    pub hit_rate: f64,
+    pub translations: u64,
+    /// Share of the translations that fell back to the original
+    /// description; `None` without translations.
+    pub translation_fallback_ratio: Option<f64>,
+}
+
+/// Per-Pokemon request counters, kept in memory and, with
+/// `USAGE_STATS_FILE`, saved across restarts.
+#[derive(Default)]
+pub struct UsageStats {
+    counters: Mutex<HashMap<String, Counters>>,
+}
+
+impl UsageStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts a request for `name`, which the caches answered alone
+    /// when `cache_hit`.
+    pub fn record_request(&self, name: &str, cache_hit: bool) {
+        self.update(name, |counters| {
+            counters.requests += 1;
+            counters.cache_hits += u64::from(cache_hit);
+        });
+    }
+
+    /// Counts a translation of `name`'s description.
+    pub fn record_translation(&self, name: &str, fell_back: bool) {
+        self.update(name, |counters| {
+            counters.translations += 1;
+            counters.translation_fallbacks += u64::from(fell_back);
+        });
+    }
+
+    fn update(&self, name: &str, update: impl FnOnce(&mut Counters)) {
+        let mut counters = self.counters.lock().unwrap();
+        match counters.get_mut(name) {
+            Some(counters) => update(counters),
+            None => {
+                let mut new = Counters::default();
+                update(&mut new);
+                counters.insert(name.to_string(), new);
+            }
+        }
+    }
+
+    /// The `limit` most requested Pokemon, ties by name.
+    pub fn top(&self, limit: usize) -> Vec<PokemonUsage> {
+        let counters = self.counters.lock().unwrap();
+        let mut ranked: Vec<_> = counters
+            .iter()
+            .filter(|(_, counters)| counters.requests > 0)
+            .collect();
+        ranked.sort_by(|(a_name, a), (b_name, b)| {
+            b.requests.cmp(&a.requests).then(a_name.cmp(b_name))
+        });
+        ranked
+            .into_iter()
+            .take(limit.clamp(1, MAX_TOP))
+            .map(|(name, counters)| {
+                let ratio = |part: u64, total: u64| {
+                    (total > 0).then(|| part as f64 / total as f64)
+                };
+                PokemonUsage {
+                    name: name.clone(),
+                    requests: counters.requests,
+                    hit_rate: ratio(
+                        counters.cache_hits,
+                        counters.requests,
+                    )
+                    .unwrap_or_default(),
+                    translations: counters.translations,
+                    translation_fallback_ratio: ratio(
+                        counters.translation_fallbacks,
+                        counters.translations,
+                    ),
+                }
+            })
+            .collect()
+    }
+
+    /// Writes the counters to `path`, replacing it atomically.
+    pub async fn save(&self, path: &Path) -> Result<usize> {
+        let json = {
+            let counters = self.counters.lock().unwrap();
+            serde_json::to_vec(&*counters).map_err(|e| {
+                AppError::Internal(format!(
+                    "Failed to serialize usage stats: {}",
+                    e
+                ))
+            })?
+        };
+
+        let temp = path.with_extension("tmp");
+        let io_error = |e: std::io::Error| {
+            AppError::Internal(format!(
+                "Failed to write usage stats {}: {}",
+                path.display(),
+                e
+            ))
+        };
+        tokio::fs::write(&temp, json).await.map_err(io_error)?;
+        tokio::fs::rename(&temp, path).await.map_err(io_error)?;
+        Ok(self.counters.lock().unwrap().len())
+    }
+
+    /// Adds the counters saved at `path`. A missing file loads
+    /// nothing.
+    pub async fn load(&self, path: &Path) -> Result<usize> {
+        let json = match tokio::fs::read(path).await {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(0);
+            }
+            Err(e) => {
+                return Err(AppError::Internal(format!(
+                    "Failed to read usage stats {}: {}",
+                    path.display(),
+                    e
+                )));
+            }
+        };
+        let saved: HashMap<String, Counters> =
+            serde_json::from_slice(&json).map_err(|e| {
+                AppError::Internal(format!(
+                    "Invalid usage stats {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+
+        let loaded = saved.len();
+        let mut counters = self.counters.lock().unwrap();
+        for (name, saved) in saved {
+            let counters = counters.entry(name).or_default();
+            counters.requests += saved.requests;
+            counters.cache_hits += saved.cache_hits;
+            counters.translations += saved.translations;
+            counters.translation_fallbacks +=
+                saved.translation_fallbacks;
+        }
+        info!(path = %path.display(), pokemon = loaded, "Loaded usage stats");
+        Ok(loaded)
+    }
+
+    /// Saves the counters to `path` every `interval`, so a crash
+    /// loses at most one interval.
+    pub fn persist(
+        self: Arc<Self>,
+        path: PathBuf,
+        interval: Duration,
+    ) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.save(&path).await {
+                    warn!(error = %e, "Failed to save usage stats");
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_top_ranks_by_requests() {
+        let stats = UsageStats::new();
+        for cache_hit in [false, true, true, true] {
+            stats.record_request("pikachu", cache_hit);
+        }
+        stats.record_request("mewtwo", false);
+        stats.record_request("bulbasaur", true);
+        stats.record_translation("pikachu", false);
+        stats.record_translation("pikachu", true);
+        stats.record_translation("ditto", true);
+
+        let top = stats.top(2);
+        assert_eq!(
+            top[0],
+            PokemonUsage {
+                name: "pikachu".to_string(),
+                requests: 4,
+                hit_rate: 0.75,
+                translations: 2,
+                translation_fallback_ratio: Some(0.5),
+            }
+        );
+        assert_eq!(top[1].name, "bulbasaur");
+        assert_eq!(top[1].translation_fallback_ratio, None);
+        // Translations alone are not requests.
+        assert_eq!(stats.top(MAX_TOP + 1).len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_save_and_load_add_up() {
+        let path = std::env::temp_dir().join(format!(
+            "pokedex-usage-{}.json",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let stats = UsageStats::new();
+        assert_eq!(stats.load(&path).await.unwrap(), 0);
+        stats.record_request("pikachu", true);
+        assert_eq!(stats.save(&path).await.unwrap(), 1);
+
+        let restarted = UsageStats::new();
+        restarted.record_request("pikachu", false);
+        assert_eq!(restarted.load(&path).await.unwrap(), 1);
+        let top = restarted.top(10);
+        assert_eq!(top[0].requests, 2);
+        assert_eq!(top[0].hit_rate, 0.5);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
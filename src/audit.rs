@@ -0,0 +1,335 @@
+use crate::auth::{self, Principal};
+use crate::error::{AppError, Result};
+use crate::forwarded::ClientIp;
+use crate::storage::Storage;
+use axum::{
+    extract::{MatchedPath, Query, RawPathParams, Request, State},
+    http::Method,
+    middleware::Next,
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::{io::AsyncWriteExt, sync::Mutex};
+use tracing::{info, warn};
+
+const NAMESPACE: &str = "audit";
+const KEY: &str = "log";
+
+/// Entries kept in the storage backend before the oldest are
+/// dropped. The file backend keeps everything.
+const MAX_STORED_ENTRIES: usize = 1000;
+
+/// One admin API call.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AuditEntry {
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// Who made the call, as in `user:ash` or `ip:10.0.0.7`.
+    pub actor: String,
+    /// Method and route, e.g. `DELETE /admin/caches/:name`.
+    pub action: String,
+    /// Path and query parameters of the call.
+    pub params: BTreeMap<String, String>,
+    /// Response status.
+    pub status: u16,
+}
+
+/// Filters of `GET /admin/audit`.
+#[derive(Debug, Default, Deserialize)]
+pub struct AuditQuery {
+    pub actor: Option<String>,
+    pub action: Option<String>,
+    /// Only entries at or after this Unix timestamp.
+    pub since: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+impl AuditQuery {
+    fn matches(&self, entry: &AuditEntry) -> bool {
+        self.actor
+            .as_ref()
+            .is_none_or(|actor| *actor == entry.actor)
+            && self
+                .action
+                .as_ref()
+                .is_none_or(|action| entry.action.contains(action))
+            && self.since.is_none_or(|since| entry.timestamp >= since)
+    }
+}
+
+enum Backend {
+    /// JSON lines appended to a file.
+    File(PathBuf),
+    /// A capped list in the storage backend.
+    Storage(Arc<dyn Storage>),
+}
+
+/// Record of the admin operations, persisted to `AUDIT_LOG_FILE` or,
+/// without one, to the storage backend.
+pub struct AuditLog {
+    backend: Backend,
+    // Serializes appends, which read and rewrite the stored list.
+    lock: Mutex<()>,
+}
+
+impl AuditLog {
+    pub fn new(
+        file: Option<PathBuf>,
+        storage: Arc<dyn Storage>,
+    ) -> Self {
+        let backend = match file {
+            Some(path) => Backend::File(path),
+            None => Backend::Storage(storage),
+        };
+        Self {
+            backend,
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub async fn record(&self, entry: AuditEntry) -> Result<()> {
+        info!(actor = %entry.actor, action = %entry.action, status = entry.status, "Audit");
+        let _guard = self.lock.lock().await;
+        match &self.backend {
+            Backend::File(path) => {
+                let mut line =
+                    serde_json::to_vec(&entry).map_err(|e| {
+                        AppError::Internal(format!(
+                            "Failed to serialize audit entry: {}",
+                            e
+                        ))
+                    })?;
+                line.push(b'\n');
+                let mut file = tokio::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .await
+                    .map_err(|e| file_error(path, e))?;
+                file.write_all(&line)
+                    .await
+                    .map_err(|e| file_error(path, e))?;
+                // Tokio files write in the background: wait for the
+                // line to land before the next call appends.
+                file.flush().await.map_err(|e| file_error(path, e))
+            }
+            Backend::Storage(storage) => {
+                let mut entries: Vec<AuditEntry> = storage
+                    .get_as(NAMESPACE, KEY)?
+                    .unwrap_or_default();
+                entries.push(entry);
+                let excess =
+                    entries.len().saturating_sub(MAX_STORED_ENTRIES);
+                entries.drain(..excess);
+                storage.put_as(NAMESPACE, KEY, &entries, None)
+            }
+        }
+    }
+
+    /// The entries matching `query`, newest first.
+    pub async fn query(
+        &self,
+        query: &AuditQuery,
+    ) -> Result<Vec<AuditEntry>> {
+        let entries = match &self.backend {
+            Backend::File(path) => {
+                let contents =
+                    match tokio::fs::read_to_string(path).await {
+                        Ok(contents) => contents,
+                        Err(e)
+                            if e.kind()
+                                == std::io::ErrorKind::NotFound =>
+                        {
+                            String::new()
+                        }
+                        Err(e) => return Err(file_error(path, e)),
+                    };
+                contents
+                    .lines()
+                    .filter(|line| !line.trim().is_empty())
+                    .filter_map(|line| match serde_json::from_str(line) {
+                        Ok(entry) => Some(entry),
+                        Err(e) => {
+                            warn!(error = %e, "Skipping corrupted audit entry");
+                            None
+                        }
+                    })
+                    .collect()
+            }
+            Backend::Storage(storage) => storage
+                .get_as::<Vec<AuditEntry>>(NAMESPACE, KEY)?
+                .unwrap_or_default(),
+        };
+
+        Ok(entries
+            .into_iter()
+            .rev()
+            .filter(|entry| query.matches(entry))
+            .take(query.limit.unwrap_or(100))
+            .collect())
+    }
+}
+
+fn file_error(path: &std::path::Path, e: std::io::Error) -> AppError {
+    AppError::Internal(format!(
+        "Failed to access audit log {}: {}",
+        path.display(),
+        e
+    ))
+}
+
+/// Records every state-changing call to the routes it wraps. Reads
+/// are not audited.
+pub async fn middleware(
+    State(log): State<Arc<AuditLog>>,
+    client: ClientIp,
+    route: Option<MatchedPath>,
+    path_params: Option<RawPathParams>,
+    query: Option<Query<BTreeMap<String, String>>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if matches!(*request.method(), Method::GET | Method::HEAD) {
+        return next.run(request).await;
+    }
+
+    let actor = auth::caller_id(
+        request.extensions().get::<Principal>(),
+        client,
+    );
+    let action = format!(
+        "{} {}",
+        request.method(),
+        route
+            .as_ref()
+            .map_or(request.uri().path(), |route| { route.as_str() })
+    );
+    let mut params =
+        query.map(|Query(query)| query).unwrap_or_default();
+    params.extend(path_params.iter().flat_map(|params| {
+        params.iter().map(|(name, value)| {
+            (name.to_string(), value.to_string())
+        })
+    }));
+
+    let response = next.run(request).await;
+    let entry = AuditEntry {
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+        actor,
+        action,
+        params,
+        status: response.status().as_u16(),
+    };
+    if let Err(e) = log.record(entry).await {
+        warn!(error = %e, "Failed to record audit entry");
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    fn entry(
+        timestamp: u64,
+        actor: &str,
+        action: &str,
+    ) -> AuditEntry {
+        AuditEntry {
+            timestamp,
+            actor: actor.to_string(),
+            action: action.to_string(),
+            params: BTreeMap::from([(
+                "name".to_string(),
+                "pokemon".to_string(),
+            )]),
+            status: 204,
+        }
+    }
+
+    async fn record_sample(log: &AuditLog) {
+        log.record(entry(1, "user:ash", "DELETE /admin/caches"))
+            .await
+            .unwrap();
+        log.record(entry(
+            2,
+            "ip:10.0.0.7",
+            "DELETE /admin/caches/:name",
+        ))
+        .await
+        .unwrap();
+        log.record(entry(
+            3,
+            "user:ash",
+            "DELETE /admin/caches/:name",
+        ))
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_storage_backend_queries_newest_first() {
+        let log = AuditLog::new(None, Arc::new(MemoryStorage::new()));
+        record_sample(&log).await;
+
+        let all = log.query(&AuditQuery::default()).await.unwrap();
+        let timestamps: Vec<u64> =
+            all.iter().map(|entry| entry.timestamp).collect();
+        assert_eq!(timestamps, [3, 2, 1]);
+
+        let query = AuditQuery {
+            actor: Some("user:ash".to_string()),
+            action: Some(":name".to_string()),
+            ..AuditQuery::default()
+        };
+        let matching = log.query(&query).await.unwrap();
+        assert_eq!(
+            matching,
+            [entry(3, "user:ash", "DELETE /admin/caches/:name")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_file_backend_appends_json_lines() {
+        let path = std::env::temp_dir().join(format!(
+            "pokedex-audit-{}.jsonl",
+            std::process::id()
+        ));
+        // A run with a reused pid may have left one behind.
+        let _ = std::fs::remove_file(&path);
+        let log = AuditLog::new(
+            Some(path.clone()),
+            Arc::new(MemoryStorage::new()),
+        );
+        assert!(
+            log.query(&AuditQuery::default())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        record_sample(&log).await;
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(contents.lines().count(), 3);
+        let query = AuditQuery {
+            since: Some(2),
+            limit: Some(1),
+            ..AuditQuery::default()
+        };
+        let latest = log.query(&query).await.unwrap();
+        assert_eq!(latest[0].timestamp, 3);
+        assert_eq!(latest.len(), 1);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
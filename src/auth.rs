@@ -0,0 +1,499 @@
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request, State},
+    http::{HeaderMap, header, request::Parts},
+    middleware::Next,
+    response::Response,
+};
+use std::{
+    collections::HashMap, fmt, marker::PhantomData, sync::Arc,
+};
+use tracing::warn;
+
+use crate::error::AppError;
+use crate::forwarded::ClientIp;
+use crate::jwt::JwtVerifier;
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// The authenticated caller of a request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Principal {
+    pub user_id: String,
+    /// The tenant the key was issued under, whose quota it uses.
+    pub tenant_id: Option<String>,
+    /// Roles granted to the caller; those naming a `Role` give
+    /// access to the admin routes.
+    pub roles: Vec<String>,
+}
+
+impl Principal {
+    /// The highest access role granted, if any.
+    pub fn role(&self) -> Option<Role> {
+        self.roles.iter().filter_map(|role| Role::parse(role)).max()
+    }
+
+    /// Fails unless the principal is the owner of `user_id`'s data.
+    pub fn ensure_user(&self, user_id: &str) -> Result<(), AppError> {
+        if self.user_id == user_id {
+            Ok(())
+        } else {
+            Err(AppError::Forbidden(format!(
+                "Not allowed to access data of user '{}'",
+                user_id
+            )))
+        }
+    }
+}
+
+/// Access levels of the admin routes, each including the ones
+/// below it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    /// Reads cache statistics and tenant usage.
+    Reader,
+    /// Also flushes caches.
+    Operator,
+    /// Also reads the audit log.
+    Admin,
+}
+
+impl Role {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "reader" => Some(Self::Reader),
+            "operator" => Some(Self::Operator),
+            "admin" => Some(Self::Admin),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Reader => "reader",
+            Self::Operator => "operator",
+            Self::Admin => "admin",
+        }
+    }
+}
+
+/// Resolves API keys to the users they were issued to.
+#[derive(Clone, Default)]
+pub struct ApiKeys {
+    keys: HashMap<String, Principal>,
+}
+
+// Keys are secrets: never print them with the configuration.
+impl fmt::Debug for ApiKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ApiKeys({} keys)", self.keys.len())
+    }
+}
+
+impl ApiKeys {
+    /// Parses `key=user` or `key=user@tenant` entries separated by
+    /// commas, each optionally followed by `+role`s.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut keys = HashMap::new();
+        for entry in spec.split(',').map(str::trim) {
+            if entry.is_empty() {
+                continue;
+            }
+            let invalid =
+                || format!("Invalid API key entry '{}'", entry);
+            let (key, owner) = entry
+                .split_once('=')
+                .filter(|(key, owner)| {
+                    !key.is_empty() && !owner.is_empty()
+                })
+                .ok_or_else(invalid)?;
+            let mut roles = owner.split('+');
+            let owner = roles.next().unwrap_or_default();
+            let roles: Vec<String> =
+                roles.map(str::to_string).collect();
+            if owner.is_empty() {
+                return Err(invalid());
+            }
+            if let Some(role) =
+                roles.iter().find(|role| Role::parse(role).is_none())
+            {
+                return Err(format!(
+                    "Unknown role '{}' in API key entry '{}'",
+                    role, entry
+                ));
+            }
+            let (user_id, tenant_id) = match owner.split_once('@') {
+                Some((user, tenant))
+                    if !user.is_empty() && !tenant.is_empty() =>
+                {
+                    (user, Some(tenant.to_string()))
+                }
+                Some(_) => return Err(invalid()),
+                None => (owner, None),
+            };
+            keys.insert(
+                key.to_string(),
+                Principal {
+                    user_id: user_id.to_string(),
+                    tenant_id,
+                    roles,
+                },
+            );
+        }
+        Ok(Self { keys })
+    }
+
+    pub fn authenticate(&self, key: &str) -> Option<Principal> {
+        self.keys.get(key).cloned()
+    }
+
+    /// The tenants keys were issued under.
+    pub fn tenant_ids(&self) -> impl Iterator<Item = &str> {
+        self.keys
+            .values()
+            .filter_map(|principal| principal.tenant_id.as_deref())
+    }
+}
+
+/// How callers authenticate, from `AUTH_MODE`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AuthMode {
+    /// Keys from `API_KEYS`.
+    ApiKey,
+    /// Bearer JWTs checked against `JWT_JWKS_URL`.
+    Jwt,
+}
+
+impl AuthMode {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.trim().to_lowercase().as_str() {
+            "api_key" => Ok(Self::ApiKey),
+            "jwt" => Ok(Self::Jwt),
+            other => Err(format!(
+                "unknown auth mode '{}', expected api_key or jwt",
+                other
+            )),
+        }
+    }
+}
+
+/// Resolves the credentials of a request to a principal.
+pub enum Authenticator {
+    ApiKeys(ApiKeys),
+    Jwt(Box<JwtVerifier>),
+}
+
+impl Authenticator {
+    /// The principal of the request, `None` when it carries no
+    /// credentials.
+    async fn authenticate(
+        &self,
+        headers: &HeaderMap,
+    ) -> Result<Option<Principal>, AppError> {
+        match self {
+            Self::ApiKeys(keys) => {
+                let Some(key) = presented_key(headers) else {
+                    return Ok(None);
+                };
+                keys.authenticate(key).map(Some).ok_or_else(|| {
+                    AppError::Unauthorized(
+                        "Invalid API key".to_string(),
+                    )
+                })
+            }
+            Self::Jwt(verifier) => match bearer_token(headers) {
+                Some(token) => verifier.verify(token).await.map(Some),
+                None => Ok(None),
+            },
+        }
+    }
+
+    fn missing(&self) -> &'static str {
+        match self {
+            Self::ApiKeys(_) => "Missing API key",
+            Self::Jwt(_) => "Missing bearer token",
+        }
+    }
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+}
+
+/// Extracts the API key from `X-Api-Key` or a bearer token.
+pub fn presented_key(headers: &HeaderMap) -> Option<&str> {
+    if let Some(key) = headers.get(API_KEY_HEADER) {
+        return key.to_str().ok();
+    }
+    bearer_token(headers)
+}
+
+/// Why a request has no principal, kept for the routes requiring
+/// one. Routes that do not are served anonymously.
+#[derive(Clone)]
+enum Unauthenticated {
+    Missing(&'static str),
+    Invalid(String),
+    /// The credentials could not be checked, e.g. the JWKS is down.
+    Unavailable(String),
+}
+
+impl Unauthenticated {
+    fn into_error(self) -> AppError {
+        match self {
+            Self::Missing(message) => {
+                AppError::Unauthorized(message.to_string())
+            }
+            Self::Invalid(message) => AppError::Unauthorized(message),
+            Self::Unavailable(message) => {
+                AppError::ExternalApi(message)
+            }
+        }
+    }
+}
+
+/// Authenticates every request, storing its `Principal` in the
+/// request extensions for the handlers and the other middlewares.
+pub async fn authenticate(
+    State(authenticator): State<Arc<Authenticator>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    match authenticator.authenticate(request.headers()).await {
+        Ok(Some(principal)) => {
+            request.extensions_mut().insert(principal);
+        }
+        Ok(None) => {
+            request.extensions_mut().insert(
+                Unauthenticated::Missing(authenticator.missing()),
+            );
+        }
+        Err(AppError::Unauthorized(message)) => {
+            request
+                .extensions_mut()
+                .insert(Unauthenticated::Invalid(message));
+        }
+        Err(e) => {
+            warn!(error = %e, "Failed to authenticate request");
+            request
+                .extensions_mut()
+                .insert(Unauthenticated::Unavailable(e.to_string()));
+        }
+    }
+    next.run(request).await
+}
+
+/// A role the routes of a group require, for `RequireRole`.
+pub trait RequiredRole {
+    const ROLE: Role;
+}
+
+pub struct Reader;
+pub struct Operator;
+pub struct Admin;
+
+impl RequiredRole for Reader {
+    const ROLE: Role = Role::Reader;
+}
+
+impl RequiredRole for Operator {
+    const ROLE: Role = Role::Operator;
+}
+
+impl RequiredRole for Admin {
+    const ROLE: Role = Role::Admin;
+}
+
+/// Admits principals granted `R::ROLE` or a higher role. Applied to
+/// a route group with `middleware::from_extractor`.
+pub struct RequireRole<R>(PhantomData<R>);
+
+#[async_trait]
+impl<S, R> FromRequestParts<S> for RequireRole<R>
+where
+    S: Send + Sync,
+    R: RequiredRole,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        let principal =
+            Principal::from_request_parts(parts, state).await?;
+        if principal.role() >= Some(R::ROLE) {
+            Ok(Self(PhantomData))
+        } else {
+            Err(AppError::Forbidden(format!(
+                "The '{}' role is required",
+                R::ROLE.name()
+            )))
+        }
+    }
+}
+
+/// Identifies the caller by user when authenticated, and by client
+/// address otherwise, e.g. `user:ash` or `ip:10.0.0.7`.
+pub fn caller_id(
+    principal: Option<&Principal>,
+    client: ClientIp,
+) -> String {
+    if let Some(principal) = principal {
+        return format!("user:{}", principal.user_id);
+    }
+    match client {
+        ClientIp(Some(addr)) => format!("ip:{}", addr),
+        ClientIp(None) => "local".to_string(),
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Principal
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        if let Some(principal) = parts.extensions.get::<Principal>() {
+            return Ok(principal.clone());
+        }
+        Err(parts
+            .extensions
+            .get::<Unauthenticated>()
+            .cloned()
+            .unwrap_or(Unauthenticated::Missing("Missing API key"))
+            .into_error())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_authenticate() {
+        let keys = ApiKeys::parse("k1=ash+admin, k2=misty@cerulean")
+            .unwrap();
+        assert_eq!(
+            keys.authenticate("k1"),
+            Some(Principal {
+                user_id: "ash".to_string(),
+                tenant_id: None,
+                roles: vec!["admin".to_string()],
+            })
+        );
+        assert_eq!(
+            keys.authenticate("k2"),
+            Some(Principal {
+                user_id: "misty".to_string(),
+                tenant_id: Some("cerulean".to_string()),
+                roles: Vec::new(),
+            })
+        );
+        assert_eq!(keys.authenticate("k3"), None);
+        assert_eq!(
+            keys.tenant_ids().collect::<Vec<_>>(),
+            ["cerulean"]
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_entries() {
+        assert!(ApiKeys::parse("k1").is_err());
+        assert!(ApiKeys::parse("=ash").is_err());
+        assert!(ApiKeys::parse("k1=ash@").is_err());
+        assert!(ApiKeys::parse("k1=ash+").is_err());
+        assert!(ApiKeys::parse("k1=+admin").is_err());
+        assert!(ApiKeys::parse("k1=ash+root").is_err());
+        assert!(ApiKeys::parse("").unwrap().keys.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_authenticate_only_trusts_known_keys() {
+        let authenticator =
+            Authenticator::ApiKeys(ApiKeys::parse("k1=ash").unwrap());
+        let headers = |key: &'static str| {
+            HeaderMap::from_iter([(
+                header::HeaderName::from_static(API_KEY_HEADER),
+                header::HeaderValue::from_static(key),
+            )])
+        };
+        let principal =
+            authenticator.authenticate(&headers("k1")).await.unwrap();
+        assert_eq!(
+            principal.map(|principal| principal.user_id),
+            Some("ash".to_string())
+        );
+        assert!(matches!(
+            authenticator.authenticate(&headers("made-up")).await,
+            Err(AppError::Unauthorized(_))
+        ));
+        assert!(matches!(
+            authenticator.authenticate(&HeaderMap::new()).await,
+            Ok(None)
+        ));
+    }
+
+    #[test]
+    fn test_caller_id() {
+        let principal =
+            ApiKeys::parse("k1=ash").unwrap().authenticate("k1");
+        let client = ClientIp(Some([10, 0, 0, 7].into()));
+        assert_eq!(caller_id(principal.as_ref(), client), "user:ash");
+        assert_eq!(caller_id(None, client), "ip:10.0.0.7");
+        assert_eq!(caller_id(None, ClientIp(None)), "local");
+    }
+
+    async fn require<R: RequiredRole>(
+        principal: Option<Principal>,
+    ) -> Result<RequireRole<R>, AppError> {
+        let mut request = axum::http::Request::new(());
+        if let Some(principal) = principal {
+            request.extensions_mut().insert(principal);
+        }
+        let (mut parts, ()) = request.into_parts();
+        RequireRole::<R>::from_request_parts(&mut parts, &()).await
+    }
+
+    #[tokio::test]
+    async fn test_require_role_includes_lower_roles() {
+        let keys =
+            ApiKeys::parse("k1=ash+operator, k2=misty").unwrap();
+        let operator = keys.authenticate("k1");
+        let ordinary = keys.authenticate("k2");
+        assert!(require::<Reader>(operator.clone()).await.is_ok());
+        assert!(require::<Operator>(operator.clone()).await.is_ok());
+        assert!(matches!(
+            require::<Admin>(operator).await,
+            Err(AppError::Forbidden(_))
+        ));
+        assert!(matches!(
+            require::<Reader>(ordinary).await,
+            Err(AppError::Forbidden(_))
+        ));
+        assert!(matches!(
+            require::<Reader>(None).await,
+            Err(AppError::Unauthorized(_))
+        ));
+    }
+
+    #[test]
+    fn test_ensure_user() {
+        let principal = Principal {
+            user_id: "ash".to_string(),
+            tenant_id: None,
+            roles: Vec::new(),
+        };
+        assert!(principal.ensure_user("ash").is_ok());
+        assert!(principal.ensure_user("gary").is_err());
+    }
+}
@@ -0,0 +1,191 @@
+//! Generates concurrent traffic against a running server and reports
+//! throughput and latency percentiles.
+//!
+//! ```text
+//! cargo run --release --bin load-test -- \
+//!     --url http://127.0.0.1:5000 --concurrency 64 --duration 30 \
+//!     --path /pokemon/pikachu --path /pokemon/mewtwo/translated
+//! ```
+
+use std::{
+    process::ExitCode,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+const USAGE: &str = "usage: load-test [--url URL] [--concurrency N] [--duration SECS] [--path PATH]...";
+
+struct Options {
+    url: String,
+    concurrency: usize,
+    duration: Duration,
+    paths: Vec<String>,
+}
+
+impl Options {
+    fn parse(
+        mut args: impl Iterator<Item = String>,
+    ) -> Result<Self, String> {
+        let mut options = Options {
+            url: "http://127.0.0.1:5000".to_string(),
+            concurrency: 32,
+            duration: Duration::from_secs(10),
+            paths: Vec::new(),
+        };
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("{} needs a value", arg))
+            };
+            match arg.as_str() {
+                "--url" => {
+                    options.url =
+                        value()?.trim_end_matches('/').to_string()
+                }
+                "--concurrency" => {
+                    options.concurrency = value()?
+                        .parse()
+                        .ok()
+                        .filter(|&n| n > 0)
+                        .ok_or("--concurrency must be positive")?
+                }
+                "--duration" => {
+                    options.duration = Duration::from_secs(
+                        value()?.parse().map_err(
+                            |_| "--duration must be seconds",
+                        )?,
+                    )
+                }
+                "--path" => options.paths.push(value()?),
+                _ => {
+                    return Err(format!(
+                        "unknown argument '{}'",
+                        arg
+                    ));
+                }
+            }
+        }
+        if options.paths.is_empty() {
+            options.paths.push("/pokemon/pikachu".to_string());
+        }
+        Ok(options)
+    }
+}
+
+/// What one worker saw.
+#[derive(Default)]
+struct Outcome {
+    latencies: Vec<Duration>,
+    errors: u64,
+}
+
+async fn worker(
+    client: reqwest::Client,
+    urls: Arc<Vec<String>>,
+    offset: usize,
+    deadline: Instant,
+) -> Outcome {
+    let mut outcome = Outcome::default();
+    let mut next = offset;
+    while Instant::now() < deadline {
+        let url = &urls[next % urls.len()];
+        next += 1;
+        let started = Instant::now();
+        let ok = match client.get(url).send().await {
+            Ok(response) => {
+                let ok = response.status().is_success();
+                ok && response.bytes().await.is_ok()
+            }
+            Err(_) => false,
+        };
+        if ok {
+            outcome.latencies.push(started.elapsed());
+        } else {
+            outcome.errors += 1;
+        }
+    }
+    outcome
+}
+
+/// The latency below which `percent` of the requests completed.
+fn percentile(sorted: &[Duration], percent: usize) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    sorted[(sorted.len() * percent / 100).min(sorted.len() - 1)]
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+    let urls: Arc<Vec<String>> = Arc::new(
+        options
+            .paths
+            .iter()
+            .map(|path| format!("{}{}", options.url, path))
+            .collect(),
+    );
+    let client = reqwest::Client::builder()
+        .pool_max_idle_per_host(options.concurrency)
+        .build()
+        .expect("failed to build the HTTP client");
+
+    println!(
+        "{} workers for {}s against {}",
+        options.concurrency,
+        options.duration.as_secs(),
+        options.url
+    );
+    let started = Instant::now();
+    let deadline = started + options.duration;
+    let workers: Vec<_> = (0..options.concurrency)
+        .map(|offset| {
+            tokio::spawn(worker(
+                client.clone(),
+                urls.clone(),
+                offset,
+                deadline,
+            ))
+        })
+        .collect();
+
+    let mut latencies = Vec::new();
+    let mut errors = 0;
+    for worker in workers {
+        let outcome = worker.await.expect("worker panicked");
+        latencies.extend(outcome.latencies);
+        errors += outcome.errors;
+    }
+    let elapsed = started.elapsed().as_secs_f64();
+    latencies.sort();
+
+    println!(
+        "requests: {} ok, {} failed, {:.1} req/s",
+        latencies.len(),
+        errors,
+        latencies.len() as f64 / elapsed
+    );
+    for (label, percent) in [("p50", 50), ("p90", 90), ("p99", 99)] {
+        println!(
+            "{}: {:.2} ms",
+            label,
+            percentile(&latencies, percent).as_secs_f64() * 1000.0
+        );
+    }
+    println!(
+        "max: {:.2} ms",
+        latencies.last().copied().unwrap_or_default().as_secs_f64()
+            * 1000.0
+    );
+    if errors == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
@@ -0,0 +1,496 @@
+//! Terminal client for the Pokedex API: search as you type, browse
+//! the matches and toggle the translation of their descriptions.
+//!
+//! ```text
+//! cargo run --features tui --bin pokedex-tui -- \
+//!     --url http://127.0.0.1:5000 --api-key k1
+//! ```
+
+use pokedex_rs::client::{ClientError, PokedexClient};
+use pokedex_rs::models::{Pokemon, SpeciesSummary};
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::event::{
+        self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers,
+    },
+    layout::{Constraint, Layout},
+    style::{Style, Stylize},
+    text::Line,
+    widgets::{Block, List, ListState, Paragraph, Wrap},
+};
+use reqwest::header::{HeaderMap, HeaderValue};
+use std::{
+    io,
+    process::ExitCode,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+use tokio::{runtime::Runtime, sync::mpsc};
+
+const USAGE: &str = "usage: pokedex-tui [--url URL] [--api-key KEY]";
+
+/// Pause in typing after which the query is searched.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+const SEARCH_LIMIT: usize = 50;
+
+struct Options {
+    url: String,
+    api_key: Option<String>,
+}
+
+impl Options {
+    fn parse(
+        mut args: impl Iterator<Item = String>,
+    ) -> Result<Self, String> {
+        let mut options = Options {
+            url: "http://127.0.0.1:5000".to_string(),
+            api_key: None,
+        };
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("{} needs a value", arg))
+            };
+            match arg.as_str() {
+                "--url" => options.url = value()?,
+                "--api-key" => options.api_key = Some(value()?),
+                _ => {
+                    return Err(format!(
+                        "unknown argument '{}'",
+                        arg
+                    ));
+                }
+            }
+        }
+        Ok(options)
+    }
+
+    fn client(&self) -> Result<PokedexClient, String> {
+        let mut headers = HeaderMap::new();
+        if let Some(key) = &self.api_key {
+            let key = HeaderValue::from_str(key)
+                .map_err(|_| "invalid API key".to_string())?;
+            headers.insert("x-api-key", key);
+        }
+        let http = reqwest::Client::builder()
+            .default_headers(headers)
+            .timeout(Duration::from_secs(30))
+            .build()
+            .map_err(|e| e.to_string())?;
+        PokedexClient::with_client(http, &self.url)
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// A request for the API, made in the background.
+#[derive(Debug, PartialEq)]
+enum Action {
+    Search {
+        id: u64,
+        query: String,
+    },
+    Fetch {
+        id: u64,
+        name: String,
+        translated: bool,
+    },
+    Quit,
+}
+
+/// The answer to an `Action`, tagged with its id so that answers
+/// to superseded requests are dropped.
+enum Message {
+    Results {
+        id: u64,
+        result: Result<Vec<SpeciesSummary>, ClientError>,
+    },
+    Details {
+        id: u64,
+        result: Result<Box<Pokemon>, ClientError>,
+    },
+}
+
+#[derive(Default)]
+struct App {
+    query: String,
+    results: Vec<SpeciesSummary>,
+    list: ListState,
+    details: Option<Pokemon>,
+    translated: bool,
+    status: String,
+    search_id: u64,
+    fetch_id: u64,
+}
+
+impl App {
+    fn key(&mut self, key: KeyEvent) -> Option<Action> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => Some(Action::Quit),
+            KeyCode::Char('c') if ctrl => Some(Action::Quit),
+            KeyCode::Char(c) if !ctrl => {
+                self.query.push(c);
+                self.search()
+            }
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.search()
+            }
+            KeyCode::Down => self.select(1),
+            KeyCode::Up => self.select(-1),
+            KeyCode::Tab => {
+                self.translated = !self.translated;
+                self.fetch()
+            }
+            _ => None,
+        }
+    }
+
+    fn search(&mut self) -> Option<Action> {
+        self.search_id += 1;
+        let query = self.query.trim().to_lowercase();
+        if query.is_empty() {
+            self.results.clear();
+            self.list.select(None);
+            return None;
+        }
+        self.status = "Searching...".to_string();
+        Some(Action::Search {
+            id: self.search_id,
+            query,
+        })
+    }
+
+    /// Moves the selection by `offset` and fetches the selected
+    /// Pokemon.
+    fn select(&mut self, offset: isize) -> Option<Action> {
+        if self.results.is_empty() {
+            return None;
+        }
+        let last = self.results.len() - 1;
+        let selected = match self.list.selected() {
+            Some(i) => i.saturating_add_signed(offset).min(last),
+            None => 0,
+        };
+        self.list.select(Some(selected));
+        self.fetch()
+    }
+
+    fn fetch(&mut self) -> Option<Action> {
+        let species = &self.results[self.list.selected()?];
+        self.fetch_id += 1;
+        self.status = format!("Fetching {}...", species.name);
+        Some(Action::Fetch {
+            id: self.fetch_id,
+            name: species.name.clone(),
+            translated: self.translated,
+        })
+    }
+
+    fn receive(&mut self, message: Message) -> Option<Action> {
+        match message {
+            Message::Results { id, result } => {
+                if id != self.search_id {
+                    return None;
+                }
+                match result {
+                    Ok(results) => {
+                        self.status =
+                            format!("{} matches", results.len());
+                        self.results = results;
+                        self.list.select(None);
+                        return self.select(0);
+                    }
+                    Err(e) => self.status = e.to_string(),
+                }
+            }
+            Message::Details { id, result } => {
+                if id != self.fetch_id {
+                    return None;
+                }
+                match result {
+                    Ok(pokemon) => {
+                        self.status.clear();
+                        self.details = Some(*pokemon);
+                    }
+                    Err(e) => {
+                        self.status = e.to_string();
+                        self.details = None;
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [search, body, footer] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [list, details] = Layout::horizontal([
+            Constraint::Percentage(30),
+            Constraint::Percentage(70),
+        ])
+        .areas(body);
+
+        frame.render_widget(
+            Paragraph::new(self.query.as_str())
+                .block(Block::bordered().title("Search")),
+            search,
+        );
+        frame.set_cursor_position((
+            search.x + 1 + self.query.chars().count() as u16,
+            search.y + 1,
+        ));
+
+        let items = self.results.iter().map(|species| {
+            format!("#{:<4} {}", species.id, species.name)
+        });
+        frame.render_stateful_widget(
+            List::new(items)
+                .block(Block::bordered().title("Pokemon"))
+                .highlight_style(Style::new().reversed()),
+            list,
+            &mut self.list,
+        );
+
+        let title = if self.translated {
+            "Details (translated)"
+        } else {
+            "Details"
+        };
+        frame.render_widget(
+            Paragraph::new(self.details_lines())
+                .wrap(Wrap { trim: true })
+                .block(Block::bordered().title(title)),
+            details,
+        );
+
+        let translation = if self.translated { "on" } else { "off" };
+        frame.render_widget(
+            Line::from(format!(
+                "↑↓ select · Tab translation: {} · Esc quit   {}",
+                translation, self.status
+            ))
+            .dim(),
+            footer,
+        );
+    }
+
+    fn details_lines(&self) -> Vec<Line<'_>> {
+        let Some(pokemon) = &self.details else {
+            return Vec::new();
+        };
+        let facts = [
+            pokemon
+                .habitat
+                .as_ref()
+                .map(|h| format!("Habitat: {}", h)),
+            pokemon.is_legendary.then(|| "Legendary".to_string()),
+            pokemon.is_mythical.then(|| "Mythical".to_string()),
+            pokemon.is_baby.then(|| "Baby".to_string()),
+        ];
+        let facts: Vec<String> =
+            facts.into_iter().flatten().collect();
+        vec![
+            Line::from(
+                pokemon
+                    .display_name
+                    .as_deref()
+                    .unwrap_or(&pokemon.name),
+            )
+            .bold(),
+            Line::from(pokemon.genus.as_deref().unwrap_or_default())
+                .italic(),
+            Line::from(facts.join(" · ")).dim(),
+            Line::default(),
+            Line::from(
+                pokemon
+                    .description
+                    .as_deref()
+                    .unwrap_or("No description."),
+            ),
+        ]
+    }
+}
+
+/// Runs `action` in the background, sending its answer to
+/// `messages`. Searches wait for a pause in typing and are skipped
+/// when `latest_search` moved past them meanwhile.
+fn spawn(
+    runtime: &Runtime,
+    client: &PokedexClient,
+    action: Action,
+    latest_search: &Arc<AtomicU64>,
+    messages: &mpsc::UnboundedSender<Message>,
+) {
+    let client = client.clone();
+    let messages = messages.clone();
+    match action {
+        Action::Search { id, query } => {
+            latest_search.store(id, Ordering::Relaxed);
+            let latest_search = latest_search.clone();
+            runtime.spawn(async move {
+                tokio::time::sleep(DEBOUNCE).await;
+                if latest_search.load(Ordering::Relaxed) != id {
+                    return;
+                }
+                let result = client
+                    .search(&query, SEARCH_LIMIT)
+                    .await
+                    .map(|page| page.results);
+                messages.send(Message::Results { id, result }).ok();
+            });
+        }
+        Action::Fetch {
+            id,
+            name,
+            translated,
+        } => {
+            runtime.spawn(async move {
+                let result = if translated {
+                    client.get_translated(&name).await
+                } else {
+                    client.get_pokemon(&name).await
+                }
+                .map(Box::new);
+                messages.send(Message::Details { id, result }).ok();
+            });
+        }
+        Action::Quit => {}
+    }
+}
+
+fn run(
+    terminal: &mut DefaultTerminal,
+    runtime: &Runtime,
+    client: &PokedexClient,
+) -> io::Result<()> {
+    let (sender, mut messages) = mpsc::unbounded_channel();
+    let latest_search = Arc::new(AtomicU64::new(0));
+    let mut app = App::default();
+    loop {
+        terminal.draw(|frame| app.draw(frame))?;
+
+        let mut actions = Vec::new();
+        if event::poll(Duration::from_millis(50))?
+            && let Event::Key(key) = event::read()?
+            && key.kind == KeyEventKind::Press
+        {
+            actions.extend(app.key(key));
+        }
+        while let Ok(message) = messages.try_recv() {
+            actions.extend(app.receive(message));
+        }
+        for action in actions {
+            if action == Action::Quit {
+                return Ok(());
+            }
+            spawn(runtime, client, action, &latest_search, &sender);
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let client = match Options::parse(std::env::args().skip(1))
+        .and_then(|options| options.client())
+    {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+    let runtime =
+        Runtime::new().expect("failed to start the tokio runtime");
+
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, &runtime, &client);
+    ratatui::restore();
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(app: &mut App, code: KeyCode) -> Option<Action> {
+        app.key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    fn species(id: u32, name: &str) -> SpeciesSummary {
+        SpeciesSummary {
+            id,
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_search_as_you_type() {
+        let mut app = App::default();
+        press(&mut app, KeyCode::Char('p'));
+        let action = press(&mut app, KeyCode::Char('i'));
+        assert_eq!(
+            action,
+            Some(Action::Search {
+                id: 2,
+                query: "pi".to_string()
+            })
+        );
+
+        // The results of the superseded search are dropped.
+        let stale = Message::Results {
+            id: 1,
+            result: Ok(vec![species(16, "pidgey")]),
+        };
+        assert_eq!(app.receive(stale), None);
+        assert!(app.results.is_empty());
+
+        let action = app.receive(Message::Results {
+            id: 2,
+            result: Ok(vec![species(25, "pikachu")]),
+        });
+        assert_eq!(
+            action,
+            Some(Action::Fetch {
+                id: 1,
+                name: "pikachu".to_string(),
+                translated: false
+            })
+        );
+        assert!(press(&mut app, KeyCode::Backspace).is_some());
+    }
+
+    #[test]
+    fn test_translation_toggle_refetches() {
+        let mut app = App {
+            results: vec![
+                species(1, "bulbasaur"),
+                species(2, "ivysaur"),
+            ],
+            ..App::default()
+        };
+        assert_eq!(press(&mut app, KeyCode::Tab), None);
+        press(&mut app, KeyCode::Down);
+        let action = press(&mut app, KeyCode::Down);
+        assert_eq!(
+            action,
+            Some(Action::Fetch {
+                id: 2,
+                name: "ivysaur".to_string(),
+                translated: true
+            })
+        );
+        assert_eq!(press(&mut app, KeyCode::Esc), Some(Action::Quit));
+    }
+}
@@ -0,0 +1,178 @@
+use axum::{
+    Json, async_trait,
+    extract::{FromRef, FromRequest, Request},
+    http::StatusCode,
+};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::error::{AppError, FieldError};
+
+/// A JSON request body whose rejections are `AppError`s: a body
+/// that does not deserialize into `T` is a `ValidationError` naming
+/// the missing or mismatched field, e.g. `names`, rather than axum's
+/// plain-text
+/// `422`, and a body past `DefaultBodyLimit` is a `PayloadTooLarge`
+/// naming the limit.
+#[derive(Debug)]
+pub struct JsonBody<T>(pub T);
+
+/// Size limit of request bodies in bytes, as set by
+/// `DefaultBodyLimit`.
+#[derive(Debug, Clone, Copy)]
+pub struct BodyLimit(pub usize);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for JsonBody<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+    BodyLimit: FromRef<S>,
+{
+    type Rejection = AppError;
+
+    async fn from_request(
+        request: Request,
+        state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<Value>::from_request(request, state)
+            .await
+            .map_err(|e| match e.status() {
+                StatusCode::PAYLOAD_TOO_LARGE => {
+                    AppError::PayloadTooLarge(format!(
+                        "Request bodies may be at most {} bytes",
+                        BodyLimit::from_ref(state).0
+                    ))
+                }
+                _ => invalid("body", e.body_text()),
+            })?;
+        serde_path_to_error::deserialize(value)
+            .map(JsonBody)
+            .map_err(|e| {
+                // A missing field is reported on the object lacking
+                // it, which is the document itself, `.`, at the top.
+                let path = match e.path().to_string() {
+                    path if path == "." => None,
+                    path => Some(path),
+                };
+                let message = e.inner().to_string();
+                match missing_field(&message) {
+                    Some(name) => {
+                        let field = match path {
+                            Some(path) => {
+                                format!("{}.{}", path, name)
+                            }
+                            None => name.to_string(),
+                        };
+                        rejected(&field, "required", message)
+                    }
+                    None => invalid(
+                        path.as_deref().unwrap_or("body"),
+                        message,
+                    ),
+                }
+            })
+    }
+}
+
+/// The name in serde's "missing field `name`" error.
+fn missing_field(message: &str) -> Option<&str> {
+    message
+        .strip_prefix("missing field `")?
+        .split_once('`')
+        .map(|(name, _)| name)
+}
+
+fn invalid(field: &str, message: String) -> AppError {
+    rejected(field, "invalid", message)
+}
+
+fn rejected(
+    field: &str,
+    code: &'static str,
+    message: String,
+) -> AppError {
+    AppError::ValidationError(vec![FieldError::new(
+        field, code, message,
+    )])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Team {
+        names: Vec<String>,
+        #[serde(default)]
+        trainer: Option<Trainer>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Trainer {
+        name: String,
+    }
+
+    async fn extract(body: &'static str) -> Result<Team, AppError> {
+        let request = Request::builder()
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .unwrap();
+        JsonBody::<Team>::from_request(request, &BodyLimit(64))
+            .await
+            .map(|JsonBody(team)| team)
+    }
+
+    fn fields(error: AppError) -> Vec<(String, &'static str)> {
+        match error {
+            AppError::ValidationError(fields) => fields
+                .into_iter()
+                .map(|f| (f.field, f.code))
+                .collect(),
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mismatched_field_is_named() {
+        let error = extract(r#"{"names":"x"}"#).await.unwrap_err();
+        assert_eq!(fields(error), [("names".to_string(), "invalid")]);
+
+        let error =
+            extract(r#"{"names":["a",1]}"#).await.unwrap_err();
+        assert_eq!(
+            fields(error),
+            [("names[1]".to_string(), "invalid")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_malformed_body_is_reported_on_the_body() {
+        let error = extract("{").await.unwrap_err();
+        assert_eq!(fields(error), [("body".to_string(), "invalid")]);
+
+        let error = extract("[]").await.unwrap_err();
+        assert_eq!(fields(error), [("body".to_string(), "invalid")]);
+    }
+
+    #[tokio::test]
+    async fn test_missing_field_is_named() {
+        let error = extract("{}").await.unwrap_err();
+        assert_eq!(
+            fields(error),
+            [("names".to_string(), "required")]
+        );
+
+        let error = extract(r#"{"names":[],"trainer":{}}"#)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            fields(error),
+            [("trainer.name".to_string(), "required")]
+        );
+    }
+}
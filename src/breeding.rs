@@ -0,0 +1,278 @@
+use crate::cache::{Cache, ManagedCache};
+use crate::error::Result;
+use crate::pokeapi::{NamedApiResource, PokeApiClient};
+use crate::pokemon::{BreedingTraits, PokemonService};
+use futures::future::{try_join, try_join_all};
+use serde::{Deserialize, Serialize};
+use std::{sync::Arc, time::Duration};
+use tracing::instrument;
+use utoipa::ToSchema;
+
+/// Egg group of the species that cannot breed at all.
+const NO_EGGS: &str = "no-eggs";
+/// Egg group of Ditto, which breeds with any species that can breed.
+const DITTO: &str = "ditto";
+
+#[derive(Debug, Serialize, PartialEq, ToSchema)]
+pub struct BreedingParent {
+    pub name: String,
+    pub egg_groups: Vec<String>,
+    /// `male` and/or `female`; empty when genderless.
+    pub genders: Vec<&'static str>,
+}
+
+#[derive(Debug, Serialize, PartialEq, ToSchema)]
+pub struct BreedingCompatibility {
+    pub parents: Vec<BreedingParent>,
+    pub can_breed: bool,
+    /// Why the pair cannot breed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// The species an egg of the pair can hatch into.
+    pub offspring: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct PokeApiEvolutionChain {
+    chain: ChainLink,
+}
+
+#[derive(Deserialize)]
+struct ChainLink {
+    species: NamedApiResource,
+}
+
+pub struct BreedingService {
+    pokeapi: PokeApiClient,
+    pokemon_service: Arc<PokemonService>,
+    /// First species of each evolution chain, by chain id.
+    chain_cache: Cache<String, Arc<String>>,
+}
+
+impl BreedingService {
+    pub fn new(
+        pokeapi: PokeApiClient,
+        pokemon_service: Arc<PokemonService>,
+        cache_ttl: Duration,
+    ) -> Self {
+        Self {
+            pokeapi,
+            pokemon_service,
+            chain_cache: Cache::new(cache_ttl),
+        }
+    }
+
+    /// The caches owned by this service, by name.
+    pub fn caches(&self) -> Vec<(&'static str, &dyn ManagedCache)> {
+        vec![("breeding.chains", &self.chain_cache)]
+    }
+
+    /// Whether `a` and `b` can breed, and what their eggs hatch into.
+    #[instrument(skip(self))]
+    pub async fn compatibility(
+        &self,
+        a: &str,
+        b: &str,
+    ) -> Result<BreedingCompatibility> {
+        let (a, b) = try_join(
+            self.pokemon_service.breeding_traits(a),
+            self.pokemon_service.breeding_traits(b),
+        )
+        .await?;
+
+        let (mothers, reason) = match mothers(&a, &b) {
+            Ok(mothers) => (mothers, None),
+            Err(reason) => (Vec::new(), Some(reason)),
+        };
+        let mut offspring = try_join_all(
+            mothers.into_iter().map(|mother| self.base_form(mother)),
+        )
+        .await?;
+        offspring.dedup();
+
+        Ok(BreedingCompatibility {
+            parents: vec![parent(&a), parent(&b)],
+            can_breed: reason.is_none(),
+            reason,
+            offspring,
+        })
+    }
+
+    /// The first species of the evolution chain of `species`, which
+    /// its eggs hatch into.
+    async fn base_form(
+        &self,
+        species: &BreedingTraits,
+    ) -> Result<String> {
+        let Some(chain) = species.evolution_chain else {
+            return Ok(species.name.clone());
+        };
+        let key = chain.to_string();
+        if let Some(base) = self.chain_cache.fetch(&key).await {
+            return Ok(base.to_string());
+        }
+
+        let chain: PokeApiEvolutionChain = self
+            .pokeapi
+            .get(&format!("evolution-chain/{}", key), || {
+                format!("Evolution chain {} not found", key)
+            })
+            .await?;
+        let base = Arc::new(chain.chain.species.name);
+        self.chain_cache.store(key, base.clone()).await;
+        Ok(base.to_string())
+    }
+}
+
+/// (male, female) of a species with `gender_rate` eighths female.
+fn genders(gender_rate: i8) -> (bool, bool) {
+    match gender_rate {
+        -1 => (false, false),
+        0 => (true, false),
+        8 => (false, true),
+        _ => (true, true),
+    }
+}
+
+fn parent(traits: &BreedingTraits) -> BreedingParent {
+    let (male, female) = genders(traits.gender_rate);
+    BreedingParent {
+        name: traits.name.clone(),
+        egg_groups: traits.egg_groups.clone(),
+        genders: [(male, "male"), (female, "female")]
+            .into_iter()
+            .filter_map(|(has, gender)| has.then_some(gender))
+            .collect(),
+    }
+}
+
+/// The parents whose species the eggs of `a` and `b` hatch into:
+/// the female or, with Ditto, the other parent. `Err` tells why the
+/// pair cannot breed.
+fn mothers<'a>(
+    a: &'a BreedingTraits,
+    b: &'a BreedingTraits,
+) -> std::result::Result<Vec<&'a BreedingTraits>, String> {
+    let in_group = |traits: &BreedingTraits, group: &str| -> bool {
+        traits.egg_groups.iter().any(|g| g == group)
+    };
+    for traits in [a, b] {
+        if in_group(traits, NO_EGGS) || traits.egg_groups.is_empty() {
+            return Err(format!("{} cannot breed", traits.name));
+        }
+    }
+    match (in_group(a, DITTO), in_group(b, DITTO)) {
+        (true, true) => {
+            return Err("Ditto cannot breed with Ditto".to_string());
+        }
+        (true, false) => return Ok(vec![b]),
+        (false, true) => return Ok(vec![a]),
+        (false, false) => {}
+    }
+
+    if !a.egg_groups.iter().any(|group| in_group(b, group)) {
+        return Err(format!(
+            "{} and {} share no egg group",
+            a.name, b.name
+        ));
+    }
+    for traits in [a, b] {
+        if genders(traits.gender_rate) == (false, false) {
+            return Err(format!(
+                "{} is genderless and only breeds with Ditto",
+                traits.name
+            ));
+        }
+    }
+    let (a_male, a_female) = genders(a.gender_rate);
+    let (b_male, b_female) = genders(b.gender_rate);
+    let mothers: Vec<_> =
+        [(a, a_female && b_male), (b, b_female && a_male)]
+            .into_iter()
+            .filter_map(|(mother, can)| can.then_some(mother))
+            .collect();
+    if mothers.is_empty() {
+        return Err(format!(
+            "{} and {} cannot be of opposite genders",
+            a.name, b.name
+        ));
+    }
+    Ok(mothers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn traits(
+        name: &str,
+        egg_groups: &[&str],
+        gender_rate: i8,
+    ) -> BreedingTraits {
+        BreedingTraits {
+            name: name.to_string(),
+            egg_groups: egg_groups
+                .iter()
+                .map(|group| group.to_string())
+                .collect(),
+            gender_rate,
+            evolution_chain: None,
+        }
+    }
+
+    fn names(
+        result: std::result::Result<Vec<&BreedingTraits>, String>,
+    ) -> std::result::Result<Vec<&str>, String> {
+        result.map(|mothers| {
+            mothers.iter().map(|m| m.name.as_str()).collect()
+        })
+    }
+
+    #[test]
+    fn test_mothers() {
+        let pikachu = traits("pikachu", &["ground", "fairy"], 4);
+        let clefairy = traits("clefairy", &["fairy"], 6);
+        let nidoking = traits("nidoking", &["monster", "ground"], 0);
+        let ditto = traits("ditto", &["ditto"], -1);
+        let magnemite = traits("magnemite", &["mineral"], -1);
+        let mewtwo = traits("mewtwo", &["no-eggs"], -1);
+
+        assert_eq!(
+            names(mothers(&pikachu, &clefairy)),
+            Ok(vec!["pikachu", "clefairy"])
+        );
+        assert_eq!(
+            names(mothers(&nidoking, &pikachu)),
+            Ok(vec!["pikachu"])
+        );
+        assert_eq!(
+            names(mothers(&magnemite, &ditto)),
+            Ok(vec!["magnemite"])
+        );
+        assert_eq!(
+            names(mothers(&nidoking, &nidoking)),
+            Err(
+                "nidoking and nidoking cannot be of opposite genders"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            names(mothers(&nidoking, &clefairy)),
+            Err("nidoking and clefairy share no egg group"
+                .to_string())
+        );
+        assert_eq!(
+            names(mothers(&magnemite, &magnemite)),
+            Err("magnemite is genderless and only breeds with Ditto"
+                .to_string())
+        );
+        assert_eq!(
+            names(mothers(&ditto, &ditto)),
+            Err("Ditto cannot breed with Ditto".to_string())
+        );
+        assert_eq!(
+            names(mothers(&ditto, &mewtwo)),
+            Err("mewtwo cannot breed".to_string())
+        );
+    }
+}
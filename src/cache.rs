@@ -0,0 +1,971 @@
+use crate::cache_status;
+use crate::cache_store::{CacheStore, unix_millis};
+use crate::context;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    hash::Hash,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+/// Entries a cache holds before its first sweep of dead entries.
+const FIRST_SWEEP: usize = 64;
+
+/// A small in-memory cache whose entries expire after a fixed TTL.
+/// Expired entries may be kept a while longer as a fallback for when
+/// the upstream is down, and every entry may also be written through
+/// to a second-level store. A cache may also be bounded to a number
+/// of entries, making room according to its `Eviction`.
+pub struct Cache<K, V> {
+    ttl: Duration,
+    stale_for: Duration,
+    entries: Mutex<HashMap<K, Entry<V>>>,
+    eviction: Mutex<Eviction>,
+    /// Bound kept whatever `Eviction::max_entries` is, for caches
+    /// keyed on caller input.
+    max_entries: Option<usize>,
+    /// Size at which an insert sweeps the entries past their stale
+    /// window, twice the size left by the last sweep so sweeping
+    /// stays amortized O(1).
+    sweep_at: AtomicUsize,
+    /// Orders inserts and hits, for `Policy::Lru`.
+    clock: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+    second_level: Option<SecondLevel>,
+}
+
+struct Entry<V> {
+    expires_at: Instant,
+    value: V,
+    /// Clock tick of the insert or of the last hit.
+    used_at: u64,
+    hits: u64,
+}
+
+/// Counters reported for a cache by the admin endpoints.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+    /// Memory misses answered by the second-level store.
+    pub l2_hits: u64,
+    /// Entries dropped to stay within `Eviction::max_entries`.
+    pub evictions: u64,
+}
+
+/// Which entries make room first, from `CACHE_EVICTION`. Expired
+/// entries kept for the stale fallback always go before live ones.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Policy {
+    /// The entries expiring soonest.
+    #[default]
+    Ttl,
+    /// The least recently used entries.
+    Lru,
+    /// The least frequently used entries, so that the few Pokemon
+    /// most of the traffic asks for stay in memory.
+    Lfu,
+}
+
+impl Policy {
+    /// Parses `ttl`, `lru` or `lfu`.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.trim().to_lowercase().as_str() {
+            "ttl" => Ok(Policy::Ttl),
+            "lru" => Ok(Policy::Lru),
+            "lfu" => Ok(Policy::Lfu),
+            _ => Err(format!(
+                "unknown policy '{}', expected ttl, lru or lfu",
+                value
+            )),
+        }
+    }
+
+    /// Orders entries from the first to evict to the last. Ranks are
+    /// unique, as no two entries were last used at the same tick.
+    fn rank<V>(
+        self,
+        entry: &Entry<V>,
+        now: Instant,
+    ) -> (bool, u64, u64) {
+        let live = entry.expires_at > now;
+        match self {
+            Policy::Ttl => (
+                live,
+                entry
+                    .expires_at
+                    .saturating_duration_since(now)
+                    .as_millis() as u64,
+                entry.used_at,
+            ),
+            Policy::Lru => (live, entry.used_at, 0),
+            Policy::Lfu => (live, entry.hits, entry.used_at),
+        }
+    }
+}
+
+/// How a cache makes room, set on every cache with
+/// `ManagedCache::set_eviction`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Eviction {
+    pub policy: Policy,
+    /// Entries a cache holds at most, from `CACHE_MAX_ENTRIES`. When
+    /// unset, the cache is bounded by the TTL and stale window alone,
+    /// as inserts sweep the entries past them.
+    pub max_entries: Option<usize>,
+}
+
+/// When an entry found in the second-level store is copied into
+/// memory, from `CACHE_L2_PROMOTION`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Promotion {
+    Always,
+    Never,
+    /// On the n-th second-level hit, so only entries requested again
+    /// and again take up memory.
+    AfterHits(u32),
+}
+
+impl Promotion {
+    /// Parses `always`, `never` or a number of hits.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.trim().to_lowercase().as_str() {
+            "always" => Ok(Promotion::Always),
+            "never" => Ok(Promotion::Never),
+            hits => match hits.parse::<u32>() {
+                Ok(0) => Ok(Promotion::Never),
+                Ok(hits) => Ok(Promotion::AfterHits(hits)),
+                Err(_) => Err(format!(
+                    "unknown policy '{}', expected always, never or a number of hits",
+                    value
+                )),
+            },
+        }
+    }
+}
+
+struct SecondLevel {
+    store: Arc<dyn CacheStore>,
+    /// Prefix of this cache's keys in the store, e.g.
+    /// `pokemon.species:`.
+    prefix: String,
+    promotion: Promotion,
+    /// Hits of entries waiting for `Promotion::AfterHits`.
+    pending: Mutex<HashMap<String, u32>>,
+    hits: AtomicU64,
+}
+
+impl SecondLevel {
+    /// Counts a hit of `id`, returning whether to promote it.
+    fn promote(&self, id: &str) -> bool {
+        let threshold = match self.promotion {
+            Promotion::Always => return true,
+            Promotion::Never => return false,
+            Promotion::AfterHits(threshold) => threshold,
+        };
+        let mut pending = self.pending.lock().unwrap();
+        let hits = pending.entry(id.to_string()).or_insert(0);
+        *hits += 1;
+        if *hits < threshold {
+            return false;
+        }
+        pending.remove(id);
+        true
+    }
+}
+
+/// An entry as written to the second-level store.
+#[derive(Serialize, Deserialize)]
+struct Stored<V> {
+    /// Unix milliseconds.
+    expires_at: u64,
+    value: V,
+}
+
+/// Type-erased view of a cache, so caches with different key and
+/// value types can be inspected and flushed together.
+pub trait ManagedCache: Send + Sync {
+    fn stats(&self) -> CacheStats;
+    fn clear(&self);
+    /// The live entries, for the snapshot written on shutdown.
+    fn save(&self) -> Vec<SavedEntry>;
+    /// Loads the entries of a snapshot that have not expired since,
+    /// returning how many. Entries that no longer deserialize, e.g.
+    /// after a model change, are skipped.
+    fn load(&self, entries: Vec<SavedEntry>) -> usize;
+    /// Approximate memory held by the entries, stale ones included,
+    /// as the size of their JSON encoding.
+    fn weight(&self) -> usize;
+    /// Drops entries, in the order of the eviction policy, until the
+    /// cache weighs at most `target`, returning how many.
+    fn evict(&self, target: usize) -> usize;
+    fn set_eviction(&self, eviction: Eviction);
+}
+
+/// A cache entry in a snapshot file.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedEntry {
+    pub key: Value,
+    /// Unix milliseconds.
+    pub expires_at: u64,
+    pub value: Value,
+}
+
+impl<K, V> Cache<K, V>
+where
+    K: Eq + Hash,
+    V: Clone,
+{
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            stale_for: Duration::ZERO,
+            entries: Mutex::new(HashMap::new()),
+            eviction: Mutex::new(Eviction::default()),
+            max_entries: None,
+            sweep_at: AtomicUsize::new(FIRST_SWEEP),
+            clock: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+            second_level: None,
+        }
+    }
+
+    /// Keeps expired entries for `stale_for` more, for `get_stale`.
+    pub fn with_stale(mut self, stale_for: Duration) -> Self {
+        self.stale_for = stale_for;
+        self
+    }
+
+    /// Holds at most `max_entries` entries, even when the eviction
+    /// set later is unbounded or allows more.
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = Some(max_entries);
+        self
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        let found = self.lookup(key);
+        match &found {
+            Some((_, age)) => cache_status::hit(*age),
+            None => cache_status::miss(),
+        }
+        found.map(|(value, _)| value)
+    }
+
+    /// The live entry for `key` and its age, counted in the
+    /// statistics but not in the response's `X-Cache`.
+    fn lookup(&self, key: &K) -> Option<(V, Duration)> {
+        if context::bypass_cache() {
+            return None;
+        }
+
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        let value = match entries.get_mut(key) {
+            Some(entry) if entry.expires_at > now => {
+                entry.used_at = self.tick();
+                entry.hits += 1;
+                Some((entry.value.clone(), self.age(entry, now)))
+            }
+            Some(entry)
+                if entry.expires_at + self.stale_for <= now =>
+            {
+                entries.remove(key);
+                None
+            }
+            _ => None,
+        };
+
+        let counter = if value.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        value
+    }
+
+    /// The entry for `key` even if it has expired, as long as it is
+    /// kept for `with_stale`. Not counted in the statistics, but
+    /// marks the response `STALE`.
+    pub fn get_stale(&self, key: &K) -> Option<V> {
+        if context::bypass_cache() {
+            return None;
+        }
+
+        let now = Instant::now();
+        let entries = self.entries.lock().unwrap();
+        let entry = entries.get(key).filter(|entry| {
+            entry.expires_at + self.stale_for > now
+        })?;
+        cache_status::stale(self.age(entry, now));
+        Some(entry.value.clone())
+    }
+
+    /// Time since `entry` was fetched, assuming it was cached for the
+    /// full TTL.
+    fn age(&self, entry: &Entry<V>, now: Instant) -> Duration {
+        if entry.expires_at > now {
+            self.ttl.saturating_sub(entry.expires_at - now)
+        } else {
+            self.ttl + (now - entry.expires_at)
+        }
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        self.insert_for(key, value, self.ttl);
+    }
+
+    fn insert_for(&self, key: K, value: V, ttl: Duration) {
+        if context::bypass_cache() {
+            return;
+        }
+
+        let now = Instant::now();
+        let eviction = *self.eviction.lock().unwrap();
+        let max_entries =
+            match (eviction.max_entries, self.max_entries) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+        let mut entries = self.entries.lock().unwrap();
+        // Entries that are never asked for again are otherwise only
+        // dropped to make room.
+        if entries.len() >= self.sweep_at.load(Ordering::Relaxed) {
+            entries.retain(|_, entry| {
+                entry.expires_at + self.stale_for > now
+            });
+            self.sweep_at.store(
+                (entries.len() * 2).max(FIRST_SWEEP),
+                Ordering::Relaxed,
+            );
+        }
+        if let Some(max_entries) = max_entries
+            && !entries.contains_key(&key)
+            && entries.len() >= max_entries
+        {
+            let count = entries.len() + 1 - max_entries;
+            evict_first(&mut entries, eviction.policy, now, count);
+            self.evictions.fetch_add(count as u64, Ordering::Relaxed);
+        }
+        entries.insert(
+            key,
+            Entry {
+                expires_at: now + ttl,
+                value,
+                used_at: self.tick(),
+                hits: 0,
+            },
+        );
+    }
+
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+/// Drops the first `count` entries in the order of `policy`.
+fn evict_first<K, V>(
+    entries: &mut HashMap<K, Entry<V>>,
+    policy: Policy,
+    now: Instant,
+    count: usize,
+) {
+    let mut ranks: Vec<_> = entries
+        .values()
+        .map(|entry| policy.rank(entry, now))
+        .collect();
+    if count == 0 || ranks.is_empty() {
+        return;
+    }
+    let index = count.min(ranks.len()) - 1;
+    let (_, last, _) = ranks.select_nth_unstable(index);
+    let last = *last;
+    entries.retain(|_, entry| policy.rank(entry, now) > last);
+}
+
+impl<K, V> Cache<K, V>
+where
+    K: Eq + Hash + Clone + Display,
+    V: Clone + Serialize + DeserializeOwned,
+{
+    /// Writes every entry through to `store`, under the cache's
+    /// `name`, and looks up there what is not in memory.
+    pub fn with_second_level(
+        mut self,
+        name: &str,
+        store: Arc<dyn CacheStore>,
+        promotion: Promotion,
+    ) -> Self {
+        self.second_level = Some(SecondLevel {
+            store,
+            prefix: format!("{}:", name),
+            promotion,
+            pending: Mutex::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+        });
+        self
+    }
+
+    /// Like `get`, falling back to the second-level store. Store
+    /// failures are logged and count as misses.
+    pub async fn fetch(&self, key: &K) -> Option<V> {
+        let found = match self.lookup(key) {
+            Some(found) => Some(found),
+            None => self.fetch_second_level(key).await,
+        };
+        match &found {
+            Some((_, age)) => cache_status::hit(*age),
+            None => cache_status::miss(),
+        }
+        found.map(|(value, _)| value)
+    }
+
+    async fn fetch_second_level(
+        &self,
+        key: &K,
+    ) -> Option<(V, Duration)> {
+        let second_level = self.second_level.as_ref()?;
+        if context::bypass_cache() {
+            return None;
+        }
+
+        let id = format!("{}{}", second_level.prefix, key);
+        let bytes = match second_level.store.get(&id).await {
+            Ok(bytes) => bytes?,
+            Err(e) => {
+                warn!(key = %id, error = %e, "Second-level cache lookup failed");
+                return None;
+            }
+        };
+        let stored: Stored<V> = serde_json::from_slice(&bytes)
+            .inspect_err(|e| {
+                warn!(key = %id, error = %e, "Corrupted second-level cache entry");
+            })
+            .ok()?;
+        let remaining = Duration::from_millis(
+            stored.expires_at.checked_sub(unix_millis())?,
+        );
+        second_level.hits.fetch_add(1, Ordering::Relaxed);
+
+        if second_level.promote(&id) {
+            let ttl = remaining.min(self.ttl);
+            self.insert_for(key.clone(), stored.value.clone(), ttl);
+        }
+        Some((stored.value, self.ttl.saturating_sub(remaining)))
+    }
+
+    /// Like `insert`, writing through to the second-level store.
+    pub async fn store(&self, key: K, value: V) {
+        let Some(second_level) = &self.second_level else {
+            self.insert(key, value);
+            return;
+        };
+        if context::bypass_cache() {
+            return;
+        }
+
+        let id = format!("{}{}", second_level.prefix, key);
+        let stored = Stored {
+            expires_at: unix_millis() + self.ttl.as_millis() as u64,
+            value: &value,
+        };
+        self.insert(key, value.clone());
+        let bytes = match serde_json::to_vec(&stored) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                warn!(key = %id, error = %e, "Failed to serialize cache entry");
+                return;
+            }
+        };
+        if let Err(e) =
+            second_level.store.set(&id, bytes, self.ttl).await
+        {
+            warn!(key = %id, error = %e, "Second-level cache write failed");
+        }
+    }
+}
+
+impl<K, V> ManagedCache for Cache<K, V>
+where
+    K: Eq + Hash + Send + Serialize + DeserializeOwned,
+    V: Clone + Send + Serialize + DeserializeOwned,
+{
+    fn stats(&self) -> CacheStats {
+        let now = Instant::now();
+        let entries = self
+            .entries
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|entry| entry.expires_at > now)
+            .count();
+        CacheStats {
+            entries,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            l2_hits: self
+                .second_level
+                .as_ref()
+                .map_or(0, |l2| l2.hits.load(Ordering::Relaxed)),
+            evictions: self.evictions.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Also clears the second-level store, in the background.
+    fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+        if let Some(second_level) = &self.second_level {
+            second_level.pending.lock().unwrap().clear();
+            let store = second_level.store.clone();
+            let prefix = second_level.prefix.clone();
+            tokio::spawn(async move {
+                if let Err(e) = store.clear(&prefix).await {
+                    warn!(%prefix, error = %e, "Failed to clear second-level cache");
+                }
+            });
+        }
+    }
+
+    fn save(&self) -> Vec<SavedEntry> {
+        let (now, unix_now) = (Instant::now(), unix_millis());
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, entry)| entry.expires_at > now)
+            .filter_map(|(key, entry)| {
+                Some(SavedEntry {
+                    key: serde_json::to_value(key).ok()?,
+                    expires_at: unix_now
+                        + (entry.expires_at - now).as_millis() as u64,
+                    value: serde_json::to_value(&entry.value).ok()?,
+                })
+            })
+            .collect()
+    }
+
+    fn load(&self, entries: Vec<SavedEntry>) -> usize {
+        let unix_now = unix_millis();
+        let mut loaded = 0;
+        for entry in entries {
+            let Some(remaining) = entry
+                .expires_at
+                .checked_sub(unix_now)
+                .filter(|remaining| *remaining > 0)
+            else {
+                continue;
+            };
+            let (Ok(key), Ok(value)) = (
+                serde_json::from_value(entry.key),
+                serde_json::from_value(entry.value),
+            ) else {
+                continue;
+            };
+            // The TTL may have been shortened since the snapshot.
+            let ttl = Duration::from_millis(remaining).min(self.ttl);
+            self.insert_for(key, value, ttl);
+            loaded += 1;
+        }
+        loaded
+    }
+
+    fn weight(&self) -> usize {
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(key, entry)| weigh(key) + weigh(&entry.value))
+            .sum()
+    }
+
+    fn evict(&self, target: usize) -> usize {
+        let (now, policy) =
+            (Instant::now(), self.eviction.lock().unwrap().policy);
+        let mut entries = self.entries.lock().unwrap();
+        let mut ranked: Vec<_> = entries
+            .iter()
+            .map(|(key, entry)| {
+                (
+                    policy.rank(entry, now),
+                    weigh(key) + weigh(&entry.value),
+                )
+            })
+            .collect();
+        let mut weight: usize =
+            ranked.iter().map(|(_, weight)| weight).sum();
+
+        ranked.sort_unstable_by_key(|(rank, _)| *rank);
+        let mut count = 0;
+        for (_, entry) in ranked {
+            if weight <= target {
+                break;
+            }
+            weight -= entry;
+            count += 1;
+        }
+        evict_first(&mut entries, policy, now, count);
+        count
+    }
+
+    fn set_eviction(&self, eviction: Eviction) {
+        *self.eviction.lock().unwrap() = eviction;
+    }
+}
+
+/// Size of the JSON encoding of `value`, without allocating it.
+fn weigh(value: &impl Serialize) -> usize {
+    struct Counter(usize);
+
+    impl std::io::Write for Counter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0 += buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut counter = Counter(0);
+    let _ = serde_json::to_writer(&mut counter, value);
+    counter.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Result;
+    use axum::async_trait;
+
+    /// A store shared by the caches of two "replicas".
+    #[derive(Default)]
+    struct MemoryStore(Mutex<HashMap<String, Vec<u8>>>);
+
+    #[async_trait]
+    impl CacheStore for MemoryStore {
+        async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+            Ok(self.0.lock().unwrap().get(key).cloned())
+        }
+
+        async fn set(
+            &self,
+            key: &str,
+            value: Vec<u8>,
+            _ttl: Duration,
+        ) -> Result<()> {
+            self.0.lock().unwrap().insert(key.to_string(), value);
+            Ok(())
+        }
+
+        async fn clear(&self, prefix: &str) -> Result<()> {
+            self.0
+                .lock()
+                .unwrap()
+                .retain(|key, _| !key.starts_with(prefix));
+            Ok(())
+        }
+    }
+
+    fn tiered(
+        store: &Arc<MemoryStore>,
+        promotion: Promotion,
+    ) -> Cache<String, u32> {
+        Cache::new(Duration::from_secs(60)).with_second_level(
+            "species",
+            store.clone(),
+            promotion,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_second_level_is_shared_and_promoted() {
+        let store = Arc::new(MemoryStore::default());
+        let pikachu = "pikachu".to_string();
+        tiered(&store, Promotion::Always)
+            .store(pikachu.clone(), 25)
+            .await;
+        assert!(
+            store.0.lock().unwrap().contains_key("species:pikachu")
+        );
+
+        let replica = tiered(&store, Promotion::Always);
+        assert_eq!(replica.fetch(&pikachu).await, Some(25));
+        assert_eq!(replica.get(&pikachu), Some(25));
+        assert_eq!(replica.fetch(&"raichu".to_string()).await, None);
+        let stats = replica.stats();
+        assert_eq!(
+            (stats.hits, stats.misses, stats.l2_hits),
+            (1, 2, 1)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_promotion_after_hits() {
+        let store = Arc::new(MemoryStore::default());
+        let pikachu = "pikachu".to_string();
+        tiered(&store, Promotion::Never)
+            .store(pikachu.clone(), 25)
+            .await;
+
+        let replica = tiered(&store, Promotion::AfterHits(2));
+        assert_eq!(replica.fetch(&pikachu).await, Some(25));
+        assert_eq!(replica.get(&pikachu), None);
+        assert_eq!(replica.fetch(&pikachu).await, Some(25));
+        assert_eq!(replica.get(&pikachu), Some(25));
+
+        let never = tiered(&store, Promotion::Never);
+        never.fetch(&pikachu).await;
+        assert_eq!(never.get(&pikachu), None);
+    }
+
+    #[test]
+    fn test_parse_promotion() {
+        assert_eq!(Promotion::parse("Always"), Ok(Promotion::Always));
+        assert_eq!(Promotion::parse("never"), Ok(Promotion::Never));
+        assert_eq!(Promotion::parse("0"), Ok(Promotion::Never));
+        assert_eq!(
+            Promotion::parse("3"),
+            Ok(Promotion::AfterHits(3))
+        );
+        assert!(Promotion::parse("sometimes").is_err());
+    }
+
+    #[test]
+    fn test_get_returns_inserted_value() {
+        let cache = Cache::new(Duration::from_secs(60));
+        cache.insert("pikachu", 25);
+        assert_eq!(cache.get(&"pikachu"), Some(25));
+        assert_eq!(cache.get(&"raichu"), None);
+    }
+
+    #[test]
+    fn test_expired_entries_are_not_returned() {
+        let cache = Cache::new(Duration::ZERO);
+        cache.insert("pikachu", 25);
+        assert_eq!(cache.get(&"pikachu"), None);
+    }
+
+    #[test]
+    fn test_stale_entries_are_kept_for_fallback() {
+        let cache = Cache::new(Duration::ZERO)
+            .with_stale(Duration::from_secs(60));
+        cache.insert("pikachu", 25);
+        assert_eq!(cache.get(&"pikachu"), None);
+        assert_eq!(cache.get_stale(&"pikachu"), Some(25));
+        assert_eq!(cache.get_stale(&"raichu"), None);
+
+        let cache = Cache::new(Duration::ZERO);
+        cache.insert("pikachu", 25);
+        assert_eq!(cache.get_stale(&"pikachu"), None);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let cache = Cache::new(Duration::from_secs(60));
+        cache.insert("pikachu".to_string(), 25);
+        let saved = cache.save();
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved[0].key, "pikachu");
+
+        let restarted: Cache<String, u32> =
+            Cache::new(Duration::from_secs(60));
+        let expired = SavedEntry {
+            key: Value::from("raichu"),
+            expires_at: unix_millis() - 1,
+            value: Value::from(26),
+        };
+        let corrupted = SavedEntry {
+            key: Value::from("mew"),
+            expires_at: unix_millis() + 60_000,
+            value: Value::from("151"),
+        };
+        assert_eq!(
+            restarted.load(
+                saved
+                    .into_iter()
+                    .chain([expired, corrupted])
+                    .collect()
+            ),
+            1
+        );
+        assert_eq!(restarted.get(&"pikachu".to_string()), Some(25));
+        assert_eq!(restarted.stats().entries, 1);
+    }
+
+    #[test]
+    fn test_stats_and_clear() {
+        let cache = Cache::new(Duration::from_secs(60));
+        cache.insert("pikachu".to_string(), 25);
+        cache.get(&"pikachu".to_string());
+        cache.get(&"raichu".to_string());
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                entries: 1,
+                hits: 1,
+                misses: 1,
+                l2_hits: 0,
+                evictions: 0,
+            }
+        );
+
+        cache.clear();
+        assert_eq!(cache.stats().entries, 0);
+        assert_eq!(cache.get(&"pikachu".to_string()), None);
+    }
+
+    #[test]
+    fn test_evict_drops_entries_expiring_soonest() {
+        let cache = Cache::new(Duration::from_secs(60));
+        cache.insert_for(
+            "bulbasaur".to_string(),
+            1,
+            Duration::from_secs(10),
+        );
+        cache.insert_for(
+            "ivysaur".to_string(),
+            2,
+            Duration::from_secs(20),
+        );
+        cache.insert("venusaur".to_string(), 3);
+        // `"venusaur"` and `3`.
+        assert_eq!(weigh(&"venusaur"), 10);
+        let weight = cache.weight();
+        assert_eq!(weight, 11 + 1 + 9 + 1 + 10 + 1);
+
+        assert_eq!(cache.evict(weight), 0);
+        assert_eq!(cache.evict(weight - 1), 1);
+        assert_eq!(cache.get(&"bulbasaur".to_string()), None);
+        assert_eq!(cache.get(&"ivysaur".to_string()), Some(2));
+        assert_eq!(cache.evict(11), 1);
+        assert_eq!(cache.get(&"venusaur".to_string()), Some(3));
+        assert_eq!(cache.evict(0), 1);
+        assert_eq!(cache.weight(), 0);
+    }
+
+    fn bounded(policy: Policy) -> Cache<String, u32> {
+        let cache = Cache::new(Duration::from_secs(60));
+        cache.set_eviction(Eviction {
+            policy,
+            max_entries: Some(2),
+        });
+        cache
+    }
+
+    #[test]
+    fn test_eviction_policies() {
+        let key = |name: &str| name.to_string();
+
+        // Pikachu was inserted first but asked for most recently.
+        let lru = bounded(Policy::Lru);
+        lru.insert(key("pikachu"), 25);
+        lru.insert(key("eevee"), 133);
+        lru.get(&key("pikachu"));
+        lru.insert(key("mew"), 151);
+        assert_eq!(lru.get(&key("eevee")), None);
+        assert_eq!(lru.get(&key("pikachu")), Some(25));
+
+        // Pikachu is asked for more often than anything else.
+        let lfu = bounded(Policy::Lfu);
+        lfu.insert(key("pikachu"), 25);
+        lfu.get(&key("pikachu"));
+        lfu.get(&key("pikachu"));
+        lfu.insert(key("eevee"), 133);
+        lfu.get(&key("eevee"));
+        lfu.insert(key("mew"), 151);
+        assert_eq!(lfu.get(&key("eevee")), None);
+        assert_eq!(lfu.get(&key("pikachu")), Some(25));
+        assert_eq!(lfu.stats().evictions, 1);
+
+        let ttl = bounded(Policy::Ttl);
+        ttl.insert(key("pikachu"), 25);
+        ttl.insert_for(key("eevee"), 133, Duration::from_secs(10));
+        ttl.get(&key("eevee"));
+        ttl.insert(key("mew"), 151);
+        assert_eq!(ttl.get(&key("eevee")), None);
+        assert_eq!(ttl.stats().entries, 2);
+
+        // Replacing an entry does not make room.
+        ttl.insert(key("mew"), 150);
+        assert_eq!(ttl.get(&key("pikachu")), Some(25));
+        assert_eq!(ttl.stats().evictions, 1);
+    }
+
+    #[test]
+    fn test_expired_entries_are_evicted_first() {
+        let cache =
+            bounded(Policy::Lfu).with_stale(Duration::from_secs(60));
+        cache.insert_for("eevee".to_string(), 133, Duration::ZERO);
+        cache.insert("pikachu".to_string(), 25);
+        cache.insert("mew".to_string(), 151);
+        assert_eq!(cache.get_stale(&"eevee".to_string()), None);
+        assert_eq!(cache.stats().entries, 2);
+    }
+
+    #[test]
+    fn test_max_entries_outlasts_an_unbounded_eviction() {
+        let cache =
+            Cache::new(Duration::from_secs(60)).with_max_entries(2);
+        cache.set_eviction(Eviction::default());
+        for (i, name) in
+            ["pikachu", "eevee", "mew"].iter().enumerate()
+        {
+            cache.insert(name.to_string(), i);
+        }
+        assert_eq!(cache.stats().entries, 2);
+        assert_eq!(cache.stats().evictions, 1);
+
+        cache.set_eviction(Eviction {
+            policy: Policy::Ttl,
+            max_entries: Some(1),
+        });
+        cache.insert("ditto".to_string(), 3);
+        assert_eq!(cache.stats().entries, 1);
+    }
+
+    #[test]
+    fn test_inserts_sweep_entries_past_their_stale_window() {
+        let cache = Cache::new(Duration::ZERO);
+        for i in 0..FIRST_SWEEP {
+            cache.insert(i, i);
+        }
+        assert_eq!(cache.entries.lock().unwrap().len(), FIRST_SWEEP);
+        cache.insert(FIRST_SWEEP, FIRST_SWEEP);
+        assert_eq!(cache.entries.lock().unwrap().len(), 1);
+        assert_eq!(cache.stats().evictions, 0);
+
+        // Expired entries are kept for the stale fallback.
+        let cache = Cache::new(Duration::ZERO)
+            .with_stale(Duration::from_secs(60));
+        for i in 0..=FIRST_SWEEP {
+            cache.insert(i, i);
+        }
+        assert_eq!(
+            cache.entries.lock().unwrap().len(),
+            FIRST_SWEEP + 1
+        );
+        assert_eq!(cache.get_stale(&0), Some(0));
+    }
+
+    #[test]
+    fn test_parse_policy() {
+        assert_eq!(Policy::parse("LFU"), Ok(Policy::Lfu));
+        assert_eq!(Policy::parse(" lru "), Ok(Policy::Lru));
+        assert_eq!(Policy::parse("ttl"), Ok(Policy::Ttl));
+        assert!(Policy::parse("fifo").is_err());
+    }
+}
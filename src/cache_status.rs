@@ -0,0 +1,152 @@
+use axum::{
+    extract::Request,
+    http::{HeaderValue, header},
+    middleware::Next,
+    response::Response,
+};
+use std::{cell::RefCell, time::Duration};
+
+/// Whether a response was served from the caches.
+pub const CACHE_STATUS_HEADER: &str = "x-cache";
+
+/// What the cache lookups made while handling a request found.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Lookups {
+    hits: usize,
+    misses: usize,
+    stale: usize,
+    /// Age of the oldest entry served.
+    oldest: Duration,
+}
+
+impl Lookups {
+    /// `STALE` when any entry served had expired, `MISS` when any
+    /// lookup missed and `HIT` otherwise, with the age of the oldest
+    /// entry served; `None` without lookups.
+    fn status(&self) -> Option<(&'static str, Option<Duration>)> {
+        if self.stale > 0 {
+            Some(("STALE", Some(self.oldest)))
+        } else if self.misses > 0 {
+            Some(("MISS", None))
+        } else if self.hits > 0 {
+            Some(("HIT", Some(self.oldest)))
+        } else {
+            None
+        }
+    }
+}
+
+tokio::task_local! {
+    static LOOKUPS: RefCell<Lookups>;
+}
+
+/// Records a lookup answered with an entry of `age`.
+pub fn hit(age: Duration) {
+    record(|lookups| {
+        lookups.hits += 1;
+        lookups.oldest = lookups.oldest.max(age);
+    });
+}
+
+pub fn miss() {
+    record(|lookups| lookups.misses += 1);
+}
+
+/// Records an expired entry served as a fallback.
+pub fn stale(age: Duration) {
+    record(|lookups| {
+        lookups.stale += 1;
+        lookups.oldest = lookups.oldest.max(age);
+    });
+}
+
+/// Whether the lookups made so far while handling the current
+/// request were all answered from the caches.
+pub fn all_hits() -> bool {
+    LOOKUPS
+        .try_with(|lookups| {
+            matches!(lookups.borrow().status(), Some(("HIT", _)))
+        })
+        .unwrap_or(false)
+}
+
+/// Lookups outside of a request, e.g. by the self-test, are not
+/// recorded.
+fn record(update: impl FnOnce(&mut Lookups)) {
+    let _ =
+        LOOKUPS.try_with(|lookups| update(&mut lookups.borrow_mut()));
+}
+
+/// Sets `X-Cache` and `Age` on responses whose handler looked up
+/// any cache.
+pub async fn middleware(request: Request, next: Next) -> Response {
+    let (lookups, mut response) = LOOKUPS
+        .scope(RefCell::default(), async {
+            let response = next.run(request).await;
+            (LOOKUPS.with(|lookups| *lookups.borrow()), response)
+        })
+        .await;
+
+    if let Some((status, age)) = lookups.status() {
+        let headers = response.headers_mut();
+        headers.insert(
+            CACHE_STATUS_HEADER,
+            HeaderValue::from_static(status),
+        );
+        if let Some(age) = age {
+            headers.insert(
+                header::AGE,
+                HeaderValue::from(age.as_secs()),
+            );
+        }
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn status(
+        lookups: impl FnOnce(),
+    ) -> Option<(&'static str, Option<Duration>)> {
+        LOOKUPS
+            .scope(RefCell::default(), async {
+                lookups();
+                LOOKUPS.with(|lookups| lookups.borrow().status())
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_status_reflects_the_lookups() {
+        let secs = Duration::from_secs;
+        assert_eq!(status(|| ()).await, None);
+        assert_eq!(
+            status(|| {
+                hit(secs(5));
+                hit(secs(42));
+            })
+            .await,
+            Some(("HIT", Some(secs(42))))
+        );
+        assert_eq!(
+            status(|| {
+                hit(secs(5));
+                miss();
+            })
+            .await,
+            Some(("MISS", None))
+        );
+        assert_eq!(
+            status(|| {
+                miss();
+                stale(secs(90));
+            })
+            .await,
+            Some(("STALE", Some(secs(90))))
+        );
+        // Outside of a request.
+        miss();
+    }
+}
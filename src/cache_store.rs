@@ -0,0 +1,249 @@
+use crate::config::Secret;
+use crate::error::{AppError, Result};
+use crate::peers::{PeerSettings, PeerStore};
+use axum::async_trait;
+use redis::{AsyncCommands, aio::ConnectionManager};
+use std::{
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::info;
+
+/// Prefix of every key written by the caches, so a shared Redis can
+/// hold other data too.
+const KEY_PREFIX: &str = "pokedex:cache:";
+
+/// Second-level store behind the in-memory caches, holding the full
+/// set of entries for every replica (Redis) or across restarts
+/// (disk). Values are opaque bytes.
+#[async_trait]
+pub trait CacheStore: Send + Sync {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>>;
+
+    async fn set(
+        &self,
+        key: &str,
+        value: Vec<u8>,
+        ttl: Duration,
+    ) -> Result<()>;
+
+    /// Removes every key starting with `prefix`.
+    async fn clear(&self, prefix: &str) -> Result<()>;
+}
+
+/// The second-level store selected with `CACHE_L2`.
+#[derive(Debug, Clone)]
+pub enum CacheBackend {
+    Disk(PathBuf),
+    /// The URL may carry a password.
+    Redis(Secret),
+    /// The in-memory caches of the other replicas.
+    Peers(PeerSettings),
+}
+
+impl CacheBackend {
+    /// Opens or connects to the store.
+    pub async fn open(&self) -> Result<Arc<dyn CacheStore>> {
+        Ok(match self {
+            CacheBackend::Disk(path) => {
+                info!(path = %path.display(), "Opening disk cache");
+                Arc::new(DiskStore::open(path)?)
+            }
+            CacheBackend::Redis(url) => {
+                info!("Connecting to the Redis cache");
+                Arc::new(RedisStore::connect(&url.0).await?)
+            }
+            CacheBackend::Peers(settings) => {
+                PeerStore::start(settings)?
+            }
+        })
+    }
+}
+
+fn store_error(e: impl std::fmt::Display) -> AppError {
+    AppError::Internal(format!("Cache store error: {}", e))
+}
+
+/// An embedded sled database. Values are prefixed with their expiry
+/// (Unix milliseconds, big-endian) and removed when read after it.
+pub struct DiskStore {
+    db: sled::Db,
+}
+
+impl DiskStore {
+    pub fn open(path: &std::path::Path) -> Result<Self> {
+        Ok(Self {
+            db: sled::open(path).map_err(store_error)?,
+        })
+    }
+}
+
+pub fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[async_trait]
+impl CacheStore for DiskStore {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let key = format!("{}{}", KEY_PREFIX, key);
+        let Some(stored) = self.db.get(&key).map_err(store_error)?
+        else {
+            return Ok(None);
+        };
+        let Some((expiry, value)) = stored.split_first_chunk::<8>()
+        else {
+            return Ok(None);
+        };
+        if u64::from_be_bytes(*expiry) <= unix_millis() {
+            self.db.remove(&key).map_err(store_error)?;
+            return Ok(None);
+        }
+        Ok(Some(value.to_vec()))
+    }
+
+    async fn set(
+        &self,
+        key: &str,
+        value: Vec<u8>,
+        ttl: Duration,
+    ) -> Result<()> {
+        let expiry = unix_millis() + ttl.as_millis() as u64;
+        let mut stored = expiry.to_be_bytes().to_vec();
+        stored.extend_from_slice(&value);
+        self.db
+            .insert(format!("{}{}", KEY_PREFIX, key), stored)
+            .map_err(store_error)?;
+        Ok(())
+    }
+
+    async fn clear(&self, prefix: &str) -> Result<()> {
+        let prefix = format!("{}{}", KEY_PREFIX, prefix);
+        for key in self.db.scan_prefix(&prefix).keys() {
+            self.db
+                .remove(key.map_err(store_error)?)
+                .map_err(store_error)?;
+        }
+        Ok(())
+    }
+}
+
+/// A Redis server shared by the replicas; expiry is left to Redis.
+pub struct RedisStore {
+    connection: ConnectionManager,
+}
+
+impl RedisStore {
+    pub async fn connect(url: &str) -> Result<Self> {
+        let client = redis::Client::open(url).map_err(store_error)?;
+        Ok(Self {
+            connection: ConnectionManager::new(client)
+                .await
+                .map_err(store_error)?,
+        })
+    }
+}
+
+#[async_trait]
+impl CacheStore for RedisStore {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        self.connection
+            .clone()
+            .get(format!("{}{}", KEY_PREFIX, key))
+            .await
+            .map_err(store_error)
+    }
+
+    async fn set(
+        &self,
+        key: &str,
+        value: Vec<u8>,
+        ttl: Duration,
+    ) -> Result<()> {
+        self.connection
+            .clone()
+            .pset_ex(
+                format!("{}{}", KEY_PREFIX, key),
+                value,
+                ttl.as_millis().max(1) as u64,
+            )
+            .await
+            .map_err(store_error)
+    }
+
+    async fn clear(&self, prefix: &str) -> Result<()> {
+        let mut connection = self.connection.clone();
+        let pattern = format!("{}{}*", KEY_PREFIX, prefix);
+        let keys: Vec<String> = {
+            let mut iter: redis::AsyncIter<String> = connection
+                .scan_match(&pattern)
+                .await
+                .map_err(store_error)?;
+            let mut keys = Vec::new();
+            while let Some(key) = iter.next_item().await {
+                keys.push(key);
+            }
+            keys
+        };
+        if !keys.is_empty() {
+            connection
+                .del::<_, ()>(keys)
+                .await
+                .map_err(store_error)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "pokedex-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_disk_store_expires_and_clears() {
+        let dir = temp_dir("disk-store");
+        let store = DiskStore::open(&dir).unwrap();
+        let ttl = Duration::from_secs(60);
+        store
+            .set("species:pikachu", b"25".to_vec(), ttl)
+            .await
+            .unwrap();
+        store
+            .set("species:mew", b"151".to_vec(), ttl)
+            .await
+            .unwrap();
+        store.set("flags:all", b"[]".to_vec(), ttl).await.unwrap();
+        store
+            .set("species:old", b"1".to_vec(), Duration::ZERO)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.get("species:pikachu").await.unwrap(),
+            Some(b"25".to_vec())
+        );
+        assert_eq!(store.get("species:old").await.unwrap(), None);
+
+        store.clear("species:").await.unwrap();
+        assert_eq!(store.get("species:mew").await.unwrap(), None);
+        assert_eq!(
+            store.get("flags:all").await.unwrap(),
+            Some(b"[]".to_vec())
+        );
+        drop(store);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
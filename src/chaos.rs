@@ -0,0 +1,120 @@
+use rand::Rng;
+use std::time::Duration;
+
+/// A fault injected into an upstream call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// Delays the call, which then proceeds normally.
+    Latency,
+    /// Answers with a synthetic `503` instead of calling upstream.
+    Error,
+    /// Fails as if the connection had been dropped.
+    Drop,
+}
+
+impl Fault {
+    fn parse(value: &str) -> Result<Self, String> {
+        match value.trim() {
+            "latency" => Ok(Fault::Latency),
+            "error" => Ok(Fault::Error),
+            "drop" => Ok(Fault::Drop),
+            other => Err(format!(
+                "unknown fault '{}', expected latency, error or drop",
+                other
+            )),
+        }
+    }
+}
+
+/// Fault injection for upstream calls, used to exercise failure
+/// handling in staging. Configured with `CHAOS_RATE` (the percentage
+/// of calls affected), `CHAOS_FAULTS` and `CHAOS_LATENCY_MS`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chaos {
+    rate: f64,
+    faults: Vec<Fault>,
+    latency: Duration,
+}
+
+impl Chaos {
+    /// Returns `None` when `rate` is zero, i.e. chaos is disabled.
+    pub fn new(
+        rate: f64,
+        faults: &str,
+        latency: Duration,
+    ) -> Result<Option<Self>, String> {
+        if !(0.0..=100.0).contains(&rate) {
+            return Err(format!(
+                "rate must be between 0 and 100, got {}",
+                rate
+            ));
+        }
+        if rate == 0.0 {
+            return Ok(None);
+        }
+
+        let faults = faults
+            .split(',')
+            .filter(|fault| !fault.trim().is_empty())
+            .map(Fault::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        if faults.is_empty() {
+            return Err("at least one fault is required".to_string());
+        }
+
+        Ok(Some(Self {
+            rate,
+            faults,
+            latency,
+        }))
+    }
+
+    pub fn latency(&self) -> Duration {
+        self.latency
+    }
+
+    /// Decides whether the next call is affected, and how.
+    pub fn pick(&self) -> Option<Fault> {
+        let mut rng = rand::rng();
+        if rng.random_range(0.0..100.0) >= self.rate {
+            return None;
+        }
+        Some(self.faults[rng.random_range(0..self.faults.len())])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_validates_configuration() {
+        let latency = Duration::from_millis(500);
+        assert_eq!(Chaos::new(0.0, "latency", latency), Ok(None));
+        assert!(Chaos::new(101.0, "latency", latency).is_err());
+        assert!(Chaos::new(10.0, "", latency).is_err());
+        assert!(Chaos::new(10.0, "latency,boom", latency).is_err());
+        assert_eq!(
+            Chaos::new(10.0, "error, drop", latency)
+                .unwrap()
+                .unwrap()
+                .faults,
+            vec![Fault::Error, Fault::Drop]
+        );
+    }
+
+    #[test]
+    fn test_pick_honors_rate() {
+        let always = Chaos::new(100.0, "drop", Duration::ZERO)
+            .unwrap()
+            .unwrap();
+        assert!((0..100).all(|_| always.pick() == Some(Fault::Drop)));
+
+        let never = Chaos {
+            rate: 0.0,
+            faults: vec![Fault::Error],
+            latency: Duration::ZERO,
+        };
+        assert!((0..100).all(|_| never.pick().is_none()));
+    }
+}
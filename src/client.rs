@@ -0,0 +1,306 @@
+//! Typed client for the Pokedex API, enabled with the `client`
+//! feature.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), pokedex_rs::client::ClientError> {
+//! use pokedex_rs::client::PokedexClient;
+//!
+//! let client = PokedexClient::new("http://localhost:5000")?;
+//! let pikachu = client.get_pokemon("pikachu").await?;
+//! println!("{:?}", pikachu.description);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::models::{
+    BatchRequest, BatchResponse, Page, Pokemon, SpeciesSummary,
+};
+use crate::webhook::{
+    DEFAULT_TOLERANCE, SignatureError, SigningKeys,
+};
+use reqwest::{Client, RequestBuilder, Url};
+use serde::{Deserialize, de::DeserializeOwned};
+use std::{
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Debug)]
+pub enum ClientError {
+    /// The base URL cannot be used to build request URLs.
+    InvalidUrl(String),
+    /// The request could not be sent or its body decoded.
+    Http(reqwest::Error),
+    /// The API answered with an error status.
+    Api { status: u16, message: String },
+    /// A webhook callback failed signature verification.
+    Signature(SignatureError),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::InvalidUrl(url) => {
+                write!(f, "Invalid base URL: {}", url)
+            }
+            ClientError::Http(e) => write!(f, "HTTP error: {}", e),
+            ClientError::Api { status, message } => {
+                write!(f, "API error {}: {}", status, message)
+            }
+            ClientError::Signature(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(e: reqwest::Error) -> Self {
+        ClientError::Http(e)
+    }
+}
+
+impl From<SignatureError> for ClientError {
+    fn from(e: SignatureError) -> Self {
+        ClientError::Signature(e)
+    }
+}
+
+/// Verifies a webhook callback received from the API: `signature` is
+/// its `X-Pokedex-Signature` header and `body` the raw request body,
+/// before any JSON parsing. During a key rotation, pass both keys.
+///
+/// ```
+/// use pokedex_rs::client::verify_webhook;
+/// use pokedex_rs::webhook::SigningKeys;
+///
+/// let keys = SigningKeys::parse("s3cret");
+/// let now = std::time::SystemTime::now()
+///     .duration_since(std::time::UNIX_EPOCH)
+///     .unwrap()
+///     .as_secs();
+/// let signature = keys.sign(b"{}", now);
+/// assert!(verify_webhook(&keys, &signature, b"{}").is_ok());
+/// ```
+pub fn verify_webhook(
+    keys: &SigningKeys,
+    signature: &str,
+    body: &[u8],
+) -> Result<(), ClientError> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    Ok(keys.verify(signature, body, now, DEFAULT_TOLERANCE)?)
+}
+
+/// Error body returned by the API.
+#[derive(Deserialize)]
+struct ErrorBody {
+    error: String,
+}
+
+#[derive(Clone)]
+pub struct PokedexClient {
+    http: Client,
+    base_url: Url,
+}
+
+impl PokedexClient {
+    pub fn new(base_url: &str) -> Result<Self, ClientError> {
+        Self::with_client(Client::new(), base_url)
+    }
+
+    /// Uses a preconfigured `reqwest::Client`, e.g. with custom
+    /// timeouts or default headers.
+    pub fn with_client(
+        http: Client,
+        base_url: &str,
+    ) -> Result<Self, ClientError> {
+        let base_url = Url::parse(base_url)
+            .ok()
+            .filter(|url| !url.cannot_be_a_base())
+            .ok_or_else(|| {
+                ClientError::InvalidUrl(base_url.to_string())
+            })?;
+        Ok(Self { http, base_url })
+    }
+
+    /// `GET /pokemon/{name}`
+    pub async fn get_pokemon(
+        &self,
+        name: &str,
+    ) -> Result<Pokemon, ClientError> {
+        let url = self.url(&["pokemon", name]);
+        self.send(self.http.get(url)).await
+    }
+
+    /// `GET /pokemon/translated/{name}`
+    pub async fn get_translated(
+        &self,
+        name: &str,
+    ) -> Result<Pokemon, ClientError> {
+        let url = self.url(&["pokemon", "translated", name]);
+        self.send(self.http.get(url)).await
+    }
+
+    /// `GET /pokemon/search?q={query}&limit={limit}`
+    pub async fn search(
+        &self,
+        query: &str,
+        limit: usize,
+    ) -> Result<Page<SpeciesSummary>, ClientError> {
+        let url = self.url(&["pokemon", "search"]);
+        let limit = limit.to_string();
+        let request = self
+            .http
+            .get(url)
+            .query(&[("q", query), ("limit", limit.as_str())]);
+        self.send(request).await
+    }
+
+    /// `POST /pokemon/batch`
+    pub async fn batch(
+        &self,
+        request: &BatchRequest,
+    ) -> Result<BatchResponse, ClientError> {
+        let url = self.url(&["pokemon", "batch"]);
+        self.send(self.http.post(url).json(request)).await
+    }
+
+    /// Appends percent-encoded `segments` to the base URL.
+    fn url(&self, segments: &[&str]) -> Url {
+        let mut url = self.base_url.clone();
+        url.path_segments_mut()
+            .expect("base URL was validated in the constructor")
+            .pop_if_empty()
+            .extend(segments);
+        url
+    }
+
+    async fn send<T: DeserializeOwned>(
+        &self,
+        request: RequestBuilder,
+    ) -> Result<T, ClientError> {
+        let response = request.send().await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response.json().await?);
+        }
+
+        let body = response.text().await.unwrap_or_default();
+        let message = serde_json::from_str::<ErrorBody>(&body)
+            .map(|body| body.error)
+            .unwrap_or(body);
+        Err(ClientError::Api {
+            status: status.as_u16(),
+            message,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_json, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn pikachu() -> serde_json::Value {
+        serde_json::json!({
+            "name": "pikachu",
+            "description": "It keeps its tail raised.",
+            "habitat": "forest",
+            "is_legendary": false,
+            "is_mythical": false,
+            "is_baby": false
+        })
+    }
+
+    #[tokio::test]
+    async fn test_get_pokemon() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/pokemon/mr.%20mime"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(pikachu()),
+            )
+            .mount(&server)
+            .await;
+
+        let client =
+            PokedexClient::new(&format!("{}/api/", server.uri()))
+                .unwrap();
+        let pokemon = client.get_pokemon("mr. mime").await.unwrap();
+        assert_eq!(pokemon.name, "pikachu");
+        assert_eq!(pokemon.habitat.as_deref(), Some("forest"));
+    }
+
+    #[tokio::test]
+    async fn test_search() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/pokemon/search"))
+            .and(query_param("q", "pika"))
+            .and(query_param("limit", "5"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({
+                    "count": 1,
+                    "results": [{"id": 25, "name": "pikachu"}],
+                    "next_cursor": null
+                }),
+            ))
+            .mount(&server)
+            .await;
+
+        let client = PokedexClient::new(&server.uri()).unwrap();
+        let page = client.search("pika", 5).await.unwrap();
+        assert_eq!(page.count, 1);
+        assert_eq!(page.results[0].name, "pikachu");
+    }
+
+    #[tokio::test]
+    async fn test_batch_and_api_errors() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/pokemon/batch"))
+            .and(body_json(serde_json::json!({
+                "names": ["pikachu", "missingno"],
+                "translated": false,
+                "include": ""
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({
+                    "pokemon": [pikachu()],
+                    "errors": [{"name": "missingno", "error": "Not found"}]
+                }),
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/pokemon/translated/missingno"))
+            .respond_with(ResponseTemplate::new(404).set_body_json(
+                serde_json::json!({"error": "Pokemon 'missingno' not found"}),
+            ))
+            .mount(&server)
+            .await;
+
+        let client = PokedexClient::new(&server.uri()).unwrap();
+        let response = client
+            .batch(&BatchRequest {
+                names: vec!["pikachu".into(), "missingno".into()],
+                ..BatchRequest::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(response.pokemon.len(), 1);
+        assert_eq!(response.errors[0].name, "missingno");
+
+        match client.get_translated("missingno").await {
+            Err(ClientError::Api { status, message }) => {
+                assert_eq!(status, 404);
+                assert_eq!(message, "Pokemon 'missingno' not found");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
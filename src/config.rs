@@ -1,45 +1,934 @@
-use std::time::Duration;
+use std::{
+    fmt::{self, Debug},
+    net::SocketAddrV4,
+    path::PathBuf,
+    str::FromStr,
+    time::Duration,
+};
+
+use crate::alerting::{AlertSettings, AlertSinkConfig};
+use crate::auth::{ApiKeys, AuthMode};
+use crate::cache::{Eviction, Policy, Promotion};
+use crate::cache_store::CacheBackend;
+use crate::chaos::Chaos;
+use crate::dns::DnsOverrides;
+use crate::events::SinkConfig;
+use crate::experiments::Experiments;
+use crate::field_case::FieldCase;
+use crate::fixtures::FixtureMode;
+use crate::flags::Feature;
+use crate::forwarded::{PublicUrlConfig, TrustedProxy};
+use crate::http::{ProxyConfig, TlsConfig};
+use crate::invalidation::BusConfig;
+use crate::jobs::RetryPolicy;
+use crate::jwt::JwtConfig;
+use crate::listener::{ListenAddr, ListenerSpec, Role};
+use crate::logging::{LogSettings, Rotation};
+use crate::mailer::MailConfig;
+use crate::output_filter::Denylist;
+use crate::peers::{Discovery, PeerSettings};
+use crate::runtime::RuntimeSettings;
+use crate::scheduler;
+use crate::sources::SourceKind;
+use crate::text::Normalization;
+use crate::translation::YodaRule;
+use crate::upstreams::BreakerSettings;
+use crate::{mt, tts};
+use cron::Schedule;
+use pokedex_rs::webhook::SigningKeys;
 
 #[derive(Debug, Clone)]
 pub struct Config {
-    pub host: String,
-    pub port: u16,
+    pub listeners: Vec<ListenerSpec>,
     pub pokeapi_base_url: String,
     pub translation_api_base_url: String,
     pub http_timeout: Duration,
+    pub upstream_proxy: ProxyConfig,
+    pub upstream_tls: TlsConfig,
+    pub dns_overrides: DnsOverrides,
+    /// Zero disables the resolver cache.
+    pub dns_cache_ttl: Duration,
     pub request_timeout: u64,
+    pub idempotency_ttl: Duration,
+    pub idempotency_max_entries: usize,
+    pub cache_ttl: Duration,
+    /// How long expired PokeAPI data is kept to answer while PokeAPI
+    /// is unavailable; zero disables the fallback.
+    pub stale_cache_ttl: Duration,
+    /// Second-level store of the PokeAPI caches, from `CACHE_L2`.
+    pub cache_l2: Option<CacheBackend>,
+    pub cache_l2_promotion: Promotion,
+    /// Where species are looked up, in order, from
+    /// `SPECIES_SOURCES`.
+    pub species_sources: Vec<SourceKind>,
+    /// How long species are kept in storage, forever when zero.
+    pub species_storage_ttl: Duration,
+    /// PokeAPI species documents for the `fixtures` source.
+    pub species_fixtures_dir: PathBuf,
+    /// Where cleared caches are announced to the other replicas,
+    /// from `INVALIDATION_BUS`.
+    pub invalidation_bus: Option<BusConfig>,
+    /// Where the caches are saved on shutdown and loaded on startup.
+    pub cache_snapshot_file: Option<PathBuf>,
+    /// How every in-memory cache makes room.
+    pub cache_eviction: Eviction,
+    /// How often the species names that unknown names are rejected
+    /// against are refreshed; zero disables the check.
+    pub name_guard_refresh: Duration,
+    /// Approximate bytes the caches may hold before entries are
+    /// evicted; zero disables the check.
+    pub cache_memory_high_water: usize,
+    pub cache_memory_check: Duration,
+    /// Database keeping quizzes, favorites and jobs across restarts;
+    /// they are kept in memory when unset.
+    pub storage_path: Option<PathBuf>,
+    pub quiz_ttl: Duration,
+    /// Seeds the quiz draws, for reproducible demos and tests.
+    pub random_seed: Option<u64>,
+    /// Translations a bulk translation job may make per minute.
+    pub job_translations_per_minute: u32,
+    /// How long jobs and their results are kept.
+    pub job_ttl: Duration,
+    pub job_retry: RetryPolicy,
+    pub api_keys: ApiKeys,
+    /// Set in the `jwt` auth mode.
+    pub jwt: Option<JwtConfig>,
+    /// Whether the admin routes require `Role`s.
+    pub admin_rbac: bool,
+    /// How absolute links find the URL the service is reached at.
+    pub public_url: PublicUrlConfig,
+    /// Key naming of JSON responses without `?case=`.
+    pub response_case: FieldCase,
+    /// Fixes applied to descriptions, none by default.
+    pub text_normalization: Normalization,
+    /// Languages a description falls back to, in order, when a
+    /// species has no English one.
+    pub description_fallback_languages: Vec<String>,
+    /// Masks matches of a pattern in translations.
+    pub translation_denylist: Option<Denylist>,
+    pub translation_strip_urls: bool,
+    /// Translates mythical Pokemon in the legendaries' style.
+    pub translation_yoda: YodaRule,
+    pub translation_mythical_as_legendary: bool,
+    /// Honors `X-Pokedex-Translator` and `?translator=`, which force
+    /// a translation style for debugging and experiments.
+    pub translator_overrides: bool,
+    /// Translation styles and description variants tried on a share
+    /// of the clients.
+    pub experiments: Experiments,
+    pub max_body_bytes: usize,
+    pub batch_max_names: usize,
+    pub upstream_max_response_bytes: usize,
+    pub circuit_breaker: BreakerSettings,
+    pub debug_upstream_overrides: bool,
+    pub debug_random_seed: bool,
+    pub fixture_mode: Option<FixtureMode>,
+    pub fixtures_dir: PathBuf,
+    pub chaos: Option<Chaos>,
+    /// Percentage of upstream payloads kept to detect schema drift;
+    /// zero disables sampling.
+    pub schema_sample_percent: f64,
+    pub schema_check: Duration,
+    /// Alternate PokeAPI a share of the calls is mirrored to.
+    pub shadow_pokeapi_base_url: Option<String>,
+    /// Percentage of PokeAPI calls mirrored to the shadow.
+    pub shadow_percent: f64,
+    /// Where domain events are published, from `EVENT_SINKS`.
+    pub event_sinks: Vec<SinkConfig>,
+    /// Where the per-Pokemon request counters are saved, if anywhere.
+    pub usage_stats_file: Option<PathBuf>,
+    pub usage_stats_save: Duration,
+    pub jobs: JobSchedules,
+    /// Where the `health_report` job emails its report.
+    pub report_email: Option<MailConfig>,
+    pub alerts: AlertSettings,
+    pub disabled_features: Vec<Feature>,
+    pub feature_flags_file: Option<PathBuf>,
+    pub feature_flags_reload: Duration,
+    pub proxy_allowed_resources: Vec<String>,
+    pub proxy_cache_max_entries: usize,
+    pub translate_max_chars: usize,
+    pub translate_rate_limit: u32,
+    pub rate_limit: u32,
+    pub audit_log_file: Option<PathBuf>,
+    pub tenants_file: Option<PathBuf>,
+    pub mt_provider: Option<mt::Provider>,
+    pub mt_api_url: String,
+    pub mt_api_key: Option<Secret>,
+    pub tts_provider: Option<tts::Provider>,
+    pub tts_api_url: String,
+    pub tts_api_key: Option<Secret>,
+    pub tts_voice: Option<String>,
+    pub runtime: RuntimeSettings,
+    pub log: LogSettings,
+    /// Where `--daemonize` writes the server's pid.
+    pub pid_file: Option<PathBuf>,
 }
 
 impl Config {
     pub fn from_env() -> Self {
+        let mt_provider =
+            mt::Provider::parse(&env_or("MT_PROVIDER", "none"))
+                .unwrap_or_else(|e| {
+                    panic!("MT_PROVIDER is invalid: {}", e)
+                });
+        let tts_provider =
+            tts::Provider::parse(&env_or("TTS_PROVIDER", "none"))
+                .unwrap_or_else(|e| {
+                    panic!("TTS_PROVIDER is invalid: {}", e)
+                });
+        let auth_mode =
+            AuthMode::parse(&env_or("AUTH_MODE", "api_key"))
+                .unwrap_or_else(|e| {
+                    panic!("AUTH_MODE is invalid: {}", e)
+                });
+        let jwt = (auth_mode == AuthMode::Jwt).then(jwt_config);
+
         Self {
-            host: std::env::var("HOST")
-                .unwrap_or_else(|_| "0.0.0.0".to_string()),
-            port: std::env::var("PORT")
-                .unwrap_or_else(|_| "5000".to_string())
-                .parse()
-                .expect("PORT must be a valid u16"),
-            pokeapi_base_url: std::env::var("POKEAPI_BASE_URL")
-                .unwrap_or_else(|_| {
-                    "https://pokeapi.co/api/v2".to_string()
-                }),
-            translation_api_base_url: std::env::var(
+            listeners: listeners(),
+            pokeapi_base_url: env_or(
+                "POKEAPI_BASE_URL",
+                "https://pokeapi.co/api/v2",
+            ),
+            translation_api_base_url: env_or(
                 "TRANSLATION_API_BASE_URL",
+                "https://api.funtranslations.com/translate",
+            ),
+            http_timeout: env_secs("HTTP_TIMEOUT_SECS", "10"),
+            upstream_proxy: proxy_config(),
+            upstream_tls: tls_config(),
+            dns_overrides: DnsOverrides::parse(&env_or(
+                "DNS_OVERRIDES",
+                "",
+            ))
+            .unwrap_or_else(|e| panic!("DNS_OVERRIDES is invalid: {}", e)),
+            dns_cache_ttl: env_secs("DNS_CACHE_TTL_SECS", "0"),
+            request_timeout: env_parse("REQUEST_TIMEOUT_SECS", "30"),
+            idempotency_ttl: env_secs("IDEMPOTENCY_TTL_SECS", "3600"),
+            idempotency_max_entries: env_parse(
+                "IDEMPOTENCY_MAX_ENTRIES",
+                "10000",
+            ),
+            cache_ttl: env_secs("CACHE_TTL_SECS", "3600"),
+            stale_cache_ttl: env_secs("STALE_CACHE_TTL_SECS", "86400"),
+            cache_l2: cache_l2(),
+            cache_l2_promotion: Promotion::parse(&env_or(
+                "CACHE_L2_PROMOTION",
+                "always",
+            ))
+            .unwrap_or_else(|e| {
+                panic!("CACHE_L2_PROMOTION is invalid: {}", e)
+            }),
+            species_sources: SourceKind::parse_list(&env_or(
+                "SPECIES_SOURCES",
+                "cache,pokeapi",
+            ))
+            .unwrap_or_else(|e| {
+                panic!("SPECIES_SOURCES is invalid: {}", e)
+            }),
+            species_storage_ttl: env_secs(
+                "SPECIES_STORAGE_TTL_SECS",
+                "0",
+            ),
+            species_fixtures_dir: PathBuf::from(env_or(
+                "SPECIES_FIXTURES_DIR",
+                "fixtures/species",
+            )),
+            invalidation_bus: invalidation_bus(),
+            cache_snapshot_file: std::env::var_os("CACHE_SNAPSHOT_FILE")
+                .map(PathBuf::from),
+            cache_eviction: Eviction {
+                policy: Policy::parse(&env_or("CACHE_EVICTION", "ttl"))
+                    .unwrap_or_else(|e| {
+                        panic!("CACHE_EVICTION is invalid: {}", e)
+                    }),
+                max_entries: Some(env_parse("CACHE_MAX_ENTRIES", "0"))
+                    .filter(|max_entries| *max_entries > 0),
+            },
+            name_guard_refresh: env_secs(
+                "NAME_GUARD_REFRESH_SECS",
+                "3600",
+            ),
+            cache_memory_high_water: env_parse::<usize>(
+                "CACHE_MEMORY_HIGH_WATER_MB",
+                "0",
+            ) * 1024
+                * 1024,
+            cache_memory_check: Some(env_secs(
+                "CACHE_MEMORY_CHECK_SECS",
+                "30",
+            ))
+            .filter(|interval| !interval.is_zero())
+            .expect("CACHE_MEMORY_CHECK_SECS must be positive"),
+            storage_path: std::env::var_os("STORAGE_PATH")
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from),
+            quiz_ttl: env_secs("QUIZ_TTL_SECS", "600"),
+            random_seed: env_nonempty("RANDOM_SEED").map(|seed| {
+                seed.parse().unwrap_or_else(|e| {
+                    panic!("RANDOM_SEED must be a valid u64: {:?}", e)
+                })
+            }),
+            job_translations_per_minute: Some(env_parse(
+                "JOB_TRANSLATIONS_PER_MINUTE",
+                "5",
+            ))
+            .filter(|rate| *rate > 0)
+            .expect("JOB_TRANSLATIONS_PER_MINUTE must be positive"),
+            job_ttl: env_secs("JOB_TTL_SECS", "86400"),
+            job_retry: RetryPolicy {
+                max_attempts: Some(env_parse("JOB_MAX_ATTEMPTS", "3"))
+                    .filter(|attempts| *attempts > 0)
+                    .expect("JOB_MAX_ATTEMPTS must be positive"),
+                backoff: env_secs("JOB_RETRY_BACKOFF_SECS", "30"),
+            },
+            api_keys: ApiKeys::parse(&env_or("API_KEYS", ""))
+                .unwrap_or_else(|e| {
+                    panic!("API_KEYS is invalid: {}", e)
+                }),
+            jwt,
+            admin_rbac: env_parse("ADMIN_RBAC", "false"),
+            public_url: public_url(),
+            response_case: FieldCase::parse(&env_or(
+                "RESPONSE_CASE",
+                "snake",
+            ))
+            .unwrap_or_else(|e| {
+                panic!("RESPONSE_CASE is invalid: {}", e)
+            }),
+            text_normalization: Normalization::parse(&env_or(
+                "TEXT_NORMALIZATION",
+                "",
+            ))
+            .unwrap_or_else(|e| {
+                panic!("TEXT_NORMALIZATION is invalid: {}", e)
+            }),
+            description_fallback_languages: env_or(
+                "DESCRIPTION_FALLBACK_LANGUAGES",
+                "",
             )
-            .unwrap_or_else(|_| {
-                "https://api.funtranslations.com/translate"
-                    .to_string()
-            }),
-            http_timeout: Duration::from_secs(
-                std::env::var("HTTP_TIMEOUT_SECS")
-                    .unwrap_or_else(|_| "10".to_string())
-                    .parse()
-                    .expect("HTTP_TIMEOUT_SECS must be a valid u64"),
-            ),
-            request_timeout: std::env::var("REQUEST_TIMEOUT_SECS")
-                .unwrap_or_else(|_| "30".to_string())
-                .parse()
-                .expect("REQUEST_TIMEOUT_SECS must be a valid u64"),
+            .split(',')
+            .map(|language| language.trim().to_lowercase())
+            .filter(|language| !language.is_empty())
+            .collect(),
+            translation_denylist: env_nonempty("TRANSLATION_DENYLIST")
+                .map(|pattern| {
+                    Denylist::new(&pattern).unwrap_or_else(|e| {
+                        panic!("TRANSLATION_DENYLIST is invalid: {}", e)
+                    })
+                }),
+            translation_strip_urls: env_parse(
+                "TRANSLATION_STRIP_URLS",
+                "false",
+            ),
+            translation_yoda: YodaRule::parse(
+                &env_or("TRANSLATION_YODA_HABITATS", "cave"),
+                &env_or("TRANSLATION_YODA_TYPES", ""),
+            ),
+            translation_mythical_as_legendary: env_parse(
+                "TRANSLATION_MYTHICAL_AS_LEGENDARY",
+                "false",
+            ),
+            translator_overrides: env_parse(
+                "TRANSLATOR_OVERRIDES",
+                "false",
+            ),
+            experiments: Experiments::parse(&env_or("EXPERIMENTS", ""))
+                .unwrap_or_else(|e| {
+                    panic!("EXPERIMENTS is invalid: {}", e)
+                }),
+            max_body_bytes: env_parse("MAX_BODY_BYTES", "65536"),
+            batch_max_names: env_parse("BATCH_MAX_NAMES", "50"),
+            upstream_max_response_bytes: env_parse(
+                "UPSTREAM_MAX_RESPONSE_BYTES",
+                "8388608",
+            ),
+            circuit_breaker: BreakerSettings {
+                failures: env_parse("CIRCUIT_BREAKER_FAILURES", "5"),
+                cooldown: env_secs("CIRCUIT_BREAKER_COOLDOWN_SECS", "30"),
+            },
+            debug_upstream_overrides: env_parse(
+                "DEBUG_UPSTREAM_OVERRIDES",
+                "false",
+            ),
+            debug_random_seed: env_parse("DEBUG_RANDOM_SEED", "false"),
+            fixture_mode: FixtureMode::parse(&env_or(
+                "UPSTREAM_MODE",
+                "live",
+            ))
+            .unwrap_or_else(|e| {
+                panic!("UPSTREAM_MODE is invalid: {}", e)
+            }),
+            chaos: Chaos::new(
+                env_parse("CHAOS_RATE", "0"),
+                &env_or("CHAOS_FAULTS", "latency,error,drop"),
+                Duration::from_millis(env_parse(
+                    "CHAOS_LATENCY_MS",
+                    "1000",
+                )),
+            )
+            .unwrap_or_else(|e| {
+                panic!("CHAOS configuration is invalid: {}", e)
+            }),
+            schema_sample_percent: Some(env_parse(
+                "SCHEMA_SAMPLE_PERCENT",
+                "0",
+            ))
+            .filter(|percent| (0.0..=100.0).contains(percent))
+            .expect("SCHEMA_SAMPLE_PERCENT must be between 0 and 100"),
+            schema_check: Some(env_secs("SCHEMA_CHECK_SECS", "3600"))
+                .filter(|interval| !interval.is_zero())
+                .expect("SCHEMA_CHECK_SECS must be positive"),
+            shadow_pokeapi_base_url: env_nonempty(
+                "SHADOW_POKEAPI_BASE_URL",
+            ),
+            shadow_percent: Some(env_parse("SHADOW_PERCENT", "10"))
+                .filter(|percent| (0.0..=100.0).contains(percent))
+                .expect("SHADOW_PERCENT must be between 0 and 100"),
+            event_sinks: event_sinks(),
+            usage_stats_file: std::env::var_os("USAGE_STATS_FILE")
+                .map(PathBuf::from),
+            usage_stats_save: Some(env_secs(
+                "USAGE_STATS_SAVE_SECS",
+                "300",
+            ))
+            .filter(|interval| !interval.is_zero())
+            .expect("USAGE_STATS_SAVE_SECS must be positive"),
+            jobs: job_schedules(),
+            report_email: report_email(),
+            alerts: alert_settings(),
+            fixtures_dir: PathBuf::from(env_or(
+                "FIXTURES_DIR",
+                "fixtures",
+            )),
+            disabled_features: Feature::parse_list(&env_or(
+                "DISABLED_FEATURES",
+                "",
+            ))
+            .unwrap_or_else(|e| {
+                panic!("DISABLED_FEATURES is invalid: {}", e)
+            }),
+            feature_flags_file: std::env::var_os(
+                "FEATURE_FLAGS_FILE",
+            )
+            .map(PathBuf::from),
+            feature_flags_reload: env_secs(
+                "FEATURE_FLAGS_RELOAD_SECS",
+                "5",
+            ),
+            proxy_allowed_resources: env_or(
+                "PROXY_ALLOWED_RESOURCES",
+                "ability,berry,egg-group,evolution-chain,generation,item,move,nature,region,version",
+            )
+            .split(',')
+            .map(|resource| resource.trim().to_string())
+            .filter(|resource| !resource.is_empty())
+            .collect(),
+            proxy_cache_max_entries: env_parse(
+                "PROXY_CACHE_MAX_ENTRIES",
+                "256",
+            ),
+            translate_max_chars: env_parse("TRANSLATE_MAX_CHARS", "1000"),
+            translate_rate_limit: env_parse("TRANSLATE_RATE_LIMIT", "10"),
+            rate_limit: env_parse("RATE_LIMIT", "0"),
+            audit_log_file: std::env::var_os("AUDIT_LOG_FILE")
+                .map(PathBuf::from),
+            tenants_file: std::env::var_os("TENANTS_FILE")
+                .map(PathBuf::from),
+            mt_provider,
+            mt_api_url: env_or(
+                "MT_API_URL",
+                mt_provider.map_or("", mt::Provider::default_url),
+            ),
+            mt_api_key: env_secret("MT_API_KEY"),
+            tts_provider,
+            tts_api_url: env_or(
+                "TTS_API_URL",
+                tts_provider.map_or("", tts::Provider::default_url),
+            ),
+            tts_api_key: env_secret("TTS_API_KEY"),
+            tts_voice: env_nonempty("TTS_VOICE"),
+            runtime: runtime_settings(),
+            log: LogSettings {
+                file: env_nonempty("LOG_FILE").map(PathBuf::from),
+                rotation: Rotation::parse(&env_or(
+                    "LOG_ROTATION",
+                    "daily",
+                ))
+                .unwrap_or_else(|e| {
+                    panic!("LOG_ROTATION is invalid: {}", e)
+                }),
+                max_files: env_parse("LOG_MAX_FILES", "7"),
+            },
+            pid_file: env_nonempty("PID_FILE").map(PathBuf::from),
+        }
+    }
+}
+
+/// A credential, redacted when the configuration is logged.
+#[derive(Clone)]
+pub struct Secret(pub String);
+
+impl Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(..)")
+    }
+}
+
+/// The second-level cache store: `none`, `disk` (at
+/// `CACHE_L2_PATH`), `redis` (at `REDIS_URL`) or `peers`.
+fn cache_l2() -> Option<CacheBackend> {
+    match env_or("CACHE_L2", "none").trim().to_lowercase().as_str() {
+        "" | "none" => None,
+        "disk" => Some(CacheBackend::Disk(PathBuf::from(env_or(
+            "CACHE_L2_PATH",
+            "cache",
+        )))),
+        "redis" => Some(CacheBackend::Redis(Secret(env_or(
+            "REDIS_URL",
+            "redis://127.0.0.1:6379",
+        )))),
+        "peers" => Some(CacheBackend::Peers(peer_settings())),
+        other => panic!(
+            "CACHE_L2 is invalid: unknown store '{}', expected none, disk, redis or peers",
+            other
+        ),
+    }
+}
+
+/// The cache invalidation bus: `none`, `redis` (on
+/// `INVALIDATION_CHANNEL` at `REDIS_URL`) or `multicast` (to
+/// `INVALIDATION_MULTICAST_ADDR`, signed with
+/// `INVALIDATION_SECRETS`).
+fn invalidation_bus() -> Option<BusConfig> {
+    match env_or("INVALIDATION_BUS", "none")
+        .trim()
+        .to_lowercase()
+        .as_str()
+    {
+        "" | "none" => None,
+        "redis" => Some(BusConfig::Redis {
+            url: Secret(env_or(
+                "REDIS_URL",
+                "redis://127.0.0.1:6379",
+            )),
+            channel: env_or(
+                "INVALIDATION_CHANNEL",
+                "pokedex:invalidations",
+            ),
+        }),
+        "multicast" => {
+            let group: SocketAddrV4 = env_parse(
+                "INVALIDATION_MULTICAST_ADDR",
+                "239.255.70.1:7070",
+            );
+            if !group.ip().is_multicast() {
+                panic!(
+                    "INVALIDATION_MULTICAST_ADDR is invalid: {} is not a multicast address",
+                    group.ip()
+                );
+            }
+            let keys = SigningKeys::parse(&env_or(
+                "INVALIDATION_SECRETS",
+                "",
+            ));
+            if keys.is_empty() {
+                panic!(
+                    "INVALIDATION_BUS=multicast requires INVALIDATION_SECRETS"
+                );
+            }
+            Some(BusConfig::Multicast { group, keys })
         }
+        other => panic!(
+            "INVALIDATION_BUS is invalid: unknown bus '{}', expected none, redis or multicast",
+            other
+        ),
     }
 }
+
+/// The replicas partitioning the caches, from `PEERS` or
+/// `PEERS_DNS`, with this one at `PEER_SELF`.
+fn peer_settings() -> PeerSettings {
+    let discovery =
+        match (env_nonempty("PEERS"), env_nonempty("PEERS_DNS")) {
+            (Some(peers), None) => Discovery::Static(
+                peers
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|peer| !peer.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+            ),
+            (None, Some(host)) => Discovery::dns(&host)
+                .unwrap_or_else(|e| {
+                    panic!("PEERS_DNS is invalid: {}", e)
+                }),
+            _ => panic!(
+                "CACHE_L2=peers requires one of PEERS or PEERS_DNS"
+            ),
+        };
+    PeerSettings {
+        discovery,
+        self_url: env_nonempty("PEER_SELF").unwrap_or_else(|| {
+            panic!("CACHE_L2=peers requires PEER_SELF")
+        }),
+        secret: env_secret("PEER_SECRET").unwrap_or_else(|| {
+            panic!("CACHE_L2=peers requires PEER_SECRET")
+        }),
+        refresh: env_secs("PEERS_DNS_REFRESH_SECS", "30"),
+        timeout: Duration::from_millis(env_parse(
+            "PEER_TIMEOUT_MS",
+            "500",
+        )),
+        max_bytes: env_parse("PEER_SHARD_MAX_BYTES", "67108864"),
+    }
+}
+
+/// The comma-separated `EVENT_SINKS`: `log`, `webhook`, `kafka` or
+/// `nats`, each configured by its own variables.
+fn event_sinks() -> Vec<SinkConfig> {
+    env_or("EVENT_SINKS", "")
+        .split(',')
+        .map(|sink| sink.trim().to_lowercase())
+        .filter(|sink| !sink.is_empty())
+        .map(|sink| match sink.as_str() {
+            "log" => SinkConfig::Log,
+            "webhook" => SinkConfig::Webhook {
+                url: env_nonempty("EVENT_WEBHOOK_URL").unwrap_or_else(
+                    || {
+                        panic!(
+                            "EVENT_WEBHOOK_URL is required for the webhook event sink"
+                        )
+                    },
+                ),
+                keys: SigningKeys::parse(&env_or("WEBHOOK_SECRETS", "")),
+            },
+            "kafka" => SinkConfig::Kafka {
+                brokers: env_or("KAFKA_BROKERS", "localhost:9092"),
+                topic: env_or("KAFKA_TOPIC", "pokedex-events"),
+            },
+            "nats" => SinkConfig::Nats {
+                url: Secret(env_or("NATS_URL", "nats://127.0.0.1:4222")),
+                subject: env_or("NATS_SUBJECT", "pokedex.events"),
+            },
+            other => panic!(
+                "EVENT_SINKS is invalid: unknown sink '{}', expected log, webhook, kafka or nats",
+                other
+            ),
+        })
+        .collect()
+}
+
+/// Cron schedules of the periodic jobs, from the `*_CRON` variables;
+/// jobs without one do not run.
+#[derive(Debug, Clone, Default)]
+pub struct JobSchedules {
+    /// Refetches the most requested Pokemon before they expire.
+    pub cache_refresh: Option<Schedule>,
+    /// Saves the cache snapshot and usage stats.
+    pub snapshot_sync: Option<Schedule>,
+    /// Publishes the most requested Pokemon as an event.
+    pub stats_rollup: Option<Schedule>,
+    /// Emails the health report.
+    pub health_report: Option<Schedule>,
+}
+
+fn job_schedules() -> JobSchedules {
+    let schedule = |var: &str| {
+        env_nonempty(var).map(|expression| {
+            scheduler::parse(&expression).unwrap_or_else(|e| {
+                panic!("{} is invalid: {}", var, e)
+            })
+        })
+    };
+    let jobs = JobSchedules {
+        cache_refresh: schedule("CACHE_REFRESH_CRON"),
+        snapshot_sync: schedule("SNAPSHOT_SYNC_CRON"),
+        stats_rollup: schedule("STATS_ROLLUP_CRON"),
+        health_report: schedule("HEALTH_REPORT_CRON"),
+    };
+    if jobs.snapshot_sync.is_some()
+        && env_nonempty("CACHE_SNAPSHOT_FILE").is_none()
+        && env_nonempty("USAGE_STATS_FILE").is_none()
+    {
+        panic!(
+            "SNAPSHOT_SYNC_CRON requires CACHE_SNAPSHOT_FILE or USAGE_STATS_FILE"
+        );
+    }
+    if jobs.health_report.is_some()
+        && env_nonempty("SMTP_URL").is_none()
+    {
+        panic!("HEALTH_REPORT_CRON requires SMTP_URL");
+    }
+    jobs
+}
+
+/// The report email settings when `SMTP_URL` is set;
+/// `REPORT_EMAIL_TO` is then required.
+fn report_email() -> Option<MailConfig> {
+    let smtp_url = env_secret("SMTP_URL")?;
+    let to = env_nonempty("REPORT_EMAIL_TO").unwrap_or_else(|| {
+        panic!("REPORT_EMAIL_TO is required with SMTP_URL")
+    });
+    Some(MailConfig {
+        smtp_url,
+        from: env_or(
+            "REPORT_EMAIL_FROM",
+            "Pokedex <pokedex@localhost>",
+        )
+        .parse()
+        .unwrap_or_else(|e| {
+            panic!("REPORT_EMAIL_FROM is invalid: {}", e)
+        }),
+        to: MailConfig::parse_recipients(&to).unwrap_or_else(|e| {
+            panic!("REPORT_EMAIL_TO is invalid: {}", e)
+        }),
+    })
+}
+
+fn alert_settings() -> AlertSettings {
+    AlertSettings {
+        sinks: alert_sinks(),
+        circuit_open: env_secs("ALERT_CIRCUIT_OPEN_SECS", "300"),
+        error_rate: Some(env_parse::<f64>("ALERT_ERROR_RATE", "50"))
+            .filter(|percent| (0.0..=100.0).contains(percent))
+            .expect("ALERT_ERROR_RATE must be between 0 and 100")
+            / 100.0,
+        error_window: Some(env_secs(
+            "ALERT_ERROR_WINDOW_SECS",
+            "300",
+        ))
+        .filter(|window| !window.is_zero())
+        .expect("ALERT_ERROR_WINDOW_SECS must be positive"),
+        min_calls: env_parse("ALERT_MIN_CALLS", "20"),
+        check: Some(env_secs("ALERT_CHECK_SECS", "30"))
+            .filter(|interval| !interval.is_zero())
+            .expect("ALERT_CHECK_SECS must be positive"),
+    }
+}
+
+/// The comma-separated `ALERT_SINKS`: `slack`, `discord` or `email`,
+/// the last sent with the report email settings.
+fn alert_sinks() -> Vec<AlertSinkConfig> {
+    let required = |var: &str, sink: &str| {
+        env_nonempty(var).unwrap_or_else(|| {
+            panic!("{} is required for the {} alert sink", var, sink)
+        })
+    };
+    env_or("ALERT_SINKS", "")
+        .split(',')
+        .map(|sink| sink.trim().to_lowercase())
+        .filter(|sink| !sink.is_empty())
+        .map(|sink| match sink.as_str() {
+            "slack" => AlertSinkConfig::Slack {
+                url: Secret(required("SLACK_WEBHOOK_URL", "slack")),
+            },
+            "discord" => AlertSinkConfig::Discord {
+                url: Secret(required(
+                    "DISCORD_WEBHOOK_URL",
+                    "discord",
+                )),
+            },
+            "email" => AlertSinkConfig::Email(
+                report_email().unwrap_or_else(|| {
+                    panic!("SMTP_URL is required for the email alert sink")
+                }),
+            ),
+            other => panic!(
+                "ALERT_SINKS is invalid: unknown sink '{}', expected slack, discord or email",
+                other
+            ),
+        })
+        .collect()
+}
+
+/// The `jwt` auth mode's settings; `JWT_JWKS_URL` is required.
+fn jwt_config() -> JwtConfig {
+    let Some(jwks_url) = env_nonempty("JWT_JWKS_URL") else {
+        panic!("JWT_JWKS_URL is required when AUTH_MODE is jwt");
+    };
+    JwtConfig {
+        jwks_url,
+        issuer: env_nonempty("JWT_ISSUER"),
+        audience: env_nonempty("JWT_AUDIENCE"),
+        tenant_claim: env_or("JWT_TENANT_CLAIM", "tenant"),
+        roles_claim: env_or("JWT_ROLES_CLAIM", "roles"),
+    }
+}
+
+/// Egress proxies from the conventional variables, upper or lower
+/// case, with credentials from `PROXY_USERNAME` and `PROXY_PASSWORD`
+/// or the proxy URLs.
+fn proxy_config() -> ProxyConfig {
+    let env = |name: &str| {
+        env_nonempty(name)
+            .or_else(|| env_nonempty(&name.to_lowercase()))
+    };
+    let proxy = ProxyConfig {
+        http: env("HTTP_PROXY"),
+        https: env("HTTPS_PROXY"),
+        no_proxy: env("NO_PROXY"),
+        username: env_nonempty("PROXY_USERNAME"),
+        password: env_nonempty("PROXY_PASSWORD"),
+    };
+    proxy.validate().unwrap_or_else(|e| {
+        panic!("Proxy settings are invalid: {}", e)
+    });
+    proxy
+}
+
+/// Extra CA certificates and the client identity of the upstream
+/// connections, read from PEM files.
+fn tls_config() -> TlsConfig {
+    let read = |name: &str| {
+        env_nonempty(name).map(|path| {
+            std::fs::read(&path).unwrap_or_else(|e| {
+                panic!("{} is invalid: {}: {}", name, path, e)
+            })
+        })
+    };
+    let identity = match (
+        read("UPSTREAM_CLIENT_CERT_FILE"),
+        read("UPSTREAM_CLIENT_KEY_FILE"),
+    ) {
+        (Some(mut cert), Some(key)) => {
+            cert.push(b'\n');
+            cert.extend(key);
+            Some(cert)
+        }
+        (None, None) => None,
+        _ => panic!(
+            "UPSTREAM_CLIENT_CERT_FILE and UPSTREAM_CLIENT_KEY_FILE must be set together"
+        ),
+    };
+    let tls = TlsConfig {
+        ca_bundle: read("UPSTREAM_CA_FILE"),
+        identity,
+    };
+    tls.validate().unwrap_or_else(|e| {
+        panic!("Upstream TLS settings are invalid: {}", e)
+    });
+    tls
+}
+
+/// `LISTENERS` wins; otherwise a single public listener is built
+/// from `LISTEN`. `ADMIN_PORT` adds an admin listener on `HOST`.
+fn listeners() -> Vec<ListenerSpec> {
+    let mut listeners = match std::env::var("LISTENERS") {
+        Ok(value) => {
+            ListenerSpec::parse_list(&value).unwrap_or_else(|e| {
+                panic!("LISTENERS is invalid: {}", e)
+            })
+        }
+        Err(_) => vec![ListenerSpec {
+            role: Role::Public,
+            addr: listen_addr(),
+        }],
+    };
+
+    if let Ok(port) = std::env::var("ADMIN_PORT") {
+        let port: u16 = port.parse().unwrap_or_else(|e| {
+            panic!("ADMIN_PORT must be a valid u16: {:?}", e)
+        });
+        listeners.push(ListenerSpec {
+            role: Role::Admin,
+            addr: ListenAddr::Tcp(format!(
+                "{}:{}",
+                env_or("HOST", "0.0.0.0"),
+                port
+            )),
+        });
+    }
+    listeners
+}
+
+/// `LISTEN` wins; otherwise a socket passed by systemd is adopted on
+/// Unix, falling back to `HOST:PORT`.
+fn listen_addr() -> ListenAddr {
+    match std::env::var("LISTEN") {
+        Ok(value) => ListenAddr::parse(&value)
+            .unwrap_or_else(|e| panic!("LISTEN is invalid: {}", e)),
+        #[cfg(unix)]
+        Err(_) if std::env::var_os("LISTEN_FDS").is_some() => {
+            ListenAddr::Systemd
+        }
+        Err(_) => ListenAddr::Tcp(format!(
+            "{}:{}",
+            env_or("HOST", "0.0.0.0"),
+            env_parse::<u16>("PORT", "5000")
+        )),
+    }
+}
+
+fn env_or(name: &str, default: &str) -> String {
+    std::env::var(name).unwrap_or_else(|_| default.to_string())
+}
+
+/// `PUBLIC_BASE_URL`, or the reverse proxies trusted to report it
+/// with `X-Forwarded-*` headers.
+fn public_url() -> PublicUrlConfig {
+    PublicUrlConfig {
+        base_url: env_nonempty("PUBLIC_BASE_URL").map(|url| {
+            PublicUrlConfig::parse_base_url(&url).unwrap_or_else(
+                |e| panic!("PUBLIC_BASE_URL is invalid: {}", e),
+            )
+        }),
+        trusted_proxies: TrustedProxy::parse_list(&env_or(
+            "TRUSTED_PROXIES",
+            "",
+        ))
+        .unwrap_or_else(|e| {
+            panic!("TRUSTED_PROXIES is invalid: {}", e)
+        }),
+    }
+}
+
+fn runtime_settings() -> RuntimeSettings {
+    let positive = |name: &str| {
+        env_nonempty(name).map(|value| {
+            value
+                .parse::<usize>()
+                .ok()
+                .filter(|&value| value > 0)
+                .unwrap_or_else(|| {
+                    panic!("{} must be a positive number", name)
+                })
+        })
+    };
+    RuntimeSettings {
+        worker_threads: positive("RUNTIME_WORKER_THREADS"),
+        max_blocking_threads: positive(
+            "RUNTIME_MAX_BLOCKING_THREADS",
+        ),
+        max_io_events_per_tick: positive("RUNTIME_MAX_IO_EVENTS"),
+    }
+}
+
+/// An optional setting; unset and empty are the same.
+fn env_nonempty(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+/// An optional credential.
+fn env_secret(name: &str) -> Option<Secret> {
+    env_nonempty(name).map(Secret)
+}
+
+fn env_parse<T>(name: &str, default: &str) -> T
+where
+    T: FromStr,
+    T::Err: Debug,
+{
+    env_or(name, default).parse().unwrap_or_else(|e| {
+        panic!(
+            "{} must be a valid {}: {:?}",
+            name,
+            std::any::type_name::<T>(),
+            e
+        )
+    })
+}
+
+fn env_secs(name: &str, default: &str) -> Duration {
+    Duration::from_secs(env_parse(name, default))
+}
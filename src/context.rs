@@ -0,0 +1,365 @@
+use crate::error::AppError;
+use axum::{
+    extract::{Request, State},
+    http::HeaderMap,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use reqwest::Url;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{debug, info};
+
+pub const UPSTREAM_POKEAPI_HEADER: &str =
+    "x-pokedex-upstream-pokeapi";
+pub const UPSTREAM_TRANSLATION_HEADER: &str =
+    "x-pokedex-upstream-translation";
+/// Absolute deadline of the caller, in Unix milliseconds.
+pub const DEADLINE_HEADER: &str = "x-request-deadline";
+/// Time the caller is willing to wait, gRPC style, e.g. `250m`.
+pub const GRPC_TIMEOUT_HEADER: &str = "grpc-timeout";
+/// Seeds the quiz draws of the request.
+pub const RANDOM_SEED_HEADER: &str = "x-random-seed";
+
+/// The debug headers honored, each behind its own flag as they let
+/// callers change what the server does.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DebugHeaders {
+    /// `DEBUG_UPSTREAM_OVERRIDES`
+    pub upstream_overrides: bool,
+    /// `DEBUG_RANDOM_SEED`
+    pub random_seed: bool,
+}
+
+/// Per-request settings that upstream clients consult while a
+/// request is being handled.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RequestContext {
+    pub pokeapi_base_url: Option<String>,
+    pub translation_base_url: Option<String>,
+    /// When the caller stops waiting for the response.
+    pub deadline: Option<Instant>,
+    /// Makes the quiz draws of the request reproducible.
+    pub random_seed: Option<u64>,
+}
+
+impl RequestContext {
+    fn has_upstream_override(&self) -> bool {
+        self.pokeapi_base_url.is_some()
+            || self.translation_base_url.is_some()
+    }
+}
+
+tokio::task_local! {
+    static CONTEXT: RequestContext;
+}
+
+/// The context of the current request, or a default one outside of
+/// a request.
+pub fn current() -> RequestContext {
+    CONTEXT.try_with(RequestContext::clone).unwrap_or_default()
+}
+
+/// Responses fetched from an overridden upstream must not be served
+/// to, or from, other requests.
+pub fn bypass_cache() -> bool {
+    CONTEXT
+        .try_with(RequestContext::has_upstream_override)
+        .unwrap_or(false)
+}
+
+/// The seed of the current request's random draws, if it has one.
+pub fn random_seed() -> Option<u64> {
+    CONTEXT
+        .try_with(|context| context.random_seed)
+        .ok()
+        .flatten()
+}
+
+/// What is left of the current request's deadline, if it has one.
+pub fn remaining() -> Option<Duration> {
+    CONTEXT
+        .try_with(|context| context.deadline)
+        .ok()
+        .flatten()
+        .map(|deadline| {
+            deadline.saturating_duration_since(Instant::now())
+        })
+}
+
+/// Reads the deadline headers and the `debug` headers enabled, and
+/// scopes the request to them.
+pub async fn middleware(
+    State(debug): State<DebugHeaders>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let mut context = if debug.upstream_overrides {
+        match overrides(request.headers()) {
+            Ok(context) => context,
+            Err(e) => return e.into_response(),
+        }
+    } else {
+        if request.headers().contains_key(UPSTREAM_POKEAPI_HEADER)
+            || request
+                .headers()
+                .contains_key(UPSTREAM_TRANSLATION_HEADER)
+        {
+            debug!("Ignoring upstream override headers");
+        }
+        RequestContext::default()
+    };
+    context.deadline = match deadline(request.headers()) {
+        Ok(deadline) => deadline,
+        Err(e) => return e.into_response(),
+    };
+    if debug.random_seed {
+        context.random_seed = match random_seed_of(request.headers())
+        {
+            Ok(seed) => seed,
+            Err(e) => return e.into_response(),
+        };
+    }
+
+    if context.has_upstream_override() {
+        info!(?context, "Using upstream overrides");
+    }
+    CONTEXT.scope(context, next.run(request)).await
+}
+
+fn overrides(
+    headers: &HeaderMap,
+) -> Result<RequestContext, AppError> {
+    Ok(RequestContext {
+        pokeapi_base_url: base_url(headers, UPSTREAM_POKEAPI_HEADER)?,
+        translation_base_url: base_url(
+            headers,
+            UPSTREAM_TRANSLATION_HEADER,
+        )?,
+        deadline: None,
+        random_seed: None,
+    })
+}
+
+fn random_seed_of(
+    headers: &HeaderMap,
+) -> Result<Option<u64>, AppError> {
+    headers
+        .get(RANDOM_SEED_HEADER)
+        .map(|value| {
+            value
+                .to_str()
+                .ok()
+                .and_then(|value| value.trim().parse().ok())
+                .ok_or_else(|| {
+                    AppError::BadRequest(format!(
+                        "{} must be an unsigned 64-bit integer",
+                        RANDOM_SEED_HEADER
+                    ))
+                })
+        })
+        .transpose()
+}
+
+/// The earlier of the `X-Request-Deadline` and `grpc-timeout`
+/// deadlines.
+fn deadline(
+    headers: &HeaderMap,
+) -> Result<Option<Instant>, AppError> {
+    let now = Instant::now();
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .map(|value| {
+                value.to_str().map(str::trim).map_err(|_| {
+                    AppError::BadRequest(format!(
+                        "{} is invalid",
+                        name
+                    ))
+                })
+            })
+            .transpose()
+    };
+
+    let absolute = header(DEADLINE_HEADER)?
+        .map(|value| {
+            let millis = value.parse::<u64>().map_err(|_| {
+                AppError::BadRequest(format!(
+                    "{} must be a Unix time in milliseconds",
+                    DEADLINE_HEADER
+                ))
+            })?;
+            let since_epoch = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            let left = Duration::from_millis(millis)
+                .saturating_sub(since_epoch);
+            Ok::<_, AppError>(now + left)
+        })
+        .transpose()?;
+    let relative = header(GRPC_TIMEOUT_HEADER)?
+        .map(|value| {
+            grpc_timeout(value)
+                .map(|timeout| now + timeout)
+                .ok_or_else(|| {
+                    AppError::BadRequest(format!(
+                        "{} must be a number followed by H, M, S, m, \
+                         u or n",
+                        GRPC_TIMEOUT_HEADER
+                    ))
+                })
+        })
+        .transpose()?;
+    Ok(absolute.into_iter().chain(relative).min())
+}
+
+/// Parses a gRPC timeout: at most eight digits and a unit.
+fn grpc_timeout(value: &str) -> Option<Duration> {
+    let unit = value.chars().last()?;
+    let amount = &value[..value.len() - unit.len_utf8()];
+    if amount.is_empty()
+        || amount.len() > 8
+        || !amount.bytes().all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+    let amount: u64 = amount.parse().ok()?;
+    match unit {
+        'H' => Some(Duration::from_secs(amount * 3600)),
+        'M' => Some(Duration::from_secs(amount * 60)),
+        'S' => Some(Duration::from_secs(amount)),
+        'm' => Some(Duration::from_millis(amount)),
+        'u' => Some(Duration::from_micros(amount)),
+        'n' => Some(Duration::from_nanos(amount)),
+        _ => None,
+    }
+}
+
+fn base_url(
+    headers: &HeaderMap,
+    name: &str,
+) -> Result<Option<String>, AppError> {
+    let Some(value) = headers.get(name) else {
+        return Ok(None);
+    };
+    let invalid = || {
+        AppError::BadRequest(format!(
+            "{} must be an absolute http(s) URL",
+            name
+        ))
+    };
+
+    let value = value.to_str().map_err(|_| invalid())?;
+    let url = Url::parse(value).map_err(|_| invalid())?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(invalid());
+    }
+    Ok(Some(value.trim_end_matches('/').to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_overrides_from_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            UPSTREAM_POKEAPI_HEADER,
+            HeaderValue::from_static("http://staging:8080/api/v2/"),
+        );
+        assert_eq!(
+            overrides(&headers).unwrap(),
+            RequestContext {
+                pokeapi_base_url: Some(
+                    "http://staging:8080/api/v2".to_string()
+                ),
+                translation_base_url: None,
+                deadline: None,
+                random_seed: None,
+            }
+        );
+
+        headers.insert(
+            UPSTREAM_TRANSLATION_HEADER,
+            HeaderValue::from_static("file:///etc/passwd"),
+        );
+        assert!(overrides(&headers).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_context_is_scoped_to_the_request() {
+        assert!(!bypass_cache());
+
+        let context = RequestContext {
+            pokeapi_base_url: Some("http://mirror".to_string()),
+            translation_base_url: None,
+            deadline: None,
+            random_seed: None,
+        };
+        CONTEXT
+            .scope(context, async {
+                assert!(bypass_cache());
+                assert_eq!(
+                    current().pokeapi_base_url.as_deref(),
+                    Some("http://mirror")
+                );
+            })
+            .await;
+
+        assert!(!bypass_cache());
+    }
+
+    #[test]
+    fn test_random_seed_header() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(random_seed_of(&headers).unwrap(), None);
+        headers.insert(
+            RANDOM_SEED_HEADER,
+            HeaderValue::from_static(" 42"),
+        );
+        assert_eq!(random_seed_of(&headers).unwrap(), Some(42));
+        headers.insert(
+            RANDOM_SEED_HEADER,
+            HeaderValue::from_static("-1"),
+        );
+        assert!(random_seed_of(&headers).is_err());
+    }
+
+    #[test]
+    fn test_deadline_is_the_earliest_header() {
+        assert_eq!(deadline(&HeaderMap::new()).unwrap(), None);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            GRPC_TIMEOUT_HEADER,
+            HeaderValue::from_static("250m"),
+        );
+        let far = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis()
+            + 60_000;
+        headers.insert(
+            DEADLINE_HEADER,
+            HeaderValue::from_str(&far.to_string()).unwrap(),
+        );
+        let left = deadline(&headers)
+            .unwrap()
+            .unwrap()
+            .saturating_duration_since(Instant::now());
+        assert!(left <= Duration::from_millis(250));
+        assert!(left > Duration::from_millis(200));
+
+        headers.insert(
+            GRPC_TIMEOUT_HEADER,
+            HeaderValue::from_static("1s"),
+        );
+        assert!(deadline(&headers).is_err());
+        assert_eq!(
+            grpc_timeout("2M"),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(grpc_timeout("123456789S"), None);
+    }
+}
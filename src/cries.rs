@@ -0,0 +1,266 @@
+use crate::cache::{Cache, ManagedCache};
+use crate::error::{AppError, Result};
+use crate::http::Upstream;
+use crate::pokemon::PokemonService;
+use crate::tts::Audio;
+use axum::{body::Bytes, http::header};
+use serde::Deserialize;
+use std::{sync::Arc, time::Duration};
+use tracing::instrument;
+use utoipa::ToSchema;
+
+/// Media type of the PokeAPI cries when the host does not say.
+const DEFAULT_CONTENT_TYPE: &str = "audio/ogg";
+
+/// Which of the cries of a Pokemon to play.
+#[derive(Debug, Clone, Copy, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CryVersion {
+    #[default]
+    Latest,
+    Legacy,
+}
+
+impl CryVersion {
+    fn as_str(self) -> &'static str {
+        match self {
+            CryVersion::Latest => "latest",
+            CryVersion::Legacy => "legacy",
+        }
+    }
+}
+
+/// Proxies the cry audio PokeAPI links to, caching it so players
+/// seeking through a cry do not download it again each time.
+pub struct CryService {
+    upstream: Upstream,
+    pokemon_service: Arc<PokemonService>,
+    /// Audio by URL.
+    cache: Cache<String, Audio>,
+}
+
+impl CryService {
+    pub fn new(
+        upstream: Upstream,
+        pokemon_service: Arc<PokemonService>,
+        cache_ttl: Duration,
+    ) -> Self {
+        Self {
+            upstream,
+            pokemon_service,
+            cache: Cache::new(cache_ttl),
+        }
+    }
+
+    /// The caches owned by this service, by name.
+    pub fn caches(&self) -> Vec<(&'static str, &dyn ManagedCache)> {
+        vec![("cries", &self.cache)]
+    }
+
+    /// The `version` cry of the Pokemon `name`.
+    #[instrument(skip(self))]
+    pub async fn cry(
+        &self,
+        name: &str,
+        version: CryVersion,
+    ) -> Result<Audio> {
+        let cries = self.pokemon_service.cries(name).await?;
+        let url = match version {
+            CryVersion::Latest => cries.latest,
+            CryVersion::Legacy => cries.legacy,
+        }
+        .ok_or_else(|| {
+            AppError::NotFound(format!(
+                "Pokemon '{}' has no {} cry",
+                name,
+                version.as_str()
+            ))
+        })?;
+        if let Some(audio) = self.cache.get(&url) {
+            return Ok(audio);
+        }
+
+        let audio = self.fetch(&url).await?;
+        self.cache.insert(url, audio.clone());
+        Ok(audio)
+    }
+
+    async fn fetch(&self, url: &str) -> Result<Audio> {
+        let response =
+            self.upstream.send(self.upstream.get(url)).await?;
+        if !response.status().is_success() {
+            return Err(AppError::ExternalApi(format!(
+                "cry host returned status: {}",
+                response.status()
+            )));
+        }
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .filter(|value| value.starts_with("audio/"))
+            .unwrap_or(DEFAULT_CONTENT_TYPE)
+            .to_string();
+        let bytes = self.upstream.read_bytes(response).await?;
+        if bytes.is_empty() {
+            return Err(AppError::UpstreamSchema(
+                "cry host returned empty audio".to_string(),
+            ));
+        }
+        Ok(Audio {
+            content_type,
+            bytes: Bytes::from(bytes),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http;
+    use crate::pokeapi::PokeApiClient;
+    use reqwest::Client;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    /// A service whose PokeAPI and cry host are both `server`, with
+    /// a `pikachu` species that has a latest cry and, if `legacy`, a
+    /// legacy one.
+    async fn service(
+        server: &MockServer,
+        legacy: bool,
+    ) -> CryService {
+        Mock::given(method("GET"))
+            .and(path("/pokemon-species/pikachu"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({
+                    "name": "pikachu",
+                    "habitat": null,
+                    "flavor_text_entries": [],
+                    "is_legendary": false,
+                    "is_mythical": false,
+                    "is_baby": false
+                }),
+            ))
+            .mount(server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/pokemon/pikachu"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({
+                    "sprites": {
+                        "front_default": null,
+                        "back_default": null,
+                        "front_shiny": null,
+                        "back_shiny": null
+                    },
+                    "cries": {
+                        "latest": format!("{}/cries/latest/25.ogg", server.uri()),
+                        "legacy": legacy.then(|| format!("{}/cries/legacy/25.ogg", server.uri()))
+                    }
+                }),
+            ))
+            .mount(server)
+            .await;
+
+        let pokemon_service = PokemonService::new(
+            PokeApiClient::new(
+                http::build_client(&http::ClientSettings::new(
+                    Duration::from_secs(5),
+                )),
+                server.uri(),
+            ),
+            Duration::from_secs(60),
+        );
+        CryService::new(
+            Upstream::new("Cries", Client::new()),
+            Arc::new(pokemon_service),
+            Duration::from_secs(60),
+        )
+    }
+
+    fn cry(body: &[u8], content_type: &str) -> ResponseTemplate {
+        ResponseTemplate::new(200)
+            .set_body_raw(body.to_vec(), content_type)
+    }
+
+    #[tokio::test]
+    async fn test_latest_cry_is_cached() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/cries/latest/25.ogg"))
+            .respond_with(cry(b"OggSlatest", "audio/ogg"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let service = service(&server, false).await;
+        for _ in 0..2 {
+            let audio = service
+                .cry("pikachu", CryVersion::Latest)
+                .await
+                .unwrap();
+            assert_eq!(audio.content_type, "audio/ogg");
+            assert_eq!(&audio.bytes[..], b"OggSlatest");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cry_version_picks_the_url() {
+        let server = MockServer::start().await;
+        for version in ["latest", "legacy"] {
+            Mock::given(method("GET"))
+                .and(path(format!("/cries/{}/25.ogg", version)))
+                .respond_with(cry(version.as_bytes(), "audio/ogg"))
+                .mount(&server)
+                .await;
+        }
+
+        let service = service(&server, true).await;
+        for version in [CryVersion::Latest, CryVersion::Legacy] {
+            let audio =
+                service.cry("pikachu", version).await.unwrap();
+            assert_eq!(&audio.bytes[..], version.as_str().as_bytes());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_missing_legacy_cry_is_not_found() {
+        let server = MockServer::start().await;
+        let service = service(&server, false).await;
+        let result = service.cry("pikachu", CryVersion::Legacy).await;
+        assert!(matches!(
+            result,
+            Err(AppError::NotFound(msg)) if msg.contains("no legacy cry")
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_non_audio_content_type_falls_back_to_ogg() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/cries/latest/25.ogg"))
+            .respond_with(cry(b"OggS", "application/octet-stream"))
+            .mount(&server)
+            .await;
+
+        let service = service(&server, false).await;
+        let audio =
+            service.cry("pikachu", CryVersion::Latest).await.unwrap();
+        assert_eq!(audio.content_type, DEFAULT_CONTENT_TYPE);
+    }
+
+    #[tokio::test]
+    async fn test_empty_audio_is_rejected() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/cries/latest/25.ogg"))
+            .respond_with(cry(b"", "audio/ogg"))
+            .mount(&server)
+            .await;
+
+        let service = service(&server, false).await;
+        let result = service.cry("pikachu", CryVersion::Latest).await;
+        assert!(matches!(result, Err(AppError::UpstreamSchema(_))));
+    }
+}
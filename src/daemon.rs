@@ -0,0 +1,43 @@
+use crate::config::Config;
+use crate::error::{AppError, Result};
+#[cfg(unix)]
+use daemonize::Daemonize;
+
+/// Detaches the server from the terminal for `--daemonize`, writing
+/// its pid to `PID_FILE`. Forking only keeps the calling thread, so
+/// this must run before the log writer and the runtime start theirs.
+#[cfg(unix)]
+pub fn daemonize(config: &Config) -> Result<()> {
+    // Standard output and error are closed, so the logs would be
+    // lost without a file.
+    if config.log.file.is_none() {
+        return Err(AppError::Internal(
+            "--daemonize requires LOG_FILE".to_string(),
+        ));
+    }
+    // Relative paths in the configuration keep resolving against
+    // the directory the server was started from.
+    let directory = std::env::current_dir().map_err(|e| {
+        AppError::Internal(format!(
+            "Failed to read the working directory: {}",
+            e
+        ))
+    })?;
+    let mut daemon = Daemonize::new().working_directory(directory);
+    if let Some(path) = &config.pid_file {
+        daemon = daemon.pid_file(path);
+    }
+    daemon.start().map_err(|e| {
+        AppError::Internal(format!("Failed to daemonize: {}", e))
+    })
+}
+
+/// There is no fork off Unix; Windows hosts run the server as a
+/// service with `--service` instead.
+#[cfg(not(unix))]
+pub fn daemonize(_config: &Config) -> Result<()> {
+    Err(AppError::Internal(
+        "--daemonize is only supported on Unix, use --service on Windows"
+            .to_string(),
+    ))
+}
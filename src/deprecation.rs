@@ -0,0 +1,222 @@
+use crate::forwarded::PublicUrl;
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{HeaderName, HeaderValue, header},
+    middleware::Next,
+    response::Response,
+};
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::debug;
+
+pub const DEPRECATION_HEADER: &str = "deprecation";
+pub const SUNSET_HEADER: &str = "sunset";
+
+/// Marks a route template, e.g. `/v1/pokemon/:name`, as deprecated.
+#[derive(Debug, Clone)]
+pub struct Deprecation {
+    route: String,
+    since: SystemTime,
+    sunset: Option<SystemTime>,
+    successor: Option<String>,
+}
+
+impl Deprecation {
+    pub fn new(route: &str, since: SystemTime) -> Self {
+        Self {
+            route: route.to_string(),
+            since,
+            sunset: None,
+            successor: None,
+        }
+    }
+
+    /// When the route stops being served.
+    pub fn sunset(mut self, at: SystemTime) -> Self {
+        self.sunset = Some(at);
+        self
+    }
+
+    /// The route replacing this one. Its `:param` segments are filled
+    /// in from the request path.
+    pub fn successor(mut self, route: &str) -> Self {
+        self.successor = Some(route.to_string());
+        self
+    }
+
+    /// `Deprecation`, `Sunset` and `Link` headers for a request to
+    /// `path`, following RFC 9745 and RFC 8594. The successor is
+    /// linked under `base`, the public URL of the service.
+    fn headers(
+        &self,
+        base: &str,
+        path: &str,
+    ) -> Vec<(HeaderName, String)> {
+        let since = self
+            .since
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let mut headers = vec![(
+            HeaderName::from_static(DEPRECATION_HEADER),
+            format!("@{}", since),
+        )];
+        if let Some(sunset) = self.sunset {
+            headers.push((
+                HeaderName::from_static(SUNSET_HEADER),
+                httpdate::fmt_http_date(sunset),
+            ));
+        }
+        if let Some(successor) = &self.successor {
+            headers.push((
+                header::LINK,
+                format!(
+                    "<{}{}>; rel=\"successor-version\"",
+                    base,
+                    fill_params(&self.route, path, successor)
+                ),
+            ));
+        }
+        headers
+    }
+}
+
+/// Lifecycle metadata of the routes that are being retired.
+#[derive(Debug, Clone, Default)]
+pub struct RouteRegistry {
+    deprecations: Vec<Deprecation>,
+}
+
+impl RouteRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn deprecate(mut self, deprecation: Deprecation) -> Self {
+        self.deprecations.push(deprecation);
+        self
+    }
+
+    fn get(&self, route: &str) -> Option<&Deprecation> {
+        self.deprecations.iter().find(|d| d.route == route)
+    }
+}
+
+/// Midnight UTC of the given day, for declaring deprecation and
+/// sunset dates.
+pub fn date(year: i64, month: u32, day: u32) -> SystemTime {
+    // Days since the epoch of a proleptic Gregorian date, after
+    // Howard Hinnant's `days_from_civil`.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let day_of_year =
+        (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5
+            + i64::from(day)
+            - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4
+        - year_of_era / 100
+        + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    UNIX_EPOCH + Duration::from_secs(days.max(0) as u64 * 86_400)
+}
+
+/// Replaces the `:param` segments of `target` with the segments of
+/// `path` found at the same names in `route`.
+fn fill_params(route: &str, path: &str, target: &str) -> String {
+    let params: Vec<(&str, &str)> = route
+        .split('/')
+        .zip(path.split('/'))
+        .filter_map(|(template, value)| {
+            template.strip_prefix(':').map(|name| (name, value))
+        })
+        .collect();
+
+    target
+        .split('/')
+        .map(|segment| {
+            segment
+                .strip_prefix(':')
+                .and_then(|name| {
+                    params.iter().find(|(param, _)| *param == name)
+                })
+                .map_or(segment, |(_, value)| value)
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Attaches the lifecycle headers to responses of deprecated routes.
+pub async fn middleware(
+    State(registry): State<Arc<RouteRegistry>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let deprecation = request
+        .extensions()
+        .get::<MatchedPath>()
+        .and_then(|route| registry.get(route.as_str()));
+    let Some(deprecation) = deprecation else {
+        return next.run(request).await;
+    };
+
+    debug!(route = %deprecation.route, "Serving deprecated route");
+    let base = request
+        .extensions()
+        .get::<PublicUrl>()
+        .map(PublicUrl::as_str)
+        .unwrap_or_default();
+    let headers = deprecation.headers(base, request.uri().path());
+    let mut response = next.run(request).await;
+    for (name, value) in headers {
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            response.headers_mut().insert(name, value);
+        }
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_date() {
+        assert_eq!(date(1970, 1, 1), UNIX_EPOCH);
+        assert_eq!(
+            httpdate::fmt_http_date(date(2027, 3, 1)),
+            "Mon, 01 Mar 2027 00:00:00 GMT"
+        );
+    }
+
+    #[test]
+    fn test_headers_link_the_successor() {
+        let registry = RouteRegistry::new().deprecate(
+            Deprecation::new(
+                "/v1/pokemon/:name/details",
+                date(2026, 1, 1),
+            )
+            .sunset(date(2026, 7, 1))
+            .successor("/v2/pokemon/:name"),
+        );
+        assert!(registry.get("/v2/pokemon/:name").is_none());
+
+        let headers = registry
+            .get("/v1/pokemon/:name/details")
+            .unwrap()
+            .headers("", "/v1/pokemon/mr.%20mime/details");
+        let values: Vec<&str> =
+            headers.iter().map(|(_, value)| value.as_str()).collect();
+        assert_eq!(
+            values,
+            [
+                "@1767225600",
+                "Wed, 01 Jul 2026 00:00:00 GMT",
+                "</v2/pokemon/mr.%20mime>; rel=\"successor-version\"",
+            ]
+        );
+    }
+}
@@ -0,0 +1,193 @@
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use std::{
+    collections::HashMap,
+    fmt,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::debug;
+
+/// Static host to address mappings, from `DNS_OVERRIDES`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DnsOverrides(HashMap<String, Vec<IpAddr>>);
+
+impl DnsOverrides {
+    /// Parses comma-separated `host=ip` entries; a host listed
+    /// several times resolves to all of its addresses.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut overrides: HashMap<String, Vec<IpAddr>> =
+            HashMap::new();
+        for entry in spec.split(',').map(str::trim) {
+            if entry.is_empty() {
+                continue;
+            }
+            let (host, ip) = entry
+                .split_once('=')
+                .filter(|(host, _)| !host.trim().is_empty())
+                .ok_or_else(|| {
+                    format!("Invalid DNS override '{}'", entry)
+                })?;
+            let ip = ip.trim().parse().map_err(|_| {
+                format!("Invalid address in DNS override '{}'", entry)
+            })?;
+            overrides
+                .entry(host.trim().to_lowercase())
+                .or_default()
+                .push(ip);
+        }
+        Ok(Self(overrides))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+type Lookups = HashMap<String, (Instant, Vec<SocketAddr>)>;
+
+/// Resolves the upstream hosts, answering overridden hosts from the
+/// static table and caching system lookups for `ttl`. Shared by all
+/// the upstream clients.
+pub struct Resolver {
+    overrides: DnsOverrides,
+    ttl: Duration,
+    cache: Arc<Mutex<Lookups>>,
+}
+
+impl fmt::Debug for Resolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Resolver")
+            .field("overrides", &self.overrides)
+            .field("ttl", &self.ttl)
+            .finish()
+    }
+}
+
+impl Resolver {
+    /// A `ttl` of zero disables the cache.
+    pub fn new(overrides: DnsOverrides, ttl: Duration) -> Self {
+        Self {
+            overrides,
+            ttl,
+            cache: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    fn cached(&self, host: &str) -> Option<Vec<SocketAddr>> {
+        let cache = self.cache.lock().unwrap();
+        cache
+            .get(host)
+            .filter(|(resolved, _)| resolved.elapsed() < self.ttl)
+            .map(|(_, addrs)| addrs.clone())
+    }
+}
+
+impl Resolve for Resolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_lowercase();
+        // The connector fills in the port of the URL.
+        if let Some(ips) = self.overrides.0.get(&host) {
+            let addrs: Vec<SocketAddr> = ips
+                .iter()
+                .map(|ip| SocketAddr::new(*ip, 0))
+                .collect();
+            return Box::pin(async move { Ok(boxed(addrs)) });
+        }
+        if let Some(addrs) = self.cached(&host) {
+            return Box::pin(async move { Ok(boxed(addrs)) });
+        }
+
+        let (ttl, cache) = (self.ttl, self.cache.clone());
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> =
+                tokio::net::lookup_host((host.as_str(), 0))
+                    .await?
+                    .collect();
+            debug!(%host, ?addrs, "Resolved upstream host");
+            if !ttl.is_zero() {
+                let mut cache = cache.lock().unwrap();
+                cache.retain(|_, (resolved, _)| {
+                    resolved.elapsed() < ttl
+                });
+                cache.insert(host, (Instant::now(), addrs.clone()));
+            }
+            Ok(boxed(addrs))
+        })
+    }
+}
+
+fn boxed(addrs: Vec<SocketAddr>) -> Addrs {
+    Box::new(addrs.into_iter())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{ClientSettings, build_client};
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate, matchers::any,
+    };
+
+    #[test]
+    fn test_parse_overrides() {
+        let overrides = DnsOverrides::parse(
+            "pokeapi.co=10.0.0.5, PokeAPI.co=::1,api.test=127.0.0.1",
+        )
+        .unwrap();
+        assert_eq!(
+            overrides.0["pokeapi.co"],
+            ["10.0.0.5", "::1"]
+                .map(|ip| ip.parse::<IpAddr>().unwrap())
+        );
+        assert!(DnsOverrides::parse("").unwrap().is_empty());
+        assert!(DnsOverrides::parse("pokeapi.co").is_err());
+        assert!(DnsOverrides::parse("pokeapi.co=nowhere").is_err());
+        assert!(DnsOverrides::parse("=10.0.0.5").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_overridden_host_reaches_address() {
+        let server = MockServer::start().await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(204))
+            .mount(&server)
+            .await;
+        let resolver = Arc::new(Resolver::new(
+            DnsOverrides::parse("pokeapi.test=127.0.0.1").unwrap(),
+            Duration::ZERO,
+        ));
+        let client = build_client(
+            &ClientSettings::new(Duration::from_secs(5))
+                .with_resolver(resolver),
+        );
+        let url = format!(
+            "http://pokeapi.test:{}/api/v2/pokemon/ditto",
+            server.address().port()
+        );
+        let response = client.get(url).send().await.unwrap();
+        assert_eq!(response.status(), 204);
+    }
+
+    #[tokio::test]
+    async fn test_lookups_are_cached_for_ttl() {
+        let resolver = Resolver::new(
+            DnsOverrides::default(),
+            Duration::from_secs(60),
+        );
+        let name: Name = "localhost".parse().unwrap();
+        let addrs: Vec<_> =
+            resolver.resolve(name).await.unwrap().collect();
+        assert!(!addrs.is_empty());
+        assert_eq!(resolver.cached("localhost"), Some(addrs));
+
+        let uncached =
+            Resolver::new(DnsOverrides::default(), Duration::ZERO);
+        let addrs = uncached
+            .resolve("localhost".parse().unwrap())
+            .await
+            .unwrap();
+        assert_ne!(addrs.count(), 0);
+        assert_eq!(uncached.cached("localhost"), None);
+    }
+}
@@ -0,0 +1,276 @@
+use crate::metrics::Metrics;
+use rand::Rng;
+use serde::de::DeserializeOwned;
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tracing::{debug, warn};
+
+/// Payloads kept for re-validation; the oldest make room.
+const MAX_SAMPLES: usize = 200;
+
+/// What re-validating a payload against its model found.
+#[derive(Debug, Default, PartialEq)]
+pub struct Findings {
+    /// Paths of fields the model does not know about, e.g.
+    /// `flavor_text_entries.0.version`.
+    pub unknown: Vec<String>,
+    /// Path and error of the field that no longer deserializes,
+    /// typically one that was removed or changed type.
+    pub broken: Option<String>,
+}
+
+/// Deserializes `body` into `T`, collecting the fields it ignores.
+pub fn check<T: DeserializeOwned>(body: &[u8]) -> Findings {
+    let mut findings = Findings::default();
+    let deserializer =
+        &mut serde_json::Deserializer::from_slice(body);
+    let result: Result<T, _> =
+        serde_ignored::deserialize(deserializer, |path| {
+            findings.unknown.push(path.to_string())
+        });
+    if result.is_err() {
+        let deserializer =
+            &mut serde_json::Deserializer::from_slice(body);
+        if let Err(e) =
+            serde_path_to_error::deserialize::<_, T>(deserializer)
+        {
+            findings.broken =
+                Some(format!("'{}': {}", e.path(), e.inner()));
+        }
+    }
+    findings
+}
+
+/// A raw upstream payload and the model it was read into.
+struct Sample {
+    upstream: &'static str,
+    model: &'static str,
+    body: Vec<u8>,
+    check: fn(&[u8]) -> Findings,
+}
+
+/// Keeps a percentage of the upstream payloads, from
+/// `SCHEMA_SAMPLE_PERCENT`, and periodically re-validates them to
+/// notice upstream schema changes before they break requests.
+pub struct SchemaSampler {
+    rate: f64,
+    samples: Mutex<VecDeque<Sample>>,
+    /// The fields each model ignored at its first check. Models
+    /// only cover part of the payloads, so only fields appearing
+    /// later are drift.
+    baseline: Mutex<BTreeMap<&'static str, BTreeSet<String>>>,
+    /// Findings already logged, so each is logged once.
+    reported: Mutex<BTreeSet<(&'static str, String)>>,
+}
+
+impl SchemaSampler {
+    /// `rate` is the percentage of payloads kept.
+    pub fn new(rate: f64) -> Self {
+        Self {
+            rate,
+            samples: Mutex::new(VecDeque::new()),
+            baseline: Mutex::new(BTreeMap::new()),
+            reported: Mutex::new(BTreeSet::new()),
+        }
+    }
+
+    /// Keeps `body`, read into a `T`, if it is sampled.
+    pub fn sample<T: DeserializeOwned>(
+        &self,
+        upstream: &'static str,
+        body: &[u8],
+    ) {
+        if rand::rng().random_range(0.0..100.0) >= self.rate {
+            return;
+        }
+        let mut samples = self.samples.lock().unwrap();
+        if samples.len() >= MAX_SAMPLES {
+            samples.pop_front();
+        }
+        samples.push_back(Sample {
+            upstream,
+            model: model_name::<T>(),
+            body: body.to_vec(),
+            check: check::<T>,
+        });
+    }
+
+    /// Re-validates every sample, recording per upstream and model
+    /// how many fields appeared since the model's first check and how
+    /// many payloads no longer deserialize. Returns the total.
+    pub fn validate(&self, metrics: &Metrics) -> usize {
+        let samples: Vec<_> = self
+            .samples
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|sample| {
+                (
+                    sample.upstream,
+                    sample.model,
+                    sample.body.clone(),
+                    sample.check,
+                )
+            })
+            .collect();
+
+        let mut totals = BTreeMap::new();
+        for (upstream, model, body, check) in samples {
+            let findings = check(&body);
+            let (unknown, broken) = totals
+                .entry((upstream, model))
+                .or_insert((BTreeSet::new(), 0));
+            unknown.extend(findings.unknown);
+            if let Some(error) = findings.broken {
+                if self.first_report(model, &error) {
+                    warn!(upstream, model, %error, "Upstream payload no longer matches the model");
+                }
+                *broken += 1;
+            }
+        }
+
+        let mut drift = 0;
+        let mut baseline = self.baseline.lock().unwrap();
+        for ((upstream, model), (unknown, broken)) in totals {
+            let Some(known) = baseline.get(model) else {
+                debug!(
+                    upstream,
+                    model,
+                    ignored = unknown.len(),
+                    "Recorded the fields the model ignores"
+                );
+                baseline.insert(model, unknown);
+                metrics
+                    .record_schema_drift(upstream, model, 0, broken);
+                drift += broken;
+                continue;
+            };
+            let added: Vec<_> = unknown.difference(known).collect();
+            for field in &added {
+                if self.first_report(model, field) {
+                    warn!(upstream, model, %field, "Upstream payload has a new field the model does not know");
+                }
+            }
+            metrics.record_schema_drift(
+                upstream,
+                model,
+                added.len(),
+                broken,
+            );
+            drift += added.len() + broken;
+        }
+        drift
+    }
+
+    fn first_report(
+        &self,
+        model: &'static str,
+        finding: &str,
+    ) -> bool {
+        self.reported
+            .lock()
+            .unwrap()
+            .insert((model, finding.to_string()))
+    }
+
+    /// Re-validates the samples every `interval`.
+    pub fn watch(
+        self: Arc<Self>,
+        interval: Duration,
+        metrics: Arc<Metrics>,
+    ) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let drifted = self.validate(&metrics);
+                debug!(
+                    drifted,
+                    "Re-validated upstream payload samples"
+                );
+            }
+        });
+    }
+}
+
+/// `PokemonSpeciesResponse` rather than its full path.
+fn model_name<T>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    let base = name.split('<').next().unwrap_or(name);
+    base.rsplit("::").next().unwrap_or(base)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[allow(dead_code)]
+    #[derive(Deserialize)]
+    struct Species {
+        name: String,
+        habitat: Option<Habitat>,
+    }
+
+    #[allow(dead_code)]
+    #[derive(Deserialize)]
+    struct Habitat {
+        name: String,
+    }
+
+    #[test]
+    fn test_check_reports_unknown_and_broken_fields() {
+        assert_eq!(
+            check::<Species>(br#"{"name":"mew","habitat":null}"#),
+            Findings::default()
+        );
+
+        let findings = check::<Species>(
+            br#"{"name":"mew","color":"pink","habitat":{"name":"rare","url":"/"}}"#,
+        );
+        // `?` stands for the `Some` of an `Option`.
+        assert_eq!(findings.unknown, ["color", "habitat.?.url"]);
+        assert_eq!(findings.broken, None);
+
+        let findings = check::<Species>(
+            br#"{"species_name":"mew","habitat":{"name":1}}"#,
+        );
+        assert!(
+            findings.broken.unwrap().starts_with("'habitat.name'")
+        );
+    }
+
+    #[test]
+    fn test_validate_records_drift() {
+        let sampler = SchemaSampler::new(100.0);
+        sampler.sample::<Species>("PokeAPI", br#"{"name":"mew"}"#);
+        sampler.sample::<Species>(
+            "PokeAPI",
+            br#"{"name":"mew","color":"pink"}"#,
+        );
+        sampler.sample::<Species>("PokeAPI", br#"{"habitat":null}"#);
+        assert_eq!(model_name::<Species>(), "Species");
+
+        let metrics = Metrics::new();
+        // The first check records what the model ignores.
+        assert_eq!(sampler.validate(&metrics), 1);
+        sampler.sample::<Species>(
+            "PokeAPI",
+            br#"{"name":"mew","color":"pink","shape":"upright"}"#,
+        );
+        assert_eq!(sampler.validate(&metrics), 2);
+        let text = metrics.render(&[]);
+        let labels = r#"upstream="PokeAPI",model="Species""#;
+        assert!(text.contains(&format!(
+            "upstream_schema_new_fields{{{}}} 1",
+            labels
+        )));
+        assert!(text.contains(&format!(
+            "upstream_schema_broken_samples{{{}}} 1",
+            labels
+        )));
+    }
+}
@@ -0,0 +1,196 @@
+use crate::cache::{Cache, ManagedCache};
+use crate::error::Result;
+use crate::pokeapi::{NamedApiResource, PokeApiClient};
+use crate::pokemon::PokemonService;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+use tracing::instrument;
+use utoipa::ToSchema;
+
+/// Cache key of the encounters in every version.
+const ALL_VERSIONS: &str = "*";
+
+/// Where and how a Pokemon can be found in one game.
+#[derive(
+    Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema,
+)]
+pub struct Encounter {
+    pub location_area: String,
+    pub version: String,
+    /// E.g. `walk`, `surf` or `old-rod`.
+    pub method: String,
+    pub min_level: u32,
+    pub max_level: u32,
+    /// Percent chance of meeting it there with that method, summed
+    /// over the encounter slots.
+    pub chance: u32,
+}
+
+#[derive(Deserialize)]
+struct PokeApiLocationAreaEncounter {
+    location_area: NamedApiResource,
+    version_details: Vec<PokeApiVersionEncounter>,
+}
+
+#[derive(Deserialize)]
+struct PokeApiVersionEncounter {
+    version: NamedApiResource,
+    encounter_details: Vec<PokeApiEncounter>,
+}
+
+#[derive(Deserialize)]
+struct PokeApiEncounter {
+    min_level: u32,
+    max_level: u32,
+    #[serde(default)]
+    chance: u32,
+    method: NamedApiResource,
+}
+
+pub struct EncounterService {
+    pokeapi: PokeApiClient,
+    pokemon_service: Arc<PokemonService>,
+    /// Encounters by variety and version, `*` for every version.
+    cache: Cache<(String, String), Arc<Vec<Encounter>>>,
+}
+
+impl EncounterService {
+    pub fn new(
+        pokeapi: PokeApiClient,
+        pokemon_service: Arc<PokemonService>,
+        cache_ttl: Duration,
+    ) -> Self {
+        Self {
+            pokeapi,
+            pokemon_service,
+            cache: Cache::new(cache_ttl),
+        }
+    }
+
+    /// The caches owned by this service, by name.
+    pub fn caches(&self) -> Vec<(&'static str, &dyn ManagedCache)> {
+        vec![("encounters", &self.cache)]
+    }
+
+    /// Where the Pokemon `name` is found in the wild, in `version`
+    /// or every version, sorted by version, location and method.
+    #[instrument(skip(self))]
+    pub async fn encounters(
+        &self,
+        name: &str,
+        version: Option<&str>,
+    ) -> Result<Arc<Vec<Encounter>>> {
+        let variety =
+            self.pokemon_service.default_variety(name).await?;
+        let version = version
+            .map_or(ALL_VERSIONS.to_string(), str::to_lowercase);
+        let key = (variety, version);
+        if let Some(encounters) = self.cache.get(&key) {
+            return Ok(encounters);
+        }
+
+        let areas: Vec<PokeApiLocationAreaEncounter> = self
+            .pokeapi
+            .get(&format!("pokemon/{}/encounters", key.0), || {
+                format!("Pokemon '{}' not found", name)
+            })
+            .await?;
+        let wanted = Some(key.1.as_str())
+            .filter(|version| *version != ALL_VERSIONS);
+        let encounters = Arc::new(reshape(areas, wanted));
+        self.cache.insert(key, encounters.clone());
+        Ok(encounters)
+    }
+}
+
+/// Merges the encounter slots of each location area, version and
+/// method into one level range, keeping only `version` if given.
+fn reshape(
+    areas: Vec<PokeApiLocationAreaEncounter>,
+    version: Option<&str>,
+) -> Vec<Encounter> {
+    let mut merged: BTreeMap<(String, String, String), Encounter> =
+        BTreeMap::new();
+    for area in areas {
+        for details in area.version_details {
+            if version.is_some_and(|v| v != details.version.name) {
+                continue;
+            }
+            for slot in details.encounter_details {
+                let key = (
+                    details.version.name.clone(),
+                    area.location_area.name.clone(),
+                    slot.method.name.clone(),
+                );
+                merged
+                    .entry(key)
+                    .and_modify(|encounter| {
+                        encounter.min_level =
+                            encounter.min_level.min(slot.min_level);
+                        encounter.max_level =
+                            encounter.max_level.max(slot.max_level);
+                        encounter.chance += slot.chance;
+                    })
+                    .or_insert_with(|| Encounter {
+                        location_area: area
+                            .location_area
+                            .name
+                            .clone(),
+                        version: details.version.name.clone(),
+                        method: slot.method.name,
+                        min_level: slot.min_level,
+                        max_level: slot.max_level,
+                        chance: slot.chance,
+                    });
+            }
+        }
+    }
+    merged.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reshape_merges_slots() {
+        let areas: Vec<PokeApiLocationAreaEncounter> =
+            serde_json::from_value(serde_json::json!([{
+                "location_area": {"name": "viridian-forest-area", "url": "u"},
+                "version_details": [
+                    {
+                        "version": {"name": "firered", "url": "u"},
+                        "max_chance": 5,
+                        "encounter_details": [
+                            {"min_level": 3, "max_level": 3, "chance": 1,
+                             "method": {"name": "walk", "url": "u"}},
+                            {"min_level": 5, "max_level": 5, "chance": 4,
+                             "method": {"name": "walk", "url": "u"}}
+                        ]
+                    },
+                    {
+                        "version": {"name": "red", "url": "u"},
+                        "max_chance": 5,
+                        "encounter_details": [
+                            {"min_level": 3, "max_level": 5, "chance": 5,
+                             "method": {"name": "walk", "url": "u"}}
+                        ]
+                    }
+                ]
+            }]))
+            .unwrap();
+
+        let encounters = reshape(areas, Some("firered"));
+        assert_eq!(
+            encounters,
+            vec![Encounter {
+                location_area: "viridian-forest-area".to_string(),
+                version: "firered".to_string(),
+                method: "walk".to_string(),
+                min_level: 3,
+                max_level: 5,
+                chance: 5,
+            }]
+        );
+    }
+}
@@ -1,27 +1,96 @@
+use crate::i18n::{self, Message};
 use axum::{
     Json,
-    http::StatusCode,
+    http::{HeaderValue, StatusCode, header},
     response::{IntoResponse, Response},
 };
 use serde::Serialize;
 use std::fmt;
 use tracing::error;
+use utoipa::ToSchema;
 
 pub type Result<T> = std::result::Result<T, AppError>;
 
 #[derive(Debug)]
 pub enum AppError {
     NotFound(String),
+    /// A Pokemon name that does not exist, with the closest known
+    /// names.
+    UnknownPokemon {
+        name: String,
+        suggestions: Vec<String>,
+    },
+    BadRequest(String),
+    Unauthorized(String),
+    Forbidden(String),
+    Conflict(String),
+    PayloadTooLarge(String),
+    TooManyRequests(String),
     ExternalApi(String),
+    UpstreamSchema(String),
     Internal(String),
     Timeout(String),
+    /// A feature switched off at runtime, named by its flag.
+    FeatureDisabled(String),
+    /// A request body with invalid fields, answered with `422`.
+    ValidationError(Vec<FieldError>),
+    /// Data still being built in the background, answered with
+    /// `503` and a `Retry-After` of `retry_after` seconds.
+    NotReady {
+        message: String,
+        retry_after: u64,
+    },
 }
 
-#[derive(Serialize)]
-struct ErrorResponse {
+/// An invalid field of a request body.
+#[derive(Debug, Clone, Serialize, PartialEq, ToSchema)]
+pub struct FieldError {
+    /// Path of the field, e.g. `names[2]`.
+    pub field: String,
+    /// Machine-readable reason, e.g. `empty` or `duplicate`.
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(
+        field: impl Into<String>,
+        code: &'static str,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            field: field.into(),
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl AppError {
+    /// Fails with every error in `errors`, if any.
+    pub fn validate(errors: Vec<FieldError>) -> Result<()> {
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::ValidationError(errors))
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ErrorResponse {
     error: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     details: Option<String>,
+    /// Machine-readable error code, for errors clients may act on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<String>,
+    /// Known names close to a misspelled one.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    suggestions: Vec<String>,
+    /// The invalid fields of a request body.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    fields: Vec<FieldError>,
 }
 
 impl fmt::Display for AppError {
@@ -30,13 +99,59 @@ impl fmt::Display for AppError {
             AppError::NotFound(msg) => {
                 write!(f, "Not found: {}", msg)
             }
+            AppError::UnknownPokemon { name, suggestions } => {
+                write!(f, "Not found: Pokemon '{}' not found", name)?;
+                if !suggestions.is_empty() {
+                    write!(
+                        f,
+                        " (did you mean {}?)",
+                        suggestions.join(", ")
+                    )?;
+                }
+                Ok(())
+            }
+            AppError::BadRequest(msg) => {
+                write!(f, "Bad request: {}", msg)
+            }
+            AppError::Unauthorized(msg) => {
+                write!(f, "Unauthorized: {}", msg)
+            }
+            AppError::Forbidden(msg) => {
+                write!(f, "Forbidden: {}", msg)
+            }
+            AppError::Conflict(msg) => write!(f, "Conflict: {}", msg),
+            AppError::PayloadTooLarge(msg) => {
+                write!(f, "Payload too large: {}", msg)
+            }
+            AppError::TooManyRequests(msg) => {
+                write!(f, "Too many requests: {}", msg)
+            }
             AppError::ExternalApi(msg) => {
                 write!(f, "External API error: {}", msg)
             }
+            AppError::UpstreamSchema(msg) => {
+                write!(f, "Upstream schema error: {}", msg)
+            }
             AppError::Internal(msg) => {
                 write!(f, "Internal error: {}", msg)
             }
             AppError::Timeout(msg) => write!(f, "Timeout: {}", msg),
+            AppError::FeatureDisabled(feature) => {
+                write!(f, "Feature disabled: {}", feature)
+            }
+            AppError::NotReady { message, .. } => {
+                write!(f, "Not ready: {}", message)
+            }
+            AppError::ValidationError(fields) => {
+                write!(f, "Validation failed: ")?;
+                for (i, field) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "{}: {}", field.field, field.message)?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -49,7 +164,30 @@ impl IntoResponse for AppError {
             AppError::NotFound(msg) => {
                 (StatusCode::NOT_FOUND, msg.clone())
             }
-            AppError::ExternalApi(msg) => {
+            AppError::UnknownPokemon { name, .. } => (
+                StatusCode::NOT_FOUND,
+                format!("Pokemon '{}' not found", name),
+            ),
+            AppError::BadRequest(msg) => {
+                (StatusCode::BAD_REQUEST, msg.clone())
+            }
+            AppError::Unauthorized(msg) => {
+                (StatusCode::UNAUTHORIZED, msg.clone())
+            }
+            AppError::Forbidden(msg) => {
+                (StatusCode::FORBIDDEN, msg.clone())
+            }
+            AppError::Conflict(msg) => {
+                (StatusCode::CONFLICT, msg.clone())
+            }
+            AppError::PayloadTooLarge(msg) => {
+                (StatusCode::PAYLOAD_TOO_LARGE, msg.clone())
+            }
+            AppError::TooManyRequests(msg) => {
+                (StatusCode::TOO_MANY_REQUESTS, msg.clone())
+            }
+            AppError::ExternalApi(msg)
+            | AppError::UpstreamSchema(msg) => {
                 (StatusCode::BAD_GATEWAY, msg.clone())
             }
             AppError::Internal(msg) => {
@@ -58,6 +196,60 @@ impl IntoResponse for AppError {
             AppError::Timeout(msg) => {
                 (StatusCode::GATEWAY_TIMEOUT, msg.clone())
             }
+            AppError::FeatureDisabled(feature) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                format!(
+                    "The {} feature is temporarily disabled",
+                    feature
+                ),
+            ),
+            AppError::ValidationError(_) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "Request validation failed".to_string(),
+            ),
+            AppError::NotReady { message, .. } => {
+                (StatusCode::SERVICE_UNAVAILABLE, message.clone())
+            }
+        };
+        let code = match &self {
+            AppError::FeatureDisabled(feature) => {
+                Some(format!("{}_disabled", feature))
+            }
+            AppError::ValidationError(_) => {
+                Some("validation_failed".to_string())
+            }
+            _ => None,
+        };
+        let suggestions = match &self {
+            AppError::UnknownPokemon { suggestions, .. } => {
+                suggestions.clone()
+            }
+            _ => Vec::new(),
+        };
+        let fields = match &self {
+            AppError::ValidationError(fields) => fields.clone(),
+            _ => Vec::new(),
+        };
+
+        // Messages without a catalog entry are replaced by the
+        // generic one of their status and kept as details.
+        let message = match &self {
+            AppError::UnknownPokemon { name, .. } => {
+                Message::UnknownPokemon { name }
+            }
+            AppError::FeatureDisabled(feature) => {
+                Message::FeatureDisabled { feature }
+            }
+            _ => Message::Status(status),
+        };
+        let locale = i18n::current();
+        let translated = message.translate(locale);
+        let (error_message, details) = match (translated, message) {
+            (Some(translated), Message::Status(_)) => {
+                (translated, Some(error_message))
+            }
+            (Some(translated), _) => (translated, None),
+            (None, _) => (error_message, None),
         };
 
         // Log the error
@@ -65,9 +257,25 @@ impl IntoResponse for AppError {
 
         let body = Json(ErrorResponse {
             error: error_message,
-            details: None,
+            details,
+            code,
+            suggestions,
+            fields,
         });
 
-        (status, body).into_response()
+        let mut response = (status, body).into_response();
+        if let AppError::NotReady { retry_after, .. } = &self {
+            response.headers_mut().insert(
+                header::RETRY_AFTER,
+                HeaderValue::from(*retry_after),
+            );
+        }
+        if locale != i18n::Locale::default() {
+            response.headers_mut().insert(
+                header::CONTENT_LANGUAGE,
+                HeaderValue::from_static(locale.tag()),
+            );
+        }
+        response
     }
 }
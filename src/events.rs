@@ -0,0 +1,482 @@
+use crate::analytics::PokemonUsage;
+use crate::config::Secret;
+use crate::error::{AppError, Result};
+use crate::http::Upstream;
+use axum::async_trait;
+use pokedex_rs::webhook::{SIGNATURE_HEADER, SigningKeys};
+use reqwest::Client;
+use serde::Serialize;
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+/// Events waiting for a sink before new ones are dropped.
+const QUEUE_SIZE: usize = 1024;
+
+/// Events handed to a sink at once.
+const BATCH_SIZE: usize = 100;
+
+/// Something the service did that analytics pipelines may want.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(tag = "type")]
+pub enum Event {
+    /// A Pokemon was returned by `/pokemon/{name}` or the translated
+    /// endpoint.
+    PokemonServed {
+        name: String,
+        translated: bool,
+        /// Served from an expired cache entry.
+        stale: bool,
+    },
+    /// A translation failed and the original description was served.
+    TranslationFallback { pokemon: String, error: String },
+    UpstreamCircuitOpened {
+        upstream: String,
+        cooldown_secs: u64,
+    },
+    /// The most requested Pokemon, published by the `stats_rollup`
+    /// job.
+    UsageRollup { top: Vec<PokemonUsage> },
+}
+
+impl Event {
+    /// `pokemon_served`, used as the Kafka key and NATS subject.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Event::PokemonServed { .. } => "pokemon_served",
+            Event::TranslationFallback { .. } => {
+                "translation_fallback"
+            }
+            Event::UpstreamCircuitOpened { .. } => {
+                "upstream_circuit_opened"
+            }
+            Event::UsageRollup { .. } => "usage_rollup",
+        }
+    }
+}
+
+/// An event as published, e.g.
+/// `{"at":1760000000000,"type":"PokemonServed","name":"pikachu",...}`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Envelope {
+    /// Unix milliseconds.
+    pub at: u64,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+/// Where events are delivered.
+#[async_trait]
+pub trait EventSink: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn publish(&self, events: &[Envelope]) -> Result<()>;
+}
+
+/// Publishes events to every sink in the background. Each sink has
+/// its own queue, so a slow one only delays itself; events are
+/// dropped when its queue is full rather than slowing requests down.
+/// The default bus has no sinks and ignores events.
+#[derive(Clone, Default)]
+pub struct EventBus {
+    queues: Vec<(&'static str, mpsc::Sender<Envelope>)>,
+}
+
+impl EventBus {
+    pub fn start(sinks: Vec<Arc<dyn EventSink>>) -> Self {
+        let queues = sinks
+            .into_iter()
+            .map(|sink| {
+                let (sender, receiver) = mpsc::channel(QUEUE_SIZE);
+                let name = sink.name();
+                tokio::spawn(deliver(sink, receiver));
+                (name, sender)
+            })
+            .collect();
+        Self { queues }
+    }
+
+    pub fn publish(&self, event: Event) {
+        if self.queues.is_empty() {
+            return;
+        }
+        let envelope = Envelope {
+            at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            event,
+        };
+        for (sink, queue) in &self.queues {
+            if queue.try_send(envelope.clone()).is_err() {
+                debug!(
+                    sink,
+                    kind = envelope.event.kind(),
+                    "Dropped an event"
+                );
+            }
+        }
+    }
+}
+
+async fn deliver(
+    sink: Arc<dyn EventSink>,
+    mut queue: mpsc::Receiver<Envelope>,
+) {
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    while queue.recv_many(&mut batch, BATCH_SIZE).await > 0 {
+        if let Err(e) = sink.publish(&batch).await {
+            warn!(sink = sink.name(), count = batch.len(), error = %e, "Failed to publish events");
+        }
+        batch.clear();
+    }
+}
+
+/// An event sink selected with `EVENT_SINKS`.
+#[derive(Debug, Clone)]
+pub enum SinkConfig {
+    Log,
+    /// Posts batches as JSON arrays, signed with the keys when any.
+    Webhook {
+        url: String,
+        keys: SigningKeys,
+    },
+    /// Requires the `kafka` feature.
+    #[cfg_attr(not(feature = "kafka"), allow(dead_code))]
+    Kafka {
+        brokers: String,
+        topic: String,
+    },
+    /// Requires the `nats` feature. The URL may carry credentials.
+    #[cfg_attr(not(feature = "nats"), allow(dead_code))]
+    Nats {
+        url: Secret,
+        subject: String,
+    },
+}
+
+impl SinkConfig {
+    /// Connects to the sink.
+    pub async fn open(
+        &self,
+        client: Client,
+    ) -> Result<Arc<dyn EventSink>> {
+        Ok(match self {
+            SinkConfig::Log => Arc::new(LogSink),
+            SinkConfig::Webhook { url, keys } => {
+                info!(%url, "Publishing events to a webhook");
+                Arc::new(WebhookSink {
+                    upstream: Upstream::new("Event webhook", client),
+                    url: url.clone(),
+                    keys: keys.clone(),
+                })
+            }
+            #[cfg(feature = "kafka")]
+            SinkConfig::Kafka { brokers, topic } => {
+                info!(%brokers, %topic, "Publishing events to Kafka");
+                Arc::new(kafka::KafkaSink::connect(brokers, topic)?)
+            }
+            #[cfg(feature = "nats")]
+            SinkConfig::Nats { url, subject } => {
+                info!(%subject, "Publishing events to NATS");
+                Arc::new(
+                    nats::NatsSink::connect(&url.0, subject).await?,
+                )
+            }
+            #[cfg(not(feature = "kafka"))]
+            SinkConfig::Kafka { .. } => {
+                return Err(not_built("kafka"));
+            }
+            #[cfg(not(feature = "nats"))]
+            SinkConfig::Nats { .. } => return Err(not_built("nats")),
+        })
+    }
+}
+
+#[cfg(not(all(feature = "kafka", feature = "nats")))]
+fn not_built(sink: &str) -> AppError {
+    AppError::Internal(format!(
+        "The {} event sink requires building with the `{}` feature",
+        sink, sink
+    ))
+}
+
+fn sink_error(sink: &str, e: impl std::fmt::Display) -> AppError {
+    AppError::ExternalApi(format!("{} sink error: {}", sink, e))
+}
+
+/// Writes each event to the log, for development or log-based
+/// pipelines.
+struct LogSink;
+
+#[async_trait]
+impl EventSink for LogSink {
+    fn name(&self) -> &'static str {
+        "log"
+    }
+
+    async fn publish(&self, events: &[Envelope]) -> Result<()> {
+        for envelope in events {
+            let event = serde_json::to_string(envelope)
+                .map_err(|e| sink_error("log", e))?;
+            info!(kind = envelope.event.kind(), %event, "Domain event");
+        }
+        Ok(())
+    }
+}
+
+struct WebhookSink {
+    upstream: Upstream,
+    url: String,
+    keys: SigningKeys,
+}
+
+#[async_trait]
+impl EventSink for WebhookSink {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    async fn publish(&self, events: &[Envelope]) -> Result<()> {
+        let body = serde_json::to_vec(events)
+            .map_err(|e| sink_error("webhook", e))?;
+        let mut request = self.upstream.post(&self.url).header(
+            reqwest::header::CONTENT_TYPE,
+            "application/json",
+        );
+        if !self.keys.is_empty() {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            request = request
+                .header(SIGNATURE_HEADER, self.keys.sign(&body, now));
+        }
+        let response = self.upstream.send(request.body(body)).await?;
+        if !response.status().is_success() {
+            return Err(sink_error(
+                "webhook",
+                format!("status {}", response.status()),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "kafka")]
+mod kafka {
+    use super::{Envelope, EventSink, sink_error};
+    use crate::error::Result;
+    use axum::async_trait;
+    use rdkafka::{
+        ClientConfig,
+        producer::{FutureProducer, FutureRecord},
+        util::Timeout,
+    };
+
+    pub struct KafkaSink {
+        producer: FutureProducer,
+        topic: String,
+    }
+
+    impl KafkaSink {
+        pub fn connect(brokers: &str, topic: &str) -> Result<Self> {
+            let producer = ClientConfig::new()
+                .set("bootstrap.servers", brokers)
+                .set("message.timeout.ms", "5000")
+                .create()
+                .map_err(|e| sink_error("Kafka", e))?;
+            Ok(Self {
+                producer,
+                topic: topic.to_string(),
+            })
+        }
+    }
+
+    #[async_trait]
+    impl EventSink for KafkaSink {
+        fn name(&self) -> &'static str {
+            "kafka"
+        }
+
+        /// One message per event, keyed by its kind.
+        async fn publish(&self, events: &[Envelope]) -> Result<()> {
+            for envelope in events {
+                let payload = serde_json::to_vec(envelope)
+                    .map_err(|e| sink_error("Kafka", e))?;
+                self.producer
+                    .send(
+                        FutureRecord::to(&self.topic)
+                            .key(envelope.event.kind())
+                            .payload(&payload),
+                        Timeout::Never,
+                    )
+                    .await
+                    .map_err(|(e, _)| sink_error("Kafka", e))?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(feature = "nats")]
+mod nats {
+    use super::{Envelope, EventSink, sink_error};
+    use crate::error::Result;
+    use axum::async_trait;
+
+    pub struct NatsSink {
+        client: async_nats::Client,
+        subject: String,
+    }
+
+    impl NatsSink {
+        pub async fn connect(
+            url: &str,
+            subject: &str,
+        ) -> Result<Self> {
+            Ok(Self {
+                client: async_nats::connect(url)
+                    .await
+                    .map_err(|e| sink_error("NATS", e))?,
+                subject: subject.to_string(),
+            })
+        }
+    }
+
+    #[async_trait]
+    impl EventSink for NatsSink {
+        fn name(&self) -> &'static str {
+            "nats"
+        }
+
+        /// One message per event, on `{subject}.{kind}`.
+        async fn publish(&self, events: &[Envelope]) -> Result<()> {
+            for envelope in events {
+                let payload = serde_json::to_vec(envelope)
+                    .map_err(|e| sink_error("NATS", e))?;
+                self.client
+                    .publish(
+                        format!(
+                            "{}.{}",
+                            self.subject,
+                            envelope.event.kind()
+                        ),
+                        payload.into(),
+                    )
+                    .await
+                    .map_err(|e| sink_error("NATS", e))?;
+            }
+            self.client
+                .flush()
+                .await
+                .map_err(|e| sink_error("NATS", e))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::Mutex, time::Duration};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[derive(Default)]
+    struct MemorySink(Mutex<Vec<Envelope>>);
+
+    #[async_trait]
+    impl EventSink for MemorySink {
+        fn name(&self) -> &'static str {
+            "memory"
+        }
+
+        async fn publish(&self, events: &[Envelope]) -> Result<()> {
+            self.0.lock().unwrap().extend_from_slice(events);
+            Ok(())
+        }
+    }
+
+    fn served(name: &str) -> Event {
+        Event::PokemonServed {
+            name: name.to_string(),
+            translated: false,
+            stale: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bus_delivers_to_every_sink() {
+        let (first, second) = (
+            Arc::new(MemorySink::default()),
+            Arc::new(MemorySink::default()),
+        );
+        let bus =
+            EventBus::start(vec![first.clone(), second.clone()]);
+        bus.publish(served("pikachu"));
+        bus.publish(Event::UpstreamCircuitOpened {
+            upstream: "PokeAPI".to_string(),
+            cooldown_secs: 30,
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        for sink in [first, second] {
+            let events = sink.0.lock().unwrap();
+            assert_eq!(events.len(), 2);
+            assert_eq!(events[0].event, served("pikachu"));
+        }
+        // Without sinks, events are ignored.
+        EventBus::default().publish(served("mew"));
+    }
+
+    #[tokio::test]
+    async fn test_webhook_sink_signs_batches() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/events"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let keys = SigningKeys::parse("whsec");
+        let sink = SinkConfig::Webhook {
+            url: format!("{}/events", server.uri()),
+            keys: keys.clone(),
+        }
+        .open(Client::new())
+        .await
+        .unwrap();
+
+        let envelope = Envelope {
+            at: 1,
+            event: served("pikachu"),
+        };
+        sink.publish(&[envelope]).await.unwrap();
+
+        let request = &server.received_requests().await.unwrap()[0];
+        let body: serde_json::Value =
+            serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!([{
+                "at": 1,
+                "type": "PokemonServed",
+                "name": "pikachu",
+                "translated": false,
+                "stale": false
+            }])
+        );
+        let signature =
+            request.headers[SIGNATURE_HEADER].to_str().unwrap();
+        let at = signature[2..signature.find(',').unwrap()]
+            .parse()
+            .unwrap();
+        assert!(
+            keys.verify(signature, &request.body, at, Duration::ZERO)
+                .is_ok()
+        );
+    }
+}
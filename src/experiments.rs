@@ -0,0 +1,175 @@
+use crate::translation::Style;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+
+/// Tags the translated responses with the variant they were served.
+pub const VARIANT_HEADER: &str = "x-pokedex-variant";
+
+/// The variant of the requests outside every experiment.
+pub const CONTROL: &str = "control";
+
+/// What an experiment changes in the translated description.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Variant {
+    /// A fixed style instead of the habitat and legendary rule.
+    Style(Style),
+    /// The first sentence of the description only.
+    Short,
+}
+
+impl Variant {
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "short" => Some(Variant::Short),
+            style => Style::parse(style).map(Variant::Style),
+        }
+    }
+}
+
+/// An experiment serving `variant` to `percent` of the clients.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Experiment {
+    pub name: String,
+    pub percent: u8,
+    pub variant: Variant,
+}
+
+/// The experiments of `EXPERIMENTS`. They take consecutive slices
+/// of the clients, so that a client is in at most one of them, and
+/// the clients left over form the control group.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Experiments {
+    experiments: Vec<Experiment>,
+}
+
+impl Experiments {
+    /// Parses comma-separated `name:percent:variant` entries, such
+    /// as `yoda-all:10:yoda,short:5:short`.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let mut experiments = Vec::new();
+        let mut names = HashSet::new();
+        let mut total = 0;
+        for entry in value.split(',').filter(|e| !e.trim().is_empty())
+        {
+            let parts: Vec<&str> =
+                entry.split(':').map(str::trim).collect();
+            let [name, percent, variant] = parts[..] else {
+                return Err(format!(
+                    "'{}' is not name:percent:variant",
+                    entry.trim()
+                ));
+            };
+            if name.is_empty() || name == CONTROL {
+                return Err(format!("invalid name '{}'", name));
+            }
+            if !names.insert(name) {
+                return Err(format!(
+                    "duplicate experiment '{}'",
+                    name
+                ));
+            }
+            let percent = percent
+                .parse::<u8>()
+                .ok()
+                .filter(|percent| (1..=100).contains(percent))
+                .ok_or_else(|| {
+                    format!("percent of '{}' must be 1-100", name)
+                })?;
+            let variant = Variant::parse(variant).ok_or_else(|| {
+                format!(
+                    "unknown variant '{}', expected yoda, shakespeare or short",
+                    variant
+                )
+            })?;
+            total += percent as u32;
+            experiments.push(Experiment {
+                name: name.to_string(),
+                percent,
+                variant,
+            });
+        }
+        if total > 100 {
+            return Err(format!(
+                "experiments take {}% of the clients",
+                total
+            ));
+        }
+        Ok(Self { experiments })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.experiments.is_empty()
+    }
+
+    /// The experiment `client` is in, `None` for the control group.
+    /// The same client always lands in the same one.
+    pub fn assign(&self, client: &str) -> Option<&Experiment> {
+        let bucket = bucket(client);
+        let mut start = 0;
+        self.experiments.iter().find(|experiment| {
+            start += experiment.percent;
+            bucket < start
+        })
+    }
+}
+
+/// A stable bucket in `0..100` for `client`.
+fn bucket(client: &str) -> u8 {
+    let digest = Sha256::digest(client.as_bytes());
+    let mut prefix = [0; 8];
+    prefix.copy_from_slice(&digest[..8]);
+    (u64::from_be_bytes(prefix) % 100) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_experiments() {
+        let experiments =
+            Experiments::parse("yoda-all:10:yoda, short:5:short")
+                .unwrap();
+        assert_eq!(
+            experiments.experiments[1],
+            Experiment {
+                name: "short".to_string(),
+                percent: 5,
+                variant: Variant::Short,
+            }
+        );
+        assert!(Experiments::parse("").unwrap().is_empty());
+        assert!(Experiments::parse("a:10").is_err());
+        assert!(Experiments::parse("a:0:yoda").is_err());
+        assert!(Experiments::parse("a:10:klingon").is_err());
+        assert!(Experiments::parse("a:10:yoda,a:5:short").is_err());
+        assert!(Experiments::parse("control:10:yoda").is_err());
+        assert!(Experiments::parse("a:60:yoda,b:50:short").is_err());
+    }
+
+    #[test]
+    fn test_assignment_is_stable_and_proportional() {
+        let experiments =
+            Experiments::parse("yoda-all:20:yoda,short:30:short")
+                .unwrap();
+        let client = "user:ash";
+        assert_eq!(
+            experiments.assign(client),
+            experiments.assign(client)
+        );
+
+        let mut counts = [0; 3];
+        for i in 0..10_000 {
+            let slot = match experiments.assign(&format!("ip:{}", i))
+            {
+                Some(e) if e.name == "yoda-all" => 0,
+                Some(_) => 1,
+                None => 2,
+            };
+            counts[slot] += 1;
+        }
+        assert!((1_800..2_200).contains(&counts[0]), "{:?}", counts);
+        assert!((2_800..3_200).contains(&counts[1]), "{:?}", counts);
+        assert!((4_800..5_200).contains(&counts[2]), "{:?}", counts);
+    }
+}
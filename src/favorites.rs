@@ -0,0 +1,148 @@
+use crate::error::Result;
+use crate::lang::Lang;
+use crate::pokemon::{Pokemon, PokemonService};
+use crate::storage::Storage;
+use futures::future::try_join_all;
+use std::sync::Arc;
+use tracing::instrument;
+
+const NAMESPACE: &str = "favorites";
+
+/// Per-user favorite Pokemon, persisted through the storage layer.
+pub struct FavoritesService {
+    pokemon_service: Arc<PokemonService>,
+    storage: Arc<dyn Storage>,
+}
+
+impl FavoritesService {
+    pub fn new(
+        pokemon_service: Arc<PokemonService>,
+        storage: Arc<dyn Storage>,
+    ) -> Self {
+        Self {
+            pokemon_service,
+            storage,
+        }
+    }
+
+    /// Adds a favorite after checking the Pokemon exists.
+    #[instrument(skip(self))]
+    pub async fn add(&self, user_id: &str, name: &str) -> Result<()> {
+        let pokemon = self
+            .pokemon_service
+            .get_pokemon(name, &Lang::default())
+            .await?;
+
+        let mut names = self.names(user_id)?;
+        if !names.contains(&pokemon.name) {
+            names.push(pokemon.name);
+            self.storage.put_as(NAMESPACE, user_id, &names, None)?;
+        }
+        Ok(())
+    }
+
+    /// Removes a favorite, returning whether it was present.
+    #[instrument(skip(self))]
+    pub fn remove(&self, user_id: &str, name: &str) -> Result<bool> {
+        let mut names = self.names(user_id)?;
+        let before = names.len();
+        names.retain(|favorite| !favorite.eq_ignore_ascii_case(name));
+        if names.len() == before {
+            return Ok(false);
+        }
+        self.storage.put_as(NAMESPACE, user_id, &names, None)?;
+        Ok(true)
+    }
+
+    /// Returns the user's favorites, hydrated through the Pokemon
+    /// cache.
+    #[instrument(skip(self, lang))]
+    pub async fn list(
+        &self,
+        user_id: &str,
+        lang: &Lang,
+    ) -> Result<Vec<Pokemon>> {
+        let names = self.names(user_id)?;
+        try_join_all(
+            names.iter().map(|name| {
+                self.pokemon_service.get_pokemon(name, lang)
+            }),
+        )
+        .await
+    }
+
+    fn names(&self, user_id: &str) -> Result<Vec<String>> {
+        Ok(self
+            .storage
+            .get_as(NAMESPACE, user_id)?
+            .unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http;
+    use crate::pokeapi::PokeApiClient;
+    use crate::storage::MemoryStorage;
+    use std::time::Duration;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_add_list_remove() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/pokemon-species/pikachu"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({
+                    "name": "pikachu",
+                    "habitat": null,
+                    "flavor_text_entries": [],
+                    "is_legendary": false,
+                    "is_mythical": false,
+                    "is_baby": false
+                }),
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/pokemon-species/missingno"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+
+        let favorites = FavoritesService::new(
+            Arc::new(PokemonService::new(
+                PokeApiClient::new(
+                    http::build_client(&http::ClientSettings::new(
+                        Duration::from_secs(5),
+                    )),
+                    server.uri(),
+                ),
+                Duration::from_secs(60),
+            )),
+            Arc::new(MemoryStorage::new()),
+        );
+
+        favorites.add("ash", "Pikachu").await.unwrap();
+        favorites.add("ash", "pikachu").await.unwrap();
+        assert!(favorites.add("ash", "missingno").await.is_err());
+
+        let list =
+            favorites.list("ash", &Lang::default()).await.unwrap();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].name, "pikachu");
+        assert!(
+            favorites
+                .list("misty", &Lang::default())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        assert!(favorites.remove("ash", "PIKACHU").unwrap());
+        assert!(!favorites.remove("ash", "pikachu").unwrap());
+    }
+}
@@ -0,0 +1,165 @@
+use crate::error::AppError;
+use axum::{
+    body::{Body, to_bytes},
+    extract::{Query, Request, State},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use tracing::warn;
+
+/// Naming of the keys of JSON responses. The models are written in
+/// snake_case; other cases are produced by renaming the keys of
+/// every response body.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FieldCase {
+    #[default]
+    Snake,
+    Camel,
+}
+
+impl FieldCase {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.trim().to_lowercase().as_str() {
+            "snake" => Ok(FieldCase::Snake),
+            "camel" => Ok(FieldCase::Camel),
+            _ => Err(format!(
+                "unknown case '{}', expected snake or camel",
+                value
+            )),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct CaseParams {
+    case: Option<String>,
+}
+
+/// Renames the keys of JSON responses to `?case=`, or to the
+/// `RESPONSE_CASE` default.
+pub async fn middleware(
+    State(default): State<FieldCase>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let case = match Query::<CaseParams>::try_from_uri(request.uri())
+        .ok()
+        .and_then(|Query(params)| params.case)
+    {
+        Some(case) => match FieldCase::parse(&case) {
+            Ok(case) => case,
+            Err(e) => {
+                return AppError::BadRequest(format!(
+                    "case is invalid: {}",
+                    e
+                ))
+                .into_response();
+            }
+        },
+        None => default,
+    };
+
+    let response = next.run(request).await;
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value.starts_with("application/json")
+                || value.ends_with("+json")
+        });
+    if case == FieldCase::Snake || !is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let body = match to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => {
+            warn!(error = %e, "Failed to buffer response body");
+            return AppError::Internal(
+                "Failed to buffer response body".to_string(),
+            )
+            .into_response();
+        }
+    };
+    let body = match serde_json::from_slice::<Value>(&body) {
+        Ok(value) => serde_json::to_vec(&rename_keys(value))
+            .unwrap_or_default(),
+        // Not actually JSON: passed through untouched.
+        Err(_) => body.to_vec(),
+    };
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(body))
+}
+
+fn rename_keys(value: Value) -> Value {
+    match value {
+        Value::Object(object) => Value::Object(
+            object
+                .into_iter()
+                .map(|(key, value)| {
+                    (camel_case(&key), rename_keys(value))
+                })
+                .collect::<Map<_, _>>(),
+        ),
+        Value::Array(values) => Value::Array(
+            values.into_iter().map(rename_keys).collect(),
+        ),
+        value => value,
+    }
+}
+
+/// `is_legendary` becomes `isLegendary`; leading underscores are
+/// kept.
+fn camel_case(key: &str) -> String {
+    let trimmed = key.trim_start_matches('_');
+    let mut camel = key[..key.len() - trimmed.len()].to_string();
+    let mut upper = false;
+    for c in trimmed.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            camel.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            camel.push(c);
+        }
+    }
+    camel
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_renames_nested_keys_to_camel_case() {
+        let value = json!({
+            "is_legendary": true,
+            "pokemon": [{"base_stats": {"special_attack": 50}}],
+            "_links": null,
+            "name": "mr_mime",
+        });
+        assert_eq!(
+            rename_keys(value),
+            json!({
+                "isLegendary": true,
+                "pokemon": [{"baseStats": {"specialAttack": 50}}],
+                "_links": null,
+                "name": "mr_mime",
+            })
+        );
+    }
+
+    #[test]
+    fn test_parses_case() {
+        assert_eq!(FieldCase::parse("Camel"), Ok(FieldCase::Camel));
+        assert_eq!(FieldCase::parse("snake"), Ok(FieldCase::Snake));
+        assert!(FieldCase::parse("kebab").is_err());
+    }
+}
@@ -0,0 +1,272 @@
+use crate::error::{AppError, Result};
+use reqwest::{Request, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::PathBuf;
+use tracing::{debug, warn};
+
+/// How upstream responses are sourced, as configured by
+/// `UPSTREAM_MODE` (`live`, `record` or `replay`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FixtureMode {
+    /// Calls the upstream and saves every response to disk.
+    Record,
+    /// Serves saved responses without touching the network.
+    Replay,
+}
+
+impl FixtureMode {
+    /// `None` means live traffic without fixtures.
+    pub fn parse(
+        value: &str,
+    ) -> std::result::Result<Option<Self>, String> {
+        match value.trim().to_lowercase().as_str() {
+            "live" => Ok(None),
+            "record" => Ok(Some(FixtureMode::Record)),
+            "replay" => Ok(Some(FixtureMode::Replay)),
+            other => Err(format!(
+                "unknown mode '{}', expected live, record or replay",
+                other
+            )),
+        }
+    }
+}
+
+/// Identifies an upstream request: its method, URL and body.
+pub struct FixtureKey {
+    method: String,
+    url: String,
+    body: Vec<u8>,
+}
+
+impl FixtureKey {
+    pub fn of(request: &Request) -> Self {
+        Self {
+            method: request.method().to_string(),
+            url: request.url().to_string(),
+            body: request
+                .body()
+                .and_then(|body| body.as_bytes())
+                .unwrap_or_default()
+                .to_vec(),
+        }
+    }
+
+    /// A readable, filesystem-safe name, made unique by a hash of the
+    /// whole key.
+    fn file_name(&self) -> String {
+        let url = self
+            .url
+            .split_once("://")
+            .map_or(self.url.as_str(), |(_, rest)| rest);
+        let readable: String = format!("{}-{}", self.method, url)
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                    c.to_ascii_lowercase()
+                } else {
+                    '_'
+                }
+            })
+            .take(120)
+            .collect();
+
+        let mut hash = Fnv1a::default();
+        hash.write(self.method.as_bytes());
+        hash.write(self.url.as_bytes());
+        hash.write(&self.body);
+        format!("{}-{:016x}.json", readable, hash.0)
+    }
+}
+
+/// A recorded upstream response. JSON bodies are stored as JSON so
+/// fixtures stay readable and editable.
+#[derive(Serialize, Deserialize)]
+struct Fixture {
+    method: String,
+    url: String,
+    status: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    json: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+}
+
+/// Upstream responses recorded to, or replayed from, a directory.
+pub struct Fixtures {
+    mode: FixtureMode,
+    dir: PathBuf,
+}
+
+impl Fixtures {
+    pub fn new(mode: FixtureMode, dir: PathBuf) -> Self {
+        Self { mode, dir }
+    }
+
+    pub fn mode(&self) -> FixtureMode {
+        self.mode
+    }
+
+    /// Loads the response recorded for `key`.
+    pub async fn replay(
+        &self,
+        key: &FixtureKey,
+    ) -> Result<(StatusCode, Vec<u8>)> {
+        let path = self.dir.join(key.file_name());
+        debug!(path = %path.display(), "Replaying fixture");
+
+        let contents =
+            tokio::fs::read(&path).await.map_err(|_| {
+                AppError::ExternalApi(format!(
+                    "No fixture recorded for {} {}",
+                    key.method, key.url
+                ))
+            })?;
+        let fixture: Fixture = serde_json::from_slice(&contents)
+            .map_err(|e| {
+                AppError::Internal(format!(
+                    "Invalid fixture {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+
+        let status =
+            StatusCode::from_u16(fixture.status).map_err(|e| {
+                AppError::Internal(format!(
+                    "Invalid status in fixture {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+        let body = match (fixture.json, fixture.text) {
+            (Some(json), _) => json.to_string().into_bytes(),
+            (None, text) => text.unwrap_or_default().into_bytes(),
+        };
+        Ok((status, body))
+    }
+
+    /// Saves a response for `key`. Failures are logged rather than
+    /// failing the request that was recorded.
+    pub async fn record(
+        &self,
+        key: &FixtureKey,
+        status: StatusCode,
+        body: &[u8],
+    ) {
+        let (json, text) = match serde_json::from_slice(body) {
+            Ok(json) => (Some(json), None),
+            Err(_) => (
+                None,
+                Some(String::from_utf8_lossy(body).into_owned()),
+            ),
+        };
+        let fixture = Fixture {
+            method: key.method.clone(),
+            url: key.url.clone(),
+            status: status.as_u16(),
+            json,
+            text,
+        };
+
+        let path = self.dir.join(key.file_name());
+        let result = async {
+            tokio::fs::create_dir_all(&self.dir).await?;
+            let contents = serde_json::to_vec_pretty(&fixture)?;
+            tokio::fs::write(&path, contents).await
+        }
+        .await;
+        match result {
+            Ok(()) => {
+                debug!(path = %path.display(), "Recorded fixture")
+            }
+            Err(e) => {
+                warn!(path = %path.display(), error = %e, "Failed to record fixture")
+            }
+        }
+    }
+}
+
+/// 64-bit FNV-1a, used instead of `DefaultHasher` because fixture
+/// names must stay stable across Rust releases.
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(method: &str, url: &str, body: &str) -> FixtureKey {
+        FixtureKey {
+            method: method.to_string(),
+            url: url.to_string(),
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!(FixtureMode::parse("live"), Ok(None));
+        assert_eq!(
+            FixtureMode::parse("Replay"),
+            Ok(Some(FixtureMode::Replay))
+        );
+        assert!(FixtureMode::parse("mock").is_err());
+    }
+
+    #[test]
+    fn test_file_name_is_readable_and_keyed_by_body() {
+        let get = key(
+            "GET",
+            "https://pokeapi.co/api/v2/pokemon-species/pikachu",
+            "",
+        );
+        assert!(get.file_name().starts_with(
+            "get-pokeapi.co_api_v2_pokemon-species_pikachu-"
+        ));
+
+        let yoda =
+            key("POST", "http://t/yoda.json", "{\"text\":\"a\"}");
+        let other =
+            key("POST", "http://t/yoda.json", "{\"text\":\"b\"}");
+        assert_ne!(yoda.file_name(), other.file_name());
+    }
+
+    #[tokio::test]
+    async fn test_record_then_replay() {
+        let dir = std::env::temp_dir()
+            .join(format!("pokedex-fixtures-{}", std::process::id()));
+        let recorder =
+            Fixtures::new(FixtureMode::Record, dir.clone());
+        let key = key("GET", "http://pokeapi/pokemon/25", "");
+        recorder
+            .record(&key, StatusCode::OK, br#"{"name":"pikachu"}"#)
+            .await;
+
+        let replayer =
+            Fixtures::new(FixtureMode::Replay, dir.clone());
+        let (status, body) = replayer.replay(&key).await.unwrap();
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, br#"{"name":"pikachu"}"#);
+
+        let missing =
+            self::key("GET", "http://pokeapi/pokemon/1", "");
+        assert!(replayer.replay(&missing).await.is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
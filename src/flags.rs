@@ -0,0 +1,232 @@
+use crate::error::{AppError, Result};
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+use tracing::{info, warn};
+
+/// An endpoint group that can be switched off at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Feature {
+    /// `/pokemon/translated/{name}`, translated batches and moves,
+    /// `translate-generation` jobs and `/translate`.
+    Translation,
+    /// `POST /pokemon/batch`.
+    Batch,
+    /// The `/admin` routes of admin listeners.
+    Admin,
+}
+
+impl Feature {
+    pub fn name(self) -> &'static str {
+        match self {
+            Feature::Translation => "translation",
+            Feature::Batch => "batch",
+            Feature::Admin => "admin",
+        }
+    }
+
+    fn parse(value: &str) -> std::result::Result<Self, String> {
+        match value.trim() {
+            "translation" => Ok(Feature::Translation),
+            "batch" => Ok(Feature::Batch),
+            "admin" => Ok(Feature::Admin),
+            other => Err(format!(
+                "unknown feature '{}', expected translation, batch or admin",
+                other
+            )),
+        }
+    }
+
+    /// Parses a comma-separated list such as `translation,batch`.
+    pub fn parse_list(
+        value: &str,
+    ) -> std::result::Result<Vec<Self>, String> {
+        value
+            .split(',')
+            .filter(|feature| !feature.trim().is_empty())
+            .map(Feature::parse)
+            .collect()
+    }
+}
+
+/// Per-endpoint switches. Features listed in `DISABLED_FEATURES` are
+/// off by default; `FEATURE_FLAGS_FILE`, a JSON object such as
+/// `{"translation": false}`, overrides them and is re-read whenever
+/// it changes.
+pub struct FeatureFlags {
+    defaults: Vec<Feature>,
+    file: Option<PathBuf>,
+    disabled: RwLock<BTreeSet<Feature>>,
+}
+
+impl FeatureFlags {
+    pub fn new(
+        defaults: Vec<Feature>,
+        file: Option<PathBuf>,
+    ) -> Self {
+        let disabled =
+            RwLock::new(defaults.iter().copied().collect());
+        Self {
+            defaults,
+            file,
+            disabled,
+        }
+    }
+
+    pub fn is_enabled(&self, feature: Feature) -> bool {
+        !self.disabled.read().unwrap().contains(&feature)
+    }
+
+    /// Fails with a `503` when `feature` is disabled.
+    pub fn check(&self, feature: Feature) -> Result<()> {
+        if self.is_enabled(feature) {
+            Ok(())
+        } else {
+            Err(AppError::FeatureDisabled(feature.name().to_string()))
+        }
+    }
+
+    /// Applies the flags file on top of the defaults. A missing file
+    /// leaves the defaults in place; an invalid one is an error and
+    /// keeps the current flags.
+    pub async fn reload(&self) -> std::result::Result<(), String> {
+        let Some(path) = &self.file else {
+            return Ok(());
+        };
+        let overrides = match tokio::fs::read(path).await {
+            Ok(contents) => parse_overrides(&contents)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                BTreeMap::new()
+            }
+            Err(e) => return Err(e.to_string()),
+        };
+
+        let mut disabled: BTreeSet<Feature> =
+            self.defaults.iter().copied().collect();
+        for (feature, enabled) in overrides {
+            if enabled {
+                disabled.remove(&feature);
+            } else {
+                disabled.insert(feature);
+            }
+        }
+
+        let mut current = self.disabled.write().unwrap();
+        if *current != disabled {
+            let names: Vec<&str> = disabled
+                .iter()
+                .map(|feature| feature.name())
+                .collect();
+            info!(disabled = ?names, "Feature flags updated");
+            *current = disabled;
+        }
+        Ok(())
+    }
+
+    /// Polls the flags file every `interval` and reloads it when its
+    /// modification time changes.
+    pub fn watch(self: Arc<Self>, interval: Duration) {
+        let Some(path) = self.file.clone() else {
+            return;
+        };
+        tokio::spawn(async move {
+            let mut last_modified = modified(&path).await;
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let modified = modified(&path).await;
+                if modified == last_modified {
+                    continue;
+                }
+                last_modified = modified;
+                if let Err(e) = self.reload().await {
+                    warn!(path = %path.display(), error = %e, "Ignoring invalid feature flags file");
+                }
+            }
+        });
+    }
+}
+
+async fn modified(path: &Path) -> Option<SystemTime> {
+    tokio::fs::metadata(path).await.ok()?.modified().ok()
+}
+
+fn parse_overrides(
+    contents: &[u8],
+) -> std::result::Result<BTreeMap<Feature, bool>, String> {
+    let raw: BTreeMap<String, bool> =
+        serde_json::from_slice(contents)
+            .map_err(|e| format!("invalid JSON: {}", e))?;
+    raw.into_iter()
+        .map(|(name, enabled)| Ok((Feature::parse(&name)?, enabled)))
+        .collect()
+}
+
+/// Rejects requests to a disabled feature with a `503`.
+pub async fn require(
+    State((flags, feature)): State<(Arc<FeatureFlags>, Feature)>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match flags.check(feature) {
+        Ok(()) => next.run(request).await,
+        Err(e) => e.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_list() {
+        assert_eq!(
+            Feature::parse_list("translation, admin"),
+            Ok(vec![Feature::Translation, Feature::Admin])
+        );
+        assert_eq!(Feature::parse_list(""), Ok(vec![]));
+        assert!(Feature::parse_list("quiz").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_file_overrides_defaults_and_reloads() {
+        let path = std::env::temp_dir().join(format!(
+            "pokedex-flags-{}.json",
+            std::process::id()
+        ));
+        let flags = FeatureFlags::new(
+            vec![Feature::Batch],
+            Some(path.clone()),
+        );
+
+        flags.reload().await.unwrap();
+        assert!(!flags.is_enabled(Feature::Batch));
+        assert!(flags.is_enabled(Feature::Translation));
+
+        std::fs::write(
+            &path,
+            r#"{"batch": true, "translation": false}"#,
+        )
+        .unwrap();
+        flags.reload().await.unwrap();
+        assert!(flags.is_enabled(Feature::Batch));
+        assert!(matches!(
+            flags.check(Feature::Translation),
+            Err(AppError::FeatureDisabled(_))
+        ));
+
+        std::fs::write(&path, r#"{"quiz": false}"#).unwrap();
+        assert!(flags.reload().await.is_err());
+        assert!(!flags.is_enabled(Feature::Translation));
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
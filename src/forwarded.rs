@@ -0,0 +1,397 @@
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts, Request, State},
+    http::{HeaderMap, header, request::Parts},
+    middleware::Next,
+    response::Response,
+};
+use reqwest::Url;
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+pub const FORWARDED_PROTO: &str = "x-forwarded-proto";
+pub const FORWARDED_HOST: &str = "x-forwarded-host";
+pub const FORWARDED_PREFIX: &str = "x-forwarded-prefix";
+pub const FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// An address or CIDR block of reverse proxies whose
+/// `X-Forwarded-*` headers are honored.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrustedProxy {
+    Network {
+        addr: IpAddr,
+        prefix: u8,
+    },
+    /// Every client of a Unix socket listener.
+    Unix,
+}
+
+impl TrustedProxy {
+    /// Parses a comma-separated list, e.g. `10.0.0.0/8,::1,unix`.
+    pub fn parse_list(value: &str) -> Result<Vec<Self>, String> {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(Self::parse)
+            .collect()
+    }
+
+    fn parse(entry: &str) -> Result<Self, String> {
+        if entry.eq_ignore_ascii_case("unix") {
+            return Ok(TrustedProxy::Unix);
+        }
+        let invalid = || format!("invalid proxy address '{}'", entry);
+        let (addr, prefix) = match entry.split_once('/') {
+            Some((addr, prefix)) => (
+                addr.parse::<IpAddr>().map_err(|_| invalid())?,
+                Some(prefix.parse::<u8>().map_err(|_| invalid())?),
+            ),
+            None => (entry.parse().map_err(|_| invalid())?, None),
+        };
+        let bits = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix.unwrap_or(bits);
+        if prefix > bits {
+            return Err(invalid());
+        }
+        Ok(TrustedProxy::Network { addr, prefix })
+    }
+
+    fn contains(&self, peer: Option<IpAddr>) -> bool {
+        match (self, peer) {
+            (TrustedProxy::Unix, None) => true,
+            (TrustedProxy::Network { addr, prefix }, Some(peer)) => {
+                match (addr, peer) {
+                    (IpAddr::V4(addr), IpAddr::V4(peer)) => {
+                        let mask = u32::MAX
+                            .checked_shl(32 - u32::from(*prefix))
+                            .unwrap_or(0);
+                        u32::from(*addr) & mask
+                            == u32::from(peer) & mask
+                    }
+                    (IpAddr::V6(addr), IpAddr::V6(peer)) => {
+                        let mask = u128::MAX
+                            .checked_shl(128 - u32::from(*prefix))
+                            .unwrap_or(0);
+                        u128::from(*addr) & mask
+                            == u128::from(peer) & mask
+                    }
+                    _ => false,
+                }
+            }
+            _ => false,
+        }
+    }
+}
+
+/// How the URL clients reach the service at is found.
+#[derive(Debug, Clone, Default)]
+pub struct PublicUrlConfig {
+    /// Fixed by `PUBLIC_BASE_URL`, overriding the request headers.
+    pub base_url: Option<String>,
+    pub trusted_proxies: Vec<TrustedProxy>,
+}
+
+impl PublicUrlConfig {
+    /// `PUBLIC_BASE_URL` must be an absolute http(s) URL; a trailing
+    /// slash is dropped.
+    pub fn parse_base_url(value: &str) -> Result<String, String> {
+        let url = Url::parse(value).map_err(|e| e.to_string())?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(format!(
+                "unsupported scheme '{}'",
+                url.scheme()
+            ));
+        }
+        Ok(value.trim_end_matches('/').to_string())
+    }
+
+    fn resolve(
+        &self,
+        headers: &HeaderMap,
+        peer: Option<IpAddr>,
+    ) -> PublicUrl {
+        if let Some(base_url) = &self.base_url {
+            return PublicUrl(base_url.clone());
+        }
+        let trusted = self
+            .trusted_proxies
+            .iter()
+            .any(|proxy| proxy.contains(peer));
+        let forwarded = |name: &str| {
+            trusted.then(|| first_value(headers, name)).flatten()
+        };
+
+        let scheme = forwarded(FORWARDED_PROTO)
+            .filter(|proto| matches!(*proto, "http" | "https"))
+            .unwrap_or("http");
+        let host = forwarded(FORWARDED_HOST)
+            .or_else(|| first_value(headers, header::HOST.as_str()))
+            .unwrap_or("localhost");
+        let prefix = forwarded(FORWARDED_PREFIX)
+            .map(|prefix| prefix.trim_matches('/'))
+            .filter(|prefix| !prefix.is_empty())
+            .map(|prefix| format!("/{}", prefix))
+            .unwrap_or_default();
+        PublicUrl(format!("{}://{}{}", scheme, host, prefix))
+    }
+
+    /// The client behind `peer`: the peer itself unless it is a
+    /// trusted proxy, else the last address of `X-Forwarded-For`
+    /// that is not a trusted proxy, walking back from the peer.
+    fn client(
+        &self,
+        headers: &HeaderMap,
+        peer: Option<IpAddr>,
+    ) -> ClientIp {
+        let trusted = |addr: Option<IpAddr>| {
+            self.trusted_proxies
+                .iter()
+                .any(|proxy| proxy.contains(addr))
+        };
+        let mut client = peer;
+        let hops = headers
+            .get_all(FORWARDED_FOR)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .collect::<Vec<_>>();
+        for hop in hops.into_iter().rev() {
+            if !trusted(client) {
+                break;
+            }
+            // A malformed hop cannot be trusted to name the client.
+            let Ok(addr) = hop.parse() else {
+                break;
+            };
+            client = Some(addr);
+        }
+        ClientIp(client)
+    }
+}
+
+/// The first entry of a possibly comma-separated header.
+fn first_value<'a>(
+    headers: &'a HeaderMap,
+    name: &str,
+) -> Option<&'a str> {
+    headers
+        .get(name)?
+        .to_str()
+        .ok()?
+        .split(',')
+        .next()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+/// The URL clients reach the service at, e.g.
+/// `https://example.com/pokedex`, for building absolute links.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PublicUrl(pub String);
+
+impl PublicUrl {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The absolute URL of `path`, which starts with a slash.
+    pub fn join(&self, path: &str) -> String {
+        format!("{}{}", self.0, path)
+    }
+}
+
+/// The address a request comes from, through any trusted proxies,
+/// for keying per-client state such as rate limits. `None` for a
+/// Unix socket client that forwarded none.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClientIp(pub Option<IpAddr>);
+
+/// Resolves the public URL and the client address of each request.
+pub async fn middleware(
+    State(config): State<Arc<PublicUrlConfig>>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let peer = peer.map(|ConnectInfo(addr)| addr.ip());
+    let url = config.resolve(request.headers(), peer);
+    let client = config.client(request.headers(), peer);
+    request.extensions_mut().insert(url);
+    request.extensions_mut().insert(client);
+    next.run(request).await
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for PublicUrl {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        Ok(parts.extensions.get().cloned().unwrap_or_else(|| {
+            PublicUrlConfig::default().resolve(&parts.headers, None)
+        }))
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ClientIp {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        if let Some(client) = parts.extensions.get::<ClientIp>() {
+            return Ok(*client);
+        }
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+        Ok(ClientIp(peer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn headers() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::HOST,
+            HeaderValue::from_static("pod:8080"),
+        );
+        headers.insert(
+            FORWARDED_PROTO,
+            HeaderValue::from_static("https"),
+        );
+        headers.insert(
+            FORWARDED_HOST,
+            HeaderValue::from_static("example.com, ingress"),
+        );
+        headers.insert(
+            FORWARDED_PREFIX,
+            HeaderValue::from_static("/pokedex/"),
+        );
+        headers
+    }
+
+    #[test]
+    fn test_forwarded_headers_need_a_trusted_proxy() {
+        let config = PublicUrlConfig {
+            base_url: None,
+            trusted_proxies: TrustedProxy::parse_list("10.0.0.0/8")
+                .unwrap(),
+        };
+        let proxy = Some("10.1.2.3".parse().unwrap());
+        let client = Some("192.168.1.5".parse().unwrap());
+        assert_eq!(
+            config.resolve(&headers(), proxy).join("/pokemon/mew"),
+            "https://example.com/pokedex/pokemon/mew"
+        );
+        assert_eq!(
+            config.resolve(&headers(), client).join("/pokemon/mew"),
+            "http://pod:8080/pokemon/mew"
+        );
+        assert_eq!(
+            config.resolve(&headers(), None).join("/pokemon/mew"),
+            "http://pod:8080/pokemon/mew"
+        );
+    }
+
+    #[test]
+    fn test_client_is_the_first_untrusted_hop() {
+        let config = PublicUrlConfig {
+            base_url: None,
+            trusted_proxies: TrustedProxy::parse_list(
+                "10.0.0.0/8,unix",
+            )
+            .unwrap(),
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            FORWARDED_FOR,
+            HeaderValue::from_static(
+                "198.51.100.9, 203.0.113.7, 10.0.0.2",
+            ),
+        );
+        let ip = |addr: &str| Some(addr.parse().unwrap());
+
+        assert_eq!(
+            config.client(&headers, ip("10.1.2.3")),
+            ClientIp(ip("203.0.113.7"))
+        );
+        assert_eq!(
+            config.client(&headers, None),
+            ClientIp(ip("203.0.113.7"))
+        );
+        // Only trusted proxies may say who the client is.
+        assert_eq!(
+            config.client(&headers, ip("192.168.1.5")),
+            ClientIp(ip("192.168.1.5"))
+        );
+        assert_eq!(
+            config.client(&HeaderMap::new(), None),
+            ClientIp(None)
+        );
+
+        headers.insert(
+            FORWARDED_FOR,
+            HeaderValue::from_static("203.0.113.7, garbage"),
+        );
+        assert_eq!(
+            config.client(&headers, ip("10.1.2.3")),
+            ClientIp(ip("10.1.2.3"))
+        );
+    }
+
+    #[test]
+    fn test_base_url_overrides_headers() {
+        let config = PublicUrlConfig {
+            base_url: Some(
+                PublicUrlConfig::parse_base_url(
+                    "https://api.example.com/v/",
+                )
+                .unwrap(),
+            ),
+            trusted_proxies: vec![TrustedProxy::Unix],
+        };
+        assert_eq!(
+            config.resolve(&headers(), None).join("/habitats"),
+            "https://api.example.com/v/habitats"
+        );
+        assert!(PublicUrlConfig::parse_base_url("ftp://x").is_err());
+    }
+
+    #[test]
+    fn test_parses_trusted_proxies() {
+        assert_eq!(
+            TrustedProxy::parse_list(" ::1, unix ").unwrap(),
+            [
+                TrustedProxy::Network {
+                    addr: "::1".parse().unwrap(),
+                    prefix: 128,
+                },
+                TrustedProxy::Unix,
+            ]
+        );
+        assert!(TrustedProxy::parse_list("10.0.0.0/33").is_err());
+        assert!(TrustedProxy::parse_list("proxy.local").is_err());
+        assert!(
+            TrustedProxy::Network {
+                addr: "0.0.0.0".parse().unwrap(),
+                prefix: 0,
+            }
+            .contains(Some("8.8.8.8".parse().unwrap()))
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use crate::cache::{Cache, ManagedCache};
+use crate::error::Result;
+use crate::pokeapi::{
+    NamedApiResource, NamedApiResourceList, PokeApiClient,
+};
+use crate::pokemon::{SpeciesSummary, species_summaries};
+use serde::{Deserialize, Serialize};
+use std::{sync::Arc, time::Duration};
+use tracing::instrument;
+use utoipa::ToSchema;
+
+const HABITAT_LIST_KEY: &str = "habitats";
+
+#[derive(
+    Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema,
+)]
+pub struct HabitatSummary {
+    pub id: u32,
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+struct PokeApiHabitat {
+    pokemon_species: Vec<NamedApiResource>,
+}
+
+pub struct HabitatService {
+    pokeapi: PokeApiClient,
+    list_cache: Cache<String, Arc<Vec<HabitatSummary>>>,
+    species_cache: Cache<String, Arc<Vec<SpeciesSummary>>>,
+}
+
+impl HabitatService {
+    pub fn new(pokeapi: PokeApiClient, cache_ttl: Duration) -> Self {
+        Self {
+            pokeapi,
+            list_cache: Cache::new(cache_ttl),
+            species_cache: Cache::new(cache_ttl),
+        }
+    }
+
+    /// The caches owned by this service, by name.
+    pub fn caches(&self) -> Vec<(&'static str, &dyn ManagedCache)> {
+        vec![
+            ("habitat.list", &self.list_cache),
+            ("habitat.species", &self.species_cache),
+        ]
+    }
+
+    /// Returns every habitat known to PokeAPI, sorted by id.
+    pub async fn list_habitats(
+        &self,
+    ) -> Result<Arc<Vec<HabitatSummary>>> {
+        if let Some(habitats) =
+            self.list_cache.get(&HABITAT_LIST_KEY.to_string())
+        {
+            return Ok(habitats);
+        }
+
+        let list: NamedApiResourceList = self
+            .pokeapi
+            .get("pokemon-habitat", || {
+                "Habitat list not found".to_string()
+            })
+            .await?;
+
+        let mut habitats: Vec<HabitatSummary> = list
+            .results
+            .into_iter()
+            .filter_map(|resource| {
+                Some(HabitatSummary {
+                    id: resource.id()?,
+                    name: resource.name,
+                })
+            })
+            .collect();
+        habitats.sort_by_key(|habitat| habitat.id);
+
+        let habitats = Arc::new(habitats);
+        self.list_cache
+            .insert(HABITAT_LIST_KEY.to_string(), habitats.clone());
+        Ok(habitats)
+    }
+
+    /// Returns the species living in `name`, sorted by id.
+    #[instrument(skip(self), fields(habitat = %name))]
+    pub async fn habitat_species(
+        &self,
+        name: &str,
+    ) -> Result<Arc<Vec<SpeciesSummary>>> {
+        let name = name.to_lowercase();
+        if let Some(species) = self.species_cache.get(&name) {
+            return Ok(species);
+        }
+
+        let habitat: PokeApiHabitat = self
+            .pokeapi
+            .get(&format!("pokemon-habitat/{}", name), || {
+                format!("Habitat '{}' not found", name)
+            })
+            .await?;
+
+        let species =
+            Arc::new(species_summaries(habitat.pokemon_species));
+        self.species_cache.insert(name, species.clone());
+        Ok(species)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn service(server: &MockServer) -> HabitatService {
+        HabitatService::new(
+            PokeApiClient::new(
+                http::build_client(&http::ClientSettings::new(
+                    Duration::from_secs(5),
+                )),
+                server.uri(),
+            ),
+            Duration::from_secs(60),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_habitat_species_are_sorted_and_cached() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/pokemon-habitat/cave"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({
+                    "pokemon_species": [
+                        {"name": "zubat", "url": "http://x/pokemon-species/41/"},
+                        {"name": "onix", "url": "http://x/pokemon-species/95/"},
+                        {"name": "diglett", "url": "http://x/pokemon-species/50/"}
+                    ]
+                }),
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let service = service(&server);
+        let species = service.habitat_species("Cave").await.unwrap();
+        let names: Vec<_> =
+            species.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["zubat", "diglett", "onix"]);
+
+        service.habitat_species("cave").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unknown_habitat_is_not_found() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/pokemon-habitat/moon"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+
+        let result = service(&server).habitat_species("moon").await;
+        assert!(matches!(
+            result,
+            Err(crate::error::AppError::NotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,178 @@
+use crate::error::Result;
+use crate::upstreams::LastError;
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencyStatus {
+    Up,
+    Down,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Healthy,
+    /// Only optional dependencies are down; the Pokemon endpoints
+    /// still work.
+    Degraded,
+    Unhealthy,
+}
+
+/// The outcome of checking one dependency.
+#[derive(Debug, Clone, Serialize)]
+pub struct Dependency {
+    pub name: &'static str,
+    pub status: DependencyStatus,
+    pub latency_ms: u64,
+    /// The error of the check or, when it passed, the last one seen
+    /// by live traffic.
+    pub last_error: Option<LastError>,
+    /// Whether the service degrades rather than fails without it.
+    #[serde(skip)]
+    pub optional: bool,
+}
+
+impl Dependency {
+    /// Runs `check`, timing it. `last_error` is the last error seen
+    /// outside of checks.
+    pub async fn check(
+        name: &'static str,
+        check: impl Future<Output = Result<()>>,
+        last_error: Option<LastError>,
+    ) -> Self {
+        let started = Instant::now();
+        let result = check.await;
+        let latency_ms = started.elapsed().as_millis() as u64;
+        match result {
+            Ok(()) => Self {
+                name,
+                status: DependencyStatus::Up,
+                latency_ms,
+                last_error,
+                optional: false,
+            },
+            Err(e) => Self {
+                name,
+                status: DependencyStatus::Down,
+                latency_ms,
+                last_error: Some(LastError {
+                    message: e.to_string(),
+                    at: SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs(),
+                }),
+                optional: false,
+            },
+        }
+    }
+
+    pub fn optional(mut self) -> Self {
+        self.optional = true;
+        self
+    }
+}
+
+/// Body of `GET /health`.
+#[derive(Debug, Serialize)]
+pub struct HealthReport {
+    pub status: HealthStatus,
+    pub service: &'static str,
+    pub version: &'static str,
+    pub dependencies: Vec<Dependency>,
+}
+
+impl HealthReport {
+    pub fn new(dependencies: Vec<Dependency>) -> Self {
+        let down = |optional: bool| {
+            dependencies.iter().any(|dependency| {
+                dependency.optional == optional
+                    && dependency.status == DependencyStatus::Down
+            })
+        };
+        let status = if down(false) {
+            HealthStatus::Unhealthy
+        } else if down(true) {
+            HealthStatus::Degraded
+        } else {
+            HealthStatus::Healthy
+        };
+        Self {
+            status,
+            service: "pokedex-api",
+            version: env!("CARGO_PKG_VERSION"),
+            dependencies,
+        }
+    }
+}
+
+impl IntoResponse for HealthReport {
+    fn into_response(self) -> Response {
+        let status = match self.status {
+            HealthStatus::Healthy | HealthStatus::Degraded => {
+                StatusCode::OK
+            }
+            HealthStatus::Unhealthy => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+        };
+        (status, Json(self)).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::AppError;
+
+    async fn dependency(name: &'static str, up: bool) -> Dependency {
+        Dependency::check(
+            name,
+            async move {
+                if up {
+                    Ok(())
+                } else {
+                    Err(AppError::ExternalApi("unreachable".into()))
+                }
+            },
+            None,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_status_depends_on_which_dependency_is_down() {
+        let report = HealthReport::new(vec![
+            dependency("pokeapi", true).await,
+            dependency("translation", false).await.optional(),
+        ]);
+        assert_eq!(report.status, HealthStatus::Degraded);
+        assert_eq!(
+            report.dependencies[1]
+                .last_error
+                .as_ref()
+                .unwrap()
+                .message,
+            "External API error: unreachable"
+        );
+        assert_eq!(report.into_response().status(), StatusCode::OK);
+
+        let report = HealthReport::new(vec![
+            dependency("pokeapi", false).await,
+            dependency("translation", true).await.optional(),
+        ]);
+        assert_eq!(report.status, HealthStatus::Unhealthy);
+
+        let report = HealthReport::new(vec![
+            dependency("pokeapi", true).await,
+        ]);
+        assert_eq!(report.status, HealthStatus::Healthy);
+    }
+}
@@ -0,0 +1,265 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use utoipa::ToSchema;
+
+/// Roman numerals of the generations, in order.
+const GENERATIONS: [&str; 9] =
+    ["i", "ii", "iii", "iv", "v", "vi", "vii", "viii", "ix"];
+
+/// The types and abilities a variety had in past generations, as
+/// PokeAPI lists them, with the current abilities by slot to compare
+/// them with.
+#[derive(
+    Debug, Clone, Default, Serialize, Deserialize, PartialEq,
+)]
+pub struct PastForms {
+    pub types: Vec<PastTypes>,
+    pub abilities: Vec<PastAbilities>,
+    pub current_abilities: Vec<SlotAbility>,
+}
+
+/// The types a variety had up to and including `generation`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PastTypes {
+    pub generation: String,
+    pub types: Vec<String>,
+}
+
+/// The abilities of the slots that differed up to and including
+/// `generation`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PastAbilities {
+    pub generation: String,
+    pub abilities: Vec<SlotAbility>,
+}
+
+/// The ability in a slot; `None` when the slot did not exist yet.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SlotAbility {
+    pub slot: u32,
+    pub name: Option<String>,
+    pub is_hidden: bool,
+}
+
+/// How the types and abilities of a Pokemon changed across
+/// generations.
+#[derive(Debug, Clone, Serialize, PartialEq, ToSchema)]
+pub struct StatHistory {
+    pub name: String,
+    pub types: Vec<String>,
+    /// Oldest first.
+    pub changes: Vec<GenerationChange>,
+}
+
+/// What changed after the generation `until`.
+#[derive(Debug, Clone, Serialize, PartialEq, ToSchema)]
+pub struct GenerationChange {
+    /// The first generation with the new values.
+    pub generation: Option<String>,
+    /// The last generation with the old values.
+    pub until: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub types: Option<Change<Vec<String>>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub abilities: Vec<AbilityChange>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, ToSchema)]
+pub struct Change<T> {
+    pub before: T,
+    pub after: T,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, ToSchema)]
+pub struct AbilityChange {
+    pub slot: u32,
+    pub is_hidden: bool,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+/// 1 for `generation-i`, `None` for names that are not generations.
+pub fn generation_number(name: &str) -> Option<usize> {
+    let roman = name.strip_prefix("generation-")?;
+    GENERATIONS.iter().position(|g| *g == roman).map(|i| i + 1)
+}
+
+pub fn generation_name(number: usize) -> Option<String> {
+    let roman = GENERATIONS.get(number.checked_sub(1)?)?;
+    Some(format!("generation-{}", roman))
+}
+
+/// Sorts generations in order, unknown ones last.
+fn order(generation: &str) -> usize {
+    generation_number(generation).unwrap_or(usize::MAX)
+}
+
+type Changes = BTreeMap<(usize, String), GenerationChange>;
+
+/// The change after the generation `until`, added if missing.
+fn change<'a>(
+    changes: &'a mut Changes,
+    until: &str,
+) -> &'a mut GenerationChange {
+    changes
+        .entry((order(until), until.to_string()))
+        .or_insert_with(|| GenerationChange {
+            generation: generation_number(until)
+                .and_then(|number| generation_name(number + 1)),
+            until: until.to_string(),
+            types: None,
+            abilities: Vec::new(),
+        })
+}
+
+/// Turns the past types and abilities of `name` into the list of
+/// changes leading to `types` and the current abilities.
+pub fn build(
+    name: &str,
+    types: &[String],
+    past: &PastForms,
+) -> StatHistory {
+    let mut changes = Changes::new();
+
+    let mut past_types: Vec<&PastTypes> = past.types.iter().collect();
+    past_types.sort_by_key(|past| order(&past.generation));
+    for (i, past_type) in past_types.iter().enumerate() {
+        let after = past_types
+            .get(i + 1)
+            .map_or(types, |next| next.types.as_slice());
+        if past_type.types != after {
+            change(&mut changes, &past_type.generation).types =
+                Some(Change {
+                    before: past_type.types.clone(),
+                    after: after.to_vec(),
+                });
+        }
+    }
+
+    let mut past_abilities: Vec<&PastAbilities> =
+        past.abilities.iter().collect();
+    past_abilities.sort_by_key(|past| order(&past.generation));
+    for (i, past_ability) in past_abilities.iter().enumerate() {
+        for before in &past_ability.abilities {
+            let in_slot = |abilities: &[SlotAbility]| {
+                abilities
+                    .iter()
+                    .find(|a| a.slot == before.slot)
+                    .map(|a| a.name.clone())
+            };
+            let after = past_abilities[i + 1..]
+                .iter()
+                .find_map(|later| in_slot(&later.abilities))
+                .or_else(|| in_slot(&past.current_abilities))
+                .flatten();
+            if before.name != after {
+                change(&mut changes, &past_ability.generation)
+                    .abilities
+                    .push(AbilityChange {
+                        slot: before.slot,
+                        is_hidden: before.is_hidden,
+                        before: before.name.clone(),
+                        after,
+                    });
+            }
+        }
+    }
+
+    StatHistory {
+        name: name.to_string(),
+        types: types.to_vec(),
+        changes: changes.into_values().collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    fn ability(slot: u32, name: Option<&str>) -> SlotAbility {
+        SlotAbility {
+            slot,
+            name: name.map(str::to_string),
+            is_hidden: slot == 3,
+        }
+    }
+
+    #[test]
+    fn test_build_history() {
+        // Clefairy was Normal until generation V; Gengar had
+        // Levitate until generation VI.
+        let past = PastForms {
+            types: vec![PastTypes {
+                generation: "generation-v".to_string(),
+                types: strings(&["normal"]),
+            }],
+            abilities: vec![
+                PastAbilities {
+                    generation: "generation-vi".to_string(),
+                    abilities: vec![ability(1, Some("levitate"))],
+                },
+                PastAbilities {
+                    generation: "generation-iv".to_string(),
+                    abilities: vec![ability(3, None)],
+                },
+            ],
+            current_abilities: vec![
+                ability(1, Some("cursed-body")),
+                ability(3, Some("friend-guard")),
+            ],
+        };
+
+        let history = build("test", &strings(&["fairy"]), &past);
+        assert_eq!(
+            history.changes,
+            vec![
+                GenerationChange {
+                    generation: Some("generation-v".to_string()),
+                    until: "generation-iv".to_string(),
+                    types: None,
+                    abilities: vec![AbilityChange {
+                        slot: 3,
+                        is_hidden: true,
+                        before: None,
+                        after: Some("friend-guard".to_string()),
+                    }],
+                },
+                GenerationChange {
+                    generation: Some("generation-vi".to_string()),
+                    until: "generation-v".to_string(),
+                    types: Some(Change {
+                        before: strings(&["normal"]),
+                        after: strings(&["fairy"]),
+                    }),
+                    abilities: Vec::new(),
+                },
+                GenerationChange {
+                    generation: Some("generation-vii".to_string()),
+                    until: "generation-vi".to_string(),
+                    types: None,
+                    abilities: vec![AbilityChange {
+                        slot: 1,
+                        is_hidden: false,
+                        before: Some("levitate".to_string()),
+                        after: Some("cursed-body".to_string()),
+                    }],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_generation_names() {
+        assert_eq!(generation_number("generation-viii"), Some(8));
+        assert_eq!(generation_number("red"), None);
+        assert_eq!(
+            generation_name(9).as_deref(),
+            Some("generation-ix")
+        );
+        assert_eq!(generation_name(10), None);
+    }
+}
@@ -0,0 +1,228 @@
+use axum::{
+    extract::Request,
+    http::{StatusCode, header},
+    middleware::Next,
+    response::Response,
+};
+
+/// Languages error messages are translated to. The messages are
+/// written in English, so English needs no catalog.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    En,
+    It,
+    Es,
+}
+
+impl Locale {
+    pub fn tag(self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::It => "it",
+            Locale::Es => "es",
+        }
+    }
+
+    fn from_tag(tag: &str) -> Option<Self> {
+        let primary = tag.split('-').next().unwrap_or_default();
+        [Locale::En, Locale::It, Locale::Es]
+            .into_iter()
+            .find(|locale| primary.eq_ignore_ascii_case(locale.tag()))
+    }
+
+    /// The supported locale of highest quality in an
+    /// `Accept-Language` header, English when there is none.
+    pub fn negotiate(header: &str) -> Self {
+        let mut tags: Vec<(&str, f32)> = header
+            .split(',')
+            .filter_map(|entry| {
+                let mut parts = entry.split(';').map(str::trim);
+                let tag = parts.next()?;
+                let quality = parts
+                    .find_map(|p| p.strip_prefix("q="))
+                    .map(|q| q.parse::<f32>().unwrap_or(0.0))
+                    .unwrap_or(1.0);
+                (quality > 0.0).then_some((tag, quality))
+            })
+            .collect();
+        tags.sort_by(|a, b| b.1.total_cmp(&a.1));
+        tags.into_iter()
+            .find_map(|(tag, _)| Self::from_tag(tag))
+            .unwrap_or_default()
+    }
+}
+
+tokio::task_local! {
+    static LOCALE: Locale;
+}
+
+/// The locale of the current request, English outside of a request.
+pub fn current() -> Locale {
+    LOCALE.try_with(|locale| *locale).unwrap_or_default()
+}
+
+/// Scopes the request to the locale negotiated from its
+/// `Accept-Language` header.
+pub async fn middleware(request: Request, next: Next) -> Response {
+    let locale = request
+        .headers()
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .map(Locale::negotiate)
+        .unwrap_or_default();
+    LOCALE.scope(locale, next.run(request)).await
+}
+
+/// A message of the catalogs, with its arguments.
+#[derive(Debug, Clone, Copy)]
+pub enum Message<'a> {
+    /// The generic sentence for an error status, standing in for a
+    /// message the catalogs do not know.
+    Status(StatusCode),
+    UnknownPokemon {
+        name: &'a str,
+    },
+    FeatureDisabled {
+        feature: &'a str,
+    },
+}
+
+impl Message<'_> {
+    /// The message in `locale`; `None` in English, which callers
+    /// already have.
+    pub fn translate(self, locale: Locale) -> Option<String> {
+        match locale {
+            Locale::En => None,
+            Locale::It => Some(italian(self)),
+            Locale::Es => Some(spanish(self)),
+        }
+    }
+}
+
+fn italian(message: Message) -> String {
+    match message {
+        Message::UnknownPokemon { name } => {
+            format!("Pokémon '{}' non trovato", name)
+        }
+        Message::FeatureDisabled { feature } => format!(
+            "La funzionalità {} è temporaneamente disattivata",
+            feature
+        ),
+        Message::Status(status) => match status {
+            StatusCode::BAD_REQUEST => "Richiesta non valida",
+            StatusCode::UNAUTHORIZED => "Autenticazione richiesta",
+            StatusCode::FORBIDDEN => "Accesso negato",
+            StatusCode::NOT_FOUND => "Risorsa non trovata",
+            StatusCode::CONFLICT => {
+                "La richiesta è in conflitto con un'altra"
+            }
+            StatusCode::PAYLOAD_TOO_LARGE => {
+                "Richiesta troppo grande"
+            }
+            StatusCode::UNPROCESSABLE_ENTITY => {
+                "La richiesta contiene campi non validi"
+            }
+            StatusCode::TOO_MANY_REQUESTS => {
+                "Troppe richieste, riprova più tardi"
+            }
+            StatusCode::BAD_GATEWAY => {
+                "Un servizio esterno non ha risposto correttamente"
+            }
+            StatusCode::GATEWAY_TIMEOUT => {
+                "Un servizio esterno non ha risposto in tempo"
+            }
+            _ => "Errore interno del server",
+        }
+        .to_string(),
+    }
+}
+
+fn spanish(message: Message) -> String {
+    match message {
+        Message::UnknownPokemon { name } => {
+            format!("Pokémon '{}' no encontrado", name)
+        }
+        Message::FeatureDisabled { feature } => format!(
+            "La función {} está desactivada temporalmente",
+            feature
+        ),
+        Message::Status(status) => match status {
+            StatusCode::BAD_REQUEST => "Solicitud no válida",
+            StatusCode::UNAUTHORIZED => "Se requiere autenticación",
+            StatusCode::FORBIDDEN => "Acceso denegado",
+            StatusCode::NOT_FOUND => "Recurso no encontrado",
+            StatusCode::CONFLICT => {
+                "La solicitud entra en conflicto con otra"
+            }
+            StatusCode::PAYLOAD_TOO_LARGE => {
+                "Solicitud demasiado grande"
+            }
+            StatusCode::UNPROCESSABLE_ENTITY => {
+                "La solicitud contiene campos no válidos"
+            }
+            StatusCode::TOO_MANY_REQUESTS => {
+                "Demasiadas solicitudes, inténtalo más tarde"
+            }
+            StatusCode::BAD_GATEWAY => {
+                "Un servicio externo no respondió correctamente"
+            }
+            StatusCode::GATEWAY_TIMEOUT => {
+                "Un servicio externo no respondió a tiempo"
+            }
+            _ => "Error interno del servidor",
+        }
+        .to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::AppError;
+    use axum::response::IntoResponse;
+
+    #[test]
+    fn test_negotiates_supported_locale() {
+        assert_eq!(Locale::negotiate("it-IT,it;q=0.9"), Locale::It);
+        assert_eq!(
+            Locale::negotiate("fr;q=0.9, es;q=0.8"),
+            Locale::Es
+        );
+        assert_eq!(Locale::negotiate("es;q=0.5, en"), Locale::En);
+        assert_eq!(Locale::negotiate("de, *"), Locale::En);
+        assert_eq!(Locale::negotiate(""), Locale::En);
+    }
+
+    #[tokio::test]
+    async fn test_errors_are_translated_in_scope() {
+        let response = LOCALE
+            .scope(Locale::It, async {
+                AppError::NotFound("Habitat 'moon' not found".into())
+                    .into_response()
+            })
+            .await;
+        assert_eq!(
+            response.headers()[header::CONTENT_LANGUAGE],
+            "it"
+        );
+        let body = axum::body::to_bytes(response.into_body(), 1024)
+            .await
+            .unwrap();
+        let body: serde_json::Value =
+            serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"], "Risorsa non trovata");
+        assert_eq!(body["details"], "Habitat 'moon' not found");
+
+        let response = AppError::UnknownPokemon {
+            name: "pikachuu".into(),
+            suggestions: Vec::new(),
+        }
+        .into_response();
+        assert!(
+            !response
+                .headers()
+                .contains_key(header::CONTENT_LANGUAGE)
+        );
+    }
+}
@@ -0,0 +1,376 @@
+use axum::{
+    body::{Body, Bytes, to_bytes},
+    extract::{Request, State},
+    http::{HeaderMap, HeaderValue, Method, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::{debug, warn};
+
+use crate::auth::{self, Principal};
+use crate::error::{AppError, FieldError};
+use crate::forwarded::ClientIp;
+
+pub const IDEMPOTENCY_KEY: &str = "idempotency-key";
+pub const IDEMPOTENT_REPLAYED: &str = "idempotent-replayed";
+
+const MAX_KEY_LENGTH: usize = 255;
+const MAX_STORED_BODY: usize = 1024 * 1024;
+
+#[derive(Clone)]
+struct StoredResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+/// SHA-256 of a request body, telling a retry from a different
+/// request reusing the key.
+type Fingerprint = [u8; 32];
+
+enum Progress {
+    InFlight,
+    Completed(StoredResponse),
+}
+
+struct Entry {
+    state: Progress,
+    fingerprint: Fingerprint,
+    expires_at: Instant,
+}
+
+enum Lookup {
+    Fresh,
+    InFlight,
+    Replay(StoredResponse),
+    /// The key was first used with a different body.
+    Mismatch,
+}
+
+/// Stores responses of POST requests carrying an `Idempotency-Key`
+/// header so that client retries replay the first response instead
+/// of re-running the upstream work. Keys are scoped to the caller
+/// and the path, and at most `max_entries` are kept: past that the
+/// oldest entry is evicted.
+pub struct IdempotencyStore {
+    ttl: Duration,
+    max_entries: usize,
+    max_body_bytes: usize,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl IdempotencyStore {
+    pub fn new(
+        ttl: Duration,
+        max_entries: usize,
+        max_body_bytes: usize,
+    ) -> Self {
+        Self {
+            ttl,
+            max_entries,
+            max_body_bytes,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn begin(&self, key: &str, fingerprint: Fingerprint) -> Lookup {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, entry| entry.expires_at > now);
+
+        match entries.get(key) {
+            Some(entry) if entry.fingerprint != fingerprint => {
+                Lookup::Mismatch
+            }
+            Some(Entry {
+                state: Progress::Completed(response),
+                ..
+            }) => Lookup::Replay(response.clone()),
+            Some(_) => Lookup::InFlight,
+            None => {
+                // Entries share the TTL, so the first to expire is
+                // the oldest.
+                while entries.len() >= self.max_entries.max(1) {
+                    let oldest = entries
+                        .iter()
+                        .min_by_key(|(_, entry)| entry.expires_at)
+                        .map(|(key, _)| key.clone());
+                    match oldest {
+                        Some(oldest) => entries.remove(&oldest),
+                        None => break,
+                    };
+                }
+                entries.insert(
+                    key.to_string(),
+                    Entry {
+                        state: Progress::InFlight,
+                        fingerprint,
+                        expires_at: now + self.ttl,
+                    },
+                );
+                Lookup::Fresh
+            }
+        }
+    }
+
+    fn complete(&self, key: &str, response: StoredResponse) {
+        let mut entries = self.entries.lock().unwrap();
+        // An entry evicted while in flight is not stored again.
+        if let Some(entry) = entries.get_mut(key) {
+            entry.state = Progress::Completed(response);
+            entry.expires_at = Instant::now() + self.ttl;
+        }
+    }
+
+    fn abandon(&self, key: &str) {
+        self.entries.lock().unwrap().remove(key);
+    }
+}
+
+pub async fn middleware(
+    State(store): State<Arc<IdempotencyStore>>,
+    client: ClientIp,
+    request: Request,
+    next: Next,
+) -> Response {
+    if request.method() != Method::POST {
+        return next.run(request).await;
+    }
+
+    let Some(key) = request.headers().get(IDEMPOTENCY_KEY) else {
+        return next.run(request).await;
+    };
+
+    // Two callers picking the same key must not see each other's
+    // responses, even behind the same proxy.
+    let caller = auth::caller_id(
+        request.extensions().get::<Principal>(),
+        client,
+    );
+    let key = match key.to_str() {
+        Ok(key) if !key.is_empty() && key.len() <= MAX_KEY_LENGTH => {
+            format!("{} {} {}", caller, request.uri().path(), key)
+        }
+        _ => {
+            return AppError::BadRequest(format!(
+                "Idempotency-Key must be 1-{} visible ASCII characters",
+                MAX_KEY_LENGTH
+            ))
+            .into_response();
+        }
+    };
+
+    let (parts, body) = request.into_parts();
+    let body = match to_bytes(body, store.max_body_bytes).await {
+        Ok(body) => body,
+        Err(_) => {
+            return AppError::PayloadTooLarge(format!(
+                "Request bodies may be at most {} bytes",
+                store.max_body_bytes
+            ))
+            .into_response();
+        }
+    };
+    let fingerprint = Sha256::digest(&body).into();
+    let request = Request::from_parts(parts, Body::from(body));
+
+    match store.begin(&key, fingerprint) {
+        Lookup::Replay(stored) => {
+            debug!(idempotency_key = %key, "Replaying stored response");
+            let mut response =
+                (stored.status, stored.headers, stored.body)
+                    .into_response();
+            response.headers_mut().insert(
+                IDEMPOTENT_REPLAYED,
+                HeaderValue::from_static("true"),
+            );
+            return response;
+        }
+        Lookup::InFlight => {
+            return AppError::Conflict(
+                "A request with this Idempotency-Key is still being processed"
+                    .to_string(),
+            )
+            .into_response();
+        }
+        Lookup::Mismatch => {
+            return AppError::ValidationError(vec![FieldError::new(
+                "Idempotency-Key",
+                "reused",
+                "This Idempotency-Key was used with a different request body",
+            )])
+            .into_response();
+        }
+        Lookup::Fresh => {}
+    }
+
+    let response = next.run(request).await;
+
+    // Server errors are not stored so that a retry can succeed.
+    if response.status().is_server_error() {
+        store.abandon(&key);
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = match to_bytes(body, MAX_STORED_BODY).await {
+        Ok(body) => body,
+        Err(e) => {
+            warn!(error = %e, "Response too large to store for idempotency");
+            store.abandon(&key);
+            return AppError::Internal(
+                "Failed to buffer response body".to_string(),
+            )
+            .into_response();
+        }
+    };
+
+    let mut headers = HeaderMap::new();
+    if let Some(content_type) =
+        parts.headers.get(header::CONTENT_TYPE)
+    {
+        headers.insert(header::CONTENT_TYPE, content_type.clone());
+    }
+    store.complete(
+        &key,
+        StoredResponse {
+            status: parts.status,
+            headers,
+            body: body.clone(),
+        },
+    );
+
+    Response::from_parts(parts, Body::from(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stored(body: &'static str) -> StoredResponse {
+        StoredResponse {
+            status: StatusCode::OK,
+            headers: HeaderMap::new(),
+            body: Bytes::from_static(body.as_bytes()),
+        }
+    }
+
+    const BODY: Fingerprint = [0; 32];
+
+    fn store(ttl: Duration) -> IdempotencyStore {
+        IdempotencyStore::new(ttl, 100, 1024)
+    }
+
+    #[test]
+    fn test_first_request_is_fresh_then_in_flight() {
+        let store = store(Duration::from_secs(60));
+        assert!(matches!(store.begin("k", BODY), Lookup::Fresh));
+        assert!(matches!(store.begin("k", BODY), Lookup::InFlight));
+    }
+
+    #[test]
+    fn test_completed_request_is_replayed() {
+        let store = store(Duration::from_secs(60));
+        store.begin("k", BODY);
+        store.complete("k", stored("hello"));
+        match store.begin("k", BODY) {
+            Lookup::Replay(response) => {
+                assert_eq!(response.body, "hello")
+            }
+            _ => panic!("expected replay"),
+        }
+    }
+
+    #[test]
+    fn test_reused_key_with_another_body_is_rejected() {
+        let store = store(Duration::from_secs(60));
+        store.begin("k", BODY);
+        store.complete("k", stored("hello"));
+        assert!(matches!(
+            store.begin("k", [1; 32]),
+            Lookup::Mismatch
+        ));
+    }
+
+    #[test]
+    fn test_abandoned_key_can_be_retried() {
+        let store = store(Duration::from_secs(60));
+        store.begin("k", BODY);
+        store.abandon("k");
+        assert!(matches!(store.begin("k", BODY), Lookup::Fresh));
+    }
+
+    #[test]
+    fn test_expired_entries_are_dropped() {
+        let store = store(Duration::ZERO);
+        store.begin("k", BODY);
+        store.complete("k", stored("hello"));
+        assert!(matches!(store.begin("k", BODY), Lookup::Fresh));
+    }
+
+    #[tokio::test]
+    async fn test_keys_are_scoped_to_the_caller_and_body() {
+        use axum::{
+            Router, extract::ConnectInfo,
+            middleware::from_fn_with_state, routing::post,
+        };
+        use std::net::SocketAddr;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use tower::Service;
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let app = Router::new()
+            .route(
+                "/batch",
+                post(move || async move {
+                    counter.fetch_add(1, Ordering::SeqCst).to_string()
+                }),
+            )
+            .layer(from_fn_with_state(
+                Arc::new(store(Duration::from_secs(60))),
+                middleware,
+            ));
+        let send = |ip: [u8; 4], body: &'static str| {
+            let mut request = Request::post("/batch")
+                .header(IDEMPOTENCY_KEY, "k")
+                .body(Body::from(body))
+                .unwrap();
+            // Every client is forwarded by the same proxy.
+            let extensions = request.extensions_mut();
+            extensions.insert(ConnectInfo(SocketAddr::from((
+                [10, 0, 0, 254],
+                1,
+            ))));
+            extensions.insert(ClientIp(Some(ip.into())));
+            app.clone().call(request)
+        };
+
+        let first = send([10, 0, 0, 1], "a").await.unwrap();
+        assert_eq!(first.status(), StatusCode::OK);
+        let replay = send([10, 0, 0, 1], "a").await.unwrap();
+        assert!(replay.headers().contains_key(IDEMPOTENT_REPLAYED));
+        let other = send([10, 0, 0, 2], "a").await.unwrap();
+        assert!(!other.headers().contains_key(IDEMPOTENT_REPLAYED));
+        let reused = send([10, 0, 0, 1], "b").await.unwrap();
+        assert_eq!(reused.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_oldest_entry_is_evicted_at_capacity() {
+        let store =
+            IdempotencyStore::new(Duration::from_secs(60), 2, 1024);
+        store.begin("a", BODY);
+        store.begin("b", BODY);
+        store.begin("c", BODY);
+        assert!(matches!(store.begin("a", BODY), Lookup::Fresh));
+        assert!(matches!(store.begin("c", BODY), Lookup::InFlight));
+    }
+}
@@ -0,0 +1,108 @@
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Query},
+    http::request::Parts,
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::error::AppError;
+use crate::pokemon::Pokemon;
+
+/// Optional response sections requested with `?include=a,b`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Include {
+    pub breeding: bool,
+    pub meta: bool,
+    pub artwork: bool,
+    pub phonetics: bool,
+}
+
+impl Include {
+    /// Drops the optional sections the client did not ask for.
+    pub fn apply(self, mut pokemon: Pokemon) -> Pokemon {
+        if !self.breeding {
+            pokemon.breeding = None;
+        }
+        if !self.meta {
+            pokemon.meta = None;
+        }
+        if !self.artwork {
+            pokemon.artwork = None;
+        }
+        if !self.phonetics {
+            pokemon.phonetics = None;
+        }
+        pokemon
+    }
+
+    pub fn parse(value: &str) -> Result<Self, AppError> {
+        let mut include = Include::default();
+        for section in value.split(',').map(str::trim) {
+            match section {
+                "" => {}
+                "breeding" => include.breeding = true,
+                "meta" => include.meta = true,
+                "artwork" => include.artwork = true,
+                "phonetics" => include.phonetics = true,
+                other => {
+                    return Err(AppError::BadRequest(format!(
+                        "Unknown include '{}'",
+                        other
+                    )));
+                }
+            }
+        }
+        Ok(include)
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct IncludeParams {
+    include: Option<String>,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Include {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        let Query(params) =
+            Query::<IncludeParams>::from_request_parts(parts, state)
+                .await
+                .map_err(|e| AppError::BadRequest(e.body_text()))?;
+        params
+            .include
+            .as_deref()
+            .map(Include::parse)
+            .unwrap_or_else(|| Ok(Include::default()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sections() {
+        assert_eq!(
+            Include::parse("breeding, meta").unwrap(),
+            Include {
+                breeding: true,
+                meta: true,
+                artwork: false,
+                phonetics: false,
+            }
+        );
+        assert_eq!(Include::parse("").unwrap(), Include::default());
+    }
+
+    #[test]
+    fn test_parse_unknown_section() {
+        assert!(Include::parse("breeding,stats").is_err());
+    }
+}
@@ -0,0 +1,351 @@
+use crate::cache_store::unix_millis;
+use crate::config::Secret;
+use crate::error::{AppError, Result};
+use futures::StreamExt;
+use pokedex_rs::webhook::{DEFAULT_TOLERANCE, SigningKeys};
+use redis::{AsyncCommands, aio::ConnectionManager};
+use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::{
+    net::{Ipv4Addr, SocketAddrV4},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::net::UdpSocket;
+use tracing::{debug, info, warn};
+
+/// Wait before subscribing again after the Redis connection drops.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Largest message read from the multicast group.
+const MAX_DATAGRAM: usize = 1024;
+
+/// Where cache invalidations are exchanged, from
+/// `INVALIDATION_BUS`.
+#[derive(Debug, Clone)]
+pub enum BusConfig {
+    /// A Redis pub/sub channel.
+    Redis { url: Secret, channel: String },
+    /// A UDP multicast group on the local network, whose messages
+    /// are signed with `keys`.
+    Multicast {
+        group: SocketAddrV4,
+        keys: SigningKeys,
+    },
+}
+
+/// A cache cleared on one replica, to be cleared on the others.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Invalidation {
+    /// The replica that cleared it, which skips its own messages.
+    pub origin: String,
+    /// The cache by name, or every cache.
+    pub cache: Option<String>,
+}
+
+enum Transport {
+    Redis {
+        url: Secret,
+        channel: String,
+        connection: Box<ConnectionManager>,
+    },
+    Multicast {
+        socket: Arc<UdpSocket>,
+        group: SocketAddrV4,
+        keys: SigningKeys,
+    },
+}
+
+/// Propagates the caches cleared by an operator to every replica.
+/// Delivery is best effort: a replica that misses a message keeps
+/// its entries until they expire.
+pub struct InvalidationBus {
+    origin: String,
+    transport: Transport,
+}
+
+fn bus_error(e: impl std::fmt::Display) -> AppError {
+    AppError::Internal(format!("Invalidation bus error: {}", e))
+}
+
+impl BusConfig {
+    /// Connects to the channel or joins the group.
+    pub async fn open(&self) -> Result<Arc<InvalidationBus>> {
+        let transport = match self {
+            BusConfig::Redis { url, channel } => {
+                info!(%channel, "Publishing invalidations on Redis");
+                let client = redis::Client::open(url.0.as_str())
+                    .map_err(bus_error)?;
+                Transport::Redis {
+                    url: url.clone(),
+                    channel: channel.clone(),
+                    connection: Box::new(
+                        ConnectionManager::new(client)
+                            .await
+                            .map_err(bus_error)?,
+                    ),
+                }
+            }
+            BusConfig::Multicast { group, keys } => {
+                info!(%group, "Publishing invalidations on multicast");
+                Transport::Multicast {
+                    socket: Arc::new(
+                        join_group(group).map_err(bus_error)?,
+                    ),
+                    group: *group,
+                    keys: keys.clone(),
+                }
+            }
+        };
+        Ok(Arc::new(InvalidationBus {
+            origin: format!("{:016x}", rand::random::<u64>()),
+            transport,
+        }))
+    }
+}
+
+/// A socket receiving the datagrams sent to `group`, bound with
+/// `SO_REUSEADDR` so that replicas sharing a host all get them.
+fn join_group(group: &SocketAddrV4) -> std::io::Result<UdpSocket> {
+    let socket =
+        Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    socket.bind(
+        &SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, group.port())
+            .into(),
+    )?;
+    socket.join_multicast_v4(group.ip(), &Ipv4Addr::UNSPECIFIED)?;
+    socket.set_multicast_loop_v4(true)?;
+    socket.set_nonblocking(true)?;
+    UdpSocket::from_std(socket.into())
+}
+
+impl InvalidationBus {
+    /// Tells the other replicas that `cache`, or every cache, was
+    /// cleared. Failures are logged.
+    pub async fn publish(&self, cache: Option<&str>) {
+        let message = Invalidation {
+            origin: self.origin.clone(),
+            cache: cache.map(str::to_string),
+        };
+        let Ok(payload) = serde_json::to_vec(&message) else {
+            return;
+        };
+        let result = match &self.transport {
+            Transport::Redis {
+                channel,
+                connection,
+                ..
+            } => connection
+                .as_ref()
+                .clone()
+                .publish::<_, _, ()>(channel, payload)
+                .await
+                .map_err(bus_error),
+            Transport::Multicast {
+                socket,
+                group,
+                keys,
+            } => socket
+                .send_to(&sign(keys, &payload), group)
+                .await
+                .map(|_| ())
+                .map_err(bus_error),
+        };
+        match result {
+            Ok(()) => debug!(?cache, "Published cache invalidation"),
+            Err(e) => {
+                warn!(?cache, error = %e, "Failed to publish cache invalidation")
+            }
+        }
+    }
+
+    /// Calls `on_invalidation` with every cache cleared by another
+    /// replica, in the background.
+    pub fn subscribe<F>(self: &Arc<Self>, on_invalidation: F)
+    where
+        F: Fn(Option<&str>) + Send + Sync + 'static,
+    {
+        let bus = self.clone();
+        tokio::spawn(async move {
+            match &bus.transport {
+                Transport::Redis { url, channel, .. } => loop {
+                    if let Err(e) = bus
+                        .listen_redis(url, channel, &on_invalidation)
+                        .await
+                    {
+                        warn!(error = %e, "Invalidation subscription failed");
+                    }
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                },
+                Transport::Multicast { socket, keys, .. } => {
+                    let mut buffer = [0; MAX_DATAGRAM];
+                    loop {
+                        match socket.recv(&mut buffer).await {
+                            Ok(len) => {
+                                match verify(keys, &buffer[..len]) {
+                                    Ok(payload) => bus.receive(
+                                        payload,
+                                        &on_invalidation,
+                                    ),
+                                    Err(e) => {
+                                        warn!(error = %e, "Dropping unverified invalidation")
+                                    }
+                                }
+                            }
+                            Err(e) => {
+                                warn!(error = %e, "Failed to receive invalidation")
+                            }
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    async fn listen_redis<F>(
+        &self,
+        url: &Secret,
+        channel: &str,
+        on_invalidation: &F,
+    ) -> Result<()>
+    where
+        F: Fn(Option<&str>),
+    {
+        let client =
+            redis::Client::open(url.0.as_str()).map_err(bus_error)?;
+        let mut pubsub =
+            client.get_async_pubsub().await.map_err(bus_error)?;
+        pubsub.subscribe(channel).await.map_err(bus_error)?;
+        let mut messages = pubsub.on_message();
+        while let Some(message) = messages.next().await {
+            self.receive(
+                message.get_payload_bytes(),
+                on_invalidation,
+            );
+        }
+        Err(bus_error("subscription closed"))
+    }
+
+    fn receive<F>(&self, payload: &[u8], on_invalidation: &F)
+    where
+        F: Fn(Option<&str>),
+    {
+        let message: Invalidation = match serde_json::from_slice(
+            payload,
+        ) {
+            Ok(message) => message,
+            Err(e) => {
+                warn!(error = %e, "Ignoring malformed invalidation");
+                return;
+            }
+        };
+        if message.origin == self.origin {
+            return;
+        }
+        info!(
+            cache = ?message.cache,
+            origin = %message.origin,
+            "Clearing cache invalidated by another replica"
+        );
+        on_invalidation(message.cache.as_deref());
+    }
+}
+
+/// A multicast datagram: the signature header of `payload`, a
+/// newline, then `payload`.
+fn sign(keys: &SigningKeys, payload: &[u8]) -> Vec<u8> {
+    let mut datagram =
+        keys.sign(payload, unix_millis() / 1000).into_bytes();
+    datagram.push(b'\n');
+    datagram.extend_from_slice(payload);
+    datagram
+}
+
+/// The payload of a datagram made by `sign` with one of `keys`.
+fn verify<'a>(
+    keys: &SigningKeys,
+    datagram: &'a [u8],
+) -> std::result::Result<&'a [u8], String> {
+    let newline = datagram
+        .iter()
+        .position(|byte| *byte == b'\n')
+        .ok_or("missing signature")?;
+    let header = std::str::from_utf8(&datagram[..newline])
+        .map_err(|_| "malformed signature")?;
+    let payload = &datagram[newline + 1..];
+    keys.verify(
+        header,
+        payload,
+        unix_millis() / 1000,
+        DEFAULT_TOLERANCE,
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[tokio::test]
+    async fn test_receive_skips_own_messages() {
+        let group =
+            SocketAddrV4::new(Ipv4Addr::new(239, 255, 70, 1), 0);
+        let bus = InvalidationBus {
+            origin: "me".to_string(),
+            transport: Transport::Multicast {
+                socket: Arc::new(
+                    UdpSocket::bind("127.0.0.1:0").await.unwrap(),
+                ),
+                group,
+                keys: SigningKeys::parse("s3cret"),
+            },
+        };
+        let cleared = Mutex::new(Vec::new());
+        let record = |cache: Option<&str>| {
+            cleared.lock().unwrap().push(cache.map(str::to_string))
+        };
+        for message in [
+            Invalidation {
+                origin: "me".to_string(),
+                cache: None,
+            },
+            Invalidation {
+                origin: "other".to_string(),
+                cache: Some("pokemon.species".to_string()),
+            },
+            Invalidation {
+                origin: "other".to_string(),
+                cache: None,
+            },
+        ] {
+            bus.receive(
+                &serde_json::to_vec(&message).unwrap(),
+                &record,
+            );
+        }
+        bus.receive(b"not json", &record);
+        assert_eq!(
+            *cleared.lock().unwrap(),
+            vec![Some("pokemon.species".to_string()), None]
+        );
+    }
+
+    #[test]
+    fn test_forged_datagrams_are_rejected() {
+        let keys = SigningKeys::parse("s3cret");
+        let payload = br#"{"origin":"other","cache":null}"#;
+        let datagram = sign(&keys, payload);
+        assert_eq!(verify(&keys, &datagram), Ok(&payload[..]));
+
+        // Signed with another secret, unsigned, or tampered with.
+        let forged = sign(&SigningKeys::parse("guess"), payload);
+        assert!(verify(&keys, &forged).is_err());
+        assert!(verify(&keys, payload).is_err());
+        let mut tampered = datagram.clone();
+        *tampered.last_mut().unwrap() = b' ';
+        assert!(verify(&keys, &tampered).is_err());
+    }
+}
@@ -0,0 +1,460 @@
+use crate::cache::{Cache, ManagedCache};
+use crate::error::Result;
+use crate::lang::Lang;
+use crate::pokeapi::{NamedApiResource, PokeApiClient};
+use crate::pokemon::Localized;
+use crate::text::{self, Normalization};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+use tracing::instrument;
+use utoipa::ToSchema;
+
+/// An item rendered in the requested language.
+#[derive(Debug, Clone, Serialize, PartialEq, ToSchema)]
+pub struct Item {
+    pub id: u32,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    pub category: Option<String>,
+    /// Price in Poke Dollars.
+    pub cost: u32,
+    /// What the item does; PokeAPI mostly has it in English only.
+    pub effect: Option<String>,
+    pub short_effect: Option<String>,
+    /// In-game description from the latest games that have one.
+    pub flavor_text: Option<String>,
+    pub sprite: Option<String>,
+}
+
+/// A berry with the item it is held and used as.
+#[derive(Debug, Clone, Serialize, PartialEq, ToSchema)]
+pub struct Berry {
+    pub id: u32,
+    pub name: String,
+    pub firmness: Option<String>,
+    /// Potency by flavor, without the flavors it lacks.
+    pub flavors: BTreeMap<String, u32>,
+    /// Hours each growth stage takes.
+    pub growth_time: u32,
+    pub max_harvest: u32,
+    /// Size in millimeters.
+    pub size: u32,
+    pub smoothness: u32,
+    pub natural_gift_type: Option<String>,
+    pub natural_gift_power: u32,
+    pub item: Item,
+}
+
+#[derive(Deserialize)]
+struct PokeApiItem {
+    id: u32,
+    name: String,
+    #[serde(default)]
+    cost: u32,
+    category: Option<NamedApiResource>,
+    #[serde(default)]
+    effect_entries: Vec<PokeApiEffect>,
+    #[serde(default)]
+    flavor_text_entries: Vec<PokeApiItemFlavorText>,
+    #[serde(default)]
+    names: Vec<PokeApiName>,
+    #[serde(default)]
+    sprites: PokeApiItemSprites,
+}
+
+#[derive(Deserialize)]
+struct PokeApiEffect {
+    effect: String,
+    short_effect: String,
+    language: NamedApiResource,
+}
+
+#[derive(Deserialize)]
+struct PokeApiItemFlavorText {
+    text: String,
+    language: NamedApiResource,
+}
+
+#[derive(Deserialize)]
+struct PokeApiName {
+    name: String,
+    language: NamedApiResource,
+}
+
+#[derive(Deserialize, Default)]
+struct PokeApiItemSprites {
+    default: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PokeApiBerry {
+    id: u32,
+    name: String,
+    firmness: Option<NamedApiResource>,
+    #[serde(default)]
+    flavors: Vec<PokeApiBerryFlavor>,
+    #[serde(default)]
+    growth_time: u32,
+    #[serde(default)]
+    max_harvest: u32,
+    #[serde(default)]
+    size: u32,
+    #[serde(default)]
+    smoothness: u32,
+    natural_gift_type: Option<NamedApiResource>,
+    #[serde(default)]
+    natural_gift_power: u32,
+    item: NamedApiResource,
+}
+
+#[derive(Deserialize)]
+struct PokeApiBerryFlavor {
+    potency: u32,
+    flavor: NamedApiResource,
+}
+
+/// Effect texts of an item or move in one language.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Effect {
+    pub language: String,
+    pub effect: String,
+    pub short_effect: String,
+}
+
+/// What we keep of an item: everything but the strings in the
+/// requested language, which are picked when rendering.
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedItem {
+    id: u32,
+    name: String,
+    category: Option<String>,
+    cost: u32,
+    sprite: Option<String>,
+    names: Vec<Localized>,
+    effects: Vec<Effect>,
+    /// Newest games first.
+    flavor_texts: Vec<Localized>,
+}
+
+impl CachedItem {
+    fn render(&self, lang: &Lang) -> Item {
+        let pick = |entries: &[Localized]| {
+            lang.pick(entries, |e| &e.language)
+                .map(|e| e.value.clone())
+        };
+        let effect = lang.pick(&self.effects, |e| &e.language);
+        Item {
+            id: self.id,
+            name: self.name.clone(),
+            display_name: pick(&self.names),
+            category: self.category.clone(),
+            cost: self.cost,
+            effect: effect.map(|e| e.effect.clone()),
+            short_effect: effect.map(|e| e.short_effect.clone()),
+            flavor_text: pick(&self.flavor_texts),
+            sprite: self.sprite.clone(),
+        }
+    }
+}
+
+/// A berry without its item.
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedBerry {
+    id: u32,
+    name: String,
+    firmness: Option<String>,
+    flavors: BTreeMap<String, u32>,
+    growth_time: u32,
+    max_harvest: u32,
+    size: u32,
+    smoothness: u32,
+    natural_gift_type: Option<String>,
+    natural_gift_power: u32,
+    /// Name of the berry's item.
+    item: String,
+}
+
+pub struct ItemService {
+    pokeapi: PokeApiClient,
+    normalization: Normalization,
+    item_cache: Cache<String, Arc<CachedItem>>,
+    berry_cache: Cache<String, Arc<CachedBerry>>,
+}
+
+impl ItemService {
+    pub fn new(pokeapi: PokeApiClient, cache_ttl: Duration) -> Self {
+        Self {
+            pokeapi,
+            normalization: Normalization::default(),
+            item_cache: Cache::new(cache_ttl),
+            berry_cache: Cache::new(cache_ttl),
+        }
+    }
+
+    /// Normalizes the effect and flavor texts like the Pokemon
+    /// descriptions.
+    pub fn with_normalization(
+        mut self,
+        normalization: Normalization,
+    ) -> Self {
+        self.normalization = normalization;
+        self
+    }
+
+    /// The caches owned by this service, by name.
+    pub fn caches(&self) -> Vec<(&'static str, &dyn ManagedCache)> {
+        vec![
+            ("item.items", &self.item_cache),
+            ("item.berries", &self.berry_cache),
+        ]
+    }
+
+    #[instrument(skip(self))]
+    pub async fn item(
+        &self,
+        name: &str,
+        lang: &Lang,
+    ) -> Result<Item> {
+        Ok(self.get_item(name).await?.render(lang))
+    }
+
+    #[instrument(skip(self))]
+    pub async fn berry(
+        &self,
+        name: &str,
+        lang: &Lang,
+    ) -> Result<Berry> {
+        let berry = self.get_berry(name).await?;
+        let item = self.get_item(&berry.item).await?;
+        Ok(Berry {
+            id: berry.id,
+            name: berry.name.clone(),
+            firmness: berry.firmness.clone(),
+            flavors: berry.flavors.clone(),
+            growth_time: berry.growth_time,
+            max_harvest: berry.max_harvest,
+            size: berry.size,
+            smoothness: berry.smoothness,
+            natural_gift_type: berry.natural_gift_type.clone(),
+            natural_gift_power: berry.natural_gift_power,
+            item: item.render(lang),
+        })
+    }
+
+    async fn get_item(&self, name: &str) -> Result<Arc<CachedItem>> {
+        let key = name.to_lowercase();
+        if let Some(item) = self.item_cache.fetch(&key).await {
+            return Ok(item);
+        }
+
+        let item: PokeApiItem = self
+            .pokeapi
+            .get(&format!("item/{}", key), || {
+                format!("Item '{}' not found", name)
+            })
+            .await?;
+        let item = Arc::new(self.map_to_item(item));
+        self.item_cache.store(key, item.clone()).await;
+        Ok(item)
+    }
+
+    async fn get_berry(
+        &self,
+        name: &str,
+    ) -> Result<Arc<CachedBerry>> {
+        let key = name.to_lowercase();
+        if let Some(berry) = self.berry_cache.fetch(&key).await {
+            return Ok(berry);
+        }
+
+        let berry: PokeApiBerry = self
+            .pokeapi
+            .get(&format!("berry/{}", key), || {
+                format!("Berry '{}' not found", name)
+            })
+            .await?;
+        let berry = Arc::new(map_to_berry(berry));
+        self.berry_cache.store(key, berry.clone()).await;
+        Ok(berry)
+    }
+
+    fn clean(&self, text: &str) -> String {
+        self.normalization
+            .apply(&text::clean_description(text))
+            .into_owned()
+    }
+
+    fn map_to_item(&self, item: PokeApiItem) -> CachedItem {
+        CachedItem {
+            id: item.id,
+            name: item.name,
+            category: item.category.map(|category| category.name),
+            cost: item.cost,
+            sprite: item.sprites.default,
+            names: item
+                .names
+                .into_iter()
+                .map(|name| Localized {
+                    language: name.language.name,
+                    value: name.name,
+                })
+                .collect(),
+            effects: item
+                .effect_entries
+                .into_iter()
+                .map(|entry| Effect {
+                    language: entry.language.name,
+                    effect: self.clean(&entry.effect),
+                    short_effect: self.clean(&entry.short_effect),
+                })
+                .collect(),
+            // PokeAPI lists them oldest games first.
+            flavor_texts: item
+                .flavor_text_entries
+                .into_iter()
+                .rev()
+                .map(|entry| Localized {
+                    language: entry.language.name,
+                    value: self.clean(&entry.text),
+                })
+                .collect(),
+        }
+    }
+}
+
+fn map_to_berry(berry: PokeApiBerry) -> CachedBerry {
+    CachedBerry {
+        id: berry.id,
+        name: berry.name,
+        firmness: berry.firmness.map(|firmness| firmness.name),
+        flavors: berry
+            .flavors
+            .into_iter()
+            .filter(|flavor| flavor.potency > 0)
+            .map(|flavor| (flavor.flavor.name, flavor.potency))
+            .collect(),
+        growth_time: berry.growth_time,
+        max_harvest: berry.max_harvest,
+        size: berry.size,
+        smoothness: berry.smoothness,
+        natural_gift_type: berry.natural_gift_type.map(|t| t.name),
+        natural_gift_power: berry.natural_gift_power,
+        item: berry.item.name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn service(server: &MockServer) -> ItemService {
+        ItemService::new(
+            PokeApiClient::new(
+                http::build_client(&http::ClientSettings::new(
+                    Duration::from_secs(5),
+                )),
+                server.uri(),
+            ),
+            Duration::from_secs(60),
+        )
+    }
+
+    fn language(name: &str) -> serde_json::Value {
+        serde_json::json!({"name": name, "url": "u"})
+    }
+
+    #[tokio::test]
+    async fn test_berry_renders_its_item_in_the_language() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/berry/cheri"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({
+                    "id": 1,
+                    "name": "cheri",
+                    "firmness": {"name": "soft", "url": "u"},
+                    "flavors": [
+                        {"potency": 10, "flavor": {"name": "spicy", "url": "u"}},
+                        {"potency": 0, "flavor": {"name": "dry", "url": "u"}}
+                    ],
+                    "growth_time": 3,
+                    "max_harvest": 5,
+                    "size": 20,
+                    "smoothness": 25,
+                    "natural_gift_type": {"name": "fire", "url": "u"},
+                    "natural_gift_power": 60,
+                    "item": {"name": "cheri-berry", "url": "u"}
+                }),
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/item/cheri-berry"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({
+                    "id": 126,
+                    "name": "cheri-berry",
+                    "cost": 80,
+                    "category": {"name": "medicine", "url": "u"},
+                    "effect_entries": [{
+                        "effect": "Held: Consumed when\npoisoned.",
+                        "short_effect": "Cures paralysis.",
+                        "language": language("en")
+                    }],
+                    "flavor_text_entries": [
+                        {"text": "Old\ntext.", "language": language("en")},
+                        {"text": "Cura la\nparálisis.", "language": language("es")},
+                        {"text": "Cures\nparalysis.", "language": language("en")}
+                    ],
+                    "names": [
+                        {"name": "Cheri Berry", "language": language("en")},
+                        {"name": "Baya Zreza", "language": language("es")}
+                    ],
+                    "sprites": {"default": "cheri-berry.png"}
+                }),
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let service = service(&server);
+        let berry = service
+            .berry("Cheri", &Lang("es".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(berry.firmness.as_deref(), Some("soft"));
+        assert_eq!(
+            berry.flavors,
+            BTreeMap::from([("spicy".to_string(), 10)])
+        );
+        assert_eq!(
+            berry.item.display_name.as_deref(),
+            Some("Baya Zreza")
+        );
+        assert_eq!(
+            berry.item.flavor_text.as_deref(),
+            Some("Cura la parálisis.")
+        );
+        // English is the fallback.
+        assert_eq!(
+            berry.item.effect.as_deref(),
+            Some("Held: Consumed when poisoned.")
+        );
+
+        let item = service
+            .item("cheri-berry", &Lang::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            item.flavor_text.as_deref(),
+            Some("Cures paralysis.")
+        );
+        assert_eq!(item.sprite.as_deref(), Some("cheri-berry.png"));
+        service.berry("cheri", &Lang::default()).await.unwrap();
+    }
+}
@@ -0,0 +1,669 @@
+use crate::error::{AppError, Result};
+use crate::flags::Feature;
+use crate::storage::Storage;
+use axum::async_trait;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::Notify;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+const NAMESPACE: &str = "jobs";
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    fn name(self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Completed => "completed",
+            JobStatus::Failed => "failed",
+            JobStatus::Cancelled => "cancelled",
+        }
+    }
+
+    fn is_finished(self) -> bool {
+        matches!(
+            self,
+            JobStatus::Completed
+                | JobStatus::Failed
+                | JobStatus::Cancelled
+        )
+    }
+}
+
+/// Queued jobs of a higher priority run first; jobs of the same
+/// priority run in the order they were queued.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    ToSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+/// How far a running job got.
+#[derive(
+    Debug, Clone, Copy, Default, Serialize, Deserialize, ToSchema,
+)]
+pub struct Progress {
+    /// Items the job works through, once known.
+    pub total: usize,
+    /// Items processed so far, successfully or not.
+    pub processed: usize,
+    /// Items that could not be processed.
+    pub failed: usize,
+}
+
+/// A background job of one of the registered kinds.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Job {
+    pub id: String,
+    /// The kind of job, e.g. `translate-generation`.
+    pub kind: String,
+    /// Parameters of the job, as normalized by its handler.
+    pub params: Value,
+    pub priority: Priority,
+    pub status: JobStatus,
+    /// Unix seconds.
+    pub created_at: u64,
+    /// Attempts started so far.
+    pub attempts: u32,
+    pub max_attempts: u32,
+    /// Unix seconds of the next attempt after a failed one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_at: Option<u64>,
+    #[serde(flatten)]
+    pub progress: Progress,
+    /// Why the last attempt failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// What is persisted of a job: its state and, apart, the results
+/// left out of the progress responses.
+#[derive(Serialize, Deserialize)]
+struct StoredJob {
+    job: Job,
+    results: Vec<Value>,
+}
+
+/// Runs the jobs of one kind.
+#[async_trait]
+pub trait JobHandler: Send + Sync {
+    /// Name jobs of this kind are submitted by.
+    fn kind(&self) -> &'static str;
+
+    /// Feature that must be enabled to submit jobs of this kind.
+    fn feature(&self) -> Option<Feature> {
+        None
+    }
+
+    /// Checks the parameters of a new job, returning them normalized.
+    fn params(&self, params: Value) -> Result<Value>;
+
+    /// Runs a job, returning one result row per item. A job may run
+    /// more than once: after a failed attempt, or when the service
+    /// stopped while it was running.
+    async fn run(&self, job: &JobContext<'_>) -> Result<Vec<Value>>;
+}
+
+/// What a handler sees of the job it runs.
+pub struct JobContext<'a> {
+    runner: &'a JobRunner,
+    job: Job,
+}
+
+impl JobContext<'_> {
+    pub fn params(&self) -> &Value {
+        &self.job.params
+    }
+
+    /// Records how far the job got; fails once the job has been
+    /// cancelled, so that the handler stops.
+    pub fn progress(&self, progress: Progress) -> Result<()> {
+        self.runner.update(&self.job.id, |job, _| {
+            if job.status == JobStatus::Cancelled {
+                return Err(AppError::Conflict(format!(
+                    "Job '{}' was cancelled",
+                    job.id
+                )));
+            }
+            job.progress = progress;
+            Ok(())
+        })
+    }
+}
+
+/// How failed attempts are retried.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Attempts a job gets, the first one included.
+    pub max_attempts: u32,
+    /// Delay before the second attempt, doubled for each later one.
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// The delay after the failed attempt `attempt`, counted from 1.
+    fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.backoff.saturating_mul(factor)
+    }
+}
+
+/// A job waiting in the queue.
+struct Queued {
+    id: String,
+    priority: Priority,
+    sequence: u64,
+    not_before: Instant,
+}
+
+/// Runs the jobs one at a time in the background, by priority, and
+/// retries failed attempts with a backoff. Jobs are kept in
+/// `Storage`, so with a persistent one the jobs left queued or
+/// running when the service stopped run again on startup.
+pub struct JobRunner {
+    handlers: HashMap<&'static str, Arc<dyn JobHandler>>,
+    storage: Arc<dyn Storage>,
+    retry: RetryPolicy,
+    ttl: Duration,
+    queue: Mutex<Vec<Queued>>,
+    sequence: AtomicU64,
+    wake: Notify,
+    /// Serializes the read-modify-write updates of stored jobs.
+    writes: Mutex<()>,
+}
+
+impl JobRunner {
+    pub fn new(
+        storage: Arc<dyn Storage>,
+        retry: RetryPolicy,
+        ttl: Duration,
+    ) -> Self {
+        Self {
+            handlers: HashMap::new(),
+            storage,
+            retry,
+            ttl,
+            queue: Mutex::new(Vec::new()),
+            sequence: AtomicU64::new(0),
+            wake: Notify::new(),
+            writes: Mutex::new(()),
+        }
+    }
+
+    pub fn with_handler(
+        mut self,
+        handler: Arc<dyn JobHandler>,
+    ) -> Self {
+        self.handlers.insert(handler.kind(), handler);
+        self
+    }
+
+    /// Requeues the jobs left unfinished by a previous run and starts
+    /// the worker.
+    pub fn start(self: &Arc<Self>) -> Result<()> {
+        let mut unfinished: Vec<Job> = self
+            .storage
+            .entries(NAMESPACE)?
+            .into_iter()
+            .filter_map(|(id, value)| {
+                serde_json::from_value::<StoredJob>(value)
+                    .inspect_err(|e| {
+                        warn!(job_id = %id, error = %e, "Skipping corrupted job");
+                    })
+                    .ok()
+            })
+            .map(|stored| stored.job)
+            .filter(|job| !job.status.is_finished())
+            .collect();
+        unfinished.sort_by_key(|job| job.created_at);
+        for job in unfinished {
+            info!(job_id = %job.id, kind = %job.kind, "Resuming job");
+            if job.status == JobStatus::Running {
+                self.update(&job.id, |job, _| {
+                    job.status = JobStatus::Queued;
+                    Ok(())
+                })?;
+            }
+            let delay = job
+                .retry_at
+                .map_or(0, |at| at.saturating_sub(unix_secs()));
+            self.push(&job, Duration::from_secs(delay));
+        }
+
+        let runner = self.clone();
+        tokio::spawn(async move {
+            loop {
+                match runner.next() {
+                    Ok(id) => runner.execute(&id).await,
+                    Err(Some(at)) => {
+                        tokio::select! {
+                            _ = tokio::time::sleep_until(at.into()) => {}
+                            _ = runner.wake.notified() => {}
+                        }
+                    }
+                    Err(None) => runner.wake.notified().await,
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// The handler of `kind`.
+    pub fn handler(
+        &self,
+        kind: &str,
+    ) -> Result<&Arc<dyn JobHandler>> {
+        self.handlers.get(kind).ok_or_else(|| {
+            let mut kinds: Vec<&str> =
+                self.handlers.keys().copied().collect();
+            kinds.sort_unstable();
+            AppError::NotFound(format!(
+                "Unknown job kind '{}', expected one of: {}",
+                kind,
+                kinds.join(", ")
+            ))
+        })
+    }
+
+    /// Queues a job of `kind` with the given parameters.
+    pub fn submit(
+        &self,
+        kind: &str,
+        params: Value,
+        priority: Priority,
+    ) -> Result<Job> {
+        let handler = self.handler(kind)?;
+        let job = Job {
+            id: format!("{:032x}", rand::rng().random::<u128>()),
+            kind: handler.kind().to_string(),
+            params: handler.params(params)?,
+            priority,
+            status: JobStatus::Queued,
+            created_at: unix_secs(),
+            attempts: 0,
+            max_attempts: self.retry.max_attempts,
+            retry_at: None,
+            progress: Progress::default(),
+            error: None,
+        };
+        self.save(&job, &[])?;
+        self.push(&job, Duration::ZERO);
+        info!(job_id = %job.id, kind = %job.kind, "Queued job");
+        Ok(job)
+    }
+
+    pub fn get(&self, id: &str) -> Result<Job> {
+        Ok(self.load(id)?.job)
+    }
+
+    /// Cancels a queued or running job; a running one stops the next
+    /// time it reports progress.
+    pub fn cancel(&self, id: &str) -> Result<Job> {
+        let job = self.update(id, |job, _| {
+            if job.status.is_finished() {
+                return Err(AppError::Conflict(format!(
+                    "Job '{}' is already {}",
+                    id,
+                    job.status.name()
+                )));
+            }
+            job.status = JobStatus::Cancelled;
+            job.retry_at = None;
+            Ok(job.clone())
+        })?;
+        info!(job_id = %id, "Cancelled job");
+        Ok(job)
+    }
+
+    /// The results of a completed job.
+    pub fn results(&self, id: &str) -> Result<Vec<Value>> {
+        let stored = self.load(id)?;
+        if stored.job.status != JobStatus::Completed {
+            return Err(AppError::Conflict(format!(
+                "Job '{}' is not completed",
+                id
+            )));
+        }
+        Ok(stored.results)
+    }
+
+    fn push(&self, job: &Job, delay: Duration) {
+        self.queue.lock().unwrap().push(Queued {
+            id: job.id.clone(),
+            priority: job.priority,
+            sequence: self.sequence.fetch_add(1, Ordering::Relaxed),
+            not_before: Instant::now() + delay,
+        });
+        self.wake.notify_one();
+    }
+
+    /// Takes the next job due to run, or tells when the earliest one
+    /// will be.
+    fn next(&self) -> std::result::Result<String, Option<Instant>> {
+        let now = Instant::now();
+        let mut queue = self.queue.lock().unwrap();
+        let due = queue
+            .iter()
+            .enumerate()
+            .filter(|(_, queued)| queued.not_before <= now)
+            .min_by_key(|(_, queued)| {
+                (std::cmp::Reverse(queued.priority), queued.sequence)
+            })
+            .map(|(i, _)| i);
+        match due {
+            Some(i) => Ok(queue.swap_remove(i).id),
+            None => Err(queue.iter().map(|q| q.not_before).min()),
+        }
+    }
+
+    async fn execute(&self, id: &str) {
+        let started = self.update(id, |job, _| {
+            if job.status != JobStatus::Queued {
+                return Ok(None);
+            }
+            job.status = JobStatus::Running;
+            job.attempts += 1;
+            job.retry_at = None;
+            Ok(Some(job.clone()))
+        });
+        // Cancelled while queued, or expired.
+        let Ok(Some(job)) = started else {
+            return;
+        };
+
+        let outcome = match self.handler(&job.kind) {
+            Ok(handler) => {
+                handler.run(&JobContext { runner: self, job }).await
+            }
+            Err(e) => Err(e),
+        };
+
+        let retry = self.update(id, |job, results| {
+            if job.status == JobStatus::Cancelled {
+                return Ok(None);
+            }
+            match outcome {
+                Ok(rows) => {
+                    info!(job_id = %id, "Job completed");
+                    job.status = JobStatus::Completed;
+                    job.error = None;
+                    *results = rows;
+                    Ok(None)
+                }
+                Err(e) if job.attempts < job.max_attempts => {
+                    let delay = self.retry.delay(job.attempts);
+                    warn!(job_id = %id, attempt = job.attempts, error = %e, "Job attempt failed, retrying");
+                    job.status = JobStatus::Queued;
+                    job.retry_at = Some(unix_secs() + delay.as_secs());
+                    job.error = Some(e.to_string());
+                    Ok(Some((job.clone(), delay)))
+                }
+                Err(e) => {
+                    warn!(job_id = %id, error = %e, "Job failed");
+                    job.status = JobStatus::Failed;
+                    job.error = Some(e.to_string());
+                    Ok(None)
+                }
+            }
+        });
+        match retry {
+            Ok(Some((job, delay))) => self.push(&job, delay),
+            Ok(None) => {}
+            Err(e) => {
+                warn!(job_id = %id, error = %e, "Failed to record job outcome");
+            }
+        }
+    }
+
+    fn load(&self, id: &str) -> Result<StoredJob> {
+        self.storage.get_as::<StoredJob>(NAMESPACE, id)?.ok_or_else(
+            || AppError::NotFound(format!("Job '{}' not found", id)),
+        )
+    }
+
+    fn save(&self, job: &Job, results: &[Value]) -> Result<()> {
+        let stored = StoredJob {
+            job: job.clone(),
+            results: results.to_vec(),
+        };
+        self.storage.put_as(
+            NAMESPACE,
+            &job.id,
+            &stored,
+            Some(self.ttl),
+        )
+    }
+
+    fn update<T>(
+        &self,
+        id: &str,
+        change: impl FnOnce(&mut Job, &mut Vec<Value>) -> Result<T>,
+    ) -> Result<T> {
+        let _writes = self.writes.lock().unwrap();
+        let StoredJob {
+            mut job,
+            mut results,
+        } = self.load(id)?;
+        let value = change(&mut job, &mut results)?;
+        self.save(&job, &results)?;
+        Ok(value)
+    }
+}
+
+fn unix_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// The result rows as CSV, with a header of every field in the order
+/// they first appear. Strings are written as is, missing fields and
+/// nulls as empty fields, other values as JSON.
+pub fn to_csv(rows: &[Value]) -> String {
+    let mut columns: Vec<&str> = Vec::new();
+    for row in rows.iter().filter_map(Value::as_object) {
+        for key in row.keys() {
+            if !columns.contains(&key.as_str()) {
+                columns.push(key);
+            }
+        }
+    }
+
+    let header: Vec<String> =
+        columns.iter().map(|column| csv_field(column)).collect();
+    let mut csv = header.join(",");
+    csv.push('\n');
+    for row in rows {
+        let fields: Vec<String> = columns
+            .iter()
+            .map(|column| match row.get(column) {
+                None | Some(Value::Null) => String::new(),
+                Some(Value::String(value)) => csv_field(value),
+                Some(value) => csv_field(&value.to_string()),
+            })
+            .collect();
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Quotes `value` when it holds a separator, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+    use serde_json::json;
+    use std::sync::atomic::AtomicU32;
+
+    /// Fails its first attempt, then returns its parameters.
+    struct Flaky {
+        attempts: AtomicU32,
+    }
+
+    #[async_trait]
+    impl JobHandler for Flaky {
+        fn kind(&self) -> &'static str {
+            "flaky"
+        }
+
+        fn params(&self, params: Value) -> Result<Value> {
+            Ok(params)
+        }
+
+        async fn run(
+            &self,
+            job: &JobContext<'_>,
+        ) -> Result<Vec<Value>> {
+            if self.attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+                return Err(AppError::Timeout(
+                    "Try again".to_string(),
+                ));
+            }
+            job.progress(Progress {
+                total: 1,
+                processed: 1,
+                failed: 0,
+            })?;
+            Ok(vec![job.params().clone()])
+        }
+    }
+
+    fn runner(storage: Arc<dyn Storage>) -> Arc<JobRunner> {
+        Arc::new(
+            JobRunner::new(
+                storage,
+                RetryPolicy {
+                    max_attempts: 2,
+                    backoff: Duration::from_millis(10),
+                },
+                Duration::from_secs(60),
+            )
+            .with_handler(Arc::new(Flaky {
+                attempts: AtomicU32::new(0),
+            })),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_jobs_resume_and_are_retried() {
+        let storage: Arc<dyn Storage> =
+            Arc::new(MemoryStorage::new());
+        let job = runner(storage.clone())
+            .submit("flaky", json!({"n": 1}), Priority::Normal)
+            .unwrap();
+
+        // A new runner over the same storage, as after a restart.
+        let runner = runner(storage);
+        runner.start().unwrap();
+
+        let mut job = runner.get(&job.id).unwrap();
+        for _ in 0..100 {
+            if job.status.is_finished() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            job = runner.get(&job.id).unwrap();
+        }
+        assert_eq!(job.status, JobStatus::Completed);
+        assert_eq!(job.attempts, 2);
+        assert_eq!(job.progress.processed, 1);
+        assert_eq!(
+            runner.results(&job.id).unwrap(),
+            vec![json!({"n": 1})]
+        );
+        assert!(matches!(
+            runner.cancel(&job.id),
+            Err(AppError::Conflict(_))
+        ));
+    }
+
+    #[test]
+    fn test_queue_order_and_cancellation() {
+        let runner = runner(Arc::new(MemoryStorage::new()));
+        let low =
+            runner.submit("flaky", json!(1), Priority::Low).unwrap();
+        let first =
+            runner.submit("flaky", json!(2), Priority::High).unwrap();
+        let second =
+            runner.submit("flaky", json!(3), Priority::High).unwrap();
+        let cancelled = runner.cancel(&second.id).unwrap();
+        assert_eq!(cancelled.status, JobStatus::Cancelled);
+
+        assert_eq!(runner.next(), Ok(first.id));
+        assert_eq!(runner.next(), Ok(second.id));
+        assert_eq!(runner.next(), Ok(low.id));
+        assert_eq!(runner.next(), Err(None));
+        assert!(matches!(
+            runner.submit("other", json!({}), Priority::Normal),
+            Err(AppError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_to_csv_quotes_fields() {
+        let rows = vec![
+            json!({
+                "name": "pikachu",
+                "description": "Stores electricity, \"zap\".",
+            }),
+            json!({
+                "name": "mew",
+                "description": null,
+                "error": "No description",
+            }),
+        ];
+        assert_eq!(
+            to_csv(&rows),
+            "name,description,error\n\
+             pikachu,\"Stores electricity, \"\"zap\"\".\",\n\
+             mew,,No description\n"
+        );
+    }
+}
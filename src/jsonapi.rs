@@ -0,0 +1,318 @@
+use crate::forwarded::PublicUrl;
+use crate::pokemon::{Pokemon, PokemonDetails};
+use axum::{
+    Json, async_trait,
+    body::{Body, to_bytes},
+    extract::{FromRequestParts, Request},
+    http::{HeaderMap, HeaderValue, header, request::Parts},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use pokedex_rs::models::BatchResponse;
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+use std::convert::Infallible;
+
+pub const MEDIA_TYPE: &str = "application/vnd.api+json";
+
+/// Largest error body rewritten into a JSON:API error document.
+const MAX_ERROR_BODY: usize = 64 * 1024;
+
+/// Set when the client asked for JSON:API documents with
+/// `Accept: application/vnd.api+json`, holding the public URL their
+/// links are built on.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JsonApi(Option<PublicUrl>);
+
+fn requested(headers: &HeaderMap) -> bool {
+    let accept = headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    accept.split(',').any(|media_type| {
+        media_type.split(';').next().unwrap_or_default().trim()
+            == MEDIA_TYPE
+    })
+}
+
+impl JsonApi {
+    /// `resource` as plain JSON or, when asked for, as a JSON:API
+    /// document.
+    pub fn respond<R: Resource>(&self, resource: &R) -> Response {
+        let Some(url) = &self.0 else {
+            return Json(resource).into_response();
+        };
+        document(json!({ "data": resource_object(resource, url) }))
+    }
+
+    /// The batch as plain JSON or, when asked for, as a JSON:API
+    /// collection. The names that failed go in `meta`, since a
+    /// document cannot hold both data and errors.
+    pub fn respond_batch(&self, batch: &BatchResponse) -> Response {
+        let Some(url) = &self.0 else {
+            return Json(batch).into_response();
+        };
+        let data: Vec<Value> = batch
+            .pokemon
+            .iter()
+            .map(|pokemon| resource_object(pokemon, url))
+            .collect();
+        document(json!({
+            "data": data,
+            "meta": { "errors": batch.errors },
+        }))
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for JsonApi {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        if !requested(&parts.headers) {
+            return Ok(JsonApi(None));
+        }
+        let url = PublicUrl::from_request_parts(parts, state).await?;
+        Ok(JsonApi(Some(url)))
+    }
+}
+
+/// A model served as a JSON:API resource object. Its serialized
+/// fields become the attributes, except the relationships.
+pub trait Resource: Serialize {
+    const TYPE: &'static str;
+
+    fn id(&self) -> &str;
+
+    /// Related resources, by relationship name, linked under `url`.
+    fn relationships(&self, _url: &PublicUrl) -> Map<String, Value> {
+        Map::new()
+    }
+}
+
+impl Resource for Pokemon {
+    const TYPE: &'static str = "pokemon";
+
+    fn id(&self) -> &str {
+        &self.name
+    }
+
+    fn relationships(&self, url: &PublicUrl) -> Map<String, Value> {
+        let data = self.habitat.as_ref().map_or(
+            Value::Null,
+            |habitat| json!({ "type": "habitat", "id": habitat }),
+        );
+        let mut habitat = json!({ "data": data });
+        if let Some(name) = &self.habitat {
+            habitat["links"] = json!({
+                "related": url.join(&format!("/habitats/{}/pokemon", name)),
+            });
+        }
+        let chain =
+            self.meta.as_ref().and_then(|meta| meta.evolution_chain);
+        let data = chain.map_or(Value::Null, |id| {
+            json!({ "type": "evolution-chain", "id": id.to_string() })
+        });
+        let mut evolution = json!({ "data": data });
+        if let Some(id) = chain {
+            evolution["links"] = json!({
+                "related": url.join(&format!(
+                    "/proxy/pokeapi/evolution-chain/{}",
+                    id
+                )),
+            });
+        }
+        Map::from_iter([
+            ("habitat".to_string(), habitat),
+            ("evolution".to_string(), evolution),
+        ])
+    }
+}
+
+impl Resource for PokemonDetails {
+    const TYPE: &'static str = Pokemon::TYPE;
+
+    fn id(&self) -> &str {
+        self.pokemon.id()
+    }
+
+    fn relationships(&self, url: &PublicUrl) -> Map<String, Value> {
+        self.pokemon.relationships(url)
+    }
+}
+
+fn resource_object<R: Resource>(
+    resource: &R,
+    url: &PublicUrl,
+) -> Value {
+    let relationships = resource.relationships(url);
+    let mut attributes = match serde_json::to_value(resource) {
+        Ok(Value::Object(attributes)) => attributes,
+        _ => Map::new(),
+    };
+    // The id is not repeated, and a field is either an attribute or
+    // a relationship.
+    attributes.shift_remove("name");
+    for name in relationships.keys() {
+        attributes.shift_remove(name);
+    }
+    json!({
+        "type": R::TYPE,
+        "id": resource.id(),
+        "attributes": attributes,
+        "relationships": relationships,
+        "links": {
+            "self": url.join(&format!("/pokemon/{}", resource.id())),
+        },
+    })
+}
+
+fn document(document: Value) -> Response {
+    (
+        [(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(MEDIA_TYPE),
+        )],
+        Json(document),
+    )
+        .into_response()
+}
+
+/// Rewrites error responses into JSON:API error documents for
+/// clients asking for them.
+pub async fn middleware(request: Request, next: Next) -> Response {
+    let requested = requested(request.headers());
+    let response = next.run(request).await;
+    let is_error = response.status().is_client_error()
+        || response.status().is_server_error();
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|value| value == "application/json");
+    if !requested || !is_error || !is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let Ok(body) = to_bytes(body, MAX_ERROR_BODY).await else {
+        return Response::from_parts(parts, Body::empty());
+    };
+    let Ok(Value::Object(error)) = serde_json::from_slice(&body)
+    else {
+        return Response::from_parts(parts, Body::from(body));
+    };
+
+    let mut object = Map::new();
+    object.insert("status".into(), parts.status.as_str().into());
+    for (from, to) in
+        [("code", "code"), ("error", "title"), ("details", "detail")]
+    {
+        if let Some(value) = error.get(from) {
+            object.insert(to.into(), value.clone());
+        }
+    }
+    if let Some(suggestions) = error.get("suggestions") {
+        object.insert(
+            "meta".into(),
+            json!({ "suggestions": suggestions }),
+        );
+    }
+    let body = json!({ "errors": [object] }).to_string();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(MEDIA_TYPE),
+    );
+    Response::from_parts(parts, Body::from(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pokemon::Meta;
+
+    fn pikachu() -> Pokemon {
+        Pokemon {
+            name: "pikachu".to_string(),
+            display_name: None,
+            genus: None,
+            description: Some("Electric mouse".to_string()),
+            description_language: None,
+            habitat: Some("forest".to_string()),
+            is_legendary: false,
+            is_mythical: false,
+            is_baby: false,
+            breeding: None,
+            meta: Some(Meta {
+                capture_rate: Some(190),
+                base_happiness: Some(50),
+                shape: Some("quadruped".to_string()),
+                color: Some("yellow".to_string()),
+                gender_rate: Some(4),
+                hatch_counter: Some(10),
+                evolution_chain: Some(10),
+            }),
+            artwork: None,
+            phonetics: None,
+            data_source: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_resource_object_splits_attributes_and_relationships() {
+        let url =
+            PublicUrl("https://example.com/pokedex".to_string());
+        let object = resource_object(&pikachu(), &url);
+        assert_eq!(object["type"], "pokemon");
+        assert_eq!(object["id"], "pikachu");
+        assert_eq!(
+            object["attributes"]["description"],
+            "Electric mouse"
+        );
+        assert!(object["attributes"].get("name").is_none());
+        assert!(object["attributes"].get("habitat").is_none());
+        assert_eq!(
+            object["relationships"]["habitat"],
+            json!({
+                "data": { "type": "habitat", "id": "forest" },
+                "links": {
+                    "related": "https://example.com/pokedex/habitats/forest/pokemon",
+                },
+            })
+        );
+        assert_eq!(
+            object["relationships"]["evolution"],
+            json!({
+                "data": { "type": "evolution-chain", "id": "10" },
+                "links": {
+                    "related": "https://example.com/pokedex/proxy/pokeapi/evolution-chain/10",
+                },
+            })
+        );
+
+        let mut ditto = pikachu();
+        ditto.meta = None;
+        let object = resource_object(&ditto, &url);
+        assert_eq!(
+            object["relationships"]["evolution"],
+            json!({ "data": null })
+        );
+    }
+
+    #[test]
+    fn test_requested_by_accept() {
+        let mut headers = HeaderMap::new();
+        assert!(!requested(&headers));
+        headers.insert(
+            header::ACCEPT,
+            HeaderValue::from_static(
+                "text/html, application/vnd.api+json",
+            ),
+        );
+        assert!(requested(&headers));
+    }
+}
@@ -0,0 +1,143 @@
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Query},
+    http::{header, request::Parts},
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::error::AppError;
+use crate::text;
+
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+/// Language requested by the client, taken from `?lang=` or, failing
+/// that, the preferred entry of `Accept-Language`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lang(pub String);
+
+impl Default for Lang {
+    fn default() -> Self {
+        Lang(DEFAULT_LANGUAGE.to_string())
+    }
+}
+
+impl Lang {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Picks the value whose language matches, first exactly and
+    /// then by primary subtag (`zh` matches `zh-Hant`), falling back
+    /// to English.
+    pub fn pick<'a, T>(
+        &self,
+        entries: &'a [T],
+        language_of: impl Fn(&T) -> &str,
+    ) -> Option<&'a T> {
+        let wanted = self.0.to_lowercase();
+        let primary = wanted.split('-').next().unwrap_or_default();
+
+        text::in_language(entries, &wanted, &language_of)
+            .or_else(|| {
+                entries.iter().find(|e| {
+                    language_of(e).split('-').next().is_some_and(
+                        |p| p.eq_ignore_ascii_case(primary),
+                    )
+                })
+            })
+            .or_else(|| {
+                text::in_language(
+                    entries,
+                    DEFAULT_LANGUAGE,
+                    &language_of,
+                )
+            })
+    }
+}
+
+/// Returns the highest-quality language tag of an `Accept-Language`
+/// header, ignoring wildcards.
+pub fn preferred_language(header: &str) -> Option<String> {
+    header
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';').map(str::trim);
+            let tag = parts.next()?;
+            if tag.is_empty() || tag == "*" {
+                return None;
+            }
+            let quality = parts
+                .find_map(|p| p.strip_prefix("q="))
+                .map(|q| q.parse::<f32>().unwrap_or(0.0))
+                .unwrap_or(1.0);
+            (quality > 0.0).then(|| (tag.to_string(), quality))
+        })
+        .fold(None, |best: Option<(String, f32)>, candidate| {
+            match best {
+                Some(best) if best.1 >= candidate.1 => Some(best),
+                _ => Some(candidate),
+            }
+        })
+        .map(|(tag, _)| tag)
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LangParams {
+    lang: Option<String>,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Lang {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        let Query(params) =
+            Query::<LangParams>::from_request_parts(parts, state)
+                .await
+                .map_err(|e| AppError::BadRequest(e.body_text()))?;
+
+        let lang = params
+            .lang
+            .filter(|lang| !lang.is_empty())
+            .or_else(|| {
+                parts
+                    .headers
+                    .get(header::ACCEPT_LANGUAGE)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(preferred_language)
+            });
+
+        Ok(lang.map(Lang).unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preferred_language_uses_quality() {
+        assert_eq!(
+            preferred_language("en;q=0.5, it-IT, fr;q=0.8"),
+            Some("it-IT".to_string())
+        );
+        assert_eq!(preferred_language("*"), None);
+        assert_eq!(preferred_language("de;q=0"), None);
+    }
+
+    #[test]
+    fn test_pick_matches_exactly_then_by_primary_subtag() {
+        let entries = vec!["en", "ja-Hrkt", "ja", "zh-Hant"];
+        let pick = |lang: &str| {
+            Lang(lang.to_string()).pick(&entries, |e| e).copied()
+        };
+        assert_eq!(pick("ja"), Some("ja"));
+        assert_eq!(pick("ZH"), Some("zh-Hant"));
+        assert_eq!(pick("it"), Some("en"));
+    }
+}
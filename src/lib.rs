@@ -0,0 +1,9 @@
+//! Response models of the Pokedex API, the signatures of its
+//! webhook callbacks and, with the `client` feature, a typed client
+//! for it.
+
+pub mod models;
+pub mod webhook;
+
+#[cfg(feature = "client")]
+pub mod client;
@@ -0,0 +1,461 @@
+use axum::Router;
+use futures::future::try_join_all;
+#[cfg(unix)]
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::{conn::auto, graceful::GracefulShutdown},
+    service::TowerToHyperService,
+};
+use std::{fmt, future::Future, io, net::SocketAddr, path::Path};
+#[cfg(unix)]
+use std::{
+    os::fd::{FromRawFd, IntoRawFd, RawFd},
+    path::PathBuf,
+};
+#[cfg(unix)]
+use tokio::net::UnixListener;
+use tokio::{net::TcpListener, sync::watch};
+#[cfg(unix)]
+use tracing::{debug, warn};
+
+/// First file descriptor passed by systemd socket activation.
+#[cfg(unix)]
+const SD_LISTEN_FDS_START: RawFd = 3;
+
+/// Where the server accepts connections, as configured by `LISTEN`.
+/// Unix sockets, including those passed by systemd, only exist on
+/// Unix.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ListenAddr {
+    /// `host:port`, optionally prefixed with `tcp:`.
+    Tcp(String),
+    /// `unix:/path/to/socket`.
+    #[cfg(unix)]
+    Unix(PathBuf),
+    /// `systemd`: the first socket passed through `LISTEN_FDS`.
+    #[cfg(unix)]
+    Systemd,
+}
+
+impl ListenAddr {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let value = value.trim();
+        #[cfg(unix)]
+        {
+            if value == "systemd" {
+                return Ok(ListenAddr::Systemd);
+            }
+            if let Some(path) = value.strip_prefix("unix:") {
+                if path.is_empty() {
+                    return Err(
+                        "unix socket path is empty".to_string()
+                    );
+                }
+                return Ok(ListenAddr::Unix(PathBuf::from(path)));
+            }
+        }
+        #[cfg(not(unix))]
+        if value == "systemd" || value.starts_with("unix:") {
+            return Err(format!(
+                "'{}' needs Unix sockets, which this platform lacks",
+                value
+            ));
+        }
+
+        let addr = value.strip_prefix("tcp:").unwrap_or(value);
+        if addr.is_empty() {
+            return Err("listen address is empty".to_string());
+        }
+        Ok(ListenAddr::Tcp(addr.to_string()))
+    }
+}
+
+impl fmt::Display for ListenAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ListenAddr::Tcp(addr) => write!(f, "http://{}", addr),
+            #[cfg(unix)]
+            ListenAddr::Unix(path) => {
+                write!(f, "unix:{}", path.display())
+            }
+            #[cfg(unix)]
+            ListenAddr::Systemd => write!(f, "systemd socket"),
+        }
+    }
+}
+
+/// Which routes a listener serves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// The public API.
+    Public,
+    /// Operational endpoints, kept off the public listeners whenever
+    /// an admin listener is configured.
+    Admin,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Role::Public => write!(f, "public"),
+            Role::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+/// One entry of `LISTENERS`: `[public=|admin=]<listen address>`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListenerSpec {
+    pub role: Role,
+    pub addr: ListenAddr,
+}
+
+impl ListenerSpec {
+    /// Parses a comma-separated list, such as
+    /// `0.0.0.0:5000,[::]:5000,admin=127.0.0.1:9000`.
+    pub fn parse_list(value: &str) -> Result<Vec<Self>, String> {
+        let specs = value
+            .split(',')
+            .filter(|entry| !entry.trim().is_empty())
+            .map(Self::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if !specs.iter().any(|spec| spec.role == Role::Public) {
+            return Err("at least one public listener is required"
+                .to_string());
+        }
+        Ok(specs)
+    }
+
+    fn parse(entry: &str) -> Result<Self, String> {
+        let entry = entry.trim();
+        let (role, addr) = match entry.split_once('=') {
+            Some(("public", addr)) => (Role::Public, addr),
+            Some(("admin", addr)) => (Role::Admin, addr),
+            Some((role, _)) => {
+                return Err(format!(
+                    "unknown listener role '{}'",
+                    role
+                ));
+            }
+            None => (Role::Public, entry),
+        };
+        Ok(Self {
+            role,
+            addr: ListenAddr::parse(addr)?,
+        })
+    }
+}
+
+/// A bound listening socket.
+pub enum Listener {
+    Tcp(TcpListener),
+    /// The path is set when we created the socket file, so it can be
+    /// removed again on shutdown.
+    #[cfg(unix)]
+    Unix(UnixListener, Option<PathBuf>),
+}
+
+impl Listener {
+    pub async fn bind(addr: &ListenAddr) -> io::Result<Self> {
+        match addr {
+            ListenAddr::Tcp(addr) => {
+                Ok(Listener::Tcp(TcpListener::bind(addr).await?))
+            }
+            #[cfg(unix)]
+            ListenAddr::Unix(path) => {
+                // A socket left behind by a previous run would make
+                // the bind fail with "address in use".
+                if path.exists() {
+                    std::fs::remove_file(path)?;
+                }
+                let listener = UnixListener::bind(path)?;
+                Ok(Listener::Unix(listener, Some(path.clone())))
+            }
+            #[cfg(unix)]
+            ListenAddr::Systemd => from_systemd(),
+        }
+    }
+
+    /// The TCP address bound, with the port the OS picked when
+    /// asked for port `0`.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        match self {
+            Listener::Tcp(listener) => listener.local_addr().ok(),
+            #[cfg(unix)]
+            Listener::Unix(..) => None,
+        }
+    }
+}
+
+/// Writes `port` to `path` for the scripts that start the server on
+/// an ephemeral port. The file is renamed into place, so that they
+/// never read a partial one.
+pub fn write_port_file(path: &Path, port: u16) -> io::Result<()> {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".tmp");
+    std::fs::write(&partial, format!("{}\n", port))?;
+    std::fs::rename(&partial, path)
+}
+
+/// Adopts the first socket passed by systemd, which may be either a
+/// TCP or a Unix stream socket.
+#[cfg(unix)]
+fn from_systemd() -> io::Result<Listener> {
+    let passed_to_us = std::env::var("LISTEN_PID")
+        .ok()
+        .and_then(|pid| pid.parse::<u32>().ok())
+        .is_some_and(|pid| pid == std::process::id());
+    let fds = std::env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|fds| fds.parse::<u32>().ok())
+        .unwrap_or(0);
+    if !passed_to_us || fds == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "no socket was passed by systemd (LISTEN_PID/LISTEN_FDS)",
+        ));
+    }
+    if fds > 1 {
+        warn!(fds, "Only the first systemd socket is used");
+    }
+
+    // SAFETY: systemd hands the process ownership of the descriptors
+    // starting at SD_LISTEN_FDS_START, and nothing else adopts them.
+    let tcp = unsafe {
+        std::net::TcpListener::from_raw_fd(SD_LISTEN_FDS_START)
+    };
+    if tcp.local_addr().is_ok() {
+        tcp.set_nonblocking(true)?;
+        return Ok(Listener::Tcp(TcpListener::from_std(tcp)?));
+    }
+
+    // SAFETY: the descriptor was released by the TCP listener above.
+    let unix = unsafe {
+        std::os::unix::net::UnixListener::from_raw_fd(
+            tcp.into_raw_fd(),
+        )
+    };
+    unix.local_addr()?;
+    unix.set_nonblocking(true)?;
+    Ok(Listener::Unix(UnixListener::from_std(unix)?, None))
+}
+
+/// Serves `app` on `listener` until `shutdown` resolves, then lets
+/// in-flight requests finish.
+pub async fn serve(
+    listener: Listener,
+    app: Router,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> io::Result<()> {
+    match listener {
+        Listener::Tcp(listener) => axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .with_graceful_shutdown(shutdown)
+        .await,
+        #[cfg(unix)]
+        Listener::Unix(listener, path) => {
+            let result = serve_unix(listener, app, shutdown).await;
+            if let Some(path) = path
+                && let Err(e) = std::fs::remove_file(&path)
+            {
+                warn!(path = %path.display(), error = %e, "Failed to remove socket");
+            }
+            result
+        }
+    }
+}
+
+/// Serves every listener with its router until `shutdown` resolves,
+/// shutting all of them down together.
+pub async fn serve_all(
+    listeners: Vec<(Listener, Router)>,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> io::Result<()> {
+    let (notify, stopped) = watch::channel(());
+    tokio::spawn(async move {
+        shutdown.await;
+        notify.send_replace(());
+    });
+
+    try_join_all(listeners.into_iter().map(|(listener, app)| {
+        let mut stopped = stopped.clone();
+        serve(listener, app, async move {
+            // An error means the sender is gone, which also ends
+            // the server.
+            stopped.changed().await.ok();
+        })
+    }))
+    .await?;
+    Ok(())
+}
+
+/// `axum::serve` only accepts TCP listeners, so Unix sockets are
+/// driven by hyper directly.
+#[cfg(unix)]
+async fn serve_unix(
+    listener: UnixListener,
+    app: Router,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> io::Result<()> {
+    let builder = auto::Builder::new(TokioExecutor::new());
+    let graceful = GracefulShutdown::new();
+    tokio::pin!(shutdown);
+
+    loop {
+        let stream = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    warn!(error = %e, "Failed to accept connection");
+                    continue;
+                }
+            },
+            _ = &mut shutdown => break,
+        };
+
+        let connection = builder
+            .serve_connection_with_upgrades(
+                TokioIo::new(stream),
+                TowerToHyperService::new(app.clone()),
+            )
+            .into_owned();
+        let connection = graceful.watch(connection);
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                debug!(error = %e, "Connection closed with error");
+            }
+        });
+    }
+
+    drop(listener);
+    graceful.shutdown().await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use axum::routing::get;
+    #[cfg(unix)]
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[test]
+    fn test_parse_listen_addr() {
+        assert_eq!(
+            ListenAddr::parse("0.0.0.0:5000"),
+            Ok(ListenAddr::Tcp("0.0.0.0:5000".to_string()))
+        );
+        assert_eq!(
+            ListenAddr::parse("tcp:[::]:5000"),
+            Ok(ListenAddr::Tcp("[::]:5000".to_string()))
+        );
+        assert!(ListenAddr::parse("").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_unix_listen_addr() {
+        assert_eq!(
+            ListenAddr::parse("unix:/run/pokedex.sock"),
+            Ok(ListenAddr::Unix(PathBuf::from("/run/pokedex.sock")))
+        );
+        assert_eq!(
+            ListenAddr::parse("systemd"),
+            Ok(ListenAddr::Systemd)
+        );
+        assert!(ListenAddr::parse("unix:").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ephemeral_port_is_reported() {
+        let listener = Listener::bind(&ListenAddr::Tcp(
+            "127.0.0.1:0".to_string(),
+        ))
+        .await
+        .unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert_ne!(port, 0);
+
+        let path = std::env::temp_dir().join(format!(
+            "pokedex-test-{}.port",
+            std::process::id()
+        ));
+        write_port_file(&path, port).unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(written.trim(), port.to_string());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_listener_specs() {
+        assert_eq!(
+            ListenerSpec::parse_list(
+                "0.0.0.0:5000, [::]:5000,admin=unix:/run/admin.sock"
+            ),
+            Ok(vec![
+                ListenerSpec {
+                    role: Role::Public,
+                    addr: ListenAddr::Tcp("0.0.0.0:5000".to_string()),
+                },
+                ListenerSpec {
+                    role: Role::Public,
+                    addr: ListenAddr::Tcp("[::]:5000".to_string()),
+                },
+                ListenerSpec {
+                    role: Role::Admin,
+                    addr: ListenAddr::Unix(PathBuf::from(
+                        "/run/admin.sock"
+                    )),
+                },
+            ])
+        );
+        assert!(
+            ListenerSpec::parse_list("admin=127.0.0.1:9000").is_err()
+        );
+        assert!(
+            ListenerSpec::parse_list("metrics=127.0.0.1:9000")
+                .is_err()
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_serve_unix_socket() {
+        let path = std::env::temp_dir().join(format!(
+            "pokedex-test-{}.sock",
+            std::process::id()
+        ));
+        let listener =
+            Listener::bind(&ListenAddr::Unix(path.clone()))
+                .await
+                .unwrap();
+        let app =
+            Router::new().route("/health", get(|| async { "ok" }));
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(serve(listener, app, async {
+            stopped.await.ok();
+        }));
+
+        let mut stream =
+            tokio::net::UnixStream::connect(&path).await.unwrap();
+        stream
+            .write_all(
+                b"GET /health HTTP/1.1\r\nHost: x\r\nConnection: close\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with("ok"));
+
+        stop.send(()).unwrap();
+        server.await.unwrap().unwrap();
+        assert!(!path.exists());
+    }
+}
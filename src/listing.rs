@@ -0,0 +1,221 @@
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use utoipa::IntoParams;
+
+use crate::error::{AppError, Result};
+
+pub use pokedex_rs::models::Page;
+
+pub const DEFAULT_LIMIT: usize = 20;
+pub const MAX_LIMIT: usize = 100;
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PageParams {
+    pub limit: Option<usize>,
+    pub cursor: Option<String>,
+}
+
+/// Opaque position in a listing: the id of the last item returned
+/// plus the filters the listing was produced with, so a cursor can't
+/// be replayed against a different query.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Cursor {
+    pub after: u32,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub filters: BTreeMap<String, String>,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        let json = serde_json::to_vec(self)
+            .expect("cursor serialization cannot fail");
+        URL_SAFE_NO_PAD.encode(json)
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self> {
+        URL_SAFE_NO_PAD
+            .decode(cursor)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .ok_or_else(|| {
+                AppError::BadRequest("Invalid cursor".to_string())
+            })
+    }
+}
+
+/// Returns the page of `items` following the cursor in `params`.
+/// `items` must be sorted by the id returned from `id_of`.
+pub fn paginate<T: Clone>(
+    items: &[T],
+    id_of: impl Fn(&T) -> u32,
+    params: &PageParams,
+    filters: BTreeMap<String, String>,
+) -> Result<Page<T>> {
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT);
+    if limit == 0 || limit > MAX_LIMIT {
+        return Err(AppError::BadRequest(format!(
+            "limit must be between 1 and {}",
+            MAX_LIMIT
+        )));
+    }
+
+    let start = match &params.cursor {
+        Some(cursor) => {
+            let cursor = Cursor::decode(cursor)?;
+            if cursor.filters != filters {
+                return Err(AppError::BadRequest(
+                    "Cursor does not match the request filters"
+                        .to_string(),
+                ));
+            }
+            items.partition_point(|item| id_of(item) <= cursor.after)
+        }
+        None => 0,
+    };
+
+    let results: Vec<T> =
+        items[start..].iter().take(limit).cloned().collect();
+    let next_cursor = if start + results.len() < items.len() {
+        results.last().map(|last| {
+            Cursor {
+                after: id_of(last),
+                filters,
+            }
+            .encode()
+        })
+    } else {
+        None
+    };
+
+    Ok(Page {
+        count: items.len(),
+        results,
+        next_cursor,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(limit: usize, cursor: Option<String>) -> PageParams {
+        PageParams {
+            limit: Some(limit),
+            cursor,
+        }
+    }
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let cursor = Cursor {
+            after: 25,
+            filters: BTreeMap::from([(
+                "q".to_string(),
+                "pika".to_string(),
+            )]),
+        };
+        assert_eq!(Cursor::decode(&cursor.encode()).unwrap(), cursor);
+    }
+
+    #[test]
+    fn test_invalid_cursor_is_rejected() {
+        assert!(Cursor::decode("not-a-cursor").is_err());
+    }
+
+    #[test]
+    fn test_paginate_walks_all_pages() {
+        let items: Vec<u32> = (1..=5).collect();
+        let first = paginate(
+            &items,
+            |i| *i,
+            &params(2, None),
+            BTreeMap::new(),
+        )
+        .unwrap();
+        assert_eq!(first.results, vec![1, 2]);
+        assert_eq!(first.count, 5);
+
+        let second = paginate(
+            &items,
+            |i| *i,
+            &params(2, first.next_cursor),
+            BTreeMap::new(),
+        )
+        .unwrap();
+        assert_eq!(second.results, vec![3, 4]);
+
+        let last = paginate(
+            &items,
+            |i| *i,
+            &params(2, second.next_cursor),
+            BTreeMap::new(),
+        )
+        .unwrap();
+        assert_eq!(last.results, vec![5]);
+        assert_eq!(last.next_cursor, None);
+    }
+
+    #[test]
+    fn test_cursor_is_stable_when_items_are_inserted() {
+        let items = vec![1, 2, 3, 4];
+        let first = paginate(
+            &items,
+            |i| *i,
+            &params(2, None),
+            BTreeMap::new(),
+        )
+        .unwrap();
+
+        let items = vec![1, 2, 3, 4, 5];
+        let second = paginate(
+            &items,
+            |i| *i,
+            &params(2, first.next_cursor),
+            BTreeMap::new(),
+        )
+        .unwrap();
+        assert_eq!(second.results, vec![3, 4]);
+    }
+
+    #[test]
+    fn test_cursor_with_other_filters_is_rejected() {
+        let items = vec![1, 2, 3];
+        let filters =
+            BTreeMap::from([("q".to_string(), "a".to_string())]);
+        let first =
+            paginate(&items, |i| *i, &params(1, None), filters)
+                .unwrap();
+        let result = paginate(
+            &items,
+            |i| *i,
+            &params(1, first.next_cursor),
+            BTreeMap::new(),
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_limit_out_of_range_is_rejected() {
+        let items = vec![1];
+        assert!(
+            paginate(
+                &items,
+                |i| *i,
+                &params(0, None),
+                BTreeMap::new()
+            )
+            .is_err()
+        );
+        assert!(
+            paginate(
+                &items,
+                |i| *i,
+                &params(MAX_LIMIT + 1, None),
+                BTreeMap::new()
+            )
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,136 @@
+use std::path::{Path, PathBuf};
+use tracing_appender::{
+    non_blocking::WorkerGuard,
+    rolling::{self, RollingFileAppender},
+};
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+
+/// Where the JSON logs go: standard output, or `LOG_FILE` rotated
+/// every `LOG_ROTATION` with at most `LOG_MAX_FILES` kept.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LogSettings {
+    pub file: Option<PathBuf>,
+    pub rotation: Rotation,
+    /// Zero keeps every rotated file.
+    pub max_files: usize,
+}
+
+/// How often the log file is rotated, suffixing the rotated files
+/// with their date (and hour and minute).
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Rotation {
+    Minutely,
+    Hourly,
+    #[default]
+    Daily,
+    Never,
+}
+
+impl Rotation {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.trim().to_lowercase().as_str() {
+            "minutely" => Ok(Rotation::Minutely),
+            "hourly" => Ok(Rotation::Hourly),
+            "daily" => Ok(Rotation::Daily),
+            "never" => Ok(Rotation::Never),
+            _ => Err(format!(
+                "unknown rotation '{}', expected minutely, hourly, daily or never",
+                value
+            )),
+        }
+    }
+}
+
+impl LogSettings {
+    /// Installs the JSON subscriber. Writes to the file go through a
+    /// background thread, flushed when the returned guard is dropped.
+    pub fn init(&self) -> Result<Option<WorkerGuard>, String> {
+        let (writer, guard) = match &self.file {
+            Some(path) => {
+                let (writer, guard) = tracing_appender::non_blocking(
+                    self.appender(path)?,
+                );
+                (BoxMakeWriter::new(writer), Some(guard))
+            }
+            None => (BoxMakeWriter::new(std::io::stdout), None),
+        };
+        tracing_subscriber::fmt()
+            .with_target(false)
+            .with_level(true)
+            .with_line_number(true)
+            .with_writer(writer)
+            .json()
+            .init();
+        Ok(guard)
+    }
+
+    fn appender(
+        &self,
+        path: &Path,
+    ) -> Result<RollingFileAppender, String> {
+        let directory = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or_else(|| ".".as_ref());
+        let name = path
+            .file_name()
+            .ok_or_else(|| {
+                format!("{} is not a file", path.display())
+            })?
+            .to_string_lossy();
+        let rotation = match self.rotation {
+            Rotation::Minutely => rolling::Rotation::MINUTELY,
+            Rotation::Hourly => rolling::Rotation::HOURLY,
+            Rotation::Daily => rolling::Rotation::DAILY,
+            Rotation::Never => rolling::Rotation::NEVER,
+        };
+        let mut builder = RollingFileAppender::builder()
+            .rotation(rotation)
+            .filename_prefix(name);
+        if self.max_files > 0 {
+            builder = builder.max_log_files(self.max_files);
+        }
+        builder.build(directory).map_err(|e| {
+            format!("Failed to open {}: {}", path.display(), e)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rotation() {
+        assert_eq!(Rotation::parse("Hourly"), Ok(Rotation::Hourly));
+        assert_eq!(Rotation::parse("never"), Ok(Rotation::Never));
+        assert!(Rotation::parse("weekly").is_err());
+    }
+
+    #[test]
+    fn test_appender_writes_in_the_directory() {
+        use std::io::Write;
+
+        let directory = std::env::temp_dir().join(format!(
+            "pokedex-test-{}-logs",
+            std::process::id()
+        ));
+        std::fs::remove_dir_all(&directory).ok();
+        std::fs::create_dir_all(&directory).unwrap();
+        let settings = LogSettings {
+            file: Some(directory.join("pokedex.log")),
+            rotation: Rotation::Never,
+            max_files: 3,
+        };
+        let mut appender = settings
+            .appender(settings.file.as_ref().unwrap())
+            .unwrap();
+        appender.write_all(b"{}\n").unwrap();
+        appender.flush().unwrap();
+
+        let written =
+            std::fs::read_to_string(directory.join("pokedex.log"));
+        std::fs::remove_dir_all(&directory).ok();
+        assert_eq!(written.unwrap(), "{}\n");
+    }
+}
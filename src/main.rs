@@ -1,79 +1,1082 @@
 use axum::{
     Json, Router,
-    extract::{Path, State},
-    http::{HeaderValue, Method, header},
-    response::IntoResponse,
-    routing::get,
+    extract::{DefaultBodyLimit, FromRef, Path, Query, State},
+    http::{HeaderMap, HeaderValue, Method, StatusCode, header},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{delete, get, post, put},
+};
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap, io, path::PathBuf, sync::Arc,
+    time::Duration,
 };
-use std::{sync::Arc, time::Duration};
 use tokio::signal;
 use tower::ServiceBuilder;
 use tower_http::{
     LatencyUnit,
-    compression::CompressionLayer,
+    compression::{
+        CompressionLayer,
+        predicate::{DefaultPredicate, NotForContentType, Predicate},
+    },
     cors::CorsLayer,
     timeout::TimeoutLayer,
     trace::{DefaultMakeSpan, DefaultOnResponse, TraceLayer},
 };
-use tracing::{Level, info};
+use tracing::{Level, info, warn};
+use utoipa::{IntoParams, ToSchema};
 
+mod alerting;
+mod analytics;
+mod audit;
+mod auth;
+mod body;
+mod breeding;
+mod cache;
+mod cache_status;
+mod cache_store;
+mod chaos;
 mod config;
+mod context;
+mod cries;
+mod daemon;
+mod deprecation;
+mod dns;
+mod drift;
+mod encounters;
 mod error;
+mod events;
+mod experiments;
+mod favorites;
+mod field_case;
+mod fixtures;
+mod flags;
+mod forwarded;
+mod habitat;
+mod health;
+mod history;
+mod http;
+mod i18n;
+mod idempotency;
+mod include;
+mod invalidation;
+mod item;
+mod jobs;
+mod jsonapi;
+mod jwt;
+mod lang;
+mod listener;
+mod listing;
+mod logging;
+mod mailer;
+mod memory_guard;
+mod metrics;
+mod moves;
+mod mt;
+mod names;
+mod nature;
+mod notify;
+mod offline;
+mod openapi;
+mod output_filter;
+mod peers;
+mod phonetics;
+mod pokeapi;
 mod pokemon;
+mod proxy;
+mod quiz;
+mod random;
+mod range;
+mod rate_limit;
+mod runtime;
+mod scheduler;
+mod self_test;
+#[cfg(windows)]
+mod service;
+mod shadow;
+mod snapshot;
+mod sources;
+mod storage;
+mod summary;
+mod team;
+mod tenants;
+mod text;
+mod tolerant;
 mod translation;
+mod translation_jobs;
+mod tts;
+mod type_chart;
+#[cfg(feature = "ui")]
+mod ui;
+mod upstreams;
+mod version;
+mod wire;
 
+use alerting::AlertMonitor;
+use analytics::{PokemonUsage, UsageStats};
+use audit::{AuditEntry, AuditLog, AuditQuery};
+use auth::{Authenticator, Principal};
+use body::{BodyLimit, JsonBody};
+use breeding::{BreedingCompatibility, BreedingService};
+use cache::{CacheStats, ManagedCache};
+use cache_store::{CacheBackend, CacheStore};
 use config::Config;
-use error::Result;
-use pokemon::{Pokemon, PokemonService};
-use translation::TranslationService;
+use context::DebugHeaders;
+use cries::{CryService, CryVersion};
+use deprecation::{Deprecation, RouteRegistry};
+use dns::Resolver;
+use drift::SchemaSampler;
+use encounters::{Encounter, EncounterService};
+use error::{FieldError, Result};
+use events::{Event, EventBus};
+use experiments::Variant;
+use favorites::FavoritesService;
+use fixtures::Fixtures;
+use flags::{Feature, FeatureFlags};
+use forwarded::ClientIp;
+use habitat::{HabitatService, HabitatSummary};
+use health::{Dependency, HealthReport};
+use history::StatHistory;
+use http::{ClientSettings, Upstream, UpstreamOptions};
+use idempotency::IdempotencyStore;
+use include::{Include, IncludeParams};
+use invalidation::InvalidationBus;
+use item::{Berry, Item, ItemService};
+use jobs::{Job, JobRunner, Priority};
+use jsonapi::JsonApi;
+use jwt::JwtVerifier;
+use lang::{Lang, LangParams};
+use listener::{Listener, Role};
+use listing::{Page, PageParams};
+use mailer::Mailer;
+use memory_guard::MemoryGuard;
+use metrics::Metrics;
+use moves::{Move, MoveService};
+use nature::{Nature, NatureService};
+use notify::Notifier;
+use openapi::VersionedPokemon;
+use output_filter::{FilterChain, UrlStripper};
+use peers::PeerStore;
+use pokeapi::PokeApiClient;
+use pokedex_rs::models::{BatchError, BatchRequest, BatchResponse};
+use pokemon::{
+    FlavorEntry, Form, Pokemon, PokemonDetails, PokemonService,
+    SpeciesFlags, SpeciesSummary,
+};
+use proxy::{ProxyParams, ProxyService};
+use quiz::{GuessResult, QuizChallenge, QuizService};
+use random::Randomness;
+use rate_limit::RateLimiter;
+use scheduler::Scheduler;
+use shadow::Shadow;
+use sources::SourceSettings;
+use storage::{DiskStorage, MemoryStorage, Storage};
+use summary::{Summary, SummaryParams};
+use team::{TeamAnalysis, TeamService};
+use tenants::{TenantUsage, Tenants};
+use translation::{Comparison, Style, TranslationService};
+use translation_jobs::TranslateGeneration;
+use tts::SpeechService;
+use type_chart::TypeService;
+use upstreams::{UpstreamRegistry, UpstreamSnapshot};
+use version::ApiVersion;
 
 #[derive(Clone)]
 struct AppState {
+    config: Arc<Config>,
     pokemon_service: Arc<PokemonService>,
+    habitat_service: Arc<HabitatService>,
+    breeding_service: Arc<BreedingService>,
+    encounter_service: Arc<EncounterService>,
+    item_service: Arc<ItemService>,
+    move_service: Arc<MoveService>,
+    nature_service: Arc<NatureService>,
+    team_service: Arc<TeamService>,
+    quiz_service: Arc<QuizService>,
+    job_runner: Arc<JobRunner>,
+    favorites_service: Arc<FavoritesService>,
     translation_service: Arc<TranslationService>,
+    speech_service: Arc<SpeechService>,
+    cry_service: Arc<CryService>,
+    type_service: Arc<TypeService>,
+    proxy_service: Arc<ProxyService>,
+    audit_log: Arc<AuditLog>,
+    tenants: Arc<Tenants>,
+    upstreams: Arc<UpstreamRegistry>,
+    authenticator: Arc<Authenticator>,
+    metrics: Arc<Metrics>,
+    events: EventBus,
+    usage: Arc<UsageStats>,
+    flags: Arc<FeatureFlags>,
+    /// Second-level store of the Pokemon caches, if any.
+    cache_store: Option<Arc<dyn CacheStore>>,
+    /// Announces the caches cleared here to the other replicas.
+    invalidations: Option<Arc<InvalidationBus>>,
+    storage: Arc<dyn Storage>,
+}
+
+impl FromRef<AppState> for BodyLimit {
+    fn from_ref(state: &AppState) -> Self {
+        BodyLimit(state.config.max_body_bytes)
+    }
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    // Initialize tracing with JSON formatting for production
-    tracing_subscriber::fmt()
-        .with_target(false)
-        .with_level(true)
-        .with_line_number(true)
-        .json()
-        .init();
+impl AppState {
+    /// Every named cache in the application.
+    fn caches(&self) -> Vec<(&'static str, &dyn ManagedCache)> {
+        let mut caches = self.pokemon_service.caches();
+        caches.extend(self.habitat_service.caches());
+        caches.extend(self.breeding_service.caches());
+        caches.extend(self.encounter_service.caches());
+        caches.extend(self.item_service.caches());
+        caches.extend(self.move_service.caches());
+        caches.extend(self.nature_service.caches());
+        caches.extend(self.cry_service.caches());
+        caches.extend(self.type_service.caches());
+        caches.extend(self.proxy_service.caches());
+        caches.extend(self.translation_service.caches());
+        caches.extend(self.speech_service.caches());
+        caches
+    }
 
-    info!("Starting Pokedex API server");
+    /// Clears the cache named `name`, or every cache, returning
+    /// whether any matched.
+    fn clear_caches(&self, name: Option<&str>) -> bool {
+        let mut found = false;
+        for (cache_name, cache) in self.caches() {
+            if name.is_none_or(|name| name == cache_name) {
+                info!(cache = cache_name, "Clearing cache");
+                cache.clear();
+                found = true;
+            }
+        }
+        found
+    }
+}
 
-    // Load configuration
+fn main() -> Result<()> {
     let config = Config::from_env();
+
+    // Forking only keeps the calling thread, so the server detaches
+    // before the log writer and the runtime start any.
+    let daemonized = std::env::args().any(|arg| arg == "--daemonize");
+    if daemonized {
+        daemon::daemonize(&config)?;
+    }
+
+    // JSON logs, to standard output or the rotated `LOG_FILE`
+    let _log_guard =
+        config.log.init().map_err(error::AppError::Internal)?;
+    info!("Starting Pokedex API server");
     info!("Configuration loaded: {:?}", config);
+    let pid_file = config.pid_file.clone().filter(|_| daemonized);
+
+    #[cfg(windows)]
+    if std::env::args().any(|arg| arg == "--service") {
+        return service::run(config, serve);
+    }
+    let result = serve(config);
+    if let Some(path) = pid_file {
+        std::fs::remove_file(path).ok();
+    }
+    result
+}
+
+/// Runs the server until a shutdown signal.
+fn serve(config: Config) -> Result<()> {
+    // Built by hand rather than with `#[tokio::main]`, so that it
+    // can be sized by the configuration.
+    config
+        .runtime
+        .build()
+        .expect("Failed to build the Tokio runtime")
+        .block_on(run(config))
+}
 
+async fn run(config: Config) -> Result<()> {
     // Initialize services with configuration
-    let pokemon_service = Arc::new(PokemonService::new(
+    let mut client_settings =
+        ClientSettings::new(config.http_timeout)
+            .with_proxy(config.upstream_proxy.clone())
+            .with_tls(config.upstream_tls.clone());
+    if !config.dns_overrides.is_empty()
+        || !config.dns_cache_ttl.is_zero()
+    {
+        client_settings =
+            client_settings.with_resolver(Arc::new(Resolver::new(
+                config.dns_overrides.clone(),
+                config.dns_cache_ttl,
+            )));
+    }
+
+    let mut sinks = Vec::new();
+    for sink in &config.event_sinks {
+        sinks.push(
+            sink.open(http::build_client(&client_settings)).await?,
+        );
+    }
+    let events = EventBus::start(sinks);
+
+    let upstreams = Arc::new(
+        UpstreamRegistry::new(config.circuit_breaker)
+            .with_events(events.clone()),
+    );
+    if !config.alerts.sinks.is_empty() {
+        let sinks = config
+            .alerts
+            .sinks
+            .iter()
+            .map(|sink| {
+                sink.open(http::build_client(&client_settings))
+            })
+            .collect::<Result<_>>()?;
+        AlertMonitor::new(config.alerts.clone())
+            .watch(upstreams.clone(), sinks);
+    }
+    let metrics = Arc::new(Metrics::new());
+    let upstream_options = UpstreamOptions {
+        max_response_bytes: config.upstream_max_response_bytes,
+        fixtures: config.fixture_mode.map(|mode| {
+            info!(?mode, dir = %config.fixtures_dir.display(), "Using upstream fixtures");
+            Arc::new(Fixtures::new(mode, config.fixtures_dir.clone()))
+        }),
+        chaos: config.chaos.clone().map(|chaos| {
+            warn!(?chaos, "Chaos fault injection is enabled");
+            Arc::new(chaos)
+        }),
+        registry: Some(upstreams.clone()),
+        timeout: Some(config.http_timeout),
+        schema_sampler: (config.schema_sample_percent > 0.0).then(
+            || {
+                let sampler =
+                    Arc::new(SchemaSampler::new(config.schema_sample_percent));
+                sampler
+                    .clone()
+                    .watch(config.schema_check, metrics.clone());
+                sampler
+            },
+        ),
+    };
+
+    let pokeapi = PokeApiClient::new(
+        http::build_client(&client_settings),
         config.pokeapi_base_url.clone(),
-        config.http_timeout,
+    )
+    .with_options(upstream_options.clone());
+    let pokeapi = match &config.shadow_pokeapi_base_url {
+        Some(base_url) => {
+            info!(
+                base_url,
+                percent = config.shadow_percent,
+                "Mirroring PokeAPI calls to a shadow upstream"
+            );
+            let upstream = Upstream::new(
+                "PokeAPI shadow",
+                http::build_client(&client_settings),
+            )
+            .with_options(UpstreamOptions {
+                max_response_bytes: config
+                    .upstream_max_response_bytes,
+                timeout: Some(config.http_timeout),
+                ..UpstreamOptions::default()
+            });
+            pokeapi.with_shadow(Arc::new(Shadow::new(
+                upstream,
+                base_url.clone(),
+                config.shadow_percent,
+                metrics.clone(),
+            )))
+        }
+        None => pokeapi,
+    };
+
+    let storage: Arc<dyn Storage> = match &config.storage_path {
+        Some(path) => {
+            info!(path = %path.display(), "Opening storage");
+            Arc::new(DiskStorage::open(path).unwrap_or_else(|e| {
+                panic!("STORAGE_PATH is invalid: {}", e)
+            }))
+        }
+        None => Arc::new(MemoryStorage::new()),
+    };
+
+    let mut pokemon_service =
+        PokemonService::new(pokeapi.clone(), config.cache_ttl)
+            .with_stale_fallback(config.stale_cache_ttl)
+            .with_normalization(config.text_normalization)
+            .with_description_fallback(
+                config.description_fallback_languages.clone(),
+            );
+    // The peer store also answers the other replicas.
+    let peer_store = match &config.cache_l2 {
+        Some(CacheBackend::Peers(settings)) => {
+            Some(PeerStore::start(settings)?)
+        }
+        _ => None,
+    };
+    let cache_store = match (&config.cache_l2, &peer_store) {
+        (_, Some(store)) => {
+            Some(store.clone() as Arc<dyn CacheStore>)
+        }
+        (Some(backend), None) => Some(backend.open().await?),
+        (None, None) => None,
+    };
+    if let Some(store) = &cache_store {
+        pokemon_service = pokemon_service.with_second_level(
+            store.clone(),
+            config.cache_l2_promotion,
+        );
+    }
+    // The sources share the species cache, so they come last.
+    let species_source = sources::open(
+        &config.species_sources,
+        &SourceSettings {
+            cache: pokemon_service.species_cache(),
+            species: pokemon_service.species_client(),
+            storage: storage.clone(),
+            storage_ttl: config.species_storage_ttl,
+            snapshot_file: config.cache_snapshot_file.clone(),
+            fixtures_dir: config.species_fixtures_dir.clone(),
+        },
+    )
+    .await?;
+    let pokemon_service =
+        Arc::new(pokemon_service.with_source(species_source));
+    let invalidations = match &config.invalidation_bus {
+        Some(bus) => Some(bus.open().await?),
+        None => None,
+    };
+    if !config.name_guard_refresh.is_zero() {
+        pokemon_service
+            .clone()
+            .guard_names(config.name_guard_refresh);
+    }
+
+    let habitat_service = Arc::new(HabitatService::new(
+        pokeapi.clone(),
+        config.cache_ttl,
+    ));
+
+    let breeding_service = Arc::new(BreedingService::new(
+        pokeapi.clone(),
+        pokemon_service.clone(),
+        config.cache_ttl,
+    ));
+
+    let encounter_service = Arc::new(EncounterService::new(
+        pokeapi.clone(),
+        pokemon_service.clone(),
+        config.cache_ttl,
+    ));
+
+    let item_service = Arc::new(
+        ItemService::new(pokeapi.clone(), config.cache_ttl)
+            .with_normalization(config.text_normalization),
+    );
+
+    let move_service = Arc::new(
+        MoveService::new(pokeapi.clone(), config.cache_ttl)
+            .with_normalization(config.text_normalization),
+    );
+
+    let nature_service = Arc::new(NatureService::new(
+        pokeapi.clone(),
+        config.cache_ttl,
+    ));
+
+    let proxy_service = Arc::new(ProxyService::new(
+        pokeapi.clone(),
+        config.proxy_allowed_resources.clone(),
+        config.cache_ttl,
+        config.proxy_cache_max_entries,
+    ));
+
+    let type_service =
+        Arc::new(TypeService::new(pokeapi.clone(), config.cache_ttl));
+
+    let team_service = Arc::new(TeamService::new(
+        pokemon_service.clone(),
+        type_service.clone(),
     ));
 
-    let translation_service = Arc::new(TranslationService::new(
+    let mut translation_filters = FilterChain::default();
+    if config.translation_strip_urls {
+        translation_filters.push(UrlStripper);
+    }
+    if let Some(denylist) = &config.translation_denylist {
+        translation_filters.push(denylist.clone());
+    }
+    let mut translation_service = TranslationService::new(
         config.translation_api_base_url.clone(),
-        config.http_timeout,
+        http::build_client(&client_settings),
+        config.cache_ttl,
+    )
+    .with_options(upstream_options.clone())
+    .with_filters(translation_filters)
+    .with_yoda_rule(config.translation_yoda.clone())
+    .with_mythical_as_legendary(
+        config.translation_mythical_as_legendary,
+    );
+    if let Some(provider) = config.mt_provider {
+        let upstream = Upstream::new(
+            "Machine translation",
+            http::build_client(&client_settings),
+        )
+        .with_options(upstream_options.clone());
+        translation_service = translation_service
+            .with_machine_translator(provider.build(
+                upstream,
+                config.mt_api_url.clone(),
+                config.mt_api_key.clone().map(|key| key.0),
+            ));
+    }
+    let translation_service = Arc::new(translation_service);
+
+    let mut speech_service = SpeechService::new(config.cache_ttl);
+    if let Some(provider) = config.tts_provider {
+        let upstream = Upstream::new(
+            "Text-to-speech",
+            http::build_client(&client_settings),
+        )
+        .with_options(upstream_options.clone());
+        speech_service =
+            speech_service.with_synthesizer(provider.build(
+                upstream,
+                config.tts_api_url.clone(),
+                config.tts_api_key.clone().map(|key| key.0),
+                config.tts_voice.clone(),
+            ));
+    }
+    let speech_service = Arc::new(speech_service);
+
+    let cry_service = Arc::new(CryService::new(
+        Upstream::new("Cries", http::build_client(&client_settings))
+            .with_options(upstream_options.clone()),
+        pokemon_service.clone(),
+        config.cache_ttl,
     ));
 
+    let quiz_service = Arc::new(
+        QuizService::new(
+            pokemon_service.clone(),
+            translation_service.clone(),
+            storage.clone(),
+            config.quiz_ttl,
+        )
+        .with_random(Randomness::new(config.random_seed)),
+    );
+
+    let job_runner = Arc::new(
+        JobRunner::new(
+            storage.clone(),
+            config.job_retry,
+            config.job_ttl,
+        )
+        .with_handler(Arc::new(TranslateGeneration::new(
+            pokeapi.clone(),
+            pokemon_service.clone(),
+            translation_service.clone(),
+            Duration::from_secs(60)
+                / config.job_translations_per_minute,
+        ))),
+    );
+    job_runner.start().unwrap_or_else(|e| {
+        panic!("Failed to resume the stored jobs: {}", e)
+    });
+
+    let audit_log = Arc::new(AuditLog::new(
+        config.audit_log_file.clone(),
+        storage.clone(),
+    ));
+
+    let tenants = Arc::new(Tenants::new(
+        config
+            .tenants_file
+            .as_deref()
+            .map(tenants::load)
+            .transpose()
+            .unwrap_or_else(|e| {
+                panic!("TENANTS_FILE is invalid: {}", e)
+            })
+            .unwrap_or_default(),
+        storage.clone(),
+    ));
+    if let Some(unknown) = config
+        .api_keys
+        .tenant_ids()
+        .find(|tenant| !tenants.contains(tenant))
+    {
+        panic!("API_KEYS is invalid: unknown tenant '{}'", unknown);
+    }
+
+    let authenticator = match &config.jwt {
+        Some(jwt) => {
+            let upstream = Upstream::new(
+                "JWKS",
+                http::build_client(&client_settings),
+            )
+            .with_options(upstream_options.clone());
+            Authenticator::Jwt(Box::new(JwtVerifier::new(
+                jwt.clone(),
+                upstream,
+            )))
+        }
+        None => Authenticator::ApiKeys(config.api_keys.clone()),
+    };
+
+    let favorites_service = Arc::new(FavoritesService::new(
+        pokemon_service.clone(),
+        storage.clone(),
+    ));
+
+    let idempotency_store = Arc::new(IdempotencyStore::new(
+        config.idempotency_ttl,
+        config.idempotency_max_entries,
+        config.max_body_bytes,
+    ));
+
+    let flags = Arc::new(FeatureFlags::new(
+        config.disabled_features.clone(),
+        config.feature_flags_file.clone(),
+    ));
+    flags.reload().await.unwrap_or_else(|e| {
+        panic!("FEATURE_FLAGS_FILE is invalid: {}", e)
+    });
+    flags.clone().watch(config.feature_flags_reload);
+
+    let usage = Arc::new(UsageStats::new());
+    if let Some(path) = &config.usage_stats_file {
+        if let Err(e) = usage.load(path).await {
+            warn!(error = %e, "Starting with empty usage stats");
+        }
+        usage.clone().persist(path.clone(), config.usage_stats_save);
+    }
+
     let state = AppState {
+        config: Arc::new(config.clone()),
         pokemon_service,
+        habitat_service,
+        breeding_service,
+        encounter_service,
+        item_service,
+        move_service,
+        nature_service,
+        team_service,
+        quiz_service,
+        job_runner,
+        favorites_service,
         translation_service,
+        speech_service,
+        cry_service,
+        type_service,
+        proxy_service,
+        audit_log: audit_log.clone(),
+        tenants: tenants.clone(),
+        upstreams,
+        authenticator: Arc::new(authenticator),
+        metrics: metrics.clone(),
+        events,
+        usage: usage.clone(),
+        flags: flags.clone(),
+        cache_store,
+        invalidations,
+        storage,
     };
 
-    // Build router with middleware stack
-    let app = Router::new()
+    // `--self-test` checks the upstreams and caches, then exits.
+    if std::env::args().any(|arg| arg == "--self-test") {
+        let report = self_test::run(
+            &state.pokemon_service,
+            &state.translation_service,
+            state.cache_store.as_deref(),
+        )
+        .await;
+        println!("{}", report);
+        std::process::exit(if report.passed() { 0 } else { 1 });
+    }
+
+    for (_, cache) in state.caches() {
+        cache.set_eviction(config.cache_eviction);
+    }
+
+    // Warm the caches with the entries saved on the last shutdown.
+    if let Some(path) = &config.cache_snapshot_file
+        && let Err(e) = snapshot::load(path, &state.caches()).await
+    {
+        warn!(error = %e, "Starting with cold caches");
+    }
+    if let Some(bus) = &state.invalidations {
+        let state = state.clone();
+        bus.subscribe(move |cache| {
+            state.metrics.record_invalidation("remote");
+            state.clear_caches(cache);
+        });
+    }
+    schedule_jobs(&state)?.start();
+    let snapshot_state = state.clone();
+    if config.cache_memory_high_water > 0 {
+        watch_memory(
+            state.clone(),
+            MemoryGuard::new(config.cache_memory_high_water),
+            config.cache_memory_check,
+        );
+    }
+
+    // Operational endpoints move to the admin listeners when any
+    // are configured, and are served next to the API otherwise. The
+    // cache admin routes are never exposed on a public listener.
+    let ops = Router::new()
         .route("/health", get(health_check))
         .route("/readiness", get(readiness_check))
+        .route("/metrics", get(render_metrics));
+    let rbac = config.admin_rbac;
+    let admin = restrict::<auth::Reader>(
+        Router::new()
+            .route("/admin/caches", get(list_caches))
+            .route("/admin/upstreams", get(list_upstreams))
+            .route("/admin/stats/top", get(top_pokemon))
+            .route("/admin/tenants/:id/usage", get(get_tenant_usage)),
+        rbac,
+    )
+    .merge(restrict::<auth::Operator>(
+        Router::new()
+            .route("/admin/caches", delete(clear_caches))
+            .route("/admin/caches/:name", delete(clear_cache))
+            .route(
+                "/admin/translate/compare",
+                get(compare_translations),
+            ),
+        rbac,
+    ))
+    .merge(restrict::<auth::Admin>(
+        Router::new().route("/admin/audit", get(list_audit)),
+        rbac,
+    ))
+    .route_layer(middleware::from_fn_with_state(
+        (flags.clone(), Feature::Admin),
+        flags::require,
+    ))
+    .route_layer(middleware::from_fn_with_state(
+        audit_log,
+        audit::middleware,
+    ))
+    .merge(ops.clone());
+    let has_admin =
+        config.listeners.iter().any(|spec| spec.role == Role::Admin);
+
+    let gates = Gates {
+        flags: flags.clone(),
+        translate_limiter: Arc::new(RateLimiter::new(
+            config.translate_rate_limit,
+            Duration::from_secs(60),
+        )),
+    };
+
+    // Every API route is served under `/v1` and `/v2`, and without
+    // a prefix at the version negotiated from the request headers.
+    let api = Router::new()
+        .nest("/v1", versioned(Some(ApiVersion::V1), &gates))
+        .nest("/v2", versioned(Some(ApiVersion::V2), &gates))
+        .merge(versioned(None, &gates))
+        .route("/proxy/pokeapi/*path", get(proxy_pokeapi))
+        .route("/openapi.json", get(openapi::document))
+        .layer(middleware::from_fn_with_state(
+            Arc::new(route_registry()),
+            deprecation::middleware,
+        ))
+        .layer(DefaultBodyLimit::max(config.max_body_bytes))
+        .layer(middleware::from_fn_with_state(
+            idempotency_store,
+            idempotency::middleware,
+        ))
+        .layer(middleware::from_fn(cache_status::middleware))
+        .layer(middleware::from_fn_with_state(
+            DebugHeaders {
+                upstream_overrides: config.debug_upstream_overrides,
+                random_seed: config.debug_random_seed,
+            },
+            context::middleware,
+        ))
+        .layer(middleware::from_fn_with_state(
+            tenants,
+            tenants::middleware,
+        ))
+        .layer(middleware::from_fn_with_state(
+            Arc::new(RateLimiter::new(
+                config.rate_limit,
+                Duration::from_secs(60),
+            )),
+            rate_limit::middleware,
+        ));
+
+    let public = if has_admin {
+        api
+    } else {
+        api.merge(ops.clone())
+    };
+    #[cfg(feature = "ui")]
+    let public = public.merge(ui::router());
+    let public = with_middleware(public, &config, &state)
+        .with_state(state.clone());
+    let public = match &peer_store {
+        Some(store) => public.merge(peers::router(store.clone())),
+        None => public,
+    };
+    let admin =
+        with_middleware(admin, &config, &state).with_state(state);
+
+    // Bind servers. With `PORT=0` the OS picks a free port, which
+    // is logged and, with `--port-file`, written out for scripts.
+    let port_file = arg_value("--port-file").map(PathBuf::from);
+    let mut listeners = Vec::new();
+    let mut bound = Vec::new();
+    let mut public_port = None;
+    for spec in &config.listeners {
+        let listener =
+            Listener::bind(&spec.addr).await.map_err(|e| {
+                let hint = if e.kind() == io::ErrorKind::AddrInUse {
+                    "; set PORT=0 to bind to a free port"
+                } else {
+                    ""
+                };
+                error::AppError::Internal(format!(
+                    "Failed to bind to {}: {}{}",
+                    spec.addr, e, hint
+                ))
+            })?;
+        let addr = match listener.local_addr() {
+            Some(addr) => {
+                if spec.role == Role::Public {
+                    public_port.get_or_insert(addr.port());
+                }
+                format!("http://{}", addr)
+            }
+            None => spec.addr.to_string(),
+        };
+        info!(role = %spec.role, %addr, "Server listening on {}", addr);
+        bound.push(addr);
+
+        let app = match spec.role {
+            Role::Public => public.clone(),
+            Role::Admin => admin.clone(),
+        };
+        listeners.push((listener, app));
+    }
+
+    if let Some(path) = &port_file {
+        let port = public_port.ok_or_else(|| {
+            error::AppError::Internal(
+                "--port-file requires a public TCP listener"
+                    .to_string(),
+            )
+        })?;
+        listener::write_port_file(path, port).map_err(|e| {
+            error::AppError::Internal(format!(
+                "Failed to write {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+    }
+
+    // Under systemd with `Type=notify`, report readiness now that
+    // the listeners are bound and the caches warm, and keep the
+    // watchdog fed when `WatchdogSec=` is set.
+    let notifier = Notifier::from_env().map(Arc::new);
+    if let Some(notifier) = &notifier {
+        notifier.ready(&format!("Listening on {}", bound.join(", ")));
+        if let Some(interval) = notify::watchdog_interval() {
+            notifier.spawn_watchdog(interval);
+        }
+    }
+    let shutdown = async move {
+        shutdown_signal().await;
+        if let Some(notifier) = notifier {
+            notifier.stopping();
+        }
+    };
+
+    // Start servers with graceful shutdown
+    listener::serve_all(listeners, shutdown)
+        .await
+        .map_err(|e| {
+            error::AppError::Internal(format!("Server error: {}", e))
+        })?;
+
+    if let Some(path) = &port_file {
+        std::fs::remove_file(path).ok();
+    }
+    if let Some(path) = &config.cache_snapshot_file
+        && let Err(e) =
+            snapshot::save(path, &snapshot_state.caches()).await
+    {
+        warn!(error = %e, "Failed to save the caches");
+    }
+    if let Some(path) = &config.usage_stats_file
+        && let Err(e) = usage.save(path).await
+    {
+        warn!(error = %e, "Failed to save usage stats");
+    }
+    info!("Server shutdown complete");
+    Ok(())
+}
+
+/// Restricts `router` to callers granted `R`'s role when `rbac` is
+/// enabled.
+fn restrict<R>(
+    router: Router<AppState>,
+    rbac: bool,
+) -> Router<AppState>
+where
+    R: auth::RequiredRole + Send + 'static,
+{
+    if rbac {
+        router.route_layer(middleware::from_extractor::<
+            auth::RequireRole<R>,
+        >())
+    } else {
+        router
+    }
+}
+
+/// Feature flags and rate limiters guarding individual routes.
+struct Gates {
+    flags: Arc<FeatureFlags>,
+    translate_limiter: Arc<RateLimiter>,
+}
+
+/// The API routes, resolved at `version` or, when `None`, at the
+/// negotiated one.
+fn versioned(
+    version: Option<ApiVersion>,
+    gates: &Gates,
+) -> Router<AppState> {
+    let flags = &gates.flags;
+    Router::new()
+        .route("/pokemon", get(list_pokemon))
+        .route("/pokemon/search", get(search_pokemon))
+        .route("/pokemon/legendary", get(list_legendary_pokemon))
+        .route("/pokemon/mythical", get(list_mythical_pokemon))
         .route("/pokemon/:name", get(get_pokemon))
+        .route("/pokemon/query", post(query_pokemon))
         .route(
             "/pokemon/translated/:name",
-            get(get_translated_pokemon),
+            get(get_translated_pokemon).route_layer(
+                middleware::from_fn_with_state(
+                    (flags.clone(), Feature::Translation),
+                    flags::require,
+                ),
+            ),
+        )
+        .route("/pokemon/:name/details", get(get_pokemon_details))
+        .route("/pokemon/:name/audio", get(get_pokemon_audio))
+        .route("/pokemon/:name/cry", get(get_pokemon_cry))
+        .route("/pokemon/:name/entries", get(get_pokemon_entries))
+        .route("/pokemon/:name/forms", get(get_pokemon_forms))
+        .route(
+            "/pokemon/:name/stats/history",
+            get(get_pokemon_stat_history),
         )
+        .route(
+            "/pokemon/:name/encounters",
+            get(get_pokemon_encounters),
+        )
+        .route(
+            "/pokemon/:name/breeding-with/:other",
+            get(get_breeding_compatibility),
+        )
+        .route(
+            "/pokemon/batch",
+            post(get_pokemon_batch).route_layer(
+                middleware::from_fn_with_state(
+                    (flags.clone(), Feature::Batch),
+                    flags::require,
+                ),
+            ),
+        )
+        .route(
+            "/translate",
+            post(translate_text)
+                .route_layer(middleware::from_fn_with_state(
+                    gates.translate_limiter.clone(),
+                    rate_limit::middleware,
+                ))
+                .route_layer(middleware::from_fn_with_state(
+                    (flags.clone(), Feature::Translation),
+                    flags::require,
+                )),
+        )
+        .route("/team/analyze", post(analyze_team))
+        .route("/quiz/start", post(start_quiz))
+        .route("/quiz/:id/guess", post(guess_quiz))
+        .route(
+            "/jobs/:id",
+            get(get_job).post(submit_job).delete(cancel_job),
+        )
+        .route("/jobs/:id/result", get(get_job_result))
+        .route("/users/:id/favorites", get(list_favorites))
+        .route(
+            "/users/:id/favorites/:name",
+            put(add_favorite).delete(remove_favorite),
+        )
+        .route("/item/:name", get(get_item))
+        .route("/berry/:name", get(get_berry))
+        .route("/move/:name", get(get_move))
+        .route("/natures", get(list_natures))
+        .route("/natures/:name", get(get_nature))
+        .route("/habitats", get(list_habitats))
+        .route("/habitats/:name/pokemon", get(get_habitat_pokemon))
+        .layer(middleware::from_fn_with_state(
+            version,
+            version::negotiate,
+        ))
+}
+
+/// Lifecycle of the routes being retired.
+fn route_registry() -> RouteRegistry {
+    // v2 returns the details model from `/pokemon/{name}` itself.
+    ["", "/v1", "/v2"].into_iter().fold(
+        RouteRegistry::new(),
+        |registry, prefix| {
+            registry.deprecate(
+                Deprecation::new(
+                    &format!("{}/pokemon/:name/details", prefix),
+                    deprecation::date(2026, 10, 17),
+                )
+                .sunset(deprecation::date(2027, 4, 1))
+                .successor("/v2/pokemon/:name"),
+            )
+        },
+    )
+}
+
+/// Wraps `router` with the authentication, metrics, logging,
+/// timeout, compression and CORS layers shared by every listener.
+fn with_middleware(
+    router: Router<AppState>,
+    config: &Config,
+    state: &AppState,
+) -> Router<AppState> {
+    router
+        .layer(middleware::from_fn_with_state(
+            state.authenticator.clone(),
+            auth::authenticate,
+        ))
+        .layer(middleware::from_fn(jsonapi::middleware))
+        .layer(middleware::from_fn_with_state(
+            config.response_case,
+            field_case::middleware,
+        ))
+        .layer(middleware::from_fn(i18n::middleware))
+        .layer(middleware::from_fn_with_state(
+            Arc::new(config.public_url.clone()),
+            forwarded::middleware,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.metrics.clone(),
+            metrics::track,
+        ))
         .layer(
             ServiceBuilder::new()
                 // Logging layer
@@ -92,130 +1095,2244 @@ async fn main() -> Result<()> {
                 .layer(TimeoutLayer::new(Duration::from_secs(
                     config.request_timeout,
                 )))
-                // Compression layer
-                .layer(CompressionLayer::new())
+                // Compression layer. Audio is compressed already,
+                // and byte ranges index the uncompressed body.
+                .layer(
+                    CompressionLayer::new().compress_when(
+                        DefaultPredicate::new().and(
+                            NotForContentType::const_new("audio/"),
+                        ),
+                    ),
+                )
                 // CORS layer
                 .layer(
                     CorsLayer::new()
                         .allow_origin(
                             "*".parse::<HeaderValue>().unwrap(),
                         )
-                        .allow_methods([Method::GET])
-                        .allow_headers([header::CONTENT_TYPE]),
+                        .allow_methods([
+                            Method::GET,
+                            Method::POST,
+                            Method::PUT,
+                            Method::DELETE,
+                        ])
+                        .allow_headers([
+                            header::CONTENT_TYPE,
+                            header::AUTHORIZATION,
+                            header::HeaderName::from_static(
+                                auth::API_KEY_HEADER,
+                            ),
+                            header::HeaderName::from_static(
+                                idempotency::IDEMPOTENCY_KEY,
+                            ),
+                            header::HeaderName::from_static(
+                                version::ACCEPT_VERSION_HEADER,
+                            ),
+                            header::HeaderName::from_static(
+                                context::DEADLINE_HEADER,
+                            ),
+                            header::HeaderName::from_static(
+                                context::GRPC_TIMEOUT_HEADER,
+                            ),
+                            header::HeaderName::from_static(
+                                TRANSLATOR_HEADER,
+                            ),
+                            header::HeaderName::from_static(
+                                context::RANDOM_SEED_HEADER,
+                            ),
+                        ])
+                        .expose_headers([
+                            header::AGE,
+                            header::RETRY_AFTER,
+                            header::HeaderName::from_static(
+                                cache_status::CACHE_STATUS_HEADER,
+                            ),
+                            header::HeaderName::from_static(
+                                rate_limit::RATE_LIMIT_LIMIT,
+                            ),
+                            header::HeaderName::from_static(
+                                rate_limit::RATE_LIMIT_REMAINING,
+                            ),
+                            header::HeaderName::from_static(
+                                rate_limit::RATE_LIMIT_RESET,
+                            ),
+                            header::HeaderName::from_static(
+                                experiments::VARIANT_HEADER,
+                            ),
+                            header::HeaderName::from_static(
+                                deprecation::DEPRECATION_HEADER,
+                            ),
+                            header::HeaderName::from_static(
+                                deprecation::SUNSET_HEADER,
+                            ),
+                            header::LINK,
+                            header::WARNING,
+                        ]),
                 ),
         )
-        .with_state(state);
+}
 
-    // Bind server
-    let addr = format!("{}:{}", config.host, config.port);
-    let listener =
-        tokio::net::TcpListener::bind(&addr).await.map_err(|e| {
-            error::AppError::Internal(format!(
-                "Failed to bind to {}: {}",
-                addr, e
-            ))
-        })?;
+/// Checks the weight of the caches every `interval`, evicting past
+/// the guard's high-water mark.
+fn watch_memory(
+    state: AppState,
+    guard: MemoryGuard,
+    interval: Duration,
+) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let evicted =
+                guard.check(&state.caches(), &state.metrics);
+            if evicted > 0 {
+                warn!(
+                    evicted,
+                    "Evicted cache entries past the memory high-water mark"
+                );
+            }
+        }
+    });
+}
 
-    info!("Server listening on http://{}", addr);
+/// Most requested Pokemon refetched by the `cache_refresh` job.
+const REFRESH_TOP: usize = 20;
 
-    // Start server with graceful shutdown
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await
-        .map_err(|e| {
-            error::AppError::Internal(format!("Server error: {}", e))
-        })?;
+/// Most requested Pokemon in the `stats_rollup` event.
+const ROLLUP_TOP: usize = 10;
 
-    info!("Server shutdown complete");
+/// The periodic jobs given a schedule by their `*_CRON` variable.
+fn schedule_jobs(state: &AppState) -> Result<Scheduler> {
+    let jobs = &state.config.jobs;
+    let mut scheduler = Scheduler::new(state.metrics.clone());
+    if let Some(schedule) = &jobs.cache_refresh {
+        let state = state.clone();
+        scheduler.register(
+            "cache_refresh",
+            schedule.clone(),
+            move || refresh_top_pokemon(state.clone()),
+        );
+    }
+    if let Some(schedule) = &jobs.snapshot_sync {
+        let state = state.clone();
+        scheduler.register(
+            "snapshot_sync",
+            schedule.clone(),
+            move || sync_snapshots(state.clone()),
+        );
+    }
+    if let Some(schedule) = &jobs.stats_rollup {
+        let state = state.clone();
+        scheduler.register(
+            "stats_rollup",
+            schedule.clone(),
+            move || roll_up_usage(state.clone()),
+        );
+    }
+    if let (Some(schedule), Some(email)) =
+        (&jobs.health_report, &state.config.report_email)
+    {
+        let mailer = Arc::new(Mailer::new(email)?);
+        let state = state.clone();
+        scheduler.register(
+            "health_report",
+            schedule.clone(),
+            move || {
+                email_health_report(state.clone(), mailer.clone())
+            },
+        );
+    }
+    Ok(scheduler)
+}
+
+/// Refetches the most requested Pokemon, so they are not served
+/// from PokeAPI when their cache entries expire.
+async fn refresh_top_pokemon(state: AppState) -> Result<()> {
+    let top = state.usage.top(REFRESH_TOP);
+    let mut failed = 0;
+    for pokemon in &top {
+        if let Err(e) =
+            state.pokemon_service.refresh(&pokemon.name).await
+        {
+            warn!(pokemon_name = %pokemon.name, error = %e, "Failed to refresh Pokemon");
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        return Err(error::AppError::Internal(format!(
+            "Failed to refresh {} of {} Pokemon",
+            failed,
+            top.len()
+        )));
+    }
     Ok(())
 }
 
-async fn health_check() -> impl IntoResponse {
-    Json(serde_json::json!({
-        "status": "healthy",
-        "service": "pokedex-api",
-        "version": env!("CARGO_PKG_VERSION")
-    }))
+/// Saves the cache snapshot and usage stats, so a crash does not
+/// lose them.
+async fn sync_snapshots(state: AppState) -> Result<()> {
+    if let Some(path) = &state.config.cache_snapshot_file {
+        snapshot::save(path, &state.caches()).await?;
+    }
+    if let Some(path) = &state.config.usage_stats_file {
+        state.usage.save(path).await?;
+    }
+    Ok(())
 }
 
-async fn readiness_check(
+async fn roll_up_usage(state: AppState) -> Result<()> {
+    let top = state.usage.top(ROLLUP_TOP);
+    let names: Vec<_> =
+        top.iter().map(|pokemon| pokemon.name.as_str()).collect();
+    info!(top = ?names, "Most requested Pokemon");
+    state.events.publish(Event::UsageRollup { top });
+    Ok(())
+}
+
+async fn email_health_report(
+    state: AppState,
+    mailer: Arc<Mailer>,
+) -> Result<()> {
+    let report = health_report(&state).await;
+    let subject = format!("Pokedex health: {:?}", report.status);
+    let body =
+        serde_json::to_string_pretty(&report).unwrap_or_default();
+    mailer.send(&subject, body).await
+}
+
+async fn render_metrics(
     State(state): State<AppState>,
-) -> Result<impl IntoResponse> {
-    // Check if external services are reachable
-    let pokemon_ready =
-        state.pokemon_service.health_check().await.is_ok();
-    let translation_ready =
-        state.translation_service.health_check().await.is_ok();
+) -> impl IntoResponse {
+    let caches: Vec<(&str, CacheStats)> = state
+        .caches()
+        .into_iter()
+        .map(|(name, cache)| (name, cache.stats()))
+        .collect();
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(&caches),
+    )
+}
 
-    if pokemon_ready && translation_ready {
-        Ok(Json(serde_json::json!({
-            "status": "ready",
-            "services": {
-                "pokeapi": "up",
-                "translation": "up"
-            }
-        })))
-    } else {
-        Err(error::AppError::Internal(
-            "Service not ready".to_string(),
-        ))
-    }
+#[derive(Serialize)]
+struct CacheSummary {
+    name: &'static str,
+    #[serde(flatten)]
+    stats: CacheStats,
 }
 
-async fn get_pokemon(
+async fn list_caches(
     State(state): State<AppState>,
-    Path(name): Path<String>,
-) -> Result<Json<Pokemon>> {
-    info!(pokemon_name = %name, "Fetching pokemon");
-    let pokemon = state.pokemon_service.get_pokemon(&name).await?;
-    Ok(Json(pokemon))
+) -> Json<Vec<CacheSummary>> {
+    Json(
+        state
+            .caches()
+            .into_iter()
+            .map(|(name, cache)| CacheSummary {
+                name,
+                stats: cache.stats(),
+            })
+            .collect(),
+    )
 }
 
-async fn get_translated_pokemon(
+#[derive(Serialize)]
+struct PoolSettings {
+    max_idle_per_host: usize,
+    idle_timeout_secs: u64,
+}
+
+#[derive(Serialize)]
+struct UpstreamsResponse {
+    /// Connection pool limits, the same for every upstream.
+    pool: PoolSettings,
+    upstreams: Vec<UpstreamSnapshot>,
+}
+
+async fn list_upstreams(
+    State(state): State<AppState>,
+) -> Json<UpstreamsResponse> {
+    Json(UpstreamsResponse {
+        pool: PoolSettings {
+            max_idle_per_host: http::POOL_MAX_IDLE_PER_HOST,
+            idle_timeout_secs: http::POOL_IDLE_TIMEOUT.as_secs(),
+        },
+        upstreams: state.upstreams.snapshot(),
+    })
+}
+
+async fn clear_caches(State(state): State<AppState>) -> StatusCode {
+    state.clear_caches(None);
+    invalidate(&state, None).await;
+    StatusCode::NO_CONTENT
+}
+
+async fn clear_cache(
     State(state): State<AppState>,
     Path(name): Path<String>,
-) -> Result<Json<Pokemon>> {
-    info!(pokemon_name = %name, "Fetching translated pokemon");
-    let mut pokemon =
-        state.pokemon_service.get_pokemon(&name).await?;
+) -> Result<StatusCode> {
+    if !state.clear_caches(Some(&name)) {
+        return Err(error::AppError::NotFound(format!(
+            "Cache '{}' not found",
+            name
+        )));
+    }
+    invalidate(&state, Some(&name)).await;
+    Ok(StatusCode::NO_CONTENT)
+}
 
-    if let Some(description) = &pokemon.description {
-        if let Ok(translated) = state
-            .translation_service
-            .translate(
-                description,
-                &pokemon.habitat,
-                pokemon.is_legendary,
-            )
-            .await
-        {
-            pokemon.description = Some(translated);
-        }
+/// Counts a cache cleared by an operator and has the other replicas
+/// clear it too.
+async fn invalidate(state: &AppState, cache: Option<&str>) {
+    state.metrics.record_invalidation("local");
+    if let Some(bus) = &state.invalidations {
+        bus.publish(cache).await;
     }
+}
 
-    Ok(Json(pokemon))
+#[derive(Serialize)]
+struct AuditResponse {
+    entries: Vec<AuditEntry>,
 }
 
-async fn shutdown_signal() {
-    let ctrl_c = async {
-        signal::ctrl_c()
-            .await
-            .expect("failed to install Ctrl+C handler");
-    };
+async fn list_audit(
+    State(state): State<AppState>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<AuditResponse>> {
+    let entries = state.audit_log.query(&query).await?;
+    Ok(Json(AuditResponse { entries }))
+}
 
-    #[cfg(unix)]
-    let terminate = async {
-        signal::unix::signal(signal::unix::SignalKind::terminate())
-            .expect("failed to install signal handler")
+#[derive(Deserialize)]
+struct UsageParams {
+    days: Option<u64>,
+}
+
+async fn get_tenant_usage(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(params): Query<UsageParams>,
+) -> Result<Json<TenantUsage>> {
+    Ok(Json(state.tenants.usage(&id, params.days.unwrap_or(7))?))
+}
+
+#[derive(Deserialize)]
+struct TopParams {
+    limit: Option<usize>,
+}
+
+/// The most requested Pokemon, up to `limit` (10 by default).
+async fn top_pokemon(
+    State(state): State<AppState>,
+    Query(params): Query<TopParams>,
+) -> Json<Vec<PokemonUsage>> {
+    Json(state.usage.top(params.limit.unwrap_or(10)))
+}
+
+#[derive(Deserialize)]
+struct CompareParams {
+    name: String,
+    /// The funtranslations style, the one the rules pick otherwise.
+    style: Option<String>,
+}
+
+#[derive(Serialize)]
+struct CompareResponse {
+    name: String,
+    description: String,
+    style: &'static str,
+    #[serde(flatten)]
+    comparison: Comparison,
+}
+
+/// Translates a Pokemon's description with funtranslations and the
+/// offline translator side by side.
+async fn compare_translations(
+    State(state): State<AppState>,
+    Query(params): Query<CompareParams>,
+) -> Result<Json<CompareResponse>> {
+    let style = params
+        .style
+        .as_deref()
+        .map(|value| {
+            Style::parse(value).ok_or_else(|| {
+                error::AppError::BadRequest(format!(
+                    "Unknown style '{}', expected yoda or shakespeare",
+                    value
+                ))
+            })
+        })
+        .transpose()?;
+    let service = &state.translation_service;
+    info!(pokemon_name = %params.name, "Comparing translations");
+
+    let pokemon = state
+        .pokemon_service
+        .get_pokemon(&params.name, &Lang::default())
+        .await?;
+    let Some(description) = pokemon.description.clone() else {
+        return Err(error::AppError::NotFound(format!(
+            "{} has no description to translate",
+            pokemon.name
+        )));
+    };
+    let style = match style {
+        Some(style) => style,
+        None => {
+            service.style_of(&pokemon, &state.pokemon_service).await
+        }
+    };
+    let comparison = service.compare(&description, style).await;
+    Ok(Json(CompareResponse {
+        name: pokemon.name,
+        description,
+        style: style.as_str(),
+        comparison,
+    }))
+}
+
+/// Checks every dependency; the translation API is optional, so the
+/// service is only degraded without it.
+async fn health_check(State(state): State<AppState>) -> HealthReport {
+    health_report(&state).await
+}
+
+async fn health_report(state: &AppState) -> HealthReport {
+    let upstreams = state.upstreams.snapshot();
+    let last_error = |name: &str| {
+        upstreams
+            .iter()
+            .find(|upstream| upstream.name == name)
+            .and_then(|upstream| upstream.last_error.clone())
+    };
+    let (pokeapi, translation, cache, storage) = tokio::join!(
+        Dependency::check(
+            "pokeapi",
+            state.pokemon_service.health_check(),
+            last_error(pokeapi::UPSTREAM_NAME),
+        ),
+        Dependency::check(
+            "translation",
+            state.translation_service.health_check(),
+            last_error(translation::UPSTREAM_NAME),
+        ),
+        Dependency::check(
+            "cache",
+            async {
+                match &state.cache_store {
+                    Some(store) => {
+                        store.get("health").await.map(drop)
+                    }
+                    None => Ok(()),
+                }
+            },
+            None,
+        ),
+        Dependency::check(
+            "storage",
+            async { state.storage.get("health", "probe").map(drop) },
+            None,
+        ),
+    );
+    HealthReport::new(vec![
+        pokeapi,
+        translation.optional(),
+        cache,
+        storage,
+    ])
+}
+
+async fn readiness_check(
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse> {
+    // Check if external services are reachable
+    let pokemon_ready =
+        state.pokemon_service.health_check().await.is_ok();
+    let translation_ready =
+        state.translation_service.health_check().await.is_ok();
+
+    if pokemon_ready && translation_ready {
+        Ok(Json(serde_json::json!({
+            "status": "ready",
+            "services": {
+                "pokeapi": "up",
+                "translation": "up"
+            }
+        })))
+    } else {
+        Err(error::AppError::Internal(
+            "Service not ready".to_string(),
+        ))
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct PokemonFilters {
+    habitat: Option<String>,
+}
+
+/// Resolves the species matching `filters`, along with the filter
+/// map their cursors are bound to.
+async fn filtered_species(
+    state: &AppState,
+    filters: &PokemonFilters,
+) -> Result<(Arc<Vec<SpeciesSummary>>, BTreeMap<String, String>)> {
+    match &filters.habitat {
+        Some(habitat) => {
+            let habitat = habitat.to_lowercase();
+            let species = state
+                .habitat_service
+                .habitat_species(&habitat)
+                .await?;
+            Ok((
+                species,
+                BTreeMap::from([("habitat".into(), habitat)]),
+            ))
+        }
+        None => Ok((
+            state.pokemon_service.list_species().await?,
+            BTreeMap::new(),
+        )),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/pokemon",
+    tag = "pokemon",
+    params(PokemonFilters, PageParams),
+    responses(
+        (
+            status = 200,
+            description = "A page of species",
+            body = Page<SpeciesSummary>,
+        ),
+    ),
+)]
+async fn list_pokemon(
+    State(state): State<AppState>,
+    Query(filters): Query<PokemonFilters>,
+    Query(params): Query<PageParams>,
+) -> Result<Json<Page<SpeciesSummary>>> {
+    let (species, filters) =
+        filtered_species(&state, &filters).await?;
+    let page =
+        listing::paginate(&species, |s| s.id, &params, filters)?;
+    Ok(Json(page))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SearchParams {
+    q: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/pokemon/search",
+    tag = "pokemon",
+    params(SearchParams, PokemonFilters, PageParams),
+    responses(
+        (
+            status = 200,
+            description = "A page of the matching species",
+            body = Page<SpeciesSummary>,
+        ),
+    ),
+)]
+async fn search_pokemon(
+    State(state): State<AppState>,
+    Query(search): Query<SearchParams>,
+    Query(filters): Query<PokemonFilters>,
+    Query(params): Query<PageParams>,
+) -> Result<Json<Page<SpeciesSummary>>> {
+    let query = search.q.trim().to_lowercase();
+    if query.is_empty() {
+        return Err(error::AppError::BadRequest(
+            "Search query must not be empty".to_string(),
+        ));
+    }
+    info!(query = %query, "Searching pokemon");
+
+    let (species, mut filters) =
+        filtered_species(&state, &filters).await?;
+    let matches: Vec<SpeciesSummary> = species
+        .iter()
+        .filter(|species| species.name.contains(&query))
+        .cloned()
+        .collect();
+
+    filters.insert("q".to_string(), query);
+    let page =
+        listing::paginate(&matches, |s| s.id, &params, filters)?;
+    Ok(Json(page))
+}
+
+#[utoipa::path(
+    get,
+    path = "/pokemon/legendary",
+    tag = "pokemon",
+    params(PageParams),
+    responses(
+        (
+            status = 200,
+            description = "A page of legendary species",
+            body = Page<SpeciesSummary>,
+        ),
+    ),
+)]
+async fn list_legendary_pokemon(
+    State(state): State<AppState>,
+    Query(params): Query<PageParams>,
+) -> Result<Json<Page<SpeciesSummary>>> {
+    flagged_species(&state, &params, |flags| flags.is_legendary).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/pokemon/mythical",
+    tag = "pokemon",
+    params(PageParams),
+    responses(
+        (
+            status = 200,
+            description = "A page of mythical species",
+            body = Page<SpeciesSummary>,
+        ),
+    ),
+)]
+async fn list_mythical_pokemon(
+    State(state): State<AppState>,
+    Query(params): Query<PageParams>,
+) -> Result<Json<Page<SpeciesSummary>>> {
+    flagged_species(&state, &params, |flags| flags.is_mythical).await
+}
+
+async fn flagged_species(
+    state: &AppState,
+    params: &PageParams,
+    predicate: impl Fn(&SpeciesFlags) -> bool,
+) -> Result<Json<Page<SpeciesSummary>>> {
+    let species: Vec<SpeciesSummary> = state
+        .pokemon_service
+        .flag_index()
+        .await?
+        .iter()
+        .filter(|flags| predicate(flags))
+        .map(|flags| flags.species.clone())
+        .collect();
+    let page = listing::paginate(
+        &species,
+        |s| s.id,
+        params,
+        BTreeMap::new(),
+    )?;
+    Ok(Json(page))
+}
+
+#[utoipa::path(
+    get,
+    path = "/habitats",
+    tag = "habitats",
+    params(PageParams),
+    responses(
+        (
+            status = 200,
+            description = "A page of habitats",
+            body = Page<HabitatSummary>,
+        ),
+    ),
+)]
+async fn list_habitats(
+    State(state): State<AppState>,
+    Query(params): Query<PageParams>,
+) -> Result<Json<Page<HabitatSummary>>> {
+    let habitats = state.habitat_service.list_habitats().await?;
+    let page = listing::paginate(
+        &habitats,
+        |h| h.id,
+        &params,
+        BTreeMap::new(),
+    )?;
+    Ok(Json(page))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct NatureFilters {
+    /// Stat the natures raise, or `none` for the neutral ones.
+    increased: Option<String>,
+    decreased: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/natures",
+    tag = "natures",
+    params(NatureFilters, PageParams),
+    responses(
+        (
+            status = 200,
+            description = "A page of natures",
+            body = Page<Nature>,
+        ),
+    ),
+)]
+async fn list_natures(
+    State(state): State<AppState>,
+    Query(filters): Query<NatureFilters>,
+    Query(params): Query<PageParams>,
+) -> Result<Json<Page<Nature>>> {
+    let natures = state.nature_service.list_natures().await?;
+    let increased = filters.increased.map(|s| s.to_lowercase());
+    let decreased = filters.decreased.map(|s| s.to_lowercase());
+    let changes = |wanted: &Option<String>, stat: &Option<String>| {
+        wanted.as_deref().is_none_or(|wanted| {
+            stat.as_deref().unwrap_or("none") == wanted
+        })
+    };
+    let natures: Vec<Nature> = natures
+        .iter()
+        .filter(|nature| {
+            changes(&increased, &nature.increased_stat)
+                && changes(&decreased, &nature.decreased_stat)
+        })
+        .cloned()
+        .collect();
+
+    let filters =
+        [("increased", increased), ("decreased", decreased)]
+            .into_iter()
+            .filter_map(|(key, value)| {
+                Some((key.to_string(), value?))
+            })
+            .collect();
+    let page =
+        listing::paginate(&natures, |n| n.id, &params, filters)?;
+    Ok(Json(page))
+}
+
+#[utoipa::path(
+    get,
+    path = "/natures/{name}",
+    tag = "natures",
+    params(("name" = String, Path, description = "Nature name")),
+    responses(
+        (status = 200, description = "The nature", body = Nature),
+    ),
+)]
+async fn get_nature(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<Arc<Nature>>> {
+    info!(nature = %name, "Fetching nature");
+    Ok(Json(state.nature_service.nature(&name).await?))
+}
+
+#[utoipa::path(
+    get,
+    path = "/habitats/{name}/pokemon",
+    tag = "habitats",
+    params(
+        ("name" = String, Path, description = "Habitat name"),
+        PageParams,
+    ),
+    responses(
+        (
+            status = 200,
+            description = "A page of the species living there",
+            body = Page<SpeciesSummary>,
+        ),
+    ),
+)]
+async fn get_habitat_pokemon(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(params): Query<PageParams>,
+) -> Result<Json<Page<SpeciesSummary>>> {
+    info!(habitat = %name, "Fetching habitat pokemon");
+    let species =
+        state.habitat_service.habitat_species(&name).await?;
+    let page = listing::paginate(
+        &species,
+        |s| s.id,
+        &params,
+        BTreeMap::new(),
+    )?;
+    Ok(Json(page))
+}
+
+/// v1 returns the `Pokemon` model and v2 the extended details.
+#[utoipa::path(
+    get,
+    path = "/pokemon/{name}",
+    tag = "pokemon",
+    params(
+        ("name" = String, Path, description = "Pokemon name"),
+        IncludeParams,
+        SummaryParams,
+        LangParams,
+    ),
+    responses(
+        (
+            status = 200,
+            description = "The Pokemon",
+            body = VersionedPokemon,
+        ),
+    ),
+)]
+async fn get_pokemon(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    version: ApiVersion,
+    include: Include,
+    summary: Summary,
+    lang: Lang,
+    jsonapi: JsonApi,
+) -> Result<Response> {
+    info!(pokemon_name = %name, %version, "Fetching pokemon");
+    let description = Description {
+        summary,
+        translation: None,
+    };
+    pokemon_response(
+        &state,
+        &name,
+        version,
+        include,
+        &lang,
+        description,
+        jsonapi,
+    )
+    .await
+}
+
+#[utoipa::path(
+    get,
+    path = "/pokemon/{name}/details",
+    tag = "pokemon",
+    params(
+        ("name" = String, Path, description = "Pokemon name"),
+        LangParams,
+    ),
+    responses(
+        (
+            status = 200,
+            description = "The Pokemon with its battle data",
+            body = PokemonDetails,
+        ),
+    ),
+)]
+async fn get_pokemon_details(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    lang: Lang,
+    jsonapi: JsonApi,
+) -> Result<Response> {
+    info!(pokemon_name = %name, "Fetching pokemon details");
+    let details =
+        state.pokemon_service.get_details(&name, &lang).await?;
+    let stale = details.pokemon.data_source.is_some();
+    Ok(stale_warning(stale, jsonapi.respond(&details)))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct TranslatedParams {
+    target: Option<String>,
+    /// A fixed style, as `X-Pokedex-Translator`.
+    translator: Option<String>,
+}
+
+/// Forces the translation style of `/pokemon/translated/{name}` with
+/// `TRANSLATOR_OVERRIDES`.
+const TRANSLATOR_HEADER: &str = "x-pokedex-translator";
+
+/// The style forced by `?translator=` or `X-Pokedex-Translator`,
+/// bypassing the habitat and legendary rule. Both are ignored unless
+/// `TRANSLATOR_OVERRIDES` is enabled.
+fn translator_override(
+    state: &AppState,
+    headers: &HeaderMap,
+    param: Option<&str>,
+) -> Result<Option<Style>> {
+    if !state.config.translator_overrides {
+        return Ok(None);
+    }
+    let header = headers
+        .get(TRANSLATOR_HEADER)
+        .and_then(|value| value.to_str().ok());
+    let Some(value) = param.or(header) else {
+        return Ok(None);
+    };
+    Style::parse(value).map(Some).ok_or_else(|| {
+        error::AppError::BadRequest(format!(
+            "Unknown translator '{}', expected yoda or shakespeare",
+            value
+        ))
+    })
+}
+
+/// Translates the description in a fun style, or into the `target`
+/// language when one is given.
+#[allow(clippy::too_many_arguments)]
+#[utoipa::path(
+    get,
+    path = "/pokemon/translated/{name}",
+    tag = "translation",
+    params(
+        ("name" = String, Path, description = "Pokemon name"),
+        TranslatedParams,
+        IncludeParams,
+        SummaryParams,
+        LangParams,
+    ),
+    responses(
+        (
+            status = 200,
+            description = "The Pokemon with its description translated",
+            body = VersionedPokemon,
+        ),
+    ),
+)]
+async fn get_translated_pokemon(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(params): Query<TranslatedParams>,
+    headers: HeaderMap,
+    principal: Option<Principal>,
+    client: ClientIp,
+    version: ApiVersion,
+    include: Include,
+    mut summary: Summary,
+    lang: Lang,
+    jsonapi: JsonApi,
+) -> Result<Response> {
+    info!(pokemon_name = %name, %version, target = ?params.target, "Fetching translated pokemon");
+    let style = translator_override(
+        &state,
+        &headers,
+        params.translator.as_deref(),
+    )?;
+    let target = params
+        .target
+        .map(|target| {
+            state.translation_service.machine_target(&target)
+        })
+        .transpose()?;
+    let mut translation = match (&target, style) {
+        (Some(_), Some(_)) => {
+            return Err(error::AppError::BadRequest(
+                "translator and target are mutually exclusive"
+                    .to_string(),
+            ));
+        }
+        (Some(target), None) => Translation::Language(target),
+        (None, Some(style)) => Translation::Style(style),
+        (None, None) => Translation::Fun,
+    };
+
+    // Requests left to the style rules take part in the experiments.
+    let experiments = &state.config.experiments;
+    let mut variant = None;
+    if matches!(translation, Translation::Fun)
+        && !experiments.is_empty()
+    {
+        let client = auth::caller_id(principal.as_ref(), client);
+        let experiment = experiments.assign(&client);
+        match experiment.map(|experiment| experiment.variant) {
+            Some(Variant::Style(style)) => {
+                translation = Translation::Experiment(style);
+            }
+            Some(Variant::Short) => {
+                summary.sentences.get_or_insert(1);
+            }
+            None => {}
+        }
+        variant = Some(
+            experiment
+                .map_or(experiments::CONTROL, |e| e.name.as_str()),
+        );
+    }
+
+    let description = Description {
+        summary,
+        translation: Some(translation),
+    };
+    let response = pokemon_response(
+        &state,
+        &name,
+        version,
+        include,
+        &lang,
+        description,
+        jsonapi,
+    )
+    .await;
+    let Some(variant) = variant else {
+        return response;
+    };
+    let mut response = response.unwrap_or_else(|e| e.into_response());
+    state
+        .metrics
+        .record_experiment(variant, response.status().as_u16());
+    if let Ok(value) = HeaderValue::from_str(variant) {
+        response
+            .headers_mut()
+            .insert(experiments::VARIANT_HEADER, value);
+    }
+    Ok(response)
+}
+
+/// Body of `POST /pokemon/query`, the options of the Pokemon
+/// endpoints in one typed request.
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+struct PokemonQuery {
+    name: String,
+    #[serde(default)]
+    translated: bool,
+    lang: Option<String>,
+    /// A fixed funtranslations style instead of the one picked from
+    /// the habitat.
+    style: Option<Style>,
+    /// A language to translate into, as `?target=`.
+    target: Option<String>,
+    /// Comma-separated sections, as in `?include=`.
+    #[serde(default)]
+    include: String,
+    sentences: Option<usize>,
+    max_len: Option<usize>,
+}
+
+/// Serves `/pokemon/{name}` or, with `translated`, the translated
+/// endpoint, from the options in the body.
+#[utoipa::path(
+    post,
+    path = "/pokemon/query",
+    tag = "pokemon",
+    params(LangParams),
+    request_body = PokemonQuery,
+    responses(
+        (
+            status = 200,
+            description = "The Pokemon",
+            body = VersionedPokemon,
+        ),
+    ),
+)]
+async fn query_pokemon(
+    State(state): State<AppState>,
+    version: ApiVersion,
+    lang: Lang,
+    jsonapi: JsonApi,
+    JsonBody(query): JsonBody<PokemonQuery>,
+) -> Result<Response> {
+    let name = query.name.trim();
+    info!(pokemon_name = %name, %version, translated = query.translated, "Querying pokemon");
+
+    let mut errors = Vec::new();
+    if name.is_empty() {
+        errors.push(FieldError::new(
+            "name",
+            "empty",
+            "name must not be empty",
+        ));
+    }
+    for (field, value) in
+        [("sentences", query.sentences), ("max_len", query.max_len)]
+    {
+        if value == Some(0) {
+            errors.push(FieldError::new(
+                field,
+                "not_positive",
+                format!("{} must be positive", field),
+            ));
+        }
+    }
+    let include = match Include::parse(&query.include) {
+        Ok(include) => include,
+        Err(e) => {
+            errors.push(invalid_field("include", "unknown", e));
+            Include::default()
+        }
+    };
+    let target = match &query.target {
+        Some(_) if query.style.is_some() => {
+            errors.push(FieldError::new(
+                "target",
+                "conflict",
+                "style and target are mutually exclusive",
+            ));
+            None
+        }
+        Some(target) => state
+            .translation_service
+            .machine_target(target)
+            .map_err(|e| {
+                errors.push(invalid_field("target", "unsupported", e))
+            })
+            .ok(),
+        None => None,
+    };
+    if !query.translated {
+        for (field, given) in [
+            ("style", query.style.is_some()),
+            ("target", query.target.is_some()),
+        ] {
+            if given {
+                errors.push(FieldError::new(
+                    field,
+                    "requires_translated",
+                    format!(
+                        "{} requires translated to be true",
+                        field
+                    ),
+                ));
+            }
+        }
+    }
+    error::AppError::validate(errors)?;
+    if query.translated {
+        state.flags.check(Feature::Translation)?;
+    }
+
+    let translation = match (&target, query.style) {
+        _ if !query.translated => None,
+        (Some(target), _) => Some(Translation::Language(target)),
+        (None, Some(style)) => Some(Translation::Style(style)),
+        (None, None) => Some(Translation::Fun),
+    };
+    let description = Description {
+        summary: Summary {
+            sentences: query.sentences,
+            max_len: query.max_len,
+        },
+        translation,
+    };
+    let lang = query.lang.map(Lang).unwrap_or(lang);
+    pokemon_response(
+        &state,
+        name,
+        version,
+        include,
+        &lang,
+        description,
+        jsonapi,
+    )
+    .await
+}
+
+/// A field error carrying the message of a `BadRequest`.
+fn invalid_field(
+    field: &str,
+    code: &'static str,
+    error: error::AppError,
+) -> FieldError {
+    let message = match error {
+        error::AppError::BadRequest(message) => message,
+        other => other.to_string(),
+    };
+    FieldError::new(field, code, message)
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct AudioParams {
+    #[serde(default)]
+    translated: bool,
+    target: Option<String>,
+}
+
+/// Reads the description aloud, translated in a fun style with
+/// `translated=true` or into the `target` language.
+#[utoipa::path(
+    get,
+    path = "/pokemon/{name}/audio",
+    tag = "pokemon",
+    params(
+        ("name" = String, Path, description = "Pokemon name"),
+        AudioParams,
+        LangParams,
+    ),
+    responses(
+        (
+            status = 200,
+            description = "The spoken description",
+            content_type = "audio/mpeg",
+        ),
+        (
+            status = 206,
+            description = "The requested range of the audio",
+            content_type = "audio/mpeg",
+        ),
+    ),
+)]
+async fn get_pokemon_audio(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(params): Query<AudioParams>,
+    lang: Lang,
+    headers: HeaderMap,
+) -> Result<Response> {
+    info!(pokemon_name = %name, target = ?params.target, "Synthesizing pokemon audio");
+    if params.translated || params.target.is_some() {
+        state.flags.check(Feature::Translation)?;
+    }
+    let target = params
+        .target
+        .map(|target| {
+            state.translation_service.machine_target(&target)
+        })
+        .transpose()?;
+
+    let pokemon =
+        state.pokemon_service.get_pokemon(&name, &lang).await?;
+    let (pokemon, language) = match &target {
+        Some(target) => (
+            translate_pokemon(
+                &state,
+                pokemon,
+                Translation::Language(target),
+            )
+            .await,
+            target.as_str(),
+        ),
+        // The fun styles are English.
+        None if params.translated => (
+            translate_pokemon(&state, pokemon, Translation::Fun)
+                .await,
+            lang::DEFAULT_LANGUAGE,
+        ),
+        None => (pokemon, lang.as_str()),
+    };
+    let description = pokemon.description.ok_or_else(|| {
+        error::AppError::NotFound(format!(
+            "Pokemon '{}' has no description",
+            name
+        ))
+    })?;
+
+    let audio = state
+        .speech_service
+        .synthesize(&description, language)
+        .await?;
+    Ok(range::serve(&audio.content_type, audio.bytes, &headers))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct CryParams {
+    #[serde(default)]
+    #[param(inline)]
+    version: CryVersion,
+}
+
+/// The cry of the Pokemon, `latest` or `legacy`.
+#[utoipa::path(
+    get,
+    path = "/pokemon/{name}/cry",
+    tag = "pokemon",
+    params(
+        ("name" = String, Path, description = "Pokemon name"),
+        CryParams,
+    ),
+    responses(
+        (
+            status = 200,
+            description = "The cry",
+            content_type = "audio/ogg",
+        ),
+        (
+            status = 206,
+            description = "The requested range of the cry",
+            content_type = "audio/ogg",
+        ),
+    ),
+)]
+async fn get_pokemon_cry(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(params): Query<CryParams>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    info!(pokemon_name = %name, version = ?params.version, "Fetching pokemon cry");
+    let audio = state.cry_service.cry(&name, params.version).await?;
+    Ok(range::serve(&audio.content_type, audio.bytes, &headers))
+}
+
+async fn pokemon_response(
+    state: &AppState,
+    name: &str,
+    version: ApiVersion,
+    include: Include,
+    lang: &Lang,
+    description: Description<'_>,
+    jsonapi: JsonApi,
+) -> Result<Response> {
+    let service = &state.pokemon_service;
+    let translated = description.translation.is_some();
+    let served = |name: &str, cache_hit: bool, stale: bool| {
+        state.usage.record_request(name, cache_hit);
+        state.events.publish(Event::PokemonServed {
+            name: name.to_string(),
+            translated,
+            stale,
+        })
+    };
+    let describe = |pokemon| async move {
+        let pokemon = description.summary.apply(pokemon);
+        match description.translation {
+            Some(translation) => {
+                translate_pokemon(state, pokemon, translation).await
+            }
+            None => pokemon,
+        }
+    };
+
+    match version {
+        ApiVersion::V1 => {
+            let pokemon = service.get_pokemon(name, lang).await?;
+            let cache_hit = cache_status::all_hits();
+            let pokemon = describe(pokemon).await;
+            let pokemon = service.expand(pokemon, include).await?;
+            let stale = pokemon.data_source.is_some();
+            served(&pokemon.name, cache_hit, stale);
+            Ok(stale_warning(stale, jsonapi.respond(&pokemon)))
+        }
+        ApiVersion::V2 => {
+            let mut details = service.get_details(name, lang).await?;
+            let cache_hit = cache_status::all_hits();
+            let pokemon = describe(details.pokemon).await;
+            details.pokemon =
+                service.expand(pokemon, include).await?;
+            let stale = details.pokemon.data_source.is_some();
+            served(&details.pokemon.name, cache_hit, stale);
+            Ok(stale_warning(stale, jsonapi.respond(&details)))
+        }
+    }
+}
+
+/// Flags responses built from stale cache entries, served while
+/// PokeAPI is unavailable, with `Warning: 110`.
+fn stale_warning(
+    stale: bool,
+    response: impl IntoResponse,
+) -> Response {
+    let mut response = response.into_response();
+    if stale {
+        response.headers_mut().insert(
+            header::WARNING,
+            HeaderValue::from_static("110 - \"Response is Stale\""),
+        );
+    }
+    response
+}
+
+#[utoipa::path(
+    post,
+    path = "/pokemon/batch",
+    tag = "pokemon",
+    request_body = BatchRequest,
+    responses(
+        (
+            status = 200,
+            description = "The Pokemon found and the names that failed",
+            body = BatchResponse,
+        ),
+    ),
+)]
+async fn get_pokemon_batch(
+    State(state): State<AppState>,
+    jsonapi: JsonApi,
+    JsonBody(request): JsonBody<BatchRequest>,
+) -> Result<Response> {
+    info!(count = request.names.len(), "Fetching pokemon batch");
+    if request.names.len() > state.config.batch_max_names {
+        return Err(error::AppError::PayloadTooLarge(format!(
+            "A batch may contain at most {} names",
+            state.config.batch_max_names
+        )));
+    }
+    let include = Include::parse(&request.include);
+    let mut errors: Vec<_> = request
+        .names
+        .iter()
+        .enumerate()
+        .filter(|(_, name)| name.trim().is_empty())
+        .map(|(i, _)| {
+            FieldError::new(
+                format!("names[{}]", i),
+                "empty",
+                "Pokemon names must not be empty",
+            )
+        })
+        .collect();
+    let include = include.unwrap_or_else(|e| {
+        errors.push(invalid_field("include", "unknown", e));
+        Include::default()
+    });
+    error::AppError::validate(errors)?;
+    if request.translated {
+        state.flags.check(Feature::Translation)?;
+    }
+    let lang = request.lang.clone().map(Lang).unwrap_or_default();
+
+    let results: Vec<Result<Pokemon>> =
+        join_all(request.names.iter().map(|name| {
+            let state = &state;
+            let lang = &lang;
+            async move {
+                let pokemon = state
+                    .pokemon_service
+                    .get_pokemon(name, lang)
+                    .await?;
+                let pokemon = if request.translated {
+                    translate_pokemon(
+                        state,
+                        pokemon,
+                        Translation::Fun,
+                    )
+                    .await
+                } else {
+                    pokemon
+                };
+                state.pokemon_service.expand(pokemon, include).await
+            }
+        }))
+        .await;
+
+    let mut response = BatchResponse {
+        pokemon: Vec::new(),
+        errors: Vec::new(),
+    };
+    for (name, result) in request.names.iter().zip(results) {
+        match result {
+            Ok(pokemon) => response.pokemon.push(pokemon),
+            Err(e) => response.errors.push(BatchError {
+                name: name.clone(),
+                error: e.to_string(),
+            }),
+        }
+    }
+
+    let stale = response
+        .pokemon
+        .iter()
+        .any(|pokemon| pokemon.data_source.is_some());
+    Ok(stale_warning(stale, jsonapi.respond_batch(&response)))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct EntriesParams {
+    /// Comma-separated languages to keep, e.g. `en,ja`.
+    language: Option<String>,
+    /// Comma-separated game versions to keep, e.g. `red,blue`.
+    version: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct EntriesResponse {
+    entries: Vec<FlavorEntry>,
+}
+
+/// Every Pokedex entry of a species, across games and languages.
+#[utoipa::path(
+    get,
+    path = "/pokemon/{name}/entries",
+    tag = "pokemon",
+    params(
+        ("name" = String, Path, description = "Pokemon name"),
+        EntriesParams,
+    ),
+    responses(
+        (
+            status = 200,
+            description = "The Pokedex entries",
+            body = EntriesResponse,
+        ),
+    ),
+)]
+async fn get_pokemon_entries(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(params): Query<EntriesParams>,
+) -> Result<Json<EntriesResponse>> {
+    info!(pokemon_name = %name, language = ?params.language, version = ?params.version, "Fetching pokedex entries");
+    let filter = |values: &Option<String>| -> Vec<String> {
+        values
+            .iter()
+            .flat_map(|values| values.split(','))
+            .map(|value| value.trim().to_lowercase())
+            .filter(|value| !value.is_empty())
+            .collect()
+    };
+    let (languages, versions) =
+        (filter(&params.language), filter(&params.version));
+    let kept = |wanted: &[String], value: &str| {
+        wanted.is_empty()
+            || wanted.iter().any(|w| w.eq_ignore_ascii_case(value))
+    };
+
+    let entries = state.pokemon_service.flavor_entries(&name).await?;
+    let entries = entries
+        .iter()
+        .filter(|entry| {
+            kept(&languages, &entry.language)
+                && kept(&versions, &entry.version)
+        })
+        .cloned()
+        .collect();
+    Ok(Json(EntriesResponse { entries }))
+}
+
+#[derive(Serialize, ToSchema)]
+struct FormsResponse {
+    forms: Vec<Form>,
+}
+
+/// Every variety of a species, for a form picker.
+#[utoipa::path(
+    get,
+    path = "/pokemon/{name}/forms",
+    tag = "pokemon",
+    params(("name" = String, Path, description = "Pokemon name")),
+    responses(
+        (
+            status = 200,
+            description = "The varieties of the species",
+            body = FormsResponse,
+        ),
+    ),
+)]
+async fn get_pokemon_forms(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<FormsResponse>> {
+    info!(pokemon_name = %name, "Listing pokemon forms");
+    let forms = state.pokemon_service.forms(&name).await?;
+    Ok(Json(FormsResponse { forms }))
+}
+
+/// How the types and abilities of a Pokemon changed across
+/// generations.
+#[utoipa::path(
+    get,
+    path = "/pokemon/{name}/stats/history",
+    tag = "pokemon",
+    params(("name" = String, Path, description = "Pokemon name")),
+    responses(
+        (
+            status = 200,
+            description = "The changes, oldest first",
+            body = StatHistory,
+        ),
+    ),
+)]
+async fn get_pokemon_stat_history(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<StatHistory>> {
+    info!(pokemon_name = %name, "Fetching pokemon stat history");
+    Ok(Json(state.pokemon_service.stat_history(&name).await?))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct EncountersParams {
+    /// Game version, e.g. `firered`.
+    version: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct EncountersResponse {
+    #[schema(value_type = Vec<Encounter>)]
+    encounters: Arc<Vec<Encounter>>,
+}
+
+/// Where a Pokemon is found in the wild, by game.
+#[utoipa::path(
+    get,
+    path = "/pokemon/{name}/encounters",
+    tag = "pokemon",
+    params(
+        ("name" = String, Path, description = "Pokemon name"),
+        EncountersParams,
+    ),
+    responses(
+        (
+            status = 200,
+            description = "The encounters by game",
+            body = EncountersResponse,
+        ),
+    ),
+)]
+async fn get_pokemon_encounters(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(params): Query<EncountersParams>,
+) -> Result<Json<EncountersResponse>> {
+    info!(pokemon_name = %name, version = ?params.version, "Fetching pokemon encounters");
+    let encounters = state
+        .encounter_service
+        .encounters(&name, params.version.as_deref())
+        .await?;
+    Ok(Json(EncountersResponse { encounters }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/pokemon/{name}/breeding-with/{other}",
+    tag = "pokemon",
+    params(
+        ("name" = String, Path, description = "Pokemon name"),
+        ("other" = String, Path, description = "Name of the other parent"),
+    ),
+    responses(
+        (
+            status = 200,
+            description = "Whether the pair can breed",
+            body = BreedingCompatibility,
+        ),
+    ),
+)]
+async fn get_breeding_compatibility(
+    State(state): State<AppState>,
+    Path((name, other)): Path<(String, String)>,
+) -> Result<Json<BreedingCompatibility>> {
+    info!(pokemon_name = %name, other = %other, "Checking breeding compatibility");
+    let compatibility =
+        state.breeding_service.compatibility(&name, &other).await?;
+    Ok(Json(compatibility))
+}
+
+#[utoipa::path(
+    get,
+    path = "/item/{name}",
+    tag = "items",
+    params(
+        ("name" = String, Path, description = "Item name"),
+        LangParams,
+    ),
+    responses(
+        (status = 200, description = "The item", body = Item),
+    ),
+)]
+async fn get_item(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    lang: Lang,
+) -> Result<Json<Item>> {
+    info!(item_name = %name, lang = %lang.as_str(), "Fetching item");
+    Ok(Json(state.item_service.item(&name, &lang).await?))
+}
+
+#[utoipa::path(
+    get,
+    path = "/berry/{name}",
+    tag = "items",
+    params(
+        ("name" = String, Path, description = "Berry name"),
+        LangParams,
+    ),
+    responses(
+        (status = 200, description = "The berry", body = Berry),
+    ),
+)]
+async fn get_berry(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    lang: Lang,
+) -> Result<Json<Berry>> {
+    info!(berry_name = %name, lang = %lang.as_str(), "Fetching berry");
+    Ok(Json(state.item_service.berry(&name, &lang).await?))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct MoveParams {
+    #[serde(default)]
+    translated: bool,
+}
+
+/// A move, with its effect translated in a fun style with
+/// `translated=true`.
+#[utoipa::path(
+    get,
+    path = "/move/{name}",
+    tag = "moves",
+    params(
+        ("name" = String, Path, description = "Move name"),
+        MoveParams,
+        LangParams,
+    ),
+    responses(
+        (status = 200, description = "The move", body = Move),
+    ),
+)]
+async fn get_move(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(params): Query<MoveParams>,
+    lang: Lang,
+) -> Result<Json<Move>> {
+    info!(move_name = %name, translated = params.translated, "Fetching move");
+    if params.translated {
+        state.flags.check(Feature::Translation)?;
+    }
+    let mut pokemon_move =
+        state.move_service.get_move(&name, &lang).await?;
+    // The fun styles are English.
+    if !params.translated || pokemon_move.effect_language.is_some() {
+        return Ok(Json(pokemon_move));
+    }
+    let Some(effect) = &pokemon_move.effect else {
+        return Ok(Json(pokemon_move));
+    };
+
+    // A move has no habitat, is no Pokemon of a type and is never
+    // legendary or mythical, so the style rules of the descriptions
+    // pick Shakespeare.
+    let service = &state.translation_service;
+    let style = service.select_style(&None, &[], false, false);
+    match service.translate_with(effect, style).await {
+        Ok(translated) => pokemon_move.effect = Some(translated),
+        Err(e) => {
+            warn!(move_name = %name, error = %e, "Keeping untranslated effect");
+        }
+    }
+    Ok(Json(pokemon_move))
+}
+
+#[derive(Deserialize, ToSchema)]
+struct TeamRequest {
+    names: Vec<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/team/analyze",
+    tag = "team",
+    request_body = TeamRequest,
+    responses(
+        (
+            status = 200,
+            description = "The analysis of the team",
+            body = TeamAnalysis,
+        ),
+    ),
+)]
+async fn analyze_team(
+    State(state): State<AppState>,
+    JsonBody(request): JsonBody<TeamRequest>,
+) -> Result<Json<TeamAnalysis>> {
+    info!(count = request.names.len(), "Analyzing team");
+    let analysis = state.team_service.analyze(&request.names).await?;
+    Ok(Json(analysis))
+}
+
+#[derive(Default, Deserialize, ToSchema)]
+struct QuizStartRequest {
+    #[serde(default)]
+    translated: bool,
+}
+
+#[utoipa::path(
+    post,
+    path = "/quiz/start",
+    tag = "quiz",
+    request_body = Option<QuizStartRequest>,
+    responses(
+        (
+            status = 200,
+            description = "A new challenge",
+            body = QuizChallenge,
+        ),
+    ),
+)]
+async fn start_quiz(
+    State(state): State<AppState>,
+    request: Option<Json<QuizStartRequest>>,
+) -> Result<Json<QuizChallenge>> {
+    let Json(request) = request.unwrap_or_default();
+    let challenge =
+        state.quiz_service.start(request.translated).await?;
+    info!(quiz_id = %challenge.id, "Started quiz");
+    Ok(Json(challenge))
+}
+
+#[derive(Deserialize, ToSchema)]
+struct GuessRequest {
+    name: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/quiz/{id}/guess",
+    tag = "quiz",
+    params(("id" = String, Path, description = "Quiz id")),
+    request_body = GuessRequest,
+    responses(
+        (
+            status = 200,
+            description = "The outcome of the guess",
+            body = GuessResult,
+        ),
+    ),
+)]
+async fn guess_quiz(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    JsonBody(request): JsonBody<GuessRequest>,
+) -> Result<Json<GuessResult>> {
+    let result = state.quiz_service.guess(&id, &request.name).await?;
+    Ok(Json(result))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SubmitJobParams {
+    #[serde(default)]
+    priority: Priority,
+}
+
+/// Queues a job of the kind in the path, e.g.
+/// `/jobs/translate-generation`, with the body as its parameters.
+#[utoipa::path(
+    post,
+    path = "/jobs/{id}",
+    tag = "jobs",
+    params(
+        ("id" = String, Path, description = "Kind of job, e.g. `translate-generation`"),
+        SubmitJobParams,
+    ),
+    request_body = Option<serde_json::Value>,
+    responses(
+        (
+            status = 202,
+            description = "The queued job, at `Location`",
+            body = Job,
+        ),
+    ),
+)]
+async fn submit_job(
+    State(state): State<AppState>,
+    Path(kind): Path<String>,
+    Query(params): Query<SubmitJobParams>,
+    body: Option<Json<serde_json::Value>>,
+) -> Result<impl IntoResponse> {
+    if let Some(feature) = state.job_runner.handler(&kind)?.feature()
+    {
+        state.flags.check(feature)?;
+    }
+    let Json(body) =
+        body.unwrap_or_else(|| Json(serde_json::json!({})));
+    let job =
+        state.job_runner.submit(&kind, body, params.priority)?;
+    Ok((
+        StatusCode::ACCEPTED,
+        [(header::LOCATION, format!("/jobs/{}", job.id))],
+        Json(job),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/jobs/{id}",
+    tag = "jobs",
+    params(("id" = String, Path, description = "Job id")),
+    responses(
+        (
+            status = 200,
+            description = "The job and its progress",
+            body = Job,
+        ),
+    ),
+)]
+async fn get_job(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Job>> {
+    Ok(Json(state.job_runner.get(&id)?))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/jobs/{id}",
+    tag = "jobs",
+    params(("id" = String, Path, description = "Job id")),
+    responses(
+        (status = 200, description = "The cancelled job", body = Job),
+    ),
+)]
+async fn cancel_job(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Job>> {
+    info!(job_id = %id, "Cancelling job");
+    Ok(Json(state.job_runner.cancel(&id)?))
+}
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+enum ResultFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct JobResultParams {
+    #[serde(default)]
+    #[param(inline)]
+    format: ResultFormat,
+}
+
+/// The rows of a finished job, one per item.
+#[derive(Serialize, ToSchema)]
+struct JobResults {
+    results: Vec<serde_json::Value>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/jobs/{id}/result",
+    tag = "jobs",
+    params(
+        ("id" = String, Path, description = "Job id"),
+        JobResultParams,
+    ),
+    responses(
+        (
+            status = 200,
+            description = "The rows of the job",
+            content((JobResults = "application/json"), (String = "text/csv")),
+        ),
+    ),
+)]
+async fn get_job_result(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(params): Query<JobResultParams>,
+) -> Result<Response> {
+    let results = state.job_runner.results(&id)?;
+    info!(job_id = %id, count = results.len(), "Serving job results");
+    Ok(match params.format {
+        ResultFormat::Json => {
+            Json(JobResults { results }).into_response()
+        }
+        ResultFormat::Csv => (
+            [(header::CONTENT_TYPE, "text/csv; charset=utf-8")],
+            jobs::to_csv(&results),
+        )
+            .into_response(),
+    })
+}
+
+#[derive(Serialize, ToSchema)]
+struct FavoritesResponse {
+    favorites: Vec<Pokemon>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/users/{id}/favorites",
+    tag = "favorites",
+    params(
+        ("id" = String, Path, description = "User id"),
+        LangParams,
+    ),
+    responses(
+        (
+            status = 200,
+            description = "The user's favorite Pokemon",
+            body = FavoritesResponse,
+        ),
+    ),
+    security(("api_key" = []), ("bearer" = [])),
+)]
+async fn list_favorites(
+    State(state): State<AppState>,
+    principal: Principal,
+    Path(user_id): Path<String>,
+    lang: Lang,
+) -> Result<Json<FavoritesResponse>> {
+    principal.ensure_user(&user_id)?;
+    let favorites =
+        state.favorites_service.list(&user_id, &lang).await?;
+    Ok(Json(FavoritesResponse { favorites }))
+}
+
+#[utoipa::path(
+    put,
+    path = "/users/{id}/favorites/{name}",
+    tag = "favorites",
+    params(
+        ("id" = String, Path, description = "User id"),
+        ("name" = String, Path, description = "Pokemon name"),
+    ),
+    responses(
+        (status = 204, description = "Added"),
+    ),
+    security(("api_key" = []), ("bearer" = [])),
+)]
+async fn add_favorite(
+    State(state): State<AppState>,
+    principal: Principal,
+    Path((user_id, name)): Path<(String, String)>,
+) -> Result<StatusCode> {
+    principal.ensure_user(&user_id)?;
+    state.favorites_service.add(&user_id, &name).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/users/{id}/favorites/{name}",
+    tag = "favorites",
+    params(
+        ("id" = String, Path, description = "User id"),
+        ("name" = String, Path, description = "Pokemon name"),
+    ),
+    responses(
+        (status = 204, description = "Removed"),
+    ),
+    security(("api_key" = []), ("bearer" = [])),
+)]
+async fn remove_favorite(
+    State(state): State<AppState>,
+    principal: Principal,
+    Path((user_id, name)): Path<(String, String)>,
+) -> Result<StatusCode> {
+    principal.ensure_user(&user_id)?;
+    if state.favorites_service.remove(&user_id, &name)? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(error::AppError::NotFound(format!(
+            "'{}' is not a favorite of user '{}'",
+            name, user_id
+        )))
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+struct TranslateRequest {
+    text: String,
+    style: String,
+}
+
+#[derive(Serialize, ToSchema)]
+struct TranslateResponse {
+    style: &'static str,
+    translated: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/translate",
+    tag = "translation",
+    request_body = TranslateRequest,
+    responses(
+        (
+            status = 200,
+            description = "The translated text",
+            body = TranslateResponse,
+        ),
+    ),
+)]
+async fn translate_text(
+    State(state): State<AppState>,
+    JsonBody(request): JsonBody<TranslateRequest>,
+) -> Result<Json<TranslateResponse>> {
+    let style = Style::parse(&request.style);
+    let text = request.text.trim();
+    let mut errors = Vec::new();
+    if text.is_empty() {
+        errors.push(FieldError::new(
+            "text",
+            "empty",
+            "text must not be empty",
+        ));
+    }
+    if style.is_none() {
+        errors.push(FieldError::new(
+            "style",
+            "unknown",
+            format!(
+                "Unknown style '{}', expected yoda or shakespeare",
+                request.style
+            ),
+        ));
+    }
+    let (Some(style), []) = (style, errors.as_slice()) else {
+        return Err(error::AppError::ValidationError(errors));
+    };
+    let max_chars = state.config.translate_max_chars;
+    if text.chars().count() > max_chars {
+        return Err(error::AppError::PayloadTooLarge(format!(
+            "text may contain at most {} characters",
+            max_chars
+        )));
+    }
+
+    info!(style = style.as_str(), "Translating text");
+    let translated = state
+        .translation_service
+        .translate_with(text, style)
+        .await?;
+    Ok(Json(TranslateResponse {
+        style: style.as_str(),
+        translated,
+    }))
+}
+
+/// Passes a PokeAPI resource of an allow-listed type through.
+#[utoipa::path(
+    get,
+    path = "/proxy/pokeapi/{path}",
+    tag = "proxy",
+    params(
+        ("path" = String, Path, description = "PokeAPI resource, e.g. `move/thunderbolt`"),
+        ProxyParams,
+    ),
+    responses(
+        (
+            status = 200,
+            description = "The resource as PokeAPI returns it",
+            body = Object,
+        ),
+    ),
+)]
+async fn proxy_pokeapi(
+    State(state): State<AppState>,
+    Path(path): Path<String>,
+    Query(params): Query<ProxyParams>,
+) -> Result<Json<Arc<serde_json::Value>>> {
+    info!(path = %path, "Proxying PokeAPI resource");
+    Ok(Json(state.proxy_service.get(&path, &params).await?))
+}
+
+/// How the description of a Pokemon is shortened and translated.
+#[derive(Clone, Copy)]
+struct Description<'a> {
+    summary: Summary,
+    translation: Option<Translation<'a>>,
+}
+
+/// How the description of a Pokemon is translated.
+#[derive(Clone, Copy)]
+enum Translation<'a> {
+    /// The funtranslations style picked from the habitat and
+    /// legendary status.
+    Fun,
+    /// A fixed funtranslations style.
+    Style(Style),
+    /// A funtranslations style assigned by an experiment.
+    Experiment(Style),
+    /// A real language, through the machine translator.
+    Language(&'a str),
+}
+
+/// Replaces the description with its translation, keeping the
+/// original when translation fails.
+async fn translate_pokemon(
+    state: &AppState,
+    mut pokemon: Pokemon,
+    translation: Translation<'_>,
+) -> Pokemon {
+    let Some(description) = &pokemon.description else {
+        return pokemon;
+    };
+    // The fun styles are English, and would mangle a description
+    // that fell back to another language.
+    if pokemon.description_language.is_some()
+        && !matches!(translation, Translation::Language(_))
+    {
+        return pokemon;
+    }
+    let service = &state.translation_service;
+    let (translated, translator, source) = match translation {
+        Translation::Fun => {
+            let style = service
+                .style_of(&pokemon, &state.pokemon_service)
+                .await;
+            let translated =
+                service.translate_with(description, style).await;
+            (translated, style.as_str(), "rule")
+        }
+        Translation::Style(style) => {
+            let translated =
+                service.translate_with(description, style).await;
+            (translated, style.as_str(), "override")
+        }
+        Translation::Experiment(style) => {
+            let translated =
+                service.translate_with(description, style).await;
+            (translated, style.as_str(), "experiment")
+        }
+        Translation::Language(target) => {
+            let translated =
+                service.translate_to(description, target).await;
+            (translated, "machine", "target")
+        }
+    };
+    state.metrics.record_translation(translator, source);
+
+    state
+        .usage
+        .record_translation(&pokemon.name, translated.is_err());
+    match translated {
+        Ok(translated) => {
+            pokemon.description = Some(translated);
+            pokemon.description_language = None;
+        }
+        Err(e) => {
+            warn!(pokemon_name = %pokemon.name, error = %e, "Keeping untranslated description");
+            state.events.publish(Event::TranslationFallback {
+                pokemon: pokemon.name.clone(),
+                error: e.to_string(),
+            });
+        }
+    }
+    pokemon
+}
+
+/// The value following `name` on the command line, as in
+/// `--port-file PATH`.
+fn arg_value(name: &str) -> Option<String> {
+    let mut args = std::env::args();
+    args.find(|arg| arg == name)?;
+    args.next()
+}
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("failed to install signal handler")
             .recv()
             .await;
     };
 
-    #[cfg(not(unix))]
+    // Stop requests of the service control manager, when running as
+    // a Windows service.
+    #[cfg(windows)]
+    let terminate = service::stopped();
+
+    #[cfg(not(any(unix, windows)))]
     let terminate = std::future::pending::<()>();
 
     tokio::select! {
         _ = ctrl_c => info!("Received Ctrl+C, starting graceful shutdown"),
-        _ = terminate => info!("Received SIGTERM, starting graceful shutdown"),
+        _ = terminate => info!("Received a stop request, starting graceful shutdown"),
     }
 }
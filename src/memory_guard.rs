@@ -0,0 +1,102 @@
+use crate::cache::ManagedCache;
+use crate::metrics::Metrics;
+
+/// Share of the high-water mark the caches are shrunk back to, so
+/// that the next few inserts do not trigger another eviction.
+const LOW_WATER_PERCENT: usize = 80;
+
+/// Keeps the approximate memory held by the caches under a
+/// high-water mark, from `CACHE_MEMORY_HIGH_WATER_MB`.
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryGuard {
+    high_water: usize,
+}
+
+impl MemoryGuard {
+    /// A guard for `high_water` bytes.
+    pub fn new(high_water: usize) -> Self {
+        Self { high_water }
+    }
+
+    fn low_water(&self) -> usize {
+        self.high_water / 100 * LOW_WATER_PERCENT
+    }
+
+    /// Weighs every cache and, past the high-water mark, evicts from
+    /// each in proportion to its weight until they are back under the
+    /// low-water mark. Returns how many entries were evicted.
+    pub fn check(
+        &self,
+        caches: &[(&str, &dyn ManagedCache)],
+        metrics: &Metrics,
+    ) -> usize {
+        let weights: Vec<usize> =
+            caches.iter().map(|(_, cache)| cache.weight()).collect();
+        let total: usize = weights.iter().sum();
+        let over = total > self.high_water;
+
+        let mut evicted = 0;
+        for ((name, cache), weight) in caches.iter().zip(weights) {
+            let (weight, count) = if over {
+                let target =
+                    (weight as u128 * self.low_water() as u128
+                        / total as u128) as usize;
+                let count = cache.evict(target);
+                (cache.weight(), count)
+            } else {
+                (weight, 0)
+            };
+            metrics.record_cache_memory(name, weight, count);
+            evicted += count;
+        }
+        evicted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::Cache;
+    use std::time::Duration;
+
+    fn filled(count: u32) -> Cache<String, u32> {
+        let cache = Cache::new(Duration::from_secs(60));
+        for id in 0..count {
+            cache.insert(format!("{:08}", id), id);
+        }
+        cache
+    }
+
+    #[test]
+    fn test_check_evicts_past_the_high_water_mark() {
+        let (species, varieties) = (filled(100), filled(50));
+        let caches: [(&str, &dyn ManagedCache); 2] = [
+            ("pokemon.species", &species),
+            ("pokemon.variety", &varieties),
+        ];
+        let total = species.weight() + varieties.weight();
+        let metrics = Metrics::new();
+
+        assert_eq!(
+            MemoryGuard::new(total).check(&caches, &metrics),
+            0
+        );
+
+        let guard = MemoryGuard::new(total / 2);
+        assert!(guard.check(&caches, &metrics) > 0);
+        let after = species.weight() + varieties.weight();
+        assert!(after <= guard.low_water());
+        // Both caches gave up about the same share.
+        assert!(species.stats().entries > varieties.stats().entries);
+
+        let text = metrics.render(&[]);
+        assert!(text.contains(&format!(
+            "cache_memory_bytes{{cache=\"pokemon.species\"}} {}",
+            species.weight()
+        )));
+        assert!(text.contains(&format!(
+            "cache_pressure_evictions_total{{cache=\"pokemon.variety\"}} {}",
+            50 - varieties.stats().entries
+        )));
+    }
+}
@@ -0,0 +1,524 @@
+use crate::cache::CacheStats;
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Upper bounds, in seconds, of the request duration histogram.
+const DURATION_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct RequestKey {
+    method: String,
+    route: String,
+    status: u16,
+}
+
+#[derive(Default)]
+struct RequestStats {
+    count: u64,
+    sum_secs: f64,
+    buckets: [u64; DURATION_BUCKETS.len()],
+}
+
+/// What the memory guard last found in a cache.
+#[derive(Default)]
+struct CacheMemory {
+    bytes: usize,
+    evictions: u64,
+}
+
+/// HTTP request counters rendered in the Prometheus text format.
+#[derive(Default)]
+pub struct Metrics {
+    requests: Mutex<BTreeMap<RequestKey, RequestStats>>,
+    memory: Mutex<BTreeMap<String, CacheMemory>>,
+    /// New fields and broken samples of the last schema check,
+    /// by upstream and model.
+    drift: Mutex<BTreeMap<(String, String), (usize, usize)>>,
+    /// Scheduled job runs by job and outcome.
+    jobs: Mutex<BTreeMap<(String, &'static str), u64>>,
+    /// Shadow comparisons by outcome.
+    shadow: Mutex<BTreeMap<&'static str, u64>>,
+    /// Description translations by translator and how it was chosen.
+    translations: Mutex<BTreeMap<(&'static str, &'static str), u64>>,
+    /// Translated responses by experiment variant and status.
+    experiments: Mutex<BTreeMap<(String, u16), u64>>,
+    /// Caches cleared here or by another replica.
+    invalidations: Mutex<BTreeMap<&'static str, u64>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(
+        &self,
+        method: &str,
+        route: &str,
+        status: u16,
+        elapsed: Duration,
+    ) {
+        let key = RequestKey {
+            method: method.to_string(),
+            route: route.to_string(),
+            status,
+        };
+        let secs = elapsed.as_secs_f64();
+
+        let mut requests = self.requests.lock().unwrap();
+        let stats = requests.entry(key).or_default();
+        stats.count += 1;
+        stats.sum_secs += secs;
+        for (bucket, bound) in
+            stats.buckets.iter_mut().zip(DURATION_BUCKETS)
+        {
+            if secs <= bound {
+                *bucket += 1;
+            }
+        }
+    }
+
+    /// Records the weight of `cache` after a memory check, and how
+    /// many entries the check evicted from it.
+    pub fn record_cache_memory(
+        &self,
+        cache: &str,
+        bytes: usize,
+        evicted: usize,
+    ) {
+        let mut memory = self.memory.lock().unwrap();
+        let entry = memory.entry(cache.to_string()).or_default();
+        entry.bytes = bytes;
+        entry.evictions += evicted as u64;
+    }
+
+    /// Records what the last re-validation of the payloads of
+    /// `upstream` read into `model` found.
+    pub fn record_schema_drift(
+        &self,
+        upstream: &str,
+        model: &str,
+        new_fields: usize,
+        broken_samples: usize,
+    ) {
+        self.drift.lock().unwrap().insert(
+            (upstream.to_string(), model.to_string()),
+            (new_fields, broken_samples),
+        );
+    }
+
+    /// Counts a run of the scheduled job `job` that ended with
+    /// `outcome`: `ok`, `error` or `skipped`.
+    pub fn record_job(&self, job: &str, outcome: &'static str) {
+        *self
+            .jobs
+            .lock()
+            .unwrap()
+            .entry((job.to_string(), outcome))
+            .or_default() += 1;
+    }
+
+    /// Counts a comparison of a shadow payload that ended with
+    /// `outcome`: `match`, `differ` or `error`.
+    pub fn record_shadow(&self, outcome: &'static str) {
+        *self.shadow.lock().unwrap().entry(outcome).or_default() += 1;
+    }
+
+    /// Counts a description translated by `translator` (a style or
+    /// `machine`), chosen by `source`: `rule`, `override` or
+    /// `target`.
+    pub fn record_translation(
+        &self,
+        translator: &'static str,
+        source: &'static str,
+    ) {
+        *self
+            .translations
+            .lock()
+            .unwrap()
+            .entry((translator, source))
+            .or_default() += 1;
+    }
+
+    /// Counts a translated response served with `variant`, an
+    /// experiment or `control`.
+    pub fn record_experiment(&self, variant: &str, status: u16) {
+        *self
+            .experiments
+            .lock()
+            .unwrap()
+            .entry((variant.to_string(), status))
+            .or_default() += 1;
+    }
+
+    /// Counts a cache cleared by an operator, `local`ly or on a
+    /// `remote` replica.
+    pub fn record_invalidation(&self, origin: &'static str) {
+        *self
+            .invalidations
+            .lock()
+            .unwrap()
+            .entry(origin)
+            .or_default() += 1;
+    }
+
+    /// Renders the request metrics followed by the given cache
+    /// statistics.
+    pub fn render(&self, caches: &[(&str, CacheStats)]) -> String {
+        let mut out = String::new();
+        let requests = self.requests.lock().unwrap();
+
+        out.push_str(
+            "# HELP http_requests_total Total number of HTTP requests.\n\
+             # TYPE http_requests_total counter\n",
+        );
+        for (key, stats) in requests.iter() {
+            let _ = writeln!(
+                out,
+                "http_requests_total{{{}}} {}",
+                labels(key),
+                stats.count
+            );
+        }
+
+        out.push_str(
+            "# HELP http_request_duration_seconds HTTP request latency.\n\
+             # TYPE http_request_duration_seconds histogram\n",
+        );
+        for (key, stats) in requests.iter() {
+            let labels = labels(key);
+            for (count, bound) in
+                stats.buckets.iter().zip(DURATION_BUCKETS)
+            {
+                let _ = writeln!(
+                    out,
+                    "http_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels, bound, count
+                );
+            }
+            let _ = writeln!(
+                out,
+                "http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+                labels, stats.count
+            );
+            let _ = writeln!(
+                out,
+                "http_request_duration_seconds_sum{{{}}} {}",
+                labels, stats.sum_secs
+            );
+            let _ = writeln!(
+                out,
+                "http_request_duration_seconds_count{{{}}} {}",
+                labels, stats.count
+            );
+        }
+
+        write_cache_metric(
+            &mut out,
+            "cache_entries",
+            "gauge",
+            "Live cache entries.",
+            caches,
+            |stats| stats.entries as u64,
+        );
+        write_cache_metric(
+            &mut out,
+            "cache_hits_total",
+            "counter",
+            "Cache hits.",
+            caches,
+            |stats| stats.hits,
+        );
+        write_cache_metric(
+            &mut out,
+            "cache_misses_total",
+            "counter",
+            "Cache misses.",
+            caches,
+            |stats| stats.misses,
+        );
+        write_cache_metric(
+            &mut out,
+            "cache_l2_hits_total",
+            "counter",
+            "Cache misses answered by the second-level store.",
+            caches,
+            |stats| stats.l2_hits,
+        );
+        write_cache_metric(
+            &mut out,
+            "cache_evictions_total",
+            "counter",
+            "Entries evicted to stay within the maximum number of entries.",
+            caches,
+            |stats| stats.evictions,
+        );
+
+        let drift = self.drift.lock().unwrap();
+        if !drift.is_empty() {
+            out.push_str(
+                "# HELP upstream_schema_new_fields Fields of sampled upstream payloads the models do not know, added since their first check.\n\
+                 # TYPE upstream_schema_new_fields gauge\n",
+            );
+            for ((upstream, model), (added, _)) in drift.iter() {
+                let _ = writeln!(
+                    out,
+                    "upstream_schema_new_fields{{upstream=\"{}\",model=\"{}\"}} {}",
+                    upstream, model, added
+                );
+            }
+            out.push_str(
+                "# HELP upstream_schema_broken_samples Sampled upstream payloads that no longer deserialize.\n\
+                 # TYPE upstream_schema_broken_samples gauge\n",
+            );
+            for ((upstream, model), (_, broken)) in drift.iter() {
+                let _ = writeln!(
+                    out,
+                    "upstream_schema_broken_samples{{upstream=\"{}\",model=\"{}\"}} {}",
+                    upstream, model, broken
+                );
+            }
+        }
+
+        let jobs = self.jobs.lock().unwrap();
+        if !jobs.is_empty() {
+            out.push_str(
+                "# HELP scheduled_job_runs_total Scheduled job runs by outcome.\n\
+                 # TYPE scheduled_job_runs_total counter\n",
+            );
+            for ((job, outcome), runs) in jobs.iter() {
+                let _ = writeln!(
+                    out,
+                    "scheduled_job_runs_total{{job=\"{}\",outcome=\"{}\"}} {}",
+                    job, outcome, runs
+                );
+            }
+        }
+
+        let shadow = self.shadow.lock().unwrap();
+        if !shadow.is_empty() {
+            out.push_str(
+                "# HELP shadow_comparisons_total Payloads of mirrored PokeAPI calls compared with the shadow upstream, by outcome.\n\
+                 # TYPE shadow_comparisons_total counter\n",
+            );
+            for (outcome, count) in shadow.iter() {
+                let _ = writeln!(
+                    out,
+                    "shadow_comparisons_total{{outcome=\"{}\"}} {}",
+                    outcome, count
+                );
+            }
+        }
+
+        let translations = self.translations.lock().unwrap();
+        if !translations.is_empty() {
+            out.push_str(
+                "# HELP translations_total Descriptions translated, by translator and by how it was chosen.\n\
+                 # TYPE translations_total counter\n",
+            );
+            for ((translator, source), count) in translations.iter() {
+                let _ = writeln!(
+                    out,
+                    "translations_total{{translator=\"{}\",source=\"{}\"}} {}",
+                    translator, source, count
+                );
+            }
+        }
+
+        let experiments = self.experiments.lock().unwrap();
+        if !experiments.is_empty() {
+            out.push_str(
+                "# HELP experiment_requests_total Translated responses by experiment variant.\n\
+                 # TYPE experiment_requests_total counter\n",
+            );
+            for ((variant, status), count) in experiments.iter() {
+                let _ = writeln!(
+                    out,
+                    "experiment_requests_total{{variant=\"{}\",status=\"{}\"}} {}",
+                    variant, status, count
+                );
+            }
+        }
+
+        let invalidations = self.invalidations.lock().unwrap();
+        if !invalidations.is_empty() {
+            out.push_str(
+                "# HELP cache_invalidations_total Caches cleared by an operator, here or on another replica.\n\
+                 # TYPE cache_invalidations_total counter\n",
+            );
+            for (origin, count) in invalidations.iter() {
+                let _ = writeln!(
+                    out,
+                    "cache_invalidations_total{{origin=\"{}\"}} {}",
+                    origin, count
+                );
+            }
+        }
+
+        let memory = self.memory.lock().unwrap();
+        if !memory.is_empty() {
+            out.push_str(
+                "# HELP cache_memory_bytes Approximate memory held by the cache entries.\n\
+                 # TYPE cache_memory_bytes gauge\n",
+            );
+            for (cache, stats) in memory.iter() {
+                let _ = writeln!(
+                    out,
+                    "cache_memory_bytes{{cache=\"{}\"}} {}",
+                    cache, stats.bytes
+                );
+            }
+            out.push_str(
+                "# HELP cache_pressure_evictions_total Entries evicted past the memory high-water mark.\n\
+                 # TYPE cache_pressure_evictions_total counter\n",
+            );
+            for (cache, stats) in memory.iter() {
+                let _ = writeln!(
+                    out,
+                    "cache_pressure_evictions_total{{cache=\"{}\"}} {}",
+                    cache, stats.evictions
+                );
+            }
+        }
+
+        out
+    }
+}
+
+fn write_cache_metric(
+    out: &mut String,
+    name: &str,
+    kind: &str,
+    help: &str,
+    caches: &[(&str, CacheStats)],
+    value: impl Fn(&CacheStats) -> u64,
+) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    for (cache, stats) in caches {
+        let _ = writeln!(
+            out,
+            "{}{{cache=\"{}\"}} {}",
+            name,
+            cache,
+            value(stats)
+        );
+    }
+}
+
+fn labels(key: &RequestKey) -> String {
+    format!(
+        "method=\"{}\",route=\"{}\",status=\"{}\"",
+        key.method, key.route, key.status
+    )
+}
+
+/// Records every request under its route template, so that
+/// `/pokemon/pikachu` and `/pokemon/mewtwo` share one series.
+pub async fn track(
+    State(metrics): State<Arc<Metrics>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let method = request.method().to_string();
+    let started = Instant::now();
+
+    let response = next.run(request).await;
+    metrics.record(
+        &method,
+        &route,
+        response.status().as_u16(),
+        started.elapsed(),
+    );
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_requests_and_caches() {
+        let metrics = Metrics::new();
+        metrics.record(
+            "GET",
+            "/pokemon/:name",
+            200,
+            Duration::from_millis(30),
+        );
+        metrics.record(
+            "GET",
+            "/pokemon/:name",
+            200,
+            Duration::from_millis(3),
+        );
+
+        let text = metrics.render(&[(
+            "pokemon.species",
+            CacheStats {
+                entries: 2,
+                hits: 5,
+                misses: 2,
+                l2_hits: 1,
+                evictions: 3,
+            },
+        )]);
+        let labels =
+            r#"method="GET",route="/pokemon/:name",status="200""#;
+        assert!(text.contains(&format!(
+            "http_requests_total{{{}}} 2",
+            labels
+        )));
+        assert!(text.contains(&format!(
+            "http_request_duration_seconds_bucket{{{},le=\"0.005\"}} 1",
+            labels
+        )));
+        assert!(text.contains(&format!(
+            "http_request_duration_seconds_bucket{{{},le=\"0.05\"}} 2",
+            labels
+        )));
+        assert!(text.contains(
+            "cache_hits_total{cache=\"pokemon.species\"} 5"
+        ));
+    }
+
+    #[test]
+    fn test_render_translations_and_experiments() {
+        let metrics = Metrics::new();
+        metrics.record_translation("yoda", "rule");
+        metrics.record_translation("yoda", "override");
+        metrics.record_translation("yoda", "override");
+        metrics.record_experiment("yoda-all", 200);
+        metrics.record_invalidation("remote");
+
+        let text = metrics.render(&[]);
+        assert!(text.contains(
+            "translations_total{translator=\"yoda\",source=\"rule\"} 1"
+        ));
+        assert!(text.contains(
+            "translations_total{translator=\"yoda\",source=\"override\"} 2"
+        ));
+        assert!(text.contains(
+            "experiment_requests_total{variant=\"yoda-all\",status=\"200\"} 1"
+        ));
+        assert!(text.contains(
+            "cache_invalidations_total{origin=\"remote\"} 1"
+        ));
+    }
+}
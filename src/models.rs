@@ -0,0 +1,174 @@
+//! Response models shared by the server and the client SDK.
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(
+    Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema,
+)]
+pub struct Pokemon {
+    pub name: String,
+    #[serde(default)]
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub genus: Option<String>,
+    pub description: Option<String>,
+    /// Language of the description when, without an English one, it
+    /// fell back to another.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description_language: Option<String>,
+    pub habitat: Option<String>,
+    pub is_legendary: bool,
+    pub is_mythical: bool,
+    pub is_baby: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub breeding: Option<Breeding>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artwork: Option<Artwork>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phonetics: Option<Phonetics>,
+    /// Set when the data is not fresh from PokeAPI.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_source: Option<DataSource>,
+    /// Why parts of the response are missing, e.g. malformed
+    /// upstream entries that were skipped, or the battle data of
+    /// details when the variety could not be fetched.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// Where the data of a response came from, when not from PokeAPI.
+#[derive(
+    Debug, Clone, Copy, Serialize, Deserialize, PartialEq, ToSchema,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum DataSource {
+    /// An expired cache entry, served while PokeAPI is unavailable.
+    StaleCache,
+}
+
+#[derive(
+    Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema,
+)]
+pub struct Breeding {
+    pub egg_groups: Vec<String>,
+    pub growth_rate: Option<String>,
+}
+
+#[derive(
+    Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema,
+)]
+pub struct Meta {
+    pub capture_rate: Option<u32>,
+    pub base_happiness: Option<u32>,
+    pub shape: Option<String>,
+    pub color: Option<String>,
+    /// Chance of being female in eighths, -1 when genderless.
+    #[serde(default)]
+    pub gender_rate: Option<i8>,
+    /// Egg cycles to hatch; each takes 255 steps in most games.
+    #[serde(default)]
+    pub hatch_counter: Option<u32>,
+    /// Id of the evolution chain the species belongs to.
+    #[serde(default)]
+    pub evolution_chain: Option<u32>,
+}
+
+#[derive(
+    Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema,
+)]
+pub struct Artwork {
+    pub official: Option<String>,
+    pub official_shiny: Option<String>,
+    pub front: Option<String>,
+    pub back: Option<String>,
+    pub front_shiny: Option<String>,
+    pub back_shiny: Option<String>,
+}
+
+/// How a Pokemon's name is pronounced, for voice assistants.
+#[derive(
+    Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema,
+)]
+pub struct Phonetics {
+    /// IPA transcription, known for well-known Pokemon only.
+    pub ipa: Option<String>,
+    /// Simple respelling with the stressed syllable in capitals,
+    /// e.g. `PEE-kuh-choo`.
+    pub respelling: String,
+}
+
+/// A Pokemon together with the battle data of its default variety.
+#[derive(
+    Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema,
+)]
+pub struct PokemonDetails {
+    #[serde(flatten)]
+    pub pokemon: Pokemon,
+    pub types: Vec<String>,
+    pub height: u32,
+    pub weight: u32,
+    pub stats: Vec<Stat>,
+    pub abilities: Vec<String>,
+}
+
+#[derive(
+    Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema,
+)]
+pub struct Stat {
+    pub name: String,
+    pub base: u32,
+}
+
+/// Body of `POST /pokemon/batch`.
+#[derive(
+    Debug, Clone, Default, Serialize, Deserialize, PartialEq, ToSchema,
+)]
+pub struct BatchRequest {
+    pub names: Vec<String>,
+    #[serde(default)]
+    pub translated: bool,
+    /// Comma-separated sections, as in `?include=`.
+    #[serde(default)]
+    pub include: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lang: Option<String>,
+}
+
+#[derive(
+    Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema,
+)]
+pub struct BatchResponse {
+    pub pokemon: Vec<Pokemon>,
+    pub errors: Vec<BatchError>,
+}
+
+/// A name of the batch that could not be fetched.
+#[derive(
+    Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema,
+)]
+pub struct BatchError {
+    pub name: String,
+    pub error: String,
+}
+
+/// Entry of the species index used by the listing endpoints.
+#[derive(
+    Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema,
+)]
+pub struct SpeciesSummary {
+    pub id: u32,
+    pub name: String,
+}
+
+/// Envelope shared by every listing endpoint.
+#[derive(
+    Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema,
+)]
+pub struct Page<T> {
+    pub count: usize,
+    pub results: Vec<T>,
+    pub next_cursor: Option<String>,
+}
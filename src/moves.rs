@@ -0,0 +1,312 @@
+use crate::cache::{Cache, ManagedCache};
+use crate::error::Result;
+use crate::item::Effect;
+use crate::lang::{DEFAULT_LANGUAGE, Lang};
+use crate::pokeapi::{NamedApiResource, PokeApiClient};
+use crate::pokemon::Localized;
+use crate::text::{self, Normalization};
+use serde::{Deserialize, Serialize};
+use std::{sync::Arc, time::Duration};
+use tracing::instrument;
+use utoipa::ToSchema;
+
+/// Placeholder PokeAPI leaves in effect texts for the chance of the
+/// secondary effect.
+const EFFECT_CHANCE: &str = "$effect_chance";
+
+/// A move rendered in the requested language.
+#[derive(Debug, Clone, Serialize, PartialEq, ToSchema)]
+pub struct Move {
+    pub id: u32,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(rename = "type")]
+    pub type_: Option<String>,
+    /// `physical`, `special` or `status`.
+    pub damage_class: Option<String>,
+    pub power: Option<u32>,
+    pub accuracy: Option<u32>,
+    pub pp: Option<u32>,
+    pub priority: i32,
+    pub effect: Option<String>,
+    pub short_effect: Option<String>,
+    /// Language of the effect when it is not English.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effect_language: Option<String>,
+    /// In-game description from the latest games that have one.
+    pub flavor_text: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PokeApiMove {
+    id: u32,
+    name: String,
+    #[serde(rename = "type")]
+    type_: Option<NamedApiResource>,
+    damage_class: Option<NamedApiResource>,
+    power: Option<u32>,
+    accuracy: Option<u32>,
+    pp: Option<u32>,
+    #[serde(default)]
+    priority: i32,
+    effect_chance: Option<u32>,
+    #[serde(default)]
+    effect_entries: Vec<PokeApiEffect>,
+    #[serde(default)]
+    flavor_text_entries: Vec<PokeApiMoveFlavorText>,
+    #[serde(default)]
+    names: Vec<PokeApiName>,
+}
+
+#[derive(Deserialize)]
+struct PokeApiEffect {
+    effect: String,
+    short_effect: String,
+    language: NamedApiResource,
+}
+
+#[derive(Deserialize)]
+struct PokeApiMoveFlavorText {
+    flavor_text: String,
+    language: NamedApiResource,
+}
+
+#[derive(Deserialize)]
+struct PokeApiName {
+    name: String,
+    language: NamedApiResource,
+}
+
+/// What we keep of a move: everything but the strings in the
+/// requested language, which are picked when rendering.
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedMove {
+    id: u32,
+    name: String,
+    type_: Option<String>,
+    damage_class: Option<String>,
+    power: Option<u32>,
+    accuracy: Option<u32>,
+    pp: Option<u32>,
+    priority: i32,
+    names: Vec<Localized>,
+    effects: Vec<Effect>,
+    /// Newest games first.
+    flavor_texts: Vec<Localized>,
+}
+
+impl CachedMove {
+    fn render(&self, lang: &Lang) -> Move {
+        let pick = |entries: &[Localized]| {
+            lang.pick(entries, |e| &e.language)
+                .map(|e| e.value.clone())
+        };
+        let effect = lang.pick(&self.effects, |e| &e.language);
+        Move {
+            id: self.id,
+            name: self.name.clone(),
+            display_name: pick(&self.names),
+            type_: self.type_.clone(),
+            damage_class: self.damage_class.clone(),
+            power: self.power,
+            accuracy: self.accuracy,
+            pp: self.pp,
+            priority: self.priority,
+            effect: effect.map(|e| e.effect.clone()),
+            short_effect: effect.map(|e| e.short_effect.clone()),
+            effect_language: effect
+                .map(|e| e.language.clone())
+                .filter(|language| language != DEFAULT_LANGUAGE),
+            flavor_text: pick(&self.flavor_texts),
+        }
+    }
+}
+
+pub struct MoveService {
+    pokeapi: PokeApiClient,
+    normalization: Normalization,
+    cache: Cache<String, Arc<CachedMove>>,
+}
+
+impl MoveService {
+    pub fn new(pokeapi: PokeApiClient, cache_ttl: Duration) -> Self {
+        Self {
+            pokeapi,
+            normalization: Normalization::default(),
+            cache: Cache::new(cache_ttl),
+        }
+    }
+
+    /// Normalizes the effect and flavor texts like the Pokemon
+    /// descriptions.
+    pub fn with_normalization(
+        mut self,
+        normalization: Normalization,
+    ) -> Self {
+        self.normalization = normalization;
+        self
+    }
+
+    /// The caches owned by this service, by name.
+    pub fn caches(&self) -> Vec<(&'static str, &dyn ManagedCache)> {
+        vec![("moves", &self.cache)]
+    }
+
+    #[instrument(skip(self))]
+    pub async fn get_move(
+        &self,
+        name: &str,
+        lang: &Lang,
+    ) -> Result<Move> {
+        let key = name.to_lowercase();
+        if let Some(cached) = self.cache.fetch(&key).await {
+            return Ok(cached.render(lang));
+        }
+
+        let pokeapi_move: PokeApiMove = self
+            .pokeapi
+            .get(&format!("move/{}", key), || {
+                format!("Move '{}' not found", name)
+            })
+            .await?;
+        let cached = Arc::new(self.map_to_move(pokeapi_move));
+        self.cache.store(key, cached.clone()).await;
+        Ok(cached.render(lang))
+    }
+
+    /// Flattens `text` and fills in the effect chance.
+    fn clean(
+        &self,
+        text: &str,
+        effect_chance: Option<u32>,
+    ) -> String {
+        let cleaned = text::clean_description(text);
+        let cleaned = match effect_chance {
+            Some(chance) if cleaned.contains(EFFECT_CHANCE) => {
+                cleaned.replace(EFFECT_CHANCE, &chance.to_string())
+            }
+            _ => cleaned.into_owned(),
+        };
+        self.normalization.apply(&cleaned).into_owned()
+    }
+
+    fn map_to_move(&self, pokeapi_move: PokeApiMove) -> CachedMove {
+        let chance = pokeapi_move.effect_chance;
+        CachedMove {
+            id: pokeapi_move.id,
+            name: pokeapi_move.name,
+            type_: pokeapi_move.type_.map(|t| t.name),
+            damage_class: pokeapi_move.damage_class.map(|c| c.name),
+            power: pokeapi_move.power,
+            accuracy: pokeapi_move.accuracy,
+            pp: pokeapi_move.pp,
+            priority: pokeapi_move.priority,
+            names: pokeapi_move
+                .names
+                .into_iter()
+                .map(|name| Localized {
+                    language: name.language.name,
+                    value: name.name,
+                })
+                .collect(),
+            effects: pokeapi_move
+                .effect_entries
+                .into_iter()
+                .map(|entry| Effect {
+                    language: entry.language.name,
+                    effect: self.clean(&entry.effect, chance),
+                    short_effect: self
+                        .clean(&entry.short_effect, chance),
+                })
+                .collect(),
+            // PokeAPI lists them oldest games first.
+            flavor_texts: pokeapi_move
+                .flavor_text_entries
+                .into_iter()
+                .rev()
+                .map(|entry| Localized {
+                    language: entry.language.name,
+                    value: self.clean(&entry.flavor_text, None),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_move_effect_is_cleaned() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/move/thunderbolt"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({
+                    "id": 85,
+                    "name": "thunderbolt",
+                    "type": {"name": "electric", "url": "u"},
+                    "damage_class": {"name": "special", "url": "u"},
+                    "power": 90,
+                    "accuracy": 100,
+                    "pp": 15,
+                    "priority": 0,
+                    "effect_chance": 10,
+                    "effect_entries": [{
+                        "effect": "Inflicts regular damage.  Has a\n$effect_chance% chance to paralyze the target.",
+                        "short_effect": "Has a $effect_chance% chance to paralyze the target.",
+                        "language": {"name": "en", "url": "u"}
+                    }],
+                    "flavor_text_entries": [{
+                        "flavor_text": "A strong electric\nblast.",
+                        "language": {"name": "en", "url": "u"}
+                    }],
+                    "names": [
+                        {"name": "Thunderbolt", "language": {"name": "en", "url": "u"}},
+                        {"name": "Fulmine", "language": {"name": "it", "url": "u"}}
+                    ]
+                }),
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let service = MoveService::new(
+            PokeApiClient::new(
+                http::build_client(&http::ClientSettings::new(
+                    Duration::from_secs(5),
+                )),
+                server.uri(),
+            ),
+            Duration::from_secs(60),
+        );
+        let thunderbolt = service
+            .get_move("Thunderbolt", &Lang::default())
+            .await
+            .unwrap();
+        assert_eq!(thunderbolt.power, Some(90));
+        assert_eq!(
+            thunderbolt.effect.as_deref(),
+            Some(
+                "Inflicts regular damage. Has a 10% chance to \
+                 paralyze the target."
+            )
+        );
+        assert_eq!(
+            thunderbolt.flavor_text.as_deref(),
+            Some("A strong electric blast.")
+        );
+        assert_eq!(thunderbolt.effect_language, None);
+
+        let fulmine = service
+            .get_move("thunderbolt", &Lang("it".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(fulmine.display_name.as_deref(), Some("Fulmine"));
+    }
+}
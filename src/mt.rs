@@ -0,0 +1,324 @@
+use crate::error::{AppError, Result};
+use crate::http::Upstream;
+use crate::tolerant::Tolerant;
+use axum::async_trait;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+/// A machine-translation provider producing real language
+/// translations, as opposed to the funtranslations styles.
+#[async_trait]
+pub trait Translator: Send + Sync {
+    /// Translates `text` into the `target` language (an ISO 639
+    /// code such as `it` or `pt-br`), detecting the source language.
+    async fn translate(
+        &self,
+        text: &str,
+        target: &str,
+    ) -> Result<String>;
+}
+
+/// The machine-translation services that can be configured with
+/// `MT_PROVIDER`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Provider {
+    LibreTranslate,
+    DeepL,
+}
+
+impl Provider {
+    /// `None` means machine translation is disabled.
+    pub fn parse(
+        value: &str,
+    ) -> std::result::Result<Option<Self>, String> {
+        match value.trim().to_lowercase().as_str() {
+            "" | "none" => Ok(None),
+            "libretranslate" => Ok(Some(Provider::LibreTranslate)),
+            "deepl" => Ok(Some(Provider::DeepL)),
+            other => Err(format!(
+                "unknown provider '{}', expected none, libretranslate or deepl",
+                other
+            )),
+        }
+    }
+
+    pub fn default_url(self) -> &'static str {
+        match self {
+            Provider::LibreTranslate => "https://libretranslate.com",
+            Provider::DeepL => "https://api-free.deepl.com",
+        }
+    }
+
+    /// Builds the translator for this provider, sending its requests
+    /// through `upstream`.
+    pub fn build(
+        self,
+        upstream: Upstream,
+        base_url: String,
+        api_key: Option<String>,
+    ) -> Arc<dyn Translator> {
+        let base_url = base_url.trim_end_matches('/').to_string();
+        match self {
+            Provider::LibreTranslate => Arc::new(LibreTranslate {
+                upstream,
+                base_url,
+                api_key,
+            }),
+            Provider::DeepL => Arc::new(DeepL {
+                upstream,
+                base_url,
+                api_key: api_key.unwrap_or_default(),
+            }),
+        }
+    }
+}
+
+/// Checks that `target` looks like a language code and normalizes
+/// it to lowercase.
+pub fn parse_target(target: &str) -> Result<String> {
+    let target = target.trim().to_lowercase();
+    let mut parts = target.splitn(2, '-');
+    let language = parts.next().unwrap_or_default();
+    let region = parts.next();
+
+    let valid = (2..=3).contains(&language.len())
+        && language.chars().all(|c| c.is_ascii_lowercase())
+        && region.is_none_or(|region| {
+            (2..=4).contains(&region.len())
+                && region.chars().all(|c| c.is_ascii_alphanumeric())
+        });
+    if valid {
+        Ok(target)
+    } else {
+        Err(AppError::BadRequest(format!(
+            "Invalid target language '{}'",
+            target
+        )))
+    }
+}
+
+async fn read_success<T: serde::de::DeserializeOwned>(
+    upstream: &Upstream,
+    name: &str,
+    response: reqwest::Response,
+) -> Result<T> {
+    if !response.status().is_success() {
+        return Err(AppError::ExternalApi(format!(
+            "{} returned status: {}",
+            name,
+            response.status()
+        )));
+    }
+    upstream.read_json(response).await
+}
+
+/// [LibreTranslate](https://libretranslate.com), self-hosted or
+/// public. The API key is optional on self-hosted instances.
+struct LibreTranslate {
+    upstream: Upstream,
+    base_url: String,
+    api_key: Option<String>,
+}
+
+#[derive(Serialize)]
+struct LibreTranslateRequest<'a> {
+    q: &'a str,
+    source: &'a str,
+    target: &'a str,
+    format: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    api_key: Option<&'a str>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LibreTranslateResponse {
+    translated_text: String,
+}
+
+#[async_trait]
+impl Translator for LibreTranslate {
+    async fn translate(
+        &self,
+        text: &str,
+        target: &str,
+    ) -> Result<String> {
+        let url = format!("{}/translate", self.base_url);
+        debug!(target, "Translating with LibreTranslate");
+        let response = self
+            .upstream
+            .send(self.upstream.post(&url).json(
+                &LibreTranslateRequest {
+                    q: text,
+                    source: "auto",
+                    target,
+                    format: "text",
+                    api_key: self.api_key.as_deref(),
+                },
+            ))
+            .await?;
+
+        let body: LibreTranslateResponse =
+            read_success(&self.upstream, "LibreTranslate", response)
+                .await?;
+        Ok(body.translated_text)
+    }
+}
+
+/// [DeepL](https://www.deepl.com/docs-api), on the free or pro API
+/// depending on the configured URL.
+struct DeepL {
+    upstream: Upstream,
+    base_url: String,
+    api_key: String,
+}
+
+#[derive(Serialize)]
+struct DeepLRequest<'a> {
+    text: [&'a str; 1],
+    target_lang: String,
+}
+
+#[derive(Deserialize)]
+struct DeepLResponse {
+    translations: Tolerant<DeepLTranslation>,
+}
+
+#[derive(Deserialize)]
+struct DeepLTranslation {
+    text: String,
+}
+
+#[async_trait]
+impl Translator for DeepL {
+    async fn translate(
+        &self,
+        text: &str,
+        target: &str,
+    ) -> Result<String> {
+        let url = format!("{}/v2/translate", self.base_url);
+        debug!(target, "Translating with DeepL");
+        let response = self
+            .upstream
+            .send(
+                self.upstream
+                    .post(&url)
+                    .header(
+                        reqwest::header::AUTHORIZATION,
+                        format!("DeepL-Auth-Key {}", self.api_key),
+                    )
+                    .json(&DeepLRequest {
+                        text: [text],
+                        target_lang: target.to_uppercase(),
+                    }),
+            )
+            .await?;
+
+        let body: DeepLResponse =
+            read_success(&self.upstream, "DeepL", response).await?;
+        for warning in body.translations.warnings("translations") {
+            warn!(%warning, "Malformed DeepL response");
+        }
+        body.translations
+            .items
+            .into_iter()
+            .next()
+            .map(|translation| translation.text)
+            .ok_or_else(|| {
+                AppError::UpstreamSchema(
+                    "DeepL returned no translation".to_string(),
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::Client;
+    use wiremock::matchers::{body_json, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn translator(
+        provider: Provider,
+        server: &MockServer,
+        api_key: Option<&str>,
+    ) -> Arc<dyn Translator> {
+        provider.build(
+            Upstream::new("MT", Client::new()),
+            server.uri(),
+            api_key.map(str::to_string),
+        )
+    }
+
+    #[test]
+    fn test_parse_target() {
+        assert_eq!(parse_target("IT").unwrap(), "it");
+        assert_eq!(parse_target("pt-BR").unwrap(), "pt-br");
+        assert!(parse_target("italian").is_err());
+        assert!(parse_target("it/../x").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_libretranslate() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/translate"))
+            .and(body_json(serde_json::json!({
+                "q": "It keeps its tail raised.",
+                "source": "auto",
+                "target": "it",
+                "format": "text"
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({"translatedText": "Tiene la coda alzata."}),
+            ))
+            .mount(&server)
+            .await;
+
+        let translated =
+            translator(Provider::LibreTranslate, &server, None)
+                .translate("It keeps its tail raised.", "it")
+                .await
+                .unwrap();
+        assert_eq!(translated, "Tiene la coda alzata.");
+    }
+
+    #[tokio::test]
+    async fn test_deepl() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v2/translate"))
+            .and(header("authorization", "DeepL-Auth-Key secret"))
+            .and(body_json(serde_json::json!({
+                "text": ["Hello"],
+                "target_lang": "DE"
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({"translations": [{"text": "Hallo"}]}),
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v2/translate"))
+            .and(header("authorization", "DeepL-Auth-Key wrong"))
+            .respond_with(ResponseTemplate::new(403))
+            .mount(&server)
+            .await;
+
+        let deepl =
+            translator(Provider::DeepL, &server, Some("secret"));
+        assert_eq!(
+            deepl.translate("Hello", "de").await.unwrap(),
+            "Hallo"
+        );
+
+        let wrong =
+            translator(Provider::DeepL, &server, Some("wrong"));
+        assert!(matches!(
+            wrong.translate("Hello", "de").await,
+            Err(AppError::ExternalApi(_))
+        ));
+    }
+}
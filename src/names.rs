@@ -0,0 +1,93 @@
+use crate::context;
+use fastbloom::BloomFilter;
+use std::sync::RwLock;
+
+/// Share of unknown names let through to PokeAPI anyway.
+const FALSE_POSITIVE_RATE: f64 = 0.001;
+
+/// Names suggested for a misspelled one, at most.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Jaro-Winkler similarity a known name needs to be suggested.
+const MIN_SIMILARITY: f64 = 0.8;
+
+/// The species names known to PokeAPI, kept in a bloom filter so
+/// names that certainly do not exist are rejected without an
+/// upstream call. A few kilobytes hold the thousand-odd names.
+#[derive(Default)]
+pub struct NameGuard {
+    filter: RwLock<Option<BloomFilter>>,
+}
+
+impl NameGuard {
+    /// Replaces the known names with `names`, in lowercase.
+    pub fn load<'a>(
+        &self,
+        names: impl ExactSizeIterator<Item = &'a str>,
+    ) {
+        let filter = BloomFilter::with_false_pos(FALSE_POSITIVE_RATE)
+            .items(names);
+        *self.filter.write().unwrap() = Some(filter);
+    }
+
+    /// Whether the species `name` may exist. Numeric ids always
+    /// may, and so does every name before the names are loaded or
+    /// when the request overrides the upstream.
+    pub fn may_exist(&self, name: &str) -> bool {
+        if name.parse::<u32>().is_ok() || context::bypass_cache() {
+            return true;
+        }
+        self.filter
+            .read()
+            .unwrap()
+            .as_ref()
+            .is_none_or(|filter| filter.contains(name))
+    }
+}
+
+/// The `known` names closest to the misspelled `name`, most similar
+/// first.
+pub fn suggest<'a>(
+    name: &str,
+    known: impl Iterator<Item = &'a str>,
+) -> Vec<String> {
+    let mut scored: Vec<(f64, &str)> = known
+        .map(|known| (strsim::jaro_winkler(name, known), known))
+        .filter(|(score, _)| *score >= MIN_SIMILARITY)
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    scored
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, known)| known.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rejects_unknown_names_once_loaded() {
+        let guard = NameGuard::default();
+        assert!(guard.may_exist("missingno"));
+
+        guard.load(["pikachu", "mewtwo"].into_iter());
+        assert!(guard.may_exist("pikachu"));
+        assert!(guard.may_exist("25"));
+        assert!(!guard.may_exist("missingno"));
+
+        // Newly added species are known after the next load.
+        guard.load(["pikachu", "mewtwo", "missingno"].into_iter());
+        assert!(guard.may_exist("missingno"));
+    }
+
+    #[test]
+    fn test_suggests_closest_names() {
+        let known = ["pikachu", "raichu", "pichu", "mewtwo"];
+        let suggestions = suggest("pikachuu", known.into_iter());
+        assert_eq!(suggestions[0], "pikachu");
+        assert!(!suggestions.contains(&"mewtwo".to_string()));
+        assert!(suggest("zzz", known.into_iter()).is_empty());
+    }
+}
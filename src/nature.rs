@@ -0,0 +1,204 @@
+use crate::cache::{Cache, ManagedCache};
+use crate::error::Result;
+use crate::pokeapi::{
+    NamedApiResource, NamedApiResourceList, PokeApiClient,
+};
+use futures::future::try_join_all;
+use serde::{Deserialize, Serialize};
+use std::{sync::Arc, time::Duration};
+use tracing::instrument;
+use utoipa::ToSchema;
+
+const NATURE_LIST_KEY: &str = "natures";
+
+/// A nature and the stats it raises and lowers by 10%.
+#[derive(
+    Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema,
+)]
+pub struct Nature {
+    pub id: u32,
+    pub name: String,
+    /// `None` for the neutral natures, which change no stat.
+    pub increased_stat: Option<String>,
+    pub decreased_stat: Option<String>,
+    /// The flavor of berries a Pokemon of this nature likes.
+    pub likes_flavor: Option<String>,
+    pub hates_flavor: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PokeApiNature {
+    id: u32,
+    name: String,
+    increased_stat: Option<NamedApiResource>,
+    decreased_stat: Option<NamedApiResource>,
+    likes_flavor: Option<NamedApiResource>,
+    hates_flavor: Option<NamedApiResource>,
+}
+
+pub struct NatureService {
+    pokeapi: PokeApiClient,
+    list_cache: Cache<String, Arc<Vec<Nature>>>,
+    nature_cache: Cache<String, Arc<Nature>>,
+}
+
+impl NatureService {
+    pub fn new(pokeapi: PokeApiClient, cache_ttl: Duration) -> Self {
+        Self {
+            pokeapi,
+            list_cache: Cache::new(cache_ttl),
+            nature_cache: Cache::new(cache_ttl),
+        }
+    }
+
+    /// The caches owned by this service, by name.
+    pub fn caches(&self) -> Vec<(&'static str, &dyn ManagedCache)> {
+        vec![
+            ("nature.list", &self.list_cache),
+            ("nature.natures", &self.nature_cache),
+        ]
+    }
+
+    /// Returns every nature known to PokeAPI, sorted by id.
+    pub async fn list_natures(&self) -> Result<Arc<Vec<Nature>>> {
+        if let Some(natures) =
+            self.list_cache.get(&NATURE_LIST_KEY.to_string())
+        {
+            return Ok(natures);
+        }
+
+        // There are 25 natures; one page holds them all.
+        let list: NamedApiResourceList = self
+            .pokeapi
+            .get("nature?limit=100", || {
+                "Nature list not found".to_string()
+            })
+            .await?;
+        let natures = try_join_all(
+            list.results
+                .iter()
+                .map(|resource| self.nature(&resource.name)),
+        )
+        .await?;
+
+        let mut natures: Vec<Nature> = natures
+            .into_iter()
+            .map(|nature| nature.as_ref().clone())
+            .collect();
+        natures.sort_by_key(|nature| nature.id);
+
+        let natures = Arc::new(natures);
+        self.list_cache
+            .insert(NATURE_LIST_KEY.to_string(), natures.clone());
+        Ok(natures)
+    }
+
+    #[instrument(skip(self), fields(nature = %name))]
+    pub async fn nature(&self, name: &str) -> Result<Arc<Nature>> {
+        let name = name.to_lowercase();
+        if let Some(nature) = self.nature_cache.get(&name) {
+            return Ok(nature);
+        }
+
+        let nature: PokeApiNature = self
+            .pokeapi
+            .get(&format!("nature/{}", name), || {
+                format!("Nature '{}' not found", name)
+            })
+            .await?;
+
+        let nature = Arc::new(map_to_nature(nature));
+        self.nature_cache.insert(name, nature.clone());
+        Ok(nature)
+    }
+}
+
+fn map_to_nature(nature: PokeApiNature) -> Nature {
+    let name = |resource: Option<NamedApiResource>| {
+        resource.map(|resource| resource.name)
+    };
+    Nature {
+        id: nature.id,
+        name: nature.name,
+        increased_stat: name(nature.increased_stat),
+        decreased_stat: name(nature.decreased_stat),
+        likes_flavor: name(nature.likes_flavor),
+        hates_flavor: name(nature.hates_flavor),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_natures_are_sorted_and_cached() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/nature"))
+            .and(query_param("limit", "100"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({
+                    "results": [
+                        {"name": "adamant", "url": "http://x/nature/3/"},
+                        {"name": "hardy", "url": "http://x/nature/1/"}
+                    ]
+                }),
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/nature/adamant"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({
+                    "id": 3,
+                    "name": "adamant",
+                    "increased_stat": {"name": "attack", "url": "u"},
+                    "decreased_stat": {"name": "special-attack", "url": "u"},
+                    "likes_flavor": {"name": "spicy", "url": "u"},
+                    "hates_flavor": {"name": "dry", "url": "u"}
+                }),
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/nature/hardy"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({
+                    "id": 1,
+                    "name": "hardy",
+                    "increased_stat": null,
+                    "decreased_stat": null,
+                    "likes_flavor": null,
+                    "hates_flavor": null
+                }),
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let service = NatureService::new(
+            PokeApiClient::new(
+                http::build_client(&http::ClientSettings::new(
+                    Duration::from_secs(5),
+                )),
+                server.uri(),
+            ),
+            Duration::from_secs(60),
+        );
+        let natures = service.list_natures().await.unwrap();
+        let names: Vec<_> =
+            natures.iter().map(|n| n.name.as_str()).collect();
+        assert_eq!(names, vec!["hardy", "adamant"]);
+        assert_eq!(natures[0].increased_stat, None);
+
+        let adamant = service.nature("Adamant").await.unwrap();
+        assert_eq!(adamant.increased_stat.as_deref(), Some("attack"));
+        service.list_natures().await.unwrap();
+    }
+}
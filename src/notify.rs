@@ -0,0 +1,184 @@
+#[cfg(unix)]
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::{io, sync::Arc, time::Duration};
+use tracing::{debug, info, warn};
+
+/// Sends service state changes to systemd (`sd_notify`), for units
+/// with `Type=notify`. Off Unix, where there is no systemd, it
+/// never connects.
+pub struct Notifier {
+    #[cfg(unix)]
+    socket: UnixDatagram,
+    #[cfg(unix)]
+    addr: SocketAddr,
+}
+
+impl Notifier {
+    /// Connects to the socket named by `NOTIFY_SOCKET`, set when
+    /// systemd expects notifications; `None` otherwise.
+    pub fn from_env() -> Option<Self> {
+        let path = std::env::var("NOTIFY_SOCKET").ok()?;
+        match Self::connect(&path) {
+            Ok(notifier) => Some(notifier),
+            Err(e) => {
+                warn!(%path, error = %e, "Failed to open NOTIFY_SOCKET");
+                None
+            }
+        }
+    }
+
+    /// `path` is a socket file or, starting with `@`, a Linux
+    /// abstract socket.
+    #[cfg(unix)]
+    pub fn connect(path: &str) -> io::Result<Self> {
+        let addr = match path.strip_prefix('@') {
+            Some(name) => abstract_addr(name)?,
+            None => SocketAddr::from_pathname(path)?,
+        };
+        Ok(Self {
+            socket: UnixDatagram::unbound()?,
+            addr,
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn connect(_path: &str) -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "systemd notifications are only supported on Unix",
+        ))
+    }
+
+    /// Sends `state`, newline-separated `KEY=VALUE` assignments.
+    #[cfg(unix)]
+    pub fn notify(&self, state: &str) -> io::Result<()> {
+        self.socket.send_to_addr(state.as_bytes(), &self.addr)?;
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    pub fn notify(&self, _state: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// The listeners are bound and the caches warm.
+    pub fn ready(&self, status: &str) {
+        self.send(&format!("READY=1\nSTATUS={}", status));
+    }
+
+    pub fn stopping(&self) {
+        self.send("STOPPING=1\nSTATUS=Shutting down");
+    }
+
+    /// Pings the watchdog every half of `interval` for as long as
+    /// the runtime is responsive.
+    pub fn spawn_watchdog(self: &Arc<Self>, interval: Duration) {
+        info!(
+            interval_ms = interval.as_millis() as u64,
+            "Pinging the systemd watchdog"
+        );
+        let notifier = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval / 2);
+            loop {
+                ticker.tick().await;
+                notifier.send("WATCHDOG=1");
+            }
+        });
+    }
+
+    /// Notifications are best effort: systemd acts on the missing
+    /// ones itself.
+    fn send(&self, state: &str) {
+        match self.notify(state) {
+            Ok(()) => debug!(%state, "Notified systemd"),
+            Err(e) => {
+                warn!(%state, error = %e, "Failed to notify systemd")
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn abstract_addr(name: &str) -> io::Result<SocketAddr> {
+    use std::os::linux::net::SocketAddrExt;
+    SocketAddr::from_abstract_name(name)
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn abstract_addr(_name: &str) -> io::Result<SocketAddr> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "abstract sockets are only supported on Linux",
+    ))
+}
+
+/// The watchdog timeout systemd set for this process with
+/// `WatchdogSec=`, from `WATCHDOG_USEC` and `WATCHDOG_PID`.
+pub fn watchdog_interval() -> Option<Duration> {
+    parse_watchdog(
+        std::env::var("WATCHDOG_USEC").ok().as_deref(),
+        std::env::var("WATCHDOG_PID").ok().as_deref(),
+        std::process::id(),
+    )
+}
+
+fn parse_watchdog(
+    usec: Option<&str>,
+    pid: Option<&str>,
+    own_pid: u32,
+) -> Option<Duration> {
+    // Without `WATCHDOG_PID` the timeout is meant for this process.
+    if let Some(pid) = pid
+        && pid.parse::<u32>().ok() != Some(own_pid)
+    {
+        return None;
+    }
+    usec?
+        .parse::<u64>()
+        .ok()
+        .filter(|usec| *usec > 0)
+        .map(Duration::from_micros)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_notify_sends_datagrams() {
+        let path = std::env::temp_dir().join(format!(
+            "pokedex-test-{}.notify",
+            std::process::id()
+        ));
+        std::fs::remove_file(&path).ok();
+        let systemd = UnixDatagram::bind(&path).unwrap();
+
+        let notifier =
+            Notifier::connect(path.to_str().unwrap()).unwrap();
+        notifier.ready("Listening");
+        let mut buffer = [0; 64];
+        let len = systemd.recv(&mut buffer).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(&buffer[..len], b"READY=1\nSTATUS=Listening");
+    }
+
+    #[test]
+    fn test_parse_watchdog() {
+        assert_eq!(
+            parse_watchdog(Some("30000000"), Some("42"), 42),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_watchdog(Some("30000000"), None, 42),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_watchdog(Some("30000000"), Some("7"), 42),
+            None
+        );
+        assert_eq!(parse_watchdog(Some("0"), None, 42), None);
+        assert_eq!(parse_watchdog(None, None, 42), None);
+    }
+}
@@ -0,0 +1,259 @@
+//! The offline rules-based translator, approximating the
+//! funtranslations styles without calling out, and the word diff
+//! comparing its output with theirs.
+
+use crate::translation::Style;
+use serde::Serialize;
+use std::mem;
+
+/// Auxiliaries ending the subject of a sentence the Yoda way, as in
+/// "Strong with the Force, you are".
+const AUXILIARIES: &[&str] = &[
+    "is", "are", "was", "were", "can", "could", "will", "would",
+    "shall", "should", "must", "may", "might", "has", "have", "had",
+    "does", "do", "did",
+];
+
+/// Words swapped for their Early Modern English form.
+const ARCHAISMS: &[(&str, &str)] = &[
+    ("you", "thee"),
+    ("your", "thy"),
+    ("yours", "thine"),
+    ("are", "art"),
+    ("has", "hath"),
+    ("does", "doth"),
+    ("could", "couldst"),
+    ("will", "wilt"),
+    ("when", "at which hour"),
+    ("before", "ere"),
+    ("often", "oft"),
+    ("yes", "aye"),
+];
+
+/// Translates `text` in `style` by rules alone.
+pub fn translate(text: &str, style: Style) -> String {
+    match style {
+        Style::Yoda => {
+            sentences(text).map(yoda).collect::<Vec<_>>().join(" ")
+        }
+        Style::Shakespeare => text
+            .split_whitespace()
+            .map(archaic)
+            .collect::<Vec<_>>()
+            .join(" "),
+    }
+}
+
+/// The sentences of `text`, each with its closing punctuation.
+fn sentences(text: &str) -> impl Iterator<Item = &str> {
+    text.split_inclusive(['.', '!', '?'])
+        .map(str::trim)
+        .filter(|sentence| !sentence.is_empty())
+}
+
+/// Moves what follows the first auxiliary of the main clause to the
+/// front: "Their electricity could build storms." becomes "Build
+/// storms, their electricity could." A leading clause, up to the
+/// last comma, moves to the end. Sentences without an auxiliary are
+/// kept.
+fn yoda(sentence: &str) -> String {
+    let body = sentence.trim_end_matches(['.', '!', '?']);
+    let end = &sentence[body.len()..];
+    let (lead, main) = match body.rsplit_once(", ") {
+        Some((lead, main)) => (Some(lead), main),
+        None => (None, body),
+    };
+    let words: Vec<&str> = main.split_whitespace().collect();
+    let Some(aux) = words
+        .iter()
+        .skip(1)
+        .position(|word| {
+            AUXILIARIES.contains(&word.to_lowercase().as_str())
+        })
+        .map(|i| i + 1)
+        .filter(|&aux| aux + 1 < words.len())
+    else {
+        return sentence.to_string();
+    };
+
+    let mut subject = words[..=aux].join(" ");
+    if lead.is_none() {
+        subject = lowercase_first(&subject);
+    }
+    let mut yoda = format!(
+        "{}, {}",
+        uppercase_first(&words[aux + 1..].join(" ")),
+        subject
+    );
+    if let Some(lead) = lead {
+        yoda = format!("{}, {}", yoda, lowercase_first(lead));
+    }
+    yoda + end
+}
+
+/// `word` in its archaic form, keeping its surrounding punctuation
+/// and initial capital.
+fn archaic(word: &str) -> String {
+    let punctuation = |c: char| !c.is_alphanumeric();
+    let start =
+        word.len() - word.trim_start_matches(punctuation).len();
+    let end = word.trim_end_matches(punctuation).len().max(start);
+    let bare = &word[start..end];
+    let lower = bare.to_lowercase();
+    let Some((_, archaic)) =
+        ARCHAISMS.iter().find(|(modern, _)| *modern == lower)
+    else {
+        return word.to_string();
+    };
+    let archaic = match bare.starts_with(char::is_uppercase) {
+        true => uppercase_first(archaic),
+        false => archaic.to_string(),
+    };
+    format!("{}{}{}", &word[..start], archaic, &word[end..])
+}
+
+fn uppercase_first(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn lowercase_first(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// A run of words of a diff: in both texts, only in the first or
+/// only in the second.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "op", content = "text", rename_all = "lowercase")]
+pub enum WordChange {
+    Equal(String),
+    Delete(String),
+    Insert(String),
+}
+
+/// The word-level changes turning `old` into `new`, along their
+/// longest common subsequence of words.
+pub fn diff_words(old: &str, new: &str) -> Vec<WordChange> {
+    let old: Vec<&str> = old.split_whitespace().collect();
+    let new: Vec<&str> = new.split_whitespace().collect();
+
+    // `common[i][j]` is the length of the longest common
+    // subsequence of `old[i..]` and `new[j..]`.
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = match old[i] == new[j] {
+                true => common[i + 1][j + 1] + 1,
+                false => common[i + 1][j].max(common[i][j + 1]),
+            };
+        }
+    }
+
+    let mut changes = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            push(&mut changes, WordChange::Equal, old[i]);
+            i += 1;
+            j += 1;
+        } else if j == new.len()
+            || (i < old.len() && common[i + 1][j] >= common[i][j + 1])
+        {
+            push(&mut changes, WordChange::Delete, old[i]);
+            i += 1;
+        } else {
+            push(&mut changes, WordChange::Insert, new[j]);
+            j += 1;
+        }
+    }
+    changes
+}
+
+/// Appends `word` to the last run if it is of the same kind as
+/// `change`, or starts a new run.
+fn push(
+    changes: &mut Vec<WordChange>,
+    change: fn(String) -> WordChange,
+    word: &str,
+) {
+    let next = change(word.to_string());
+    match changes.last_mut() {
+        Some(last)
+            if mem::discriminant(last)
+                == mem::discriminant(&next) =>
+        {
+            let (WordChange::Equal(run)
+            | WordChange::Delete(run)
+            | WordChange::Insert(run)) = last;
+            run.push(' ');
+            run.push_str(word);
+        }
+        _ => changes.push(next),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_yoda_moves_the_predicate_first() {
+        assert_eq!(
+            translate(
+                "Their electricity could build storms. It sleeps.",
+                Style::Yoda
+            ),
+            "Build storms, their electricity could. It sleeps."
+        );
+        assert_eq!(
+            translate(
+                "When several gather, their electricity could build and cause lightning storms.",
+                Style::Yoda
+            ),
+            "Build and cause lightning storms, their electricity could, when several gather."
+        );
+    }
+
+    #[test]
+    fn test_shakespeare_swaps_archaisms() {
+        assert_eq!(
+            translate(
+                "When you are near, it has sparks (yes).",
+                Style::Shakespeare
+            ),
+            "At which hour thee art near, it hath sparks (aye)."
+        );
+    }
+
+    #[test]
+    fn test_diff_words() {
+        let changes = diff_words(
+            "their electricity couldst buildeth storms",
+            "their electricity couldst build and cause storms",
+        );
+        assert_eq!(
+            changes,
+            [
+                WordChange::Equal(
+                    "their electricity couldst".to_string()
+                ),
+                WordChange::Delete("buildeth".to_string()),
+                WordChange::Insert("build and cause".to_string()),
+                WordChange::Equal("storms".to_string()),
+            ]
+        );
+        assert_eq!(
+            serde_json::to_value(&changes[1]).unwrap(),
+            json!({"op": "delete", "text": "buildeth"})
+        );
+        assert!(diff_words("", "").is_empty());
+    }
+}
@@ -0,0 +1,186 @@
+//! The OpenAPI document of the API, generated from the handlers and
+//! the models they answer with, and served at `/openapi.json`.
+
+use crate::auth::API_KEY_HEADER;
+use crate::error::ErrorResponse;
+use crate::forwarded::PublicUrl;
+use axum::Json;
+use pokedex_rs::models::{Pokemon, PokemonDetails};
+use std::sync::LazyLock;
+use utoipa::openapi::response::ResponseBuilder;
+use utoipa::openapi::schema::{AnyOfBuilder, Schema};
+use utoipa::openapi::security::{
+    ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme,
+};
+use utoipa::openapi::server::Server;
+use utoipa::openapi::{ContentBuilder, Ref, RefOr};
+use utoipa::{Modify, OpenApi, PartialSchema, ToSchema};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Pokedex API",
+        description = "Pokemon with their descriptions translated in a fun style. Every route is also served under `/v1` and `/v2`."
+    ),
+    paths(
+        crate::list_pokemon,
+        crate::search_pokemon,
+        crate::list_legendary_pokemon,
+        crate::list_mythical_pokemon,
+        crate::get_pokemon,
+        crate::query_pokemon,
+        crate::get_translated_pokemon,
+        crate::get_pokemon_details,
+        crate::get_pokemon_audio,
+        crate::get_pokemon_cry,
+        crate::get_pokemon_entries,
+        crate::get_pokemon_forms,
+        crate::get_pokemon_stat_history,
+        crate::get_pokemon_encounters,
+        crate::get_breeding_compatibility,
+        crate::get_pokemon_batch,
+        crate::translate_text,
+        crate::analyze_team,
+        crate::start_quiz,
+        crate::guess_quiz,
+        crate::submit_job,
+        crate::get_job,
+        crate::cancel_job,
+        crate::get_job_result,
+        crate::list_favorites,
+        crate::add_favorite,
+        crate::remove_favorite,
+        crate::get_item,
+        crate::get_berry,
+        crate::get_move,
+        crate::list_natures,
+        crate::get_nature,
+        crate::list_habitats,
+        crate::get_habitat_pokemon,
+        crate::proxy_pokeapi,
+    ),
+    components(schemas(ErrorResponse)),
+    modifiers(&Errors, &Credentials)
+)]
+pub struct ApiDoc;
+
+/// The document, built once.
+static DOCUMENT: LazyLock<utoipa::openapi::OpenApi> =
+    LazyLock::new(ApiDoc::openapi);
+
+/// The document, with the public URL of the request, as set by
+/// `PUBLIC_BASE_URL` or the trusted `X-Forwarded-*` headers, as its
+/// server.
+pub async fn document(
+    public_url: PublicUrl,
+) -> Json<utoipa::openapi::OpenApi> {
+    let mut document = DOCUMENT.clone();
+    document.servers = Some(vec![Server::new(public_url.as_str())]);
+    Json(document)
+}
+
+/// The `Pokemon` model on v1 and the details on v2, as the routes
+/// built on `/pokemon/{name}` answer.
+pub struct VersionedPokemon;
+
+impl PartialSchema for VersionedPokemon {
+    fn schema() -> RefOr<Schema> {
+        AnyOfBuilder::new()
+            .item(Ref::from_schema_name(Pokemon::name()))
+            .item(Ref::from_schema_name(PokemonDetails::name()))
+            .into()
+    }
+}
+
+impl ToSchema for VersionedPokemon {
+    fn schemas(schemas: &mut Vec<(String, RefOr<Schema>)>) {
+        schemas.push((Pokemon::name().into(), Pokemon::schema()));
+        Pokemon::schemas(schemas);
+        schemas.push((
+            PokemonDetails::name().into(),
+            PokemonDetails::schema(),
+        ));
+        PokemonDetails::schemas(schemas);
+    }
+}
+
+/// Documents the JSON error every route may answer with.
+struct Errors;
+
+impl Modify for Errors {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let error = ResponseBuilder::new()
+            .description(
+                "The error, with the invalid fields of a `422`",
+            )
+            .content(
+                "application/json",
+                ContentBuilder::new()
+                    .schema(Some(Ref::from_schema_name(
+                        ErrorResponse::name(),
+                    )))
+                    .build(),
+            )
+            .build();
+        for item in openapi.paths.paths.values_mut() {
+            let operations = [
+                &mut item.get,
+                &mut item.put,
+                &mut item.post,
+                &mut item.delete,
+            ];
+            for operation in operations.into_iter().flatten() {
+                operation.responses.responses.insert(
+                    "default".to_string(),
+                    error.clone().into(),
+                );
+            }
+        }
+    }
+}
+
+/// The API keys of `API_KEYS` and the JWTs of `JWT_JWKS_URL`.
+struct Credentials;
+
+impl Modify for Credentials {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_default();
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new(
+                API_KEY_HEADER,
+            ))),
+        );
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .build(),
+            ),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_servers_follow_the_public_url() {
+        let Json(document) = document(PublicUrl(
+            "https://example.com/pokedex".to_string(),
+        ))
+        .await;
+        let servers = document.servers.unwrap();
+        assert_eq!(servers.len(), 1);
+        assert_eq!(servers[0].url, "https://example.com/pokedex");
+        assert!(
+            document
+                .paths
+                .paths
+                .contains_key("/proxy/pokeapi/{path}")
+        );
+    }
+}
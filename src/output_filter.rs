@@ -0,0 +1,121 @@
+use crate::text;
+use regex::{Regex, RegexBuilder};
+use std::{borrow::Cow, sync::LazyLock};
+use tracing::debug;
+
+/// Post-processing of text from a third-party API before it is
+/// served.
+pub trait OutputFilter: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// `text`, borrowed when the filter leaves it untouched.
+    fn apply<'a>(&self, text: &'a str) -> Cow<'a, str>;
+}
+
+/// Masks every match of a case-insensitive pattern with asterisks.
+#[derive(Debug, Clone)]
+pub struct Denylist {
+    pattern: Regex,
+}
+
+impl Denylist {
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        let pattern = RegexBuilder::new(pattern)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| e.to_string())?;
+        Ok(Self { pattern })
+    }
+}
+
+impl OutputFilter for Denylist {
+    fn name(&self) -> &'static str {
+        "denylist"
+    }
+
+    fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        self.pattern.replace_all(
+            text,
+            |captures: &regex::Captures| {
+                "*".repeat(captures[0].chars().count())
+            },
+        )
+    }
+}
+
+static URL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b(?:https?://|www\.)[^\s]+").unwrap()
+});
+
+/// Removes links, tidying the whitespace left behind.
+pub struct UrlStripper;
+
+impl OutputFilter for UrlStripper {
+    fn name(&self) -> &'static str {
+        "urls"
+    }
+
+    fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        match URL.replace_all(text, "") {
+            Cow::Borrowed(text) => Cow::Borrowed(text),
+            Cow::Owned(stripped) => {
+                text::clean_description(&stripped).into_owned().into()
+            }
+        }
+    }
+}
+
+/// Filters run in order on every translation. Empty, and so a no-op,
+/// unless configured.
+#[derive(Default)]
+pub struct FilterChain {
+    filters: Vec<Box<dyn OutputFilter>>,
+}
+
+impl FilterChain {
+    pub fn push(&mut self, filter: impl OutputFilter + 'static) {
+        self.filters.push(Box::new(filter));
+    }
+
+    pub fn apply(&self, mut text: String) -> String {
+        for filter in &self.filters {
+            if let Cow::Owned(filtered) = filter.apply(&text) {
+                debug!(
+                    filter = filter.name(),
+                    "Filtered translated text"
+                );
+                text = filtered;
+            }
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chain_strips_urls_and_masks_denied_words() {
+        let mut chain = FilterChain::default();
+        chain.push(UrlStripper);
+        chain.push(Denylist::new(r"\bdarn\b").unwrap());
+        assert_eq!(
+            chain.apply(
+                "Darn, visit https://spam.example/x now, darnit."
+                    .to_string()
+            ),
+            "****, visit now, darnit."
+        );
+        assert_eq!(
+            FilterChain::default()
+                .apply("www.example.com".to_string()),
+            "www.example.com"
+        );
+    }
+
+    #[test]
+    fn test_invalid_denylist() {
+        assert!(Denylist::new("(unclosed").is_err());
+    }
+}
@@ -0,0 +1,640 @@
+use crate::cache_store::{CacheStore, unix_millis};
+use crate::config::Secret;
+use crate::error::{AppError, Result};
+use crate::http::read_limited;
+use axum::async_trait;
+use axum::{
+    Router,
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex, RwLock},
+    time::Duration,
+};
+use subtle::ConstantTimeEq;
+use tracing::{info, warn};
+
+/// Carries `PEER_SECRET` on the requests between replicas.
+pub const PEER_HEADER: &str = "x-pokedex-peer";
+
+/// The TTL of an entry forwarded to its owner, in milliseconds.
+const TTL_HEADER: &str = "x-pokedex-ttl-ms";
+
+/// Points of each replica on the ring, so that the keys spread
+/// evenly and a replica leaving only moves its own keys.
+const VIRTUAL_NODES: usize = 100;
+
+/// Expired entries of the shard are dropped every this many writes.
+const SWEEP_EVERY: usize = 256;
+
+/// Where the replicas are listed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Discovery {
+    /// Base URLs, from `PEERS`.
+    Static(Vec<String>),
+    /// Every address `host` resolves to, e.g. a Kubernetes headless
+    /// service, from `PEERS_DNS`.
+    Dns { host: String, port: u16 },
+}
+
+impl Discovery {
+    /// Parses a `host:port` for `PEERS_DNS`.
+    pub fn dns(value: &str) -> std::result::Result<Self, String> {
+        let (host, port) = value
+            .trim()
+            .rsplit_once(':')
+            .filter(|(host, _)| !host.is_empty())
+            .ok_or_else(|| format!("'{}' is not host:port", value))?;
+        let port = port
+            .parse()
+            .map_err(|_| format!("invalid port in '{}'", value))?;
+        Ok(Discovery::Dns {
+            host: host.to_string(),
+            port,
+        })
+    }
+}
+
+/// The `peers` second-level store of `CACHE_L2`.
+#[derive(Debug, Clone)]
+pub struct PeerSettings {
+    pub discovery: Discovery,
+    /// Base URL of this replica as the others reach it, from
+    /// `PEER_SELF`.
+    pub self_url: String,
+    pub secret: Secret,
+    /// How often `PEERS_DNS` is resolved again.
+    pub refresh: Duration,
+    pub timeout: Duration,
+    /// Bytes of entries this replica keeps for the others, past
+    /// which the entries closest to expiry are evicted.
+    pub max_bytes: usize,
+}
+
+/// A consistent hash ring of the replicas' base URLs.
+#[derive(Debug, Default)]
+pub struct Ring {
+    nodes: BTreeMap<u64, usize>,
+    peers: Vec<String>,
+}
+
+impl Ring {
+    pub fn new(mut peers: Vec<String>) -> Self {
+        for peer in &mut peers {
+            *peer = normalize(peer);
+        }
+        peers.sort();
+        peers.dedup();
+        let mut nodes = BTreeMap::new();
+        for (index, peer) in peers.iter().enumerate() {
+            for node in 0..VIRTUAL_NODES {
+                nodes.insert(
+                    hash(&format!("{}#{}", peer, node)),
+                    index,
+                );
+            }
+        }
+        Self { nodes, peers }
+    }
+
+    /// The replica owning `key`: the first point at or after its
+    /// hash, wrapping around.
+    pub fn owner(&self, key: &str) -> Option<&str> {
+        let (_, index) = self
+            .nodes
+            .range(hash(key)..)
+            .next()
+            .or_else(|| self.nodes.iter().next())?;
+        Some(&self.peers[*index])
+    }
+
+    pub fn peers(&self) -> &[String] {
+        &self.peers
+    }
+}
+
+fn hash(value: &str) -> u64 {
+    let digest = Sha256::digest(value.as_bytes());
+    let mut prefix = [0; 8];
+    prefix.copy_from_slice(&digest[..8]);
+    u64::from_be_bytes(prefix)
+}
+
+fn normalize(url: &str) -> String {
+    url.trim().trim_end_matches('/').to_string()
+}
+
+/// Partitions the cache entries across the replicas: each replica
+/// keeps in memory the entries it owns on the ring, and looks up and
+/// writes the others at their owner. Together the replicas cache
+/// every entry once, as a shared store would, without one.
+pub struct PeerStore {
+    self_url: String,
+    secret: Secret,
+    client: reqwest::Client,
+    ring: RwLock<Arc<Ring>>,
+    shard: Mutex<Shard>,
+    max_bytes: usize,
+}
+
+/// The owned entries, with their expiry in Unix milliseconds, and
+/// the bytes of their values.
+#[derive(Default)]
+struct Shard {
+    entries: HashMap<String, (u64, Vec<u8>)>,
+    bytes: usize,
+}
+
+impl Shard {
+    fn remove(&mut self, key: &str) {
+        if let Some((_, value)) = self.entries.remove(key) {
+            self.bytes -= value.len();
+        }
+    }
+
+    fn retain(&mut self, keep: impl Fn(&str, u64) -> bool) {
+        let mut bytes = 0;
+        self.entries.retain(|key, (expires_at, value)| {
+            let kept = keep(key, *expires_at);
+            if kept {
+                bytes += value.len();
+            }
+            kept
+        });
+        self.bytes = bytes;
+    }
+
+    /// Evicts the entries closest to expiry until `needed` more
+    /// bytes fit in `max_bytes`.
+    fn make_room(&mut self, needed: usize, max_bytes: usize) {
+        while self.bytes + needed > max_bytes {
+            let Some(key) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (expires_at, _))| *expires_at)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            self.remove(&key);
+        }
+    }
+}
+
+impl PeerStore {
+    pub fn new(settings: &PeerSettings) -> Result<Self> {
+        let peers = match &settings.discovery {
+            Discovery::Static(peers) => peers.clone(),
+            Discovery::Dns { .. } => Vec::new(),
+        };
+        let client = reqwest::Client::builder()
+            .no_proxy()
+            .timeout(settings.timeout)
+            .build()
+            .map_err(|e| {
+                AppError::Internal(format!(
+                    "Failed to create the peer client: {}",
+                    e
+                ))
+            })?;
+        Ok(Self {
+            self_url: normalize(&settings.self_url),
+            secret: settings.secret.clone(),
+            client,
+            ring: RwLock::new(Arc::new(Ring::new(peers))),
+            shard: Mutex::new(Shard::default()),
+            max_bytes: settings.max_bytes,
+        })
+    }
+
+    /// Creates the store and, with `PEERS_DNS`, keeps its ring in
+    /// line with the resolved addresses. Until they first resolve,
+    /// every entry is kept locally.
+    pub fn start(settings: &PeerSettings) -> Result<Arc<Self>> {
+        let store = Arc::new(Self::new(settings)?);
+        info!(
+            peers = store.ring().peers().len(),
+            self_url = %store.self_url,
+            "Partitioning the caches across the peers"
+        );
+        if let Discovery::Dns { host, port } = &settings.discovery {
+            store.clone().spawn_discovery(
+                host.clone(),
+                *port,
+                settings.refresh,
+            );
+        }
+        Ok(store)
+    }
+
+    fn spawn_discovery(
+        self: Arc<Self>,
+        host: String,
+        port: u16,
+        refresh: Duration,
+    ) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(refresh);
+            loop {
+                ticker.tick().await;
+                match tokio::net::lookup_host((host.as_str(), port))
+                    .await
+                {
+                    Ok(addrs) => self.set_peers(
+                        addrs
+                            .map(|addr| format!("http://{}", addr))
+                            .collect(),
+                    ),
+                    Err(e) => {
+                        warn!(%host, error = %e, "Failed to resolve the peers")
+                    }
+                }
+            }
+        });
+    }
+
+    /// Replaces the ring, when the peers changed.
+    pub fn set_peers(&self, peers: Vec<String>) {
+        let ring = Ring::new(peers);
+        if ring.peers() == self.ring().peers() {
+            return;
+        }
+        info!(peers = ?ring.peers(), "Peers changed");
+        *self.ring.write().unwrap() = Arc::new(ring);
+    }
+
+    fn ring(&self) -> Arc<Ring> {
+        self.ring.read().unwrap().clone()
+    }
+
+    /// The owner of `key` when it is another replica.
+    fn remote_owner(&self, key: &str) -> Option<String> {
+        let ring = self.ring();
+        ring.owner(key)
+            .filter(|owner| *owner != self.self_url)
+            .map(str::to_string)
+    }
+
+    /// The URL of `segments` under the base URL of `peer`.
+    fn url(
+        &self,
+        peer: &str,
+        segments: &[&str],
+    ) -> Result<reqwest::Url> {
+        let mut url =
+            reqwest::Url::parse(peer).map_err(peer_error)?;
+        url.path_segments_mut()
+            .map_err(|_| {
+                peer_error(format!("{} is not a base", peer))
+            })?
+            .pop_if_empty()
+            .extend(segments);
+        Ok(url)
+    }
+
+    fn get_local(&self, key: &str) -> Option<Vec<u8>> {
+        let mut shard = self.shard.lock().unwrap();
+        let (expires_at, value) = shard.entries.get(key)?;
+        if *expires_at <= unix_millis() {
+            shard.remove(key);
+            return None;
+        }
+        Some(value.clone())
+    }
+
+    /// Keeps `value` for `ttl`, unless it is larger than the whole
+    /// shard.
+    fn set_local(&self, key: &str, value: Vec<u8>, ttl: Duration) {
+        let now = unix_millis();
+        let mut shard = self.shard.lock().unwrap();
+        shard.remove(key);
+        if value.len() > self.max_bytes {
+            return;
+        }
+        if shard.entries.len().is_multiple_of(SWEEP_EVERY)
+            || shard.bytes + value.len() > self.max_bytes
+        {
+            shard.retain(|_, expires_at| expires_at > now);
+        }
+        shard.make_room(value.len(), self.max_bytes);
+        shard.bytes += value.len();
+        shard.entries.insert(
+            key.to_string(),
+            (now + ttl.as_millis() as u64, value),
+        );
+    }
+
+    fn clear_local(&self, prefix: &str) {
+        self.shard
+            .lock()
+            .unwrap()
+            .retain(|key, _| !key.starts_with(prefix));
+    }
+
+    /// Whether `headers` carry the shared secret, compared in
+    /// constant time.
+    fn authorize(&self, headers: &HeaderMap) -> Result<()> {
+        let secret = headers
+            .get(PEER_HEADER)
+            .map(|value| value.as_bytes())
+            .unwrap_or_default();
+        if !bool::from(secret.ct_eq(self.secret.0.as_bytes())) {
+            return Err(AppError::Unauthorized(
+                "Invalid peer secret".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn peer_error(e: impl std::fmt::Display) -> AppError {
+    AppError::Internal(format!("Peer cache error: {}", e))
+}
+
+#[async_trait]
+impl CacheStore for PeerStore {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let Some(owner) = self.remote_owner(key) else {
+            return Ok(self.get_local(key));
+        };
+        let response = self
+            .client
+            .get(self.url(&owner, &["internal", "cache", key])?)
+            .header(PEER_HEADER, &self.secret.0)
+            .send()
+            .await
+            .map_err(peer_error)?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let response =
+            response.error_for_status().map_err(peer_error)?;
+        Ok(Some(
+            read_limited(response, self.max_bytes, "peer").await?,
+        ))
+    }
+
+    async fn set(
+        &self,
+        key: &str,
+        value: Vec<u8>,
+        ttl: Duration,
+    ) -> Result<()> {
+        let Some(owner) = self.remote_owner(key) else {
+            self.set_local(key, value, ttl);
+            return Ok(());
+        };
+        self.client
+            .put(self.url(&owner, &["internal", "cache", key])?)
+            .header(PEER_HEADER, &self.secret.0)
+            .header(TTL_HEADER, ttl.as_millis().to_string())
+            .body(value)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(peer_error)?;
+        Ok(())
+    }
+
+    /// Also clears the entries owned by the other replicas.
+    async fn clear(&self, prefix: &str) -> Result<()> {
+        self.clear_local(prefix);
+        let ring = self.ring();
+        for peer in
+            ring.peers().iter().filter(|p| **p != self.self_url)
+        {
+            let mut url = self.url(peer, &["internal", "cache"])?;
+            url.query_pairs_mut().append_pair("prefix", prefix);
+            let result = self
+                .client
+                .delete(url)
+                .header(PEER_HEADER, &self.secret.0)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            if let Err(e) = result {
+                warn!(%peer, %prefix, error = %e, "Failed to clear the peer cache");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The endpoints the other replicas call, only answered with
+/// `PEER_SECRET`. They never forward a request on.
+pub fn router<S>(store: Arc<PeerStore>) -> Router<S> {
+    Router::new()
+        .route("/internal/cache", axum::routing::delete(clear))
+        .route("/internal/cache/*key", get(fetch).put(store_entry))
+        .with_state(store)
+}
+
+async fn fetch(
+    State(store): State<Arc<PeerStore>>,
+    Path(key): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    store.authorize(&headers)?;
+    let value = store.get_local(&key).ok_or_else(|| {
+        AppError::NotFound(format!("Cache entry '{}' not found", key))
+    })?;
+    Ok(([(header::CONTENT_TYPE, "application/json")], value)
+        .into_response())
+}
+
+async fn store_entry(
+    State(store): State<Arc<PeerStore>>,
+    Path(key): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<StatusCode> {
+    store.authorize(&headers)?;
+    let ttl = headers
+        .get(TTL_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+        .map(Duration::from_millis)
+        .ok_or_else(|| {
+            AppError::BadRequest(format!("Missing {}", TTL_HEADER))
+        })?;
+    store.set_local(&key, body.to_vec(), ttl);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+struct ClearParams {
+    prefix: String,
+}
+
+async fn clear(
+    State(store): State<Arc<PeerStore>>,
+    Query(params): Query<ClearParams>,
+    headers: HeaderMap,
+) -> Result<StatusCode> {
+    store.authorize(&headers)?;
+    store.clear_local(&params.prefix);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_spreads_and_moves_few_keys() {
+        let peers: Vec<String> = (1..=3)
+            .map(|i| format!("http://10.0.0.{}:8080/", i))
+            .collect();
+        let ring = Ring::new(peers.clone());
+        let keys: Vec<String> = (0..3_000)
+            .map(|i| format!("pokemon.species:{}", i))
+            .collect();
+        let mut counts = HashMap::new();
+        for key in &keys {
+            *counts.entry(ring.owner(key).unwrap()).or_insert(0) += 1;
+        }
+        assert_eq!(counts.len(), 3);
+        assert!(
+            counts.values().all(|count| *count > 700),
+            "{:?}",
+            counts
+        );
+
+        // A replica leaving only moves the keys it owned.
+        let smaller = Ring::new(peers[..2].to_vec());
+        for key in &keys {
+            let owner = ring.owner(key).unwrap();
+            if owner != "http://10.0.0.3:8080" {
+                assert_eq!(smaller.owner(key), Some(owner));
+            }
+        }
+        assert_eq!(Ring::new(Vec::new()).owner("pikachu"), None);
+    }
+
+    #[test]
+    fn test_parse_dns_discovery() {
+        assert_eq!(
+            Discovery::dns("pokedex-peers:8080"),
+            Ok(Discovery::Dns {
+                host: "pokedex-peers".to_string(),
+                port: 8080,
+            })
+        );
+        assert!(Discovery::dns("pokedex-peers").is_err());
+        assert!(Discovery::dns(":8080").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_entries_are_stored_at_their_owner() {
+        let owner = wiremock::MockServer::start().await;
+        let settings = |self_url: &str| PeerSettings {
+            discovery: Discovery::Static(vec![
+                owner.uri(),
+                "http://127.0.0.1:9".to_string(),
+            ]),
+            self_url: self_url.to_string(),
+            secret: Secret("s3cret".to_string()),
+            refresh: Duration::from_secs(30),
+            timeout: Duration::from_secs(1),
+            max_bytes: 1024,
+        };
+        let local =
+            PeerStore::new(&settings("http://127.0.0.1:9")).unwrap();
+        let key = (0..)
+            .map(|i| format!("pokemon.species:{}", i))
+            .find(|key| local.remote_owner(key).is_some())
+            .unwrap();
+
+        wiremock::Mock::given(wiremock::matchers::method("GET"))
+            .and(wiremock::matchers::path(format!(
+                "/internal/cache/{}",
+                key
+            )))
+            .and(wiremock::matchers::header(PEER_HEADER, "s3cret"))
+            .respond_with(
+                wiremock::ResponseTemplate::new(200)
+                    .set_body_string("{}"),
+            )
+            .expect(1)
+            .mount(&owner)
+            .await;
+        assert_eq!(
+            local.get(&key).await.unwrap(),
+            Some(b"{}".to_vec())
+        );
+
+        // Answers larger than the shard are refused.
+        let large = (0..)
+            .map(|i| format!("pokemon.varieties:{}", i))
+            .find(|key| local.remote_owner(key).is_some())
+            .unwrap();
+        wiremock::Mock::given(wiremock::matchers::path(format!(
+            "/internal/cache/{}",
+            large
+        )))
+        .respond_with(
+            wiremock::ResponseTemplate::new(200)
+                .set_body_bytes(vec![b' '; 2048]),
+        )
+        .mount(&owner)
+        .await;
+        assert!(local.get(&large).await.is_err());
+
+        // The owner keeps its entries itself.
+        let remote = PeerStore::new(&settings(&owner.uri())).unwrap();
+        remote
+            .set(&key, b"{}".to_vec(), Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(remote.get_local(&key), Some(b"{}".to_vec()));
+    }
+
+    fn store(max_bytes: usize) -> PeerStore {
+        PeerStore::new(&PeerSettings {
+            discovery: Discovery::Static(Vec::new()),
+            self_url: "http://127.0.0.1:9".to_string(),
+            secret: Secret("s3cret".to_string()),
+            refresh: Duration::from_secs(30),
+            timeout: Duration::from_secs(1),
+            max_bytes,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_shard_evicts_the_entries_closest_to_expiry() {
+        let store = store(10);
+        store.set_local("a", vec![0; 4], Duration::from_secs(10));
+        store.set_local("b", vec![0; 4], Duration::from_secs(60));
+        store.set_local("c", vec![0; 4], Duration::from_secs(30));
+        assert_eq!(store.get_local("a"), None);
+        assert!(store.get_local("b").is_some());
+        assert!(store.get_local("c").is_some());
+
+        // Replacing an entry frees its old value, and values larger
+        // than the shard are not kept.
+        store.set_local("c", vec![0; 6], Duration::from_secs(30));
+        assert!(store.get_local("b").is_some());
+        store.set_local("d", vec![0; 11], Duration::from_secs(30));
+        assert_eq!(store.get_local("d"), None);
+        assert_eq!(store.shard.lock().unwrap().bytes, 10);
+    }
+
+    #[test]
+    fn test_authorize_requires_the_secret() {
+        let store = store(1024);
+        let mut headers = HeaderMap::new();
+        assert!(store.authorize(&headers).is_err());
+        headers.insert(PEER_HEADER, "s3cre".parse().unwrap());
+        assert!(store.authorize(&headers).is_err());
+        headers.insert(PEER_HEADER, "s3cret".parse().unwrap());
+        assert!(store.authorize(&headers).is_ok());
+    }
+}
@@ -0,0 +1,164 @@
+use pokedex_rs::models::Phonetics;
+
+/// Pronunciations of well-known Pokemon as said in the English
+/// games and anime: name, IPA and respelling.
+const KNOWN: &[(&str, &str, &str)] = &[
+    ("bulbasaur", "ˈbʌlbəsɔːr", "BUL-buh-sor"),
+    ("ivysaur", "ˈaɪvisɔːr", "EYE-vee-sor"),
+    ("venusaur", "ˈviːnəsɔːr", "VEE-nuh-sor"),
+    ("charmander", "ˈtʃɑːrmændər", "CHAR-man-der"),
+    ("charmeleon", "tʃɑːrˈmiːliən", "char-MEE-lee-un"),
+    ("charizard", "ˈtʃɑːrɪzɑːrd", "CHAR-iz-ard"),
+    ("squirtle", "ˈskwɜːrtəl", "SKWUR-tul"),
+    ("wartortle", "wɔːrˈtɔːrtəl", "wor-TOR-tul"),
+    ("blastoise", "ˈblæstɔɪz", "BLAS-toyz"),
+    ("pichu", "ˈpiːtʃuː", "PEE-choo"),
+    ("pikachu", "ˈpiːkətʃuː", "PEE-kuh-choo"),
+    ("raichu", "ˈraɪtʃuː", "RY-choo"),
+    ("jigglypuff", "ˈdʒɪɡlipʌf", "JIG-lee-puf"),
+    ("meowth", "miˈaʊθ", "mee-OWTH"),
+    ("psyduck", "ˈsaɪdʌk", "SY-duk"),
+    ("onix", "ˈoʊnɪks", "OH-niks"),
+    ("gengar", "ˈɡɛŋɡɑːr", "GENG-gar"),
+    ("eevee", "ˈiːviː", "EE-vee"),
+    ("snorlax", "ˈsnɔːrlæks", "SNOR-laks"),
+    ("mewtwo", "ˈmjuːtuː", "MYOO-too"),
+    ("mew", "mjuː", "MYOO"),
+    ("lucario", "luːˈkɑːrioʊ", "loo-KAR-ee-oh"),
+];
+
+/// Digraphs kept within one syllable.
+const DIGRAPHS: &[&str] = &["ch", "sh", "th", "ph", "ck", "qu"];
+
+/// The pronunciation of `name`: curated for well-known Pokemon, and
+/// otherwise a respelling generated from the spelling, without IPA.
+pub fn of(name: &str) -> Phonetics {
+    let name = name.to_lowercase();
+    match KNOWN.iter().find(|(known, _, _)| *known == name) {
+        Some((_, ipa, respelling)) => Phonetics {
+            ipa: Some(ipa.to_string()),
+            respelling: respelling.to_string(),
+        },
+        None => Phonetics {
+            ipa: None,
+            respelling: respell(&name),
+        },
+    }
+}
+
+/// Splits each word of `name` into syllables and stresses the first
+/// one, e.g. `garchomp` becomes `GAR-chomp`.
+fn respell(name: &str) -> String {
+    name.split(|c: char| !c.is_ascii_alphabetic())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut syllables = syllables(word);
+            if let Some(first) = syllables.first_mut() {
+                *first = first.to_uppercase();
+            }
+            syllables.join("-")
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn is_vowel(units: &[&str], i: usize) -> bool {
+    match units[i] {
+        "a" | "e" | "i" | "o" | "u" => true,
+        // `y` is a vowel unless it starts a syllable before a vowel.
+        "y" => {
+            i > 0
+                || units.get(1).is_none_or(|next| {
+                    !matches!(*next, "a" | "e" | "i" | "o" | "u")
+                })
+        }
+        _ => false,
+    }
+}
+
+/// Splits a word into syllables around its vowel groups: a single
+/// consonant between vowels starts the next syllable, and a cluster
+/// is split after its first consonant. A final silent `e` stays with
+/// the previous syllable.
+fn syllables(word: &str) -> Vec<String> {
+    let mut units: Vec<&str> = Vec::new();
+    let mut i = 0;
+    while i < word.len() {
+        let len = if DIGRAPHS
+            .iter()
+            .any(|digraph| word[i..].starts_with(digraph))
+        {
+            2
+        } else {
+            1
+        };
+        units.push(&word[i..i + len]);
+        i += len;
+    }
+
+    let vowel: Vec<bool> =
+        (0..units.len()).map(|i| is_vowel(&units, i)).collect();
+    // Vowel groups as (start, end) unit ranges.
+    let mut groups: Vec<(usize, usize)> = Vec::new();
+    for (i, &is_vowel) in vowel.iter().enumerate() {
+        if !is_vowel {
+            continue;
+        }
+        match groups.last_mut() {
+            Some((_, end)) if *end == i => *end = i + 1,
+            _ => groups.push((i, i + 1)),
+        }
+    }
+    let silent_e = groups.len() > 1
+        && groups.last().is_some_and(|&(start, end)| {
+            end == units.len()
+                && end - start == 1
+                && units[start] == "e"
+                && start >= 2
+                && !vowel[start - 1]
+        });
+    if silent_e {
+        groups.pop();
+    }
+    if groups.len() <= 1 {
+        return vec![word.to_string()];
+    }
+
+    let mut breaks = Vec::new();
+    for pair in groups.windows(2) {
+        let (end, next) = (pair[0].1, pair[1].0);
+        let consonants = next - end;
+        breaks.push(if consonants <= 1 { end } else { end + 1 });
+    }
+
+    let mut syllables = Vec::new();
+    let mut start = 0;
+    for end in breaks.into_iter().chain([units.len()]) {
+        syllables.push(units[start..end].concat());
+        start = end;
+    }
+    syllables
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_names_have_ipa() {
+        let pikachu = of("Pikachu");
+        assert_eq!(pikachu.ipa.as_deref(), Some("ˈpiːkətʃuː"));
+        assert_eq!(pikachu.respelling, "PEE-kuh-choo");
+    }
+
+    #[test]
+    fn test_respell() {
+        assert_eq!(respell("garchomp"), "GAR-chomp");
+        assert_eq!(respell("tyranitar"), "TY-ra-ni-tar");
+        assert_eq!(respell("mr-mime"), "MR MIME");
+        assert_eq!(respell("yanma"), "YAN-ma");
+        assert_eq!(respell("ho-oh"), "HO OH");
+        assert_eq!(of("dragonite").ipa, None);
+        assert_eq!(of("dragonite").respelling, "DRA-go-nite");
+    }
+}
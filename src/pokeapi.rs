@@ -0,0 +1,165 @@
+use crate::context;
+use crate::error::{AppError, Result};
+use crate::http::{Upstream, UpstreamOptions};
+use crate::shadow::Shadow;
+use reqwest::Client;
+use serde::{Deserialize, de::DeserializeOwned};
+use std::sync::Arc;
+use tracing::debug;
+
+pub use crate::wire::NamedApiResource;
+
+#[derive(Deserialize)]
+pub struct NamedApiResourceList {
+    pub results: Vec<NamedApiResource>,
+}
+
+impl NamedApiResource {
+    pub fn id(&self) -> Option<u32> {
+        resource_id(&self.url)
+    }
+}
+
+/// Thin wrapper around PokeAPI shared by every service that reads
+/// from it, mapping transport and status failures to `AppError`.
+#[derive(Clone)]
+pub struct PokeApiClient {
+    upstream: Upstream,
+    base_url: String,
+    shadow: Option<Arc<Shadow>>,
+}
+
+/// Name of PokeAPI in the upstream statistics.
+pub const UPSTREAM_NAME: &str = "PokeAPI";
+
+impl PokeApiClient {
+    pub fn new(client: Client, base_url: String) -> Self {
+        Self {
+            upstream: Upstream::new(UPSTREAM_NAME, client),
+            base_url,
+            shadow: None,
+        }
+    }
+
+    pub fn with_options(mut self, options: UpstreamOptions) -> Self {
+        self.upstream = self.upstream.with_options(options);
+        self
+    }
+
+    /// Mirrors a share of the calls to `shadow`.
+    pub fn with_shadow(mut self, shadow: Arc<Shadow>) -> Self {
+        self.shadow = Some(shadow);
+        self
+    }
+
+    /// Fetches `path` (relative to the base URL) and deserializes it,
+    /// using `not_found` to build the message of a 404.
+    pub async fn get<T: DeserializeOwned + 'static>(
+        &self,
+        path: &str,
+        not_found: impl FnOnce() -> String,
+    ) -> Result<T> {
+        let url = format!("{}/{}", self.base_url(), path);
+        debug!("Fetching from PokeAPI: {}", url);
+
+        let response =
+            self.upstream.send(self.upstream.get(&url)).await?;
+
+        if !response.status().is_success() {
+            if response.status() == reqwest::StatusCode::NOT_FOUND {
+                return Err(AppError::NotFound(not_found()));
+            }
+            return Err(AppError::ExternalApi(format!(
+                "PokeAPI returned status: {}",
+                response.status()
+            )));
+        }
+
+        // Calls against an overridden base URL are not mirrored,
+        // their payloads would not be comparable.
+        match &self.shadow {
+            Some(shadow)
+                if context::current().pokeapi_base_url.is_none()
+                    && shadow.sampled() =>
+            {
+                let body = self.upstream.read_bytes(response).await?;
+                let value = self.upstream.parse_json(&body)?;
+                shadow.mirror::<T>(path, body);
+                Ok(value)
+            }
+            _ => self.upstream.read_json(response).await,
+        }
+    }
+
+    /// The configured base URL, unless the current request
+    /// overrides it.
+    fn base_url(&self) -> String {
+        context::current()
+            .pokeapi_base_url
+            .unwrap_or_else(|| self.base_url.clone())
+    }
+
+    pub async fn health_check(&self) -> Result<()> {
+        let url = format!("{}/pokemon-species/1", self.base_url);
+        self.upstream.probe(self.upstream.get(&url)).await
+    }
+}
+
+/// Extracts the numeric id from a PokeAPI resource URL such as
+/// `https://pokeapi.co/api/v2/pokemon-species/25/`.
+pub fn resource_id(url: &str) -> Option<u32> {
+    url.trim_end_matches('/').rsplit('/').next()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http;
+    use std::time::Duration;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn test_resource_id() {
+        assert_eq!(
+            resource_id(
+                "https://pokeapi.co/api/v2/pokemon-species/25/"
+            ),
+            Some(25)
+        );
+        assert_eq!(resource_id("https://pokeapi.co/api/v2/"), None);
+    }
+
+    #[tokio::test]
+    async fn test_get_rejects_oversized_body() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/pokemon-species/25"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(format!(
+                    "\"{}\"",
+                    "a".repeat(64)
+                )),
+            )
+            .mount(&server)
+            .await;
+
+        let client = PokeApiClient::new(
+            http::build_client(&http::ClientSettings::new(
+                Duration::from_secs(5),
+            )),
+            server.uri(),
+        )
+        .with_options(UpstreamOptions {
+            max_response_bytes: 32,
+            ..UpstreamOptions::default()
+        });
+
+        let result = client
+            .get::<String>("pokemon-species/25", String::new)
+            .await;
+        assert!(
+            matches!(result, Err(AppError::ExternalApi(msg)) if msg.contains("exceeds 32 bytes"))
+        );
+    }
+}
@@ -1,177 +1,2041 @@
+use crate::cache::{Cache, ManagedCache, Promotion};
+use crate::cache_store::CacheStore;
 use crate::error::{AppError, Result};
-use reqwest::Client;
+use crate::history::{
+    self, PastAbilities, PastForms, PastTypes, SlotAbility,
+    StatHistory,
+};
+use crate::include::Include;
+use crate::lang::{DEFAULT_LANGUAGE, Lang};
+use crate::names::{self, NameGuard};
+use crate::phonetics;
+use crate::pokeapi::{
+    self, NamedApiResource, NamedApiResourceList, PokeApiClient,
+};
+use crate::sources::{PokemonSource, SourceChain};
+use crate::text::{self, Normalization};
+use crate::wire::PokeApiSpecies;
+use futures::{StreamExt, future::try_join_all, stream};
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
-use tracing::{debug, instrument};
+use std::{
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+use tracing::{debug, info, instrument, warn};
+use utoipa::ToSchema;
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub struct Pokemon {
-    pub name: String,
-    pub description: Option<String>,
-    pub habitat: Option<String>,
-    pub is_legendary: bool,
+pub use pokedex_rs::models::{
+    Artwork, Breeding, DataSource, Meta, Pokemon, PokemonDetails,
+    SpeciesSummary, Stat,
+};
+
+/// What we keep of the `/pokemon/{name}` resource of a variety.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Variety {
+    artwork: Artwork,
+    types: Vec<String>,
+    height: u32,
+    weight: u32,
+    stats: Vec<Stat>,
+    abilities: Vec<String>,
+    /// `None` when cached before the cries were kept.
+    #[serde(default)]
+    cries: Option<Cries>,
+    /// `None` when cached before the past types and abilities were
+    /// kept.
+    #[serde(default)]
+    past: Option<PastForms>,
+}
+
+/// URLs of the cry audio of a variety.
+#[derive(
+    Debug, Clone, Default, Serialize, Deserialize, PartialEq,
+)]
+pub struct Cries {
+    /// The cry of the latest games.
+    pub latest: Option<String>,
+    /// The cry of the games it first appeared in, when it changed.
+    pub legacy: Option<String>,
 }
 
+/// The `/pokemon/{name}` resource of a species' variety.
 #[derive(Deserialize)]
-struct PokeApiSpecies {
-    name: String,
-    habitat: Option<Habitat>,
-    flavor_text_entries: Vec<FlavorTextEntry>,
-    is_legendary: bool,
+struct PokeApiPokemon {
+    sprites: PokeApiSprites,
+    #[serde(default)]
+    types: Vec<PokeApiType>,
+    #[serde(default)]
+    height: u32,
+    #[serde(default)]
+    weight: u32,
+    #[serde(default)]
+    stats: Vec<PokeApiStat>,
+    #[serde(default)]
+    abilities: Vec<PokeApiAbility>,
+    #[serde(default)]
+    cries: Cries,
+    #[serde(default)]
+    past_types: Vec<PokeApiPastTypes>,
+    #[serde(default)]
+    past_abilities: Vec<PokeApiPastAbilities>,
 }
 
 #[derive(Deserialize)]
-struct FlavorTextEntry {
-    flavor_text: String,
-    language: Language,
+struct PokeApiPastTypes {
+    generation: NamedApiResource,
+    types: Vec<PokeApiType>,
 }
 
 #[derive(Deserialize)]
-struct Language {
-    name: String,
+struct PokeApiPastAbilities {
+    generation: NamedApiResource,
+    abilities: Vec<PokeApiPastAbility>,
+}
+
+/// An ability slot of a past generation; `ability` is null when the
+/// slot did not exist yet.
+#[derive(Deserialize)]
+struct PokeApiPastAbility {
+    ability: Option<NamedApiResource>,
+    #[serde(default)]
+    is_hidden: bool,
+    slot: u32,
+}
+
+#[derive(Deserialize)]
+struct PokeApiType {
+    slot: u32,
+    #[serde(rename = "type")]
+    type_: NamedApiResource,
+}
+
+#[derive(Deserialize)]
+struct PokeApiStat {
+    base_stat: u32,
+    stat: NamedApiResource,
+}
+
+#[derive(Deserialize)]
+struct PokeApiAbility {
+    ability: NamedApiResource,
+    #[serde(default)]
+    is_hidden: bool,
+    #[serde(default)]
+    slot: u32,
 }
 
 #[derive(Deserialize)]
-struct Habitat {
+struct PokeApiSprites {
+    front_default: Option<String>,
+    back_default: Option<String>,
+    front_shiny: Option<String>,
+    back_shiny: Option<String>,
+    #[serde(default)]
+    other: PokeApiOtherSprites,
+}
+
+#[derive(Default, Deserialize)]
+struct PokeApiOtherSprites {
+    #[serde(rename = "official-artwork")]
+    official_artwork: Option<PokeApiOfficialArtwork>,
+}
+
+#[derive(Deserialize)]
+struct PokeApiOfficialArtwork {
+    front_default: Option<String>,
+    front_shiny: Option<String>,
+}
+
+/// Species together with the flags the legendary and mythical
+/// listings are built from.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SpeciesFlags {
+    pub species: SpeciesSummary,
+    pub is_legendary: bool,
+    pub is_mythical: bool,
+    pub is_baby: bool,
+}
+
+/// A string in one of PokeAPI's languages.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Localized {
+    pub language: String,
+    pub value: String,
+}
+
+/// What we keep of a species: the English `Pokemon` plus the
+/// localized strings it can be rendered with.
+#[derive(Serialize, Deserialize)]
+pub struct CachedSpecies {
+    pokemon: Pokemon,
+    names: Vec<Localized>,
+    genera: Vec<Localized>,
+    default_variety: String,
+    /// Missing from species cached before it was kept.
+    #[serde(default)]
+    gender_rate: Option<i8>,
+    #[serde(default)]
+    evolution_chain: Option<u32>,
+    /// Empty for species cached before the varieties were kept.
+    #[serde(default)]
+    varieties: Vec<VarietyRef>,
+}
+
+/// A variety listed by a species.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct VarietyRef {
     name: String,
+    id: Option<u32>,
+    is_default: bool,
+}
+
+/// What sets a form apart from the default variety.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum FormKind {
+    Default,
+    Mega,
+    Gmax,
+    /// Alolan, Galarian, Hisuian and Paldean forms.
+    Regional,
+    Other,
+}
+
+/// Name suffixes of the regional forms.
+const REGIONS: [&str; 4] = ["-alola", "-galar", "-hisui", "-paldea"];
+
+impl FormKind {
+    fn of(name: &str, is_default: bool) -> Self {
+        if is_default {
+            FormKind::Default
+        } else if name.contains("-mega") {
+            FormKind::Mega
+        } else if name.ends_with("-gmax") {
+            FormKind::Gmax
+        } else if REGIONS.iter().any(|region| name.contains(region)) {
+            FormKind::Regional
+        } else {
+            FormKind::Other
+        }
+    }
+}
+
+/// A variety of a species, for a form picker.
+#[derive(Debug, Clone, Serialize, PartialEq, ToSchema)]
+pub struct Form {
+    pub name: String,
+    /// Id of the `/pokemon` resource of the variety.
+    pub id: Option<u32>,
+    pub is_default: bool,
+    pub kind: FormKind,
+    pub types: Vec<String>,
+    pub artwork: Artwork,
+}
+
+impl CachedSpecies {
+    fn render(&self, lang: &Lang) -> Pokemon {
+        let pick = |entries: &[Localized]| {
+            lang.pick(entries, |e| &e.language)
+                .map(|e| e.value.clone())
+        };
+        Pokemon {
+            display_name: pick(&self.names),
+            genus: pick(&self.genera),
+            ..self.pokemon.clone()
+        }
+    }
+}
+
+/// A Pokedex entry of a species in one game and language.
+#[derive(
+    Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema,
+)]
+pub struct FlavorEntry {
+    pub version: String,
+    pub language: String,
+    pub text: String,
+}
+
+/// What breeding depends on for a species.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BreedingTraits {
+    pub name: String,
+    pub egg_groups: Vec<String>,
+    /// Chance of being female in eighths, -1 when genderless.
+    pub gender_rate: i8,
+    /// Id of the evolution chain the species belongs to.
+    pub evolution_chain: Option<u32>,
+}
+
+/// A cached value, `stale` when it is an expired entry served
+/// because PokeAPI could not be reached.
+struct Fetched<T> {
+    value: Arc<T>,
+    stale: bool,
+}
+
+impl<T> Fetched<T> {
+    fn fresh(value: Arc<T>) -> Self {
+        Self {
+            value,
+            stale: false,
+        }
+    }
+
+    /// Answers a failed fetch with `cached`, an expired entry, when
+    /// PokeAPI is unavailable: a server error, a dropped connection
+    /// or a timeout.
+    fn stale(
+        error: AppError,
+        cached: Option<Arc<T>>,
+    ) -> Result<Self> {
+        match (error, cached) {
+            (
+                e @ (AppError::ExternalApi(_) | AppError::Timeout(_)),
+                Some(value),
+            ) => {
+                warn!(error = %e, "PokeAPI unavailable, serving stale data");
+                Ok(Self { value, stale: true })
+            }
+            (e, _) => Err(e),
+        }
+    }
+}
+
+const SPECIES_INDEX_KEY: &str = "species";
+const FLAG_INDEX_KEY: &str = "flags";
+const FLAG_INDEX_CONCURRENCY: usize = 16;
+/// Seconds clients are asked to wait while the flag index is built.
+const FLAG_INDEX_RETRY_AFTER: u64 = 5;
+
+/// The cache of mapped species, shared with the `cache` source.
+pub type SpeciesCache = Cache<String, Arc<CachedSpecies>>;
+
+/// Fetches species from PokeAPI and maps them to what is kept, for
+/// the service and the sources that fetch or parse species.
+#[derive(Clone)]
+pub struct SpeciesClient {
+    pokeapi: PokeApiClient,
+    normalization: Normalization,
+    /// Languages tried in order when a species has no English
+    /// description.
+    description_fallback: Vec<String>,
 }
 
 pub struct PokemonService {
-    client: Client,
-    base_url: String,
+    pokeapi: PokeApiClient,
+    species: SpeciesClient,
+    /// Only shared once the sources are built, so its builders can
+    /// still replace it until then.
+    species_cache: Arc<SpeciesCache>,
+    variety_cache: Cache<String, Arc<Variety>>,
+    index_cache: Cache<String, Arc<Vec<SpeciesSummary>>>,
+    flag_cache: Cache<String, Arc<Vec<SpeciesFlags>>>,
+    /// The last flag index built, served while it is rebuilt.
+    last_flags: Mutex<Option<Arc<Vec<SpeciesFlags>>>>,
+    /// Whether the flag index is being built.
+    building_flags: AtomicBool,
+    /// Every Pokedex entry of the species asked for, by name.
+    entries_cache: Cache<String, Arc<Vec<FlavorEntry>>>,
+    names: NameGuard,
+    /// Where species are looked up: the caches and then PokeAPI
+    /// unless set with `with_source`.
+    source: OnceLock<Arc<dyn PokemonSource>>,
 }
 
 impl PokemonService {
-    pub fn new(base_url: String, timeout: Duration) -> Self {
-        let client = Client::builder()
-            .timeout(timeout)
-            .user_agent(concat!(
-                env!("CARGO_PKG_NAME"),
-                "/",
-                env!("CARGO_PKG_VERSION")
-            ))
-            .pool_max_idle_per_host(10)
-            .pool_idle_timeout(Duration::from_secs(90))
-            .build()
-            .expect("Failed to create HTTP client");
+    pub fn new(pokeapi: PokeApiClient, cache_ttl: Duration) -> Self {
+        Self {
+            species: SpeciesClient {
+                pokeapi: pokeapi.clone(),
+                normalization: Normalization::default(),
+                description_fallback: Vec::new(),
+            },
+            pokeapi,
+            species_cache: Arc::new(Cache::new(cache_ttl)),
+            variety_cache: Cache::new(cache_ttl),
+            index_cache: Cache::new(cache_ttl),
+            flag_cache: Cache::new(cache_ttl),
+            last_flags: Mutex::new(None),
+            building_flags: AtomicBool::new(false),
+            entries_cache: Cache::new(cache_ttl),
+            names: NameGuard::default(),
+            source: OnceLock::new(),
+        }
+    }
+
+    /// Keeps expired species and varieties for `stale_for` more and
+    /// serves them when PokeAPI fails with a server error or a
+    /// timeout. Zero disables the fallback.
+    pub fn with_stale_fallback(
+        mut self,
+        stale_for: Duration,
+    ) -> Self {
+        self.species_cache = Arc::new(
+            unshared(self.species_cache).with_stale(stale_for),
+        );
+        self.variety_cache = self.variety_cache.with_stale(stale_for);
+        self
+    }
+
+    /// Fixes up the legacy characters and casing of descriptions.
+    pub fn with_normalization(
+        mut self,
+        normalization: Normalization,
+    ) -> Self {
+        self.species.normalization = normalization;
+        self
+    }
 
-        Self { client, base_url }
+    /// Takes the description in the first of `languages` available
+    /// when a species has no English one.
+    pub fn with_description_fallback(
+        mut self,
+        languages: Vec<String>,
+    ) -> Self {
+        self.species.description_fallback = languages;
+        self
     }
 
-    #[instrument(skip(self), fields(pokemon_name = %name))]
-    pub async fn get_pokemon(&self, name: &str) -> Result<Pokemon> {
-        let url = format!(
-            "{}/pokemon-species/{}",
-            self.base_url,
-            name.to_lowercase()
+    /// Looks species up in `source` rather than in the caches and
+    /// then PokeAPI. Set last, as the sources may share the species
+    /// cache.
+    pub fn with_source(
+        mut self,
+        source: Arc<dyn PokemonSource>,
+    ) -> Self {
+        self.source = OnceLock::from(source);
+        self
+    }
+
+    /// Backs every cache of this service with `store`.
+    pub fn with_second_level(
+        mut self,
+        store: Arc<dyn CacheStore>,
+        promotion: Promotion,
+    ) -> Self {
+        self.species_cache =
+            Arc::new(unshared(self.species_cache).with_second_level(
+                "pokemon.species",
+                store.clone(),
+                promotion,
+            ));
+        self.variety_cache = self.variety_cache.with_second_level(
+            "pokemon.varieties",
+            store.clone(),
+            promotion,
+        );
+        self.index_cache = self.index_cache.with_second_level(
+            "pokemon.index",
+            store.clone(),
+            promotion,
+        );
+        self.flag_cache = self.flag_cache.with_second_level(
+            "pokemon.flags",
+            store.clone(),
+            promotion,
+        );
+        self.entries_cache = self.entries_cache.with_second_level(
+            "pokemon.entries",
+            store,
+            promotion,
         );
-        debug!("Fetching pokemon from: {}", url);
+        self
+    }
 
-        let response =
-            self.client.get(&url).send().await.map_err(|e| {
-                if e.is_timeout() {
-                    AppError::Timeout(format!(
-                        "Request to PokeAPI timed out: {}",
-                        e
-                    ))
-                } else if e.is_connect() {
-                    AppError::ExternalApi(format!(
-                        "Failed to connect to PokeAPI: {}",
-                        e
-                    ))
-                } else {
-                    AppError::ExternalApi(format!(
-                        "Failed to fetch pokemon: {}",
-                        e
+    /// The caches owned by this service, by name.
+    pub fn caches(&self) -> Vec<(&'static str, &dyn ManagedCache)> {
+        vec![
+            ("pokemon.species", self.species_cache.as_ref()),
+            ("pokemon.varieties", &self.variety_cache),
+            ("pokemon.index", &self.index_cache),
+            ("pokemon.flags", &self.flag_cache),
+            ("pokemon.entries", &self.entries_cache),
+        ]
+    }
+
+    /// Fetches a species, localizing its display name and genus to
+    /// `lang`.
+    #[instrument(skip(self, lang), fields(pokemon_name = %name, lang = %lang.as_str()))]
+    pub async fn get_pokemon(
+        &self,
+        name: &str,
+        lang: &Lang,
+    ) -> Result<Pokemon> {
+        let species = self.get_species(name).await?;
+        let mut pokemon = species.value.render(lang);
+        if species.stale {
+            pokemon.data_source = Some(DataSource::StaleCache);
+        }
+        Ok(pokemon)
+    }
+
+    /// Applies `include` to `pokemon`, fetching the sections that are
+    /// not part of the species data.
+    pub async fn expand(
+        &self,
+        pokemon: Pokemon,
+        include: Include,
+    ) -> Result<Pokemon> {
+        let mut pokemon = include.apply(pokemon);
+        if include.artwork {
+            let variety = self.get_variety(&pokemon.name).await?;
+            pokemon.artwork = Some(variety.value.artwork.clone());
+            if variety.stale {
+                pokemon.data_source = Some(DataSource::StaleCache);
+            }
+        }
+        if include.phonetics {
+            pokemon.phonetics = Some(phonetics::of(&pokemon.name));
+        }
+        Ok(pokemon)
+    }
+
+    /// Fetches a species along with the types, stats and abilities
+    /// of its default variety.
+    pub async fn get_details(
+        &self,
+        name: &str,
+        lang: &Lang,
+    ) -> Result<PokemonDetails> {
+        // Most species are named after their default variety, so the
+        // variety is fetched alongside the species on that guess,
+        // unless the name is an id or unknown to the name guard.
+        let key = name.to_lowercase();
+        let guess = async {
+            if key.parse::<u32>().is_ok()
+                || !self.names.may_exist(&key)
+            {
+                return None;
+            }
+            Some(self.fetch_variety(&key).await)
+        };
+        let (species, guessed) =
+            tokio::join!(self.get_species(name), guess);
+        let species = species?;
+        let variety = match guessed {
+            Some(variety) if species.value.default_variety == key => {
+                variety
+            }
+            _ => {
+                self.fetch_variety(&species.value.default_variety)
+                    .await
+            }
+        };
+
+        let mut pokemon = species.value.render(lang);
+        if species.stale {
+            pokemon.data_source = Some(DataSource::StaleCache);
+        }
+        let mut details = PokemonDetails {
+            pokemon,
+            types: Vec::new(),
+            height: 0,
+            weight: 0,
+            stats: Vec::new(),
+            abilities: Vec::new(),
+        };
+        // Without the variety the species data is still served, with
+        // a warning instead of the battle data.
+        match variety {
+            Ok(Fetched {
+                value: variety,
+                stale,
+            }) => {
+                if stale {
+                    details.pokemon.data_source =
+                        Some(DataSource::StaleCache);
+                }
+                details.types = variety.types.clone();
+                details.height = variety.height;
+                details.weight = variety.weight;
+                details.stats = variety.stats.clone();
+                details.abilities = variety.abilities.clone();
+            }
+            Err(e) => {
+                warn!(pokemon_name = %name, error = %e, "Serving details without the variety");
+                details
+                    .pokemon
+                    .warnings
+                    .push(format!("Battle data unavailable: {}", e));
+            }
+        }
+        Ok(details)
+    }
+
+    async fn get_variety(
+        &self,
+        name: &str,
+    ) -> Result<Fetched<Variety>> {
+        let species = self.get_species(name).await?.value;
+        self.fetch_variety(&species.default_variety).await
+    }
+
+    /// The `/pokemon/{variety_name}` resource, from the cache when
+    /// possible.
+    async fn fetch_variety(
+        &self,
+        variety_name: &str,
+    ) -> Result<Fetched<Variety>> {
+        let key = variety_name.to_string();
+        if let Some(variety) = self.variety_cache.fetch(&key).await {
+            return Ok(Fetched::fresh(variety));
+        }
+
+        match self.load_variety(variety_name).await {
+            Ok(variety) => Ok(Fetched::fresh(variety)),
+            Err(e) => {
+                Fetched::stale(e, self.variety_cache.get_stale(&key))
+            }
+        }
+    }
+
+    /// Fetches the `/pokemon/{variety_name}` resource and caches it.
+    async fn load_variety(
+        &self,
+        variety_name: &str,
+    ) -> Result<Arc<Variety>> {
+        let pokemon: PokeApiPokemon = self
+            .pokeapi
+            .get(&format!("pokemon/{}", variety_name), || {
+                format!("Pokemon '{}' not found", variety_name)
+            })
+            .await?;
+        let variety = Arc::new(map_to_variety(pokemon));
+        self.variety_cache
+            .store(variety_name.to_string(), variety.clone())
+            .await;
+        Ok(variety)
+    }
+
+    /// The default variety of a species, fetched again when it was
+    /// cached before what `kept` checks for was.
+    async fn default_variety_with(
+        &self,
+        name: &str,
+        kept: fn(&Variety) -> bool,
+    ) -> Result<Arc<Variety>> {
+        let species = self.get_species(name).await?.value;
+        let variety =
+            self.fetch_variety(&species.default_variety).await?.value;
+        if kept(&variety) {
+            return Ok(variety);
+        }
+        self.load_variety(&species.default_variety).await
+    }
+
+    /// The cry URLs of the default variety of a species.
+    pub async fn cries(&self, name: &str) -> Result<Cries> {
+        let variety = self
+            .default_variety_with(name, |v| v.cries.is_some())
+            .await?;
+        Ok(variety.cries.clone().unwrap_or_default())
+    }
+
+    /// The types of the default variety of a species.
+    pub async fn types(&self, name: &str) -> Result<Vec<String>> {
+        Ok(self.get_variety(name).await?.value.types.clone())
+    }
+
+    /// How the types and abilities of the default variety of a
+    /// species changed across generations.
+    pub async fn stat_history(
+        &self,
+        name: &str,
+    ) -> Result<StatHistory> {
+        let variety = self
+            .default_variety_with(name, |v| v.past.is_some())
+            .await?;
+        Ok(history::build(
+            &name.to_lowercase(),
+            &variety.types,
+            &variety.past.clone().unwrap_or_default(),
+        ))
+    }
+
+    /// Every variety of a species, the default one first, with the
+    /// types and artwork of each.
+    pub async fn forms(&self, name: &str) -> Result<Vec<Form>> {
+        let mut species = self.get_species(name).await?.value;
+        if species.varieties.is_empty() {
+            // Cached before the varieties were kept.
+            species =
+                self.load_species(&name.to_lowercase(), name).await?;
+        }
+        let mut varieties = species.varieties.clone();
+        if varieties.is_empty() {
+            varieties.push(VarietyRef {
+                name: species.default_variety.clone(),
+                id: None,
+                is_default: true,
+            });
+        }
+        // PokeAPI lists the default variety first; keep it there.
+        varieties.sort_by_key(|variety| !variety.is_default);
+
+        try_join_all(varieties.into_iter().map(
+            |variety| async move {
+                let details =
+                    self.fetch_variety(&variety.name).await?.value;
+                Ok(Form {
+                    kind: FormKind::of(
+                        &variety.name,
+                        variety.is_default,
+                    ),
+                    name: variety.name,
+                    id: variety.id,
+                    is_default: variety.is_default,
+                    types: details.types.clone(),
+                    artwork: details.artwork.clone(),
+                })
+            },
+        ))
+        .await
+    }
+
+    /// The name of the default variety of a species, the `/pokemon`
+    /// resource PokeAPI keeps its per-form data under.
+    pub async fn default_variety(
+        &self,
+        name: &str,
+    ) -> Result<String> {
+        Ok(self
+            .get_species(name)
+            .await?
+            .value
+            .default_variety
+            .clone())
+    }
+
+    async fn get_species(
+        &self,
+        name: &str,
+    ) -> Result<Fetched<CachedSpecies>> {
+        let key = name.to_lowercase();
+        if !self.names.may_exist(&key) {
+            debug!(name = %key, "Rejecting unknown species name");
+            return Err(self.unknown_species(name));
+        }
+
+        match self.source().get(&key).await {
+            Ok(Some(species)) => Ok(Fetched::fresh(species)),
+            Ok(None) => Err(self.unknown_species(name)),
+            Err(AppError::NotFound(_)) => {
+                Err(self.unknown_species(name))
+            }
+            Err(e) => {
+                Fetched::stale(e, self.species_cache.get_stale(&key))
+            }
+        }
+    }
+
+    /// Fetches the species of `name` from PokeAPI again, replacing
+    /// the cached one before it expires.
+    pub async fn refresh(&self, name: &str) -> Result<()> {
+        self.load_species(&name.to_lowercase(), name).await?;
+        Ok(())
+    }
+
+    /// The egg groups, gender rate and evolution chain of a species.
+    pub async fn breeding_traits(
+        &self,
+        name: &str,
+    ) -> Result<BreedingTraits> {
+        let mut species = self.get_species(name).await?.value;
+        if species.gender_rate.is_none() {
+            // Cached before the gender rate was kept.
+            species =
+                self.load_species(&name.to_lowercase(), name).await?;
+        }
+        Ok(BreedingTraits {
+            name: species.pokemon.name.clone(),
+            egg_groups: species
+                .pokemon
+                .breeding
+                .as_ref()
+                .map(|breeding| breeding.egg_groups.clone())
+                .unwrap_or_default(),
+            gender_rate: species.gender_rate.unwrap_or_default(),
+            evolution_chain: species.evolution_chain,
+        })
+    }
+
+    /// Every Pokedex entry of a species, cleaned, in PokeAPI's order
+    /// of the games.
+    pub async fn flavor_entries(
+        &self,
+        name: &str,
+    ) -> Result<Arc<Vec<FlavorEntry>>> {
+        let key = name.to_lowercase();
+        if let Some(entries) = self.entries_cache.fetch(&key).await {
+            return Ok(entries);
+        }
+        if !self.names.may_exist(&key) {
+            return Err(self.unknown_species(name));
+        }
+
+        let species =
+            match self.species.fetch_document(&key, name).await {
+                Ok(species) => species,
+                Err(AppError::NotFound(_)) => {
+                    return Err(self.unknown_species(name));
+                }
+                Err(e) => return Err(e),
+            };
+        let entries: Vec<FlavorEntry> = species
+            .flavor_text_entries
+            .items
+            .iter()
+            .map(|entry| FlavorEntry {
+                version: entry
+                    .version
+                    .as_ref()
+                    .map(|version| version.name.clone())
+                    .unwrap_or_default(),
+                language: entry.language.name.clone(),
+                text: self
+                    .species
+                    .normalization
+                    .apply(&text::clean_description(
+                        &entry.flavor_text,
                     ))
+                    .into_owned(),
+            })
+            .collect();
+        // The species comes along, so keep it too.
+        let species = Arc::new(self.species.map(species));
+        self.source().put(&key, &species).await;
+
+        let entries = Arc::new(entries);
+        self.entries_cache.store(key, entries.clone()).await;
+        Ok(entries)
+    }
+
+    /// Fetches the species `key` from PokeAPI and keeps it in every
+    /// other source.
+    async fn load_species(
+        &self,
+        key: &str,
+        name: &str,
+    ) -> Result<Arc<CachedSpecies>> {
+        let species = self.species.fetch_document(key, name).await?;
+        let species = Arc::new(self.species.map(species));
+        self.source().put(key, &species).await;
+        Ok(species)
+    }
+
+    /// Where species are looked up.
+    fn source(&self) -> &Arc<dyn PokemonSource> {
+        self.source.get_or_init(|| {
+            Arc::new(SourceChain::cached(
+                self.species_cache.clone(),
+                self.species.clone(),
+            ))
+        })
+    }
+
+    /// The species cache, for the `cache` source.
+    pub fn species_cache(&self) -> Arc<SpeciesCache> {
+        self.species_cache.clone()
+    }
+
+    /// The client fetching and mapping species, for the sources.
+    pub fn species_client(&self) -> SpeciesClient {
+        self.species.clone()
+    }
+
+    /// The error for a species PokeAPI does not know, suggesting the
+    /// closest names of the cached species index.
+    fn unknown_species(&self, name: &str) -> AppError {
+        let suggestions = self
+            .index_cache
+            .get(&SPECIES_INDEX_KEY.to_string())
+            .map(|index| {
+                names::suggest(
+                    &name.to_lowercase(),
+                    index.iter().map(|s| s.name.as_str()),
+                )
+            })
+            .unwrap_or_default();
+        AppError::UnknownPokemon {
+            name: name.to_string(),
+            suggestions,
+        }
+    }
+
+    /// Returns every species known to PokeAPI, sorted by id.
+    pub async fn list_species(
+        &self,
+    ) -> Result<Arc<Vec<SpeciesSummary>>> {
+        if let Some(index) = self
+            .index_cache
+            .fetch(&SPECIES_INDEX_KEY.to_string())
+            .await
+        {
+            return Ok(index);
+        }
+        self.fetch_species_list().await
+    }
+
+    /// Fetches the species list from PokeAPI, refreshing the cached
+    /// one.
+    async fn fetch_species_list(
+        &self,
+    ) -> Result<Arc<Vec<SpeciesSummary>>> {
+        let list: NamedApiResourceList = self
+            .pokeapi
+            .get("pokemon-species?limit=100000", || {
+                "Species index not found".to_string()
+            })
+            .await?;
+
+        let index = Arc::new(species_summaries(list.results));
+        self.index_cache
+            .store(SPECIES_INDEX_KEY.to_string(), index.clone())
+            .await;
+        Ok(index)
+    }
+
+    /// Reloads the species names that unknown names are rejected
+    /// against from PokeAPI, returning how many there are. The cached
+    /// species list is skipped, or new species would only show up
+    /// once it expires.
+    pub async fn refresh_names(&self) -> Result<usize> {
+        let species = self.fetch_species_list().await?;
+        self.names.load(species.iter().map(|s| s.name.as_str()));
+        Ok(species.len())
+    }
+
+    /// Refreshes the species names now and every `interval`, so
+    /// newly added species are let through.
+    pub fn guard_names(self: Arc<Self>, interval: Duration) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                match self.refresh_names().await {
+                    Ok(count) => {
+                        debug!(count, "Refreshed species names")
+                    }
+                    Err(e) => {
+                        warn!(error = %e, "Failed to refresh species names")
+                    }
                 }
-            })?;
+            }
+        });
+    }
+
+    /// Returns the legendary/mythical/baby flags of every species,
+    /// sorted by id. Building the index fetches every species, so it
+    /// is built once in the background: until the first build is
+    /// done this fails with a `503`, and the previous index is served
+    /// while an expired one is rebuilt.
+    pub async fn flag_index(
+        self: &Arc<Self>,
+    ) -> Result<Arc<Vec<SpeciesFlags>>> {
+        if let Some(index) =
+            self.flag_cache.fetch(&FLAG_INDEX_KEY.to_string()).await
+        {
+            return Ok(index);
+        }
 
-        if !response.status().is_success() {
-            if response.status() == reqwest::StatusCode::NOT_FOUND {
-                return Err(AppError::NotFound(format!(
-                    "Pokemon '{}' not found",
-                    name
-                )));
+        if !self.building_flags.swap(true, Ordering::AcqRel) {
+            let service = self.clone();
+            tokio::spawn(async move {
+                if let Err(e) = service.build_flag_index().await {
+                    warn!(error = %e, "Failed to build the species flag index");
+                }
+                service
+                    .building_flags
+                    .store(false, Ordering::Release);
+            });
+        }
+        self.last_flags.lock().unwrap().clone().ok_or_else(|| {
+            AppError::NotReady {
+                message: "The species flag index is being built"
+                    .to_string(),
+                retry_after: FLAG_INDEX_RETRY_AFTER,
             }
-            return Err(AppError::ExternalApi(format!(
-                "PokeAPI returned status: {}",
-                response.status()
-            )));
+        })
+    }
+
+    /// Fetches the flags of every species. Species that fail are
+    /// left out and the index is not cached, so that the next call
+    /// retries them; the others come from the species cache then.
+    async fn build_flag_index(
+        &self,
+    ) -> Result<Arc<Vec<SpeciesFlags>>> {
+        let species = self.list_species().await?;
+        info!(count = species.len(), "Building species flag index");
+
+        let mut index: Vec<SpeciesFlags> =
+            stream::iter(species.iter().cloned())
+                .map(|summary| async move {
+                    match self.get_species(&summary.name).await {
+                        Ok(species) => {
+                            let pokemon = &species.value.pokemon;
+                            Some(SpeciesFlags {
+                                species: summary,
+                                is_legendary: pokemon.is_legendary,
+                                is_mythical: pokemon.is_mythical,
+                                is_baby: pokemon.is_baby,
+                            })
+                        }
+                        Err(e) => {
+                            warn!(species = %summary.name, error = %e, "Left species out of the flag index");
+                            None
+                        }
+                    }
+                })
+                .buffer_unordered(FLAG_INDEX_CONCURRENCY)
+                .filter_map(|flags| async move { flags })
+                .collect()
+                .await;
+        index.sort_by_key(|flags| flags.species.id);
+
+        let index = Arc::new(index);
+        if index.len() == species.len() {
+            self.flag_cache
+                .store(FLAG_INDEX_KEY.to_string(), index.clone())
+                .await;
         }
+        *self.last_flags.lock().unwrap() = Some(index.clone());
+        Ok(index)
+    }
+
+    pub async fn health_check(&self) -> Result<()> {
+        self.pokeapi.health_check().await
+    }
+}
 
+impl SpeciesClient {
+    /// The species `key` from PokeAPI, mapped.
+    pub async fn fetch(&self, key: &str) -> Result<CachedSpecies> {
+        let species = self.fetch_document(key, key).await?;
+        Ok(self.map(species))
+    }
+
+    /// A PokeAPI `pokemon-species` document as kept, for the
+    /// sources holding raw documents.
+    pub fn parse(
+        &self,
+        document: serde_json::Value,
+    ) -> Result<CachedSpecies> {
         let species =
-            response.json::<PokeApiSpecies>().await.map_err(|e| {
-                AppError::ExternalApi(format!(
-                    "Failed to parse pokemon data: {}",
+            serde_json::from_value(document).map_err(|e| {
+                AppError::Internal(format!(
+                    "Invalid species document: {}",
                     e
                 ))
             })?;
-
-        Ok(self.map_to_pokemon(species))
+        Ok(self.map(species))
     }
 
-    pub async fn health_check(&self) -> Result<()> {
-        let url = format!("{}/pokemon-species/1", self.base_url);
-        self.client.get(&url).send().await.map_err(|e| {
-            AppError::ExternalApi(format!(
-                "Health check failed: {}",
-                e
-            ))
-        })?;
-        Ok(())
+    async fn fetch_document(
+        &self,
+        key: &str,
+        name: &str,
+    ) -> Result<PokeApiSpecies> {
+        self.pokeapi
+            .get(&format!("pokemon-species/{}", key), || {
+                format!("Pokemon '{}' not found", name)
+            })
+            .await
     }
 
-    fn map_to_pokemon(&self, species: PokeApiSpecies) -> Pokemon {
-        let description = species
-            .flavor_text_entries
+    /// What is kept of a PokeAPI `pokemon-species` document.
+    fn map(&self, species: PokeApiSpecies) -> CachedSpecies {
+        // Malformed entries are skipped rather than failing the
+        // whole species.
+        let warnings: Vec<String> = [
+            species
+                .flavor_text_entries
+                .warnings("flavor_text_entries"),
+            species.names.warnings("names"),
+            species.genera.warnings("genera"),
+        ]
+        .concat();
+        if !warnings.is_empty() {
+            warn!(species = %species.name, ?warnings, "Skipped malformed PokeAPI entries");
+        }
+
+        let entry = std::iter::once(DEFAULT_LANGUAGE)
+            .chain(
+                self.description_fallback.iter().map(String::as_str),
+            )
+            .find_map(|language| {
+                text::in_language(
+                    &species.flavor_text_entries.items,
+                    language,
+                    |entry| &entry.language.name,
+                )
+            });
+        let description = entry.map(|entry| {
+            let cleaned = text::clean_description(&entry.flavor_text);
+            self.normalization.apply(&cleaned).into_owned()
+        });
+        let description_language = entry
+            .map(|entry| entry.language.name.clone())
+            .filter(|language| language != DEFAULT_LANGUAGE);
+
+        let names = species
+            .names
+            .items
+            .into_iter()
+            .map(|name| Localized {
+                language: name.language.name,
+                value: name.name,
+            })
+            .collect();
+        let genera = species
+            .genera
+            .items
+            .into_iter()
+            .map(|genus| Localized {
+                language: genus.language.name,
+                value: genus.genus,
+            })
+            .collect();
+
+        let varieties: Vec<VarietyRef> = species
+            .varieties
+            .into_iter()
+            .map(|variety| VarietyRef {
+                id: variety.pokemon.id(),
+                name: variety.pokemon.name,
+                is_default: variety.is_default,
+            })
+            .collect();
+        let default_variety = varieties
             .iter()
-            .find(|entry| entry.language.name == "en")
-            .map(|entry| clean_description(&entry.flavor_text));
+            .find(|variety| variety.is_default)
+            .map(|variety| variety.name.clone())
+            .unwrap_or_else(|| species.name.clone());
 
-        Pokemon {
+        let pokemon = Pokemon {
             name: species.name,
+            display_name: None,
+            genus: None,
             description,
+            description_language,
             habitat: species.habitat.map(|h| h.name),
             is_legendary: species.is_legendary,
+            is_mythical: species.is_mythical,
+            is_baby: species.is_baby,
+            breeding: Some(Breeding {
+                egg_groups: species
+                    .egg_groups
+                    .into_iter()
+                    .map(|group| group.name)
+                    .collect(),
+                growth_rate: species
+                    .growth_rate
+                    .map(|rate| rate.name),
+            }),
+            meta: Some(Meta {
+                capture_rate: species.capture_rate,
+                base_happiness: species.base_happiness,
+                shape: species.shape.map(|shape| shape.name),
+                color: species.color.map(|color| color.name),
+                gender_rate: species.gender_rate,
+                hatch_counter: species.hatch_counter,
+                evolution_chain: species
+                    .evolution_chain
+                    .as_ref()
+                    .and_then(|chain| {
+                        pokeapi::resource_id(&chain.url)
+                    }),
+            }),
+            artwork: None,
+            phonetics: None,
+            data_source: None,
+            warnings,
+        };
+
+        CachedSpecies {
+            pokemon,
+            names,
+            genera,
+            default_variety,
+            gender_rate: species.gender_rate,
+            evolution_chain: species
+                .evolution_chain
+                .and_then(|chain| pokeapi::resource_id(&chain.url)),
+            varieties,
         }
     }
 }
 
-fn clean_description(text: &str) -> String {
-    text.replace('\n', " ")
-        .replace('\r', " ")
-        .replace('\u{000C}', " ")
-        .split_whitespace()
-        .collect::<Vec<_>>()
-        .join(" ")
+/// The species cache of a service being built, which nothing else
+/// holds yet.
+fn unshared(cache: Arc<SpeciesCache>) -> SpeciesCache {
+    Arc::into_inner(cache).expect(
+        "the species cache is shared before the service is built",
+    )
+}
+
+fn map_to_variety(pokemon: PokeApiPokemon) -> Variety {
+    let sprites = pokemon.sprites;
+    let official = sprites.other.official_artwork;
+    let artwork = Artwork {
+        official: official
+            .as_ref()
+            .and_then(|art| art.front_default.clone()),
+        official_shiny: official.and_then(|art| art.front_shiny),
+        front: sprites.front_default,
+        back: sprites.back_default,
+        front_shiny: sprites.front_shiny,
+        back_shiny: sprites.back_shiny,
+    };
+
+    let mut types = pokemon.types;
+    types.sort_by_key(|t| t.slot);
+    let past = past_forms(
+        pokemon.past_types,
+        pokemon.past_abilities,
+        &pokemon.abilities,
+    );
+
+    Variety {
+        artwork,
+        types: types.into_iter().map(|t| t.type_.name).collect(),
+        height: pokemon.height,
+        weight: pokemon.weight,
+        stats: pokemon
+            .stats
+            .into_iter()
+            .map(|stat| Stat {
+                name: stat.stat.name,
+                base: stat.base_stat,
+            })
+            .collect(),
+        abilities: pokemon
+            .abilities
+            .into_iter()
+            .map(|ability| ability.ability.name)
+            .collect(),
+        cries: Some(pokemon.cries),
+        past: Some(past),
+    }
+}
+
+fn past_forms(
+    past_types: Vec<PokeApiPastTypes>,
+    past_abilities: Vec<PokeApiPastAbilities>,
+    abilities: &[PokeApiAbility],
+) -> PastForms {
+    let type_names = |mut types: Vec<PokeApiType>| {
+        types.sort_by_key(|t| t.slot);
+        types.into_iter().map(|t| t.type_.name).collect()
+    };
+    PastForms {
+        types: past_types
+            .into_iter()
+            .map(|past| PastTypes {
+                generation: past.generation.name,
+                types: type_names(past.types),
+            })
+            .collect(),
+        abilities: past_abilities
+            .into_iter()
+            .map(|past| PastAbilities {
+                generation: past.generation.name,
+                abilities: past
+                    .abilities
+                    .into_iter()
+                    .map(|ability| SlotAbility {
+                        slot: ability.slot,
+                        name: ability.ability.map(|a| a.name),
+                        is_hidden: ability.is_hidden,
+                    })
+                    .collect(),
+            })
+            .collect(),
+        current_abilities: abilities
+            .iter()
+            .map(|ability| SlotAbility {
+                slot: ability.slot,
+                name: Some(ability.ability.name.clone()),
+                is_hidden: ability.is_hidden,
+            })
+            .collect(),
+    }
+}
+
+/// Converts PokeAPI species resources into summaries sorted by id.
+pub fn species_summaries(
+    resources: Vec<NamedApiResource>,
+) -> Vec<SpeciesSummary> {
+    let mut summaries: Vec<SpeciesSummary> = resources
+        .into_iter()
+        .filter_map(|resource| {
+            Some(SpeciesSummary {
+                id: resource.id()?,
+                name: resource.name,
+            })
+        })
+        .collect();
+    summaries.sort_by_key(|species| species.id);
+    summaries
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::http;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn species_json(
+        name: &str,
+        legendary: bool,
+    ) -> serde_json::Value {
+        serde_json::json!({
+            "name": name,
+            "habitat": null,
+            "flavor_text_entries": [],
+            "is_legendary": legendary,
+            "is_mythical": false,
+            "is_baby": false
+        })
+    }
+
+    #[tokio::test]
+    async fn test_refresh_names_skips_the_cached_list() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/pokemon-species"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({
+                    "results": [
+                        {"name": "pikachu", "url": "http://x/pokemon-species/25/"}
+                    ]
+                }),
+            ))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let service = PokemonService::new(
+            PokeApiClient::new(
+                http::build_client(&http::ClientSettings::new(
+                    Duration::from_secs(5),
+                )),
+                server.uri(),
+            ),
+            Duration::from_secs(60),
+        );
+        service.list_species().await.unwrap();
+        assert_eq!(service.refresh_names().await.unwrap(), 1);
+        assert!(service.names.may_exist("pikachu"));
+        // The refreshed list is cached for the listings.
+        service.list_species().await.unwrap();
+    }
+
+    async fn built_flag_index(
+        service: &Arc<PokemonService>,
+    ) -> Arc<Vec<SpeciesFlags>> {
+        for _ in 0..100 {
+            match service.flag_index().await {
+                Ok(index) => return index,
+                Err(AppError::NotReady { .. }) => {
+                    tokio::time::sleep(Duration::from_millis(10))
+                        .await
+                }
+                Err(e) => panic!("unexpected error: {}", e),
+            }
+        }
+        panic!("the flag index was not built");
+    }
+
+    #[tokio::test]
+    async fn test_flag_index_keeps_partial_results() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/pokemon-species"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({
+                    "results": [
+                        {"name": "mewtwo", "url": "http://x/pokemon-species/150/"},
+                        {"name": "pikachu", "url": "http://x/pokemon-species/25/"}
+                    ]
+                }),
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/pokemon-species/pikachu"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(species_json("pikachu", false)),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/pokemon-species/mewtwo"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+
+        let service = Arc::new(PokemonService::new(
+            PokeApiClient::new(
+                http::build_client(&http::ClientSettings::new(
+                    Duration::from_secs(5),
+                )),
+                server.uri(),
+            ),
+            Duration::from_secs(60),
+        ));
+        let index = built_flag_index(&service).await;
+        assert_eq!(index.len(), 1);
+        assert_eq!(index[0].species.name, "pikachu");
+
+        // The partial index is served while the missing species are
+        // retried, the others coming from the cache.
+        let index = service.flag_index().await.unwrap();
+        assert_eq!(index.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_flag_index() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/pokemon-species"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({
+                    "results": [
+                        {"name": "mewtwo", "url": "http://x/pokemon-species/150/"},
+                        {"name": "pikachu", "url": "http://x/pokemon-species/25/"}
+                    ]
+                }),
+            ))
+            .mount(&server)
+            .await;
+        for (name, legendary) in
+            [("pikachu", false), ("mewtwo", true)]
+        {
+            Mock::given(method("GET"))
+                .and(path(format!("/pokemon-species/{}", name)))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .set_body_json(species_json(name, legendary)),
+                )
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+
+        let service = PokemonService::new(
+            PokeApiClient::new(
+                http::build_client(&http::ClientSettings::new(
+                    Duration::from_secs(5),
+                )),
+                server.uri(),
+            ),
+            Duration::from_secs(60),
+        );
+        let service = Arc::new(service);
+
+        // Concurrent first calls share one build, and fail until it
+        // is done.
+        let calls = futures::future::join_all(
+            (0..3).map(|_| service.flag_index()),
+        )
+        .await;
+        assert!(calls.iter().all(|call| matches!(
+            call,
+            Err(AppError::NotReady { retry_after: 5, .. })
+        )));
+        let index = built_flag_index(&service).await;
+        assert_eq!(index.len(), 2);
+        assert_eq!(index[0].species.name, "pikachu");
+        assert!(!index[0].is_legendary);
+        assert!(index[1].is_legendary);
+
+        // Species fetched for the index are served from the cache.
+        let mewtwo = service
+            .get_pokemon("MewTwo", &Lang::default())
+            .await
+            .unwrap();
+        assert!(mewtwo.is_legendary);
+    }
+
+    #[tokio::test]
+    async fn test_expand_fetches_artwork_of_default_variety() {
+        let server = MockServer::start().await;
+        let mut species = species_json("deoxys", false);
+        species["varieties"] = serde_json::json!([
+            {"is_default": false, "pokemon": {"name": "deoxys-attack", "url": "u"}},
+            {"is_default": true, "pokemon": {"name": "deoxys-normal", "url": "u"}}
+        ]);
+        Mock::given(method("GET"))
+            .and(path("/pokemon-species/deoxys"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(species),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/pokemon/deoxys-normal"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({
+                    "sprites": {
+                        "front_default": "front.png",
+                        "back_default": null,
+                        "front_shiny": "shiny.png",
+                        "back_shiny": null,
+                        "other": {
+                            "official-artwork": {
+                                "front_default": "official.png",
+                                "front_shiny": null
+                            }
+                        }
+                    }
+                }),
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let service = PokemonService::new(
+            PokeApiClient::new(
+                http::build_client(&http::ClientSettings::new(
+                    Duration::from_secs(5),
+                )),
+                server.uri(),
+            ),
+            Duration::from_secs(60),
+        );
+        let include = Include {
+            artwork: true,
+            ..Include::default()
+        };
+        for _ in 0..2 {
+            let pokemon = service
+                .get_pokemon("deoxys", &Lang::default())
+                .await
+                .unwrap();
+            let artwork = service
+                .expand(pokemon, include)
+                .await
+                .unwrap()
+                .artwork
+                .unwrap();
+            assert_eq!(
+                artwork.official.as_deref(),
+                Some("official.png")
+            );
+            assert_eq!(
+                artwork.front_shiny.as_deref(),
+                Some("shiny.png")
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_details_survive_a_failing_variety() {
+        let server = MockServer::start().await;
+        let mut deoxys = species_json("deoxys", false);
+        deoxys["varieties"] = serde_json::json!([
+            {"is_default": true, "pokemon": {"name": "deoxys-normal", "url": "u"}}
+        ]);
+        for (name, species) in [
+            ("pikachu", species_json("pikachu", false)),
+            ("deoxys", deoxys),
+        ] {
+            Mock::given(method("GET"))
+                .and(path(format!("/pokemon-species/{}", name)))
+                .respond_with(
+                    ResponseTemplate::new(200).set_body_json(species),
+                )
+                .mount(&server)
+                .await;
+        }
+        Mock::given(method("GET"))
+            .and(path("/pokemon/pikachu"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({
+                    "sprites": {
+                        "front_default": null,
+                        "back_default": null,
+                        "front_shiny": null,
+                        "back_shiny": null
+                    },
+                    "types": [{"slot": 1, "type": {"name": "electric", "url": "u"}}],
+                    "height": 4,
+                    "weight": 60
+                }),
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/pokemon/deoxys-normal"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+
+        let service = PokemonService::new(
+            PokeApiClient::new(
+                http::build_client(&http::ClientSettings::new(
+                    Duration::from_secs(5),
+                )),
+                server.uri(),
+            ),
+            Duration::from_secs(60),
+        );
+
+        // The guessed variety is the default one: fetched once.
+        let pikachu = service
+            .get_details("pikachu", &Lang::default())
+            .await
+            .unwrap();
+        assert_eq!(pikachu.types, ["electric"]);
+        assert_eq!(pikachu.height, 4);
+        assert!(pikachu.pokemon.warnings.is_empty());
+
+        let deoxys = service
+            .get_details("deoxys", &Lang::default())
+            .await
+            .unwrap();
+        assert_eq!(deoxys.pokemon.name, "deoxys");
+        assert!(deoxys.types.is_empty());
+        assert_eq!(deoxys.pokemon.warnings.len(), 1);
+        assert!(
+            deoxys.pokemon.warnings[0]
+                .starts_with("Battle data unavailable")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_serves_stale_species_while_pokeapi_is_down() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/pokemon-species/pikachu"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(species_json("pikachu", false)),
+            )
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+
+        let pokeapi = PokeApiClient::new(
+            http::build_client(&http::ClientSettings::new(
+                Duration::from_secs(5),
+            )),
+            server.uri(),
+        );
+        let service = PokemonService::new(pokeapi, Duration::ZERO)
+            .with_stale_fallback(Duration::from_secs(60));
+        let lang = Lang::default();
+        let fresh =
+            service.get_pokemon("pikachu", &lang).await.unwrap();
+        assert_eq!(fresh.data_source, None);
+        let stale =
+            service.get_pokemon("pikachu", &lang).await.unwrap();
+        assert_eq!(stale.data_source, Some(DataSource::StaleCache));
+        assert_eq!(stale.name, "pikachu");
+
+        // Without a stale entry the error stands.
+        assert!(matches!(
+            service.get_pokemon("raichu", &lang).await,
+            Err(AppError::ExternalApi(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_source_order() {
+        use crate::sources::{self, SourceKind, SourceSettings};
+        use crate::storage::{MemoryStorage, Storage};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/pokemon-species/pikachu"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(species_json("pikachu", false)),
+            )
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+        let storage: Arc<dyn Storage> =
+            Arc::new(MemoryStorage::new());
+        let service = async |kinds: &[SourceKind]| {
+            let service = PokemonService::new(
+                PokeApiClient::new(
+                    http::build_client(&http::ClientSettings::new(
+                        Duration::from_secs(5),
+                    )),
+                    server.uri(),
+                ),
+                Duration::ZERO,
+            );
+            let settings = SourceSettings {
+                cache: service.species_cache(),
+                species: service.species_client(),
+                storage: storage.clone(),
+                storage_ttl: Duration::ZERO,
+                snapshot_file: None,
+                fixtures_dir: "fixtures/species".into(),
+            };
+            let source =
+                sources::open(kinds, &settings).await.unwrap();
+            service.with_source(source)
+        };
+        let lang = Lang::default();
+
+        // Fresh from PokeAPI, then from storage once it is down.
+        let fresh_first = service(&[
+            SourceKind::Cache,
+            SourceKind::PokeApi,
+            SourceKind::Storage,
+        ])
+        .await;
+        fresh_first.get_pokemon("pikachu", &lang).await.unwrap();
+        let stored = fresh_first.get_pokemon("pikachu", &lang).await;
+        assert_eq!(stored.unwrap().name, "pikachu");
+
+        // Storage first never reaches PokeAPI for a stored species.
+        let stored_first = service(&[
+            SourceKind::Cache,
+            SourceKind::Storage,
+            SourceKind::PokeApi,
+        ])
+        .await;
+        let stored = stored_first.get_pokemon("pikachu", &lang).await;
+        assert_eq!(stored.unwrap().name, "pikachu");
+        assert!(matches!(
+            stored_first.get_pokemon("raichu", &lang).await,
+            Err(AppError::ExternalApi(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_fixture_source_serves_offline() {
+        use crate::sources::FixtureSource;
+
+        let service = PokemonService::new(
+            PokeApiClient::new(
+                http::build_client(&http::ClientSettings::new(
+                    Duration::from_secs(1),
+                )),
+                "http://127.0.0.1:9".to_string(),
+            ),
+            Duration::from_secs(60),
+        );
+        let fixtures = FixtureSource::new(service.species_client());
+        fixtures.insert("pikachu", species_json("pikachu", false));
+        let service = service.with_source(Arc::new(fixtures));
+        let lang = Lang::default();
+        let pokemon = service.get_pokemon("Pikachu", &lang).await;
+        assert_eq!(pokemon.unwrap().name, "pikachu");
+        assert!(matches!(
+            service.get_pokemon("raichu", &lang).await,
+            Err(AppError::UnknownPokemon { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_flavor_entries_are_cleaned_and_cached() {
+        let server = MockServer::start().await;
+        let mut json = species_json("pikachu", false);
+        json["flavor_text_entries"] = serde_json::json!([
+            {
+                "flavor_text": "When several of\nthese POKéMON\u{c}gather",
+                "language": {"name": "en"},
+                "version": {"name": "red", "url": "u"}
+            },
+            {
+                "flavor_text": "ほっぺたの りょうがわに",
+                "language": {"name": "ja"},
+                "version": {"name": "x", "url": "u"}
+            }
+        ]);
+        Mock::given(method("GET"))
+            .and(path("/pokemon-species/pikachu"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(json),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let pokeapi = PokeApiClient::new(
+            http::build_client(&http::ClientSettings::new(
+                Duration::from_secs(5),
+            )),
+            server.uri(),
+        );
+        let service =
+            PokemonService::new(pokeapi, Duration::from_secs(60));
+        let entries =
+            service.flavor_entries("Pikachu").await.unwrap();
+        assert_eq!(
+            *entries,
+            [
+                FlavorEntry {
+                    version: "red".to_string(),
+                    language: "en".to_string(),
+                    text: "When several of these POKéMON gather"
+                        .to_string(),
+                },
+                FlavorEntry {
+                    version: "x".to_string(),
+                    language: "ja".to_string(),
+                    text: "ほっぺたの りょうがわに".to_string(),
+                },
+            ]
+        );
+        // The species came along with the entries.
+        service.flavor_entries("pikachu").await.unwrap();
+        service
+            .get_pokemon("pikachu", &Lang::default())
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn test_form_kind() {
+        assert_eq!(
+            FormKind::of("charizard", true),
+            FormKind::Default
+        );
+        assert_eq!(
+            FormKind::of("charizard-mega-x", false),
+            FormKind::Mega
+        );
+        assert_eq!(
+            FormKind::of("charizard-gmax", false),
+            FormKind::Gmax
+        );
+        assert_eq!(
+            FormKind::of("vulpix-alola", false),
+            FormKind::Regional
+        );
+        assert_eq!(
+            FormKind::of("darmanitan-galar-zen", false),
+            FormKind::Regional
+        );
+        assert_eq!(
+            FormKind::of("pikachu-rock-star", false),
+            FormKind::Other
+        );
+    }
 
     #[test]
-    fn test_clean_description() {
-        let input = "Line one\nLine two\u{000C}Line three";
-        let expected = "Line one Line two Line three";
-        assert_eq!(clean_description(input), expected);
+    fn test_render_localizes_names() {
+        let localized = |language: &str, value: &str| Localized {
+            language: language.to_string(),
+            value: value.to_string(),
+        };
+        let species = CachedSpecies {
+            pokemon: Pokemon {
+                name: "pikachu".to_string(),
+                display_name: None,
+                genus: None,
+                description: None,
+                description_language: None,
+                habitat: None,
+                is_legendary: false,
+                is_mythical: false,
+                is_baby: false,
+                breeding: None,
+                meta: None,
+                artwork: None,
+                phonetics: None,
+                data_source: None,
+                warnings: Vec::new(),
+            },
+            default_variety: "pikachu".to_string(),
+            gender_rate: Some(4),
+            evolution_chain: Some(10),
+            varieties: Vec::new(),
+            names: vec![
+                localized("en", "Pikachu"),
+                localized("ja", "ピカチュウ"),
+            ],
+            genera: vec![
+                localized("en", "Mouse Pokémon"),
+                localized("it", "Pokémon Topo"),
+            ],
+        };
+
+        let italian = species.render(&Lang("it".to_string()));
+        assert_eq!(italian.display_name.as_deref(), Some("Pikachu"));
+        assert_eq!(italian.genus.as_deref(), Some("Pokémon Topo"));
+
+        let japanese = species.render(&Lang("ja".to_string()));
+        assert_eq!(
+            japanese.display_name.as_deref(),
+            Some("ピカチュウ")
+        );
+        assert_eq!(japanese.genus.as_deref(), Some("Mouse Pokémon"));
     }
 
     #[test]
-    fn test_clean_description_multiple_spaces() {
-        let input = "Word1   Word2     Word3";
-        let expected = "Word1 Word2 Word3";
-        assert_eq!(clean_description(input), expected);
+    fn test_malformed_entries_are_skipped_with_warnings() {
+        let service = PokemonService::new(
+            PokeApiClient::new(
+                http::build_client(&http::ClientSettings::new(
+                    Duration::from_secs(5),
+                )),
+                "http://127.0.0.1:1".to_string(),
+            ),
+            Duration::from_secs(60),
+        );
+        let mut json = species_json("pikachu", false);
+        json["flavor_text_entries"] = serde_json::json!([
+            {"flavor_text": "Broken.", "language": null},
+            {"flavor_text": "Stores electricity.", "language": {"name": "en"}}
+        ]);
+        json["names"] = serde_json::json!([
+            {"name": "Pikachu", "language": {"name": "en"}},
+            {"name": null, "language": {"name": "fr"}}
+        ]);
+
+        let species = service
+            .species
+            .map(serde_json::from_value(json).unwrap());
+        let pokemon = &species.pokemon;
+        assert_eq!(
+            pokemon.description.as_deref(),
+            Some("Stores electricity.")
+        );
+        assert_eq!(species.names.len(), 1);
+        assert_eq!(pokemon.warnings.len(), 2);
+        assert!(
+            pokemon.warnings[0].starts_with(
+                "Skipped malformed flavor_text_entries[0]"
+            )
+        );
+        assert!(
+            pokemon.warnings[1]
+                .starts_with("Skipped malformed names[1]")
+        );
+    }
+
+    #[test]
+    fn test_description_falls_back_to_preferred_language() {
+        let service = PokemonService::new(
+            PokeApiClient::new(
+                http::build_client(&http::ClientSettings::new(
+                    Duration::from_secs(5),
+                )),
+                "http://127.0.0.1:1".to_string(),
+            ),
+            Duration::from_secs(60),
+        )
+        .with_description_fallback(vec![
+            "de".to_string(),
+            "fr".to_string(),
+        ]);
+        let mut json = species_json("pikachu", false);
+        json["flavor_text_entries"] = serde_json::json!([
+            {"flavor_text": "Il stocke l'électricité.", "language": {"name": "fr"}},
+            {"flavor_text": "ピカチュウ", "language": {"name": "ja"}}
+        ]);
+
+        let pokemon = service
+            .species
+            .map(serde_json::from_value(json).unwrap())
+            .pokemon;
+        assert_eq!(
+            pokemon.description.as_deref(),
+            Some("Il stocke l'électricité.")
+        );
+        assert_eq!(
+            pokemon.description_language.as_deref(),
+            Some("fr")
+        );
+
+        let english = service
+            .species
+            .map(
+                serde_json::from_value(species_json("mew", false))
+                    .unwrap(),
+            )
+            .pokemon;
+        assert_eq!(english.description, None);
+        assert_eq!(english.description_language, None);
+    }
+
+    #[test]
+    fn test_with_includes_drops_unrequested_sections() {
+        let pokemon = Pokemon {
+            name: "pikachu".to_string(),
+            display_name: None,
+            genus: None,
+            description: None,
+            description_language: None,
+            habitat: None,
+            is_legendary: false,
+            is_mythical: false,
+            is_baby: false,
+            breeding: Some(Breeding {
+                egg_groups: vec!["ground".to_string()],
+                growth_rate: Some("medium".to_string()),
+            }),
+            meta: Some(Meta {
+                capture_rate: Some(190),
+                base_happiness: Some(50),
+                shape: Some("quadruped".to_string()),
+                color: Some("yellow".to_string()),
+                gender_rate: Some(4),
+                hatch_counter: Some(10),
+                evolution_chain: Some(10),
+            }),
+            artwork: None,
+            phonetics: None,
+            data_source: None,
+            warnings: Vec::new(),
+        };
+
+        let trimmed = Include {
+            breeding: true,
+            ..Include::default()
+        }
+        .apply(pokemon.clone());
+        assert!(trimmed.breeding.is_some());
+        assert!(trimmed.meta.is_none());
+
+        let json =
+            serde_json::to_value(Include::default().apply(pokemon))
+                .unwrap();
+        assert!(json.get("breeding").is_none());
+        assert!(json.get("meta").is_none());
     }
 
     #[test]
     fn test_pokemon_equality() {
         let p1 = Pokemon {
             name: "pikachu".to_string(),
+            display_name: Some("Pikachu".to_string()),
+            genus: Some("Mouse Pokémon".to_string()),
             description: Some("Electric mouse".to_string()),
+            description_language: None,
             habitat: Some("forest".to_string()),
             is_legendary: false,
+            is_mythical: false,
+            is_baby: false,
+            breeding: None,
+            meta: None,
+            artwork: None,
+            phonetics: None,
+            data_source: None,
+            warnings: Vec::new(),
         };
         let p2 = p1.clone();
         assert_eq!(p1, p2);
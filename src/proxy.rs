@@ -0,0 +1,213 @@
+use crate::cache::{Cache, ManagedCache};
+use crate::error::{AppError, Result};
+use crate::listing::MAX_LIMIT;
+use crate::pokeapi::PokeApiClient;
+use serde::Deserialize;
+use serde_json::Value;
+use std::{sync::Arc, time::Duration};
+use tracing::instrument;
+use utoipa::IntoParams;
+
+/// Pagination parameters forwarded to PokeAPI list resources.
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ProxyParams {
+    pub offset: Option<u32>,
+    pub limit: Option<u32>,
+}
+
+/// Read-only passthrough to PokeAPI resources the API does not model,
+/// limited to an allow-list of resource types and sharing the TTL of
+/// the other caches. As the cache is keyed on the caller's query, it
+/// holds at most `PROXY_CACHE_MAX_ENTRIES` responses.
+pub struct ProxyService {
+    pokeapi: PokeApiClient,
+    allowed: Vec<String>,
+    cache: Cache<String, Arc<Value>>,
+}
+
+impl ProxyService {
+    pub fn new(
+        pokeapi: PokeApiClient,
+        allowed: Vec<String>,
+        cache_ttl: Duration,
+        cache_max_entries: usize,
+    ) -> Self {
+        Self {
+            pokeapi,
+            allowed,
+            cache: Cache::new(cache_ttl)
+                .with_max_entries(cache_max_entries),
+        }
+    }
+
+    /// The caches owned by this service, by name.
+    pub fn caches(&self) -> Vec<(&'static str, &dyn ManagedCache)> {
+        vec![("proxy.pokeapi", &self.cache)]
+    }
+
+    /// Fetches `path`, e.g. `move/thunderbolt`, from PokeAPI.
+    #[instrument(skip(self))]
+    pub async fn get(
+        &self,
+        path: &str,
+        params: &ProxyParams,
+    ) -> Result<Arc<Value>> {
+        let path = self.validate(path)?;
+        if params.limit.is_some_and(|limit| {
+            limit == 0 || limit as usize > MAX_LIMIT
+        }) {
+            return Err(AppError::BadRequest(format!(
+                "limit must be between 1 and {}",
+                MAX_LIMIT
+            )));
+        }
+        let mut query = Vec::new();
+        if let Some(offset) = params.offset {
+            query.push(format!("offset={}", offset));
+        }
+        if let Some(limit) = params.limit {
+            query.push(format!("limit={}", limit));
+        }
+        let key = if query.is_empty() {
+            path
+        } else {
+            format!("{}?{}", path, query.join("&"))
+        };
+
+        if let Some(value) = self.cache.get(&key) {
+            return Ok(value);
+        }
+
+        let value: Value = self
+            .pokeapi
+            .get(&key, || {
+                format!("PokeAPI resource '{}' not found", key)
+            })
+            .await?;
+        let value = Arc::new(value);
+        self.cache.insert(key, value.clone());
+        Ok(value)
+    }
+
+    /// Normalizes `path` and checks that it names an allow-listed
+    /// resource type with plain segments only, so it cannot escape
+    /// the PokeAPI base URL.
+    fn validate(&self, path: &str) -> Result<String> {
+        let segments: Vec<&str> = path
+            .trim_matches('/')
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .collect();
+
+        let plain = |segment: &&str| {
+            segment.chars().all(|c| {
+                c.is_ascii_lowercase()
+                    || c.is_ascii_digit()
+                    || c == '-'
+            })
+        };
+        if segments.is_empty()
+            || segments.len() > 2
+            || !segments.iter().all(plain)
+        {
+            return Err(AppError::BadRequest(format!(
+                "Invalid PokeAPI path '{}'",
+                path
+            )));
+        }
+        if !self.allowed.iter().any(|allowed| allowed == segments[0])
+        {
+            return Err(AppError::Forbidden(format!(
+                "PokeAPI resource '{}' is not available through the proxy",
+                segments[0]
+            )));
+        }
+        Ok(segments.join("/"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::Client;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn service(base_url: &str) -> ProxyService {
+        ProxyService::new(
+            PokeApiClient::new(Client::new(), base_url.to_string()),
+            vec!["move".to_string(), "ability".to_string()],
+            Duration::from_secs(60),
+            100,
+        )
+    }
+
+    #[test]
+    fn test_validate() {
+        let service = service("http://pokeapi");
+        assert_eq!(
+            service.validate("/move/thunderbolt/").unwrap(),
+            "move/thunderbolt"
+        );
+        assert!(matches!(
+            service.validate("berry/1"),
+            Err(AppError::Forbidden(_))
+        ));
+        for invalid in ["", "move/../pokemon", "move/a/b", "move/%2e"]
+        {
+            assert!(matches!(
+                service.validate(invalid),
+                Err(AppError::BadRequest(_))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_forwards_and_caches() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/ability"))
+            .and(query_param("limit", "5"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({"count": 1, "results": []}),
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let service = service(&server.uri());
+        let params = ProxyParams {
+            offset: None,
+            limit: Some(5),
+        };
+        for _ in 0..2 {
+            let value =
+                service.get("ability", &params).await.unwrap();
+            assert_eq!(value["count"], 1);
+        }
+        assert_eq!(service.cache.stats().hits, 1);
+    }
+
+    #[tokio::test]
+    async fn test_out_of_range_limit_is_rejected() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let service = service(&server.uri());
+        for limit in [0, 101, 100_000] {
+            let params = ProxyParams {
+                offset: None,
+                limit: Some(limit),
+            };
+            assert!(matches!(
+                service.get("ability", &params).await,
+                Err(AppError::BadRequest(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,351 @@
+use crate::error::{AppError, Result};
+use crate::lang::Lang;
+use crate::pokemon::{Pokemon, PokemonService};
+use crate::random::Randomness;
+use crate::storage::Storage;
+use crate::text::replace_ignore_case;
+use crate::translation::TranslationService;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::{sync::Arc, time::Duration};
+use tracing::{debug, instrument};
+use utoipa::ToSchema;
+
+const NAMESPACE: &str = "quiz";
+const MAX_ATTEMPTS: u32 = 3;
+const MAX_DRAWS: usize = 5;
+const MASK: &str = "???";
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct QuizChallenge {
+    pub id: String,
+    pub description: String,
+    pub genus: Option<String>,
+    pub attempts_allowed: u32,
+    pub expires_in_secs: u64,
+}
+
+#[derive(Debug, Serialize, PartialEq, ToSchema)]
+pub struct GuessResult {
+    pub correct: bool,
+    pub attempts_left: u32,
+    /// Revealed once the quiz is over, whether it was won or lost.
+    pub answer: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct QuizSession {
+    answer: String,
+    attempts: u32,
+}
+
+pub struct QuizService {
+    pokemon_service: Arc<PokemonService>,
+    translation_service: Arc<TranslationService>,
+    storage: Arc<dyn Storage>,
+    ttl: Duration,
+    random: Randomness,
+}
+
+impl QuizService {
+    pub fn new(
+        pokemon_service: Arc<PokemonService>,
+        translation_service: Arc<TranslationService>,
+        storage: Arc<dyn Storage>,
+        ttl: Duration,
+    ) -> Self {
+        Self {
+            pokemon_service,
+            translation_service,
+            storage,
+            ttl,
+            random: Randomness::default(),
+        }
+    }
+
+    /// Draws the Pokemon from `random` rather than unseeded.
+    pub fn with_random(mut self, random: Randomness) -> Self {
+        self.random = random;
+        self
+    }
+
+    /// Draws a random Pokemon with a description and stores a new
+    /// quiz session for it.
+    #[instrument(skip(self))]
+    pub async fn start(
+        &self,
+        translated: bool,
+    ) -> Result<QuizChallenge> {
+        let species = self.pokemon_service.list_species().await?;
+        if species.is_empty() {
+            return Err(AppError::Internal(
+                "No species available for the quiz".to_string(),
+            ));
+        }
+
+        let mut rng = self.random.rng();
+        for _ in 0..MAX_DRAWS {
+            let index = rng.random_range(0..species.len());
+            let pokemon = self
+                .pokemon_service
+                .get_pokemon(&species[index].name, &Lang::default())
+                .await?;
+            let Some(description) =
+                self.describe(&pokemon, translated).await
+            else {
+                debug!(pokemon_name = %pokemon.name, "Skipping Pokemon without description");
+                continue;
+            };
+
+            // Not from `rng`: seeded draws must not make the ids of
+            // other players' sessions guessable.
+            let id = format!("{:032x}", rand::rng().random::<u128>());
+            self.storage.put_as(
+                NAMESPACE,
+                &id,
+                &QuizSession {
+                    answer: pokemon.name.clone(),
+                    attempts: 0,
+                },
+                Some(self.ttl),
+            )?;
+
+            return Ok(QuizChallenge {
+                id,
+                description: obfuscate(&description, &pokemon),
+                genus: pokemon.genus,
+                attempts_allowed: MAX_ATTEMPTS,
+                expires_in_secs: self.ttl.as_secs(),
+            });
+        }
+
+        Err(AppError::Internal(
+            "Could not find a Pokemon with a description".to_string(),
+        ))
+    }
+
+    #[instrument(skip(self))]
+    pub async fn guess(
+        &self,
+        id: &str,
+        name: &str,
+    ) -> Result<GuessResult> {
+        let mut session: QuizSession =
+            self.storage.get_as(NAMESPACE, id)?.ok_or_else(|| {
+                AppError::NotFound(format!(
+                    "Quiz '{}' not found or expired",
+                    id
+                ))
+            })?;
+
+        session.attempts += 1;
+        let correct = normalize(name) == normalize(&session.answer);
+        let attempts_left = MAX_ATTEMPTS - session.attempts;
+
+        if correct || attempts_left == 0 {
+            self.storage.delete(NAMESPACE, id)?;
+            return Ok(GuessResult {
+                correct,
+                attempts_left,
+                answer: Some(session.answer),
+            });
+        }
+
+        self.storage.put_as(
+            NAMESPACE,
+            id,
+            &session,
+            Some(self.ttl),
+        )?;
+        Ok(GuessResult {
+            correct,
+            attempts_left,
+            answer: None,
+        })
+    }
+
+    async fn describe(
+        &self,
+        pokemon: &Pokemon,
+        translated: bool,
+    ) -> Option<String> {
+        let description = pokemon.description.clone()?;
+        if !translated {
+            return Some(description);
+        }
+
+        let style = self
+            .translation_service
+            .style_of(pokemon, &self.pokemon_service)
+            .await;
+        let translation = self
+            .translation_service
+            .translate_with(&description, style)
+            .await;
+        Some(translation.unwrap_or(description))
+    }
+}
+
+fn normalize(name: &str) -> String {
+    name.trim().to_lowercase().replace([' ', '_'], "-")
+}
+
+/// Masks every (case-insensitive) mention of the Pokemon's name.
+fn obfuscate(description: &str, pokemon: &Pokemon) -> String {
+    let mut names = vec![pokemon.name.replace('-', " ")];
+    names.extend(pokemon.display_name.clone());
+
+    names.iter().fold(description.to_string(), |text, name| {
+        replace_ignore_case(&text, name, MASK)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+    use proptest::prelude::*;
+
+    fn pokemon(name: &str, display_name: &str) -> Pokemon {
+        Pokemon {
+            name: name.to_string(),
+            display_name: Some(display_name.to_string()),
+            genus: None,
+            description: None,
+            description_language: None,
+            habitat: None,
+            is_legendary: false,
+            is_mythical: false,
+            is_baby: false,
+            breeding: None,
+            meta: None,
+            artwork: None,
+            phonetics: None,
+            data_source: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    fn service(storage: Arc<dyn Storage>) -> QuizService {
+        QuizService::new(
+            Arc::new(PokemonService::new(
+                crate::pokeapi::PokeApiClient::new(
+                    crate::http::build_client(
+                        &crate::http::ClientSettings::new(
+                            Duration::from_secs(1),
+                        ),
+                    ),
+                    "http://127.0.0.1:9".to_string(),
+                ),
+                Duration::from_secs(60),
+            )),
+            Arc::new(TranslationService::new(
+                "http://127.0.0.1:9".to_string(),
+                crate::http::build_client(
+                    &crate::http::ClientSettings::new(
+                        Duration::from_secs(1),
+                    ),
+                ),
+                Duration::from_secs(60),
+            )),
+            storage,
+            Duration::from_secs(60),
+        )
+    }
+
+    #[test]
+    fn test_obfuscate_masks_names() {
+        let text =
+            "PIKACHU stores electricity. Mr. Mime is not Pikachu.";
+        assert_eq!(
+            obfuscate(text, &pokemon("pikachu", "Pikachu")),
+            "??? stores electricity. Mr. Mime is not ???."
+        );
+        assert_eq!(
+            obfuscate(
+                "MR. MIME mimes.",
+                &pokemon("mr-mime", "Mr. Mime")
+            ),
+            "??? mimes."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_guess_until_exhausted() {
+        let storage: Arc<dyn Storage> =
+            Arc::new(MemoryStorage::new());
+        storage
+            .put_as(
+                NAMESPACE,
+                "q1",
+                &QuizSession {
+                    answer: "mr-mime".to_string(),
+                    attempts: 0,
+                },
+                None,
+            )
+            .unwrap();
+        let quiz = service(storage);
+
+        let first = quiz.guess("q1", "pikachu").await.unwrap();
+        assert!(!first.correct);
+        assert_eq!(first.attempts_left, 2);
+        assert_eq!(first.answer, None);
+
+        let second = quiz.guess("q1", "Mr Mime").await.unwrap();
+        assert!(second.correct);
+        assert_eq!(second.answer.as_deref(), Some("mr-mime"));
+
+        assert!(quiz.guess("q1", "mr-mime").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_last_wrong_guess_reveals_answer() {
+        let storage: Arc<dyn Storage> =
+            Arc::new(MemoryStorage::new());
+        storage
+            .put_as(
+                NAMESPACE,
+                "q1",
+                &QuizSession {
+                    answer: "ditto".to_string(),
+                    attempts: MAX_ATTEMPTS - 1,
+                },
+                None,
+            )
+            .unwrap();
+
+        let result =
+            service(storage).guess("q1", "mew").await.unwrap();
+        assert_eq!(
+            result,
+            GuessResult {
+                correct: false,
+                attempts_left: 0,
+                answer: Some("ditto".to_string()),
+            }
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn test_normalize_is_idempotent(name in any::<String>()) {
+            let normalized = normalize(&name);
+            prop_assert_eq!(normalize(&normalized), normalized.clone());
+            prop_assert!(!normalized.contains([' ', '_']));
+        }
+
+        #[test]
+        fn test_spelled_names_resolve_to_the_species(
+            name in "[a-z0-9]{1,8}(-[a-z0-9]{1,8}){0,2}",
+            separator in prop_oneof![Just(" "), Just("_"), Just("-")],
+            upper: bool,
+        ) {
+            let mut spelled = name.replace('-', separator);
+            if upper {
+                spelled = spelled.to_uppercase();
+            }
+            prop_assert_eq!(normalize(&format!(" {} ", spelled)), name);
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use crate::context;
+use rand::{SeedableRng, rngs::StdRng};
+use std::sync::Mutex;
+
+/// Where the random draws of a request come from: the request's
+/// `X-Random-Seed` when debug headers allow it, else a generator
+/// seeded once with `RANDOM_SEED`, else the thread's generator.
+///
+/// With `RANDOM_SEED`, a server replays the same draws for the same
+/// sequence of requests; with the header, each request replays on
+/// its own.
+#[derive(Default)]
+pub struct Randomness {
+    seeded: Option<Mutex<StdRng>>,
+}
+
+impl Randomness {
+    pub fn new(seed: Option<u64>) -> Self {
+        Self {
+            seeded: seed
+                .map(|seed| Mutex::new(StdRng::seed_from_u64(seed))),
+        }
+    }
+
+    /// A generator for the draws of the current request.
+    pub fn rng(&self) -> StdRng {
+        if let Some(seed) = context::random_seed() {
+            return StdRng::seed_from_u64(seed);
+        }
+        match &self.seeded {
+            Some(seeded) => {
+                StdRng::from_rng(&mut *seeded.lock().unwrap())
+            }
+            None => StdRng::from_rng(&mut rand::rng()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    fn draws(randomness: &Randomness) -> Vec<u32> {
+        (0..3)
+            .map(|_| randomness.rng().random_range(0..1_000_000))
+            .collect()
+    }
+
+    #[test]
+    fn test_seed_replays_the_draws() {
+        assert_eq!(
+            draws(&Randomness::new(Some(7))),
+            draws(&Randomness::new(Some(7)))
+        );
+        assert_ne!(
+            draws(&Randomness::new(Some(7))),
+            draws(&Randomness::new(Some(8)))
+        );
+    }
+}
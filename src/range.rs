@@ -0,0 +1,175 @@
+use axum::{
+    body::{Body, Bytes},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+
+/// The part of a body a `Range` header asks for.
+#[derive(Debug, PartialEq)]
+enum ByteRange {
+    /// No range, or one we do not serve, such as several ranges:
+    /// the whole body.
+    Full,
+    /// The inclusive byte span `start..=end`.
+    Partial(u64, u64),
+    /// A range starting past the end of the body.
+    Unsatisfiable,
+}
+
+/// Answers with `bytes` as `content_type`, honouring a single byte
+/// range in `headers` so that audio players can seek and resume.
+pub fn serve(
+    content_type: &str,
+    bytes: Bytes,
+    headers: &HeaderMap,
+) -> Response {
+    let len = bytes.len() as u64;
+    let range = headers
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok())
+        .map_or(ByteRange::Full, |value| parse(value, len));
+    let content_type = HeaderValue::from_str(content_type).unwrap_or(
+        HeaderValue::from_static("application/octet-stream"),
+    );
+    let accept_ranges = HeaderValue::from_static("bytes");
+
+    match range {
+        ByteRange::Full => (
+            [
+                (header::CONTENT_TYPE, content_type),
+                (header::ACCEPT_RANGES, accept_ranges),
+            ],
+            Body::from(bytes),
+        )
+            .into_response(),
+        ByteRange::Partial(start, end) => (
+            StatusCode::PARTIAL_CONTENT,
+            [
+                (header::CONTENT_TYPE, content_type),
+                (header::ACCEPT_RANGES, accept_ranges),
+                (
+                    header::CONTENT_RANGE,
+                    content_range(&format!(
+                        "bytes {}-{}/{}",
+                        start, end, len
+                    )),
+                ),
+            ],
+            Body::from(bytes.slice(start as usize..=end as usize)),
+        )
+            .into_response(),
+        ByteRange::Unsatisfiable => (
+            StatusCode::RANGE_NOT_SATISFIABLE,
+            [
+                (header::ACCEPT_RANGES, accept_ranges),
+                (
+                    header::CONTENT_RANGE,
+                    content_range(&format!("bytes */{}", len)),
+                ),
+            ],
+        )
+            .into_response(),
+    }
+}
+
+fn content_range(value: &str) -> HeaderValue {
+    HeaderValue::from_str(value).expect("Content-Range is ASCII")
+}
+
+/// Reads a `Range` header against a body of `len` bytes. Headers
+/// that do not parse are ignored, as RFC 9110 asks.
+fn parse(value: &str, len: u64) -> ByteRange {
+    let Some(spec) = value.trim().strip_prefix("bytes=") else {
+        return ByteRange::Full;
+    };
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return ByteRange::Full;
+    };
+
+    let (start, end) = match (start.trim(), end.trim()) {
+        // The last `suffix` bytes.
+        ("", suffix) => match suffix.parse::<u64>() {
+            Ok(0) => return ByteRange::Unsatisfiable,
+            Ok(suffix) => (len.saturating_sub(suffix), len),
+            Err(_) => return ByteRange::Full,
+        },
+        (start, "") => match start.parse::<u64>() {
+            Ok(start) => (start, len),
+            Err(_) => return ByteRange::Full,
+        },
+        (start, end) => {
+            match (start.parse::<u64>(), end.parse::<u64>()) {
+                (Ok(start), Ok(end)) if start <= end => {
+                    (start, end.saturating_add(1).min(len))
+                }
+                _ => return ByteRange::Full,
+            }
+        }
+    };
+    if start >= len {
+        return ByteRange::Unsatisfiable;
+    }
+    ByteRange::Partial(start, end - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse("bytes=0-99", 1000),
+            ByteRange::Partial(0, 99)
+        );
+        assert_eq!(
+            parse("bytes=900-", 1000),
+            ByteRange::Partial(900, 999)
+        );
+        assert_eq!(
+            parse("bytes=-100", 1000),
+            ByteRange::Partial(900, 999)
+        );
+        assert_eq!(
+            parse("bytes=500-5000", 1000),
+            ByteRange::Partial(500, 999)
+        );
+        assert_eq!(
+            parse("bytes=-5000", 1000),
+            ByteRange::Partial(0, 999)
+        );
+        assert_eq!(
+            parse("bytes=1000-", 1000),
+            ByteRange::Unsatisfiable
+        );
+        assert_eq!(parse("bytes=-0", 1000), ByteRange::Unsatisfiable);
+        assert_eq!(parse("bytes=0-", 0), ByteRange::Unsatisfiable);
+        assert_eq!(parse("bytes=0-1,5-9", 1000), ByteRange::Full);
+        assert_eq!(parse("bytes=9-1", 1000), ByteRange::Full);
+        assert_eq!(parse("items=0-1", 1000), ByteRange::Full);
+        assert_eq!(parse("bytes=a-b", 1000), ByteRange::Full);
+    }
+
+    #[test]
+    fn test_serve_partial() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::RANGE, "bytes=2-4".parse().unwrap());
+        let response = serve(
+            "audio/ogg",
+            Bytes::from_static(b"OggS-cry"),
+            &headers,
+        );
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            response.headers()[header::CONTENT_RANGE],
+            "bytes 2-4/8"
+        );
+        assert_eq!(
+            response.headers()[header::ACCEPT_RANGES],
+            "bytes"
+        );
+    }
+}
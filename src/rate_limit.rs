@@ -0,0 +1,274 @@
+use crate::auth::{self, Principal};
+use crate::error::AppError;
+use crate::forwarded::ClientIp;
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, HeaderName, HeaderValue, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+/// Buckets kept before full ones are pruned.
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+pub const RATE_LIMIT_LIMIT: &str = "ratelimit-limit";
+pub const RATE_LIMIT_REMAINING: &str = "ratelimit-remaining";
+pub const RATE_LIMIT_RESET: &str = "ratelimit-reset";
+
+/// The state of a client's bucket after a request.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quota {
+    pub limit: u32,
+    /// Whole tokens left.
+    pub remaining: u32,
+    /// Time until the bucket is full again.
+    pub reset: Duration,
+}
+
+/// A request refused for lack of tokens.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limited {
+    pub quota: Quota,
+    /// Time until the next token is available.
+    pub retry_after: Duration,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token bucket limiter allowing `limit` requests per `period` to
+/// each client, with bursts of up to `limit`.
+pub struct RateLimiter {
+    limit: u32,
+    period: Duration,
+    buckets: Mutex<HashMap<String, Bucket>>,
+    /// Buckets tracked before the next prune, half as many again as
+    /// the last one left, so pruning stays amortized O(1) however
+    /// many clients are active.
+    prune_at: AtomicUsize,
+}
+
+impl RateLimiter {
+    pub fn new(limit: u32, period: Duration) -> Self {
+        Self {
+            limit,
+            period,
+            buckets: Mutex::new(HashMap::new()),
+            prune_at: AtomicUsize::new(MAX_TRACKED_CLIENTS),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.limit > 0
+    }
+
+    /// Takes a token for `client`, or returns how long until one is
+    /// available, along with the bucket state. A limit of zero
+    /// disables limiting.
+    pub fn acquire(&self, client: &str) -> Result<Quota, Limited> {
+        if !self.is_enabled() {
+            return Ok(Quota {
+                limit: 0,
+                remaining: 0,
+                reset: Duration::ZERO,
+            });
+        }
+        let capacity = f64::from(self.limit);
+        let per_sec = capacity / self.period.as_secs_f64();
+        let now = Instant::now();
+
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= self.prune_at.load(Ordering::Relaxed) {
+            buckets.retain(|_, bucket| {
+                let refilled = bucket.tokens
+                    + now
+                        .duration_since(bucket.updated)
+                        .as_secs_f64()
+                        * per_sec;
+                refilled < capacity
+            });
+            self.prune_at.store(
+                (buckets.len() * 3 / 2).max(MAX_TRACKED_CLIENTS),
+                Ordering::Relaxed,
+            );
+        }
+
+        let bucket =
+            buckets.entry(client.to_string()).or_insert(Bucket {
+                tokens: capacity,
+                updated: now,
+            });
+        let elapsed =
+            now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens =
+            (bucket.tokens + elapsed * per_sec).min(capacity);
+        bucket.updated = now;
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+        let quota = Quota {
+            limit: self.limit,
+            remaining: bucket.tokens.floor() as u32,
+            reset: Duration::from_secs_f64(
+                (capacity - bucket.tokens) / per_sec,
+            ),
+        };
+        if allowed {
+            Ok(quota)
+        } else {
+            Err(Limited {
+                quota,
+                retry_after: Duration::from_secs_f64(
+                    (1.0 - bucket.tokens) / per_sec,
+                ),
+            })
+        }
+    }
+}
+
+/// Whole seconds, rounded up so clients never retry too early.
+fn ceil_secs(duration: Duration) -> u64 {
+    duration.as_secs_f64().ceil() as u64
+}
+
+/// Sets the `RateLimit-*` headers from `quota`, unless a stricter
+/// limiter already reported fewer remaining requests.
+fn insert_quota(headers: &mut HeaderMap, quota: Quota) {
+    let stricter = headers
+        .get(RATE_LIMIT_REMAINING)
+        .and_then(|value| value.to_str().ok()?.parse::<u32>().ok())
+        .is_some_and(|remaining| remaining < quota.remaining);
+    if stricter {
+        return;
+    }
+    for (name, value) in [
+        (RATE_LIMIT_LIMIT, u64::from(quota.limit)),
+        (RATE_LIMIT_REMAINING, u64::from(quota.remaining)),
+        (RATE_LIMIT_RESET, ceil_secs(quota.reset)),
+    ] {
+        headers.insert(
+            HeaderName::from_static(name),
+            HeaderValue::from(value),
+        );
+    }
+}
+
+/// Reports the client's quota in `RateLimit-*` headers and rejects
+/// requests over the limit with a `429` and `Retry-After`.
+pub async fn middleware(
+    State(limiter): State<Arc<RateLimiter>>,
+    client: ClientIp,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !limiter.is_enabled() {
+        return next.run(request).await;
+    }
+    // Made-up credentials fall back to the address, so they cannot
+    // dodge the limit.
+    let client = auth::caller_id(
+        request.extensions().get::<Principal>(),
+        client,
+    );
+    match limiter.acquire(&client) {
+        Ok(quota) => {
+            let mut response = next.run(request).await;
+            insert_quota(response.headers_mut(), quota);
+            response
+        }
+        Err(limited) => {
+            let retry_after = ceil_secs(limited.retry_after).max(1);
+            let mut response = AppError::TooManyRequests(format!(
+                "Rate limit exceeded, retry in {} seconds",
+                retry_after
+            ))
+            .into_response();
+            let headers = response.headers_mut();
+            insert_quota(headers, limited.quota);
+            headers.insert(
+                header::RETRY_AFTER,
+                HeaderValue::from(retry_after),
+            );
+            response
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_acquire_limits_each_client() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+        assert_eq!(limiter.acquire("a").unwrap().remaining, 1);
+        let quota = limiter.acquire("a").unwrap();
+        assert_eq!(quota.remaining, 0);
+        assert!(quota.reset > Duration::from_secs(55));
+
+        let limited = limiter.acquire("a").unwrap_err();
+        let wait = limited.retry_after;
+        assert!(wait > Duration::from_secs(25));
+        assert!(wait <= Duration::from_secs(30));
+        assert_eq!(limited.quota.limit, 2);
+        assert!(limiter.acquire("b").is_ok());
+    }
+
+    #[test]
+    fn test_pruning_waits_for_new_clients() {
+        let limiter = RateLimiter::new(1, Duration::from_secs(60));
+        for client in 0..=MAX_TRACKED_CLIENTS {
+            assert!(limiter.acquire(&client.to_string()).is_ok());
+        }
+        // Every bucket was drained, so none was pruned, and the next
+        // prune waits for half as many clients again.
+        let tracked = limiter.buckets.lock().unwrap().len();
+        assert_eq!(tracked, MAX_TRACKED_CLIENTS + 1);
+        assert_eq!(
+            limiter.prune_at.load(Ordering::Relaxed),
+            MAX_TRACKED_CLIENTS * 3 / 2
+        );
+
+        // Full buckets go at the next prune.
+        let limiter = RateLimiter::new(1, Duration::from_nanos(1));
+        for client in 0..=MAX_TRACKED_CLIENTS {
+            limiter.acquire(&client.to_string()).ok();
+        }
+        assert!(limiter.buckets.lock().unwrap().len() < 10);
+    }
+
+    #[test]
+    fn test_insert_quota_keeps_the_stricter_limit() {
+        let quota = |limit, remaining| Quota {
+            limit,
+            remaining,
+            reset: Duration::from_millis(1500),
+        };
+        let mut headers = HeaderMap::new();
+        insert_quota(&mut headers, quota(10, 3));
+        assert_eq!(headers[RATE_LIMIT_RESET], "2");
+
+        insert_quota(&mut headers, quota(100, 50));
+        assert_eq!(headers[RATE_LIMIT_LIMIT], "10");
+        insert_quota(&mut headers, quota(100, 1));
+        assert_eq!(headers[RATE_LIMIT_REMAINING], "1");
+    }
+
+    #[test]
+    fn test_zero_limit_disables_limiting() {
+        let limiter = RateLimiter::new(0, Duration::from_secs(60));
+        assert!((0..100).all(|_| limiter.acquire("a").is_ok()));
+    }
+}
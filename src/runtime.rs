@@ -0,0 +1,45 @@
+use tokio::runtime::{Builder, Runtime};
+
+/// Sizing of the Tokio runtime, for right-sizing the service on
+/// small containers. Unset knobs keep Tokio's defaults: a worker per
+/// core, 512 blocking threads and 1024 IO events per tick.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RuntimeSettings {
+    pub worker_threads: Option<usize>,
+    pub max_blocking_threads: Option<usize>,
+    pub max_io_events_per_tick: Option<usize>,
+}
+
+impl RuntimeSettings {
+    pub fn build(&self) -> std::io::Result<Runtime> {
+        let mut builder = Builder::new_multi_thread();
+        builder.enable_all();
+        if let Some(threads) = self.worker_threads {
+            builder.worker_threads(threads);
+        }
+        if let Some(threads) = self.max_blocking_threads {
+            builder.max_blocking_threads(threads);
+        }
+        if let Some(events) = self.max_io_events_per_tick {
+            builder.max_io_events_per_tick(events);
+        }
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builds_sized_runtime() {
+        let settings = RuntimeSettings {
+            worker_threads: Some(2),
+            max_blocking_threads: Some(4),
+            max_io_events_per_tick: Some(256),
+        };
+        let runtime = settings.build().unwrap();
+        assert_eq!(runtime.metrics().num_workers(), 2);
+        assert_eq!(runtime.block_on(async { 1 + 1 }), 2);
+    }
+}
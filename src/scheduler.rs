@@ -0,0 +1,177 @@
+use crate::error::Result;
+use crate::metrics::Metrics;
+use chrono::Utc;
+use cron::Schedule;
+use futures::future::BoxFuture;
+use std::{
+    str::FromStr,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Instant,
+};
+use tracing::{info, warn};
+
+/// Parses a cron expression in UTC: the standard five fields
+/// (`*/15 * * * *`) or six with leading seconds.
+pub fn parse(
+    expression: &str,
+) -> std::result::Result<Schedule, String> {
+    let expression = expression.trim();
+    let expression = if expression.split_whitespace().count() == 5 {
+        format!("0 {}", expression)
+    } else {
+        expression.to_string()
+    };
+    Schedule::from_str(&expression).map_err(|e| e.to_string())
+}
+
+type Task =
+    Box<dyn Fn() -> BoxFuture<'static, Result<()>> + Send + Sync>;
+
+struct Job {
+    name: &'static str,
+    schedule: Schedule,
+    task: Task,
+    /// Set while a run is in progress, so runs never overlap.
+    running: AtomicBool,
+}
+
+impl Job {
+    /// Starts a run unless the previous one is still in progress,
+    /// returning whether it started.
+    fn trigger(self: &Arc<Self>, metrics: &Arc<Metrics>) -> bool {
+        if self.running.swap(true, Ordering::AcqRel) {
+            warn!(
+                job = self.name,
+                "Skipping run, the previous one is still in progress"
+            );
+            metrics.record_job(self.name, "skipped");
+            return false;
+        }
+
+        let job = self.clone();
+        let metrics = metrics.clone();
+        let run = (self.task)();
+        tokio::spawn(async move {
+            let started = Instant::now();
+            let result = run.await;
+            job.running.store(false, Ordering::Release);
+            let elapsed_ms = started.elapsed().as_millis() as u64;
+            match result {
+                Ok(()) => {
+                    info!(job = job.name, elapsed_ms, "Job finished");
+                    metrics.record_job(job.name, "ok");
+                }
+                Err(e) => {
+                    warn!(job = job.name, elapsed_ms, error = %e, "Job failed");
+                    metrics.record_job(job.name, "error");
+                }
+            }
+        });
+        true
+    }
+}
+
+/// Runs named jobs on cron schedules. A run due while the previous
+/// one is still in progress is skipped.
+pub struct Scheduler {
+    jobs: Vec<Arc<Job>>,
+    metrics: Arc<Metrics>,
+}
+
+impl Scheduler {
+    pub fn new(metrics: Arc<Metrics>) -> Self {
+        Self {
+            jobs: Vec::new(),
+            metrics,
+        }
+    }
+
+    pub fn register<F, Fut>(
+        &mut self,
+        name: &'static str,
+        schedule: Schedule,
+        task: F,
+    ) where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.jobs.push(Arc::new(Job {
+            name,
+            schedule,
+            task: Box::new(move || Box::pin(task())),
+            running: AtomicBool::new(false),
+        }));
+    }
+
+    /// Runs every job at its scheduled times from now on.
+    pub fn start(self) {
+        for job in self.jobs {
+            info!(job = job.name, schedule = %job.schedule, "Scheduled job");
+            let metrics = self.metrics.clone();
+            tokio::spawn(async move {
+                while let Some(next) =
+                    job.schedule.upcoming(Utc).next()
+                {
+                    let wait = (next - Utc::now())
+                        .to_std()
+                        .unwrap_or_default();
+                    tokio::time::sleep(wait).await;
+                    job.trigger(&metrics);
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::Notify;
+
+    #[test]
+    fn test_parse_accepts_five_and_six_fields() {
+        let every_quarter = parse("*/15 * * * *").unwrap();
+        assert_eq!(every_quarter, parse("0 */15 * * * *").unwrap());
+        assert!(parse("0 9 * * MON").is_ok());
+        assert!(parse("every hour").is_err());
+        assert!(parse("61 * * * *").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_runs_do_not_overlap() {
+        let metrics = Arc::new(Metrics::new());
+        let release = Arc::new(Notify::new());
+        let finished = Arc::new(Notify::new());
+        let mut scheduler = Scheduler::new(metrics.clone());
+        let (release_run, finished_run) =
+            (release.clone(), finished.clone());
+        scheduler.register(
+            "slow",
+            parse("* * * * *").unwrap(),
+            move || {
+                let (release, finished) =
+                    (release_run.clone(), finished_run.clone());
+                async move {
+                    release.notified().await;
+                    finished.notify_one();
+                    Ok(())
+                }
+            },
+        );
+        let job = scheduler.jobs[0].clone();
+
+        assert!(job.trigger(&metrics));
+        assert!(!job.trigger(&metrics));
+        release.notify_one();
+        finished.notified().await;
+        assert!(job.trigger(&metrics));
+
+        let text = metrics.render(&[]);
+        assert!(text.contains(
+            r#"scheduled_job_runs_total{job="slow",outcome="skipped"} 1"#
+        ));
+    }
+}
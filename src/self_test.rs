@@ -0,0 +1,186 @@
+use crate::cache_store::CacheStore;
+use crate::error::{AppError, Result};
+use crate::lang::Lang;
+use crate::pokemon::PokemonService;
+use crate::translation::{Style, TranslationService};
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// Pokemon fetched by the self-test.
+const POKEMON: &str = "pikachu";
+
+/// Text translated by the self-test.
+const SAMPLE: &str = "Master Obiwan has lost a planet.";
+
+/// One step of the self-test and what it found.
+struct Step {
+    name: &'static str,
+    elapsed: Duration,
+    result: Result<String>,
+}
+
+/// Outcome of `--self-test`, printed before exiting.
+pub struct Report {
+    steps: Vec<Step>,
+}
+
+impl Report {
+    pub fn passed(&self) -> bool {
+        self.steps.iter().all(|step| step.result.is_ok())
+    }
+
+    async fn step(
+        &mut self,
+        name: &'static str,
+        step: impl Future<Output = Result<String>>,
+    ) {
+        let started = Instant::now();
+        let result = step.await;
+        self.steps.push(Step {
+            name,
+            elapsed: started.elapsed(),
+            result,
+        });
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for step in &self.steps {
+            let (outcome, detail) = match &step.result {
+                Ok(detail) => ("ok", detail.clone()),
+                Err(e) => ("FAILED", e.to_string()),
+            };
+            writeln!(
+                f,
+                "{:<24} {:<6} {:>6} ms  {}",
+                step.name,
+                outcome,
+                step.elapsed.as_millis(),
+                detail
+            )?;
+        }
+        let failed = self
+            .steps
+            .iter()
+            .filter(|step| step.result.is_err())
+            .count();
+        write!(
+            f,
+            "self-test {}: {} of {} steps failed",
+            if failed == 0 { "passed" } else { "failed" },
+            failed,
+            self.steps.len()
+        )
+    }
+}
+
+/// Runs a fixed sequence against the configured upstreams: fetching
+/// a Pokemon, translating a sample and reading both back from the
+/// caches.
+pub async fn run(
+    pokemon: &PokemonService,
+    translation: &TranslationService,
+    store: Option<&dyn CacheStore>,
+) -> Report {
+    let mut report = Report { steps: Vec::new() };
+    let lang = Lang::default();
+
+    report
+        .step("fetch pikachu", async {
+            let fetched = pokemon.get_pokemon(POKEMON, &lang).await?;
+            Ok(format!(
+                "'{}', habitat {}",
+                fetched.name,
+                fetched.habitat.as_deref().unwrap_or("unknown")
+            ))
+        })
+        .await;
+
+    report
+        .step("translate sample", async {
+            let translated = translation
+                .translate_with(SAMPLE, Style::Yoda)
+                .await?;
+            Ok(format!("'{}'", translated))
+        })
+        .await;
+
+    report
+        .step("cache round-trip", async {
+            let hits = || {
+                pokemon
+                    .caches()
+                    .iter()
+                    .map(|(_, cache)| cache.stats().hits)
+                    .sum::<u64>()
+            };
+            let before = hits();
+            pokemon.get_pokemon(POKEMON, &lang).await?;
+            if hits() > before {
+                Ok("second fetch served from memory".to_string())
+            } else {
+                Err(AppError::Internal(
+                    "second fetch missed the cache".to_string(),
+                ))
+            }
+        })
+        .await;
+
+    if let Some(store) = store {
+        report
+            .step("cache store round-trip", async {
+                let value = POKEMON.as_bytes().to_vec();
+                store
+                    .set(
+                        "self-test",
+                        value.clone(),
+                        Duration::from_secs(60),
+                    )
+                    .await?;
+                match store.get("self-test").await? {
+                    Some(read) if read == value => {
+                        Ok("value read back".to_string())
+                    }
+                    _ => Err(AppError::Internal(
+                        "value not read back".to_string(),
+                    )),
+                }
+            })
+            .await;
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_report_fails_with_any_step() {
+        let mut report = Report { steps: Vec::new() };
+        report
+            .step("fetch pikachu", async {
+                Ok("'pikachu'".to_string())
+            })
+            .await;
+        assert!(report.passed());
+
+        report
+            .step("translate sample", async {
+                Err(AppError::Timeout("no answer".to_string()))
+            })
+            .await;
+        assert!(!report.passed());
+        let printed = report.to_string();
+        assert!(printed.contains("translate sample"));
+        assert!(printed.contains("FAILED"));
+        assert!(printed.contains("Timeout: no answer"));
+        assert!(
+            printed
+                .ends_with("self-test failed: 1 of 2 steps failed")
+        );
+    }
+}
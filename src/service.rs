@@ -0,0 +1,124 @@
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use std::{ffi::OsString, sync::Mutex, time::Duration};
+use tokio::sync::Notify;
+use tracing::error;
+use windows_service::{
+    define_windows_service,
+    service::{
+        ServiceControl, ServiceControlAccept, ServiceExitCode,
+        ServiceState, ServiceStatus, ServiceType,
+    },
+    service_control_handler::{self, ServiceControlHandlerResult},
+    service_dispatcher,
+};
+
+/// Name the service is registered under with `sc.exe create`.
+pub const SERVICE_NAME: &str = "pokedex";
+
+/// The configuration and entry point of the server, handed over to
+/// the service main function, which the dispatcher calls without
+/// arguments of ours.
+type Server = (Config, fn(Config) -> Result<()>);
+
+static SERVER: Mutex<Option<Server>> = Mutex::new(None);
+
+/// Raised by the service control manager's stop and shutdown
+/// requests.
+static STOP: Notify = Notify::const_new();
+
+define_windows_service!(ffi_service_main, service_main);
+
+/// Runs `serve` with `config` as the Windows service `SERVICE_NAME`
+/// for `--service`, until the service control manager stops it.
+/// Services have no console, so the logs need `LOG_FILE`.
+pub fn run(
+    config: Config,
+    serve: fn(Config) -> Result<()>,
+) -> Result<()> {
+    if config.log.file.is_none() {
+        return Err(AppError::Internal(
+            "--service requires LOG_FILE".to_string(),
+        ));
+    }
+    *SERVER.lock().unwrap() = Some((config, serve));
+    // Blocks until the service has stopped.
+    service_dispatcher::start(SERVICE_NAME, ffi_service_main).map_err(
+        |e| {
+            AppError::Internal(format!(
+                "Failed to start the service: {}",
+                e
+            ))
+        },
+    )
+}
+
+/// Resolves once the service control manager asks the service to
+/// stop; never when not running as a service.
+pub async fn stopped() {
+    STOP.notified().await
+}
+
+fn service_main(_arguments: Vec<OsString>) {
+    if let Err(e) = serve() {
+        error!(error = %e, "Service failed");
+    }
+}
+
+fn serve() -> Result<()> {
+    let handler = |control: ServiceControl| match control {
+        ServiceControl::Stop | ServiceControl::Shutdown => {
+            STOP.notify_one();
+            ServiceControlHandlerResult::NoError
+        }
+        ServiceControl::Interrogate => {
+            ServiceControlHandlerResult::NoError
+        }
+        _ => ServiceControlHandlerResult::NotImplemented,
+    };
+    let status =
+        service_control_handler::register(SERVICE_NAME, handler)
+            .map_err(|e| {
+                AppError::Internal(format!(
+                    "Failed to register the service: {}",
+                    e
+                ))
+            })?;
+    let report = |state, controls_accepted, exit_code| {
+        let result = status.set_service_status(ServiceStatus {
+            service_type: ServiceType::OWN_PROCESS,
+            current_state: state,
+            controls_accepted,
+            exit_code,
+            checkpoint: 0,
+            wait_hint: Duration::default(),
+            process_id: None,
+        });
+        if let Err(e) = result {
+            error!(error = %e, "Failed to report the service status");
+        }
+    };
+
+    report(
+        ServiceState::Running,
+        ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+        ServiceExitCode::NO_ERROR,
+    );
+    let server = SERVER.lock().unwrap().take();
+    let result = match server {
+        Some((config, serve)) => serve(config),
+        None => Err(AppError::Internal(
+            "The service was started twice".to_string(),
+        )),
+    };
+    let exit_code = match &result {
+        Ok(()) => ServiceExitCode::NO_ERROR,
+        Err(_) => ServiceExitCode::ServiceSpecific(1),
+    };
+    report(
+        ServiceState::Stopped,
+        ServiceControlAccept::empty(),
+        exit_code,
+    );
+    result
+}
@@ -0,0 +1,207 @@
+use crate::error::{AppError, Result};
+use crate::http::Upstream;
+use crate::metrics::Metrics;
+use rand::Rng;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tracing::{debug, warn};
+
+/// Most shadow requests in flight; calls sampled past it are not
+/// mirrored, so a slow shadow never piles up work.
+const MAX_IN_FLIGHT: usize = 32;
+
+/// Most differing paths logged for one comparison.
+const MAX_DIFFS: usize = 10;
+
+/// Mirrors a share of PokeAPI calls to an alternate base URL in the
+/// background, comparing its payloads with the ones served. Responses
+/// never wait on, or change with, the shadow.
+pub struct Shadow {
+    upstream: Upstream,
+    base_url: String,
+    /// Percentage of calls mirrored.
+    rate: f64,
+    metrics: Arc<Metrics>,
+    in_flight: Arc<Semaphore>,
+}
+
+impl Shadow {
+    pub fn new(
+        upstream: Upstream,
+        base_url: String,
+        rate: f64,
+        metrics: Arc<Metrics>,
+    ) -> Self {
+        Self {
+            upstream,
+            base_url,
+            rate,
+            metrics,
+            in_flight: Arc::new(Semaphore::new(MAX_IN_FLIGHT)),
+        }
+    }
+
+    /// Whether the current call is mirrored.
+    pub fn sampled(&self) -> bool {
+        rand::rng().random_range(0.0..100.0) < self.rate
+    }
+
+    /// Fetches `path` from the shadow and compares it with `primary`,
+    /// the body served for it, also checking that the shadow body
+    /// still reads into a `T`.
+    pub fn mirror<T: DeserializeOwned + 'static>(
+        self: &Arc<Self>,
+        path: &str,
+        primary: Vec<u8>,
+    ) {
+        let Ok(permit) = self.in_flight.clone().try_acquire_owned()
+        else {
+            debug!(path, "Shadow is saturated, not mirroring");
+            return;
+        };
+        let shadow = self.clone();
+        let path = path.to_string();
+        tokio::spawn(async move {
+            let outcome = shadow.compare::<T>(&path, &primary).await;
+            drop(permit);
+            shadow.metrics.record_shadow(outcome);
+        });
+    }
+
+    async fn compare<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        primary: &[u8],
+    ) -> &'static str {
+        let url = format!("{}/{}", self.base_url, path);
+        let body = match self.fetch(&url).await {
+            Ok(body) => body,
+            Err(e) => {
+                warn!(path, error = %e, "Shadow request failed");
+                return "error";
+            }
+        };
+        if let Err(e) = self.upstream.parse_json::<T>(&body) {
+            warn!(path, error = %e, "Shadow payload does not deserialize");
+            return "error";
+        }
+
+        let (Ok(primary), Ok(shadow)) = (
+            serde_json::from_slice::<Value>(primary),
+            serde_json::from_slice::<Value>(&body),
+        ) else {
+            return "error";
+        };
+        let diffs = diff(&primary, &shadow);
+        if diffs.is_empty() {
+            debug!(path, "Shadow payload matches");
+            return "match";
+        }
+        warn!(path, ?diffs, "Shadow payload differs");
+        "differ"
+    }
+
+    async fn fetch(&self, url: &str) -> Result<Vec<u8>> {
+        let response =
+            self.upstream.send(self.upstream.get(url)).await?;
+        if !response.status().is_success() {
+            return Err(AppError::ExternalApi(format!(
+                "shadow returned status: {}",
+                response.status()
+            )));
+        }
+        self.upstream.read_bytes(response).await
+    }
+}
+
+/// The JSON paths at which `primary` and `shadow` differ, e.g.
+/// `/names/3/name`, at most `MAX_DIFFS` of them.
+pub fn diff(primary: &Value, shadow: &Value) -> Vec<String> {
+    let mut diffs = Vec::new();
+    collect_diffs(primary, shadow, &mut String::new(), &mut diffs);
+    diffs
+}
+
+fn collect_diffs(
+    primary: &Value,
+    shadow: &Value,
+    path: &mut String,
+    diffs: &mut Vec<String>,
+) {
+    if diffs.len() >= MAX_DIFFS {
+        return;
+    }
+    let len = path.len();
+    match (primary, shadow) {
+        (Value::Object(a), Value::Object(b)) => {
+            let mut keys: Vec<_> = a.keys().chain(b.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                path.push('/');
+                path.push_str(key);
+                match (a.get(key), b.get(key)) {
+                    (Some(a), Some(b)) => {
+                        collect_diffs(a, b, path, diffs)
+                    }
+                    _ if diffs.len() < MAX_DIFFS => {
+                        diffs.push(path.clone())
+                    }
+                    _ => {}
+                }
+                path.truncate(len);
+            }
+        }
+        (Value::Array(a), Value::Array(b)) if a.len() == b.len() => {
+            for (i, (a, b)) in a.iter().zip(b).enumerate() {
+                path.push('/');
+                path.push_str(&i.to_string());
+                collect_diffs(a, b, path, diffs);
+                path.truncate(len);
+            }
+        }
+        (a, b) if a != b => diffs.push(if path.is_empty() {
+            "/".to_string()
+        } else {
+            path.clone()
+        }),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_diff_reports_changed_paths() {
+        let primary = json!({
+            "name": "pikachu",
+            "names": [{"name": "Pikachu"}, {"name": "ピカチュウ"}],
+            "is_legendary": false,
+        });
+        assert!(diff(&primary, &primary).is_empty());
+
+        let shadow = json!({
+            "name": "pikachu",
+            "names": [{"name": "Pikachu"}, {"name": "Pika"}],
+            "is_mythical": false,
+        });
+        assert_eq!(
+            diff(&primary, &shadow),
+            ["/is_legendary", "/is_mythical", "/names/1/name"]
+        );
+        assert_eq!(diff(&json!([1, 2]), &json!([1])), ["/"]);
+
+        let many: serde_json::Map<_, _> = (0..20)
+            .map(|i| (format!("field{:02}", i), json!(i)))
+            .collect();
+        assert_eq!(
+            diff(&Value::Object(many), &json!({})).len(),
+            MAX_DIFFS
+        );
+    }
+}
@@ -0,0 +1,160 @@
+use crate::cache::{ManagedCache, SavedEntry};
+use crate::error::{AppError, Result};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::Path};
+use tracing::info;
+
+/// Bumped when the file layout changes; older snapshots are ignored.
+const VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    version: u32,
+    /// Entries by cache name.
+    caches: BTreeMap<String, Vec<SavedEntry>>,
+}
+
+/// Writes the live entries of `caches` to `path`. The file is
+/// replaced atomically, so a crash mid-write keeps the previous one.
+pub async fn save(
+    path: &Path,
+    caches: &[(&'static str, &dyn ManagedCache)],
+) -> Result<usize> {
+    let snapshot = Snapshot {
+        version: VERSION,
+        caches: caches
+            .iter()
+            .map(|(name, cache)| (name.to_string(), cache.save()))
+            .collect(),
+    };
+    let entries = snapshot.caches.values().map(Vec::len).sum();
+    let json = serde_json::to_vec(&snapshot).map_err(|e| {
+        AppError::Internal(format!(
+            "Failed to serialize cache snapshot: {}",
+            e
+        ))
+    })?;
+
+    let temp = path.with_extension("tmp");
+    let io_error = |e: std::io::Error| {
+        AppError::Internal(format!(
+            "Failed to write cache snapshot {}: {}",
+            path.display(),
+            e
+        ))
+    };
+    tokio::fs::write(&temp, json).await.map_err(io_error)?;
+    tokio::fs::rename(&temp, path).await.map_err(io_error)?;
+    info!(path = %path.display(), entries, "Saved cache snapshot");
+    Ok(entries)
+}
+
+/// Reads the snapshot at `path`: `None` when there is none, or it
+/// is outdated.
+async fn read(path: &Path) -> Result<Option<Snapshot>> {
+    let json = match tokio::fs::read(path).await {
+        Ok(json) => json,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(None);
+        }
+        Err(e) => {
+            return Err(AppError::Internal(format!(
+                "Failed to read cache snapshot {}: {}",
+                path.display(),
+                e
+            )));
+        }
+    };
+    let snapshot: Snapshot =
+        serde_json::from_slice(&json).map_err(|e| {
+            AppError::Internal(format!(
+                "Invalid cache snapshot {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+    if snapshot.version != VERSION {
+        info!(
+            version = snapshot.version,
+            "Ignoring outdated cache snapshot"
+        );
+        return Ok(None);
+    }
+    Ok(Some(snapshot))
+}
+
+/// Loads the snapshot at `path` into `caches`, skipping expired
+/// entries and unknown caches. A missing file loads nothing.
+pub async fn load(
+    path: &Path,
+    caches: &[(&'static str, &dyn ManagedCache)],
+) -> Result<usize> {
+    let Some(mut snapshot) = read(path).await? else {
+        return Ok(0);
+    };
+
+    let mut loaded = 0;
+    for (name, cache) in caches {
+        if let Some(entries) = snapshot.caches.remove(*name) {
+            loaded += cache.load(entries);
+        }
+    }
+    info!(path = %path.display(), entries = loaded, "Loaded cache snapshot");
+    Ok(loaded)
+}
+
+/// Every entry of the cache `name` in the snapshot at `path`,
+/// expired ones included.
+pub async fn read_cache(
+    path: &Path,
+    name: &str,
+) -> Result<Vec<SavedEntry>> {
+    Ok(read(path)
+        .await?
+        .and_then(|mut snapshot| snapshot.caches.remove(name))
+        .unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::Cache;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_snapshot_round_trip() {
+        let path = std::env::temp_dir().join(format!(
+            "pokedex-snapshot-{}.json",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let species: Cache<String, String> =
+            Cache::new(Duration::from_secs(60));
+        let types: Cache<String, u32> =
+            Cache::new(Duration::from_secs(60));
+        species.insert("pikachu".to_string(), "mouse".to_string());
+        types.insert("electric".to_string(), 13);
+
+        assert_eq!(
+            load(&path, &[("species", &species)]).await.unwrap(),
+            0
+        );
+        let saved =
+            save(&path, &[("species", &species), ("types", &types)])
+                .await
+                .unwrap();
+        assert_eq!(saved, 2);
+
+        let restarted: Cache<String, String> =
+            Cache::new(Duration::from_secs(60));
+        assert_eq!(
+            load(&path, &[("species", &restarted)]).await.unwrap(),
+            1
+        );
+        assert_eq!(
+            restarted.get(&"pikachu".to_string()).as_deref(),
+            Some("mouse")
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
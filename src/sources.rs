@@ -0,0 +1,439 @@
+use crate::error::{AppError, Result};
+use crate::pokemon::{CachedSpecies, SpeciesCache, SpeciesClient};
+use crate::snapshot;
+use crate::storage::Storage;
+use axum::async_trait;
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+use tracing::{debug, info, warn};
+
+/// Storage namespace of the species kept by `SourceKind::Storage`.
+const NAMESPACE: &str = "species";
+
+/// Where species data comes from: PokeAPI, a copy of it, or a set
+/// of fixtures. The service behind the handlers looks species up in
+/// one source, usually a `SourceChain` of several. Each source is
+/// given what it reads from when it is built.
+#[async_trait]
+pub trait PokemonSource: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Whether species found here are fresh, and so written to
+    /// every other source of a chain rather than only to those
+    /// before it.
+    fn is_origin(&self) -> bool {
+        false
+    }
+
+    /// `None` when the source does not have the species. A
+    /// `NotFound` error ends the lookup of a chain.
+    async fn get(
+        &self,
+        key: &str,
+    ) -> Result<Option<Arc<CachedSpecies>>>;
+
+    /// Keeps a species found elsewhere, if the source keeps any.
+    /// Failures are logged.
+    async fn put(&self, _key: &str, _species: &Arc<CachedSpecies>) {}
+}
+
+/// The sources `SPECIES_SOURCES` may list.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SourceKind {
+    /// The in-memory species cache, with its second level.
+    Cache,
+    /// The `STORAGE_PATH` database.
+    Storage,
+    /// Every species of `CACHE_SNAPSHOT_FILE`, expired or not.
+    Snapshot,
+    /// PokeAPI species documents in `SPECIES_FIXTURES_DIR`.
+    Fixtures,
+    PokeApi,
+}
+
+impl SourceKind {
+    /// Parses a comma-separated order, e.g. `cache,storage,pokeapi`,
+    /// which lists `cache` once.
+    pub fn parse_list(
+        value: &str,
+    ) -> std::result::Result<Vec<Self>, String> {
+        let mut kinds = Vec::new();
+        for name in value.split(',').map(str::trim) {
+            let kind = match name.to_lowercase().as_str() {
+                "cache" => SourceKind::Cache,
+                "storage" => SourceKind::Storage,
+                "snapshot" => SourceKind::Snapshot,
+                "fixtures" => SourceKind::Fixtures,
+                "pokeapi" => SourceKind::PokeApi,
+                _ => {
+                    return Err(format!(
+                        "unknown source '{}', expected cache, storage, snapshot, fixtures or pokeapi",
+                        name
+                    ));
+                }
+            };
+            if kinds.contains(&kind) {
+                return Err(format!("'{}' is listed twice", name));
+            }
+            kinds.push(kind);
+        }
+        if !kinds.contains(&SourceKind::Cache) {
+            return Err("'cache' is not listed".to_string());
+        }
+        Ok(kinds)
+    }
+}
+
+/// What the sources of `SPECIES_SOURCES` are read from.
+pub struct SourceSettings {
+    /// The species cache of the service, fully configured.
+    pub cache: Arc<SpeciesCache>,
+    /// Fetches species for `pokeapi` and parses them for `fixtures`.
+    pub species: SpeciesClient,
+    pub storage: Arc<dyn Storage>,
+    /// How long species are kept in storage, forever when zero.
+    pub storage_ttl: Duration,
+    pub snapshot_file: Option<PathBuf>,
+    pub fixtures_dir: PathBuf,
+}
+
+/// Opens the sources of `kinds`, chained in that order.
+pub async fn open(
+    kinds: &[SourceKind],
+    settings: &SourceSettings,
+) -> Result<Arc<dyn PokemonSource>> {
+    let mut sources: Vec<Arc<dyn PokemonSource>> = Vec::new();
+    for kind in kinds {
+        sources.push(match kind {
+            SourceKind::Cache => Arc::new(CacheSource {
+                cache: settings.cache.clone(),
+            }),
+            SourceKind::Storage => Arc::new(StorageSource {
+                storage: settings.storage.clone(),
+                ttl: (!settings.storage_ttl.is_zero())
+                    .then_some(settings.storage_ttl),
+            }),
+            SourceKind::Snapshot => {
+                let path =
+                    settings.snapshot_file.as_ref().ok_or_else(|| {
+                        AppError::Internal(
+                            "The snapshot source needs CACHE_SNAPSHOT_FILE"
+                                .to_string(),
+                        )
+                    })?;
+                Arc::new(SnapshotSource::open(path).await?)
+            }
+            SourceKind::Fixtures => Arc::new(FixtureSource::open(
+                &settings.fixtures_dir,
+                settings.species.clone(),
+            )?),
+            SourceKind::PokeApi => Arc::new(PokeApiSource {
+                species: settings.species.clone(),
+            }),
+        });
+    }
+    Ok(Arc::new(SourceChain::new(sources)))
+}
+
+/// Sources looked up in turn. A hit is written back to the sources
+/// before it, or to all of them when it is fresh from the origin.
+/// Failing sources are skipped, so a later one can stand in for
+/// them; when none has the species, the first error stands.
+pub struct SourceChain {
+    sources: Vec<Arc<dyn PokemonSource>>,
+}
+
+impl SourceChain {
+    pub fn new(sources: Vec<Arc<dyn PokemonSource>>) -> Self {
+        Self { sources }
+    }
+
+    /// `cache`, then PokeAPI through `species`.
+    pub fn cached(
+        cache: Arc<SpeciesCache>,
+        species: SpeciesClient,
+    ) -> Self {
+        Self::new(vec![
+            Arc::new(CacheSource { cache }),
+            Arc::new(PokeApiSource { species }),
+        ])
+    }
+}
+
+#[async_trait]
+impl PokemonSource for SourceChain {
+    fn name(&self) -> &'static str {
+        "chain"
+    }
+
+    async fn get(
+        &self,
+        key: &str,
+    ) -> Result<Option<Arc<CachedSpecies>>> {
+        let mut error = None;
+        for (index, source) in self.sources.iter().enumerate() {
+            let species = match source.get(key).await {
+                Ok(Some(species)) => species,
+                Ok(None) => continue,
+                Err(e @ AppError::NotFound(_)) => return Err(e),
+                Err(e) => {
+                    warn!(source = source.name(), species = %key, error = %e, "Species source failed");
+                    error.get_or_insert(e);
+                    continue;
+                }
+            };
+            debug!(source = source.name(), species = %key, "Species found");
+            let fill = if source.is_origin() {
+                self.sources.len()
+            } else {
+                index
+            };
+            for (other, target) in
+                self.sources[..fill].iter().enumerate()
+            {
+                if other != index {
+                    target.put(key, &species).await;
+                }
+            }
+            return Ok(Some(species));
+        }
+        error.map_or(Ok(None), Err)
+    }
+
+    /// Writes a species fresh from the origin to every other source.
+    async fn put(&self, key: &str, species: &Arc<CachedSpecies>) {
+        for source in self.sources.iter().filter(|s| !s.is_origin()) {
+            source.put(key, species).await;
+        }
+    }
+}
+
+struct CacheSource {
+    cache: Arc<SpeciesCache>,
+}
+
+#[async_trait]
+impl PokemonSource for CacheSource {
+    fn name(&self) -> &'static str {
+        "cache"
+    }
+
+    async fn get(
+        &self,
+        key: &str,
+    ) -> Result<Option<Arc<CachedSpecies>>> {
+        Ok(self.cache.fetch(&key.to_string()).await)
+    }
+
+    async fn put(&self, key: &str, species: &Arc<CachedSpecies>) {
+        self.cache.store(key.to_string(), species.clone()).await;
+    }
+}
+
+struct StorageSource {
+    storage: Arc<dyn Storage>,
+    ttl: Option<Duration>,
+}
+
+#[async_trait]
+impl PokemonSource for StorageSource {
+    fn name(&self) -> &'static str {
+        "storage"
+    }
+
+    async fn get(
+        &self,
+        key: &str,
+    ) -> Result<Option<Arc<CachedSpecies>>> {
+        Ok(self
+            .storage
+            .get_as::<CachedSpecies>(NAMESPACE, key)?
+            .map(Arc::new))
+    }
+
+    async fn put(&self, key: &str, species: &Arc<CachedSpecies>) {
+        if let Err(e) = self.storage.put_as(
+            NAMESPACE,
+            key,
+            species.as_ref(),
+            self.ttl,
+        ) {
+            warn!(species = %key, error = %e, "Failed to store species");
+        }
+    }
+}
+
+/// The species of a cache snapshot, for serving offline. Unlike the
+/// snapshot loaded into the caches on startup, expired species are
+/// kept. Read-only.
+pub struct SnapshotSource {
+    species: HashMap<String, Arc<CachedSpecies>>,
+}
+
+impl SnapshotSource {
+    pub async fn open(path: &Path) -> Result<Self> {
+        let entries =
+            snapshot::read_cache(path, "pokemon.species").await?;
+        let species: HashMap<_, _> = entries
+            .into_iter()
+            .filter_map(|entry| {
+                Some((
+                    serde_json::from_value(entry.key).ok()?,
+                    Arc::new(
+                        serde_json::from_value(entry.value).ok()?,
+                    ),
+                ))
+            })
+            .collect();
+        info!(path = %path.display(), count = species.len(), "Loaded snapshot species");
+        Ok(Self { species })
+    }
+}
+
+#[async_trait]
+impl PokemonSource for SnapshotSource {
+    fn name(&self) -> &'static str {
+        "snapshot"
+    }
+
+    async fn get(
+        &self,
+        key: &str,
+    ) -> Result<Option<Arc<CachedSpecies>>> {
+        Ok(self.species.get(key).cloned())
+    }
+}
+
+/// PokeAPI `pokemon-species` documents by name, e.g. for tests or
+/// for serving a fixed set of species offline. A directory holds one
+/// `<name>.json` file per species.
+pub struct FixtureSource {
+    documents: RwLock<HashMap<String, Value>>,
+    /// Maps the documents as species fetched from PokeAPI are.
+    species: SpeciesClient,
+}
+
+impl FixtureSource {
+    pub fn new(species: SpeciesClient) -> Self {
+        Self {
+            documents: RwLock::new(HashMap::new()),
+            species,
+        }
+    }
+
+    /// Loads every `.json` file of `dir`.
+    pub fn open(dir: &Path, species: SpeciesClient) -> Result<Self> {
+        let io_error = |e: std::io::Error| {
+            AppError::Internal(format!(
+                "Failed to read species fixtures {}: {}",
+                dir.display(),
+                e
+            ))
+        };
+        let fixtures = Self::new(species);
+        for entry in std::fs::read_dir(dir).map_err(io_error)? {
+            let path = entry.map_err(io_error)?.path();
+            let Some(name) = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_suffix(".json"))
+            else {
+                continue;
+            };
+            let json = std::fs::read(&path).map_err(io_error)?;
+            let document =
+                serde_json::from_slice(&json).map_err(|e| {
+                    AppError::Internal(format!(
+                        "Invalid species fixture {}: {}",
+                        path.display(),
+                        e
+                    ))
+                })?;
+            fixtures.insert(name, document);
+        }
+        info!(dir = %dir.display(), count = fixtures.documents.read().unwrap().len(), "Loaded species fixtures");
+        Ok(fixtures)
+    }
+
+    /// Adds or replaces the document of `name`.
+    pub fn insert(&self, name: &str, document: Value) {
+        self.documents
+            .write()
+            .unwrap()
+            .insert(name.to_lowercase(), document);
+    }
+}
+
+#[async_trait]
+impl PokemonSource for FixtureSource {
+    fn name(&self) -> &'static str {
+        "fixtures"
+    }
+
+    async fn get(
+        &self,
+        key: &str,
+    ) -> Result<Option<Arc<CachedSpecies>>> {
+        let Some(document) =
+            self.documents.read().unwrap().get(key).cloned()
+        else {
+            return Ok(None);
+        };
+        Ok(Some(Arc::new(self.species.parse(document)?)))
+    }
+}
+
+struct PokeApiSource {
+    species: SpeciesClient,
+}
+
+#[async_trait]
+impl PokemonSource for PokeApiSource {
+    fn name(&self) -> &'static str {
+        "pokeapi"
+    }
+
+    fn is_origin(&self) -> bool {
+        true
+    }
+
+    async fn get(
+        &self,
+        key: &str,
+    ) -> Result<Option<Arc<CachedSpecies>>> {
+        Ok(Some(Arc::new(self.species.fetch(key).await?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_source_list() {
+        assert_eq!(
+            SourceKind::parse_list("cache, Storage,pokeapi"),
+            Ok(vec![
+                SourceKind::Cache,
+                SourceKind::Storage,
+                SourceKind::PokeApi
+            ])
+        );
+        assert_eq!(
+            SourceKind::parse_list("cache,snapshot"),
+            Ok(vec![SourceKind::Cache, SourceKind::Snapshot])
+        );
+        assert!(
+            SourceKind::parse_list("cache,pokeapi,cache").is_err()
+        );
+        assert!(SourceKind::parse_list("storage,pokeapi").is_err());
+        assert!(
+            SourceKind::parse_list("cache,redis,pokeapi").is_err()
+        );
+    }
+}
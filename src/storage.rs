@@ -0,0 +1,334 @@
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::cache_store::unix_millis;
+use crate::error::{AppError, Result};
+
+/// Namespaced key/value persistence for the stateful features
+/// (quiz sessions, favorites, ...). Values are JSON documents and may
+/// carry a TTL after which they are no longer returned.
+pub trait Storage: Send + Sync {
+    fn get(
+        &self,
+        namespace: &str,
+        key: &str,
+    ) -> Result<Option<Value>>;
+
+    fn put(
+        &self,
+        namespace: &str,
+        key: &str,
+        value: Value,
+        ttl: Option<Duration>,
+    ) -> Result<()>;
+
+    /// Removes a key, returning whether it existed.
+    fn delete(&self, namespace: &str, key: &str) -> Result<bool>;
+
+    /// The live entries of a namespace, in no particular order.
+    fn entries(
+        &self,
+        namespace: &str,
+    ) -> Result<Vec<(String, Value)>>;
+}
+
+impl dyn Storage {
+    pub fn get_as<T: DeserializeOwned>(
+        &self,
+        namespace: &str,
+        key: &str,
+    ) -> Result<Option<T>> {
+        self.get(namespace, key)?
+            .map(|value| {
+                serde_json::from_value(value).map_err(|e| {
+                    AppError::Internal(format!(
+                        "Corrupted {} entry '{}': {}",
+                        namespace, key, e
+                    ))
+                })
+            })
+            .transpose()
+    }
+
+    pub fn put_as<T: Serialize>(
+        &self,
+        namespace: &str,
+        key: &str,
+        value: &T,
+        ttl: Option<Duration>,
+    ) -> Result<()> {
+        let value = serde_json::to_value(value).map_err(|e| {
+            AppError::Internal(format!(
+                "Failed to serialize {} entry: {}",
+                namespace, e
+            ))
+        })?;
+        self.put(namespace, key, value, ttl)
+    }
+}
+
+struct StoredValue {
+    value: Value,
+    expires_at: Option<Instant>,
+}
+
+impl StoredValue {
+    fn is_live(&self, now: Instant) -> bool {
+        self.expires_at.is_none_or(|expires_at| expires_at > now)
+    }
+}
+
+/// Process-local storage; everything is lost on restart.
+#[derive(Default)]
+pub struct MemoryStorage {
+    entries: Mutex<HashMap<(String, String), StoredValue>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn get(
+        &self,
+        namespace: &str,
+        key: &str,
+    ) -> Result<Option<Value>> {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        let id = (namespace.to_string(), key.to_string());
+        match entries.get(&id) {
+            Some(stored) if stored.is_live(now) => {
+                Ok(Some(stored.value.clone()))
+            }
+            Some(_) => {
+                entries.remove(&id);
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn put(
+        &self,
+        namespace: &str,
+        key: &str,
+        value: Value,
+        ttl: Option<Duration>,
+    ) -> Result<()> {
+        let now = Instant::now();
+        let expires_at = ttl.map(|ttl| now + ttl);
+        let mut entries = self.entries.lock().unwrap();
+        // Drop abandoned entries (e.g. quizzes nobody finished).
+        entries.retain(|_, stored| stored.is_live(now));
+        entries.insert(
+            (namespace.to_string(), key.to_string()),
+            StoredValue { value, expires_at },
+        );
+        Ok(())
+    }
+
+    fn delete(&self, namespace: &str, key: &str) -> Result<bool> {
+        Ok(self
+            .entries
+            .lock()
+            .unwrap()
+            .remove(&(namespace.to_string(), key.to_string()))
+            .is_some())
+    }
+
+    fn entries(
+        &self,
+        namespace: &str,
+    ) -> Result<Vec<(String, Value)>> {
+        let now = Instant::now();
+        Ok(self
+            .entries
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|((ns, _), stored)| {
+                ns == namespace && stored.is_live(now)
+            })
+            .map(|((_, key), stored)| {
+                (key.clone(), stored.value.clone())
+            })
+            .collect())
+    }
+}
+
+fn storage_error(e: impl std::fmt::Display) -> AppError {
+    AppError::Internal(format!("Storage error: {}", e))
+}
+
+/// An embedded sled database at `STORAGE_PATH`, kept across
+/// restarts. Values are prefixed with their expiry (Unix
+/// milliseconds, big-endian, `u64::MAX` for none) and removed when
+/// read after it.
+pub struct DiskStorage {
+    db: sled::Db,
+}
+
+impl DiskStorage {
+    pub fn open(path: &Path) -> Result<Self> {
+        Ok(Self {
+            db: sled::open(path).map_err(storage_error)?,
+        })
+    }
+
+    fn key(namespace: &str, key: &str) -> String {
+        format!("{}/{}", namespace, key)
+    }
+
+    /// The value of `stored` unless it has expired.
+    fn decode(stored: &[u8]) -> Result<Option<Value>> {
+        let Some((expiry, value)) = stored.split_first_chunk::<8>()
+        else {
+            return Ok(None);
+        };
+        if u64::from_be_bytes(*expiry) <= unix_millis() {
+            return Ok(None);
+        }
+        serde_json::from_slice(value)
+            .map(Some)
+            .map_err(storage_error)
+    }
+}
+
+impl Storage for DiskStorage {
+    fn get(
+        &self,
+        namespace: &str,
+        key: &str,
+    ) -> Result<Option<Value>> {
+        let key = Self::key(namespace, key);
+        let Some(stored) =
+            self.db.get(&key).map_err(storage_error)?
+        else {
+            return Ok(None);
+        };
+        let value = Self::decode(&stored)?;
+        if value.is_none() {
+            self.db.remove(&key).map_err(storage_error)?;
+        }
+        Ok(value)
+    }
+
+    fn put(
+        &self,
+        namespace: &str,
+        key: &str,
+        value: Value,
+        ttl: Option<Duration>,
+    ) -> Result<()> {
+        let expiry = ttl.map_or(u64::MAX, |ttl| {
+            unix_millis().saturating_add(ttl.as_millis() as u64)
+        });
+        let mut stored = expiry.to_be_bytes().to_vec();
+        serde_json::to_writer(&mut stored, &value)
+            .map_err(storage_error)?;
+        self.db
+            .insert(Self::key(namespace, key), stored)
+            .map_err(storage_error)?;
+        Ok(())
+    }
+
+    fn delete(&self, namespace: &str, key: &str) -> Result<bool> {
+        Ok(self
+            .db
+            .remove(Self::key(namespace, key))
+            .map_err(storage_error)?
+            .is_some())
+    }
+
+    fn entries(
+        &self,
+        namespace: &str,
+    ) -> Result<Vec<(String, Value)>> {
+        let prefix = Self::key(namespace, "");
+        let mut entries = Vec::new();
+        for entry in self.db.scan_prefix(&prefix) {
+            let (key, stored) = entry.map_err(storage_error)?;
+            if let Some(value) = Self::decode(&stored)? {
+                let key =
+                    String::from_utf8_lossy(&key[prefix.len()..]);
+                entries.push((key.into_owned(), value));
+            }
+        }
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_put_get_delete() {
+        let storage = MemoryStorage::new();
+        storage.put("ns", "a", json!({"x": 1}), None).unwrap();
+        assert_eq!(
+            storage.get("ns", "a").unwrap(),
+            Some(json!({"x": 1}))
+        );
+        assert_eq!(storage.get("other", "a").unwrap(), None);
+        assert!(storage.delete("ns", "a").unwrap());
+        assert!(!storage.delete("ns", "a").unwrap());
+    }
+
+    #[test]
+    fn test_expired_values_are_hidden() {
+        let storage = MemoryStorage::new();
+        storage
+            .put("ns", "a", json!(1), Some(Duration::ZERO))
+            .unwrap();
+        storage.put("ns", "b", json!(2), None).unwrap();
+        assert_eq!(storage.get("ns", "a").unwrap(), None);
+        assert_eq!(storage.get("ns", "b").unwrap(), Some(json!(2)));
+    }
+
+    #[test]
+    fn test_typed_helpers() {
+        let storage: Box<dyn Storage> =
+            Box::new(MemoryStorage::new());
+        storage
+            .put_as("ns", "names", &vec!["pikachu"], None)
+            .unwrap();
+        let names: Option<Vec<String>> =
+            storage.get_as("ns", "names").unwrap();
+        assert_eq!(names, Some(vec!["pikachu".to_string()]));
+    }
+
+    #[test]
+    fn test_disk_storage_survives_reopening() {
+        let path = std::env::temp_dir()
+            .join(format!("pokedex-storage-{}", std::process::id()));
+        {
+            let storage = DiskStorage::open(&path).unwrap();
+            storage.put("ns", "a", json!({"x": 1}), None).unwrap();
+            storage
+                .put("ns", "b", json!(2), Some(Duration::ZERO))
+                .unwrap();
+            storage.put("other", "c", json!(3), None).unwrap();
+        }
+
+        let storage = DiskStorage::open(&path).unwrap();
+        assert_eq!(
+            storage.entries("ns").unwrap(),
+            vec![("a".to_string(), json!({"x": 1}))]
+        );
+        assert_eq!(storage.get("ns", "b").unwrap(), None);
+        assert!(storage.delete("other", "c").unwrap());
+        drop(storage);
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+}
@@ -0,0 +1,134 @@
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Query},
+    http::request::Parts,
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::error::AppError;
+use crate::pokemon::Pokemon;
+use crate::text;
+
+/// How much of the description to keep, asked for with
+/// `?sentences=` and `?max_len=`. Applied before translation, since
+/// long flavor texts trip up the translators.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Summary {
+    /// Keep the first sentences only.
+    pub sentences: Option<usize>,
+    /// Cut at a word boundary, with an ellipsis, to at most this many
+    /// characters.
+    pub max_len: Option<usize>,
+}
+
+impl Summary {
+    pub fn apply(self, mut pokemon: Pokemon) -> Pokemon {
+        if let Some(description) = &pokemon.description {
+            let mut shortened = description.as_str();
+            if let Some(sentences) = self.sentences {
+                shortened =
+                    text::first_sentences(shortened, sentences);
+            }
+            let shortened = match self.max_len {
+                Some(max_len) => text::truncate(shortened, max_len),
+                None => shortened.into(),
+            };
+            if shortened.len() < description.len() {
+                pokemon.description = Some(shortened.into_owned());
+            }
+        }
+        pokemon
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SummaryParams {
+    sentences: Option<usize>,
+    max_len: Option<usize>,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Summary {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        let Query(params) =
+            Query::<SummaryParams>::from_request_parts(parts, state)
+                .await
+                .map_err(|e| AppError::BadRequest(e.body_text()))?;
+        for (name, value) in [
+            ("sentences", params.sentences),
+            ("max_len", params.max_len),
+        ] {
+            if value == Some(0) {
+                return Err(AppError::BadRequest(format!(
+                    "{} must be positive",
+                    name
+                )));
+            }
+        }
+        Ok(Summary {
+            sentences: params.sentences,
+            max_len: params.max_len,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pokemon(description: &str) -> Pokemon {
+        Pokemon {
+            name: "pikachu".to_string(),
+            display_name: None,
+            genus: None,
+            description: Some(description.to_string()),
+            description_language: None,
+            habitat: None,
+            is_legendary: false,
+            is_mythical: false,
+            is_baby: false,
+            breeding: None,
+            meta: None,
+            artwork: None,
+            phonetics: None,
+            data_source: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_apply_sentences_then_max_len() {
+        let description = "It stores electricity in its cheeks. \
+                           It discharges when threatened.";
+        let summary = Summary {
+            sentences: Some(1),
+            max_len: Some(20),
+        };
+        assert_eq!(
+            summary.apply(pokemon(description)).description.unwrap(),
+            "It stores…"
+        );
+        let summary = Summary {
+            sentences: Some(1),
+            max_len: None,
+        };
+        assert_eq!(
+            summary.apply(pokemon(description)).description.unwrap(),
+            "It stores electricity in its cheeks."
+        );
+        assert_eq!(
+            Summary::default()
+                .apply(pokemon(description))
+                .description
+                .unwrap(),
+            description
+        );
+    }
+}
@@ -0,0 +1,297 @@
+use crate::error::{AppError, FieldError, Result};
+use crate::lang::Lang;
+use crate::pokemon::{PokemonDetails, PokemonService};
+use crate::type_chart::{
+    ALL_TYPES, TypeRelations, TypeService, effectiveness,
+};
+use futures::future::try_join_all;
+use serde::Serialize;
+use std::{cmp::Reverse, collections::BTreeSet, sync::Arc};
+use tracing::instrument;
+use utoipa::ToSchema;
+
+pub const MAX_TEAM_SIZE: usize = 6;
+
+#[derive(Debug, Serialize, PartialEq, ToSchema)]
+pub struct TeamAnalysis {
+    pub members: Vec<TeamMember>,
+    pub coverage: Coverage,
+    pub shared_weaknesses: Vec<SharedWeakness>,
+    pub legendary_count: usize,
+}
+
+#[derive(Debug, Serialize, PartialEq, ToSchema)]
+pub struct TeamMember {
+    pub name: String,
+    pub types: Vec<String>,
+    pub is_legendary: bool,
+    pub weaknesses: Vec<String>,
+}
+
+/// Types the team's own types hit super-effectively.
+#[derive(Debug, Serialize, PartialEq, ToSchema)]
+pub struct Coverage {
+    pub super_effective: Vec<String>,
+    pub uncovered: Vec<String>,
+}
+
+#[derive(Debug, Serialize, PartialEq, ToSchema)]
+pub struct SharedWeakness {
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub members: usize,
+}
+
+/// Checks the team size and rejects empty and duplicate names,
+/// reporting every invalid field.
+pub fn validate_team(names: &[String]) -> Result<()> {
+    let mut errors = Vec::new();
+    if names.is_empty() || names.len() > MAX_TEAM_SIZE {
+        errors.push(FieldError::new(
+            "names",
+            "length",
+            format!(
+                "A team must have between 1 and {} Pokemon",
+                MAX_TEAM_SIZE
+            ),
+        ));
+    }
+
+    let mut seen = BTreeSet::new();
+    for (i, name) in names.iter().enumerate() {
+        let field = format!("names[{}]", i);
+        let name = name.trim().to_lowercase();
+        if name.is_empty() {
+            errors.push(FieldError::new(
+                field,
+                "empty",
+                "Pokemon names must not be empty",
+            ));
+        } else if !seen.insert(name.clone()) {
+            errors.push(FieldError::new(
+                field,
+                "duplicate",
+                format!(
+                    "'{}' appears more than once in the team",
+                    name
+                ),
+            ));
+        }
+    }
+
+    AppError::validate(errors)
+}
+
+pub struct TeamService {
+    pokemon_service: Arc<PokemonService>,
+    type_service: Arc<TypeService>,
+}
+
+impl TeamService {
+    pub fn new(
+        pokemon_service: Arc<PokemonService>,
+        type_service: Arc<TypeService>,
+    ) -> Self {
+        Self {
+            pokemon_service,
+            type_service,
+        }
+    }
+
+    #[instrument(skip(self))]
+    pub async fn analyze(
+        &self,
+        names: &[String],
+    ) -> Result<TeamAnalysis> {
+        validate_team(names)?;
+
+        let lang = Lang::default();
+        let members = try_join_all(names.iter().map(|name| async {
+            let details = self
+                .pokemon_service
+                .get_details(name.trim(), &lang)
+                .await?;
+            let relations = self
+                .type_service
+                .get_all_relations(&details.types)
+                .await?;
+            Ok::<_, AppError>((details, relations))
+        }))
+        .await?;
+
+        Ok(analyze_team(&members))
+    }
+}
+
+/// Computes the analysis of a team from each member's details and
+/// the damage relations of its types.
+pub fn analyze_team(
+    members: &[(PokemonDetails, Vec<Arc<TypeRelations>>)],
+) -> TeamAnalysis {
+    let mut super_effective = BTreeSet::new();
+    let mut team_members = Vec::new();
+
+    for (details, relations) in members {
+        for relation in relations {
+            super_effective
+                .extend(relation.double_damage_to.iter().cloned());
+        }
+
+        let defending: Vec<TypeRelations> =
+            relations.iter().map(|r| r.as_ref().clone()).collect();
+        let weaknesses = ALL_TYPES
+            .iter()
+            .filter(|attacking| {
+                effectiveness(&defending, attacking) > 1.0
+            })
+            .map(|attacking| attacking.to_string())
+            .collect();
+
+        team_members.push(TeamMember {
+            name: details.pokemon.name.clone(),
+            types: details.types.clone(),
+            is_legendary: details.pokemon.is_legendary,
+            weaknesses,
+        });
+    }
+
+    let mut shared_weaknesses: Vec<SharedWeakness> = ALL_TYPES
+        .iter()
+        .map(|attacking| SharedWeakness {
+            type_: attacking.to_string(),
+            members: team_members
+                .iter()
+                .filter(|m| {
+                    m.weaknesses.iter().any(|w| w == attacking)
+                })
+                .count(),
+        })
+        .filter(|weakness| weakness.members >= 2)
+        .collect();
+    shared_weaknesses.sort_by_key(|w| Reverse(w.members));
+
+    let uncovered = ALL_TYPES
+        .iter()
+        .filter(|t| !super_effective.contains(**t))
+        .map(|t| t.to_string())
+        .collect();
+
+    TeamAnalysis {
+        legendary_count: team_members
+            .iter()
+            .filter(|m| m.is_legendary)
+            .count(),
+        members: team_members,
+        coverage: Coverage {
+            super_effective: super_effective.into_iter().collect(),
+            uncovered,
+        },
+        shared_weaknesses,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pokemon::Pokemon;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    fn member(
+        name: &str,
+        legendary: bool,
+        relations: TypeRelations,
+    ) -> (PokemonDetails, Vec<Arc<TypeRelations>>) {
+        let details = PokemonDetails {
+            pokemon: Pokemon {
+                name: name.to_string(),
+                display_name: None,
+                genus: None,
+                description: None,
+                description_language: None,
+                habitat: None,
+                is_legendary: legendary,
+                is_mythical: false,
+                is_baby: false,
+                breeding: None,
+                meta: None,
+                artwork: None,
+                phonetics: None,
+                data_source: None,
+                warnings: Vec::new(),
+            },
+            types: vec![relations.name.clone()],
+            height: 0,
+            weight: 0,
+            stats: Vec::new(),
+            abilities: Vec::new(),
+        };
+        (details, vec![Arc::new(relations)])
+    }
+
+    fn electric() -> TypeRelations {
+        TypeRelations {
+            name: "electric".to_string(),
+            double_damage_from: vec!["ground".to_string()],
+            double_damage_to: vec![
+                "water".to_string(),
+                "flying".to_string(),
+            ],
+            ..TypeRelations::default()
+        }
+    }
+
+    #[test]
+    fn test_validate_team() {
+        assert!(validate_team(&names(&["pikachu"])).is_ok());
+        assert!(validate_team(&[]).is_err());
+        assert!(
+            validate_team(&names(&[
+                "a", "b", "c", "d", "e", "f", "g"
+            ]))
+            .is_err()
+        );
+        assert!(
+            validate_team(&names(&["pikachu", "Pikachu"])).is_err()
+        );
+        assert!(validate_team(&names(&["pikachu", " "])).is_err());
+
+        let Err(AppError::ValidationError(fields)) =
+            validate_team(&names(&["", "mew", "Mew"]))
+        else {
+            panic!("expected a validation error");
+        };
+        assert_eq!(
+            fields
+                .iter()
+                .map(|field| (field.field.as_str(), field.code))
+                .collect::<Vec<_>>(),
+            [("names[0]", "empty"), ("names[2]", "duplicate")]
+        );
+    }
+
+    #[test]
+    fn test_analyze_team() {
+        let analysis = analyze_team(&[
+            member("pikachu", false, electric()),
+            member("zapdos", true, electric()),
+        ]);
+
+        assert_eq!(analysis.legendary_count, 1);
+        assert_eq!(analysis.members[0].weaknesses, vec!["ground"]);
+        assert_eq!(
+            analysis.shared_weaknesses,
+            vec![SharedWeakness {
+                type_: "ground".to_string(),
+                members: 2,
+            }]
+        );
+        assert_eq!(
+            analysis.coverage.super_effective,
+            vec!["flying", "water"]
+        );
+        assert_eq!(analysis.coverage.uncovered.len(), 16);
+    }
+}
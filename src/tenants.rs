@@ -0,0 +1,313 @@
+use crate::auth::Principal;
+use crate::error::{AppError, Result};
+use crate::storage::Storage;
+use axum::{
+    extract::{Request, State},
+    http::{HeaderValue, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const NAMESPACE: &str = "tenant-usage";
+
+/// Days of usage kept for reporting.
+pub const USAGE_RETENTION_DAYS: u64 = 31;
+
+const SECS_PER_DAY: u64 = 86_400;
+
+/// Limits of a tenant, from `TENANTS_FILE`.
+#[derive(
+    Debug, Clone, Default, Serialize, Deserialize, PartialEq,
+)]
+pub struct Tenant {
+    /// Requests per UTC day across all of the tenant's keys; `None`
+    /// is unlimited.
+    #[serde(default)]
+    pub daily_quota: Option<u64>,
+    /// Route prefixes the tenant may call, e.g. `/pokemon`, without
+    /// the version prefix. Empty allows every endpoint.
+    #[serde(default)]
+    pub endpoints: Vec<String>,
+}
+
+impl Tenant {
+    fn allows(&self, path: &str) -> bool {
+        let path = ["/v1", "/v2"]
+            .iter()
+            .find_map(|version| {
+                path.strip_prefix(version).filter(|rest| {
+                    rest.is_empty() || rest.starts_with('/')
+                })
+            })
+            .unwrap_or(path);
+        self.endpoints.is_empty()
+            || self.endpoints.iter().any(|endpoint| {
+                let endpoint = endpoint.trim_end_matches('/');
+                path.strip_prefix(endpoint).is_some_and(|rest| {
+                    rest.is_empty() || rest.starts_with('/')
+                })
+            })
+    }
+}
+
+/// Reads the tenants of a JSON file such as
+/// `{"acme": {"daily_quota": 10000, "endpoints": ["/pokemon"]}}`.
+pub fn load(
+    path: &Path,
+) -> std::result::Result<HashMap<String, Tenant>, String> {
+    let contents = std::fs::read(path).map_err(|e| e.to_string())?;
+    serde_json::from_slice(&contents)
+        .map_err(|e| format!("invalid JSON: {}", e))
+}
+
+#[derive(Debug, Serialize)]
+pub struct DailyUsage {
+    /// UTC day, as `YYYY-MM-DD`.
+    pub date: String,
+    pub requests: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TenantUsage {
+    pub tenant: String,
+    #[serde(flatten)]
+    pub limits: Tenant,
+    /// Most recent day first.
+    pub days: Vec<DailyUsage>,
+}
+
+/// Enforces the tenants' endpoint scopes and daily quotas, counting
+/// requests per tenant and day in the storage backend.
+pub struct Tenants {
+    tenants: HashMap<String, Tenant>,
+    storage: Arc<dyn Storage>,
+    // Serializes the read-increment-write of the counters.
+    lock: Mutex<()>,
+}
+
+impl Tenants {
+    pub fn new(
+        tenants: HashMap<String, Tenant>,
+        storage: Arc<dyn Storage>,
+    ) -> Self {
+        Self {
+            tenants,
+            storage,
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.tenants.contains_key(id)
+    }
+
+    /// Counts a request of `tenant_id` to `path`, failing with a
+    /// `403` outside its endpoints and a `429` over its quota.
+    pub fn admit(&self, tenant_id: &str, path: &str) -> Result<()> {
+        let tenant = self.get(tenant_id)?;
+        if !tenant.allows(path) {
+            return Err(AppError::Forbidden(format!(
+                "Tenant '{}' may not call {}",
+                tenant_id, path
+            )));
+        }
+
+        let key = usage_key(tenant_id, today());
+        let _guard = self.lock.lock().unwrap();
+        let used: u64 =
+            self.storage.get_as(NAMESPACE, &key)?.unwrap_or_default();
+        if tenant.daily_quota.is_some_and(|quota| used >= quota) {
+            return Err(AppError::TooManyRequests(format!(
+                "Daily quota of tenant '{}' exhausted",
+                tenant_id
+            )));
+        }
+        self.storage.put_as(
+            NAMESPACE,
+            &key,
+            &(used + 1),
+            Some(Duration::from_secs(
+                USAGE_RETENTION_DAYS * SECS_PER_DAY,
+            )),
+        )
+    }
+
+    /// Requests of `tenant_id` over the last `days` days.
+    pub fn usage(
+        &self,
+        tenant_id: &str,
+        days: u64,
+    ) -> Result<TenantUsage> {
+        let tenant = self.get(tenant_id)?;
+        let today = today();
+        let days = (0..days.clamp(1, USAGE_RETENTION_DAYS))
+            .map(|ago| {
+                let day = today.saturating_sub(ago);
+                let requests = self
+                    .storage
+                    .get_as(NAMESPACE, &usage_key(tenant_id, day))?
+                    .unwrap_or_default();
+                Ok(DailyUsage {
+                    date: format_day(day),
+                    requests,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(TenantUsage {
+            tenant: tenant_id.to_string(),
+            limits: tenant.clone(),
+            days,
+        })
+    }
+
+    fn get(&self, tenant_id: &str) -> Result<&Tenant> {
+        self.tenants.get(tenant_id).ok_or_else(|| {
+            AppError::NotFound(format!(
+                "Tenant '{}' not found",
+                tenant_id
+            ))
+        })
+    }
+}
+
+fn usage_key(tenant_id: &str, day: u64) -> String {
+    format!("{}:{}", tenant_id, day)
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Days since the Unix epoch, in UTC.
+fn today() -> u64 {
+    now_secs() / SECS_PER_DAY
+}
+
+/// `YYYY-MM-DD` of a day since the epoch, after Howard Hinnant's
+/// `civil_from_days`.
+fn format_day(day: u64) -> String {
+    let z = day as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460
+        + day_of_era / 36_524
+        - day_of_era / 146_096)
+        / 365;
+    let day_of_year = day_of_era
+        - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Applies the tenant limits to requests made by a tenant's users.
+/// Other requests, and those of tenants missing from
+/// `TENANTS_FILE`, pass through.
+pub async fn middleware(
+    State(tenants): State<Arc<Tenants>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let tenant_id = request
+        .extensions()
+        .get::<Principal>()
+        .and_then(|principal| principal.tenant_id.clone())
+        .filter(|tenant_id| tenants.contains(tenant_id));
+    let Some(tenant_id) = tenant_id else {
+        return next.run(request).await;
+    };
+
+    match tenants.admit(&tenant_id, request.uri().path()) {
+        Ok(()) => next.run(request).await,
+        Err(e) => {
+            let quota_exhausted =
+                matches!(e, AppError::TooManyRequests(_));
+            let mut response = e.into_response();
+            if quota_exhausted {
+                let until_midnight =
+                    SECS_PER_DAY - now_secs() % SECS_PER_DAY;
+                response.headers_mut().insert(
+                    header::RETRY_AFTER,
+                    HeaderValue::from(until_midnight),
+                );
+            }
+            response
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    fn tenants() -> Tenants {
+        Tenants::new(
+            HashMap::from([(
+                "acme".to_string(),
+                Tenant {
+                    daily_quota: Some(2),
+                    endpoints: vec!["/pokemon".to_string()],
+                },
+            )]),
+            Arc::new(MemoryStorage::new()),
+        )
+    }
+
+    #[test]
+    fn test_format_day() {
+        assert_eq!(format_day(0), "1970-01-01");
+        assert_eq!(format_day(20_743), "2026-10-17");
+        assert_eq!(format_day(11_016), "2000-02-29");
+    }
+
+    #[test]
+    fn test_allows_endpoint_prefixes() {
+        let tenant = Tenant {
+            daily_quota: None,
+            endpoints: vec!["/pokemon/".to_string()],
+        };
+        assert!(tenant.allows("/pokemon/pikachu"));
+        assert!(tenant.allows("/v2/pokemon"));
+        assert!(!tenant.allows("/pokemonx"));
+        assert!(!tenant.allows("/v1/translate"));
+        assert!(Tenant::default().allows("/translate"));
+    }
+
+    #[test]
+    fn test_admit_enforces_quota_and_reports_usage() {
+        let tenants = tenants();
+        assert!(matches!(
+            tenants.admit("acme", "/translate"),
+            Err(AppError::Forbidden(_))
+        ));
+        tenants.admit("acme", "/pokemon/pikachu").unwrap();
+        tenants.admit("acme", "/v1/pokemon/mew").unwrap();
+        assert!(matches!(
+            tenants.admit("acme", "/pokemon/pikachu"),
+            Err(AppError::TooManyRequests(_))
+        ));
+
+        let usage = tenants.usage("acme", 3).unwrap();
+        let requests: Vec<u64> =
+            usage.days.iter().map(|day| day.requests).collect();
+        assert_eq!(requests, [2, 0, 0]);
+        assert_eq!(usage.days[0].date, format_day(today()));
+        assert!(matches!(
+            tenants.usage("globex", 1),
+            Err(AppError::NotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,526 @@
+//! Text helpers shared by the services, without dependencies on the
+//! rest of the crate so that the benchmarks can include them.
+
+use std::borrow::Cow;
+use unicode_normalization::{UnicodeNormalization, is_nfc};
+
+/// Flattens a PokeAPI flavor text onto one line: runs of whitespace,
+/// including the line breaks and form feeds of the game text, become
+/// single spaces and the ends are trimmed.
+///
+/// Works in a single pass and only allocates when a run has to be
+/// rewritten; text that is already clean, up to trimming, is
+/// borrowed.
+pub fn clean_description(text: &str) -> Cow<'_, str> {
+    // Where the first word starts.
+    let mut first = None;
+    // The whitespace run after the last word: where it starts and
+    // whether it is a lone space, which is kept as is.
+    let mut gap: Option<(usize, bool)> = None;
+    let mut cleaned: Option<String> = None;
+
+    for (i, c) in text.char_indices() {
+        if c.is_whitespace() {
+            match &mut gap {
+                Some((_, lone)) => *lone = false,
+                None if first.is_some() => gap = Some((i, c == ' ')),
+                None => {}
+            }
+            continue;
+        }
+        let first = *first.get_or_insert(i);
+        if let Some((start, lone)) = gap.take() {
+            match &mut cleaned {
+                Some(cleaned) => cleaned.push(' '),
+                None if !lone => {
+                    let mut owned =
+                        String::with_capacity(text.len() - first);
+                    owned.push_str(&text[first..start]);
+                    owned.push(' ');
+                    cleaned = Some(owned);
+                }
+                None => {}
+            }
+        }
+        if let Some(cleaned) = &mut cleaned {
+            cleaned.push(c);
+        }
+    }
+
+    match cleaned {
+        Some(cleaned) => Cow::Owned(cleaned),
+        None => {
+            let first = first.unwrap_or(text.len());
+            let end = gap.map_or(text.len(), |(start, _)| start);
+            Cow::Borrowed(&text[first..end])
+        }
+    }
+}
+
+/// Appended to text cut short by `truncate`.
+pub const ELLIPSIS: char = '…';
+
+/// Abbreviations whose period does not end a sentence.
+const ABBREVIATIONS: [&str; 5] = ["Mr", "Mrs", "Ms", "Dr", "St"];
+
+/// The first `count` sentences of `text`. A sentence ends with `.`,
+/// `!` or `?` followed by whitespace and a capital letter, or by the
+/// end of the text, except after an abbreviation like `Mr.`.
+pub fn first_sentences(text: &str, count: usize) -> &str {
+    let mut chars = text.char_indices().peekable();
+    let mut seen = 0;
+    while let Some((i, c)) = chars.next() {
+        if !matches!(c, '.' | '!' | '?') {
+            continue;
+        }
+        let end = i + c.len_utf8();
+        let next = text[end..].trim_start().chars().next();
+        let ends_sentence = match next {
+            None => true,
+            Some(next) => {
+                text[end..].starts_with(char::is_whitespace)
+                    && next.is_uppercase()
+            }
+        };
+        let word = text[..i]
+            .rsplit(char::is_whitespace)
+            .next()
+            .unwrap_or("");
+        let abbreviated = c == '.' && ABBREVIATIONS.contains(&word);
+        // `?!` and `...` end a sentence once.
+        if ends_sentence
+            && !abbreviated
+            && !chars.peek().is_some_and(|&(_, next)| {
+                matches!(next, '.' | '!' | '?')
+            })
+        {
+            seen += 1;
+            if seen == count {
+                return &text[..end];
+            }
+        }
+    }
+    text
+}
+
+/// Shortens `text` to at most `max_chars` characters, cutting at the
+/// last word boundary that leaves room for an ellipsis. A first word
+/// longer than that is cut mid-word.
+pub fn truncate(text: &str, max_chars: usize) -> Cow<'_, str> {
+    if text.chars().count() <= max_chars {
+        return Cow::Borrowed(text);
+    }
+    let Some(budget) = max_chars.checked_sub(1) else {
+        return Cow::Borrowed("");
+    };
+    let end = text
+        .char_indices()
+        .nth(budget)
+        .map_or(text.len(), |(i, _)| i);
+    let kept = &text[..end];
+    let kept = if !text[end..].starts_with(char::is_alphanumeric) {
+        kept
+    } else {
+        match kept.rfind(char::is_whitespace) {
+            Some(space) => &kept[..space],
+            None => kept,
+        }
+    };
+    let kept = kept.trim_end_matches(|c: char| {
+        c.is_whitespace() || matches!(c, ',' | ';' | ':' | '.')
+    });
+    let mut truncated = String::with_capacity(kept.len() + 3);
+    truncated.push_str(kept);
+    truncated.push(ELLIPSIS);
+    Cow::Owned(truncated)
+}
+
+/// How the `POKé` words of old flavor texts, and the `BALL` of
+/// `POKé BALL`, are recased.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Casing {
+    /// `POKÉMON`
+    Upper,
+    /// `Pokémon`
+    Title,
+}
+
+/// Opt-in fixes for the legacy characters and casing of old flavor
+/// texts.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Normalization {
+    /// Composes characters to Unicode NFC, e.g. `e` and a combining
+    /// acute accent into `é`.
+    pub nfc: bool,
+    /// Straightens curly quotes and apostrophes.
+    pub quotes: bool,
+    pub casing: Option<Casing>,
+}
+
+impl Normalization {
+    /// Parses a comma-separated list of passes, e.g.
+    /// `nfc,quotes,case=title`.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let mut normalization = Normalization::default();
+        for pass in value.split(',').map(str::trim) {
+            match pass.to_lowercase().as_str() {
+                "" => {}
+                "nfc" => normalization.nfc = true,
+                "quotes" => normalization.quotes = true,
+                "case=upper" => {
+                    normalization.casing = Some(Casing::Upper)
+                }
+                "case=title" => {
+                    normalization.casing = Some(Casing::Title)
+                }
+                _ => {
+                    return Err(format!(
+                        "unknown pass '{}', expected nfc, quotes, \
+                         case=upper or case=title",
+                        pass
+                    ));
+                }
+            }
+        }
+        Ok(normalization)
+    }
+
+    /// Runs the enabled passes, borrowing `text` when none changes
+    /// it.
+    pub fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
+        if self.nfc && !is_nfc(&text) {
+            text = Cow::Owned(text.nfc().collect());
+        }
+        if self.quotes && text.contains(CURLY_QUOTES) {
+            text = Cow::Owned(
+                text.replace(['‘', '’'], "'")
+                    .replace(['“', '”'], "\""),
+            );
+        }
+        if let Some(casing) = self.casing
+            && let Cow::Owned(fixed) = recase(&text, casing)
+        {
+            text = Cow::Owned(fixed);
+        }
+        text
+    }
+}
+
+const CURLY_QUOTES: [char; 4] = ['‘', '’', '“', '”'];
+
+/// Recases the words starting with `poké`, and a `ball` right after
+/// one of them.
+fn recase(text: &str, casing: Casing) -> Cow<'_, str> {
+    let recased = |word: &str| match casing {
+        Casing::Upper => word.to_uppercase(),
+        Casing::Title => {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first
+                    .to_uppercase()
+                    .chain(chars.flat_map(char::to_lowercase))
+                    .collect(),
+                None => String::new(),
+            }
+        }
+    };
+
+    let mut fixed = String::with_capacity(text.len());
+    let mut copied = 0;
+    let mut start = None;
+    let mut after_poke = false;
+    // A non-letter at the end flushes the last word.
+    for (i, c) in text.char_indices().chain([(text.len(), ' ')]) {
+        if c.is_alphabetic() {
+            start.get_or_insert(i);
+            continue;
+        }
+        let Some(word_start) = start.take() else {
+            if !c.is_whitespace() {
+                after_poke = false;
+            }
+            continue;
+        };
+        let word = &text[word_start..i];
+        let lower = word.to_lowercase();
+        let is_poke = lower.starts_with("poké");
+        if is_poke || (after_poke && lower == "ball") {
+            fixed.push_str(&text[copied..word_start]);
+            fixed.push_str(&recased(word));
+            copied = i;
+        }
+        after_poke = is_poke && c.is_whitespace();
+    }
+    if copied == 0 {
+        return Cow::Borrowed(text);
+    }
+    fixed.push_str(&text[copied..]);
+    if fixed == text {
+        Cow::Borrowed(text)
+    } else {
+        Cow::Owned(fixed)
+    }
+}
+
+/// The entry in `language` (e.g. `en`, compared case-insensitively)
+/// of a PokeAPI list of localized entries.
+pub fn in_language<'a, T>(
+    entries: &'a [T],
+    language: &str,
+    language_of: impl Fn(&T) -> &str,
+) -> Option<&'a T> {
+    entries.iter().find(|entry| {
+        language_of(entry).eq_ignore_ascii_case(language)
+    })
+}
+
+/// Replaces every ASCII case-insensitive occurrence of `needle`.
+pub fn replace_ignore_case(
+    text: &str,
+    needle: &str,
+    with: &str,
+) -> String {
+    if needle.is_empty() {
+        return text.to_string();
+    }
+    // ASCII lowercasing keeps byte offsets identical to `text`.
+    let haystack = text.to_ascii_lowercase();
+    let needle = needle.to_ascii_lowercase();
+
+    let mut result = String::with_capacity(text.len());
+    let mut last = 0;
+    for (start, _) in haystack.match_indices(&needle) {
+        result.push_str(&text[last..start]);
+        result.push_str(with);
+        last = start + needle.len();
+    }
+    result.push_str(&text[last..]);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// The implementation `clean_description` replaced.
+    fn reference(text: &str) -> String {
+        text.replace(['\n', '\r', '\u{000C}'], " ")
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn test_clean_description() {
+        let input = "Line one\nLine two\u{000C}Line three";
+        let expected = "Line one Line two Line three";
+        assert_eq!(clean_description(input), expected);
+    }
+
+    #[test]
+    fn test_clean_description_multiple_spaces() {
+        let input = "Word1   Word2     Word3";
+        let expected = "Word1 Word2 Word3";
+        assert_eq!(clean_description(input), expected);
+    }
+
+    #[test]
+    fn test_clean_text_is_borrowed() {
+        assert!(matches!(
+            clean_description("Electric mouse"),
+            Cow::Borrowed("Electric mouse")
+        ));
+        assert!(matches!(
+            clean_description("\n Electric mouse \u{000C}"),
+            Cow::Borrowed("Electric mouse")
+        ));
+        assert!(matches!(
+            clean_description("Electric\nmouse"),
+            Cow::Owned(_)
+        ));
+        assert_eq!(clean_description(" \n "), "");
+    }
+
+    #[test]
+    fn test_first_sentences() {
+        let text = "It lives in forests. Mr. Mime waves! Is it shy?";
+        assert_eq!(first_sentences(text, 1), "It lives in forests.");
+        assert_eq!(
+            first_sentences(text, 2),
+            "It lives in forests. Mr. Mime waves!"
+        );
+        assert_eq!(first_sentences(text, 5), text);
+        assert_eq!(
+            first_sentences("Wait... What?! No.", 1),
+            "Wait..."
+        );
+        assert_eq!(
+            first_sentences("Wait... What?! No.", 2),
+            "Wait... What?!"
+        );
+        assert_eq!(
+            first_sentences("Version 1.5 ships", 1),
+            "Version 1.5 ships"
+        );
+    }
+
+    #[test]
+    fn test_truncate_at_word_boundary() {
+        let text = "Electric mouse, it stores electricity";
+        assert_eq!(truncate(text, 100), text);
+        assert_eq!(truncate(text, 16), "Electric mouse…");
+        assert_eq!(truncate(text, 15), "Electric mouse…");
+        assert_eq!(truncate(text, 14), "Electric…");
+        assert_eq!(truncate("Pikachu", 4), "Pik…");
+        assert_eq!(truncate("Pikachu", 1), "…");
+        assert_eq!(truncate("Pikachu", 0), "");
+    }
+
+    #[test]
+    fn test_normalization_passes() {
+        let text = "A POKe\u{301}MON caught in a POKé BALL. \
+                    It’s in the “POKéDEX”.";
+        let all =
+            Normalization::parse("nfc, quotes,case=title").unwrap();
+        assert_eq!(
+            all.apply(text),
+            "A Pokémon caught in a Poké Ball. It's in the \"Pokédex\"."
+        );
+        let upper = Normalization {
+            casing: Some(Casing::Upper),
+            ..Normalization::default()
+        };
+        assert_eq!(
+            upper.apply("POKéMON like BALLS, not the POKéMON BALL"),
+            "POKÉMON like BALLS, not the POKÉMON BALL"
+        );
+        assert!(matches!(
+            all.apply("A Pokémon in a Poké Ball."),
+            Cow::Borrowed(_)
+        ));
+        assert_eq!(
+            Normalization::parse("").unwrap(),
+            Normalization::default()
+        );
+        assert!(Normalization::parse("nfkc").is_err());
+    }
+
+    #[test]
+    fn test_in_language() {
+        let entries = [("ja", "ピカチュウ"), ("EN", "Pikachu")];
+        assert_eq!(
+            in_language(&entries, "en", |entry| entry.0),
+            Some(&("EN", "Pikachu"))
+        );
+        assert_eq!(
+            in_language(&entries, "it", |entry| entry.0),
+            None
+        );
+    }
+
+    #[test]
+    fn test_replace_ignore_case() {
+        assert_eq!(
+            replace_ignore_case(
+                "Pikachu likes PIKACHU",
+                "pikachu",
+                "???"
+            ),
+            "??? likes ???"
+        );
+        assert_eq!(replace_ignore_case("Mew", "", "???"), "Mew");
+    }
+
+    proptest! {
+        #[test]
+        fn test_matches_reference_on_flavor_text(
+            text in "[a-zé. \n\r\t\u{000C}\u{00A0}\u{2003}]{0,64}"
+        ) {
+            prop_assert_eq!(clean_description(&text), reference(&text));
+        }
+
+        #[test]
+        fn test_matches_reference_on_any_text(text in any::<String>()) {
+            prop_assert_eq!(clean_description(&text), reference(&text));
+        }
+
+        #[test]
+        fn test_truncate_fits(
+            text in "[a-zé.,! ]{0,64}",
+            max_chars in 0usize..40,
+        ) {
+            let truncated = truncate(&text, max_chars);
+            prop_assert!(truncated.chars().count() <= max_chars);
+            let kept = truncated.trim_end_matches(ELLIPSIS);
+            prop_assert!(text.starts_with(kept));
+        }
+
+        #[test]
+        fn test_clean_description_is_idempotent(text in any::<String>()) {
+            let cleaned = clean_description(&text);
+            prop_assert!(matches!(
+                clean_description(&cleaned),
+                Cow::Borrowed(again) if again == cleaned
+            ));
+            prop_assert!(!cleaned.contains("  "));
+            prop_assert!(!cleaned
+                .contains(|c: char| c.is_whitespace() && c != ' '));
+            prop_assert_eq!(cleaned.trim(), &*cleaned);
+        }
+
+        #[test]
+        fn test_normalization_is_stable(
+            text in "[a-zA-ZéÉ.,!'\" ‘’“”\u{301}]{0,64}",
+            nfc: bool,
+            quotes: bool,
+            casing in prop_oneof![
+                Just(None),
+                Just(Some(Casing::Upper)),
+                Just(Some(Casing::Title)),
+            ],
+        ) {
+            let normalization = Normalization { nfc, quotes, casing };
+            let once = normalization.apply(&text);
+            prop_assert!(matches!(
+                normalization.apply(&once),
+                Cow::Borrowed(again) if again == once
+            ));
+            if nfc {
+                prop_assert!(is_nfc(&once));
+            }
+            if quotes {
+                prop_assert!(!once.contains(CURLY_QUOTES));
+            }
+        }
+
+        #[test]
+        fn test_first_sentences_is_a_prefix(
+            text in "[A-Za-z.!? ]{0,64}",
+            count in 1usize..4,
+        ) {
+            let sentences = first_sentences(&text, count);
+            prop_assert!(text.starts_with(sentences));
+            prop_assert_eq!(first_sentences(sentences, count), sentences);
+        }
+
+        #[test]
+        fn test_replace_ignore_case_removes_needle(
+            text in "[a-zA-Z ]{0,64}",
+            needle in "[a-zA-Z]{1,4}",
+        ) {
+            let replaced = replace_ignore_case(&text, &needle, "?");
+            prop_assert!(
+                !replaced
+                    .to_ascii_lowercase()
+                    .contains(&needle.to_ascii_lowercase())
+            );
+            prop_assert_eq!(
+                replace_ignore_case(&text, &needle, &needle)
+                    .to_ascii_lowercase(),
+                text.to_ascii_lowercase()
+            );
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use serde::{Deserialize, Deserializer, de::DeserializeOwned};
+use serde_json::Value;
+
+/// A list read element by element, so that one malformed element is
+/// skipped instead of failing the whole payload.
+#[derive(Debug)]
+pub struct Tolerant<T> {
+    pub items: Vec<T>,
+    /// Index and error of every skipped element.
+    pub skipped: Vec<(usize, String)>,
+}
+
+impl<T> Tolerant<T> {
+    /// One warning per skipped element, e.g. `Skipped malformed
+    /// flavor_text_entries[3]: missing field `language``.
+    pub fn warnings(&self, field: &str) -> Vec<String> {
+        self.skipped
+            .iter()
+            .map(|(index, error)| {
+                format!(
+                    "Skipped malformed {}[{}]: {}",
+                    field, index, error
+                )
+            })
+            .collect()
+    }
+}
+
+impl<T> Default for Tolerant<T> {
+    fn default() -> Self {
+        Self {
+            items: Vec::new(),
+            skipped: Vec::new(),
+        }
+    }
+}
+
+impl<'de, T: DeserializeOwned> Deserialize<'de> for Tolerant<T> {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        let mut list = Tolerant::default();
+        for (index, element) in
+            Vec::<Value>::deserialize(deserializer)?
+                .into_iter()
+                .enumerate()
+        {
+            match serde_json::from_value(element) {
+                Ok(item) => list.items.push(item),
+                Err(e) => list.skipped.push((index, e.to_string())),
+            }
+        }
+        Ok(list)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Entry {
+        flavor_text: String,
+    }
+
+    #[test]
+    fn test_malformed_elements_are_skipped() {
+        let list: Tolerant<Entry> = serde_json::from_str(
+            r#"[{"flavor_text":"Spits fire."},{"flavor_text":7},{}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            list.items,
+            [Entry {
+                flavor_text: "Spits fire.".to_string()
+            }]
+        );
+        assert_eq!(
+            list.warnings("flavor_text_entries"),
+            [
+                "Skipped malformed flavor_text_entries[1]: invalid type: integer `7`, expected a string",
+                "Skipped malformed flavor_text_entries[2]: missing field `flavor_text`",
+            ]
+        );
+
+        assert!(
+            serde_json::from_str::<Tolerant<Entry>>("{}").is_err()
+        );
+    }
+}
@@ -1,98 +1,262 @@
+use crate::cache::{Cache, ManagedCache};
+use crate::context;
 use crate::error::{AppError, Result};
+use crate::http::{Upstream, UpstreamOptions};
+use crate::mt::{self, Translator};
+use crate::offline::{self, WordChange};
+use crate::output_filter::FilterChain;
+use crate::pokemon::{Pokemon, PokemonService};
+use crate::wire::TranslationResponse;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 use tracing::{debug, instrument, warn};
-
-#[derive(Deserialize)]
-struct TranslationResponse {
-    contents: TranslationContents,
-}
-
-#[derive(Deserialize)]
-struct TranslationContents {
-    translated: String,
-}
+use utoipa::ToSchema;
 
 #[derive(Serialize)]
 struct TranslationRequest {
     text: String,
 }
 
-#[derive(Debug, Clone, Copy)]
-enum Translator {
+/// A funtranslations style.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    ToSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Style {
     Yoda,
     Shakespeare,
 }
 
-impl Translator {
-    fn as_str(&self) -> &str {
+impl Style {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "yoda" => Some(Style::Yoda),
+            "shakespeare" => Some(Style::Shakespeare),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
         match self {
-            Translator::Yoda => "yoda",
-            Translator::Shakespeare => "shakespeare",
+            Style::Yoda => "yoda",
+            Style::Shakespeare => "shakespeare",
+        }
+    }
+}
+
+/// What gets a Pokemon the Yoda style besides being legendary: its
+/// habitat or one of its types being listed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct YodaRule {
+    pub habitats: Vec<String>,
+    pub types: Vec<String>,
+}
+
+impl Default for YodaRule {
+    /// Cave dwellers only.
+    fn default() -> Self {
+        Self {
+            habitats: vec!["cave".to_string()],
+            types: Vec::new(),
+        }
+    }
+}
+
+impl YodaRule {
+    /// Reads comma-separated lists of habitats and types, e.g.
+    /// `cave,rare` and `psychic,ghost`; an empty list matches
+    /// nothing.
+    pub fn parse(habitats: &str, types: &str) -> Self {
+        let list = |value: &str| {
+            value
+                .split(',')
+                .map(|name| name.trim().to_lowercase())
+                .filter(|name| !name.is_empty())
+                .collect()
+        };
+        Self {
+            habitats: list(habitats),
+            types: list(types),
+        }
+    }
+
+    fn matches(
+        &self,
+        habitat: Option<&str>,
+        types: &[String],
+    ) -> bool {
+        habitat.is_some_and(|habitat| {
+            self.habitats.iter().any(|listed| listed == habitat)
+        }) || types.iter().any(|name| self.types.contains(name))
+    }
+}
+
+/// The outputs of funtranslations and the offline translator for
+/// the same text, with the word diff between them.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Comparison {
+    pub funtranslations: Outcome,
+    pub offline: String,
+    /// The changes from the funtranslations output to the offline
+    /// one; absent when funtranslations failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff: Option<Vec<WordChange>>,
+}
+
+/// A translation, or why it failed.
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Translated(String),
+    Error(String),
+}
+
+impl From<Result<String>> for Outcome {
+    fn from(result: Result<String>) -> Self {
+        match result {
+            Ok(translated) => Outcome::Translated(translated),
+            Err(e) => Outcome::Error(e.to_string()),
         }
     }
 }
 
 pub struct TranslationService {
-    client: Client,
+    upstream: Upstream,
     base_url: String,
+    cache: Cache<(Style, String), String>,
+    machine: Option<Arc<dyn Translator>>,
+    machine_cache: Cache<(String, String), String>,
+    filters: FilterChain,
+    /// Whether mythical Pokemon get the legendaries' style.
+    mythical_as_legendary: bool,
+    yoda: YodaRule,
 }
 
+/// Name of the translation API in the upstream statistics.
+pub const UPSTREAM_NAME: &str = "Translation API";
+
 impl TranslationService {
-    pub fn new(base_url: String, timeout: Duration) -> Self {
-        let client = Client::builder()
-            .timeout(timeout)
-            .user_agent(concat!(
-                env!("CARGO_PKG_NAME"),
-                "/",
-                env!("CARGO_PKG_VERSION")
-            ))
-            .pool_max_idle_per_host(10)
-            .pool_idle_timeout(Duration::from_secs(90))
-            .build()
-            .expect("Failed to create HTTP client");
+    pub fn new(
+        base_url: String,
+        client: Client,
+        cache_ttl: Duration,
+    ) -> Self {
+        Self {
+            upstream: Upstream::new(UPSTREAM_NAME, client),
+            base_url,
+            cache: Cache::new(cache_ttl),
+            machine: None,
+            machine_cache: Cache::new(cache_ttl),
+            filters: FilterChain::default(),
+            mythical_as_legendary: false,
+            yoda: YodaRule::default(),
+        }
+    }
+
+    /// Enables translations into real languages with `translator`.
+    pub fn with_machine_translator(
+        mut self,
+        translator: Arc<dyn Translator>,
+    ) -> Self {
+        self.machine = Some(translator);
+        self
+    }
+
+    /// Runs `filters` on every translation before returning it. The
+    /// caches keep the unfiltered text.
+    pub fn with_filters(mut self, filters: FilterChain) -> Self {
+        self.filters = filters;
+        self
+    }
+
+    /// Translates mythical Pokemon in the Yoda style, like
+    /// legendaries.
+    pub fn with_mythical_as_legendary(
+        mut self,
+        enabled: bool,
+    ) -> Self {
+        self.mythical_as_legendary = enabled;
+        self
+    }
+
+    /// Picks the Yoda style by `rule` instead of for cave dwellers.
+    pub fn with_yoda_rule(mut self, rule: YodaRule) -> Self {
+        self.yoda = rule;
+        self
+    }
+
+    /// The caches owned by this service, by name.
+    pub fn caches(&self) -> Vec<(&'static str, &dyn ManagedCache)> {
+        vec![
+            ("translation", &self.cache),
+            ("translation.machine", &self.machine_cache),
+        ]
+    }
 
-        Self { client, base_url }
+    pub fn with_options(mut self, options: UpstreamOptions) -> Self {
+        self.upstream = self.upstream.with_options(options);
+        self
     }
 
-    #[instrument(skip(self, text), fields(translator, text_length = text.len()))]
-    pub async fn translate(
+    /// The style of `pokemon`'s description. Its types are only
+    /// fetched when the Yoda rule lists some; without them, the
+    /// style goes by the habitat and legendary status.
+    pub async fn style_of(
+        &self,
+        pokemon: &Pokemon,
+        pokemon_service: &PokemonService,
+    ) -> Style {
+        let mut types = Vec::new();
+        if !self.yoda.types.is_empty() {
+            match pokemon_service.types(&pokemon.name).await {
+                Ok(fetched) => types = fetched,
+                Err(e) => {
+                    warn!(pokemon_name = %pokemon.name, error = %e, "Picking the style without the types")
+                }
+            }
+        }
+        self.select_style(
+            &pokemon.habitat,
+            &types,
+            pokemon.is_legendary,
+            pokemon.is_mythical,
+        )
+    }
+
+    /// Translates `text` in `style`. Translations
+    /// are cached, as the translation API has a tight quota.
+    #[instrument(skip(self, text), fields(style = style.as_str(), text_length = text.len()))]
+    pub async fn translate_with(
         &self,
         text: &str,
-        habitat: &Option<String>,
-        is_legendary: bool,
+        style: Style,
     ) -> Result<String> {
-        let translator =
-            self.select_translator(habitat, is_legendary);
-        tracing::Span::current()
-            .record("translator", translator.as_str());
+        let key = (style, text.to_string());
+        if let Some(translated) = self.cache.get(&key) {
+            return Ok(self.filters.apply(translated));
+        }
 
         let url =
-            format!("{}/{}.json", self.base_url, translator.as_str());
-        debug!("Translating with {} translator", translator.as_str());
+            format!("{}/{}.json", self.base_url(), style.as_str());
+        debug!("Translating with {} style", style.as_str());
 
         let response = self
-            .client
-            .post(&url)
-            .json(&TranslationRequest {
-                text: text.to_string(),
-            })
-            .send()
-            .await
-            .map_err(|e| {
-                if e.is_timeout() {
-                    AppError::Timeout(format!(
-                        "Translation request timed out: {}",
-                        e
-                    ))
-                } else {
-                    AppError::ExternalApi(format!(
-                        "Translation request failed: {}",
-                        e
-                    ))
-                }
-            })?;
+            .upstream
+            .send(self.upstream.post(&url).json(
+                &TranslationRequest {
+                    text: text.to_string(),
+                },
+            ))
+            .await?;
 
         if !response.status().is_success() {
             let status = response.status();
@@ -103,47 +267,107 @@ impl TranslationService {
             )));
         }
 
-        let translation = response
-            .json::<TranslationResponse>()
-            .await
-            .map_err(|e| {
-                AppError::ExternalApi(format!(
-                    "Failed to parse translation response: {}",
-                    e
-                ))
-            })?;
+        let translation: TranslationResponse =
+            self.upstream.read_json(response).await?;
+
+        let translated = translation.contents.translated;
+        self.cache.insert(key, translated.clone());
+        Ok(self.filters.apply(translated))
+    }
+
+    /// Checks that `target` is a language code the configured
+    /// machine translator can be asked for, normalizing it.
+    pub fn machine_target(&self, target: &str) -> Result<String> {
+        if self.machine.is_none() {
+            return Err(AppError::BadRequest(
+                "Machine translation is not configured".to_string(),
+            ));
+        }
+        mt::parse_target(target)
+    }
+
+    /// Translates `text` into the `target` language with the machine
+    /// translator. Translations are cached like the fun ones.
+    #[instrument(skip(self, text), fields(text_length = text.len()))]
+    pub async fn translate_to(
+        &self,
+        text: &str,
+        target: &str,
+    ) -> Result<String> {
+        let Some(machine) = &self.machine else {
+            return Err(AppError::BadRequest(
+                "Machine translation is not configured".to_string(),
+            ));
+        };
+        let key = (target.to_string(), text.to_string());
+        if let Some(translated) = self.machine_cache.get(&key) {
+            return Ok(self.filters.apply(translated));
+        }
 
-        Ok(translation.contents.translated)
+        let translated = machine.translate(text, target).await?;
+        self.machine_cache.insert(key, translated.clone());
+        Ok(self.filters.apply(translated))
+    }
+
+    /// Translates `text` in `style` with funtranslations and the
+    /// offline translator side by side. Funtranslations failing
+    /// leaves the offline output in the comparison.
+    pub async fn compare(
+        &self,
+        text: &str,
+        style: Style,
+    ) -> Comparison {
+        let fun = self.translate_with(text, style).await;
+        let offline =
+            self.filters.apply(offline::translate(text, style));
+        let diff = fun.as_ref().ok().map(|translated| {
+            offline::diff_words(translated, &offline)
+        });
+        Comparison {
+            funtranslations: fun.into(),
+            offline,
+            diff,
+        }
+    }
+
+    /// The configured base URL, unless the current request
+    /// overrides it.
+    fn base_url(&self) -> String {
+        context::current()
+            .translation_base_url
+            .unwrap_or_else(|| self.base_url.clone())
     }
 
     pub async fn health_check(&self) -> Result<()> {
         // Simple health check - just verify the base URL is reachable
         let url = format!("{}/shakespeare.json", self.base_url);
-        self.client
-            .post(&url)
-            .json(&TranslationRequest {
-                text: "test".to_string(),
-            })
-            .send()
+        self.upstream
+            .probe(self.upstream.post(&url).json(
+                &TranslationRequest {
+                    text: "test".to_string(),
+                },
+            ))
             .await
-            .map_err(|e| {
-                AppError::ExternalApi(format!(
-                    "Health check failed: {}",
-                    e
-                ))
-            })?;
-        Ok(())
     }
 
-    fn select_translator(
+    /// The style of a Pokemon's description: Yoda for the legendary
+    /// ones and those matching the Yoda rule, by default those living
+    /// in caves, Shakespeare otherwise.
+    pub fn select_style(
         &self,
         habitat: &Option<String>,
+        types: &[String],
         is_legendary: bool,
-    ) -> Translator {
-        if habitat.as_deref() == Some("cave") || is_legendary {
-            Translator::Yoda
+        is_mythical: bool,
+    ) -> Style {
+        let is_legendary = is_legendary
+            || (self.mythical_as_legendary && is_mythical);
+        if is_legendary
+            || self.yoda.matches(habitat.as_deref(), types)
+        {
+            Style::Yoda
         } else {
-            Translator::Shakespeare
+            Style::Shakespeare
         }
     }
 }
@@ -151,43 +375,194 @@ impl TranslationService {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{body_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
 
     #[test]
     fn test_translator_selection_legendary() {
         let service = TranslationService::new(
             "http://example.com".to_string(),
-            Duration::from_secs(10),
+            crate::http::build_client(
+                &crate::http::ClientSettings::new(
+                    Duration::from_secs(10),
+                ),
+            ),
+            Duration::from_secs(60),
+        );
+        let style = service.select_style(
+            &Some("forest".to_string()),
+            &[],
+            true,
+            false,
         );
-        let translator = service
-            .select_translator(&Some("forest".to_string()), true);
-        assert_eq!(translator.as_str(), "yoda");
+        assert_eq!(style.as_str(), "yoda");
     }
 
     #[test]
     fn test_translator_selection_cave() {
         let service = TranslationService::new(
             "http://example.com".to_string(),
-            Duration::from_secs(10),
+            crate::http::build_client(
+                &crate::http::ClientSettings::new(
+                    Duration::from_secs(10),
+                ),
+            ),
+            Duration::from_secs(60),
         );
-        let translator = service
-            .select_translator(&Some("cave".to_string()), false);
-        assert_eq!(translator.as_str(), "yoda");
+        let style = service.select_style(
+            &Some("cave".to_string()),
+            &[],
+            false,
+            false,
+        );
+        assert_eq!(style.as_str(), "yoda");
     }
 
     #[test]
     fn test_translator_selection_shakespeare() {
         let service = TranslationService::new(
             "http://example.com".to_string(),
-            Duration::from_secs(10),
+            crate::http::build_client(
+                &crate::http::ClientSettings::new(
+                    Duration::from_secs(10),
+                ),
+            ),
+            Duration::from_secs(60),
+        );
+        let style = service.select_style(
+            &Some("forest".to_string()),
+            &[],
+            false,
+            false,
+        );
+        assert_eq!(style.as_str(), "shakespeare");
+    }
+
+    #[test]
+    fn test_translator_selection_mythical() {
+        let service = TranslationService::new(
+            "http://example.com".to_string(),
+            crate::http::build_client(
+                &crate::http::ClientSettings::new(
+                    Duration::from_secs(10),
+                ),
+            ),
+            Duration::from_secs(60),
+        );
+        let forest = Some("forest".to_string());
+        assert_eq!(
+            service.select_style(&forest, &[], false, true),
+            Style::Shakespeare
+        );
+        let service = service.with_mythical_as_legendary(true);
+        assert_eq!(
+            service.select_style(&forest, &[], false, true),
+            Style::Yoda
+        );
+    }
+
+    #[test]
+    fn test_translator_selection_yoda_rule() {
+        let service = TranslationService::new(
+            "http://example.com".to_string(),
+            crate::http::build_client(
+                &crate::http::ClientSettings::new(
+                    Duration::from_secs(10),
+                ),
+            ),
+            Duration::from_secs(60),
+        )
+        .with_yoda_rule(YodaRule::parse("Rare, mountain", "ghost"));
+        let style = |habitat: &str, types: &[&str]| {
+            let types: Vec<String> =
+                types.iter().map(|name| name.to_string()).collect();
+            service.select_style(
+                &Some(habitat.to_string()),
+                &types,
+                false,
+                false,
+            )
+        };
+        assert_eq!(style("rare", &[]), Style::Yoda);
+        assert_eq!(
+            style("forest", &["poison", "ghost"]),
+            Style::Yoda
+        );
+        assert_eq!(style("cave", &["poison"]), Style::Shakespeare);
+        assert_eq!(
+            YodaRule::parse("", "").habitats,
+            Vec::<String>::new()
+        );
+    }
+
+    async fn compared(
+        funtranslations: ResponseTemplate,
+    ) -> Comparison {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/yoda.json"))
+            .and(body_json(
+                json!({"text": "Their electricity could build storms."}),
+            ))
+            .respond_with(funtranslations)
+            .expect(1)
+            .mount(&server)
+            .await;
+        TranslationService::new(
+            server.uri(),
+            reqwest::Client::new(),
+            Duration::from_secs(60),
+        )
+        .compare("Their electricity could build storms.", Style::Yoda)
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_compare() {
+        let comparison = compared(
+            ResponseTemplate::new(200).set_body_json(json!({
+                "contents": {
+                    "translated": "Build storms, their electricity could, yes."
+                }
+            })),
+        )
+        .await;
+        assert_eq!(
+            serde_json::to_value(&comparison).unwrap(),
+            json!({
+                "funtranslations": {
+                    "translated": "Build storms, their electricity could, yes."
+                },
+                "offline": "Build storms, their electricity could.",
+                "diff": [
+                    {"op": "equal", "text": "Build storms, their electricity"},
+                    {"op": "delete", "text": "could, yes."},
+                    {"op": "insert", "text": "could."},
+                ]
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_compare_keeps_the_offline_output() {
+        let comparison = compared(ResponseTemplate::new(429)).await;
+        assert!(matches!(
+            comparison.funtranslations,
+            Outcome::Error(_)
+        ));
+        assert_eq!(
+            comparison.offline,
+            "Build storms, their electricity could."
         );
-        let translator = service
-            .select_translator(&Some("forest".to_string()), false);
-        assert_eq!(translator.as_str(), "shakespeare");
+        assert_eq!(comparison.diff, None);
     }
 
     #[test]
     fn test_translator_as_str() {
-        assert_eq!(Translator::Yoda.as_str(), "yoda");
-        assert_eq!(Translator::Shakespeare.as_str(), "shakespeare");
+        assert_eq!(Style::Yoda.as_str(), "yoda");
+        assert_eq!(Style::Shakespeare.as_str(), "shakespeare");
+        assert_eq!(Style::parse("Yoda"), Some(Style::Yoda));
+        assert_eq!(Style::parse("pirate"), None);
     }
 }
@@ -0,0 +1,207 @@
+use crate::error::{AppError, FieldError, Result};
+use crate::flags::Feature;
+use crate::history;
+use crate::jobs::{JobContext, JobHandler, Progress};
+use crate::lang::Lang;
+use crate::pokeapi::{NamedApiResource, PokeApiClient};
+use crate::pokemon::{PokemonService, species_summaries};
+use crate::translation::TranslationService;
+use axum::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::{sync::Arc, time::Duration};
+use tokio::time::MissedTickBehavior;
+
+/// The translation of one species' description.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TranslationResult {
+    pub name: String,
+    pub description: Option<String>,
+    pub translated: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PokeApiGeneration {
+    pokemon_species: Vec<NamedApiResource>,
+}
+
+/// `translate-generation` jobs: translate the description of every
+/// species of `{"generation": ...}`, spacing the translations so
+/// that jobs stay within the translation API's budget.
+pub struct TranslateGeneration {
+    pokeapi: PokeApiClient,
+    pokemon_service: Arc<PokemonService>,
+    translation_service: Arc<TranslationService>,
+    /// Time between two translations.
+    pace: Duration,
+}
+
+impl TranslateGeneration {
+    pub fn new(
+        pokeapi: PokeApiClient,
+        pokemon_service: Arc<PokemonService>,
+        translation_service: Arc<TranslationService>,
+        pace: Duration,
+    ) -> Self {
+        Self {
+            pokeapi,
+            pokemon_service,
+            translation_service,
+            pace,
+        }
+    }
+
+    /// Translates the description of `name` by the usual style
+    /// rules, recording instead of returning the errors.
+    async fn translate(&self, name: &str) -> TranslationResult {
+        let mut result = TranslationResult {
+            name: name.to_string(),
+            description: None,
+            translated: None,
+            error: None,
+        };
+        let pokemon = match self
+            .pokemon_service
+            .get_pokemon(name, &Lang::default())
+            .await
+        {
+            Ok(pokemon) => pokemon,
+            Err(e) => {
+                result.error = Some(e.to_string());
+                return result;
+            }
+        };
+        let Some(description) = pokemon.description.clone() else {
+            result.error = Some("No description".to_string());
+            return result;
+        };
+
+        let style = self
+            .translation_service
+            .style_of(&pokemon, &self.pokemon_service)
+            .await;
+        match self
+            .translation_service
+            .translate_with(&description, style)
+            .await
+        {
+            Ok(translated) => result.translated = Some(translated),
+            Err(e) => result.error = Some(e.to_string()),
+        }
+        result.description = Some(description);
+        result
+    }
+}
+
+#[async_trait]
+impl JobHandler for TranslateGeneration {
+    fn kind(&self) -> &'static str {
+        "translate-generation"
+    }
+
+    fn feature(&self) -> Option<Feature> {
+        Some(Feature::Translation)
+    }
+
+    fn params(&self, params: Value) -> Result<Value> {
+        let generation = match params.get("generation") {
+            Some(Value::String(value)) => value.clone(),
+            Some(Value::Number(value)) => value.to_string(),
+            _ => {
+                return Err(AppError::ValidationError(vec![
+                    FieldError::new(
+                        "generation",
+                        "required",
+                        "A generation is required",
+                    ),
+                ]));
+            }
+        };
+        let name =
+            parse_generation(&generation).ok_or_else(|| {
+                AppError::ValidationError(vec![FieldError::new(
+                    "generation",
+                    "unknown",
+                    format!(
+                        "Unknown generation '{}'; expected 1-9",
+                        generation
+                    ),
+                )])
+            })?;
+        Ok(json!({ "generation": name }))
+    }
+
+    async fn run(&self, job: &JobContext<'_>) -> Result<Vec<Value>> {
+        let generation = job.params()["generation"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        let list: PokeApiGeneration = self
+            .pokeapi
+            .get(&format!("generation/{}", generation), || {
+                format!("Generation '{}' not found", generation)
+            })
+            .await?;
+        let species = species_summaries(list.pokemon_species);
+        let mut progress = Progress {
+            total: species.len(),
+            ..Progress::default()
+        };
+        job.progress(progress)?;
+
+        let mut ticker = tokio::time::interval(self.pace);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut rows = Vec::with_capacity(species.len());
+        for species in species {
+            ticker.tick().await;
+            let result = self.translate(&species.name).await;
+            progress.processed += 1;
+            if result.error.is_some() {
+                progress.failed += 1;
+            }
+            job.progress(progress)?;
+            rows.push(json!(result));
+        }
+        Ok(rows)
+    }
+}
+
+/// Normalizes `1`, `i` or `generation-i` to PokeAPI's
+/// `generation-i`.
+pub fn parse_generation(value: &str) -> Option<String> {
+    let value = value.trim().to_lowercase();
+    if let Ok(number) = value.parse::<usize>() {
+        return history::generation_name(number);
+    }
+    let name = if value.starts_with("generation-") {
+        value
+    } else {
+        format!("generation-{}", value)
+    };
+    history::generation_number(&name).map(|_| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_generation() {
+        assert_eq!(
+            parse_generation("1").as_deref(),
+            Some("generation-i")
+        );
+        assert_eq!(
+            parse_generation("IV").as_deref(),
+            Some("generation-iv")
+        );
+        assert_eq!(
+            parse_generation("generation-ix").as_deref(),
+            Some("generation-ix")
+        );
+        assert_eq!(parse_generation("10"), None);
+        assert_eq!(parse_generation("kanto"), None);
+    }
+}
@@ -0,0 +1,388 @@
+use crate::cache::{Cache, ManagedCache};
+use crate::error::{AppError, Result};
+use crate::http::Upstream;
+use axum::{async_trait, body::Bytes};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{sync::Arc, time::Duration};
+use tracing::{debug, instrument};
+
+/// Synthesized speech and its media type.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Audio {
+    pub content_type: String,
+    /// Base64 in cache snapshots.
+    #[serde(
+        serialize_with = "serialize_base64",
+        deserialize_with = "deserialize_base64"
+    )]
+    pub bytes: Bytes,
+}
+
+fn serialize_base64<S: Serializer>(
+    bytes: &Bytes,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_str(&STANDARD.encode(bytes))
+}
+
+fn deserialize_base64<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Bytes, D::Error> {
+    let encoded = String::deserialize(deserializer)?;
+    STANDARD
+        .decode(encoded)
+        .map(Bytes::from)
+        .map_err(serde::de::Error::custom)
+}
+
+/// A text-to-speech provider.
+#[async_trait]
+pub trait Synthesizer: Send + Sync {
+    /// Reads `text` aloud in `language`, an ISO 639 code such as
+    /// `en` or `pt-br`.
+    async fn synthesize(
+        &self,
+        text: &str,
+        language: &str,
+    ) -> Result<Audio>;
+}
+
+/// The text-to-speech services that can be configured with
+/// `TTS_PROVIDER`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Provider {
+    /// The OpenAI speech API, or a compatible self-hosted server.
+    OpenAi,
+    MaryTts,
+}
+
+impl Provider {
+    /// `None` means text-to-speech is disabled.
+    pub fn parse(
+        value: &str,
+    ) -> std::result::Result<Option<Self>, String> {
+        match value.trim().to_lowercase().as_str() {
+            "" | "none" => Ok(None),
+            "openai" => Ok(Some(Provider::OpenAi)),
+            "marytts" => Ok(Some(Provider::MaryTts)),
+            other => Err(format!(
+                "unknown provider '{}', expected none, openai or marytts",
+                other
+            )),
+        }
+    }
+
+    pub fn default_url(self) -> &'static str {
+        match self {
+            Provider::OpenAi => "https://api.openai.com",
+            Provider::MaryTts => "http://localhost:59125",
+        }
+    }
+
+    /// Builds the synthesizer for this provider, sending its
+    /// requests through `upstream`. `voice` falls back to the
+    /// provider's default voice.
+    pub fn build(
+        self,
+        upstream: Upstream,
+        base_url: String,
+        api_key: Option<String>,
+        voice: Option<String>,
+    ) -> Arc<dyn Synthesizer> {
+        let base_url = base_url.trim_end_matches('/').to_string();
+        match self {
+            Provider::OpenAi => Arc::new(OpenAi {
+                upstream,
+                base_url,
+                api_key: api_key.unwrap_or_default(),
+                voice: voice.unwrap_or_else(|| "alloy".to_string()),
+            }),
+            Provider::MaryTts => Arc::new(MaryTts {
+                upstream,
+                base_url,
+                voice,
+            }),
+        }
+    }
+}
+
+/// Reads Pokemon descriptions aloud, caching the audio as the
+/// providers are slow and billed per character.
+pub struct SpeechService {
+    synthesizer: Option<Arc<dyn Synthesizer>>,
+    cache: Cache<(String, String), Audio>,
+}
+
+impl SpeechService {
+    pub fn new(cache_ttl: Duration) -> Self {
+        Self {
+            synthesizer: None,
+            cache: Cache::new(cache_ttl),
+        }
+    }
+
+    pub fn with_synthesizer(
+        mut self,
+        synthesizer: Arc<dyn Synthesizer>,
+    ) -> Self {
+        self.synthesizer = Some(synthesizer);
+        self
+    }
+
+    /// The caches owned by this service, by name.
+    pub fn caches(&self) -> Vec<(&'static str, &dyn ManagedCache)> {
+        vec![("tts", &self.cache)]
+    }
+
+    #[instrument(skip(self, text), fields(text_length = text.len()))]
+    pub async fn synthesize(
+        &self,
+        text: &str,
+        language: &str,
+    ) -> Result<Audio> {
+        let Some(synthesizer) = &self.synthesizer else {
+            return Err(AppError::NotFound(
+                "Text-to-speech is not configured".to_string(),
+            ));
+        };
+        let key = (language.to_lowercase(), text.to_string());
+        if let Some(audio) = self.cache.get(&key) {
+            return Ok(audio);
+        }
+
+        let audio = synthesizer.synthesize(text, &key.0).await?;
+        self.cache.insert(key, audio.clone());
+        Ok(audio)
+    }
+}
+
+async fn read_audio(
+    upstream: &Upstream,
+    name: &str,
+    response: reqwest::Response,
+    content_type: &'static str,
+) -> Result<Audio> {
+    if !response.status().is_success() {
+        return Err(AppError::ExternalApi(format!(
+            "{} returned status: {}",
+            name,
+            response.status()
+        )));
+    }
+    let bytes = upstream.read_bytes(response).await?;
+    if bytes.is_empty() {
+        return Err(AppError::UpstreamSchema(format!(
+            "{} returned no audio",
+            name
+        )));
+    }
+    Ok(Audio {
+        content_type: content_type.to_string(),
+        bytes: Bytes::from(bytes),
+    })
+}
+
+/// The [OpenAI speech API](https://platform.openai.com/docs/api-reference/audio/createSpeech).
+/// Its voices are multilingual, so the language is inferred from the
+/// text.
+struct OpenAi {
+    upstream: Upstream,
+    base_url: String,
+    api_key: String,
+    voice: String,
+}
+
+#[derive(Serialize)]
+struct OpenAiRequest<'a> {
+    model: &'a str,
+    input: &'a str,
+    voice: &'a str,
+    response_format: &'a str,
+}
+
+#[async_trait]
+impl Synthesizer for OpenAi {
+    async fn synthesize(
+        &self,
+        text: &str,
+        _language: &str,
+    ) -> Result<Audio> {
+        let url = format!("{}/v1/audio/speech", self.base_url);
+        debug!(voice = %self.voice, "Synthesizing with OpenAI");
+        let response = self
+            .upstream
+            .send(
+                self.upstream
+                    .post(&url)
+                    .bearer_auth(&self.api_key)
+                    .json(&OpenAiRequest {
+                        model: "tts-1",
+                        input: text,
+                        voice: &self.voice,
+                        response_format: "mp3",
+                    }),
+            )
+            .await?;
+
+        read_audio(&self.upstream, "OpenAI", response, "audio/mpeg")
+            .await
+    }
+}
+
+/// A [MaryTTS](https://marytts.github.io) server, usually
+/// self-hosted.
+struct MaryTts {
+    upstream: Upstream,
+    base_url: String,
+    voice: Option<String>,
+}
+
+#[async_trait]
+impl Synthesizer for MaryTts {
+    async fn synthesize(
+        &self,
+        text: &str,
+        language: &str,
+    ) -> Result<Audio> {
+        let url = format!("{}/process", self.base_url);
+        let locale = mary_locale(language);
+        debug!(%locale, "Synthesizing with MaryTTS");
+
+        let mut query = vec![
+            ("INPUT_TEXT", text),
+            ("INPUT_TYPE", "TEXT"),
+            ("OUTPUT_TYPE", "AUDIO"),
+            ("AUDIO", "WAVE_FILE"),
+            ("LOCALE", locale.as_str()),
+        ];
+        if let Some(voice) = &self.voice {
+            query.push(("VOICE", voice.as_str()));
+        }
+        let response = self
+            .upstream
+            .send(self.upstream.get(&url).query(&query))
+            .await?;
+
+        read_audio(&self.upstream, "MaryTTS", response, "audio/wav")
+            .await
+    }
+}
+
+/// MaryTTS locales use an underscore and an uppercase region, e.g.
+/// `en_US`.
+fn mary_locale(language: &str) -> String {
+    match language.split_once('-') {
+        Some((language, region)) => format!(
+            "{}_{}",
+            language.to_lowercase(),
+            region.to_uppercase()
+        ),
+        None => language.to_lowercase(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::Client;
+    use wiremock::matchers::{
+        body_json, header, method, path, query_param,
+    };
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn synthesizer(
+        provider: Provider,
+        server: &MockServer,
+        api_key: Option<&str>,
+    ) -> Arc<dyn Synthesizer> {
+        provider.build(
+            Upstream::new("TTS", Client::new()),
+            server.uri(),
+            api_key.map(str::to_string),
+            None,
+        )
+    }
+
+    #[test]
+    fn test_mary_locale() {
+        assert_eq!(mary_locale("pt-br"), "pt_BR");
+        assert_eq!(mary_locale("IT"), "it");
+    }
+
+    #[tokio::test]
+    async fn test_openai() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/audio/speech"))
+            .and(header("authorization", "Bearer secret"))
+            .and(body_json(serde_json::json!({
+                "model": "tts-1",
+                "input": "Pika pika",
+                "voice": "alloy",
+                "response_format": "mp3"
+            })))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_bytes(b"ID3mp3"),
+            )
+            .mount(&server)
+            .await;
+
+        let audio =
+            synthesizer(Provider::OpenAi, &server, Some("secret"))
+                .synthesize("Pika pika", "en")
+                .await
+                .unwrap();
+        assert_eq!(audio.content_type, "audio/mpeg");
+        assert_eq!(&audio.bytes[..], b"ID3mp3");
+    }
+
+    #[tokio::test]
+    async fn test_service_caches_audio_per_language() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/process"))
+            .and(query_param("INPUT_TEXT", "Pika pika"))
+            .and(query_param("LOCALE", "en_US"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_bytes(b"RIFFwav"),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/process"))
+            .and(query_param("LOCALE", "it"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+
+        let service = SpeechService::new(Duration::from_secs(60))
+            .with_synthesizer(synthesizer(
+                Provider::MaryTts,
+                &server,
+                None,
+            ));
+        for _ in 0..2 {
+            let audio = service
+                .synthesize("Pika pika", "en-US")
+                .await
+                .unwrap();
+            assert_eq!(audio.content_type, "audio/wav");
+        }
+        assert_eq!(service.cache.stats().hits, 1);
+        assert!(matches!(
+            service.synthesize("Pika pika", "it").await,
+            Err(AppError::ExternalApi(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_unconfigured_service() {
+        let service = SpeechService::new(Duration::from_secs(60));
+        assert!(matches!(
+            service.synthesize("Pika pika", "en").await,
+            Err(AppError::NotFound(_))
+        ));
+    }
+}
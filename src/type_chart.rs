@@ -0,0 +1,173 @@
+use crate::cache::{Cache, ManagedCache};
+use crate::error::Result;
+use crate::pokeapi::{NamedApiResource, PokeApiClient};
+use futures::future::try_join_all;
+use serde::{Deserialize, Serialize};
+use std::{sync::Arc, time::Duration};
+
+/// The attacking types of the current generation.
+pub const ALL_TYPES: [&str; 18] = [
+    "normal", "fighting", "flying", "poison", "ground", "rock",
+    "bug", "ghost", "steel", "fire", "water", "grass", "electric",
+    "psychic", "ice", "dragon", "dark", "fairy",
+];
+
+/// Damage relations of a single type.
+#[derive(
+    Debug, Clone, Default, PartialEq, Serialize, Deserialize,
+)]
+pub struct TypeRelations {
+    pub name: String,
+    pub double_damage_from: Vec<String>,
+    pub half_damage_from: Vec<String>,
+    pub no_damage_from: Vec<String>,
+    pub double_damage_to: Vec<String>,
+}
+
+impl TypeRelations {
+    /// Multiplier applied when `attacking` hits this type.
+    pub fn defensive_multiplier(&self, attacking: &str) -> f32 {
+        let has =
+            |types: &[String]| types.iter().any(|t| t == attacking);
+        if has(&self.no_damage_from) {
+            0.0
+        } else if has(&self.double_damage_from) {
+            2.0
+        } else if has(&self.half_damage_from) {
+            0.5
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Multiplier applied when `attacking` hits a Pokemon of the given
+/// (one or two) types.
+pub fn effectiveness(
+    defending: &[TypeRelations],
+    attacking: &str,
+) -> f32 {
+    defending
+        .iter()
+        .map(|relations| relations.defensive_multiplier(attacking))
+        .product()
+}
+
+#[derive(Deserialize)]
+struct PokeApiType {
+    name: String,
+    damage_relations: PokeApiDamageRelations,
+}
+
+#[derive(Deserialize)]
+struct PokeApiDamageRelations {
+    double_damage_from: Vec<NamedApiResource>,
+    half_damage_from: Vec<NamedApiResource>,
+    no_damage_from: Vec<NamedApiResource>,
+    double_damage_to: Vec<NamedApiResource>,
+}
+
+pub struct TypeService {
+    pokeapi: PokeApiClient,
+    cache: Cache<String, Arc<TypeRelations>>,
+}
+
+impl TypeService {
+    pub fn new(pokeapi: PokeApiClient, cache_ttl: Duration) -> Self {
+        Self {
+            pokeapi,
+            cache: Cache::new(cache_ttl),
+        }
+    }
+
+    /// The caches owned by this service, by name.
+    pub fn caches(&self) -> Vec<(&'static str, &dyn ManagedCache)> {
+        vec![("type.relations", &self.cache)]
+    }
+
+    pub async fn get_relations(
+        &self,
+        name: &str,
+    ) -> Result<Arc<TypeRelations>> {
+        let name = name.to_lowercase();
+        if let Some(relations) = self.cache.get(&name) {
+            return Ok(relations);
+        }
+
+        let pokemon_type: PokeApiType = self
+            .pokeapi
+            .get(&format!("type/{}", name), || {
+                format!("Type '{}' not found", name)
+            })
+            .await?;
+
+        let names = |resources: Vec<NamedApiResource>| {
+            resources.into_iter().map(|r| r.name).collect()
+        };
+        let relations = pokemon_type.damage_relations;
+        let relations = Arc::new(TypeRelations {
+            name: pokemon_type.name,
+            double_damage_from: names(relations.double_damage_from),
+            half_damage_from: names(relations.half_damage_from),
+            no_damage_from: names(relations.no_damage_from),
+            double_damage_to: names(relations.double_damage_to),
+        });
+        self.cache.insert(name, relations.clone());
+        Ok(relations)
+    }
+
+    pub async fn get_all_relations(
+        &self,
+        names: &[String],
+    ) -> Result<Vec<Arc<TypeRelations>>> {
+        try_join_all(
+            names.iter().map(|name| self.get_relations(name)),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn relations(
+        name: &str,
+        double_from: &[&str],
+        half_from: &[&str],
+        no_from: &[&str],
+    ) -> TypeRelations {
+        let strings =
+            |v: &[&str]| v.iter().map(|s| s.to_string()).collect();
+        TypeRelations {
+            name: name.to_string(),
+            double_damage_from: strings(double_from),
+            half_damage_from: strings(half_from),
+            no_damage_from: strings(no_from),
+            double_damage_to: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_dual_type_effectiveness() {
+        // Gyarados: water/flying.
+        let water = relations(
+            "water",
+            &["electric", "grass"],
+            &["fire", "water", "ice", "steel"],
+            &[],
+        );
+        let flying = relations(
+            "flying",
+            &["electric", "ice", "rock"],
+            &["grass", "fighting", "bug"],
+            &["ground"],
+        );
+        let gyarados = [water, flying];
+        assert_eq!(effectiveness(&gyarados, "electric"), 4.0);
+        assert_eq!(effectiveness(&gyarados, "grass"), 1.0);
+        assert_eq!(effectiveness(&gyarados, "ground"), 0.0);
+        assert_eq!(effectiveness(&gyarados, "ice"), 1.0);
+        assert_eq!(effectiveness(&gyarados, "normal"), 1.0);
+    }
+}
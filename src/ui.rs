@@ -0,0 +1,120 @@
+use crate::error::AppError;
+use axum::{
+    Router,
+    extract::Path,
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use rust_embed::RustEmbed;
+
+/// The single-page UI, embedded in the binary at build time.
+#[derive(RustEmbed)]
+#[folder = "ui/"]
+struct Assets;
+
+const INDEX: &str = "index.html";
+
+/// Serves the UI at `/ui`, on top of the public API routes.
+pub fn router<S>() -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/ui", get(index))
+        .route("/ui/", get(index))
+        .route("/ui/*path", get(file))
+}
+
+async fn index(headers: HeaderMap) -> Response {
+    asset(INDEX, &headers)
+}
+
+async fn file(
+    Path(path): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    asset(&path, &headers)
+}
+
+/// Responds with the asset at `path`, or with the index for the
+/// client-side routes, which have no extension. Assets are
+/// revalidated by their hash.
+fn asset(path: &str, headers: &HeaderMap) -> Response {
+    let file = Assets::get(path).or_else(|| {
+        (!path.contains('.')).then(|| Assets::get(INDEX)).flatten()
+    });
+    let Some(file) = file else {
+        return AppError::NotFound(format!(
+            "UI asset '{}' not found",
+            path
+        ))
+        .into_response();
+    };
+
+    let etag =
+        format!("\"{}\"", hex::encode(file.metadata.sha256_hash()));
+    let cached = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value.split(',').any(|tag| tag.trim() == etag)
+        });
+    let mut response = if cached {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        let mut response = file.data.into_owned().into_response();
+        if let Ok(value) =
+            HeaderValue::from_str(file.metadata.mimetype())
+        {
+            response
+                .headers_mut()
+                .insert(header::CONTENT_TYPE, value);
+        }
+        response
+    };
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        response.headers_mut().insert(header::ETAG, value);
+    }
+    response.headers_mut().insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("no-cache"),
+    );
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_asset_routes() {
+        let headers = HeaderMap::new();
+        let response = asset("app.js", &headers);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/javascript"
+        );
+
+        // Client-side routes get the index, missing files a 404.
+        let response = asset("pokemon/pikachu", &headers);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/html"
+        );
+        let response = asset("missing.css", &headers);
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_asset_revalidation() {
+        let etag = asset(INDEX, &HeaderMap::new()).headers()
+            [header::ETAG]
+            .clone();
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, etag);
+        let response = asset(INDEX, &headers);
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    }
+}
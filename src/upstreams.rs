@@ -0,0 +1,393 @@
+use crate::events::{Event, EventBus};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tracing::warn;
+
+/// Period over which the success rate is computed.
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// Outcomes kept for the success rate, whatever their age.
+const MAX_OUTCOMES: usize = 1000;
+
+/// When a circuit opens, from `CIRCUIT_BREAKER_*`.
+#[derive(Debug, Clone, Copy)]
+pub struct BreakerSettings {
+    /// Consecutive failures opening the circuit; zero disables it.
+    pub failures: u32,
+    /// How long an open circuit fails calls before letting a probe
+    /// through.
+    pub cooldown: Duration,
+}
+
+impl BreakerSettings {
+    pub const DISABLED: Self = Self {
+        failures: 0,
+        cooldown: Duration::ZERO,
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// Calls go through.
+    Closed,
+    /// Calls fail fast until the cooldown is over.
+    Open,
+    /// One probe call decides whether the circuit closes again.
+    HalfOpen,
+}
+
+#[derive(Debug)]
+enum Circuit {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    HalfOpen { probing: bool },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LastError {
+    pub message: String,
+    /// Seconds since the Unix epoch.
+    pub at: u64,
+}
+
+/// Live statistics of one upstream, shared by its clients.
+#[derive(Debug, Clone, Serialize)]
+pub struct UpstreamSnapshot {
+    pub name: &'static str,
+    pub in_flight: u64,
+    /// Calls over the last minute.
+    pub requests: usize,
+    /// Share of successful calls over the last minute, `None`
+    /// without calls.
+    pub success_rate: Option<f64>,
+    pub last_error: Option<LastError>,
+    pub circuit: CircuitState,
+    /// Seconds since the circuit opened, while it is not closed.
+    pub circuit_open_secs: Option<u64>,
+    /// Calls and failed calls since startup.
+    pub total_calls: u64,
+    pub total_failures: u64,
+}
+
+struct State {
+    circuit: Circuit,
+    /// When the circuit last opened after being closed.
+    opened_at: Option<Instant>,
+    calls: u64,
+    failures: u64,
+    /// (finished, succeeded), oldest first.
+    outcomes: VecDeque<(Instant, bool)>,
+    last_error: Option<LastError>,
+}
+
+/// Calls, failures and the circuit breaker of one upstream.
+pub struct UpstreamStats {
+    name: &'static str,
+    breaker: BreakerSettings,
+    events: EventBus,
+    in_flight: AtomicU64,
+    state: Mutex<State>,
+}
+
+impl UpstreamStats {
+    pub fn new(name: &'static str, breaker: BreakerSettings) -> Self {
+        Self {
+            name,
+            breaker,
+            events: EventBus::default(),
+            in_flight: AtomicU64::new(0),
+            state: Mutex::new(State {
+                circuit: Circuit::Closed { failures: 0 },
+                opened_at: None,
+                calls: 0,
+                failures: 0,
+                outcomes: VecDeque::new(),
+                last_error: None,
+            }),
+        }
+    }
+
+    /// Admits a call, unless the circuit is open. The call must be
+    /// reported with `Call::finish`.
+    pub fn start(&self) -> Option<Call<'_>> {
+        let mut state = self.state.lock().unwrap();
+        match state.circuit {
+            Circuit::Closed { .. } => {}
+            Circuit::Open { until } if Instant::now() >= until => {
+                state.circuit = Circuit::HalfOpen { probing: true };
+            }
+            Circuit::Open { .. }
+            | Circuit::HalfOpen { probing: true } => {
+                return None;
+            }
+            Circuit::HalfOpen { probing: false } => {
+                state.circuit = Circuit::HalfOpen { probing: true };
+            }
+        }
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        Some(Call {
+            stats: self,
+            finished: false,
+        })
+    }
+
+    fn finish(&self, error: Option<String>) {
+        self.in_flight.fetch_sub(1, Ordering::Relaxed);
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        state.outcomes.push_back((now, error.is_none()));
+        prune(&mut state.outcomes, now);
+        state.calls += 1;
+
+        let Some(message) = error else {
+            state.circuit = Circuit::Closed { failures: 0 };
+            state.opened_at = None;
+            return;
+        };
+        state.failures += 1;
+        state.last_error = Some(LastError {
+            message,
+            at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        });
+        if self.breaker.failures == 0 {
+            return;
+        }
+        let failures = match state.circuit {
+            Circuit::Closed { failures } => failures + 1,
+            // A failed probe reopens the circuit.
+            _ => self.breaker.failures,
+        };
+        state.circuit = if failures >= self.breaker.failures {
+            warn!(
+                upstream = self.name,
+                cooldown = ?self.breaker.cooldown,
+                "Circuit opened"
+            );
+            self.events.publish(Event::UpstreamCircuitOpened {
+                upstream: self.name.to_string(),
+                cooldown_secs: self.breaker.cooldown.as_secs(),
+            });
+            state.opened_at.get_or_insert(now);
+            Circuit::Open {
+                until: now + self.breaker.cooldown,
+            }
+        } else {
+            Circuit::Closed { failures }
+        };
+    }
+
+    pub fn snapshot(&self) -> UpstreamSnapshot {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        prune(&mut state.outcomes, now);
+        let requests = state.outcomes.len();
+        let successes =
+            state.outcomes.iter().filter(|(_, ok)| *ok).count();
+        UpstreamSnapshot {
+            name: self.name,
+            in_flight: self.in_flight.load(Ordering::Relaxed),
+            requests,
+            success_rate: (requests > 0)
+                .then(|| successes as f64 / requests as f64),
+            last_error: state.last_error.clone(),
+            circuit: match state.circuit {
+                Circuit::Closed { .. } => CircuitState::Closed,
+                Circuit::Open { until } if now < until => {
+                    CircuitState::Open
+                }
+                Circuit::Open { .. } | Circuit::HalfOpen { .. } => {
+                    CircuitState::HalfOpen
+                }
+            },
+            circuit_open_secs: state
+                .opened_at
+                .map(|opened_at| (now - opened_at).as_secs()),
+            total_calls: state.calls,
+            total_failures: state.failures,
+        }
+    }
+}
+
+fn prune(outcomes: &mut VecDeque<(Instant, bool)>, now: Instant) {
+    while outcomes.len() > MAX_OUTCOMES
+        || outcomes
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) > WINDOW)
+    {
+        outcomes.pop_front();
+    }
+}
+
+/// A call in flight. Dropping it unfinished, e.g. when the request
+/// is cancelled, counts neither as a success nor as a failure.
+pub struct Call<'a> {
+    stats: &'a UpstreamStats,
+    finished: bool,
+}
+
+impl Call<'_> {
+    /// `error` describes the failure of a failed call.
+    pub fn finish(mut self, error: Option<String>) {
+        self.finished = true;
+        self.stats.finish(error);
+    }
+}
+
+impl Drop for Call<'_> {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+        self.stats.in_flight.fetch_sub(1, Ordering::Relaxed);
+        // Let the next call probe a half-open circuit.
+        let mut state = self.stats.state.lock().unwrap();
+        if let Circuit::HalfOpen { probing: true } = state.circuit {
+            state.circuit = Circuit::HalfOpen { probing: false };
+        }
+    }
+}
+
+/// The upstreams of the application, by name.
+pub struct UpstreamRegistry {
+    breaker: BreakerSettings,
+    events: EventBus,
+    upstreams: Mutex<BTreeMap<&'static str, Arc<UpstreamStats>>>,
+}
+
+impl UpstreamRegistry {
+    pub fn new(breaker: BreakerSettings) -> Self {
+        Self {
+            breaker,
+            events: EventBus::default(),
+            upstreams: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Publishes `UpstreamCircuitOpened` on `events`.
+    pub fn with_events(mut self, events: EventBus) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// The statistics of `name`, shared by every client of it.
+    pub fn register(&self, name: &'static str) -> Arc<UpstreamStats> {
+        self.upstreams
+            .lock()
+            .unwrap()
+            .entry(name)
+            .or_insert_with(|| {
+                let mut stats =
+                    UpstreamStats::new(name, self.breaker);
+                stats.events = self.events.clone();
+                Arc::new(stats)
+            })
+            .clone()
+    }
+
+    /// Every upstream, by name.
+    pub fn snapshot(&self) -> Vec<UpstreamSnapshot> {
+        self.upstreams
+            .lock()
+            .unwrap()
+            .values()
+            .map(|stats| stats.snapshot())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(cooldown: Duration) -> UpstreamStats {
+        UpstreamStats::new(
+            "PokeAPI",
+            BreakerSettings {
+                failures: 2,
+                cooldown,
+            },
+        )
+    }
+
+    #[test]
+    fn test_snapshot_reports_calls() {
+        let stats = stats(Duration::from_secs(30));
+        let call = stats.start().unwrap();
+        assert_eq!(stats.snapshot().in_flight, 1);
+        call.finish(None);
+        stats.start().unwrap().finish(Some("boom".to_string()));
+        drop(stats.start().unwrap());
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.in_flight, 0);
+        assert_eq!(snapshot.requests, 2);
+        assert_eq!(snapshot.success_rate, Some(0.5));
+        assert_eq!(snapshot.last_error.unwrap().message, "boom");
+        assert_eq!(snapshot.circuit, CircuitState::Closed);
+        assert_eq!(snapshot.circuit_open_secs, None);
+        assert_eq!(
+            (snapshot.total_calls, snapshot.total_failures),
+            (2, 1)
+        );
+    }
+
+    #[test]
+    fn test_circuit_opens_after_consecutive_failures() {
+        let stats = stats(Duration::from_secs(30));
+        stats.start().unwrap().finish(Some("boom".to_string()));
+        stats.start().unwrap().finish(None);
+        stats.start().unwrap().finish(Some("boom".to_string()));
+        assert_eq!(stats.snapshot().circuit, CircuitState::Closed);
+        stats.start().unwrap().finish(Some("boom".to_string()));
+        assert_eq!(stats.snapshot().circuit, CircuitState::Open);
+        assert!(stats.start().is_none());
+    }
+
+    #[test]
+    fn test_half_open_circuit_lets_one_probe_through() {
+        let stats = stats(Duration::ZERO);
+        stats.start().unwrap().finish(Some("boom".to_string()));
+        stats.start().unwrap().finish(Some("boom".to_string()));
+        assert_eq!(stats.snapshot().circuit, CircuitState::HalfOpen);
+        assert_eq!(stats.snapshot().circuit_open_secs, Some(0));
+
+        let probe = stats.start().unwrap();
+        assert!(stats.start().is_none());
+        probe.finish(None);
+        assert_eq!(stats.snapshot().circuit, CircuitState::Closed);
+        assert_eq!(stats.snapshot().circuit_open_secs, None);
+    }
+
+    #[test]
+    fn test_disabled_breaker_never_opens() {
+        let stats =
+            UpstreamStats::new("PokeAPI", BreakerSettings::DISABLED);
+        for _ in 0..10 {
+            stats.start().unwrap().finish(Some("boom".to_string()));
+        }
+        assert_eq!(stats.snapshot().circuit, CircuitState::Closed);
+    }
+
+    #[test]
+    fn test_registry_shares_stats_by_name() {
+        let registry =
+            UpstreamRegistry::new(BreakerSettings::DISABLED);
+        registry.register("PokeAPI").start().unwrap().finish(None);
+        registry.register("Translation API");
+        let snapshot = registry.snapshot();
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot[0].name, "PokeAPI");
+        assert_eq!(snapshot[0].requests, 1);
+    }
+}
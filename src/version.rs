@@ -0,0 +1,184 @@
+use crate::error::AppError;
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request, State},
+    http::{HeaderMap, HeaderValue, header, request::Parts},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::{convert::Infallible, fmt};
+
+pub const ACCEPT_VERSION_HEADER: &str = "accept-version";
+pub const API_VERSION_HEADER: &str = "api-version";
+
+/// Media type prefix of `Accept: application/vnd.pokedex.v2+json`.
+const VENDOR_MEDIA_TYPE: &str = "application/vnd.pokedex.v";
+
+/// Version of the response envelope. v2 returns the extended details
+/// model from the single Pokemon endpoints.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ApiVersion {
+    #[default]
+    V1,
+    V2,
+}
+
+impl ApiVersion {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().trim_start_matches(['v', 'V']) {
+            "1" => Some(ApiVersion::V1),
+            "2" => Some(ApiVersion::V2),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "1",
+            ApiVersion::V2 => "2",
+        }
+    }
+
+    /// The version asked for by `Accept-Version` or a vendor media
+    /// type in `Accept`, if any.
+    fn requested(
+        headers: &HeaderMap,
+    ) -> Result<Option<Self>, AppError> {
+        let unsupported = |value: &str| {
+            AppError::BadRequest(format!(
+                "Unsupported API version '{}', expected 1 or 2",
+                value
+            ))
+        };
+
+        if let Some(value) = headers.get(ACCEPT_VERSION_HEADER) {
+            let value = value.to_str().unwrap_or_default();
+            return Self::parse(value)
+                .map(Some)
+                .ok_or_else(|| unsupported(value));
+        }
+
+        let accept = headers
+            .get(header::ACCEPT)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        for media_type in accept.split(',') {
+            let media_type = media_type
+                .split(';')
+                .next()
+                .unwrap_or_default()
+                .trim();
+            if let Some(rest) =
+                media_type.strip_prefix(VENDOR_MEDIA_TYPE)
+            {
+                let version = rest.trim_end_matches("+json");
+                return Self::parse(version)
+                    .map(Some)
+                    .ok_or_else(|| unsupported(version));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl fmt::Display for ApiVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "v{}", self.as_str())
+    }
+}
+
+/// Resolves the version of each request: `pinned` by a `/v1` or
+/// `/v2` prefix, otherwise negotiated from the request headers and
+/// defaulting to v1, so unprefixed routes keep their v1 behaviour.
+pub async fn negotiate(
+    State(pinned): State<Option<ApiVersion>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let version = match pinned {
+        Some(version) => version,
+        None => match ApiVersion::requested(request.headers()) {
+            Ok(version) => version.unwrap_or_default(),
+            Err(e) => return e.into_response(),
+        },
+    };
+    request.extensions_mut().insert(version);
+
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    headers.insert(
+        API_VERSION_HEADER,
+        HeaderValue::from_static(version.as_str()),
+    );
+    if pinned.is_none() {
+        headers.append(
+            header::VARY,
+            HeaderValue::from_static(ACCEPT_VERSION_HEADER),
+        );
+    }
+    response
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ApiVersion
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        Ok(parts.extensions.get().copied().unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(name: &'static str, value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(ApiVersion::parse("2"), Some(ApiVersion::V2));
+        assert_eq!(ApiVersion::parse("v1"), Some(ApiVersion::V1));
+        assert_eq!(ApiVersion::parse("3"), None);
+    }
+
+    #[test]
+    fn test_requested_version_from_headers() {
+        assert_eq!(
+            ApiVersion::requested(&HeaderMap::new()).unwrap(),
+            None
+        );
+        assert_eq!(
+            ApiVersion::requested(&headers(
+                ACCEPT_VERSION_HEADER,
+                "v2"
+            ))
+            .unwrap(),
+            Some(ApiVersion::V2)
+        );
+        assert_eq!(
+            ApiVersion::requested(&headers(
+                "accept",
+                "text/html, application/vnd.pokedex.v2+json;q=0.9"
+            ))
+            .unwrap(),
+            Some(ApiVersion::V2)
+        );
+        assert!(
+            ApiVersion::requested(&headers(
+                ACCEPT_VERSION_HEADER,
+                "9"
+            ))
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,210 @@
+//! HMAC-SHA256 signatures of webhook callbacks.
+//!
+//! Callback posts carry an `X-Pokedex-Signature` header such as
+//! `t=1760000000,v1=5257a8...`: the Unix time of the delivery and the
+//! hex HMAC of `"{t}.{body}"` under each signing key. While a key is
+//! being rotated both the new and the old key sign, so receivers
+//! holding either of them keep verifying deliveries.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::{fmt, time::Duration};
+
+pub const SIGNATURE_HEADER: &str = "x-pokedex-signature";
+
+/// How far a delivery's timestamp may be from the receiver's clock
+/// before it is rejected as a replay.
+pub const DEFAULT_TOLERANCE: Duration = Duration::from_secs(300);
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Debug, PartialEq)]
+pub enum SignatureError {
+    /// The header is not `t=<secs>,v1=<hex>[,v1=<hex>...]`.
+    Malformed,
+    /// The timestamp is outside the tolerance.
+    Expired,
+    /// No signature matches any of the keys.
+    Mismatch,
+}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureError::Malformed => {
+                f.write_str("Malformed webhook signature")
+            }
+            SignatureError::Expired => {
+                f.write_str("Webhook signature has expired")
+            }
+            SignatureError::Mismatch => {
+                f.write_str("Webhook signature does not match")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SignatureError {}
+
+/// The shared secrets of the webhook signatures, newest first.
+#[derive(Clone, Default)]
+pub struct SigningKeys {
+    secrets: Vec<String>,
+}
+
+// Keys are secrets: never print them with the configuration.
+impl fmt::Debug for SigningKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SigningKeys({} keys)", self.secrets.len())
+    }
+}
+
+impl SigningKeys {
+    /// Parses comma-separated secrets. To rotate, prepend the new
+    /// secret and drop the old one once every receiver has it.
+    pub fn parse(spec: &str) -> Self {
+        Self {
+            secrets: spec
+                .split(',')
+                .map(str::trim)
+                .filter(|secret| !secret.is_empty())
+                .map(str::to_string)
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.secrets.is_empty()
+    }
+
+    /// The signature header of `body` delivered at `timestamp`
+    /// (Unix seconds), signed with every key.
+    pub fn sign(&self, body: &[u8], timestamp: u64) -> String {
+        let mut header = format!("t={}", timestamp);
+        for secret in &self.secrets {
+            let mac = mac(secret, timestamp, body);
+            header.push_str(",v1=");
+            header
+                .push_str(&hex::encode(mac.finalize().into_bytes()));
+        }
+        header
+    }
+
+    /// Checks `header` against `body`, accepting a signature by any
+    /// of the keys made within `tolerance` of `now` (Unix seconds).
+    pub fn verify(
+        &self,
+        header: &str,
+        body: &[u8],
+        now: u64,
+        tolerance: Duration,
+    ) -> Result<(), SignatureError> {
+        let mut timestamp = None;
+        let mut signatures = Vec::new();
+        for part in header.split(',').map(str::trim) {
+            match part.split_once('=') {
+                Some(("t", value)) => {
+                    timestamp =
+                        Some(value.parse::<u64>().map_err(|_| {
+                            SignatureError::Malformed
+                        })?);
+                }
+                Some(("v1", value)) => signatures.push(
+                    hex::decode(value)
+                        .map_err(|_| SignatureError::Malformed)?,
+                ),
+                // Unknown schemes are skipped for forward
+                // compatibility.
+                Some(_) => {}
+                None => return Err(SignatureError::Malformed),
+            }
+        }
+        let timestamp = timestamp.ok_or(SignatureError::Malformed)?;
+        if signatures.is_empty() {
+            return Err(SignatureError::Malformed);
+        }
+        if now.abs_diff(timestamp) > tolerance.as_secs() {
+            return Err(SignatureError::Expired);
+        }
+
+        let matches = self.secrets.iter().any(|secret| {
+            signatures.iter().any(|signature| {
+                // Constant-time comparison.
+                mac(secret, timestamp, body)
+                    .verify_slice(signature)
+                    .is_ok()
+            })
+        });
+        if matches {
+            Ok(())
+        } else {
+            Err(SignatureError::Mismatch)
+        }
+    }
+}
+
+fn mac(secret: &str, timestamp: u64, body: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: &[u8] = br#"{"event":"pokemon.served"}"#;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let keys = SigningKeys::parse("s3cret");
+        let header = keys.sign(BODY, 1_000);
+        assert!(header.starts_with("t=1000,v1="));
+        assert_eq!(
+            keys.verify(&header, BODY, 1_100, DEFAULT_TOLERANCE),
+            Ok(())
+        );
+        assert_eq!(
+            keys.verify(&header, b"{}", 1_100, DEFAULT_TOLERANCE),
+            Err(SignatureError::Mismatch)
+        );
+        assert_eq!(
+            keys.verify(&header, BODY, 2_000, DEFAULT_TOLERANCE),
+            Err(SignatureError::Expired)
+        );
+        assert_eq!(
+            keys.verify("v1=00", BODY, 1_000, DEFAULT_TOLERANCE),
+            Err(SignatureError::Malformed)
+        );
+    }
+
+    #[test]
+    fn test_rotation_keeps_both_receivers_verifying() {
+        let sender = SigningKeys::parse("new, old");
+        let header = sender.sign(BODY, 1_000);
+        assert_eq!(header.matches("v1=").count(), 2);
+        for receiver in ["new", "old", "old,new"] {
+            assert_eq!(
+                SigningKeys::parse(receiver).verify(
+                    &header,
+                    BODY,
+                    1_000,
+                    DEFAULT_TOLERANCE
+                ),
+                Ok(())
+            );
+        }
+        assert_eq!(
+            SigningKeys::parse("other").verify(
+                &header,
+                BODY,
+                1_000,
+                DEFAULT_TOLERANCE
+            ),
+            Err(SignatureError::Mismatch)
+        );
+    }
+}
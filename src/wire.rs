@@ -0,0 +1,91 @@
+//! The PokeAPI species and funtranslations documents as received,
+//! without dependencies on the rest of the crate beyond `tolerant`
+//! so that the fuzz targets can include them.
+
+use crate::tolerant::Tolerant;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct NamedApiResource {
+    pub name: String,
+    pub url: String,
+}
+
+#[derive(Deserialize)]
+pub struct PokeApiSpecies {
+    pub name: String,
+    pub habitat: Option<Habitat>,
+    pub flavor_text_entries: Tolerant<FlavorTextEntry>,
+    pub is_legendary: bool,
+    pub is_mythical: bool,
+    pub is_baby: bool,
+    pub capture_rate: Option<u32>,
+    pub base_happiness: Option<u32>,
+    pub growth_rate: Option<NamedApiResource>,
+    #[serde(default)]
+    pub egg_groups: Vec<NamedApiResource>,
+    /// Chance of being female in eighths, -1 when genderless.
+    pub gender_rate: Option<i8>,
+    pub hatch_counter: Option<u32>,
+    pub evolution_chain: Option<ApiResource>,
+    pub shape: Option<NamedApiResource>,
+    pub color: Option<NamedApiResource>,
+    #[serde(default)]
+    pub names: Tolerant<PokeApiName>,
+    #[serde(default)]
+    pub genera: Tolerant<PokeApiGenus>,
+    #[serde(default)]
+    pub varieties: Vec<PokeApiVariety>,
+}
+
+/// A PokeAPI link without a name, such as an evolution chain.
+#[derive(Deserialize)]
+pub struct ApiResource {
+    pub url: String,
+}
+
+#[derive(Deserialize)]
+pub struct PokeApiVariety {
+    pub is_default: bool,
+    pub pokemon: NamedApiResource,
+}
+
+#[derive(Deserialize)]
+pub struct PokeApiName {
+    pub name: String,
+    pub language: Language,
+}
+
+#[derive(Deserialize)]
+pub struct PokeApiGenus {
+    pub genus: String,
+    pub language: Language,
+}
+
+#[derive(Deserialize)]
+pub struct FlavorTextEntry {
+    pub flavor_text: String,
+    pub language: Language,
+    /// The game the text appears in.
+    pub version: Option<NamedApiResource>,
+}
+
+#[derive(Deserialize)]
+pub struct Language {
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct Habitat {
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct TranslationResponse {
+    pub contents: TranslationContents,
+}
+
+#[derive(Deserialize)]
+pub struct TranslationContents {
+    pub translated: String,
+}
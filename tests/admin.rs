@@ -0,0 +1,71 @@
+//! The admin routes served on an admin listener, next to the API.
+
+mod common;
+
+use common::{call, fixture_server, free_port};
+use serde_json::{Value, json};
+
+/// The status and JSON body of `GET path` on the admin listener.
+async fn admin(port: u16, path: &str) -> Value {
+    let response =
+        reqwest::get(format!("http://127.0.0.1:{}{}", port, path))
+            .await
+            .unwrap();
+    let status = response.status().as_u16();
+    let body = response.json::<Value>().await.unwrap_or(Value::Null);
+    json!({ "status": status, "body": body })
+}
+
+#[tokio::test]
+async fn test_compare_translations() {
+    let port = free_port();
+    let server =
+        fixture_server(&[("ADMIN_PORT", &port.to_string())]).await;
+    let pokemon =
+        call(&server, "GET", "/pokemon/pikachu", None).await;
+    let description = &pokemon["body"]["description"];
+    let shakespeare = "At which hour several of these pokémon gather, their electricity couldst buildeth and cause lightning storms.";
+
+    let response =
+        admin(port, "/admin/translate/compare?name=pikachu").await;
+    assert_eq!(response["status"], 200);
+    let body = &response["body"];
+    assert_eq!(body["name"], "pikachu");
+    assert_eq!(&body["description"], description);
+    assert_eq!(body["style"], "shakespeare");
+    assert_eq!(
+        body["funtranslations"],
+        json!({"translated": shakespeare})
+    );
+    let offline = body["offline"].as_str().unwrap();
+    assert!(
+        offline.starts_with("At which hour several"),
+        "{}",
+        offline
+    );
+    assert!(offline.contains("couldst build and"), "{}", offline);
+    assert!(
+        body["diff"]
+            .as_array()
+            .unwrap()
+            .contains(&json!({"op": "delete", "text": "buildeth"})),
+        "{}",
+        body["diff"]
+    );
+
+    let response = admin(
+        port,
+        "/admin/translate/compare?name=pikachu&style=yoda",
+    )
+    .await;
+    assert_eq!(response["status"], 200);
+    assert_eq!(response["body"]["style"], "yoda");
+    assert!(response["body"]["offline"].is_string());
+
+    let response = admin(
+        port,
+        "/admin/translate/compare?name=pikachu&style=pirate",
+    )
+    .await;
+    assert_eq!(response["status"], 400);
+}
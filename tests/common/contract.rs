@@ -0,0 +1,209 @@
+//! Checks responses against the OpenAPI document the server
+//! publishes at `/openapi.json`.
+
+use super::{Server, call};
+use serde_json::{Map, Value, json};
+
+/// The documented responses, with every object closed to the
+/// properties it lists, so that renamed and undocumented fields fail
+/// the checks too.
+pub struct Contract {
+    document: Value,
+}
+
+impl Contract {
+    pub async fn fetch(server: &Server) -> Self {
+        let response =
+            call(server, "GET", "/openapi.json", None).await;
+        assert_eq!(response["status"], 200, "{}", response);
+        let mut document = response["body"].clone();
+        let schemas = document["components"]["schemas"].clone();
+        let closed = schemas
+            .as_object()
+            .expect("the document has no schemas")
+            .iter()
+            .map(|(name, schema)| {
+                (name.clone(), close(schema, &schemas))
+            })
+            .collect();
+        document["components"]["schemas"] = Value::Object(closed);
+        Self { document }
+    }
+
+    /// Panics unless `response`, the status and body `call` returned
+    /// for `method path`, is documented: successes by their status,
+    /// errors by their status or the default response.
+    pub fn check(&self, method: &str, path: &str, response: &Value) {
+        let template = self
+            .template(path)
+            .unwrap_or_else(|| panic!("{} is not documented", path));
+        let method = method.to_lowercase();
+        let operation = &self.document["paths"][&template][&method];
+        assert!(
+            operation.is_object(),
+            "{} {} is not documented",
+            method,
+            template
+        );
+
+        let status = response["status"].to_string();
+        let status = match operation["responses"].get(&status) {
+            Some(_) => status,
+            None if !status.starts_with('2') => "default".to_string(),
+            None => panic!(
+                "{} {} does not document a {}",
+                method, template, status
+            ),
+        };
+        let documented = &operation["responses"][&status];
+        let body = &response["body"];
+        if body.is_null() {
+            assert!(
+                documented.get("content").is_none(),
+                "{} {} answered {} without a body",
+                method,
+                template,
+                status
+            );
+            return;
+        }
+
+        let pointer = format!(
+            "#/paths/{}/{}/responses/{}/content/application~1json/schema",
+            escape(&template),
+            method,
+            status
+        );
+        let mut root = self.document.clone();
+        root["$ref"] = json!(pointer);
+        let validator = jsonschema::draft202012::new(&root)
+            .unwrap_or_else(|e| panic!("invalid schema: {}", e));
+        let errors: Vec<String> = validator
+            .iter_errors(body)
+            .map(|e| format!("{}: {}", e.instance_path, e))
+            .collect();
+        assert!(
+            errors.is_empty(),
+            "{} {} answered {} against the document:\n{}\n{}",
+            method,
+            path,
+            status,
+            errors.join("\n"),
+            body
+        );
+    }
+
+    /// The documented path matching `path`, preferring literal
+    /// segments such as `/pokemon/legendary` over parameters. A
+    /// trailing `{path}` parameter spans the remaining segments, like
+    /// the `*path` wildcards of the router.
+    fn template(&self, path: &str) -> Option<String> {
+        let path = path.split('?').next().unwrap_or_default();
+        let path = ["/v1/", "/v2/"]
+            .iter()
+            .find(|prefix| path.starts_with(*prefix))
+            .map_or(path, |prefix| &path[prefix.len() - 1..]);
+        let segments: Vec<&str> = path.split('/').collect();
+        let paths = self.document["paths"].as_object()?;
+        paths
+            .keys()
+            .filter_map(|template| {
+                let parts: Vec<&str> = template.split('/').collect();
+                let wildcard = parts.last() == Some(&"{path}");
+                let matches = (parts.len() == segments.len()
+                    || (wildcard && parts.len() < segments.len()))
+                    && parts.iter().zip(&segments).all(
+                        |(part, segment)| {
+                            part.starts_with('{') || part == segment
+                        },
+                    );
+                let literals = parts
+                    .iter()
+                    .filter(|part| !part.starts_with('{'))
+                    .count();
+                matches.then_some((literals, template))
+            })
+            .max()
+            .map(|(_, template)| template.clone())
+    }
+}
+
+/// `schema` with its `allOf` compositions, which `#[serde(flatten)]`
+/// produces, merged into one object, and every object closed with
+/// `additionalProperties: false` unless it is a map.
+fn close(schema: &Value, schemas: &Value) -> Value {
+    match schema {
+        Value::Object(object) => {
+            let mut closed: Map<String, Value> = object
+                .iter()
+                .map(|(key, value)| {
+                    (key.clone(), close(value, schemas))
+                })
+                .collect();
+            if let Some(Value::Array(parts)) = closed.remove("allOf")
+            {
+                for part in parts {
+                    merge(&mut closed, &resolve(&part, schemas));
+                }
+            }
+            if closed.contains_key("properties")
+                && !closed.contains_key("additionalProperties")
+            {
+                closed.insert(
+                    "additionalProperties".into(),
+                    json!(false),
+                );
+            }
+            Value::Object(closed)
+        }
+        Value::Array(items) => Value::Array(
+            items.iter().map(|item| close(item, schemas)).collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// The schema `schema` refers to, if it is a reference.
+fn resolve(schema: &Value, schemas: &Value) -> Value {
+    match schema["$ref"].as_str() {
+        Some(reference) => {
+            let name =
+                reference.trim_start_matches("#/components/schemas/");
+            close(&schemas[name], schemas)
+        }
+        None => close(schema, schemas),
+    }
+}
+
+/// Adds the properties and required fields of `part` to `object`.
+fn merge(object: &mut Map<String, Value>, part: &Value) {
+    for key in ["properties", "required"] {
+        let Some(added) = part.get(key) else {
+            continue;
+        };
+        let existing =
+            object.entry(key).or_insert_with(|| match added {
+                Value::Array(_) => json!([]),
+                _ => json!({}),
+            });
+        match (existing, added) {
+            (Value::Object(existing), Value::Object(added)) => {
+                existing.extend(added.clone());
+            }
+            (Value::Array(existing), Value::Array(added)) => {
+                existing.extend(added.iter().cloned());
+            }
+            _ => {}
+        }
+    }
+    object.insert("type".into(), json!("object"));
+}
+
+/// `template` as a JSON pointer token in a URI fragment.
+fn escape(template: &str) -> String {
+    template
+        .replace('~', "~0")
+        .replace('/', "~1")
+        .replace('{', "%7B")
+        .replace('}', "%7D")
+}
@@ -0,0 +1,182 @@
+//! Test harness shared by the integration tests: the `pokedex`
+//! binary started against mock upstreams serving the fixtures of
+//! `testdata/upstreams`.
+
+#![allow(dead_code)]
+
+pub mod contract;
+
+use reqwest::{StatusCode, header::RETRY_AFTER};
+use serde_json::{Value, json};
+use std::{
+    net::TcpListener,
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    time::Duration,
+};
+use wiremock::{MockServer, Request, Respond, ResponseTemplate};
+
+/// Serves `<root>/<path>.json` for every request, or a `404`.
+struct Fixtures {
+    root: PathBuf,
+}
+
+impl Respond for Fixtures {
+    fn respond(&self, request: &Request) -> ResponseTemplate {
+        let path = request.url.path().trim_matches('/');
+        let file = match path.ends_with(".json") {
+            true => self.root.join(path),
+            false => self.root.join(format!("{}.json", path)),
+        };
+        match std::fs::read(file) {
+            Ok(body) => ResponseTemplate::new(200)
+                .set_body_raw(body, "application/json"),
+            Err(_) => ResponseTemplate::new(404),
+        }
+    }
+}
+
+pub async fn mock_upstream(name: &str) -> MockServer {
+    let server = MockServer::start().await;
+    wiremock::Mock::given(wiremock::matchers::any())
+        .respond_with(Fixtures {
+            root: PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("testdata/upstreams")
+                .join(name),
+        })
+        .mount(&server)
+        .await;
+    server
+}
+
+/// A port nothing listens on, for the server's listeners.
+pub fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .expect("no free port")
+        .port()
+}
+
+/// The `pokedex` binary, killed when dropped.
+pub struct Server {
+    child: Child,
+    pub base_url: String,
+    /// Mock upstreams the server calls, stopped with it.
+    upstreams: Vec<MockServer>,
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+pub async fn start_server(
+    pokeapi: &str,
+    funtranslations: &str,
+) -> Server {
+    start_server_with(pokeapi, funtranslations, &[]).await
+}
+
+/// Like `start_server`, with extra environment variables.
+pub async fn start_server_with(
+    pokeapi: &str,
+    funtranslations: &str,
+    env: &[(&str, &str)],
+) -> Server {
+    let port = free_port();
+    let child = Command::new(env!("CARGO_BIN_EXE_pokedex"))
+        .env_clear()
+        .env("HOST", "127.0.0.1")
+        .env("PORT", port.to_string())
+        .env("POKEAPI_BASE_URL", pokeapi)
+        .env("TRANSLATION_API_BASE_URL", funtranslations)
+        .env("NAME_GUARD_REFRESH_SECS", "0")
+        .env("RUST_LOG", "error")
+        .envs(env.iter().copied())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("failed to start the server");
+    let server = Server {
+        child,
+        base_url: format!("http://127.0.0.1:{}", port),
+        upstreams: Vec::new(),
+    };
+
+    let health = format!("{}/health", server.base_url);
+    for _ in 0..100 {
+        if reqwest::get(&health).await.is_ok() {
+            return server;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("the server did not start");
+}
+
+/// A server against mock PokeAPI and funtranslations upstreams
+/// serving the fixtures, with `env`.
+pub async fn fixture_server(env: &[(&str, &str)]) -> Server {
+    let pokeapi = mock_upstream("pokeapi").await;
+    let funtranslations = mock_upstream("funtranslations").await;
+    let mut server = start_server_with(
+        &pokeapi.uri(),
+        &funtranslations.uri(),
+        env,
+    )
+    .await;
+    server.upstreams = vec![pokeapi, funtranslations];
+    server
+}
+
+/// The status and JSON body of `method path`, with `body` if any,
+/// once the server is ready to answer it.
+pub async fn call(
+    server: &Server,
+    method: &str,
+    path: &str,
+    body: Option<Value>,
+) -> Value {
+    call_with(server, method, path, body, &[]).await
+}
+
+/// Like `call`, with extra request headers.
+pub async fn call_with(
+    server: &Server,
+    method: &str,
+    path: &str,
+    body: Option<Value>,
+    headers: &[(&str, &str)],
+) -> Value {
+    let client = reqwest::Client::new();
+    let url = format!("{}{}", server.base_url, path);
+    let mut request = match method {
+        "POST" => client.post(url),
+        "PUT" => client.put(url),
+        "DELETE" => client.delete(url),
+        _ => client.get(url),
+    };
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    if let Some(body) = body {
+        request = request.json(&body);
+    }
+    // Indexes built in the background answer `503` with a
+    // `Retry-After` until they are ready.
+    let mut response =
+        request.try_clone().unwrap().send().await.unwrap();
+    for _ in 0..50 {
+        if !response.headers().contains_key(RETRY_AFTER)
+            || response.status() != StatusCode::SERVICE_UNAVAILABLE
+        {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        response = request.try_clone().unwrap().send().await.unwrap();
+    }
+    let status = response.status().as_u16();
+    let body = response.json::<Value>().await.unwrap_or(Value::Null);
+    json!({ "status": status, "body": body })
+}
@@ -0,0 +1,114 @@
+//! The request guards: oversized bodies and batches are answered
+//! with `413 Payload Too Large` and a JSON error, and clients over
+//! their rate limit with `429 Too Many Requests`.
+
+mod common;
+
+use common::{call, call_with, fixture_server};
+use serde_json::json;
+
+#[tokio::test]
+async fn test_oversized_requests_are_rejected() {
+    let server = fixture_server(&[
+        ("BATCH_MAX_NAMES", "2"),
+        ("MAX_BODY_BYTES", "256"),
+    ])
+    .await;
+
+    let response = call(
+        &server,
+        "POST",
+        "/pokemon/batch",
+        Some(json!({"names": ["pikachu", "mewtwo", "mew"]})),
+    )
+    .await;
+    assert_eq!(
+        response,
+        json!({
+            "status": 413,
+            "body": {
+                "error": "A batch may contain at most 2 names",
+            },
+        })
+    );
+
+    let names = vec!["pikachu"; 64];
+    let response = call(
+        &server,
+        "POST",
+        "/pokemon/batch",
+        Some(json!({ "names": names })),
+    )
+    .await;
+    assert_eq!(
+        response,
+        json!({
+            "status": 413,
+            "body": {
+                "error": "Request bodies may be at most 256 bytes",
+            },
+        })
+    );
+    let text = "a".repeat(512);
+    for path in ["/translate", "/team/analyze"] {
+        let response = call(
+            &server,
+            "POST",
+            path,
+            Some(json!({ "text": text, "names": [text] })),
+        )
+        .await;
+        assert_eq!(
+            response,
+            json!({
+                "status": 413,
+                "body": {
+                    "error": "Request bodies may be at most 256 bytes",
+                },
+            }),
+            "{}",
+            path
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_forwarded_clients_have_their_own_rate_limit() {
+    let server = fixture_server(&[
+        ("RATE_LIMIT", "2"),
+        ("TRUSTED_PROXIES", "127.0.0.1"),
+    ])
+    .await;
+    let from = |client| [("x-forwarded-for", client)];
+
+    for _ in 0..2 {
+        let response = call_with(
+            &server,
+            "GET",
+            "/natures",
+            None,
+            &from("203.0.113.1"),
+        )
+        .await;
+        assert_eq!(response["status"], 200);
+    }
+    let response = call_with(
+        &server,
+        "GET",
+        "/natures",
+        None,
+        &from("203.0.113.1"),
+    )
+    .await;
+    assert_eq!(response["status"], 429);
+
+    let response = call_with(
+        &server,
+        "GET",
+        "/natures",
+        None,
+        &from("203.0.113.2"),
+    )
+    .await;
+    assert_eq!(response["status"], 200);
+}
@@ -0,0 +1,214 @@
+//! Snapshots of the JSON returned by the API for the fixtures of
+//! `testdata/upstreams`, served by the `pokedex` binary against mock
+//! upstreams, so that renamed fields and serialization changes show
+//! up in review. Accept intended changes with `cargo insta review`.
+//! Every response is also checked against the OpenAPI document the
+//! server publishes, so a model change that breaks it fails here.
+
+mod common;
+
+use common::contract::Contract;
+use common::{call, call_with, fixture_server};
+use serde_json::json;
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_response_shapes() {
+    let server = fixture_server(&[]).await;
+    let contract = Contract::fetch(&server).await;
+
+    let cases = [
+        ("pokemon", "GET", "/pokemon/pikachu", None),
+        ("pokemon_legendary", "GET", "/pokemon/mewtwo", None),
+        ("pokemon_not_found", "GET", "/pokemon/missingno", None),
+        (
+            "pokemon_translated",
+            "GET",
+            "/pokemon/translated/pikachu",
+            None,
+        ),
+        (
+            "pokemon_translated_yoda",
+            "GET",
+            "/pokemon/translated/mewtwo",
+            None,
+        ),
+        ("pokemon_details", "GET", "/pokemon/pikachu/details", None),
+        ("pokemon_entries", "GET", "/pokemon/pikachu/entries", None),
+        ("pokemon_forms", "GET", "/pokemon/pikachu/forms", None),
+        (
+            "pokemon_stats_history",
+            "GET",
+            "/pokemon/mewtwo/stats/history",
+            None,
+        ),
+        (
+            "pokemon_encounters",
+            "GET",
+            "/pokemon/pikachu/encounters",
+            None,
+        ),
+        (
+            "pokemon_breeding",
+            "GET",
+            "/pokemon/pikachu/breeding-with/mewtwo",
+            None,
+        ),
+        (
+            "pokemon_batch",
+            "POST",
+            "/pokemon/batch",
+            Some(
+                json!({"names": ["pikachu", "mewtwo", "missingno"]}),
+            ),
+        ),
+        (
+            "pokemon_query",
+            "POST",
+            "/pokemon/query",
+            Some(json!({"name": "mewtwo", "translated": true})),
+        ),
+        ("pokemon_list", "GET", "/pokemon", None),
+        ("pokemon_search", "GET", "/pokemon/search?q=pika", None),
+        ("pokemon_legendary_list", "GET", "/pokemon/legendary", None),
+        ("pokemon_mythical_list", "GET", "/pokemon/mythical", None),
+        (
+            "team_analysis",
+            "POST",
+            "/team/analyze",
+            Some(json!({"names": ["pikachu", "mewtwo"]})),
+        ),
+        ("habitats", "GET", "/habitats", None),
+        ("habitat_pokemon", "GET", "/habitats/rare/pokemon", None),
+        ("natures", "GET", "/natures", None),
+        ("nature", "GET", "/natures/adamant", None),
+        ("item", "GET", "/item/master-ball", None),
+        ("berry", "GET", "/berry/cheri", None),
+        ("move", "GET", "/move/thunderbolt", None),
+        (
+            "move_translated",
+            "GET",
+            "/move/thunderbolt?translated=true",
+            None,
+        ),
+        (
+            "proxy_pokeapi",
+            "GET",
+            "/proxy/pokeapi/generation/generation-i",
+            None,
+        ),
+        (
+            "translate",
+            "POST",
+            "/translate",
+            Some(json!({
+                "text": "It was created by a scientist after years of horrific gene splicing and DNA engineering experiments.",
+                "style": "yoda"
+            })),
+        ),
+    ];
+    for (name, method, path, body) in cases {
+        let response = call(&server, method, path, body).await;
+        contract.check(method, path, &response);
+        insta::assert_json_snapshot!(name, response);
+    }
+}
+
+#[tokio::test]
+async fn test_quiz_shapes() {
+    let server = fixture_server(&[("RANDOM_SEED", "7")]).await;
+    let contract = Contract::fetch(&server).await;
+
+    let challenge = call(&server, "POST", "/quiz/start", None).await;
+    contract.check("POST", "/quiz/start", &challenge);
+    insta::assert_json_snapshot!("quiz_start", challenge, {
+        ".body.id" => "[id]",
+    });
+    let id = challenge["body"]["id"].as_str().unwrap();
+    for (name, guess) in
+        [("quiz_guess_wrong", "mewtwo"), ("quiz_guess", "pikachu")]
+    {
+        let path = format!("/quiz/{}/guess", id);
+        let response = call(
+            &server,
+            "POST",
+            &path,
+            Some(json!({ "name": guess })),
+        )
+        .await;
+        contract.check("POST", &path, &response);
+        insta::assert_json_snapshot!(name, response);
+    }
+}
+
+#[tokio::test]
+async fn test_favorites_shapes() {
+    let server =
+        fixture_server(&[("API_KEYS", "snapshot-key=ash")]).await;
+    let as_ash = [("x-api-key", "snapshot-key")];
+    let contract = Contract::fetch(&server).await;
+
+    for name in ["pikachu", "mew"] {
+        let path = format!("/users/ash/favorites/{}", name);
+        let response =
+            call_with(&server, "PUT", &path, None, &as_ash).await;
+        assert_eq!(response["status"], 204);
+        contract.check("PUT", &path, &response);
+    }
+    let response = call_with(
+        &server,
+        "GET",
+        "/users/ash/favorites",
+        None,
+        &as_ash,
+    )
+    .await;
+    contract.check("GET", "/users/ash/favorites", &response);
+    insta::assert_json_snapshot!("favorites", response);
+    let response =
+        call(&server, "GET", "/users/ash/favorites", None).await;
+    contract.check("GET", "/users/ash/favorites", &response);
+    insta::assert_json_snapshot!("favorites_anonymous", response);
+}
+
+#[tokio::test]
+async fn test_job_shapes() {
+    let server =
+        fixture_server(&[("JOB_TRANSLATIONS_PER_MINUTE", "6000")])
+            .await;
+    let contract = Contract::fetch(&server).await;
+    let submitted = call(
+        &server,
+        "POST",
+        "/jobs/translate-generation",
+        Some(json!({ "generation": 1 })),
+    )
+    .await;
+    contract.check("POST", "/jobs/translate-generation", &submitted);
+    insta::assert_json_snapshot!("job_submitted", submitted, {
+        ".body.id" => "[id]",
+        ".body.created_at" => "[created_at]",
+    });
+
+    let path = format!(
+        "/jobs/{}",
+        submitted["body"]["id"].as_str().unwrap()
+    );
+    let mut job = call(&server, "GET", &path, None).await;
+    for _ in 0..100 {
+        if job["body"]["status"] == "completed" {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        job = call(&server, "GET", &path, None).await;
+    }
+    contract.check("GET", &path, &job);
+    insta::assert_json_snapshot!("job_completed", job, {
+        ".body.id" => "[id]",
+        ".body.created_at" => "[created_at]",
+    });
+    let result = format!("{}/result", path);
+    let results = call(&server, "GET", &result, None).await;
+    contract.check("GET", &result, &results);
+    insta::assert_json_snapshot!("job_results", results);
+}
@@ -0,0 +1,48 @@
+//! Request bodies that do not deserialize are answered with the
+//! `422` JSON error naming the invalid or missing fields.
+
+mod common;
+
+use common::{call, fixture_server};
+use serde_json::json;
+
+#[tokio::test]
+async fn test_mismatched_bodies_list_the_invalid_fields() {
+    let server = fixture_server(&[]).await;
+
+    for (path, body, field) in [
+        ("/translate", json!({"text": 1}), "text"),
+        ("/team/analyze", json!({"names": "x"}), "names"),
+        ("/pokemon/batch", json!({"names": [1]}), "names[0]"),
+        ("/quiz/unknown/guess", json!({"name": false}), "name"),
+    ] {
+        let response = call(&server, "POST", path, Some(body)).await;
+        assert_eq!(response["status"], 422, "{}", path);
+        assert_eq!(
+            response["body"]["code"], "validation_failed",
+            "{}",
+            path
+        );
+        let fields = response["body"]["fields"].as_array().unwrap();
+        assert_eq!(fields.len(), 1, "{}", path);
+        assert_eq!(fields[0]["field"], field, "{}", path);
+        assert_eq!(fields[0]["code"], "invalid", "{}", path);
+    }
+}
+
+#[tokio::test]
+async fn test_missing_fields_are_named() {
+    let server = fixture_server(&[]).await;
+
+    for (path, field) in
+        [("/translate", "text"), ("/team/analyze", "names")]
+    {
+        let response =
+            call(&server, "POST", path, Some(json!({}))).await;
+        assert_eq!(response["status"], 422, "{}", path);
+        let fields = response["body"]["fields"].as_array().unwrap();
+        assert_eq!(fields.len(), 1, "{}", path);
+        assert_eq!(fields[0]["field"], field, "{}", path);
+        assert_eq!(fields[0]["code"], "required", "{}", path);
+    }
+}
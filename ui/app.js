@@ -0,0 +1,130 @@
+// Single-page UI over the public API. Routes are kept in the path
+// (`/ui/pokemon/{name}`) so that pages can be bookmarked.
+"use strict";
+
+const $ = (id) => document.getElementById(id);
+let nextCursor = null;
+
+async function api(path) {
+  const headers = { Accept: "application/json" };
+  const key = $("api-key").value;
+  if (key) {
+    headers["X-Api-Key"] = key;
+  }
+  const response = await fetch(path, { headers });
+  const body = await response.json().catch(() => ({}));
+  if (!response.ok) {
+    throw new Error(body.error || `${response.status} ${response.statusText}`);
+  }
+  return body;
+}
+
+function showError(error) {
+  $("error").textContent = error ? error.message : "";
+  $("error").hidden = !error;
+}
+
+function showPage(page, append) {
+  if (!append) {
+    $("results").replaceChildren();
+  }
+  for (const species of page.results) {
+    const link = document.createElement("a");
+    link.href = `/ui/pokemon/${species.name}`;
+    link.textContent = `#${species.id} ${species.name}`;
+    const item = document.createElement("li");
+    item.append(link);
+    $("results").append(item);
+  }
+  nextCursor = page.next_cursor;
+  $("more").hidden = !nextCursor;
+}
+
+async function search(query, cursor) {
+  const params = new URLSearchParams({ q: query, limit: 20 });
+  if (cursor) {
+    params.set("cursor", cursor);
+  }
+  $("pokemon").hidden = true;
+  showPage(await api(`/pokemon/search?${params}`), Boolean(cursor));
+}
+
+async function show(name) {
+  const pokemon = await api(`/pokemon/${encodeURIComponent(name)}`);
+  $("results").replaceChildren();
+  $("more").hidden = true;
+  $("name").textContent = pokemon.display_name || pokemon.name;
+  $("name").dataset.name = pokemon.name;
+  $("genus").textContent = pokemon.genus || "";
+  $("facts").textContent = [
+    pokemon.habitat && `Habitat: ${pokemon.habitat}`,
+    pokemon.is_legendary && "Legendary",
+    pokemon.is_mythical && "Mythical",
+    pokemon.is_baby && "Baby",
+  ].filter(Boolean).join(" · ");
+  $("description").textContent = pokemon.description || "No description.";
+  const artwork = pokemon.artwork && pokemon.artwork.official;
+  $("artwork").hidden = !artwork;
+  $("artwork").src = artwork || "";
+  $("artwork").alt = pokemon.name;
+  $("translated").hidden = true;
+  $("pokemon").hidden = false;
+}
+
+async function translate() {
+  const params = new URLSearchParams();
+  if ($("target").value) {
+    params.set("target", $("target").value);
+  }
+  const name = encodeURIComponent($("name").dataset.name);
+  const pokemon = await api(`/pokemon/translated/${name}?${params}`);
+  $("translated").textContent = pokemon.description || "";
+  $("translated").hidden = false;
+}
+
+async function route() {
+  showError(null);
+  const path = location.pathname.replace(/^\/ui\/?/, "");
+  const query = new URLSearchParams(location.search).get("q");
+  try {
+    if (path.startsWith("pokemon/")) {
+      await show(decodeURIComponent(path.slice("pokemon/".length)));
+    } else if (query) {
+      $("query").value = query;
+      await search(query);
+    }
+  } catch (error) {
+    showError(error);
+  }
+}
+
+function navigate(url) {
+  history.pushState(null, "", url);
+  route();
+}
+
+$("search").addEventListener("submit", (event) => {
+  event.preventDefault();
+  navigate(`/ui/?q=${encodeURIComponent($("query").value.trim())}`);
+});
+
+$("results").addEventListener("click", (event) => {
+  const link = event.target.closest("a");
+  if (link) {
+    event.preventDefault();
+    navigate(link.href);
+  }
+});
+
+$("more").addEventListener("click", () =>
+  search($("query").value.trim(), nextCursor).catch(showError));
+
+$("translate").addEventListener("click", () =>
+  translate().catch(showError));
+
+$("api-key").value = localStorage.getItem("api-key") || "";
+$("api-key").addEventListener("change", () =>
+  localStorage.setItem("api-key", $("api-key").value));
+
+window.addEventListener("popstate", route);
+route();